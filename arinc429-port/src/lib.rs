@@ -0,0 +1,592 @@
+//! ARINC 429 port model for [NeXosim][NX]-based simulations.
+//!
+//! This model
+//! * listens the specified ARINC 429 channels injecting the received words
+//!   into the simulation, both as raw words and as labeled engineering values,
+//! * outputs words and engineering values from the simulation to the
+//!   specified ARINC 429 channels.
+//!
+//! Engineering values are decoded from and encoded into words according to the
+//! label definitions of the `labels` configuration, which give the name, the
+//! label number, the optional source/destination identifier and the data
+//! format of each label. Received words with a parity error or without a
+//! matching label definition are only forwarded as raw words. Word encoding
+//! and decoding utilities are provided by the [`word`] module.
+//!
+//! ARINC 429 channels are addressed by their index in the `channels`
+//! configuration. Channels are accessed with the UDP backend by default, see
+//! the [`udp`] module. Hardware ARINC 429 cards can be plugged in by
+//! implementing [`Arinc429Backend`], setting the `backend` configuration to
+//! `custom` and providing an [`Arinc429BackendFactory`] to the model
+//! prototype.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_arinc429_port::{Arinc429Encoding, Arinc429PortConfig};
+//!
+//! let config = ConfigLoader::<Arinc429PortConfig>::new()
+//!     .code(
+//!         r#"
+//! channels = ["0.0.0.0:5001/10.0.0.2:5000"]
+//! period = 10
+//!
+//! [[labels]]
+//! name = "pressure-altitude"
+//! label = 0o203
+//! bits = 17
+//!
+//! [[labels]]
+//! name = "vhf-frequency"
+//! label = 0o030
+//! encoding = "bcd"
+//! digits = 4
+//! resolution = 0.01
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.labels[1].encoding, Arinc429Encoding::Bcd);
+//! ```
+//!
+//! [NX]: https://github.com/asynchronics/nexosim
+#![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
+#![forbid(unsafe_code)]
+
+pub mod udp;
+pub mod word;
+
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Arc;
+use std::time::Duration;
+
+use mio::event::Source;
+use mio::{Interest, Registry, Token};
+
+use schematic::{Config, ConfigEnum};
+use serde::{Deserialize, Serialize};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus};
+
+use crate::udp::UdpBackend;
+use crate::word::{Arinc429Format, Arinc429Status, Arinc429Word, MAX_BCD_DIGITS, MAX_BNR_BITS};
+
+/// ARINC 429 channel backend.
+///
+/// A backend is a non-blocking MIO source providing access to a single
+/// ARINC 429 channel.
+pub trait Arinc429Backend: Source + Send {
+    /// Returns the MIO interest of the backend.
+    ///
+    /// Backends buffering outgoing data should register writable interest, so
+    /// that the pending words are written again when the channel is writable.
+    fn interest(&self) -> Interest {
+        Interest::READABLE
+    }
+
+    /// Reads a word.
+    ///
+    /// This method should return an error of kind [`ErrorKind::WouldBlock`]
+    /// when no word is available.
+    fn read_word(&mut self) -> Result<Arinc429Word>;
+
+    /// Writes a word.
+    ///
+    /// An error of kind [`ErrorKind::WouldBlock`] may be returned when the
+    /// transmit buffer of the channel is full, in which case the word is
+    /// written again after the next writable event.
+    fn write_word(&mut self, word: Arinc429Word) -> Result<()>;
+}
+
+/// Factory opening the channels of the `custom` backend.
+///
+/// The factory is called with the name of the channel to open.
+pub type Arinc429BackendFactory = dyn Fn(&str) -> Result<Box<dyn Arinc429Backend>> + Send + Sync;
+
+/// ARINC 429 port model instance config.
+#[derive(Config, Debug)]
+pub struct Arinc429PortConfig {
+    /// List of ARINC 429 channels.
+    ///
+    /// With the UDP backend, channels are the addresses of the remote
+    /// endpoints. With the custom backend, channels are the names passed to
+    /// the backend factory.
+    pub channels: Vec<String>,
+
+    /// Backend used to access the ARINC 429 channels.
+    pub backend: Arinc429BackendKind,
+
+    /// Label definitions.
+    #[setting(nested)]
+    pub labels: Vec<Arinc429LabelConfig>,
+
+    /// Time shift for scheduling events at the present moment.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Activation period for cyclic activities inside the simulation.
+    ///
+    /// If no value is provided, cyclic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+
+    /// Maximum time spent transmitting the pending words when the model is
+    /// dropped.
+    ///
+    /// If no value is provided, the words not yet transmitted are discarded.
+    pub shutdown_timeout: Option<ConfigDuration>,
+}
+
+/// ARINC 429 backend kind.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Arinc429BackendKind {
+    /// Words tunneled over UDP.
+    #[default]
+    Udp,
+
+    /// Channels opened by the backend factory provided to the model
+    /// prototype.
+    Custom,
+}
+
+/// Label definition.
+#[derive(Config, Debug)]
+pub struct Arinc429LabelConfig {
+    /// Name of the engineering value.
+    pub name: String,
+
+    /// Octal label number.
+    pub label: u8,
+
+    /// Source/destination identifier.
+    ///
+    /// If no value is provided, words are decoded whatever their SDI, and the
+    /// SDI of the engineering values is used when encoding words.
+    pub sdi: Option<u8>,
+
+    /// Data format of the label.
+    pub encoding: Arinc429Encoding,
+
+    /// Number of significant bits of BNR data, sign bit excluded.
+    #[setting(default = 18)]
+    pub bits: u8,
+
+    /// Number of digits of BCD data.
+    #[setting(default = 5)]
+    pub digits: u8,
+
+    /// Value of the least significant bit or digit of BNR and BCD data.
+    #[setting(default = 1.0)]
+    pub resolution: f64,
+}
+
+/// Data format of a label.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Arinc429Encoding {
+    /// Two's complement binary data.
+    #[default]
+    Bnr,
+
+    /// Binary-coded decimal data.
+    Bcd,
+
+    /// Discrete data.
+    Discrete,
+}
+
+/// ARINC 429 word exchanged inside the simulation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Arinc429Data {
+    /// Index of the ARINC 429 channel.
+    pub channel: usize,
+
+    /// ARINC 429 word.
+    pub word: Arinc429Word,
+}
+
+/// Labeled engineering value exchanged inside the simulation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arinc429Value {
+    /// Index of the ARINC 429 channel.
+    pub channel: usize,
+
+    /// Name of the engineering value.
+    ///
+    /// The name is ignored for transmitted values.
+    pub name: String,
+
+    /// Octal label number.
+    pub label: u8,
+
+    /// Source/destination identifier.
+    pub sdi: u8,
+
+    /// Engineering value.
+    pub value: f64,
+
+    /// Status of the value.
+    pub status: Arinc429Status,
+}
+
+/// Label definition with its data format.
+#[derive(Clone, Debug)]
+struct Label {
+    /// Name of the engineering value.
+    name: String,
+
+    /// Octal label number.
+    label: u8,
+
+    /// Source/destination identifier, if any.
+    sdi: Option<u8>,
+
+    /// Data format.
+    format: Arinc429Format,
+}
+
+impl Label {
+    /// Creates a label definition from its configuration.
+    fn new(config: &Arinc429LabelConfig) -> std::result::Result<Self, String> {
+        if config.sdi.is_some_and(|sdi| sdi > 3) {
+            return Err(format!("invalid SDI for label {}", config.name));
+        }
+        let format = match config.encoding {
+            Arinc429Encoding::Bnr if config.bits == 0 || config.bits > MAX_BNR_BITS => {
+                return Err(format!("invalid bit count for label {}", config.name));
+            }
+            Arinc429Encoding::Bcd if config.digits == 0 || config.digits > MAX_BCD_DIGITS => {
+                return Err(format!("invalid digit count for label {}", config.name));
+            }
+            Arinc429Encoding::Bnr => Arinc429Format::Bnr {
+                bits: config.bits,
+                resolution: config.resolution,
+            },
+            Arinc429Encoding::Bcd => Arinc429Format::Bcd {
+                digits: config.digits,
+                resolution: config.resolution,
+            },
+            Arinc429Encoding::Discrete => Arinc429Format::Discrete,
+        };
+
+        Ok(Self {
+            name: config.name.clone(),
+            label: config.label,
+            sdi: config.sdi,
+            format,
+        })
+    }
+
+    /// Checks whether the definition applies to a label and an SDI.
+    fn matches(&self, label: u8, sdi: u8) -> bool {
+        self.label == label && self.sdi.is_none_or(|s| s == sdi)
+    }
+}
+
+/// ARINC 429 port.
+struct Arinc429PortInner {
+    /// Channel backends.
+    backends: Vec<Box<dyn Arinc429Backend>>,
+}
+
+impl Arinc429PortInner {
+    /// Opens the configured channels.
+    fn new(
+        config: &Arinc429PortConfig,
+        backend_factory: Option<&Arinc429BackendFactory>,
+    ) -> Result<Self> {
+        let backends = config
+            .channels
+            .iter()
+            .map(|channel| -> Result<Box<dyn Arinc429Backend>> {
+                match config.backend {
+                    Arinc429BackendKind::Udp => {
+                        UdpBackend::open(channel).map(|b| Box::new(b) as Box<dyn Arinc429Backend>)
+                    }
+                    Arinc429BackendKind::Custom => match backend_factory {
+                        Some(factory) => factory(channel),
+                        None => Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "No backend factory provided for the custom backend.",
+                        )),
+                    },
+                }
+                .map_err(|e| Error::new(e.kind(), format!("channel {channel}: {e}")))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { backends })
+    }
+}
+
+impl IoPort<dyn Arinc429Backend, Arinc429Data, Arinc429Data> for Arinc429PortInner {
+    fn register(&mut self, registry: &Registry) -> Result<Token> {
+        for (i, backend) in self.backends.iter_mut().enumerate() {
+            let interest = backend.interest();
+            registry.register(backend.as_mut(), Token(i), interest)?;
+        }
+
+        Ok(Token(self.backends.len()))
+    }
+
+    fn read(&mut self, token: Token) -> Result<Arinc429Data> {
+        let Some(backend) = self.backends.get_mut(token.0) else {
+            // Unknown event: should never happen.
+            return Err(Error::new(ErrorKind::InvalidInput, "Unknown event."));
+        };
+        let word = backend.read_word()?;
+
+        Ok(Arinc429Data {
+            channel: token.0,
+            word,
+        })
+    }
+
+    fn write(&mut self, data: &Arinc429Data) -> Result<()> {
+        // Words sent to unknown channels are discarded.
+        match self.backends.get_mut(data.channel) {
+            Some(backend) => backend.write_word(data.word),
+            None => Ok(()),
+        }
+    }
+}
+
+/// ARINC 429 port model.
+///
+/// This model:
+/// * forwards the words received on the ARINC 429 channels to the word output,
+///   and their engineering values to the value output,
+/// * transmits the words and engineering values from the model inputs on the
+///   ARINC 429 channels,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct Arinc429Port {
+    /// Received ARINC 429 words -- output port.
+    pub word_out: Output<Arinc429Data>,
+
+    /// Received engineering values -- output port.
+    pub value_out: Output<Arinc429Value>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: Arinc429PortConfig,
+
+    /// Label definitions.
+    labels: Vec<Label>,
+
+    /// I/O thread.
+    io_thread: IoThread<Arinc429Data, Arinc429Data>,
+}
+
+impl Arinc429Port {
+    /// Transmits a word -- input port.
+    pub async fn word_in(&mut self, data: Arinc429Data) {
+        let _ = self.io_thread.send(data);
+    }
+
+    /// Transmits an engineering value -- input port.
+    ///
+    /// The value is encoded with the first label definition matching its
+    /// label and SDI, and is discarded if there is none.
+    pub async fn value_in(&mut self, value: Arinc429Value) {
+        let Some(label) = self
+            .labels
+            .iter()
+            .find(|label| label.matches(value.label, value.sdi))
+        else {
+            return;
+        };
+        let word = label
+            .format
+            .encode(value.label, value.sdi, value.value, value.status);
+        let _ = self.io_thread.send(Arinc429Data {
+            channel: value.channel,
+            word,
+        });
+    }
+
+    /// Forwards the received words and the I/O thread status -- input port.
+    pub async fn process(&mut self) {
+        for data in self.io_thread.try_recv_all() {
+            self.word_out.send(data).await;
+            if let Some(value) = self.decode(data) {
+                self.value_out.send(value).await;
+            }
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+
+    /// Decodes the engineering value of a received word, if any.
+    fn decode(&self, data: Arinc429Data) -> Option<Arinc429Value> {
+        let word = data.word;
+        if !word.has_valid_parity() {
+            return None;
+        }
+        let label = self
+            .labels
+            .iter()
+            .find(|label| label.matches(word.label(), word.sdi()))?;
+        let (value, status) = label.format.decode(word)?;
+
+        Some(Arinc429Value {
+            channel: data.channel,
+            name: label.name.clone(),
+            label: word.label(),
+            sdi: word.sdi(),
+            value,
+            status,
+        })
+    }
+}
+
+impl Model for Arinc429Port {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for Arinc429Port {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Arinc429Port")
+            .field("channels", &self.config.channels)
+            .finish_non_exhaustive()
+    }
+}
+
+/// ARINC 429 port model prototype.
+pub struct ProtoArinc429Port {
+    /// Received ARINC 429 words -- output port.
+    pub word_out: Output<Arinc429Data>,
+
+    /// Received engineering values -- output port.
+    pub value_out: Output<Arinc429Value>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// ARINC 429 port model instance configuration.
+    config: Arinc429PortConfig,
+
+    /// Factory of the custom backend.
+    backend_factory: Option<Arc<Arinc429BackendFactory>>,
+}
+
+impl ProtoArinc429Port {
+    /// Creates a new ARINC 429 port model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if a label definition is invalid, if a
+    /// channel cannot be opened or if the I/O thread cannot be created.
+    pub fn new(config: Arinc429PortConfig) -> Self {
+        Self {
+            word_out: Output::new(),
+            value_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+            backend_factory: None,
+        }
+    }
+
+    /// Sets the factory opening the channels when the `backend`
+    /// configuration is `custom`.
+    pub fn with_backend_factory(
+        mut self,
+        factory: impl Fn(&str) -> Result<Box<dyn Arinc429Backend>> + Send + Sync + 'static,
+    ) -> Self {
+        self.backend_factory = Some(Arc::new(factory));
+        self
+    }
+}
+
+impl ProtoModel for ProtoArinc429Port {
+    type Model = Arinc429Port;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let labels = self
+            .config
+            .labels
+            .iter()
+            .map(Label::new)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap_or_else(|e| panic!("Invalid ARINC 429 label definition: {e}."));
+        let port = Arinc429PortInner::new(&self.config, self.backend_factory.as_deref())
+            .unwrap_or_else(|e| panic!("Failed to open the ARINC 429 port: {e}"));
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            shutdown_timeout: self.config.shutdown_timeout.map(Duration::from),
+        };
+        let io_thread = IoThread::try_with_options(port, options).unwrap_or_else(|e| {
+            panic!("Failed to start the I/O thread of the ARINC 429 port: {e}.")
+        });
+
+        Arinc429Port {
+            word_out: self.word_out,
+            value_out: self.value_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            labels,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoArinc429Port {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoArinc429Port")
+            .field("channels", &self.config.channels)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,137 @@
+//! UDP backend.
+//!
+//! This module contains an [`Arinc429Backend`] implementation tunneling
+//! ARINC 429 words over UDP, so that the ARINC 429 port can exchange words
+//! with remote equipment or with ARINC 429-to-Ethernet converters.
+//!
+//! The backend is selected by setting the `backend` configuration of the
+//! ARINC 429 port to `udp`, in which case the configured channels are the
+//! addresses of the remote endpoints, optionally preceded by the local address
+//! to bind to and a slash, e.g. `0.0.0.0:5001/10.0.0.2:5000`. If no local
+//! address is provided, the unspecified address is bound with the port of the
+//! remote address.
+//!
+//! Each datagram carries one or more words as 32-bit big-endian integers, bit
+//! 1 of each word being the least significant bit. Transmitted words are sent
+//! in separate datagrams, and trailing bytes of received datagrams which do
+//! not make a whole word are ignored.
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+
+use mio::event::Source;
+use mio::net::UdpSocket;
+use mio::{Interest, Registry, Token};
+
+use crate::Arinc429Backend;
+use crate::word::Arinc429Word;
+
+/// Maximum size of a received datagram.
+const MAX_DATAGRAM_LEN: usize = 65536;
+
+/// ARINC 429 channel tunneled to a remote endpoint.
+pub struct UdpBackend {
+    /// UDP socket connected to the remote endpoint.
+    socket: UdpSocket,
+
+    /// Receive buffer.
+    rx_buf: Vec<u8>,
+
+    /// Received words not yet read.
+    rx_words: VecDeque<Arinc429Word>,
+}
+
+impl UdpBackend {
+    /// Binds the local address and connects to the remote endpoint.
+    ///
+    /// The address has the form `[LOCAL/]REMOTE`, see the module
+    /// documentation.
+    pub fn open(address: &str) -> Result<Self> {
+        let (local, remote) = match address.split_once('/') {
+            Some((local, remote)) => (Some(resolve(local)?), resolve(remote)?),
+            None => (None, resolve(address)?),
+        };
+        let local = local.unwrap_or_else(|| {
+            let ip = match remote {
+                SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            };
+            SocketAddr::new(ip, remote.port())
+        });
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(remote)?;
+
+        Ok(Self {
+            socket,
+            rx_buf: vec![0; MAX_DATAGRAM_LEN],
+            rx_words: VecDeque::new(),
+        })
+    }
+}
+
+impl Arinc429Backend for UdpBackend {
+    fn interest(&self) -> Interest {
+        Interest::READABLE | Interest::WRITABLE
+    }
+
+    fn read_word(&mut self) -> Result<Arinc429Word> {
+        loop {
+            if let Some(word) = self.rx_words.pop_front() {
+                return Ok(word);
+            }
+            match self.socket.recv(&mut self.rx_buf) {
+                Ok(len) => self
+                    .rx_words
+                    .extend(self.rx_buf[..len].chunks_exact(4).map(|bytes| {
+                        Arinc429Word::from_raw(u32::from_be_bytes(bytes.try_into().unwrap()))
+                    })),
+                // The remote endpoint may not be listening yet.
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Sends a word in a datagram.
+    fn write_word(&mut self, word: Arinc429Word) -> Result<()> {
+        match self.socket.send(&word.raw().to_be_bytes()) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Source for UdpBackend {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        self.socket.register(registry, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        self.socket.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> Result<()> {
+        self.socket.deregister(registry)
+    }
+}
+
+impl fmt::Debug for UdpBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UdpBackend")
+            .field("local_addr", &self.socket.local_addr().ok())
+            .field("peer_addr", &self.socket.peer_addr().ok())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Resolves a socket address.
+fn resolve(address: &str) -> Result<SocketAddr> {
+    address.to_socket_addrs()?.next().ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Cannot resolve address {address}."),
+        )
+    })
+}
@@ -0,0 +1,451 @@
+//! ARINC 429 word codec.
+//!
+//! This module contains the [`Arinc429Word`] type, giving access to the
+//! label, SDI, data, SSM and parity fields of ARINC 429 words, and the
+//! [`Arinc429Format`] type, converting the data field of words to and from
+//! engineering values.
+//!
+//! Words are represented as 32-bit integers whose least significant bit is bit
+//! 1 of the word, i.e. the first bit transmitted on the bus. Since labels are
+//! transmitted most significant bit first, the label field of the integer is
+//! bit-reversed with respect to the octal label number.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_arinc429_port::word::{Arinc429Format, Arinc429Status, Arinc429Word};
+//!
+//! // Pressure altitude, label 203, in feet.
+//! let format = Arinc429Format::Bnr {
+//!     bits: 17,
+//!     resolution: 1.0,
+//! };
+//! let word = format.encode(0o203, 0, 35000.0, Arinc429Status::Normal);
+//!
+//! assert_eq!(word.label(), 0o203);
+//! assert!(word.has_valid_parity());
+//! assert_eq!(
+//!     format.decode(word),
+//!     Some((35000.0, Arinc429Status::Normal))
+//! );
+//! ```
+use std::fmt;
+
+/// Maximum number of significant bits of BNR data.
+pub const MAX_BNR_BITS: u8 = 18;
+
+/// Maximum number of digits of BCD data.
+pub const MAX_BCD_DIGITS: u8 = 5;
+
+/// Position of the SDI field.
+const SDI_SHIFT: u32 = 8;
+
+/// Position of the data field.
+const DATA_SHIFT: u32 = 10;
+
+/// Mask of the data field, once shifted.
+const DATA_MASK: u32 = 0x7_FFFF;
+
+/// Position of the SSM field.
+const SSM_SHIFT: u32 = 29;
+
+/// Parity bit.
+const PARITY_BIT: u32 = 1 << 31;
+
+/// Position of the sign bit of BNR data.
+const SIGN_BIT: u32 = 28;
+
+/// ARINC 429 word.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Arinc429Word(u32);
+
+impl Arinc429Word {
+    /// Creates a word from its label, SDI, data and SSM fields, with odd
+    /// parity.
+    ///
+    /// The label is the octal label number, and the extra bits of the SDI,
+    /// data and SSM fields are ignored.
+    pub fn new(label: u8, sdi: u8, data: u32, ssm: u8) -> Self {
+        let raw = u32::from(label.reverse_bits())
+            | (u32::from(sdi) & 0x3) << SDI_SHIFT
+            | (data & DATA_MASK) << DATA_SHIFT
+            | (u32::from(ssm) & 0x3) << SSM_SHIFT;
+
+        Self(raw).with_parity()
+    }
+
+    /// Creates a word from its 32-bit representation.
+    pub const fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
+    /// Returns the 32-bit representation of the word.
+    pub const fn raw(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the octal label number.
+    pub const fn label(self) -> u8 {
+        (self.0 as u8).reverse_bits()
+    }
+
+    /// Returns the source/destination identifier.
+    pub const fn sdi(self) -> u8 {
+        (self.0 >> SDI_SHIFT) as u8 & 0x3
+    }
+
+    /// Returns the 19-bit data field, including the sign bit of BNR data.
+    pub const fn data(self) -> u32 {
+        (self.0 >> DATA_SHIFT) & DATA_MASK
+    }
+
+    /// Returns the sign/status matrix.
+    pub const fn ssm(self) -> u8 {
+        (self.0 >> SSM_SHIFT) as u8 & 0x3
+    }
+
+    /// Checks whether the word has odd parity.
+    pub const fn has_valid_parity(self) -> bool {
+        self.0.count_ones() % 2 == 1
+    }
+
+    /// Returns the word with the parity bit set for odd parity.
+    pub const fn with_parity(self) -> Self {
+        let raw = self.0 & !PARITY_BIT;
+        if !Self(raw).has_valid_parity() {
+            Self(raw | PARITY_BIT)
+        } else {
+            Self(raw)
+        }
+    }
+}
+
+impl fmt::Debug for Arinc429Word {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Arinc429Word")
+            .field("label", &format_args!("{:03o}", self.label()))
+            .field("sdi", &self.sdi())
+            .field("data", &format_args!("{:#07x}", self.data()))
+            .field("ssm", &self.ssm())
+            .field("parity", &self.has_valid_parity())
+            .finish()
+    }
+}
+
+/// Status of an engineering value, as given by the sign/status matrix.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Arinc429Status {
+    /// Normal operation.
+    #[default]
+    Normal,
+    /// No computed data.
+    NoComputedData,
+    /// Functional test.
+    FunctionalTest,
+    /// Failure warning.
+    FailureWarning,
+}
+
+/// Format of the data field of a label.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arinc429Format {
+    /// Two's complement binary data.
+    ///
+    /// The most significant bit is bit 28 of the word, the sign being bit 29,
+    /// and the unused least significant bits are zero.
+    Bnr {
+        /// Number of significant bits, sign bit excluded, at most
+        /// [`MAX_BNR_BITS`].
+        bits: u8,
+        /// Value of the least significant bit.
+        resolution: f64,
+    },
+    /// Binary-coded decimal data.
+    ///
+    /// The most significant digit is a 3-bit digit in bits 27 to 29 of the
+    /// word, followed by 4-bit digits, and the sign is given by the SSM.
+    Bcd {
+        /// Number of digits, at most [`MAX_BCD_DIGITS`].
+        digits: u8,
+        /// Value of the least significant digit.
+        resolution: f64,
+    },
+    /// Discrete data.
+    ///
+    /// The value is the unsigned 19-bit data field.
+    Discrete,
+}
+
+impl Arinc429Format {
+    /// Decodes the engineering value and status of a word.
+    ///
+    /// `None` is returned if BCD data contains invalid digits.
+    pub fn decode(&self, word: Arinc429Word) -> Option<(f64, Arinc429Status)> {
+        let ssm = word.ssm();
+        match *self {
+            Self::Bnr { bits, resolution } => {
+                let bits = bits.min(MAX_BNR_BITS);
+                // Move the sign bit to the most significant bit so that the
+                // significant bits are sign-extended when shifted back.
+                let raw = ((word.0 << (31 - SIGN_BIT)) as i32) >> (31 - u32::from(bits));
+                let status = match ssm {
+                    0b00 => Arinc429Status::FailureWarning,
+                    0b01 => Arinc429Status::NoComputedData,
+                    0b10 => Arinc429Status::FunctionalTest,
+                    _ => Arinc429Status::Normal,
+                };
+
+                Some((f64::from(raw) * resolution, status))
+            }
+            Self::Bcd { digits, resolution } => {
+                let mut value = 0;
+                for (width, shift) in bcd_digits(digits) {
+                    let digit = (word.0 >> shift) & ((1 << width) - 1);
+                    if digit > 9 {
+                        return None;
+                    }
+                    value = value * 10 + digit;
+                }
+                let value = f64::from(value) * resolution;
+                let (value, status) = match ssm {
+                    0b00 => (value, Arinc429Status::Normal),
+                    0b11 => (-value, Arinc429Status::Normal),
+                    0b01 => (value, Arinc429Status::NoComputedData),
+                    _ => (value, Arinc429Status::FunctionalTest),
+                };
+
+                Some((value, status))
+            }
+            Self::Discrete => {
+                let status = match ssm {
+                    0b00 => Arinc429Status::Normal,
+                    0b01 => Arinc429Status::NoComputedData,
+                    0b10 => Arinc429Status::FunctionalTest,
+                    _ => Arinc429Status::FailureWarning,
+                };
+
+                Some((f64::from(word.data()), status))
+            }
+        }
+    }
+
+    /// Encodes an engineering value and status into a word.
+    ///
+    /// Values are rounded to the resolution and saturated to the range of the
+    /// format. The sign of BCD values is encoded in the SSM, so a BCD value
+    /// with a status other than [`Arinc429Status::Normal`] is encoded as a
+    /// positive value.
+    pub fn encode(&self, label: u8, sdi: u8, value: f64, status: Arinc429Status) -> Arinc429Word {
+        match *self {
+            Self::Bnr { bits, resolution } => {
+                let bits = bits.min(MAX_BNR_BITS);
+                let max = (1i32 << bits) - 1;
+                let raw = (value / resolution)
+                    .round()
+                    .clamp(f64::from(-max - 1), f64::from(max));
+                let shift = SIGN_BIT - u32::from(bits) - DATA_SHIFT;
+                let data = ((raw as i32) << shift) as u32;
+                let ssm = match status {
+                    Arinc429Status::FailureWarning => 0b00,
+                    Arinc429Status::NoComputedData => 0b01,
+                    Arinc429Status::FunctionalTest => 0b10,
+                    Arinc429Status::Normal => 0b11,
+                };
+
+                Arinc429Word::new(label, sdi, data, ssm)
+            }
+            Self::Bcd { digits, resolution } => {
+                let digits = bcd_digits(digits);
+                let max = digits
+                    .clone()
+                    .fold(0, |max, (width, _)| max * 10 + ((1u32 << width) - 1).min(9));
+                let mut magnitude = (value.abs() / resolution).round().min(f64::from(max)) as u32;
+                let mut raw = 0;
+                for (_, shift) in digits.rev() {
+                    raw |= (magnitude % 10) << shift;
+                    magnitude /= 10;
+                }
+                let ssm = match status {
+                    Arinc429Status::Normal if value < 0.0 => 0b11,
+                    Arinc429Status::Normal => 0b00,
+                    Arinc429Status::NoComputedData => 0b01,
+                    Arinc429Status::FunctionalTest | Arinc429Status::FailureWarning => 0b10,
+                };
+
+                Arinc429Word::new(label, sdi, raw >> DATA_SHIFT, ssm)
+            }
+            Self::Discrete => {
+                let ssm = match status {
+                    Arinc429Status::Normal => 0b00,
+                    Arinc429Status::NoComputedData => 0b01,
+                    Arinc429Status::FunctionalTest => 0b10,
+                    Arinc429Status::FailureWarning => 0b11,
+                };
+
+                Arinc429Word::new(label, sdi, value as u32, ssm)
+            }
+        }
+    }
+}
+
+/// Returns the width and position of the BCD digits, most significant digit
+/// first.
+fn bcd_digits(digits: u8) -> impl DoubleEndedIterator<Item = (u32, u32)> + Clone {
+    (0..u32::from(digits.min(MAX_BCD_DIGITS))).map(|i| match i {
+        0 => (3, 26),
+        i => (4, 26 - 4 * i),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_layout() {
+        let word = Arinc429Word::new(0o203, 2, 0x12345, 3);
+        assert_eq!(word.label(), 0o203);
+        assert_eq!(word.sdi(), 2);
+        assert_eq!(word.data(), 0x12345);
+        assert_eq!(word.ssm(), 3);
+
+        // The label is transmitted most significant bit first.
+        assert_eq!(word.raw() & 0xFF, 0xC1);
+        assert_eq!(word.raw() & !PARITY_BIT, 0x648D_16C1);
+
+        // Extra bits are ignored.
+        let word = Arinc429Word::new(0o203, 0xFF, 0xFFFF_FFFF, 0xFF);
+        assert_eq!(word.sdi(), 3);
+        assert_eq!(word.data(), DATA_MASK);
+        assert_eq!(word.ssm(), 3);
+    }
+
+    #[test]
+    fn odd_parity() {
+        // A single label bit set: already odd.
+        let word = Arinc429Word::new(0o001, 0, 0, 0);
+        assert_eq!(word.raw(), 0x0000_0080);
+        assert!(word.has_valid_parity());
+
+        // Eight label bits set: the parity bit is set.
+        let word = Arinc429Word::new(0o377, 0, 0, 0);
+        assert_eq!(word.raw(), 0x8000_00FF);
+        assert!(word.has_valid_parity());
+
+        let corrupted = Arinc429Word::from_raw(word.raw() ^ 0x0000_0400);
+        assert!(!corrupted.has_valid_parity());
+        assert!(corrupted.with_parity().has_valid_parity());
+        assert_eq!(corrupted.with_parity().raw(), 0x0000_04FF);
+    }
+
+    #[test]
+    fn bnr() {
+        let format = Arinc429Format::Bnr {
+            bits: 17,
+            resolution: 1.0,
+        };
+
+        let word = format.encode(0o203, 0, 35000.0, Arinc429Status::Normal);
+        assert_eq!(word.raw(), 0x6445_C0C1);
+        assert_eq!(format.decode(word), Some((35000.0, Arinc429Status::Normal)));
+
+        // Negative values are in two's complement, with the sign in bit 29.
+        let word = format.encode(0o203, 0, -1000.0, Arinc429Status::Normal);
+        assert_ne!(word.raw() & 1 << SIGN_BIT, 0);
+        assert_eq!(format.decode(word), Some((-1000.0, Arinc429Status::Normal)));
+
+        // Values are saturated to the range of the format.
+        let word = format.encode(0o203, 0, 1e9, Arinc429Status::Normal);
+        assert_eq!(
+            format.decode(word),
+            Some((131071.0, Arinc429Status::Normal))
+        );
+        let word = format.encode(0o203, 0, -1e9, Arinc429Status::Normal);
+        assert_eq!(
+            format.decode(word),
+            Some((-131072.0, Arinc429Status::Normal))
+        );
+
+        // Values are rounded to the resolution.
+        let format = Arinc429Format::Bnr {
+            bits: 12,
+            resolution: 0.25,
+        };
+        let word = format.encode(0o310, 1, 10.1, Arinc429Status::FunctionalTest);
+        assert_eq!(word.sdi(), 1);
+        assert_eq!(word.ssm(), 0b10);
+        assert_eq!(
+            format.decode(word),
+            Some((10.0, Arinc429Status::FunctionalTest))
+        );
+
+        for status in [
+            Arinc429Status::FailureWarning,
+            Arinc429Status::NoComputedData,
+        ] {
+            let word = format.encode(0o310, 0, -2.5, status);
+            assert_eq!(format.decode(word), Some((-2.5, status)));
+        }
+    }
+
+    #[test]
+    fn bcd() {
+        let format = Arinc429Format::Bcd {
+            digits: 5,
+            resolution: 0.1,
+        };
+
+        let word = format.encode(0o012, 0, 1234.5, Arinc429Status::Normal);
+        assert_eq!(word.raw(), 0x048D_1450);
+        assert_eq!(word.data(), 0x12345);
+        assert_eq!(format.decode(word), Some((1234.5, Arinc429Status::Normal)));
+
+        // The sign is encoded in the SSM.
+        let word = format.encode(0o012, 0, -1234.5, Arinc429Status::Normal);
+        assert_eq!(word.raw(), 0x648D_1450);
+        assert_eq!(format.decode(word), Some((-1234.5, Arinc429Status::Normal)));
+
+        // The most significant digit has 3 bits only.
+        let word = format.encode(0o012, 0, 1e6, Arinc429Status::Normal);
+        assert_eq!(word.data(), 0x79999);
+
+        let word = format.encode(0o012, 0, 12.0, Arinc429Status::NoComputedData);
+        assert_eq!(
+            format.decode(word),
+            Some((12.0, Arinc429Status::NoComputedData))
+        );
+
+        // Digits above 9 are invalid.
+        let word = Arinc429Word::new(0o012, 0, 0x1234A, 0);
+        assert_eq!(format.decode(word), None);
+
+        // Unused digits are ignored.
+        let format = Arinc429Format::Bcd {
+            digits: 3,
+            resolution: 1.0,
+        };
+        let word = format.encode(0o012, 0, 765.0, Arinc429Status::Normal);
+        assert_eq!(word.data(), 0x76500);
+        let word = Arinc429Word::new(0o012, 0, 0x765FF, 0);
+        assert_eq!(format.decode(word), Some((765.0, Arinc429Status::Normal)));
+    }
+
+    #[test]
+    fn discrete() {
+        let format = Arinc429Format::Discrete;
+        let word = format.encode(0o270, 3, 0x5_5555 as f64, Arinc429Status::Normal);
+        assert_eq!(word.data(), 0x5_5555);
+        assert_eq!(word.sdi(), 3);
+        assert_eq!(
+            format.decode(word),
+            Some((f64::from(0x5_5555), Arinc429Status::Normal))
+        );
+
+        let word = format.encode(0o270, 0, 1.0, Arinc429Status::FailureWarning);
+        assert_eq!(word.ssm(), 0b11);
+        assert_eq!(
+            format.decode(word),
+            Some((1.0, Arinc429Status::FailureWarning))
+        );
+    }
+}
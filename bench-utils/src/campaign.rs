@@ -0,0 +1,337 @@
+//! Monte Carlo campaigns.
+//!
+//! A [`Campaign`] runs repeated randomized trials of a bench. Each trial is
+//! given a seed derived from the campaign master seed, which should be used to
+//! seed all random number generators of the bench, e.g. those of impairment
+//! models. The seed of each trial is recorded with its verdict and statistics
+//! so that any failed trial can be reproduced in isolation with
+//! [`Campaign::trial`].
+//!
+//! #### Examples
+//!
+//! ```no_run
+//! use nexosim_bench_utils::campaign::{Campaign, TrialOutcome};
+//! use nexosim_bench_utils::sweep::RunStats;
+//!
+//! let results = Campaign::new(1000, 42).check_teardown(true).run(|trial| {
+//!     // Build the bench with impairment models seeded with `trial.seed()`,
+//!     // run it and check the delivered data.
+//!     let mut stats = RunStats::new();
+//!     stats.record("retries", (trial.seed() % 4) as f64);
+//!
+//!     Ok::<_, std::io::Error>(TrialOutcome::pass(stats))
+//! });
+//!
+//! println!("pass rate: {}", results.pass_rate());
+//! for record in results.failures() {
+//!     println!("trial {} failed, seed {}", record.index, record.seed);
+//! }
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Result as IoResult, Write};
+use std::path::Path;
+
+use nexosim_io_utils::teardown::TeardownCheck;
+
+use crate::csv;
+use crate::sweep::RunStats;
+
+/// Randomized trial of a campaign.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Trial {
+    /// Index of the trial in the campaign.
+    index: usize,
+
+    /// Seed of the trial.
+    seed: u64,
+}
+
+impl Trial {
+    /// Returns the index of the trial in the campaign.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the seed to be used by all random number generators of the
+    /// bench.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+/// Outcome of a trial.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrialOutcome {
+    /// The bench behaved as expected.
+    pub passed: bool,
+
+    /// Statistics of the trial.
+    pub stats: RunStats,
+}
+
+impl TrialOutcome {
+    /// Creates a passed trial outcome.
+    pub fn pass(stats: RunStats) -> Self {
+        Self {
+            passed: true,
+            stats,
+        }
+    }
+
+    /// Creates a failed trial outcome.
+    pub fn fail(stats: RunStats) -> Self {
+        Self {
+            passed: false,
+            stats,
+        }
+    }
+}
+
+/// Record of a trial.
+#[derive(Clone, Debug)]
+pub struct TrialRecord {
+    /// Index of the trial in the campaign.
+    pub index: usize,
+
+    /// Seed of the trial.
+    pub seed: u64,
+
+    /// The trial passed.
+    pub passed: bool,
+
+    /// Statistics of the trial.
+    pub stats: RunStats,
+
+    /// Error message, if the trial could not be completed.
+    pub error: Option<String>,
+}
+
+/// Monte Carlo campaign.
+#[derive(Clone, Debug)]
+pub struct Campaign {
+    /// Number of trials.
+    trials: usize,
+
+    /// Master seed.
+    master_seed: u64,
+
+    /// Teardown checks are performed after each trial.
+    check_teardown: bool,
+}
+
+impl Campaign {
+    /// Creates a new campaign of `trials` trials with seeds derived from the
+    /// master seed.
+    pub fn new(trials: usize, master_seed: u64) -> Self {
+        Self {
+            trials,
+            master_seed,
+            check_teardown: false,
+        }
+    }
+
+    /// Enables or disables teardown checks after each trial.
+    ///
+    /// A trial which leaks resources is reported as failed.
+    pub fn check_teardown(mut self, enabled: bool) -> Self {
+        self.check_teardown = enabled;
+        self
+    }
+
+    /// Returns the trial with the provided index.
+    ///
+    /// This can be used to reproduce a single trial of the campaign.
+    pub fn trial(&self, index: usize) -> Trial {
+        Trial {
+            index,
+            seed: derive_seed(self.master_seed, index as u64),
+        }
+    }
+
+    /// Runs all trials of the campaign.
+    ///
+    /// The closure should build the bench seeded for the provided trial, run
+    /// it to completion and return its outcome. A trial returning an error is
+    /// reported as failed.
+    pub fn run<F, E>(&self, mut bench: F) -> CampaignResults
+    where
+        F: FnMut(&Trial) -> Result<TrialOutcome, E>,
+        E: fmt::Display,
+    {
+        let trials = (0..self.trials)
+            .map(|index| {
+                let trial = self.trial(index);
+                let check = TeardownCheck::new();
+                let outcome = bench(&trial);
+                let leak = if self.check_teardown {
+                    check.verify().err().map(|report| report.to_string())
+                } else {
+                    None
+                };
+
+                let (passed, stats, error) = match outcome {
+                    Ok(outcome) => (outcome.passed && leak.is_none(), outcome.stats, leak),
+                    Err(e) => (false, RunStats::new(), Some(e.to_string())),
+                };
+
+                TrialRecord {
+                    index,
+                    seed: trial.seed,
+                    passed,
+                    stats,
+                    error,
+                }
+            })
+            .collect();
+
+        CampaignResults {
+            master_seed: self.master_seed,
+            trials,
+        }
+    }
+}
+
+/// Statistic summary over the trials of a campaign.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StatSummary {
+    /// Number of trials which recorded the statistic.
+    pub count: usize,
+
+    /// Mean value.
+    pub mean: f64,
+
+    /// Sample standard deviation.
+    pub std_dev: f64,
+
+    /// Minimum value.
+    pub min: f64,
+
+    /// Maximum value.
+    pub max: f64,
+}
+
+/// Results of a Monte Carlo campaign.
+#[derive(Clone, Debug)]
+pub struct CampaignResults {
+    /// Master seed.
+    master_seed: u64,
+
+    /// Trial records.
+    trials: Vec<TrialRecord>,
+}
+
+impl CampaignResults {
+    /// Returns the master seed of the campaign.
+    pub fn master_seed(&self) -> u64 {
+        self.master_seed
+    }
+
+    /// Returns the trial records, in trial order.
+    pub fn trials(&self) -> &[TrialRecord] {
+        &self.trials
+    }
+
+    /// Returns the number of passed trials.
+    pub fn pass_count(&self) -> usize {
+        self.trials.iter().filter(|record| record.passed).count()
+    }
+
+    /// Returns the ratio of passed trials, or 1 if the campaign is empty.
+    pub fn pass_rate(&self) -> f64 {
+        if self.trials.is_empty() {
+            return 1.0;
+        }
+
+        self.pass_count() as f64 / self.trials.len() as f64
+    }
+
+    /// Returns the records of the failed trials.
+    pub fn failures(&self) -> impl Iterator<Item = &TrialRecord> {
+        self.trials.iter().filter(|record| !record.passed)
+    }
+
+    /// Returns a summary of a statistic over all trials which recorded it.
+    pub fn summary(&self, name: &str) -> Option<StatSummary> {
+        let values: Vec<f64> = self
+            .trials
+            .iter()
+            .filter_map(|record| record.stats.get(name))
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let variance = if count > 1 {
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (count - 1) as f64
+        } else {
+            0.0
+        };
+
+        Some(StatSummary {
+            count,
+            mean,
+            std_dev: variance.sqrt(),
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+
+    /// Writes the trial records in CSV format.
+    ///
+    /// The table has one row per trial with its index, seed, verdict,
+    /// statistics and an error column which is empty for completed trials.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> IoResult<()> {
+        let mut stat_names: Vec<&str> = Vec::new();
+        for record in &self.trials {
+            for (name, _) in record.stats.iter() {
+                if !stat_names.contains(&name) {
+                    stat_names.push(name);
+                }
+            }
+        }
+
+        let header = ["trial", "seed", "passed"]
+            .into_iter()
+            .chain(stat_names.iter().copied())
+            .chain(["error"]);
+        csv::write_row(&mut writer, header)?;
+
+        for record in &self.trials {
+            let mut row = vec![
+                record.index.to_string(),
+                record.seed.to_string(),
+                record.passed.to_string(),
+            ];
+            row.extend(stat_names.iter().map(|name| {
+                record
+                    .stats
+                    .get(name)
+                    .map(|v| v.to_string())
+                    .unwrap_or_default()
+            }));
+            row.push(record.error.clone().unwrap_or_default());
+            csv::write_row(&mut writer, row.iter().map(String::as_str))?;
+        }
+
+        writer.flush()
+    }
+
+    /// Writes the trial records in CSV format to a file.
+    pub fn save_csv(&self, path: impl AsRef<Path>) -> IoResult<()> {
+        self.write_csv(BufWriter::new(File::create(path)?))
+    }
+}
+
+/// Derives the seed of a trial from the master seed using the SplitMix64
+/// generator.
+fn derive_seed(master_seed: u64, index: u64) -> u64 {
+    let mut z = master_seed.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+    z ^ (z >> 31)
+}
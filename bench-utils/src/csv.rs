@@ -0,0 +1,21 @@
+//! CSV output helpers.
+
+use std::io::{Result as IoResult, Write};
+
+/// Writes a CSV row, quoting fields as needed.
+pub(crate) fn write_row<'a, W: Write>(
+    writer: &mut W,
+    fields: impl IntoIterator<Item = &'a str>,
+) -> IoResult<()> {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i != 0 {
+            writer.write_all(b",")?;
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            writer.write_all(field.as_bytes())?;
+        }
+    }
+    writer.write_all(b"\n")
+}
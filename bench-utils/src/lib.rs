@@ -0,0 +1,10 @@
+//! Bench running utilities for [NeXosim][NX]-based simulations.
+//!
+//! [NX]: https://github.com/asynchronics/nexosim
+#![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
+#![forbid(unsafe_code)]
+
+pub mod campaign;
+mod csv;
+pub mod sweep;
+pub mod topology;
@@ -0,0 +1,391 @@
+//! Parameter sweeps.
+//!
+//! A [`SweepRunner`] runs a bench for every point of a grid of parameter
+//! values and gathers the statistics of each run in a single results table.
+//!
+//! The bench is built, run to completion and dropped by a user-provided
+//! closure, so each point starts from a freshly initialized bench. When
+//! teardown checks are enabled, the runner additionally verifies that each
+//! run released all its resources, see
+//! [`TeardownCheck`](nexosim_io_utils::teardown::TeardownCheck).
+//!
+//! #### Examples
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use nexosim_bench_utils::sweep::{RunStats, SweepRunner};
+//!
+//! let results = SweepRunner::new()
+//!     .axis("baud_rate", [9600, 115200])
+//!     .axis("period", [Duration::from_millis(10), Duration::from_millis(100)])
+//!     .check_teardown(true)
+//!     .run(|point| {
+//!         let baud_rate = point.get("baud_rate").unwrap().as_int().unwrap();
+//!         let period = point.get("period").unwrap().as_duration().unwrap();
+//!
+//!         // Build and run the bench, then collect its statistics.
+//!         let mut stats = RunStats::new();
+//!         stats.record("throughput", baud_rate as f64 / period.as_secs_f64());
+//!
+//!         Ok::<_, std::io::Error>(stats)
+//!     });
+//!
+//! results.save_csv("sweep.csv").unwrap();
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Result as IoResult, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use nexosim_io_utils::teardown::TeardownCheck;
+
+use crate::csv;
+
+/// Value of a sweep parameter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamValue {
+    /// Integer value.
+    Int(i64),
+    /// Floating-point value.
+    Float(f64),
+    /// Duration.
+    Duration(Duration),
+    /// Text value.
+    Text(String),
+}
+
+impl ParamValue {
+    /// Returns the integer value, if any.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a floating-point number, if numeric.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Self::Int(value) => Some(*value as f64),
+            Self::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the duration, if any.
+    pub fn as_duration(&self) -> Option<Duration> {
+        match self {
+            Self::Duration(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the text value, if any.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Int(value) => value.fmt(f),
+            Self::Float(value) => value.fmt(f),
+            Self::Duration(value) => write!(f, "{}", value.as_secs_f64()),
+            Self::Text(value) => value.fmt(f),
+        }
+    }
+}
+
+macro_rules! impl_from_int {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for ParamValue {
+                fn from(value: $ty) -> Self {
+                    Self::Int(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_int!(i8, i16, i32, i64, u8, u16, u32);
+
+impl From<f32> for ParamValue {
+    fn from(value: f32) -> Self {
+        Self::Float(value.into())
+    }
+}
+
+impl From<f64> for ParamValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<Duration> for ParamValue {
+    fn from(value: Duration) -> Self {
+        Self::Duration(value)
+    }
+}
+
+impl From<&str> for ParamValue {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_string())
+    }
+}
+
+impl From<String> for ParamValue {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+/// Point of the parameter grid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SweepPoint {
+    /// Index of the point in the sweep.
+    index: usize,
+
+    /// Parameter names and values.
+    params: Vec<(String, ParamValue)>,
+}
+
+impl SweepPoint {
+    /// Returns the index of the point in the sweep.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the value of a parameter.
+    pub fn get(&self, name: &str) -> Option<&ParamValue> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns an iterator over the parameter names and values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ParamValue)> {
+        self.params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+}
+
+/// Statistics collected from a bench run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunStats {
+    /// Statistic names and values, in recording order.
+    values: Vec<(String, f64)>,
+}
+
+impl RunStats {
+    /// Creates an empty set of statistics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a statistic, replacing any previous value with the same name.
+    pub fn record(&mut self, name: impl Into<String>, value: f64) {
+        let name = name.into();
+        match self.values.iter_mut().find(|(stat, _)| *stat == name) {
+            Some((_, stat_value)) => *stat_value = value,
+            None => self.values.push((name, value)),
+        }
+    }
+
+    /// Returns the value of a statistic.
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.values
+            .iter()
+            .find(|(stat, _)| stat == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// Returns an iterator over the statistic names and values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+    }
+}
+
+/// Bench run of a sweep.
+#[derive(Clone, Debug)]
+pub struct SweepRun {
+    /// Parameters of the run.
+    pub point: SweepPoint,
+
+    /// Statistics of the run or error message.
+    pub outcome: Result<RunStats, String>,
+}
+
+/// Parameter sweep runner.
+#[derive(Clone, Debug, Default)]
+pub struct SweepRunner {
+    /// Parameter names and values.
+    axes: Vec<(String, Vec<ParamValue>)>,
+
+    /// Teardown checks are performed after each run.
+    check_teardown: bool,
+}
+
+impl SweepRunner {
+    /// Creates a new runner with an empty grid.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a parameter to the grid.
+    pub fn axis<I>(mut self, name: impl Into<String>, values: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<ParamValue>,
+    {
+        self.axes
+            .push((name.into(), values.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Enables or disables teardown checks after each run.
+    ///
+    /// A run which leaks resources is reported as failed.
+    pub fn check_teardown(mut self, enabled: bool) -> Self {
+        self.check_teardown = enabled;
+        self
+    }
+
+    /// Returns all points of the grid.
+    ///
+    /// The last parameter varies fastest. A grid without parameters has a
+    /// single point.
+    pub fn points(&self) -> Vec<SweepPoint> {
+        let count = self.axes.iter().map(|(_, values)| values.len()).product();
+
+        (0..count)
+            .map(|index| {
+                let mut rest = index;
+                let mut params = Vec::with_capacity(self.axes.len());
+                for (name, values) in self.axes.iter().rev() {
+                    params.push((name.clone(), values[rest % values.len()].clone()));
+                    rest /= values.len();
+                }
+                params.reverse();
+
+                SweepPoint { index, params }
+            })
+            .collect()
+    }
+
+    /// Runs the bench for each point of the grid.
+    ///
+    /// The closure should build the bench for the provided point, run it to
+    /// completion and return its statistics.
+    pub fn run<F, E>(&self, mut bench: F) -> SweepResults
+    where
+        F: FnMut(&SweepPoint) -> Result<RunStats, E>,
+        E: fmt::Display,
+    {
+        let runs = self
+            .points()
+            .into_iter()
+            .map(|point| {
+                let check = TeardownCheck::new();
+                let mut outcome = bench(&point).map_err(|e| e.to_string());
+                if self.check_teardown {
+                    outcome = outcome
+                        .and_then(|stats| check.verify().map(|_| stats).map_err(|r| r.to_string()));
+                }
+
+                SweepRun { point, outcome }
+            })
+            .collect();
+
+        SweepResults {
+            params: self.axes.iter().map(|(name, _)| name.clone()).collect(),
+            runs,
+        }
+    }
+}
+
+/// Results of a parameter sweep.
+#[derive(Clone, Debug)]
+pub struct SweepResults {
+    /// Parameter names.
+    params: Vec<String>,
+
+    /// Bench runs.
+    runs: Vec<SweepRun>,
+}
+
+impl SweepResults {
+    /// Returns the bench runs, in sweep order.
+    pub fn runs(&self) -> &[SweepRun] {
+        &self.runs
+    }
+
+    /// Returns the names of all statistics, in order of first appearance.
+    pub fn stat_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for stats in self.runs.iter().filter_map(|run| run.outcome.as_ref().ok()) {
+            for (name, _) in stats.iter() {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+
+        names
+    }
+
+    /// Writes the results table in CSV format.
+    ///
+    /// The table has one row per run with the parameter values, the
+    /// statistics and an error column which is empty for successful runs.
+    /// Durations are written in seconds.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> IoResult<()> {
+        let stat_names = self.stat_names();
+
+        let header: Vec<&str> = self
+            .params
+            .iter()
+            .map(String::as_str)
+            .chain(stat_names.iter().copied())
+            .chain(["error"])
+            .collect();
+        csv::write_row(&mut writer, header)?;
+
+        for run in &self.runs {
+            let mut row: Vec<String> = run.point.iter().map(|(_, v)| v.to_string()).collect();
+            match &run.outcome {
+                Ok(stats) => {
+                    row.extend(
+                        stat_names
+                            .iter()
+                            .map(|name| stats.get(name).map(|v| v.to_string()).unwrap_or_default()),
+                    );
+                    row.push(String::new());
+                }
+                Err(error) => {
+                    row.extend(stat_names.iter().map(|_| String::new()));
+                    row.push(error.clone());
+                }
+            }
+            csv::write_row(&mut writer, row.iter().map(String::as_str))?;
+        }
+
+        writer.flush()
+    }
+
+    /// Writes the results table in CSV format to a file.
+    pub fn save_csv(&self, path: impl AsRef<Path>) -> IoResult<()> {
+        self.write_csv(BufWriter::new(File::create(path)?))
+    }
+}
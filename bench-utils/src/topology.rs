@@ -0,0 +1,708 @@
+//! Declarative bench assembly.
+//!
+//! A [`BenchLoader`] reads a topology file listing the models of a bench and
+//! the connections between their ports, instantiates the models with their
+//! mailboxes and connects them, so that standard benches can be assembled
+//! without wiring boilerplate.
+//!
+//! Model kinds are registered in the loader with a factory that builds a
+//! [`Node`], i.e. a model together with its named output and input ports.
+//! Models are then referred to by kind in the topology file, which is loaded
+//! with [schematic] from any supported format (TOML, YAML, ...). The
+//! configuration of each model is read from a separate file whose path is
+//! relative to the topology file.
+//!
+//! The models of the workspace are available as standard kinds, see
+//! [`BenchLoader::with_standard_kinds`]. Models that are generic over their
+//! item type, such as codecs, are registered by the user for the item types
+//! of the bench.
+//!
+//! Example of topology file in the TOML format:
+//!
+//! ```toml
+//! [[models]]
+//! name = "can"
+//! kind = "can_port"
+//! config = "can.toml"
+//!
+//! [[models]]
+//! name = "counter"
+//! kind = "counter"
+//!
+//! [[connections]]
+//! from = "can.frame_out"
+//! to = "counter.frame_in"
+//! ```
+//!
+//! #### Examples
+//!
+//! ```no_run
+//! # use std::error::Error;
+//! use bytes::Bytes;
+//! use nexosim::model::Model;
+//! use nexosim::simulation::SimInit;
+//! use nexosim_bench_utils::topology::{BenchLoader, Node};
+//!
+//! /// Counter of the received bytes.
+//! #[derive(Default)]
+//! struct Counter {
+//!     count: usize,
+//! }
+//!
+//! impl Counter {
+//!     fn bytes_in(&mut self, data: Bytes) {
+//!         self.count += data.len();
+//!     }
+//! }
+//!
+//! impl Model for Counter {}
+//!
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! let bench = BenchLoader::with_standard_kinds()
+//!     .register("counter", |_| {
+//!         let counter = Counter::default();
+//!
+//!         Ok(Node::new(counter).input("bytes_in", Counter::bytes_in))
+//!     })
+//!     .load("bench.toml")?;
+//!
+//! let sim_init = bench.into_sim_init(SimInit::new());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [schematic]: https://docs.rs/schematic
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use bytes::Bytes;
+use schematic::{Config, ConfigError, ConfigLoader};
+
+use nexosim::model::ProtoModel;
+use nexosim::ports::{InputFn, Output};
+use nexosim::simulation::{Address, Mailbox, SimInit};
+
+use nexosim_io_utils::ber::{BerChannel, BerChannelConfig};
+use nexosim_io_utils::fault::{FaultInjector, FaultInjectorConfig, Faulty};
+use nexosim_io_utils::latency::{LatencyChannel, LatencyChannelConfig};
+
+/// Bench topology configuration.
+#[derive(Config, Debug)]
+pub struct TopologyConfig {
+    /// Models of the bench.
+    #[setting(nested)]
+    pub models: Vec<ModelConfig>,
+
+    /// Connections between model ports.
+    #[setting(nested)]
+    pub connections: Vec<ConnectionConfig>,
+}
+
+/// Model instance configuration.
+#[derive(Config, Debug)]
+pub struct ModelConfig {
+    /// Model instance name.
+    pub name: String,
+
+    /// Model kind, as registered in the loader.
+    pub kind: String,
+
+    /// Path of the model configuration file, relative to the topology file.
+    ///
+    /// If no value is provided, the default configuration is used.
+    pub config: Option<String>,
+}
+
+/// Connection configuration.
+#[derive(Config, Debug)]
+pub struct ConnectionConfig {
+    /// Output port, as `model.port`.
+    pub from: String,
+
+    /// Input port, as `model.port`.
+    pub to: String,
+}
+
+/// Topology error.
+#[derive(Debug)]
+pub enum TopologyError {
+    /// Topology or model configuration file could not be loaded.
+    Config(ConfigError),
+
+    /// Model kind is not registered.
+    UnknownKind {
+        /// Model instance name.
+        model: String,
+        /// Model kind.
+        kind: String,
+    },
+
+    /// Several models have the same name.
+    DuplicateModel(String),
+
+    /// Model does not exist.
+    UnknownModel(String),
+
+    /// Port does not exist.
+    UnknownPort(String),
+
+    /// Port is not formatted as `model.port`.
+    InvalidPort(String),
+
+    /// Output and input ports have different types.
+    TypeMismatch {
+        /// Output port.
+        from: String,
+        /// Input port.
+        to: String,
+    },
+
+    /// Model factory error.
+    Model {
+        /// Model instance name.
+        model: String,
+        /// Error.
+        error: Box<dyn Error + Send + Sync>,
+    },
+}
+
+impl From<ConfigError> for TopologyError {
+    fn from(error: ConfigError) -> Self {
+        Self::Config(error)
+    }
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Config(error) => write!(f, "configuration error: {error}"),
+            Self::UnknownKind { model, kind } => {
+                write!(f, "unknown kind '{kind}' of model '{model}'")
+            }
+            Self::DuplicateModel(model) => write!(f, "duplicate model '{model}'"),
+            Self::UnknownModel(model) => write!(f, "unknown model '{model}'"),
+            Self::UnknownPort(port) => write!(f, "unknown port '{port}'"),
+            Self::InvalidPort(port) => write!(f, "invalid port '{port}', expected 'model.port'"),
+            Self::TypeMismatch { from, to } => {
+                write!(f, "type mismatch between ports '{from}' and '{to}'")
+            }
+            Self::Model { model, error } => write!(f, "model '{model}': {error}"),
+        }
+    }
+}
+
+impl Error for TopologyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Config(error) => Some(error),
+            Self::Model { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Model specification passed to model factories.
+#[derive(Debug)]
+pub struct ModelSpec {
+    /// Model instance name.
+    name: String,
+
+    /// Resolved path of the model configuration file.
+    config_path: Option<PathBuf>,
+}
+
+impl ModelSpec {
+    /// Returns the model instance name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the resolved path of the model configuration file, if any.
+    pub fn config_path(&self) -> Option<&Path> {
+        self.config_path.as_deref()
+    }
+
+    /// Loads the model configuration.
+    ///
+    /// The default configuration is returned if no configuration file is
+    /// specified.
+    pub fn load_config<C: Config>(&self) -> Result<C, TopologyError> {
+        let mut loader = ConfigLoader::<C>::new();
+        if let Some(path) = &self.config_path {
+            loader.file(path.as_path())?;
+        }
+
+        Ok(loader.load()?.config)
+    }
+}
+
+/// Accessor to an output port of a model prototype.
+type OutputAccessor<P> = Box<dyn for<'a> Fn(&'a mut P) -> &'a mut dyn Any>;
+
+/// Connects a type-erased output port to an input port, returning `false` on
+/// type mismatch.
+type Connector = Rc<dyn Fn(&mut dyn Any) -> bool>;
+
+/// Model prototype with named ports.
+pub struct Node<P: ProtoModel> {
+    /// Model prototype.
+    model: P,
+
+    /// Model mailbox.
+    mailbox: Mailbox<P::Model>,
+
+    /// Output ports.
+    outputs: HashMap<String, OutputAccessor<P>>,
+
+    /// Input ports.
+    inputs: HashMap<String, Connector>,
+}
+
+impl<P: ProtoModel + 'static> Node<P> {
+    /// Creates a new node without ports.
+    pub fn new(model: P) -> Self {
+        Self {
+            model,
+            mailbox: Mailbox::new(),
+            outputs: HashMap::new(),
+            inputs: HashMap::new(),
+        }
+    }
+
+    /// Declares an output port.
+    pub fn output<T, F>(mut self, name: impl Into<String>, accessor: F) -> Self
+    where
+        T: Clone + Send + 'static,
+        F: for<'a> Fn(&'a mut P) -> &'a mut Output<T> + 'static,
+    {
+        self.outputs.insert(
+            name.into(),
+            Box::new(move |model| accessor(model) as &mut dyn Any),
+        );
+        self
+    }
+
+    /// Declares an input port.
+    pub fn input<T, F, S>(mut self, name: impl Into<String>, input: F) -> Self
+    where
+        T: Clone + Send + 'static,
+        F: for<'a> InputFn<'a, P::Model, T, S> + Clone,
+        S: Send + 'static,
+    {
+        let address = self.mailbox.address();
+        self.inputs.insert(
+            name.into(),
+            Rc::new(
+                move |output: &mut dyn Any| match output.downcast_mut::<Output<T>>() {
+                    Some(output) => {
+                        output.connect(input.clone(), &address);
+                        true
+                    }
+                    None => false,
+                },
+            ),
+        );
+        self
+    }
+}
+
+impl<P: ProtoModel> fmt::Debug for Node<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Node").finish_non_exhaustive()
+    }
+}
+
+/// Type-erased node.
+trait AnyNode {
+    /// Returns an output port.
+    fn output(&mut self, port: &str) -> Option<&mut dyn Any>;
+
+    /// Returns the connector of an input port.
+    fn input(&self, port: &str) -> Option<Connector>;
+
+    /// Returns the model address.
+    fn address(&self) -> Box<dyn Any>;
+
+    /// Adds the model to the simulation.
+    fn add_to(self: Box<Self>, sim_init: SimInit, name: &str) -> SimInit;
+}
+
+impl<P: ProtoModel + 'static> AnyNode for Node<P> {
+    fn output(&mut self, port: &str) -> Option<&mut dyn Any> {
+        let accessor = self.outputs.get(port)?;
+
+        Some(accessor(&mut self.model))
+    }
+
+    fn input(&self, port: &str) -> Option<Connector> {
+        self.inputs.get(port).cloned()
+    }
+
+    fn address(&self) -> Box<dyn Any> {
+        Box::new(self.mailbox.address())
+    }
+
+    fn add_to(self: Box<Self>, sim_init: SimInit, name: &str) -> SimInit {
+        let node = *self;
+
+        sim_init.add_model(node.model, node.mailbox, name)
+    }
+}
+
+/// Model factory.
+type Factory = Box<dyn Fn(&ModelSpec) -> Result<Box<dyn AnyNode>, Box<dyn Error + Send + Sync>>>;
+
+/// Bench loader.
+#[derive(Default)]
+pub struct BenchLoader {
+    /// Model factories by kind.
+    kinds: HashMap<String, Factory>,
+}
+
+impl BenchLoader {
+    /// Creates a new loader without registered model kinds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new loader with the standard model kinds.
+    ///
+    /// The following kinds are registered, with ports named after the fields
+    /// and input methods of the models:
+    ///
+    /// * `latency_channel`, `ber_channel` and `fault_injector`: channel models
+    ///   of the I/O utilities carrying [`Bytes`],
+    /// * `serial_port` (feature `serial-port`): serial port model,
+    /// * `can_port`, `can_shaper` and `can_gateway` (feature `can-port`): CAN
+    ///   port model, bus bandwidth shaper and gateway, as well as
+    ///   `can_latency_channel`, `can_ber_channel` and `can_fault_injector`:
+    ///   channel models carrying CAN data.
+    ///
+    /// Standard kinds can be overridden by registering another factory for
+    /// the same kind.
+    pub fn with_standard_kinds() -> Self {
+        let loader = Self::new()
+            .register("latency_channel", latency_channel::<Bytes>)
+            .register("ber_channel", ber_channel::<Bytes>)
+            .register("fault_injector", fault_injector::<Bytes>);
+
+        #[cfg(feature = "serial-port")]
+        let loader = loader.register("serial_port", standard::serial_port);
+
+        #[cfg(feature = "can-port")]
+        let loader = loader
+            .register("can_port", standard::can_port)
+            .register("can_shaper", standard::can_shaper)
+            .register("can_gateway", standard::can_gateway)
+            .register(
+                "can_latency_channel",
+                latency_channel::<nexosim_can_port::CanData>,
+            )
+            .register("can_ber_channel", ber_channel::<nexosim_can_port::CanData>)
+            .register(
+                "can_fault_injector",
+                fault_injector::<nexosim_can_port::CanData>,
+            );
+
+        loader
+    }
+
+    /// Registers a model kind.
+    pub fn register<P, F>(mut self, kind: impl Into<String>, factory: F) -> Self
+    where
+        P: ProtoModel + 'static,
+        F: Fn(&ModelSpec) -> Result<Node<P>, Box<dyn Error + Send + Sync>> + 'static,
+    {
+        self.kinds.insert(
+            kind.into(),
+            Box::new(move |spec| Ok(Box::new(factory(spec)?) as Box<dyn AnyNode>)),
+        );
+        self
+    }
+
+    /// Loads a topology file and assembles the bench.
+    pub fn load(&self, path: impl AsRef<Path>) -> Result<Bench, TopologyError> {
+        let path = path.as_ref();
+        let mut loader = ConfigLoader::<TopologyConfig>::new();
+        loader.file(path)?;
+        let topology = loader.load()?.config;
+
+        self.assemble(&topology, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Assembles the bench described by a topology configuration.
+    ///
+    /// Paths of model configuration files are relative to `root`.
+    pub fn assemble(
+        &self,
+        topology: &TopologyConfig,
+        root: impl AsRef<Path>,
+    ) -> Result<Bench, TopologyError> {
+        let mut nodes: Vec<(String, Box<dyn AnyNode>)> = Vec::new();
+        for model in &topology.models {
+            if nodes.iter().any(|(name, _)| *name == model.name) {
+                return Err(TopologyError::DuplicateModel(model.name.clone()));
+            }
+            let factory =
+                self.kinds
+                    .get(&model.kind)
+                    .ok_or_else(|| TopologyError::UnknownKind {
+                        model: model.name.clone(),
+                        kind: model.kind.clone(),
+                    })?;
+            let spec = ModelSpec {
+                name: model.name.clone(),
+                config_path: model
+                    .config
+                    .as_ref()
+                    .map(|config| root.as_ref().join(config)),
+            };
+            let node = factory(&spec).map_err(|error| TopologyError::Model {
+                model: model.name.clone(),
+                error,
+            })?;
+            nodes.push((model.name.clone(), node));
+        }
+
+        let mut bench = Bench { nodes };
+        for connection in &topology.connections {
+            let (to_model, to_port) = split_port(&connection.to)?;
+            let connector = bench
+                .node(to_model)?
+                .input(to_port)
+                .ok_or_else(|| TopologyError::UnknownPort(connection.to.clone()))?;
+
+            let (from_model, from_port) = split_port(&connection.from)?;
+            let output = bench
+                .node_mut(from_model)?
+                .output(from_port)
+                .ok_or_else(|| TopologyError::UnknownPort(connection.from.clone()))?;
+
+            if !connector(output) {
+                return Err(TopologyError::TypeMismatch {
+                    from: connection.from.clone(),
+                    to: connection.to.clone(),
+                });
+            }
+        }
+
+        Ok(bench)
+    }
+}
+
+impl fmt::Debug for BenchLoader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BenchLoader").finish_non_exhaustive()
+    }
+}
+
+/// Assembled bench.
+pub struct Bench {
+    /// Model names and nodes.
+    nodes: Vec<(String, Box<dyn AnyNode>)>,
+}
+
+impl Bench {
+    /// Returns an output port of a model, e.g. to connect it to a sink.
+    pub fn output<T: Clone + Send + 'static>(
+        &mut self,
+        model: &str,
+        port: &str,
+    ) -> Result<&mut Output<T>, TopologyError> {
+        let full_port = || format!("{model}.{port}");
+        self.node_mut(model)?
+            .output(port)
+            .ok_or_else(|| TopologyError::UnknownPort(full_port()))?
+            .downcast_mut()
+            .ok_or_else(|| TopologyError::TypeMismatch {
+                from: full_port(),
+                to: full_port(),
+            })
+    }
+
+    /// Returns the address of a model.
+    ///
+    /// Returns `None` if the model does not exist or has another type.
+    pub fn address<P: ProtoModel + 'static>(&self, model: &str) -> Option<Address<P::Model>> {
+        self.node(model)
+            .ok()?
+            .address()
+            .downcast()
+            .ok()
+            .map(|address| *address)
+    }
+
+    /// Adds all models of the bench to the simulation.
+    pub fn into_sim_init(self, mut sim_init: SimInit) -> SimInit {
+        for (name, node) in self.nodes {
+            sim_init = node.add_to(sim_init, &name);
+        }
+
+        sim_init
+    }
+
+    /// Returns a node by name.
+    fn node(&self, model: &str) -> Result<&dyn AnyNode, TopologyError> {
+        self.nodes
+            .iter()
+            .find(|(name, _)| name == model)
+            .map(|(_, node)| node.as_ref())
+            .ok_or_else(|| TopologyError::UnknownModel(model.to_string()))
+    }
+
+    /// Returns a mutable node by name.
+    fn node_mut(&mut self, model: &str) -> Result<&mut Box<dyn AnyNode>, TopologyError> {
+        self.nodes
+            .iter_mut()
+            .find(|(name, _)| name == model)
+            .map(|(_, node)| node)
+            .ok_or_else(|| TopologyError::UnknownModel(model.to_string()))
+    }
+}
+
+impl fmt::Debug for Bench {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Bench").finish_non_exhaustive()
+    }
+}
+
+/// Result of a model factory.
+type FactoryResult<P> = Result<Node<P>, Box<dyn Error + Send + Sync>>;
+
+/// Builds a latency channel.
+fn latency_channel<T: Clone + Send + 'static>(
+    spec: &ModelSpec,
+) -> FactoryResult<LatencyChannel<T>> {
+    let config = spec.load_config::<LatencyChannelConfig>()?;
+
+    Ok(Node::new(LatencyChannel::new(config))
+        .output("data_out", |channel| &mut channel.data_out)
+        .input("data_in", LatencyChannel::data_in))
+}
+
+/// Builds a bit error rate channel.
+fn ber_channel<T>(spec: &ModelSpec) -> FactoryResult<BerChannel<T>>
+where
+    T: Faulty + Send + 'static,
+{
+    let config = spec.load_config::<BerChannelConfig>()?;
+
+    Ok(Node::new(BerChannel::new(config))
+        .output("data_out", |channel| &mut channel.data_out)
+        .output("bit_errors_out", |channel| &mut channel.bit_errors_out)
+        .input("data_in", BerChannel::data_in))
+}
+
+/// Builds a fault injector.
+fn fault_injector<T>(spec: &ModelSpec) -> FactoryResult<FaultInjector<T>>
+where
+    T: Faulty + Send + 'static,
+{
+    let config = spec.load_config::<FaultInjectorConfig>()?;
+
+    Ok(Node::new(FaultInjector::new(config))
+        .output("data_out", |injector| &mut injector.data_out)
+        .output("fault_out", |injector| &mut injector.fault_out)
+        .input("data_in", FaultInjector::data_in)
+        .input("flush", FaultInjector::flush))
+}
+
+/// Factories of the standard port models.
+#[cfg(any(feature = "can-port", feature = "serial-port"))]
+mod standard {
+    use super::{FactoryResult, ModelSpec, Node};
+
+    /// Builds a serial port.
+    #[cfg(feature = "serial-port")]
+    pub(super) fn serial_port(
+        spec: &ModelSpec,
+    ) -> FactoryResult<nexosim_serial_port::ProtoSerialPort> {
+        use nexosim_serial_port::{ProtoSerialPort, SerialPort, SerialPortConfig};
+
+        let config = spec.load_config::<SerialPortConfig>()?;
+
+        Ok(Node::new(ProtoSerialPort::new(config))
+            .output("bytes_out", |port| &mut port.bytes_out)
+            .output("timestamped_bytes_out", |port| {
+                &mut port.timestamped_bytes_out
+            })
+            .output("lines_out", |port| &mut port.lines_out)
+            .output("break_out", |port| &mut port.break_out)
+            .output("status_out", |port| &mut port.status_out)
+            .output("stalled_out", |port| &mut port.stalled_out)
+            .output("io_status_out", |port| &mut port.io_status_out)
+            .output("link_status_out", |port| &mut port.link_status_out)
+            .output("metrics_out", |port| &mut port.metrics_out)
+            .output("drained_out", |port| &mut port.drained_out)
+            .output("collision_out", |port| &mut port.collision_out)
+            .input("bytes_in", SerialPort::bytes_in)
+            .input("send_break", SerialPort::send_break)
+            .input("drain", SerialPort::drain)
+            .input("report_metrics", SerialPort::report_metrics))
+    }
+
+    /// Builds a CAN port.
+    #[cfg(feature = "can-port")]
+    pub(super) fn can_port(spec: &ModelSpec) -> FactoryResult<nexosim_can_port::ProtoCanPort> {
+        use nexosim_can_port::{CanPort, CanPortConfig, ProtoCanPort};
+
+        let config = spec.load_config::<CanPortConfig>()?;
+
+        Ok(Node::new(ProtoCanPort::new(config))
+            .output("frame_out", |port| &mut port.frame_out)
+            .output("error_out", |port| &mut port.error_out)
+            .output("stalled_out", |port| &mut port.stalled_out)
+            .output("io_status_out", |port| &mut port.io_status_out)
+            .output("bus_state_out", |port| &mut port.bus_state_out)
+            .output("tx_confirm_out", |port| &mut port.tx_confirm_out)
+            .output("tx_failure_out", |port| &mut port.tx_failure_out)
+            .output("status_out", |port| &mut port.status_out)
+            .output("link_status_out", |port| &mut port.link_status_out)
+            .output("metrics_out", |port| &mut port.metrics_out)
+            .input("frame_in", CanPort::frame_in)
+            .input("attach_in", CanPort::attach_in)
+            .input("detach_in", CanPort::detach_in)
+            .input("query_bus_state", CanPort::query_bus_state)
+            .input("report_metrics", CanPort::report_metrics))
+    }
+
+    /// Builds a CAN bus bandwidth shaper.
+    #[cfg(feature = "can-port")]
+    pub(super) fn can_shaper(
+        spec: &ModelSpec,
+    ) -> FactoryResult<nexosim_can_port::shaper::CanShaper> {
+        use nexosim_can_port::shaper::{CanShaper, CanShaperConfig};
+
+        let config = spec.load_config::<CanShaperConfig>()?;
+
+        Ok(Node::new(CanShaper::new(config))
+            .output("frame_out", |shaper| &mut shaper.frame_out)
+            .output("dropped_out", |shaper| &mut shaper.dropped_out)
+            .input("frame_in", CanShaper::frame_in))
+    }
+
+    /// Builds a CAN gateway.
+    #[cfg(feature = "can-port")]
+    pub(super) fn can_gateway(
+        spec: &ModelSpec,
+    ) -> FactoryResult<nexosim_can_port::gateway::CanGateway> {
+        use nexosim_can_port::gateway::{CanGateway, CanGatewayConfig};
+
+        let config = spec.load_config::<CanGatewayConfig>()?;
+
+        Ok(Node::new(CanGateway::new(config))
+            .output("frame_out", |gateway| &mut gateway.frame_out)
+            .input("frame_in", CanGateway::frame_in))
+    }
+}
+
+/// Splits a port into model and port names.
+fn split_port(port: &str) -> Result<(&str, &str), TopologyError> {
+    port.split_once('.')
+        .ok_or_else(|| TopologyError::InvalidPort(port.to_string()))
+}
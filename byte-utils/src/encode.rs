@@ -0,0 +1,176 @@
+//! Byte stream encoding utilities.
+//!
+//! Serde-based encoders are available behind the `postcard`, `cbor` and `json`
+//! features.
+use std::fmt;
+use std::marker::PhantomData;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use nexosim::model::Model;
+use nexosim::ports::Output;
+
+/// Buffer encoder trait.
+pub trait BufEncoder<T> {
+    /// Error type.
+    type Error;
+
+    /// Encodes data, appending it to the output buffer.
+    fn encode<B: BufMut>(&mut self, data: &T, buf: &mut B) -> Result<(), Self::Error>;
+}
+
+/// Byte stream encoder model.
+///
+/// Data which cannot be encoded is dropped.
+pub struct ByteStreamEncoder<T: Clone + Send + 'static, E: BufEncoder<T> + Send + 'static> {
+    /// Encoded bytes.
+    pub bytes_out: Output<Bytes>,
+
+    /// Internal buffer.
+    buf: BytesMut,
+
+    /// Data encoder.
+    encoder: E,
+
+    /// Encoded data type.
+    _phantom: PhantomData<fn(T)>,
+}
+
+impl<T, E> ByteStreamEncoder<T, E>
+where
+    T: Clone + Send + 'static,
+    E: BufEncoder<T> + Send + 'static,
+{
+    /// Creates new byte stream encoder model.
+    pub fn new(encoder: E) -> Self {
+        Self {
+            bytes_out: Output::new(),
+            buf: BytesMut::new(),
+            encoder,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Input data -- input port.
+    pub async fn data_in(&mut self, data: T) {
+        self.buf.clear();
+        if self.encoder.encode(&data, &mut self.buf).is_ok() {
+            self.bytes_out.send(self.buf.split().freeze()).await;
+        }
+    }
+}
+
+impl<T, E> Model for ByteStreamEncoder<T, E>
+where
+    T: Clone + Send + 'static,
+    E: BufEncoder<T> + Send + 'static,
+{
+}
+
+impl<T, E> fmt::Debug for ByteStreamEncoder<T, E>
+where
+    T: Clone + Send + 'static,
+    E: BufEncoder<T> + Send + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ByteStreamEncoder").finish_non_exhaustive()
+    }
+}
+
+/// Postcard encoder.
+#[cfg(feature = "postcard")]
+#[derive(Clone, Debug, Default)]
+pub struct PostcardEncoder {
+    /// Messages are COBS-encoded.
+    cobs: bool,
+}
+
+#[cfg(feature = "postcard")]
+impl PostcardEncoder {
+    /// Creates a new postcard encoder.
+    pub fn new() -> Self {
+        Self { cobs: false }
+    }
+
+    /// Creates a new postcard encoder producing COBS-encoded messages
+    /// terminated by a zero byte.
+    ///
+    /// Such messages can be delimited in a byte stream.
+    pub fn cobs() -> Self {
+        Self { cobs: true }
+    }
+}
+
+#[cfg(feature = "postcard")]
+impl<T: serde::Serialize> BufEncoder<T> for PostcardEncoder {
+    type Error = postcard::Error;
+
+    fn encode<B: BufMut>(&mut self, data: &T, buf: &mut B) -> Result<(), Self::Error> {
+        let encoded = if self.cobs {
+            postcard::to_allocvec_cobs(data)?
+        } else {
+            postcard::to_allocvec(data)?
+        };
+        buf.put_slice(&encoded);
+
+        Ok(())
+    }
+}
+
+/// CBOR encoder.
+#[cfg(feature = "cbor")]
+#[derive(Clone, Debug, Default)]
+pub struct CborEncoder;
+
+#[cfg(feature = "cbor")]
+impl CborEncoder {
+    /// Creates a new CBOR encoder.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl<T: serde::Serialize> BufEncoder<T> for CborEncoder {
+    type Error = ciborium::ser::Error<std::io::Error>;
+
+    fn encode<B: BufMut>(&mut self, data: &T, buf: &mut B) -> Result<(), Self::Error> {
+        ciborium::into_writer(data, buf.writer())
+    }
+}
+
+/// JSON encoder.
+#[cfg(feature = "json")]
+#[derive(Clone, Debug, Default)]
+pub struct JsonEncoder {
+    /// A newline is appended to each message.
+    lines: bool,
+}
+
+#[cfg(feature = "json")]
+impl JsonEncoder {
+    /// Creates a new JSON encoder producing compact messages.
+    pub fn new() -> Self {
+        Self { lines: false }
+    }
+
+    /// Creates a new JSON encoder producing compact messages terminated by a
+    /// newline, as in the JSON Lines format.
+    pub fn lines() -> Self {
+        Self { lines: true }
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: serde::Serialize> BufEncoder<T> for JsonEncoder {
+    type Error = serde_json::Error;
+
+    fn encode<B: BufMut>(&mut self, data: &T, buf: &mut B) -> Result<(), Self::Error> {
+        serde_json::to_writer(buf.writer(), data)?;
+        if self.lines {
+            buf.put_u8(b'\n');
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,384 @@
+//! Payload fragmentation.
+//!
+//! This module contains models splitting payloads into fragments fitting the
+//! maximum transmission unit (MTU) of a link and reassembling them on the
+//! receiving side.
+//!
+//! When headers are enabled, each fragment starts with a 2-byte big-endian
+//! [`FragmentHeader`]: the most significant bit is the more-fragments flag and
+//! the remaining 15 bits are the index of the fragment within the payload.
+use std::fmt;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use nexosim::model::Model;
+use nexosim::ports::Output;
+
+/// Fragment header.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FragmentHeader {
+    /// Index of the fragment within the payload.
+    pub index: u16,
+
+    /// More fragments follow.
+    pub more: bool,
+}
+
+impl FragmentHeader {
+    /// Header size, in bytes.
+    pub const SIZE: usize = 2;
+
+    /// Maximum fragment index.
+    pub const MAX_INDEX: u16 = 0x7FFF;
+
+    /// More-fragments flag mask.
+    const MORE_FLAG: u16 = 0x8000;
+
+    /// Parses the header at the start of a fragment.
+    ///
+    /// Returns `None` if the fragment is too short.
+    pub fn parse(fragment: &[u8]) -> Option<Self> {
+        let raw = u16::from_be_bytes(fragment.get(..Self::SIZE)?.try_into().ok()?);
+
+        Some(Self {
+            index: raw & Self::MAX_INDEX,
+            more: raw & Self::MORE_FLAG != 0,
+        })
+    }
+
+    /// Writes the header to a buffer.
+    pub fn write<B: BufMut>(&self, buf: &mut B) {
+        let more = if self.more { Self::MORE_FLAG } else { 0 };
+        buf.put_u16((self.index & Self::MAX_INDEX) | more);
+    }
+}
+
+/// Payload fragmentation model.
+pub struct Fragmenter {
+    /// Fragments -- output port.
+    pub fragment_out: Output<Bytes>,
+
+    /// Maximum fragment size, including the header.
+    mtu: usize,
+
+    /// Fragment headers are added.
+    with_header: bool,
+}
+
+impl Fragmenter {
+    /// Creates a new fragmentation model.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the MTU cannot accommodate at least one byte of
+    /// payload.
+    pub fn new(mtu: usize, with_header: bool) -> Self {
+        let header_size = if with_header { FragmentHeader::SIZE } else { 0 };
+        assert!(
+            mtu > header_size,
+            "the MTU should be larger than the fragment header"
+        );
+
+        Self {
+            fragment_out: Output::new(),
+            mtu,
+            with_header,
+        }
+    }
+
+    /// Payload -- input port.
+    ///
+    /// Payloads which would require more fragments than the header can index
+    /// are dropped.
+    pub async fn payload_in(&mut self, payload: Bytes) {
+        for fragment in self.fragment(&payload) {
+            self.fragment_out.send(fragment).await;
+        }
+    }
+
+    /// Splits a payload into fragments.
+    ///
+    /// Returns no fragments if the payload would require more fragments than
+    /// the header can index.
+    fn fragment(&self, payload: &Bytes) -> Vec<Bytes> {
+        if !self.with_header {
+            return (0..payload.len())
+                .step_by(self.mtu)
+                .map(|start| payload.slice(start..(start + self.mtu).min(payload.len())))
+                .collect();
+        }
+
+        let chunk_size = self.mtu - FragmentHeader::SIZE;
+        let count = payload.len().div_ceil(chunk_size).max(1);
+        if count > FragmentHeader::MAX_INDEX as usize + 1 {
+            return Vec::new();
+        }
+
+        (0..count)
+            .map(|index| {
+                let start = index * chunk_size;
+                let end = (start + chunk_size).min(payload.len());
+
+                let mut fragment = BytesMut::with_capacity(FragmentHeader::SIZE + end - start);
+                FragmentHeader {
+                    index: index as u16,
+                    more: index + 1 < count,
+                }
+                .write(&mut fragment);
+                fragment.put_slice(&payload[start..end]);
+
+                fragment.freeze()
+            })
+            .collect()
+    }
+}
+
+impl Model for Fragmenter {}
+
+impl fmt::Debug for Fragmenter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Fragmenter")
+            .field("mtu", &self.mtu)
+            .field("with_header", &self.with_header)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Fragment reassembly model.
+///
+/// This model expects fragments with headers, as produced by a [`Fragmenter`].
+/// A payload with a missing or out-of-order fragment is discarded.
+pub struct Reassembler {
+    /// Reassembled payloads -- output port.
+    pub payload_out: Output<Bytes>,
+
+    /// Payload being reassembled.
+    buf: BytesMut,
+
+    /// Index of the next expected fragment, if reassembly is in progress.
+    next_index: Option<u16>,
+}
+
+impl Reassembler {
+    /// Creates a new reassembly model.
+    pub fn new() -> Self {
+        Self {
+            payload_out: Output::new(),
+            buf: BytesMut::new(),
+            next_index: None,
+        }
+    }
+
+    /// Fragment -- input port.
+    pub async fn fragment_in(&mut self, fragment: Bytes) {
+        if let Some(payload) = self.reassemble(&fragment) {
+            self.payload_out.send(payload).await;
+        }
+    }
+
+    /// Adds a fragment to the payload being reassembled, returning the
+    /// payload when its last fragment is received.
+    fn reassemble(&mut self, fragment: &[u8]) -> Option<Bytes> {
+        let header = FragmentHeader::parse(fragment)?;
+
+        if header.index == 0 {
+            self.buf.clear();
+        } else if self.next_index != Some(header.index) {
+            // A fragment was lost: wait for the start of the next payload.
+            self.next_index = None;
+            return None;
+        }
+
+        self.buf
+            .extend_from_slice(&fragment[FragmentHeader::SIZE..]);
+
+        if header.more {
+            self.next_index = header.index.checked_add(1);
+
+            None
+        } else {
+            self.next_index = None;
+
+            Some(self.buf.split().freeze())
+        }
+    }
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Model for Reassembler {}
+
+impl fmt::Debug for Reassembler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Reassembler").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment(index: u16, more: bool, data: &[u8]) -> Bytes {
+        let mut fragment = BytesMut::new();
+        FragmentHeader { index, more }.write(&mut fragment);
+        fragment.put_slice(data);
+
+        fragment.freeze()
+    }
+
+    fn reassemble_all(reassembler: &mut Reassembler, fragments: &[Bytes]) -> Vec<Bytes> {
+        fragments
+            .iter()
+            .filter_map(|fragment| reassembler.reassemble(fragment))
+            .collect()
+    }
+
+    #[test]
+    fn header_round_trip() {
+        for header in [
+            FragmentHeader {
+                index: 0,
+                more: true,
+            },
+            FragmentHeader {
+                index: 0x1234,
+                more: false,
+            },
+            FragmentHeader {
+                index: FragmentHeader::MAX_INDEX,
+                more: true,
+            },
+        ] {
+            let mut buf = Vec::new();
+            header.write(&mut buf);
+
+            assert_eq!(buf.len(), FragmentHeader::SIZE);
+            assert_eq!(FragmentHeader::parse(&buf), Some(header));
+        }
+
+        assert_eq!(FragmentHeader::parse(&[0x80]), None);
+    }
+
+    #[test]
+    fn fragment_round_trip() {
+        let payload = Bytes::from_static(b"a payload split into several fragments");
+        let fragments = Fragmenter::new(8, true).fragment(&payload);
+
+        assert_eq!(fragments.len(), payload.len().div_ceil(6));
+        assert!(fragments.iter().all(|fragment| fragment.len() <= 8));
+
+        let mut reassembler = Reassembler::new();
+        assert_eq!(reassemble_all(&mut reassembler, &fragments), [payload]);
+    }
+
+    #[test]
+    fn fragment_without_header() {
+        let payload = Bytes::from_static(b"0123456789");
+        let fragments = Fragmenter::new(4, false).fragment(&payload);
+
+        assert_eq!(fragments, [&b"0123"[..], &b"4567"[..], &b"89"[..]]);
+    }
+
+    #[test]
+    fn empty_payload() {
+        let fragments = Fragmenter::new(8, true).fragment(&Bytes::new());
+
+        assert_eq!(fragments, [fragment(0, false, &[])]);
+        assert_eq!(
+            Reassembler::new().reassemble(&fragments[0]),
+            Some(Bytes::new())
+        );
+    }
+
+    #[test]
+    fn header_overflow() {
+        let fragmenter = Fragmenter::new(FragmentHeader::SIZE + 1, true);
+
+        let max_len = FragmentHeader::MAX_INDEX as usize + 1;
+        let fragments = fragmenter.fragment(&Bytes::from(vec![0; max_len]));
+        assert_eq!(fragments.len(), max_len);
+        assert_eq!(
+            FragmentHeader::parse(fragments.last().unwrap()),
+            Some(FragmentHeader {
+                index: FragmentHeader::MAX_INDEX,
+                more: false,
+            })
+        );
+
+        assert!(
+            fragmenter
+                .fragment(&Bytes::from(vec![0; max_len + 1]))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn reordered_fragments() {
+        let fragments = [
+            fragment(0, true, b"ab"),
+            fragment(2, true, b"ef"),
+            fragment(1, true, b"cd"),
+            fragment(3, false, b"gh"),
+        ];
+        let mut reassembler = Reassembler::new();
+
+        assert!(reassemble_all(&mut reassembler, &fragments).is_empty());
+
+        // The next payload is reassembled normally.
+        let fragments = [fragment(0, true, b"ab"), fragment(1, false, b"cd")];
+        assert_eq!(reassemble_all(&mut reassembler, &fragments), [&b"abcd"[..]]);
+    }
+
+    #[test]
+    fn duplicate_fragments() {
+        let fragments = [
+            fragment(0, true, b"ab"),
+            fragment(1, true, b"cd"),
+            fragment(1, true, b"cd"),
+            fragment(2, false, b"ef"),
+        ];
+        let mut reassembler = Reassembler::new();
+
+        assert!(reassemble_all(&mut reassembler, &fragments).is_empty());
+
+        // A repeated first fragment restarts the reassembly.
+        let fragments = [
+            fragment(0, true, b"xx"),
+            fragment(0, true, b"ab"),
+            fragment(1, false, b"cd"),
+        ];
+        assert_eq!(reassemble_all(&mut reassembler, &fragments), [&b"abcd"[..]]);
+    }
+
+    #[test]
+    fn missing_last_fragment() {
+        let fragments = [
+            fragment(0, true, b"ab"),
+            fragment(1, true, b"cd"),
+            fragment(0, true, b"ef"),
+            fragment(1, false, b"gh"),
+        ];
+        let mut reassembler = Reassembler::new();
+
+        assert_eq!(reassemble_all(&mut reassembler, &fragments), [&b"efgh"[..]]);
+    }
+
+    #[test]
+    fn missing_first_fragment() {
+        let fragments = [fragment(1, true, b"cd"), fragment(2, false, b"ef")];
+        let mut reassembler = Reassembler::new();
+
+        assert!(reassemble_all(&mut reassembler, &fragments).is_empty());
+    }
+
+    #[test]
+    fn truncated_header() {
+        let mut reassembler = Reassembler::new();
+
+        assert_eq!(reassembler.reassemble(&[0x00]), None);
+    }
+}
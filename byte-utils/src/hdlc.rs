@@ -0,0 +1,455 @@
+//! HDLC framing for synchronous serial links.
+//!
+//! This module contains an encoder and a decoder for bit-oriented HDLC
+//! frames, to be used with [`ByteStreamEncoder`](crate::encode::ByteStreamEncoder)
+//! and [`ByteStreamDecoder`](crate::decode::ByteStreamDecoder) respectively.
+//!
+//! A frame is delimited by `0x7E` flags, a zero bit is stuffed after any
+//! sequence of five consecutive one bits between flags, and the frame check
+//! sequence (FCS) is appended to the payload before stuffing. Bits are
+//! transmitted least significant bit first and each encoded frame is padded
+//! with idle one bits to a byte boundary.
+use std::convert::Infallible;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::decode::{BufDecoder, BufDecoderResult};
+use crate::encode::BufEncoder;
+
+/// Flag delimiting HDLC frames.
+const FLAG: u8 = 0x7E;
+
+/// Frame check sequence.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Fcs {
+    /// 16-bit CRC (CRC-16/X.25).
+    #[default]
+    Fcs16,
+    /// 32-bit CRC (CRC-32/ISO-HDLC).
+    Fcs32,
+}
+
+impl Fcs {
+    /// Returns the size of the frame check sequence, in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            Fcs::Fcs16 => 2,
+            Fcs::Fcs32 => 4,
+        }
+    }
+
+    /// Computes the frame check sequence of the data.
+    ///
+    /// The returned bytes are in transmission order.
+    fn compute(&self, data: &[u8]) -> ([u8; 4], usize) {
+        match self {
+            Fcs::Fcs16 => {
+                let mut crc: u16 = 0xFFFF;
+                for byte in data {
+                    crc ^= *byte as u16;
+                    for _ in 0..8 {
+                        crc = if crc & 1 != 0 {
+                            (crc >> 1) ^ 0x8408
+                        } else {
+                            crc >> 1
+                        };
+                    }
+                }
+                let [b0, b1] = (!crc).to_le_bytes();
+
+                ([b0, b1, 0, 0], 2)
+            }
+            Fcs::Fcs32 => {
+                let mut crc: u32 = 0xFFFF_FFFF;
+                for byte in data {
+                    crc ^= *byte as u32;
+                    for _ in 0..8 {
+                        crc = if crc & 1 != 0 {
+                            (crc >> 1) ^ 0xEDB8_8320
+                        } else {
+                            crc >> 1
+                        };
+                    }
+                }
+
+                ((!crc).to_le_bytes(), 4)
+            }
+        }
+    }
+}
+
+/// Least significant bit first bit writer.
+struct BitWriter<'a, B: BufMut> {
+    /// Output buffer.
+    buf: &'a mut B,
+
+    /// Bits not yet written to the buffer.
+    acc: u8,
+
+    /// Number of bits in the accumulator.
+    count: u32,
+
+    /// Number of consecutive one bits written since the last zero.
+    ones: u32,
+}
+
+impl<'a, B: BufMut> BitWriter<'a, B> {
+    fn new(buf: &'a mut B) -> Self {
+        Self {
+            buf,
+            acc: 0,
+            count: 0,
+            ones: 0,
+        }
+    }
+
+    /// Writes a single bit.
+    fn put_bit(&mut self, bit: bool) {
+        if bit {
+            self.acc |= 1 << self.count;
+        }
+        self.count += 1;
+        if self.count == 8 {
+            self.buf.put_u8(self.acc);
+            self.acc = 0;
+            self.count = 0;
+        }
+    }
+
+    /// Writes a flag.
+    fn put_flag(&mut self) {
+        for i in 0..8 {
+            self.put_bit(FLAG & (1 << i) != 0);
+        }
+        self.ones = 0;
+    }
+
+    /// Writes a byte, stuffing zero bits as needed.
+    fn put_stuffed(&mut self, byte: u8) {
+        for i in 0..8 {
+            let bit = byte & (1 << i) != 0;
+            self.put_bit(bit);
+            if bit {
+                self.ones += 1;
+                if self.ones == 5 {
+                    self.put_bit(false);
+                    self.ones = 0;
+                }
+            } else {
+                self.ones = 0;
+            }
+        }
+    }
+
+    /// Pads the last byte with idle one bits.
+    fn finish(mut self) {
+        while self.count != 0 {
+            self.put_bit(true);
+        }
+    }
+}
+
+/// HDLC frame encoder.
+///
+/// Encodes each payload as a complete frame with opening and closing flags.
+#[derive(Clone, Debug, Default)]
+pub struct HdlcEncoder {
+    /// Frame check sequence.
+    fcs: Fcs,
+}
+
+impl HdlcEncoder {
+    /// Creates a new HDLC encoder.
+    pub fn new(fcs: Fcs) -> Self {
+        Self { fcs }
+    }
+}
+
+impl BufEncoder<Bytes> for HdlcEncoder {
+    type Error = Infallible;
+
+    fn encode<B: BufMut>(&mut self, data: &Bytes, buf: &mut B) -> Result<(), Self::Error> {
+        let (fcs, fcs_size) = self.fcs.compute(data);
+
+        let mut writer = BitWriter::new(buf);
+        writer.put_flag();
+        for byte in data.iter().chain(&fcs[..fcs_size]) {
+            writer.put_stuffed(*byte);
+        }
+        writer.put_flag();
+        writer.finish();
+
+        Ok(())
+    }
+}
+
+/// HDLC frame decoder.
+///
+/// Decodes payloads of frames with a valid frame check sequence. Frames which
+/// are aborted, not a whole number of bytes long or fail the frame check are
+/// silently discarded.
+#[derive(Debug, Default)]
+pub struct HdlcDecoder {
+    /// Frame check sequence.
+    fcs: Fcs,
+
+    /// Frame being decoded.
+    frame: BytesMut,
+
+    /// A frame was opened by a flag.
+    in_frame: bool,
+
+    /// Bits not yet added to the frame.
+    acc: u8,
+
+    /// Number of bits in the accumulator.
+    count: u32,
+
+    /// Number of consecutive one bits received.
+    ones: u32,
+
+    /// Input byte being decoded.
+    byte: u8,
+
+    /// Number of bits of the input byte still to be decoded.
+    remaining: u32,
+}
+
+impl HdlcDecoder {
+    /// Creates a new HDLC decoder.
+    pub fn new(fcs: Fcs) -> Self {
+        Self {
+            fcs,
+            ..Default::default()
+        }
+    }
+
+    /// Starts a new frame.
+    fn restart(&mut self, in_frame: bool) {
+        self.frame.clear();
+        self.in_frame = in_frame;
+        self.acc = 0;
+        self.count = 0;
+    }
+
+    /// Processes a single bit, returning a payload when a valid frame is
+    /// closed.
+    fn process_bit(&mut self, bit: bool) -> Option<Bytes> {
+        if bit {
+            self.ones += 1;
+            if self.ones >= 7 {
+                // Abort sequence or idle line.
+                self.restart(false);
+                return None;
+            }
+        } else {
+            let ones = self.ones;
+            self.ones = 0;
+            match ones {
+                // Stuffed zero.
+                5 => return None,
+                // Flag: the last 7 accumulated bits were part of it.
+                6 => {
+                    let is_valid = self.in_frame
+                        && self.count == 7
+                        && self.frame.len() > self.fcs.size()
+                        && self.check_fcs();
+                    let payload = is_valid.then(|| {
+                        let len = self.frame.len() - self.fcs.size();
+                        self.frame.split_to(len).freeze()
+                    });
+                    self.restart(true);
+
+                    return payload;
+                }
+                _ => {}
+            }
+        }
+
+        if self.in_frame {
+            if bit {
+                self.acc |= 1 << self.count;
+            }
+            self.count += 1;
+            if self.count == 8 {
+                self.frame.put_u8(self.acc);
+                self.acc = 0;
+                self.count = 0;
+            }
+        }
+
+        None
+    }
+
+    /// Checks the frame check sequence at the end of the frame.
+    fn check_fcs(&self) -> bool {
+        let (payload, received) = self.frame.split_at(self.frame.len() - self.fcs.size());
+        let (fcs, fcs_size) = self.fcs.compute(payload);
+
+        received == &fcs[..fcs_size]
+    }
+}
+
+impl BufDecoder<Bytes> for HdlcDecoder {
+    type Error = Infallible;
+
+    fn decode<B: Buf>(&mut self, buf: &mut B) -> BufDecoderResult<Bytes, Self::Error> {
+        loop {
+            if self.remaining == 0 {
+                if !buf.has_remaining() {
+                    return if self.in_frame && !(self.frame.is_empty() && self.count == 0) {
+                        BufDecoderResult::Partial
+                    } else {
+                        BufDecoderResult::Empty
+                    };
+                }
+                self.byte = buf.get_u8();
+                self.remaining = 8;
+            }
+
+            while self.remaining > 0 {
+                let bit = self.byte & (1 << (8 - self.remaining)) != 0;
+                self.remaining -= 1;
+                if let Some(payload) = self.process_bit(bit) {
+                    return BufDecoderResult::Decoded(payload);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(fcs: Fcs, payload: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        HdlcEncoder::new(fcs)
+            .encode(&Bytes::copy_from_slice(payload), &mut buf)
+            .unwrap();
+
+        buf
+    }
+
+    fn decode_all(decoder: &mut HdlcDecoder, mut data: &[u8]) -> Vec<Bytes> {
+        let mut payloads = Vec::new();
+        while let BufDecoderResult::Decoded(payload) = decoder.decode(&mut data) {
+            payloads.push(payload);
+        }
+
+        payloads
+    }
+
+    /// Counts the flag patterns in the bit stream, least significant bit
+    /// first.
+    fn count_flags(data: &[u8]) -> usize {
+        let bits: Vec<bool> = data
+            .iter()
+            .flat_map(|byte| (0..8).map(move |i| byte & (1 << i) != 0))
+            .collect();
+
+        bits.windows(8)
+            .filter(|w| !w[0] && w[1..7].iter().all(|b| *b) && !w[7])
+            .count()
+    }
+
+    #[test]
+    fn fcs_check_values() {
+        assert_eq!(Fcs::Fcs16.compute(b"123456789"), ([0x6E, 0x90, 0, 0], 2));
+        assert_eq!(
+            Fcs::Fcs32.compute(b"123456789"),
+            ([0x26, 0x39, 0xF4, 0xCB], 4)
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for fcs in [Fcs::Fcs16, Fcs::Fcs32] {
+            for payload in [&b"\x01"[..], b"hello", &[0x55; 64]] {
+                let encoded = encode(fcs, payload);
+                let mut decoder = HdlcDecoder::new(fcs);
+
+                assert_eq!(decode_all(&mut decoder, &encoded), [payload]);
+                assert_eq!(decoder.decode(&mut &[][..]), BufDecoderResult::Empty);
+            }
+        }
+    }
+
+    #[test]
+    fn consecutive_frames() {
+        let mut encoded = encode(Fcs::Fcs16, b"first");
+        encoded.extend(encode(Fcs::Fcs16, b"second"));
+        let mut decoder = HdlcDecoder::new(Fcs::Fcs16);
+
+        assert_eq!(
+            decode_all(&mut decoder, &encoded),
+            [&b"first"[..], &b"second"[..]]
+        );
+    }
+
+    #[test]
+    fn bit_stuffing() {
+        let payload = [0x7E, 0xFF, 0xFF, 0x7D, 0x3F, 0x7E];
+        let encoded = encode(Fcs::Fcs16, &payload);
+
+        // Only the opening and closing flags may appear on the line.
+        assert_eq!(count_flags(&encoded), 2);
+        assert!(encoded.len() > payload.len() + Fcs::Fcs16.size() + 2);
+
+        let mut decoder = HdlcDecoder::new(Fcs::Fcs16);
+        assert_eq!(decode_all(&mut decoder, &encoded), [&payload[..]]);
+    }
+
+    #[test]
+    fn bad_fcs() {
+        let mut encoded = encode(Fcs::Fcs16, &[0x00; 4]);
+        // The opening flag is byte aligned, so this flips a payload bit.
+        encoded[1] ^= 0x01;
+        let mut decoder = HdlcDecoder::new(Fcs::Fcs16);
+
+        assert!(decode_all(&mut decoder, &encoded).is_empty());
+
+        // The decoder recovers on the next frame.
+        let encoded = encode(Fcs::Fcs16, b"next");
+        assert_eq!(decode_all(&mut decoder, &encoded), [&b"next"[..]]);
+    }
+
+    #[test]
+    fn fcs_mismatch() {
+        let encoded = encode(Fcs::Fcs32, b"payload");
+        let mut decoder = HdlcDecoder::new(Fcs::Fcs16);
+
+        assert!(decode_all(&mut decoder, &encoded).is_empty());
+    }
+
+    #[test]
+    fn split_frame() {
+        let encoded = encode(Fcs::Fcs32, b"split payload");
+        let (head, tail) = encoded.split_at(encoded.len() / 2);
+        let mut decoder = HdlcDecoder::new(Fcs::Fcs32);
+
+        assert_eq!(decoder.decode(&mut &head[..]), BufDecoderResult::Partial);
+        assert_eq!(decode_all(&mut decoder, tail), [&b"split payload"[..]]);
+    }
+
+    #[test]
+    fn truncated_frame() {
+        let truncated = encode(Fcs::Fcs16, b"truncated");
+        let mut encoded = truncated[..truncated.len() / 2].to_vec();
+        encoded.extend(encode(Fcs::Fcs16, b"complete"));
+        let mut decoder = HdlcDecoder::new(Fcs::Fcs16);
+
+        assert_eq!(decode_all(&mut decoder, &encoded), [&b"complete"[..]]);
+    }
+
+    #[test]
+    fn aborted_frame() {
+        let aborted = encode(Fcs::Fcs16, b"aborted");
+        let mut encoded = aborted[..aborted.len() / 2].to_vec();
+        encoded.push(0xFF);
+        let mut decoder = HdlcDecoder::new(Fcs::Fcs16);
+
+        assert!(decode_all(&mut decoder, &encoded).is_empty());
+        assert_eq!(decoder.decode(&mut &[][..]), BufDecoderResult::Empty);
+    }
+}
@@ -6,3 +6,7 @@
 #![forbid(unsafe_code)]
 
 pub mod decode;
+pub mod encode;
+pub mod fragment;
+pub mod hdlc;
+pub mod pacing;
@@ -0,0 +1,162 @@
+//! Byte stream pacing.
+//!
+//! This module contains a model re-emitting bytes over simulated time
+//! according to the line rate of a serial link, so that consumers see
+//! realistic byte arrival times instead of whole frames at one instant.
+use std::fmt;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use nexosim::model::{Context, Model};
+use nexosim::ports::Output;
+use nexosim::time::MonotonicTime;
+
+/// Parity mode of a character frame.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Parity {
+    /// No parity bit.
+    #[default]
+    None,
+    /// Even parity bit.
+    Even,
+    /// Odd parity bit.
+    Odd,
+}
+
+/// Line rate of an asynchronous serial link.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LineRate {
+    /// Baud rate, in bits per second.
+    baud_rate: u32,
+
+    /// Number of bits on the wire per transmitted byte, including start,
+    /// parity and stop bits.
+    bits_per_char: u32,
+}
+
+impl LineRate {
+    /// Creates a new line rate.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the baud rate or the number of data bits is
+    /// zero.
+    pub fn new(baud_rate: u32, data_bits: u32, parity: Parity, stop_bits: u32) -> Self {
+        assert!(baud_rate > 0, "the baud rate should be non-zero");
+        assert!(data_bits > 0, "the number of data bits should be non-zero");
+
+        let parity_bits = match parity {
+            Parity::None => 0,
+            Parity::Even | Parity::Odd => 1,
+        };
+
+        Self {
+            baud_rate,
+            bits_per_char: 1 + data_bits + parity_bits + stop_bits,
+        }
+    }
+
+    /// Creates a new line rate for the usual 8N1 character frame (8 data
+    /// bits, no parity, 1 stop bit).
+    pub fn uart_8n1(baud_rate: u32) -> Self {
+        Self::new(baud_rate, 8, Parity::None, 1)
+    }
+
+    /// Returns the baud rate.
+    pub fn baud_rate(&self) -> u32 {
+        self.baud_rate
+    }
+
+    /// Returns the number of bits on the wire per transmitted byte.
+    pub fn bits_per_char(&self) -> u32 {
+        self.bits_per_char
+    }
+
+    /// Returns the time needed to transmit `count` bytes.
+    pub fn transmission_time(&self, count: usize) -> Duration {
+        let bits = count as u128 * self.bits_per_char as u128;
+        let nanos = bits * 1_000_000_000 / self.baud_rate as u128;
+
+        Duration::from_nanos(nanos as u64)
+    }
+}
+
+/// Byte stream pacing model.
+///
+/// This model re-emits the input bytes at the time their last bit would have
+/// been received on a link with the configured line rate. Back-to-back inputs
+/// are queued as they would be in a transmitter FIFO.
+pub struct BytePacer {
+    /// Paced bytes -- output port.
+    pub bytes_out: Output<Bytes>,
+
+    /// Line rate.
+    line_rate: LineRate,
+
+    /// Maximum number of bytes emitted at once.
+    chunk_size: usize,
+
+    /// Time at which the line becomes idle, if ever used.
+    busy_until: Option<MonotonicTime>,
+}
+
+impl BytePacer {
+    /// Creates a new pacing model emitting bytes one by one.
+    pub fn new(line_rate: LineRate) -> Self {
+        Self::with_chunk_size(line_rate, 1)
+    }
+
+    /// Creates a new pacing model emitting bytes by chunks of at most
+    /// `chunk_size` bytes.
+    ///
+    /// Larger chunks reduce the number of simulation events at the cost of a
+    /// coarser arrival time resolution.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the chunk size is zero.
+    pub fn with_chunk_size(line_rate: LineRate, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "the chunk size should be non-zero");
+
+        Self {
+            bytes_out: Output::new(),
+            line_rate,
+            chunk_size,
+            busy_until: None,
+        }
+    }
+
+    /// Bytes to be paced -- input port.
+    pub async fn bytes_in(&mut self, data: Bytes, cx: &mut Context<Self>) {
+        let now = cx.time();
+        let start = self.busy_until.filter(|t| *t > now).unwrap_or(now);
+
+        let mut sent = 0;
+        while sent < data.len() {
+            let end = (sent + self.chunk_size).min(data.len());
+            let deadline = start + self.line_rate.transmission_time(end);
+            cx.schedule_event(deadline, Self::emit, data.slice(sent..end))
+                .unwrap();
+            sent = end;
+        }
+
+        self.busy_until = Some(start + self.line_rate.transmission_time(data.len()));
+    }
+
+    /// Emits a chunk of bytes.
+    async fn emit(&mut self, data: Bytes) {
+        self.bytes_out.send(data).await;
+    }
+}
+
+impl Model for BytePacer {}
+
+impl fmt::Debug for BytePacer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BytePacer")
+            .field("line_rate", &self.line_rate)
+            .field("chunk_size", &self.chunk_size)
+            .finish_non_exhaustive()
+    }
+}
@@ -24,8 +24,6 @@
 use std::thread::{self, sleep};
 use std::time::Duration;
 
-use schematic::{ConfigLoader, Format};
-
 use socketcan::{BlockingCan, CanFrame, CanSocket, EmbeddedFrame, Id, Socket, StandardId};
 
 use nexosim::model::{Context, Model};
@@ -156,13 +154,15 @@ fn main() -> Result<(), SimulationError> {
         &counter_mbox,
     );
     counter.count.map_connect(
-        |c| CanData {
-            interface: 0,
-            frame: CanFrame::new(
-                Id::Standard(StandardId::new(STAT_ID).unwrap()),
-                &c.to_le_bytes(),
+        |c| {
+            CanData::new(
+                0,
+                CanFrame::new(
+                    Id::Standard(StandardId::new(STAT_ID).unwrap()),
+                    &c.to_le_bytes(),
+                )
+                .unwrap(),
             )
-            .unwrap(),
         },
         CanPort::frame_in,
         &can_mbox,
@@ -275,15 +275,13 @@ fn main() -> Result<(), SimulationError> {
 
 /// Gets serial port configuration.
 fn get_can_port_cfg(interfaces: &[&str]) -> CanPortConfig {
-    let mut loader = ConfigLoader::<CanPortConfig>::new();
-    loader
-        .code(format!("interfaces = {:?}", interfaces), Format::Toml)
-        .unwrap();
-    loader
-        .code(format!("delta = {}", DELTA), Format::Toml)
-        .unwrap();
-    loader
-        .code(format!("period = {}", PERIOD), Format::Toml)
-        .unwrap();
-    loader.load().unwrap().config
+    interfaces
+        .iter()
+        .fold(CanPortConfig::builder(), |builder, interface| {
+            builder.interface(*interface)
+        })
+        .delta_ms(DELTA)
+        .period_ms(PERIOD)
+        .build()
+        .unwrap()
 }
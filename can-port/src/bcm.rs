@@ -0,0 +1,571 @@
+//! SocketCAN broadcast manager.
+//!
+//! This module contains a model offloading cyclic transmissions and content
+//! monitoring to the kernel broadcast manager (BCM):
+//! * configured frames are transmitted cyclically by the kernel, and their
+//!   content can be updated from the simulation,
+//! * configured identifiers are monitored by the kernel, which only notifies
+//!   the simulation when the frame content changes or when no frame is
+//!   received within a timeout.
+//!
+//! For benches with many cyclic frames this avoids routing each frame through
+//! the simulation.
+//!
+//! Cyclic transmissions start when the model is built and stop when it is
+//! dropped.
+use std::fmt;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+use mio::{Interest, Registry, Token, unix::SourceFd};
+
+use schematic::Config;
+
+use socket2::{Domain, Protocol, Socket as RawSocket, Type};
+use socketcan::{CanAddr, CanFrame, EmbeddedFrame, ExtendedId, Id, StandardId};
+
+#[cfg(feature = "tracing")]
+use tracing::{info, warn};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread};
+
+use crate::{CanData, CanInterface, InterfaceName};
+
+/// CAN protocol family.
+const AF_CAN: i32 = 29;
+
+/// Broadcast manager protocol.
+const CAN_BCM: i32 = 2;
+
+/// Create or update a cyclic transmission.
+const TX_SETUP: u32 = 1;
+
+/// Create or update a receive filter.
+const RX_SETUP: u32 = 5;
+
+/// Receive timeout notification.
+const RX_TIMEOUT: u32 = 11;
+
+/// Frame content change notification.
+const RX_CHANGED: u32 = 12;
+
+/// Set the timer intervals.
+const SETTIMER: u32 = 0x0001;
+
+/// Start the timer.
+const STARTTIMER: u32 = 0x0002;
+
+/// Check the DLC for content changes.
+const RX_CHECK_DLC: u32 = 0x0040;
+
+/// Notify when reception resumes after a timeout.
+const RX_ANNOUNCE_RESUME: u32 = 0x0100;
+
+/// Extended frame format flag of raw identifiers.
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+
+/// Size of a `timeval` structure.
+const TIMEVAL_LEN: usize = 2 * size_of::<std::ffi::c_long>();
+
+/// Offset of the CAN identifier in a `bcm_msg_head` structure.
+const ID_OFFSET: usize = 12usize.next_multiple_of(size_of::<std::ffi::c_long>()) + 2 * TIMEVAL_LEN;
+
+/// Size of a `bcm_msg_head` structure, aligned for the following frames.
+const HEAD_LEN: usize = (ID_OFFSET + 8).next_multiple_of(8);
+
+/// Size of a `can_frame` structure.
+const FRAME_LEN: usize = 16;
+
+/// CAN broadcast manager model instance config.
+#[derive(Config, Debug)]
+pub struct CanBcmConfig {
+    /// List of CAN interfaces.
+    #[setting(default = vec!["vcan0".into()])]
+    pub interfaces: Vec<String>,
+
+    /// Received frames and timeouts are addressed by interface name rather
+    /// than by interface index.
+    pub interface_names: bool,
+
+    /// Time shift for scheduling events at the present moment.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Activation period for cyclic activities inside the simulation.
+    ///
+    /// If no value is provided, cyclic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Cyclic transmissions.
+    #[setting(nested)]
+    pub tx: Vec<CanBcmTxConfig>,
+
+    /// Monitored identifiers.
+    #[setting(nested)]
+    pub rx: Vec<CanBcmRxConfig>,
+}
+
+/// Cyclic transmission configuration.
+#[derive(Config, Debug)]
+pub struct CanBcmTxConfig {
+    /// CAN interface name.
+    pub interface: String,
+
+    /// CAN identifier.
+    pub id: u32,
+
+    /// Identifier is extended.
+    pub extended: bool,
+
+    /// Initial frame data.
+    pub data: Vec<u8>,
+
+    /// Transmission period.
+    pub period: ConfigDuration,
+}
+
+/// Monitored identifier configuration.
+#[derive(Config, Debug)]
+pub struct CanBcmRxConfig {
+    /// CAN interface name.
+    pub interface: String,
+
+    /// CAN identifier.
+    pub id: u32,
+
+    /// Identifier is extended.
+    pub extended: bool,
+
+    /// Mask of the data bits monitored for changes.
+    ///
+    /// If no value is provided, changes of any data bit are notified.
+    pub mask: Option<Vec<u8>>,
+
+    /// Reception timeout.
+    ///
+    /// If no value is provided, timeouts are not monitored.
+    pub timeout: Option<ConfigDuration>,
+}
+
+/// CAN reception timeout event.
+#[derive(Clone, Debug)]
+pub struct CanBcmTimeout {
+    /// CAN interface.
+    pub interface: CanInterface,
+
+    /// Monitored CAN identifier.
+    pub id: Id,
+}
+
+/// Event notified by the broadcast manager.
+#[derive(Debug)]
+enum BcmEvent {
+    /// Content of a monitored frame changed.
+    Changed(CanData),
+
+    /// Monitored frame was not received in time.
+    Timeout(CanBcmTimeout),
+}
+
+/// Message to the broadcast manager of an interface.
+#[derive(Debug)]
+struct BcmMessage {
+    /// Interface index.
+    interface: usize,
+
+    /// Encoded message.
+    bytes: Vec<u8>,
+}
+
+/// Returns the CAN identifier of a configuration.
+fn config_id(id: u32, extended: bool) -> Result<Id> {
+    let invalid = || Error::new(ErrorKind::InvalidInput, "Invalid CAN identifier.");
+    if extended {
+        Ok(Id::Extended(ExtendedId::new(id).ok_or_else(invalid)?))
+    } else {
+        let id = u16::try_from(id).map_err(|_| invalid())?;
+        Ok(Id::Standard(StandardId::new(id).ok_or_else(invalid)?))
+    }
+}
+
+/// Returns the raw identifier of a CAN identifier, with the extended frame
+/// format flag.
+fn raw_id(id: Id) -> u32 {
+    match id {
+        Id::Standard(id) => id.as_raw() as u32,
+        Id::Extended(id) => id.as_raw() | CAN_EFF_FLAG,
+    }
+}
+
+/// Appends a `timeval` structure.
+fn push_timeval(buf: &mut Vec<u8>, interval: Duration) {
+    let secs = interval.as_secs() as std::ffi::c_long;
+    let micros = interval.subsec_micros() as std::ffi::c_long;
+    buf.extend_from_slice(&secs.to_ne_bytes());
+    buf.extend_from_slice(&micros.to_ne_bytes());
+}
+
+/// Encodes a broadcast manager message.
+fn encode(
+    opcode: u32,
+    flags: u32,
+    ival1: Duration,
+    ival2: Duration,
+    id: Id,
+    frames: &[(Id, &[u8])],
+) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEAD_LEN + frames.len() * FRAME_LEN);
+    buf.extend_from_slice(&opcode.to_ne_bytes());
+    buf.extend_from_slice(&flags.to_ne_bytes());
+    // Count of `ival1` intervals.
+    buf.extend_from_slice(&0u32.to_ne_bytes());
+    buf.resize(ID_OFFSET - 2 * TIMEVAL_LEN, 0);
+    push_timeval(&mut buf, ival1);
+    push_timeval(&mut buf, ival2);
+    buf.extend_from_slice(&raw_id(id).to_ne_bytes());
+    buf.extend_from_slice(&(frames.len() as u32).to_ne_bytes());
+    buf.resize(HEAD_LEN, 0);
+
+    for (id, data) in frames {
+        let len = data.len().min(8);
+        buf.extend_from_slice(&raw_id(*id).to_ne_bytes());
+        buf.extend_from_slice(&[len as u8, 0, 0, 0]);
+        buf.extend_from_slice(&data[..len]);
+        buf.resize(buf.len() + 8 - len, 0);
+    }
+
+    buf
+}
+
+/// Decodes a raw identifier.
+fn decode_id(buf: &[u8]) -> Option<Id> {
+    let raw = u32::from_ne_bytes(buf[..4].try_into().unwrap());
+    if raw & CAN_EFF_FLAG != 0 {
+        ExtendedId::new(raw & ExtendedId::MAX.as_raw()).map(Id::Extended)
+    } else {
+        StandardId::new(raw as u16 & StandardId::MAX.as_raw()).map(Id::Standard)
+    }
+}
+
+/// Decodes a `can_frame` structure.
+fn decode_frame(buf: &[u8]) -> Option<CanFrame> {
+    let len = (buf[4] as usize).min(8);
+
+    CanFrame::new(decode_id(buf)?, &buf[8..8 + len])
+}
+
+/// Broadcast manager sockets of the CAN interfaces.
+struct CanBcmInner {
+    sockets: Vec<RawSocket>,
+}
+
+impl CanBcmInner {
+    fn new(config: &CanBcmConfig) -> Self {
+        let mut sockets = Vec::with_capacity(config.interfaces.len());
+
+        for interface in config.interfaces.iter() {
+            let socket = RawSocket::new(
+                Domain::from(AF_CAN),
+                Type::DGRAM,
+                Some(Protocol::from(CAN_BCM)),
+            )
+            .unwrap();
+            let addr = CanAddr::from_iface(interface).unwrap();
+            socket.connect(&addr.into_sock_addr()).unwrap();
+            socket.set_nonblocking(true).unwrap();
+
+            for tx in config.tx.iter().filter(|tx| tx.interface == *interface) {
+                let id = config_id(tx.id, tx.extended).unwrap();
+                let message = encode(
+                    TX_SETUP,
+                    SETTIMER | STARTTIMER,
+                    Duration::ZERO,
+                    Duration::from(tx.period),
+                    id,
+                    &[(id, &tx.data)],
+                );
+                (&socket).write_all(&message).unwrap();
+            }
+            for rx in config.rx.iter().filter(|rx| rx.interface == *interface) {
+                let id = config_id(rx.id, rx.extended).unwrap();
+                let mask = rx.mask.clone().unwrap_or_else(|| vec![0xFF; 8]);
+                let (flags, timeout) = match rx.timeout {
+                    Some(timeout) => (
+                        SETTIMER | STARTTIMER | RX_CHECK_DLC | RX_ANNOUNCE_RESUME,
+                        Duration::from(timeout),
+                    ),
+                    None => (RX_CHECK_DLC, Duration::ZERO),
+                };
+                let message = encode(RX_SETUP, flags, timeout, Duration::ZERO, id, &[(id, &mask)]);
+                (&socket).write_all(&message).unwrap();
+            }
+
+            sockets.push(socket);
+        }
+
+        Self { sockets }
+    }
+}
+
+impl IoPort<SourceFd<'static>, BcmEvent, BcmMessage> for CanBcmInner {
+    fn register(&mut self, registry: &Registry) -> Result<Token> {
+        for (i, socket) in self.sockets.iter().enumerate() {
+            registry.register(
+                &mut SourceFd(&socket.as_raw_fd()),
+                Token(i),
+                Interest::READABLE,
+            )?;
+        }
+
+        Ok(Token(self.sockets.len()))
+    }
+
+    fn read(&mut self, token: Token) -> Result<BcmEvent> {
+        let Token(i) = token;
+        let socket = self
+            .sockets
+            .get(i)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Unknown event."))?;
+
+        let mut buf = [0; HEAD_LEN + FRAME_LEN];
+        loop {
+            let len = (&*socket).read(&mut buf)?;
+            if len < HEAD_LEN {
+                continue;
+            }
+            let opcode = u32::from_ne_bytes(buf[..4].try_into().unwrap());
+            match opcode {
+                RX_CHANGED if len >= HEAD_LEN + FRAME_LEN => {
+                    if let Some(frame) = decode_frame(&buf[HEAD_LEN..]) {
+                        return Ok(BcmEvent::Changed(CanData::new(i, frame)));
+                    }
+                }
+                RX_TIMEOUT => {
+                    if let Some(id) = decode_id(&buf[ID_OFFSET..]) {
+                        return Ok(BcmEvent::Timeout(CanBcmTimeout {
+                            interface: CanInterface::Index(i),
+                            id,
+                        }));
+                    }
+                }
+                // Other notifications are ignored.
+                _ => {}
+            }
+        }
+    }
+
+    fn write(&mut self, message: &BcmMessage) -> Result<()> {
+        self.sockets.get(message.interface).map_or(
+            Err(Error::new(ErrorKind::InvalidInput, "Unknown interface.")),
+            |socket| (&*socket).write_all(&message.bytes),
+        )
+    }
+}
+
+/// CAN broadcast manager model.
+///
+/// This model
+/// * programs the kernel broadcast manager with the configured cyclic
+///   transmissions and monitored identifiers,
+/// * updates the content of cyclic transmissions from the simulation,
+/// * injects into the simulation the content changes and reception timeouts
+///   of the monitored identifiers.
+pub struct CanBcm {
+    /// Changed CAN frame -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// CAN reception timeout -- output port.
+    pub timeout_out: Output<CanBcmTimeout>,
+
+    /// Model instance configuration.
+    config: CanBcmConfig,
+
+    /// Names of the interfaces, by index.
+    names: Vec<InterfaceName>,
+
+    /// I/O thread.
+    io_thread: IoThread<BcmEvent, BcmMessage>,
+}
+
+impl CanBcm {
+    /// Creates a new CAN broadcast manager model.
+    fn new(
+        frame_out: Output<CanData>,
+        timeout_out: Output<CanBcmTimeout>,
+        config: CanBcmConfig,
+        io_thread: IoThread<BcmEvent, BcmMessage>,
+    ) -> Self {
+        let names = config
+            .interfaces
+            .iter()
+            .map(|interface| InterfaceName::new(interface))
+            .collect();
+
+        Self {
+            frame_out,
+            timeout_out,
+            config,
+            names,
+            io_thread,
+        }
+    }
+
+    /// Updates the content of a cyclic transmission -- input port.
+    ///
+    /// Updates of frames without a configured cyclic transmission are
+    /// dropped, as well as updates sent after the I/O thread has exited.
+    pub fn update_in(&mut self, data: CanData) {
+        let configured = data.interface.index(&self.config.interfaces).filter(|i| {
+            let interface = &self.config.interfaces[*i];
+            self.config.tx.iter().any(|tx| {
+                tx.interface == *interface
+                    && config_id(tx.id, tx.extended).ok() == Some(data.frame.id())
+            })
+        });
+        let Some(interface) = configured else {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Dropping update of the unknown cyclic CAN frame {:?} on the CAN interface {}.",
+                data.frame.id(),
+                data.interface
+            );
+            return;
+        };
+        #[cfg(feature = "tracing")]
+        info!(
+            "Will update cyclic CAN frame on the CAN interface {}: {:?}.",
+            self.config.interfaces[interface], data.frame
+        );
+
+        let id = data.frame.id();
+        let bytes = encode(
+            TX_SETUP,
+            0,
+            Duration::ZERO,
+            Duration::ZERO,
+            id,
+            &[(id, data.frame.data())],
+        );
+        if let Err(_e) = self.io_thread.send(BcmMessage { interface, bytes }) {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Dropping a cyclic CAN frame update after the exit of the I/O thread: {}.",
+                _e
+            );
+        }
+    }
+
+    /// Forwards the notifications of the broadcast manager.
+    pub async fn process(&mut self) {
+        for event in self.io_thread.try_recv_all() {
+            match event {
+                BcmEvent::Changed(mut data) => {
+                    data.interface = self.address(data.interface);
+                    #[cfg(feature = "tracing")]
+                    info!(
+                        "CAN frame changed on the CAN interface {}: {:?}.",
+                        data.interface, data.frame
+                    );
+                    self.frame_out.send(data).await;
+                }
+                BcmEvent::Timeout(mut timeout) => {
+                    timeout.interface = self.address(timeout.interface);
+                    #[cfg(feature = "tracing")]
+                    warn!(
+                        "CAN frame {:?} timed out on the CAN interface {}.",
+                        timeout.id, timeout.interface
+                    );
+                    self.timeout_out.send(timeout).await;
+                }
+            }
+        }
+    }
+
+    /// Returns the address of a receiving interface as configured.
+    fn address(&self, interface: CanInterface) -> CanInterface {
+        match interface {
+            CanInterface::Index(index) if self.config.interface_names => self
+                .names
+                .get(index)
+                .map_or(interface, |name| CanInterface::Name(*name)),
+            interface => interface,
+        }
+    }
+}
+
+impl Model for CanBcm {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for CanBcm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CanBcm").finish_non_exhaustive()
+    }
+}
+
+/// CAN broadcast manager model prototype.
+pub struct ProtoCanBcm {
+    /// Changed CAN frames -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// CAN reception timeouts -- output port.
+    pub timeout_out: Output<CanBcmTimeout>,
+
+    /// CAN broadcast manager model instance configuration.
+    config: CanBcmConfig,
+}
+
+impl ProtoCanBcm {
+    /// Creates a new CAN broadcast manager model prototype.
+    pub fn new(config: CanBcmConfig) -> Self {
+        Self {
+            frame_out: Output::default(),
+            timeout_out: Output::default(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoCanBcm {
+    type Model = CanBcm;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let inner = CanBcmInner::new(&self.config);
+        let io_thread = IoThread::try_new(inner).unwrap_or_else(|e| {
+            panic!("Failed to start the I/O thread of the CAN broadcast manager: {e}.")
+        });
+
+        Self::Model::new(self.frame_out, self.timeout_out, self.config, io_thread)
+    }
+}
+
+impl fmt::Debug for ProtoCanBcm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoCanBcm").finish_non_exhaustive()
+    }
+}
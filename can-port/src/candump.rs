@@ -0,0 +1,352 @@
+//! candump log recording and replay.
+//!
+//! This module contains a model writing the CAN traffic of the simulation to
+//! log files in the format of the `candump -l` utility of
+//! [can-utils](https://github.com/linux-can/can-utils), and a model replaying
+//! such log files into the simulation:
+//!
+//! ```text
+//! (1735270496.916858) can0 110#00112233
+//! (1735270509.245511) can0 18FEF100#FFFF00FFFFFFFFFF
+//! (1735279041.257318) can1 104#R
+//! ```
+//!
+//! Recorded frames are time-stamped with the simulation time. Replayed frames
+//! are emitted with the time offsets of the log relative to its first frame,
+//! optionally scaled. CAN FD frames are not supported and are skipped when
+//! replaying a log.
+//!
+//! #### Examples
+//!
+//! ```no_run
+//! use nexosim_can_port::candump::{CandumpLogger, CandumpReplayer};
+//!
+//! // Connect `CanPort::frame_out` to `CandumpLogger::frame_in`.
+//! let logger = CandumpLogger::new("traffic.log")
+//!     .unwrap()
+//!     .with_interface_names(vec!["vcan0".into(), "vcan1".into()]);
+//!
+//! // Replay a field capture of `can0` on `vcan0` at half speed, starting when
+//! // `CandumpReplayer::start` is scheduled. Connect
+//! // `CandumpReplayer::frame_out` to `CanPort::frame_in`.
+//! let replayer = CandumpReplayer::new("capture.log")
+//!     .unwrap()
+//!     .with_interface("can0", "vcan0")
+//!     .with_time_scale(2.0);
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use socketcan::dump::{ParseError, Reader};
+use socketcan::{CanAnyFrame, CanFrame, EmbeddedFrame, Frame};
+
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use nexosim::model::{Context, Model};
+use nexosim::ports::Output;
+use nexosim::simulation::ActionKey;
+use nexosim::time::MonotonicTime;
+
+use nexosim_io_utils::teardown::TrackedWriter;
+
+use crate::{CanData, CanInterface};
+
+/// Model writing CAN traffic to a candump log file.
+pub struct CandumpLogger {
+    /// Log file.
+    writer: TrackedWriter<BufWriter<File>>,
+
+    /// Names of the interfaces addressed by index.
+    interface_names: Vec<String>,
+}
+
+impl CandumpLogger {
+    /// Creates a new logger writing to the provided file.
+    ///
+    /// The file is truncated if it already exists.
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            writer: TrackedWriter::create(path)?,
+            interface_names: Vec::new(),
+        })
+    }
+
+    /// Sets the names of the interfaces addressed by index, in the order of
+    /// the `interfaces` configuration of the CAN port.
+    ///
+    /// Interfaces addressed by an index without name are logged as `canN`.
+    pub fn with_interface_names(mut self, names: Vec<String>) -> Self {
+        self.interface_names = names;
+        self
+    }
+
+    /// Returns the path of the log file.
+    pub fn path(&self) -> &Path {
+        self.writer.path()
+    }
+
+    /// Frame -- input port.
+    pub fn frame_in(&mut self, data: CanData, cx: &mut Context<Self>) {
+        let time = cx.time();
+        let line = format!(
+            "({}.{:06}) {} {}\n",
+            time.as_secs(),
+            time.subsec_nanos() / 1000,
+            self.interface_name(data.interface),
+            format_frame(&data.frame)
+        );
+        if let Err(_e) = self.writer.write_all(line.as_bytes()) {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Failed to write to {}: {}.",
+                self.writer.path().display(),
+                _e
+            );
+        }
+    }
+
+    /// Flushes the log file -- input port.
+    pub fn flush(&mut self) {
+        if let Err(_e) = self.writer.flush() {
+            #[cfg(feature = "tracing")]
+            warn!("Failed to flush {}: {}.", self.writer.path().display(), _e);
+        }
+    }
+
+    /// Returns the name of an interface.
+    fn interface_name(&self, interface: CanInterface) -> String {
+        match interface {
+            CanInterface::Index(index) => self
+                .interface_names
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| format!("can{index}")),
+            CanInterface::Name(name) => name.as_str().to_string(),
+        }
+    }
+}
+
+impl Model for CandumpLogger {}
+
+impl fmt::Debug for CandumpLogger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CandumpLogger")
+            .field("path", &self.writer.path())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Formats a frame as `<id>#<data>`.
+///
+/// Standard identifiers are written with 3 hexadecimal digits, extended
+/// identifiers and error frames with 8 digits, the latter including the error
+/// flag.
+fn format_frame(frame: &CanFrame) -> String {
+    let mut text = match frame {
+        CanFrame::Error(_) => format!("{:08X}#", frame.id_word()),
+        _ if frame.is_extended() => format!("{:08X}#", frame.raw_id()),
+        _ => format!("{:03X}#", frame.raw_id()),
+    };
+    if frame.is_remote_frame() {
+        text.push('R');
+        if frame.dlc() > 0 {
+            text.push_str(&format!("{:X}", frame.dlc()));
+        }
+    } else {
+        for byte in frame.data() {
+            text.push_str(&format!("{byte:02X}"));
+        }
+    }
+
+    text
+}
+
+/// Model replaying a candump log file.
+///
+/// The replay starts when the [`start`](Self::start) input is triggered. The
+/// first frame of the log is emitted immediately and the following frames
+/// with their time offset relative to the first frame, multiplied by the time
+/// scale. Frames logged with a timestamp earlier than the one of their
+/// predecessor are emitted immediately.
+///
+/// Interfaces are addressed by the name found in the log unless mapped to
+/// another interface with [`with_interface`](Self::with_interface).
+pub struct CandumpReplayer {
+    /// Replayed frames -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// Path of the log file.
+    path: PathBuf,
+
+    /// Log reader, `None` once the log is exhausted.
+    reader: Option<Reader<BufReader<File>>>,
+
+    /// The log reader has been used.
+    started: bool,
+
+    /// Time scale applied to the log time offsets.
+    time_scale: f64,
+
+    /// Interface mapping.
+    interfaces: HashMap<String, CanInterface>,
+
+    /// Replay start time and timestamp of the first frame, in microseconds.
+    origin: Option<(MonotonicTime, u64)>,
+
+    /// Key of the next scheduled frame.
+    next_key: Option<ActionKey>,
+}
+
+impl CandumpReplayer {
+    /// Creates a new replayer reading the provided file.
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let reader = Reader::from_file(&path)?;
+
+        Ok(Self {
+            frame_out: Output::default(),
+            path,
+            reader: Some(reader),
+            started: false,
+            time_scale: 1.0,
+            interfaces: HashMap::new(),
+            origin: None,
+            next_key: None,
+        })
+    }
+
+    /// Sets the time scale applied to the time offsets of the log.
+    ///
+    /// A scale greater than 1 slows the replay down, a scale smaller than 1
+    /// speeds it up and a zero scale emits all frames at once.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the time scale is negative or not finite.
+    pub fn with_time_scale(mut self, time_scale: f64) -> Self {
+        assert!(
+            time_scale.is_finite() && time_scale >= 0.0,
+            "the time scale should be finite and non-negative"
+        );
+        self.time_scale = time_scale;
+        self
+    }
+
+    /// Replays the frames logged on the specified log interface on another
+    /// interface.
+    pub fn with_interface(mut self, log_name: &str, interface: impl Into<CanInterface>) -> Self {
+        self.interfaces
+            .insert(log_name.to_string(), interface.into());
+        self
+    }
+
+    /// Starts the replay -- input port.
+    ///
+    /// If a replay is in progress, it is restarted from the beginning of the
+    /// log.
+    pub async fn start(&mut self, _: (), cx: &mut Context<Self>) {
+        self.stop();
+        if self.started {
+            self.reader = Reader::from_file(&self.path)
+                .inspect_err(|_e| {
+                    #[cfg(feature = "tracing")]
+                    warn!("Failed to reopen {}: {}.", self.path.display(), _e);
+                })
+                .ok();
+        }
+        self.started = true;
+        self.origin = None;
+
+        self.replay(cx).await;
+    }
+
+    /// Stops the replay -- input port.
+    pub fn stop(&mut self) {
+        if let Some(key) = self.next_key.take() {
+            key.cancel();
+        }
+    }
+
+    /// Emits a scheduled frame and continues the replay.
+    async fn emit(&mut self, data: CanData, cx: &mut Context<Self>) {
+        self.next_key = None;
+        self.frame_out.send(data).await;
+        self.replay(cx).await;
+    }
+
+    /// Emits the frames which are due and schedules the next one.
+    async fn replay(&mut self, cx: &mut Context<Self>) {
+        while let Some((t_us, data)) = self.next_frame() {
+            let (start, t0_us) = *self.origin.get_or_insert((cx.time(), t_us));
+            let offset = Duration::from_micros(t_us.saturating_sub(t0_us));
+            let deadline = start + offset.mul_f64(self.time_scale);
+            if deadline > cx.time() {
+                self.schedule(deadline, data, cx);
+                return;
+            }
+            self.frame_out.send(data).await;
+        }
+    }
+
+    /// Schedules the emission of a frame.
+    fn schedule(&mut self, deadline: MonotonicTime, data: CanData, cx: &mut Context<Self>) {
+        self.next_key = Some(cx.schedule_keyed_event(deadline, Self::emit, data).unwrap());
+    }
+
+    /// Reads the next replayable frame and its timestamp from the log.
+    fn next_frame(&mut self) -> Option<(u64, CanData)> {
+        loop {
+            let record = match self.reader.as_mut()?.next_record() {
+                Ok(Some(record)) => record,
+                Ok(None) => {
+                    self.reader = None;
+                    return None;
+                }
+                Err(ParseError::Io(_e)) => {
+                    #[cfg(feature = "tracing")]
+                    warn!("Failed to read {}: {}.", self.path.display(), _e);
+                    self.reader = None;
+                    return None;
+                }
+                Err(_e) => {
+                    #[cfg(feature = "tracing")]
+                    warn!("Skipping invalid line in {}: {}.", self.path.display(), _e);
+                    continue;
+                }
+            };
+            let frame = match record.frame {
+                CanAnyFrame::Normal(frame) => CanFrame::Data(frame),
+                CanAnyFrame::Remote(frame) => CanFrame::Remote(frame),
+                CanAnyFrame::Error(frame) => CanFrame::Error(frame),
+                CanAnyFrame::Fd(_) => {
+                    #[cfg(feature = "tracing")]
+                    warn!("Skipping CAN FD frame in {}.", self.path.display());
+                    continue;
+                }
+            };
+            let interface = self
+                .interfaces
+                .get(&record.device)
+                .cloned()
+                .unwrap_or_else(|| CanInterface::named(&record.device));
+
+            return Some((record.t_us, CanData::new(interface, frame)));
+        }
+    }
+}
+
+impl Model for CandumpReplayer {}
+
+impl fmt::Debug for CandumpReplayer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CandumpReplayer")
+            .field("path", &self.path)
+            .field("time_scale", &self.time_scale)
+            .finish_non_exhaustive()
+    }
+}
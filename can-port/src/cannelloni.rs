@@ -0,0 +1,228 @@
+//! cannelloni backend.
+//!
+//! This module contains a [`CanBackend`] implementation tunneling CAN frames
+//! over UDP in the format of
+//! [cannelloni](https://github.com/mguentner/cannelloni), so that the CAN port
+//! can be attached to remote CAN buses.
+//!
+//! The backend is selected by setting the `backend` configuration of the CAN
+//! port to `cannelloni`, in which case the configured interfaces are the
+//! addresses of the remote cannelloni instances, optionally preceded by the
+//! local address to bind to and a slash, e.g. `0.0.0.0:20001/10.0.0.2:20000`.
+//! If no local address is provided, the unspecified address is bound with the
+//! port of the remote address, as cannelloni does by default.
+//!
+//! Each transmitted frame is sent in a separate datagram. CAN FD frames
+//! received from the remote instance are skipped. Filters, timestamps and
+//! loopback settings are not supported and are ignored.
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+
+use mio::event::Source;
+use mio::net::UdpSocket;
+use mio::{Interest, Registry, Token};
+
+use socketcan::{
+    CanErrorFrame, CanFrame, CanTimestamps, EmbeddedFrame, ExtendedId, Frame, Id, StandardId,
+};
+
+use crate::CanBackend;
+
+/// Version of the cannelloni frame format.
+const VERSION: u8 = 2;
+
+/// Operation code of data datagrams.
+const OP_DATA: u8 = 0;
+
+/// Size of the datagram header: version, operation code, sequence number and
+/// frame count.
+const HEADER_LEN: usize = 5;
+
+/// Maximum size of a received datagram.
+const MAX_DATAGRAM_LEN: usize = 65536;
+
+/// Extended identifier flag of the encoded identifiers.
+const EFF_FLAG: u32 = 0x8000_0000;
+
+/// Remote frame flag of the encoded identifiers.
+const RTR_FLAG: u32 = 0x4000_0000;
+
+/// Error frame flag of the encoded identifiers.
+const ERR_FLAG: u32 = 0x2000_0000;
+
+/// CAN FD flag of the encoded lengths.
+const FD_FLAG: u8 = 0x80;
+
+/// CAN interface tunneled to a remote cannelloni instance.
+pub struct CannelloniBackend {
+    /// UDP socket connected to the remote instance.
+    socket: UdpSocket,
+
+    /// Receive buffer.
+    rx_buf: Vec<u8>,
+
+    /// Received frames not yet read.
+    rx_frames: VecDeque<CanFrame>,
+
+    /// Sequence number of the next transmitted datagram.
+    seq_no: u8,
+}
+
+impl CannelloniBackend {
+    /// Binds the local address and connects to the remote cannelloni
+    /// instance.
+    ///
+    /// The address has the form `[LOCAL/]REMOTE`, see the module
+    /// documentation.
+    pub fn open(address: &str) -> Result<Self> {
+        let (local, remote) = match address.split_once('/') {
+            Some((local, remote)) => (Some(resolve(local)?), resolve(remote)?),
+            None => (None, resolve(address)?),
+        };
+        let local = local.unwrap_or_else(|| {
+            let ip = match remote {
+                SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            };
+            SocketAddr::new(ip, remote.port())
+        });
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(remote)?;
+
+        Ok(Self {
+            socket,
+            rx_buf: vec![0; MAX_DATAGRAM_LEN],
+            rx_frames: VecDeque::new(),
+            seq_no: 0,
+        })
+    }
+}
+
+impl CanBackend for CannelloniBackend {
+    fn read_frame(&mut self) -> Result<(CanFrame, CanTimestamps)> {
+        loop {
+            if let Some(frame) = self.rx_frames.pop_front() {
+                return Ok((frame, CanTimestamps::default()));
+            }
+            let len = self.socket.recv(&mut self.rx_buf)?;
+            decode_datagram(&self.rx_buf[..len], &mut self.rx_frames);
+        }
+    }
+
+    /// Sends a frame in a datagram.
+    fn write_frame(&mut self, frame: &CanFrame) -> Result<()> {
+        let mut datagram = vec![VERSION, OP_DATA, self.seq_no];
+        datagram.extend_from_slice(&1u16.to_be_bytes());
+        encode_frame(frame, &mut datagram);
+        self.seq_no = self.seq_no.wrapping_add(1);
+        self.socket.send(&datagram)?;
+
+        Ok(())
+    }
+}
+
+impl Source for CannelloniBackend {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        self.socket.register(registry, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        self.socket.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> Result<()> {
+        self.socket.deregister(registry)
+    }
+}
+
+impl fmt::Debug for CannelloniBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CannelloniBackend")
+            .field("local_addr", &self.socket.local_addr().ok())
+            .field("peer_addr", &self.socket.peer_addr().ok())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Resolves a socket address.
+fn resolve(address: &str) -> Result<SocketAddr> {
+    address.to_socket_addrs()?.next().ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Cannot resolve address {address}."),
+        )
+    })
+}
+
+/// Decodes the frames of a data datagram.
+///
+/// Datagrams with an unsupported version or operation code are ignored, as
+/// well as truncated frames and CAN FD frames.
+fn decode_datagram(datagram: &[u8], frames: &mut VecDeque<CanFrame>) {
+    if datagram.len() < HEADER_LEN || datagram[0] != VERSION || datagram[1] != OP_DATA {
+        return;
+    }
+    let count = u16::from_be_bytes([datagram[3], datagram[4]]);
+    let mut rest = &datagram[HEADER_LEN..];
+    for _ in 0..count {
+        let Some((can_id, rest_after_id)) = rest.split_first_chunk::<4>() else {
+            return;
+        };
+        let can_id = u32::from_be_bytes(*can_id);
+        let Some((&len, rest_after_len)) = rest_after_id.split_first() else {
+            return;
+        };
+        // CAN FD frames carry an additional flags byte.
+        let (is_fd, len, payload) = if len & FD_FLAG != 0 {
+            let Some((_, payload)) = rest_after_len.split_first() else {
+                return;
+            };
+            (true, usize::from(len & !FD_FLAG), payload)
+        } else {
+            (false, usize::from(len), rest_after_len)
+        };
+        // Remote frames carry no data.
+        let data_len = if can_id & RTR_FLAG != 0 { 0 } else { len };
+        if payload.len() < data_len {
+            return;
+        }
+        let (data, next) = payload.split_at(data_len);
+        rest = next;
+        if is_fd {
+            continue;
+        }
+        if let Some(frame) = decode_frame(can_id, len, data) {
+            frames.push_back(frame);
+        }
+    }
+}
+
+/// Decodes a frame from its encoded identifier, length and data.
+fn decode_frame(can_id: u32, len: usize, data: &[u8]) -> Option<CanFrame> {
+    if can_id & ERR_FLAG != 0 {
+        return CanErrorFrame::new_error(can_id, data)
+            .ok()
+            .map(CanFrame::Error);
+    }
+    let id: Id = if can_id & EFF_FLAG != 0 {
+        ExtendedId::new(can_id & 0x1FFF_FFFF)?.into()
+    } else {
+        StandardId::new((can_id & 0x7FF) as u16)?.into()
+    };
+    if can_id & RTR_FLAG != 0 {
+        CanFrame::new_remote(id, len)
+    } else {
+        CanFrame::new(id, data)
+    }
+}
+
+/// Encodes a frame.
+fn encode_frame(frame: &CanFrame, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&frame.id_word().to_be_bytes());
+    buf.push(frame.dlc() as u8);
+    if !frame.is_remote_frame() {
+        buf.extend_from_slice(frame.data());
+    }
+}
@@ -0,0 +1,1300 @@
+//! CANopen node.
+//!
+//! This module contains a model implementing a CANopen node on top of the raw
+//! CAN frames of a [`CanPort`](crate::CanPort):
+//! * NMT slave state machine, with boot-up message and NMT commands,
+//! * heartbeat production and consumption,
+//! * SDO server giving access to the object dictionary, and SDO client to
+//!   access the object dictionaries of other nodes, both with expedited and
+//!   segmented transfers,
+//! * transmit and receive PDOs mapped onto the object dictionary, transmitted
+//!   either on SYNC or when a mapped object is written by the simulation.
+//!
+//! The object dictionary and the PDO mappings are defined by the
+//! configuration. PDO mappings are byte-aligned, and SDO transfers do not time
+//! out.
+//!
+//! The node handles a single CAN interface and should be connected to the
+//! frame output and input of the CAN port. Since the node addresses its
+//! interface by name, the CAN port should be configured with
+//! `interface_names` enabled.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+use schematic::Config;
+
+use socketcan::{CanFrame, EmbeddedFrame, Id, StandardId};
+
+#[cfg(feature = "tracing")]
+use tracing::{info, warn};
+
+use nexosim::model::{Context, InitializedModel, Model};
+use nexosim::ports::Output;
+use nexosim::simulation::ActionKey;
+
+use nexosim_io_utils::duration::ConfigDuration;
+
+use crate::{CanData, CanInterface};
+
+/// NMT command COB-ID.
+const COB_NMT: u16 = 0x000;
+
+/// SYNC COB-ID.
+const COB_SYNC: u16 = 0x080;
+
+/// SDO server to client COB-ID base.
+const COB_SDO_TX: u16 = 0x580;
+
+/// SDO client to server COB-ID base.
+const COB_SDO_RX: u16 = 0x600;
+
+/// NMT error control (heartbeat) COB-ID base.
+const COB_HEARTBEAT: u16 = 0x700;
+
+/// Boot-up state byte.
+const BOOT_UP: u8 = 0x00;
+
+/// SDO abort: toggle bit not alternated.
+pub const SDO_ABORT_TOGGLE: u32 = 0x0503_0000;
+
+/// SDO abort: invalid or unknown command specifier.
+pub const SDO_ABORT_COMMAND: u32 = 0x0504_0001;
+
+/// SDO abort: attempt to write a read-only object.
+pub const SDO_ABORT_READ_ONLY: u32 = 0x0601_0002;
+
+/// SDO abort: object does not exist in the object dictionary.
+pub const SDO_ABORT_NO_OBJECT: u32 = 0x0602_0000;
+
+/// SDO abort: data type length does not match.
+pub const SDO_ABORT_LENGTH: u32 = 0x0607_0010;
+
+/// CANopen node model instance config.
+#[derive(Config, Debug)]
+pub struct CanopenNodeConfig {
+    /// CAN interface name.
+    #[setting(default = "vcan0")]
+    pub interface: String,
+
+    /// Node ID, from 1 to 127.
+    #[setting(default = 1)]
+    pub node_id: u8,
+
+    /// The node enters the operational state after boot-up without waiting
+    /// for an NMT start command.
+    pub auto_start: bool,
+
+    /// Heartbeat producer period.
+    ///
+    /// If no value is provided, no heartbeat is produced.
+    pub heartbeat_period: Option<ConfigDuration>,
+
+    /// Heartbeat consumers.
+    #[setting(nested)]
+    pub heartbeat_consumers: Vec<HeartbeatConsumerConfig>,
+
+    /// Object dictionary entries.
+    #[setting(nested)]
+    pub objects: Vec<ObjectConfig>,
+
+    /// Transmit PDOs.
+    #[setting(nested)]
+    pub tpdos: Vec<PdoConfig>,
+
+    /// Receive PDOs.
+    #[setting(nested)]
+    pub rpdos: Vec<PdoConfig>,
+}
+
+/// Heartbeat consumer configuration.
+#[derive(Config, Debug)]
+pub struct HeartbeatConsumerConfig {
+    /// Monitored node ID.
+    pub node_id: u8,
+
+    /// Heartbeat timeout.
+    pub timeout: ConfigDuration,
+}
+
+/// Object dictionary entry configuration.
+#[derive(Config, Debug)]
+pub struct ObjectConfig {
+    /// Object index.
+    pub index: u16,
+
+    /// Object sub-index.
+    pub subindex: u8,
+
+    /// Initial value, in little-endian byte order.
+    ///
+    /// Objects with an empty initial value accept values of any length,
+    /// other objects only accept values of the same length.
+    pub data: Vec<u8>,
+
+    /// Object cannot be written through SDO.
+    pub read_only: bool,
+}
+
+/// PDO configuration.
+#[derive(Config, Debug)]
+pub struct PdoConfig {
+    /// COB-ID.
+    pub cob_id: u16,
+
+    /// The PDO is transmitted on SYNC rather than when a mapped object is
+    /// written.
+    ///
+    /// Ignored for receive PDOs.
+    pub sync: bool,
+
+    /// Mapped objects, in PDO data order.
+    #[setting(nested)]
+    pub mappings: Vec<PdoMappingConfig>,
+}
+
+/// PDO mapping configuration.
+#[derive(Config, Debug)]
+pub struct PdoMappingConfig {
+    /// Object index.
+    pub index: u16,
+
+    /// Object sub-index.
+    pub subindex: u8,
+
+    /// Mapped length, in bytes.
+    pub size: u8,
+}
+
+/// NMT state.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NmtState {
+    /// Initializing, until the boot-up message is sent.
+    Initializing,
+    /// Pre-operational: SDO allowed, PDO disabled.
+    PreOperational,
+    /// Operational: SDO and PDO allowed.
+    Operational,
+    /// Stopped: only NMT and heartbeat.
+    Stopped,
+}
+
+impl NmtState {
+    /// Returns the heartbeat state byte.
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Initializing => BOOT_UP,
+            Self::Stopped => 0x04,
+            Self::Operational => 0x05,
+            Self::PreOperational => 0x7F,
+        }
+    }
+
+    /// Decodes a heartbeat state byte.
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte & 0x7F {
+            BOOT_UP => Some(Self::Initializing),
+            0x04 => Some(Self::Stopped),
+            0x05 => Some(Self::Operational),
+            0x7F => Some(Self::PreOperational),
+            _ => None,
+        }
+    }
+}
+
+/// NMT command.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NmtCommand {
+    /// Enter the operational state.
+    Start,
+    /// Enter the stopped state.
+    Stop,
+    /// Enter the pre-operational state.
+    EnterPreOperational,
+    /// Reset the application and the communication.
+    ResetNode,
+    /// Reset the communication.
+    ResetCommunication,
+}
+
+impl NmtCommand {
+    /// Returns the command specifier.
+    fn specifier(self) -> u8 {
+        match self {
+            Self::Start => 0x01,
+            Self::Stop => 0x02,
+            Self::EnterPreOperational => 0x80,
+            Self::ResetNode => 0x81,
+            Self::ResetCommunication => 0x82,
+        }
+    }
+
+    /// Decodes a command specifier.
+    fn from_specifier(specifier: u8) -> Option<Self> {
+        match specifier {
+            0x01 => Some(Self::Start),
+            0x02 => Some(Self::Stop),
+            0x80 => Some(Self::EnterPreOperational),
+            0x81 => Some(Self::ResetNode),
+            0x82 => Some(Self::ResetCommunication),
+            _ => None,
+        }
+    }
+}
+
+/// NMT command sent to other nodes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NmtRequest {
+    /// Addressed node ID, or 0 for all nodes.
+    pub node_id: u8,
+
+    /// NMT command.
+    pub command: NmtCommand,
+}
+
+/// State of a monitored node.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RemoteNodeState {
+    /// Node ID.
+    pub node_id: u8,
+
+    /// Reported NMT state, or `None` if the heartbeat timed out.
+    pub state: Option<NmtState>,
+}
+
+/// Object dictionary entry.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ObjectEntry {
+    /// Object index.
+    pub index: u16,
+
+    /// Object sub-index.
+    pub subindex: u8,
+
+    /// Value, in little-endian byte order.
+    pub data: Vec<u8>,
+}
+
+/// SDO client request.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SdoRequest {
+    /// Server node ID.
+    pub node_id: u8,
+
+    /// Object index.
+    pub index: u16,
+
+    /// Object sub-index.
+    pub subindex: u8,
+
+    /// Value to be written, or `None` to read the object.
+    pub data: Option<Vec<u8>>,
+}
+
+/// SDO client response.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SdoResponse {
+    /// Server node ID.
+    pub node_id: u8,
+
+    /// Object index.
+    pub index: u16,
+
+    /// Object sub-index.
+    pub subindex: u8,
+
+    /// Read value (empty for writes), or SDO abort code.
+    pub result: Result<Vec<u8>, u32>,
+}
+
+/// Result of an SDO client request: read value (empty for writes) or SDO
+/// abort code.
+type SdoResult = Result<Vec<u8>, u32>;
+
+/// Object of the object dictionary.
+#[derive(Clone, Debug)]
+struct Object {
+    /// Value.
+    data: Vec<u8>,
+
+    /// Object cannot be written through SDO.
+    read_only: bool,
+}
+
+/// SDO transfer in progress.
+#[derive(Debug)]
+struct SdoTransfer {
+    /// Object index.
+    index: u16,
+
+    /// Object sub-index.
+    subindex: u8,
+
+    /// Transferred data.
+    data: Vec<u8>,
+
+    /// Offset of the next segment, for uploads.
+    offset: usize,
+
+    /// Expected toggle bit.
+    toggle: bool,
+}
+
+impl SdoTransfer {
+    /// Creates a new transfer.
+    fn new(index: u16, subindex: u8, data: Vec<u8>) -> Self {
+        Self {
+            index,
+            subindex,
+            data,
+            offset: 0,
+            toggle: false,
+        }
+    }
+}
+
+/// Encodes an SDO frame with multiplexer.
+fn sdo_frame(command: u8, index: u16, subindex: u8, payload: &[u8]) -> [u8; 8] {
+    let mut data = [0; 8];
+    data[0] = command;
+    data[1..3].copy_from_slice(&index.to_le_bytes());
+    data[3] = subindex;
+    data[4..4 + payload.len()].copy_from_slice(payload);
+
+    data
+}
+
+/// Encodes a segment frame.
+fn segment_frame(toggle: bool, segment: &[u8], is_last: bool) -> [u8; 8] {
+    let mut data = [0; 8];
+    data[0] = ((toggle as u8) << 4) | (((7 - segment.len()) as u8) << 1) | is_last as u8;
+    data[1..1 + segment.len()].copy_from_slice(segment);
+
+    data
+}
+
+/// Encodes the frame initiating an SDO client request.
+fn sdo_initiate_frame(request: &SdoRequest) -> [u8; 8] {
+    let (index, subindex) = (request.index, request.subindex);
+    match &request.data {
+        None => sdo_frame(0x40, index, subindex, &[]),
+        Some(data) if !data.is_empty() && data.len() <= 4 => {
+            let command = 0x23 | (((4 - data.len()) as u8) << 2);
+            sdo_frame(command, index, subindex, data)
+        }
+        Some(data) => {
+            let size = (data.len() as u32).to_le_bytes();
+            sdo_frame(0x21, index, subindex, &size)
+        }
+    }
+}
+
+/// Encodes an SDO server abort frame.
+fn sdo_abort_frame(index: u16, subindex: u8, code: u32) -> [u8; 8] {
+    #[cfg(feature = "tracing")]
+    warn!(
+        "Aborting CANopen SDO transfer of {:04X}:{:02X} with code {:08X}.",
+        index, subindex, code
+    );
+
+    sdo_frame(0x80, index, subindex, &code.to_le_bytes())
+}
+
+/// CANopen node model.
+///
+/// This model converts raw CAN frames to CANopen services and maintains the
+/// object dictionary of the node.
+pub struct CanopenNode {
+    /// CAN frames to be transmitted -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// NMT state of the node -- output port.
+    pub nmt_state_out: Output<NmtState>,
+
+    /// State of the monitored nodes -- output port.
+    pub remote_state_out: Output<RemoteNodeState>,
+
+    /// Object written through SDO or received PDO -- output port.
+    pub object_out: Output<ObjectEntry>,
+
+    /// SDO client response -- output port.
+    pub sdo_response_out: Output<SdoResponse>,
+
+    /// Model instance configuration.
+    config: CanopenNodeConfig,
+
+    /// CAN interface.
+    interface: CanInterface,
+
+    /// NMT state.
+    state: NmtState,
+
+    /// Object dictionary.
+    objects: BTreeMap<(u16, u8), Object>,
+
+    /// SDO server transfer in progress.
+    sdo_server: Option<SdoTransfer>,
+
+    /// SDO client request and transfer in progress.
+    sdo_client: Option<(SdoRequest, SdoTransfer)>,
+
+    /// Pending SDO client requests.
+    sdo_queue: VecDeque<SdoRequest>,
+
+    /// Heartbeat timeout keys, by monitored node ID.
+    heartbeat_timeouts: HashMap<u8, ActionKey>,
+}
+
+impl CanopenNode {
+    /// Creates a new CANopen node.
+    pub fn new(config: CanopenNodeConfig) -> Self {
+        let interface = CanInterface::named(&config.interface);
+        let objects = Self::initial_objects(&config);
+
+        Self {
+            frame_out: Output::new(),
+            nmt_state_out: Output::new(),
+            remote_state_out: Output::new(),
+            object_out: Output::new(),
+            sdo_response_out: Output::new(),
+            config,
+            interface,
+            state: NmtState::Initializing,
+            objects,
+            sdo_server: None,
+            sdo_client: None,
+            sdo_queue: VecDeque::new(),
+            heartbeat_timeouts: HashMap::new(),
+        }
+    }
+
+    /// Returns the object dictionary as configured.
+    fn initial_objects(config: &CanopenNodeConfig) -> BTreeMap<(u16, u8), Object> {
+        config
+            .objects
+            .iter()
+            .map(|object| {
+                (
+                    (object.index, object.subindex),
+                    Object {
+                        data: object.data.clone(),
+                        read_only: object.read_only,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Received CAN frame -- input port.
+    pub async fn frame_in(&mut self, data: CanData, cx: &mut Context<Self>) {
+        if data.interface != self.interface {
+            return;
+        }
+        let (CanFrame::Data(frame), Id::Standard(id)) = (data.frame, data.frame.id()) else {
+            return;
+        };
+        let cob_id = id.as_raw();
+        let payload = frame.data();
+        let node_id = self.config.node_id as u16;
+
+        match cob_id {
+            COB_NMT if payload.len() == 2 => {
+                if payload[1] == 0 || payload[1] == self.config.node_id {
+                    if let Some(command) = NmtCommand::from_specifier(payload[0]) {
+                        self.on_nmt_command(command).await;
+                    }
+                }
+            }
+            COB_SYNC => {
+                if self.state == NmtState::Operational {
+                    for i in 0..self.config.tpdos.len() {
+                        if self.config.tpdos[i].sync {
+                            self.send_tpdo(i).await;
+                        }
+                    }
+                }
+            }
+            _ if cob_id == COB_SDO_RX + node_id && payload.len() == 8 => {
+                if matches!(self.state, NmtState::PreOperational | NmtState::Operational) {
+                    self.on_sdo_server(payload).await;
+                }
+            }
+            _ if self
+                .sdo_client
+                .as_ref()
+                .is_some_and(|(request, _)| cob_id == COB_SDO_TX + request.node_id as u16)
+                && payload.len() == 8 =>
+            {
+                self.on_sdo_client(payload).await;
+            }
+            0x701..=0x77F if payload.len() == 1 => {
+                self.on_heartbeat((cob_id - COB_HEARTBEAT) as u8, payload[0], cx)
+                    .await;
+            }
+            _ => {
+                if self.state == NmtState::Operational {
+                    if let Some(i) = self
+                        .config
+                        .rpdos
+                        .iter()
+                        .position(|rpdo| rpdo.cob_id == cob_id)
+                    {
+                        self.on_rpdo(i, payload).await;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes an object of the object dictionary -- input port.
+    ///
+    /// Event-driven transmit PDOs mapping the object are transmitted if the
+    /// node is operational.
+    pub async fn object_in(&mut self, entry: ObjectEntry) {
+        let key = (entry.index, entry.subindex);
+        let Some(object) = self.objects.get_mut(&key) else {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Ignoring write of the unknown CANopen object {:04X}:{:02X}.",
+                entry.index, entry.subindex
+            );
+            return;
+        };
+        object.data = entry.data;
+
+        if self.state == NmtState::Operational {
+            for i in 0..self.config.tpdos.len() {
+                let tpdo = &self.config.tpdos[i];
+                if !tpdo.sync
+                    && tpdo
+                        .mappings
+                        .iter()
+                        .any(|mapping| (mapping.index, mapping.subindex) == key)
+                {
+                    self.send_tpdo(i).await;
+                }
+            }
+        }
+    }
+
+    /// Sends an NMT command to other nodes -- input port.
+    pub async fn nmt_in(&mut self, request: NmtRequest) {
+        self.send(COB_NMT, &[request.command.specifier(), request.node_id])
+            .await;
+    }
+
+    /// Reads or writes an object of another node -- input port.
+    ///
+    /// Requests are processed one at a time, in order.
+    pub async fn sdo_request_in(&mut self, request: SdoRequest) {
+        self.sdo_queue.push_back(request);
+        if self.sdo_client.is_none() {
+            self.start_sdo_request().await;
+        }
+    }
+
+    /// Sends the heartbeat.
+    async fn send_heartbeat(&mut self) {
+        let cob_id = COB_HEARTBEAT + self.config.node_id as u16;
+        self.send(cob_id, &[self.state.to_byte()]).await;
+    }
+
+    /// Reports a heartbeat timeout.
+    async fn heartbeat_timeout(&mut self, node_id: u8) {
+        self.heartbeat_timeouts.remove(&node_id);
+        #[cfg(feature = "tracing")]
+        warn!("CANopen node {} heartbeat timed out.", node_id);
+        self.remote_state_out
+            .send(RemoteNodeState {
+                node_id,
+                state: None,
+            })
+            .await;
+    }
+
+    /// Handles a received heartbeat.
+    async fn on_heartbeat(&mut self, node_id: u8, byte: u8, cx: &mut Context<Self>) {
+        let Some(consumer) = self
+            .config
+            .heartbeat_consumers
+            .iter()
+            .find(|consumer| consumer.node_id == node_id)
+        else {
+            return;
+        };
+        let timeout = Duration::from(consumer.timeout);
+        if let Some(key) = self.heartbeat_timeouts.remove(&node_id) {
+            key.cancel();
+        }
+        let key = cx
+            .schedule_keyed_event(timeout, Self::heartbeat_timeout, node_id)
+            .unwrap();
+        self.heartbeat_timeouts.insert(node_id, key);
+
+        self.remote_state_out
+            .send(RemoteNodeState {
+                node_id,
+                state: NmtState::from_byte(byte),
+            })
+            .await;
+    }
+
+    /// Handles an NMT command addressed to the node.
+    async fn on_nmt_command(&mut self, command: NmtCommand) {
+        match command {
+            NmtCommand::Start => self.set_state(NmtState::Operational).await,
+            NmtCommand::Stop => self.set_state(NmtState::Stopped).await,
+            NmtCommand::EnterPreOperational => self.set_state(NmtState::PreOperational).await,
+            NmtCommand::ResetNode => {
+                self.objects = Self::initial_objects(&self.config);
+                self.boot_up().await;
+            }
+            NmtCommand::ResetCommunication => self.boot_up().await,
+        }
+    }
+
+    /// Sends the boot-up message and enters the pre-operational state, or
+    /// the operational state if configured.
+    async fn boot_up(&mut self) {
+        self.state = NmtState::Initializing;
+        self.sdo_server = None;
+        self.send_heartbeat().await;
+        if self.config.auto_start {
+            self.set_state(NmtState::Operational).await;
+        } else {
+            self.set_state(NmtState::PreOperational).await;
+        }
+    }
+
+    /// Changes the NMT state.
+    async fn set_state(&mut self, state: NmtState) {
+        if state == self.state {
+            return;
+        }
+        #[cfg(feature = "tracing")]
+        info!(
+            "CANopen node {} entering state {:?}.",
+            self.config.node_id, state
+        );
+        self.state = state;
+        self.nmt_state_out.send(state).await;
+    }
+
+    /// Sends a transmit PDO.
+    async fn send_tpdo(&mut self, i: usize) {
+        let tpdo = &self.config.tpdos[i];
+        let mut data = Vec::with_capacity(8);
+        for mapping in &tpdo.mappings {
+            let value = self
+                .objects
+                .get(&(mapping.index, mapping.subindex))
+                .map(|object| object.data.as_slice())
+                .unwrap_or_default();
+            let size = mapping.size as usize;
+            let start = data.len();
+            data.extend(value.iter().take(size));
+            data.resize(start + size, 0);
+        }
+        data.truncate(8);
+
+        let cob_id = tpdo.cob_id;
+        self.send(cob_id, &data).await;
+    }
+
+    /// Handles a received PDO.
+    async fn on_rpdo(&mut self, i: usize, payload: &[u8]) {
+        let mut offset = 0;
+        let mut entries = Vec::new();
+        for mapping in &self.config.rpdos[i].mappings {
+            let end = offset + mapping.size as usize;
+            let Some(value) = payload.get(offset..end) else {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    "Ignoring short CANopen PDO {:03X}.",
+                    self.config.rpdos[i].cob_id
+                );
+                return;
+            };
+            entries.push(ObjectEntry {
+                index: mapping.index,
+                subindex: mapping.subindex,
+                data: value.to_vec(),
+            });
+            offset = end;
+        }
+
+        for entry in entries {
+            if let Some(object) = self.objects.get_mut(&(entry.index, entry.subindex)) {
+                object.data.clone_from(&entry.data);
+            }
+            self.object_out.send(entry).await;
+        }
+    }
+
+    /// Handles an SDO request to the server.
+    async fn on_sdo_server(&mut self, payload: &[u8]) {
+        let (response, entry) = self.serve_sdo(payload);
+        if let Some(entry) = entry {
+            self.object_out.send(entry).await;
+        }
+        if let Some(response) = response {
+            let cob_id = COB_SDO_TX + self.config.node_id as u16;
+            self.send(cob_id, &response).await;
+        }
+    }
+
+    /// Processes an SDO request to the server, returning the response, if
+    /// any, and the object written, if any.
+    fn serve_sdo(&mut self, payload: &[u8]) -> (Option<[u8; 8]>, Option<ObjectEntry>) {
+        let command = payload[0];
+        let index = u16::from_le_bytes([payload[1], payload[2]]);
+        let subindex = payload[3];
+
+        match command >> 5 {
+            // Initiate download.
+            1 => {
+                self.sdo_server = None;
+                let object = match self.objects.get(&(index, subindex)) {
+                    None => Err(SDO_ABORT_NO_OBJECT),
+                    Some(object) if object.read_only => Err(SDO_ABORT_READ_ONLY),
+                    Some(object) => Ok(object),
+                };
+                let object = match object {
+                    Ok(object) => object,
+                    Err(code) => return (Some(sdo_abort_frame(index, subindex, code)), None),
+                };
+                let is_expedited = command & 0x02 != 0;
+                let is_size_indicated = command & 0x01 != 0;
+                let mut written = None;
+                if is_expedited {
+                    let len = if is_size_indicated {
+                        4 - ((command >> 2) & 0x03) as usize
+                    } else {
+                        object.data.len().clamp(1, 4)
+                    };
+                    let entry = ObjectEntry {
+                        index,
+                        subindex,
+                        data: payload[4..4 + len].to_vec(),
+                    };
+                    if let Err(code) = self.write_object(&entry) {
+                        return (Some(sdo_abort_frame(index, subindex, code)), None);
+                    }
+                    written = Some(entry);
+                } else {
+                    self.sdo_server = Some(SdoTransfer::new(index, subindex, Vec::new()));
+                }
+
+                (Some(sdo_frame(0x60, index, subindex, &[])), written)
+            }
+            // Download segment.
+            0 => {
+                let Some(transfer) = self.sdo_server.as_mut() else {
+                    return (Some(sdo_abort_frame(0, 0, SDO_ABORT_COMMAND)), None);
+                };
+                let toggle = command & 0x10 != 0;
+                if toggle != transfer.toggle {
+                    let (index, subindex) = (transfer.index, transfer.subindex);
+                    self.sdo_server = None;
+                    return (
+                        Some(sdo_abort_frame(index, subindex, SDO_ABORT_TOGGLE)),
+                        None,
+                    );
+                }
+                let len = 7 - ((command >> 1) & 0x07) as usize;
+                transfer.data.extend_from_slice(&payload[1..1 + len]);
+                transfer.toggle = !toggle;
+                let mut written = None;
+                if command & 0x01 != 0 {
+                    let transfer = self.sdo_server.take().unwrap();
+                    let (index, subindex) = (transfer.index, transfer.subindex);
+                    let entry = ObjectEntry {
+                        index,
+                        subindex,
+                        data: transfer.data,
+                    };
+                    if let Err(code) = self.write_object(&entry) {
+                        return (Some(sdo_abort_frame(index, subindex, code)), None);
+                    }
+                    written = Some(entry);
+                }
+
+                (
+                    Some([0x20 | ((toggle as u8) << 4), 0, 0, 0, 0, 0, 0, 0]),
+                    written,
+                )
+            }
+            // Initiate upload.
+            2 => {
+                self.sdo_server = None;
+                let Some(object) = self.objects.get(&(index, subindex)) else {
+                    return (
+                        Some(sdo_abort_frame(index, subindex, SDO_ABORT_NO_OBJECT)),
+                        None,
+                    );
+                };
+                let data = object.data.clone();
+                if data.len() <= 4 {
+                    let command = 0x43 | (((4 - data.len()) as u8) << 2);
+
+                    (Some(sdo_frame(command, index, subindex, &data)), None)
+                } else {
+                    let size = (data.len() as u32).to_le_bytes();
+                    self.sdo_server = Some(SdoTransfer::new(index, subindex, data));
+
+                    (Some(sdo_frame(0x41, index, subindex, &size)), None)
+                }
+            }
+            // Upload segment.
+            3 => {
+                let Some(transfer) = self.sdo_server.as_mut() else {
+                    return (Some(sdo_abort_frame(0, 0, SDO_ABORT_COMMAND)), None);
+                };
+                let toggle = command & 0x10 != 0;
+                if toggle != transfer.toggle {
+                    let (index, subindex) = (transfer.index, transfer.subindex);
+                    self.sdo_server = None;
+                    return (
+                        Some(sdo_abort_frame(index, subindex, SDO_ABORT_TOGGLE)),
+                        None,
+                    );
+                }
+                let end = (transfer.offset + 7).min(transfer.data.len());
+                let is_last = end == transfer.data.len();
+                let frame = segment_frame(toggle, &transfer.data[transfer.offset..end], is_last);
+                transfer.offset = end;
+                transfer.toggle = !toggle;
+                if is_last {
+                    self.sdo_server = None;
+                }
+
+                (Some(frame), None)
+            }
+            // Abort.
+            4 => {
+                self.sdo_server = None;
+
+                (None, None)
+            }
+            _ => (
+                Some(sdo_abort_frame(index, subindex, SDO_ABORT_COMMAND)),
+                None,
+            ),
+        }
+    }
+
+    /// Writes an object received through SDO.
+    fn write_object(&mut self, entry: &ObjectEntry) -> Result<(), u32> {
+        let object = self
+            .objects
+            .get_mut(&(entry.index, entry.subindex))
+            .ok_or(SDO_ABORT_NO_OBJECT)?;
+        if !object.data.is_empty() && object.data.len() != entry.data.len() {
+            return Err(SDO_ABORT_LENGTH);
+        }
+        object.data.clone_from(&entry.data);
+
+        Ok(())
+    }
+
+    /// Starts the next pending SDO client request.
+    async fn start_sdo_request(&mut self) {
+        let Some(request) = self.sdo_queue.pop_front() else {
+            return;
+        };
+        let frame = sdo_initiate_frame(&request);
+        let data = request.data.clone().unwrap_or_default();
+        let node_id = request.node_id;
+        let transfer = SdoTransfer::new(request.index, request.subindex, data);
+        self.sdo_client = Some((request, transfer));
+        self.send(COB_SDO_RX + node_id as u16, &frame).await;
+    }
+
+    /// Handles an SDO server response to the client.
+    async fn on_sdo_client(&mut self, payload: &[u8]) {
+        let node_id = self.sdo_client.as_ref().unwrap().0.node_id;
+        let (frame, result) = self.sdo_client_step(payload);
+        if let Some(frame) = frame {
+            self.send(COB_SDO_RX + node_id as u16, &frame).await;
+        }
+        if let Some(result) = result {
+            self.finish_sdo_request(result).await;
+        }
+    }
+
+    /// Processes an SDO server response to the client, returning the next
+    /// request, if any, and the result of the transfer once completed.
+    fn sdo_client_step(&mut self, payload: &[u8]) -> (Option<[u8; 8]>, Option<SdoResult>) {
+        let (request, transfer) = self.sdo_client.as_mut().unwrap();
+        let command = payload[0];
+        let is_download = request.data.is_some();
+
+        match (command >> 5, is_download) {
+            // Abort.
+            (4, _) => {
+                let code = u32::from_le_bytes(payload[4..8].try_into().unwrap());
+
+                (None, Some(Err(code)))
+            }
+            // Initiate download response or download segment response.
+            (3, true) | (1, true) => {
+                if command >> 5 == 1 && (command & 0x10 != 0) != transfer.toggle {
+                    return (None, Some(Err(SDO_ABORT_TOGGLE)));
+                }
+                if command >> 5 == 1 {
+                    transfer.toggle = !transfer.toggle;
+                }
+                let is_expedited = transfer.data.len() <= 4;
+                if (command >> 5 == 3 && is_expedited) || transfer.offset == transfer.data.len() {
+                    return (None, Some(Ok(Vec::new())));
+                }
+                let end = (transfer.offset + 7).min(transfer.data.len());
+                let is_last = end == transfer.data.len();
+                let frame = segment_frame(
+                    transfer.toggle,
+                    &transfer.data[transfer.offset..end],
+                    is_last,
+                );
+                transfer.offset = end;
+
+                (Some(frame), None)
+            }
+            // Initiate upload response.
+            (2, false) => {
+                if command & 0x02 != 0 {
+                    let len = if command & 0x01 != 0 {
+                        4 - ((command >> 2) & 0x03) as usize
+                    } else {
+                        4
+                    };
+                    return (None, Some(Ok(payload[4..4 + len].to_vec())));
+                }
+
+                (Some([0x60, 0, 0, 0, 0, 0, 0, 0]), None)
+            }
+            // Upload segment response.
+            (0, false) => {
+                if (command & 0x10 != 0) != transfer.toggle {
+                    return (None, Some(Err(SDO_ABORT_TOGGLE)));
+                }
+                let len = 7 - ((command >> 1) & 0x07) as usize;
+                transfer.data.extend_from_slice(&payload[1..1 + len]);
+                transfer.toggle = !transfer.toggle;
+                if command & 0x01 != 0 {
+                    let data = std::mem::take(&mut transfer.data);
+                    return (None, Some(Ok(data)));
+                }
+
+                (
+                    Some([0x60 | ((transfer.toggle as u8) << 4), 0, 0, 0, 0, 0, 0, 0]),
+                    None,
+                )
+            }
+            _ => {
+                let (index, subindex) = (transfer.index, transfer.subindex);
+                let abort = sdo_frame(0x80, index, subindex, &SDO_ABORT_COMMAND.to_le_bytes());
+
+                (Some(abort), Some(Err(SDO_ABORT_COMMAND)))
+            }
+        }
+    }
+
+    /// Reports the result of the SDO client request in progress and starts
+    /// the next one.
+    async fn finish_sdo_request(&mut self, result: SdoResult) {
+        let (request, _) = self.sdo_client.take().unwrap();
+        self.sdo_response_out
+            .send(SdoResponse {
+                node_id: request.node_id,
+                index: request.index,
+                subindex: request.subindex,
+                result,
+            })
+            .await;
+        self.start_sdo_request().await;
+    }
+
+    /// Sends a CAN frame.
+    async fn send(&mut self, cob_id: u16, data: &[u8]) {
+        let id = Id::Standard(StandardId::new(cob_id).unwrap());
+        let frame = CanFrame::new(id, data).unwrap();
+        self.frame_out
+            .send(CanData::new(self.interface, frame))
+            .await;
+    }
+}
+
+impl Model for CanopenNode {
+    async fn init(mut self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        self.boot_up().await;
+
+        if let Some(period) = self.config.heartbeat_period {
+            let period = Duration::from(period);
+            context
+                .schedule_periodic_event(period, period, Self::send_heartbeat, ())
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for CanopenNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CanopenNode")
+            .field("node_id", &self.config.node_id)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODE_ID: u8 = 5;
+
+    fn object(index: u16, subindex: u8, data: &[u8], read_only: bool) -> ObjectConfig {
+        ObjectConfig {
+            index,
+            subindex,
+            data: data.to_vec(),
+            read_only,
+        }
+    }
+
+    fn node(node_id: u8, objects: Vec<ObjectConfig>) -> CanopenNode {
+        CanopenNode::new(CanopenNodeConfig {
+            interface: "can0".into(),
+            node_id,
+            auto_start: false,
+            heartbeat_period: None,
+            heartbeat_consumers: Vec::new(),
+            objects,
+            tpdos: Vec::new(),
+            rpdos: Vec::new(),
+        })
+    }
+
+    fn server() -> CanopenNode {
+        node(
+            NODE_ID,
+            vec![
+                object(0x1000, 0, &[0x91, 0x01, 0x0F, 0x00], true),
+                object(0x2000, 1, &[0; 4], false),
+                object(0x2001, 0, &[], false),
+                object(0x2002, 0, &[0; 2], false),
+            ],
+        )
+    }
+
+    /// Runs an SDO client request against a server, returning the result and
+    /// the objects written on the server.
+    fn transfer(server: &mut CanopenNode, request: SdoRequest) -> (SdoResult, Vec<ObjectEntry>) {
+        let mut client = node(1, Vec::new());
+        let mut frame = sdo_initiate_frame(&request);
+        let transfer = SdoTransfer::new(
+            request.index,
+            request.subindex,
+            request.data.clone().unwrap_or_default(),
+        );
+        client.sdo_client = Some((request, transfer));
+
+        let mut written = Vec::new();
+        for _ in 0..100 {
+            let (response, entry) = server.serve_sdo(&frame);
+            written.extend(entry);
+            let (next, result) = client.sdo_client_step(&response.unwrap());
+            if let Some(result) = result {
+                return (result, written);
+            }
+            frame = next.unwrap();
+        }
+
+        panic!("the SDO transfer should complete");
+    }
+
+    fn read(index: u16, subindex: u8) -> SdoRequest {
+        SdoRequest {
+            node_id: NODE_ID,
+            index,
+            subindex,
+            data: None,
+        }
+    }
+
+    fn write(index: u16, subindex: u8, data: &[u8]) -> SdoRequest {
+        SdoRequest {
+            node_id: NODE_ID,
+            index,
+            subindex,
+            data: Some(data.to_vec()),
+        }
+    }
+
+    #[test]
+    fn nmt_encoding() {
+        for state in [
+            NmtState::Initializing,
+            NmtState::PreOperational,
+            NmtState::Operational,
+            NmtState::Stopped,
+        ] {
+            assert_eq!(NmtState::from_byte(state.to_byte()), Some(state));
+        }
+        // The toggle bit of legacy node guarding is ignored.
+        assert_eq!(NmtState::from_byte(0x85), Some(NmtState::Operational));
+        assert_eq!(NmtState::from_byte(0x01), None);
+
+        for command in [
+            NmtCommand::Start,
+            NmtCommand::Stop,
+            NmtCommand::EnterPreOperational,
+            NmtCommand::ResetNode,
+            NmtCommand::ResetCommunication,
+        ] {
+            assert_eq!(
+                NmtCommand::from_specifier(command.specifier()),
+                Some(command)
+            );
+        }
+        assert_eq!(NmtCommand::from_specifier(0x03), None);
+    }
+
+    #[test]
+    fn expedited_download() {
+        let mut server = server();
+        let request = write(0x2000, 1, &[0x78, 0x56, 0x34, 0x12]);
+        assert_eq!(
+            sdo_initiate_frame(&request),
+            [0x23, 0x00, 0x20, 0x01, 0x78, 0x56, 0x34, 0x12]
+        );
+
+        let (response, entry) = server.serve_sdo(&sdo_initiate_frame(&request));
+        assert_eq!(response, Some([0x60, 0x00, 0x20, 0x01, 0, 0, 0, 0]));
+        assert_eq!(
+            entry,
+            Some(ObjectEntry {
+                index: 0x2000,
+                subindex: 1,
+                data: vec![0x78, 0x56, 0x34, 0x12],
+            })
+        );
+
+        let request = write(0x2002, 0, &[0xCD, 0xAB]);
+        assert_eq!(sdo_initiate_frame(&request)[0], 0x2B);
+        assert_eq!(transfer(&mut server, request).0, Ok(Vec::new()));
+        assert_eq!(
+            transfer(&mut server, read(0x2002, 0)).0,
+            Ok(vec![0xCD, 0xAB])
+        );
+    }
+
+    #[test]
+    fn expedited_upload() {
+        let mut server = server();
+        let request = read(0x1000, 0);
+        assert_eq!(
+            sdo_initiate_frame(&request),
+            [0x40, 0x00, 0x10, 0x00, 0, 0, 0, 0]
+        );
+
+        let (response, _) = server.serve_sdo(&sdo_initiate_frame(&request));
+        assert_eq!(
+            response,
+            Some([0x43, 0x00, 0x10, 0x00, 0x91, 0x01, 0x0F, 0x00])
+        );
+        assert_eq!(
+            transfer(&mut server, request).0,
+            Ok(vec![0x91, 0x01, 0x0F, 0x00])
+        );
+    }
+
+    #[test]
+    fn segmented_transfer() {
+        let mut server = server();
+        let data: Vec<u8> = (0..20).collect();
+
+        let request = write(0x2001, 0, &data);
+        assert_eq!(
+            sdo_initiate_frame(&request),
+            [0x21, 0x01, 0x20, 0x00, 20, 0, 0, 0]
+        );
+        let (result, written) = transfer(&mut server, request);
+        assert_eq!(result, Ok(Vec::new()));
+        assert_eq!(
+            written,
+            [ObjectEntry {
+                index: 0x2001,
+                subindex: 0,
+                data: data.clone(),
+            }]
+        );
+
+        let (response, _) = server.serve_sdo(&sdo_initiate_frame(&read(0x2001, 0)));
+        assert_eq!(response, Some([0x41, 0x01, 0x20, 0x00, 20, 0, 0, 0]));
+
+        // Segments of 7, 7 and 6 bytes, with alternating toggle bits.
+        let (response, _) = server.serve_sdo(&[0x60, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(response, Some([0x00, 0, 1, 2, 3, 4, 5, 6]));
+        let (response, _) = server.serve_sdo(&[0x70, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(response, Some([0x10, 7, 8, 9, 10, 11, 12, 13]));
+        let (response, _) = server.serve_sdo(&[0x60, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(response, Some([0x03, 14, 15, 16, 17, 18, 19, 0]));
+        assert!(server.sdo_server.is_none());
+
+        assert_eq!(transfer(&mut server, read(0x2001, 0)).0, Ok(data));
+    }
+
+    #[test]
+    fn toggle_error() {
+        let mut server = server();
+        server.serve_sdo(&sdo_initiate_frame(&write(0x2001, 0, &[0; 10])));
+
+        // The first segment should have a cleared toggle bit.
+        let (response, entry) = server.serve_sdo(&[0x10, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            response,
+            Some(sdo_frame(0x80, 0x2001, 0, &SDO_ABORT_TOGGLE.to_le_bytes()))
+        );
+        assert_eq!(entry, None);
+        assert!(server.sdo_server.is_none());
+
+        // Segments without a transfer in progress are rejected.
+        let (response, _) = server.serve_sdo(&[0x00, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            response,
+            Some(sdo_frame(0x80, 0, 0, &SDO_ABORT_COMMAND.to_le_bytes()))
+        );
+    }
+
+    #[test]
+    fn abort_codes() {
+        let mut server = server();
+        assert_eq!(
+            transfer(&mut server, read(0x3000, 0)).0,
+            Err(SDO_ABORT_NO_OBJECT)
+        );
+        assert_eq!(
+            transfer(&mut server, write(0x1000, 0, &[0; 4])).0,
+            Err(SDO_ABORT_READ_ONLY)
+        );
+        assert_eq!(
+            transfer(&mut server, write(0x2002, 0, &[0; 4])).0,
+            Err(SDO_ABORT_LENGTH)
+        );
+        assert_eq!(
+            transfer(&mut server, write(0x2000, 1, &[0; 8])).0,
+            Err(SDO_ABORT_LENGTH)
+        );
+
+        // Rejected writes leave the object unchanged.
+        assert_eq!(transfer(&mut server, read(0x2002, 0)).0, Ok(vec![0; 2]));
+
+        let (response, _) = server.serve_sdo(&[0xE0, 0x00, 0x20, 0x01, 0, 0, 0, 0]);
+        assert_eq!(
+            response,
+            Some(sdo_frame(0x80, 0x2000, 1, &SDO_ABORT_COMMAND.to_le_bytes()))
+        );
+    }
+}
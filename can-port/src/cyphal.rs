@@ -0,0 +1,809 @@
+//! Cyphal/CAN transport.
+//!
+//! This module contains a model implementing the Cyphal (formerly UAVCAN v1)
+//! transport layer on top of the raw CAN frames of a
+//! [`CanPort`](crate::CanPort):
+//! * subject and service identifiers encoded in the 29-bit identifiers,
+//! * multi-frame transfer segmentation and reassembly with the transfer CRC,
+//! * transfer-ID and toggle bit handling.
+//!
+//! Reassembled transfers are emitted on a single output, and can be
+//! demultiplexed into typed outputs by connecting it with
+//! [`Output::filter_map_connect`] and the filters returned by [`subject`] and
+//! [`service`], which deserialize the payload with the [`CyphalType`]
+//! implementation of the connected input.
+//!
+//! The transport handles a single classic CAN interface and should be
+//! connected to the frame output and input of the CAN port. Since it addresses
+//! its interface by name, the CAN port should be configured with
+//! `interface_names` enabled.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_can_port::cyphal::{CyphalTransfer, CyphalTransport, CyphalType};
+//!
+//! /// Heartbeat message (`uavcan.node.Heartbeat.1.0`).
+//! #[derive(Clone, Debug)]
+//! struct Heartbeat {
+//!     uptime: u32,
+//!     health: u8,
+//!     mode: u8,
+//!     vendor_specific_status_code: u8,
+//! }
+//!
+//! impl CyphalType for Heartbeat {
+//!     fn deserialize(payload: &[u8]) -> Option<Self> {
+//!         Some(Self {
+//!             uptime: u32::from_le_bytes(payload.get(..4)?.try_into().ok()?),
+//!             health: *payload.get(4)? & 0x03,
+//!             mode: *payload.get(5)? & 0x07,
+//!             vendor_specific_status_code: *payload.get(6)?,
+//!         })
+//!     }
+//!
+//!     fn serialize(&self, payload: &mut Vec<u8>) {
+//!         payload.extend_from_slice(&self.uptime.to_le_bytes());
+//!         payload.extend_from_slice(&[self.health, self.mode]);
+//!         payload.push(self.vendor_specific_status_code);
+//!     }
+//! }
+//!
+//! let transport = CyphalTransport::new("can0", Some(42));
+//!
+//! // Connect `transport.transfer_out` to the bench models with
+//! // `filter_map_connect(subject::<Heartbeat>(7509), ...)`, and send
+//! // transfers such as:
+//! let heartbeat = Heartbeat {
+//!     uptime: 1,
+//!     health: 0,
+//!     mode: 0,
+//!     vendor_specific_status_code: 0,
+//! };
+//! let transfer = CyphalTransfer::message(7509, &heartbeat);
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+
+use socketcan::{CanFrame, EmbeddedFrame, ExtendedId, Id};
+
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use nexosim::model::Model;
+use nexosim::ports::Output;
+
+use crate::{CanData, CanInterface};
+
+/// Maximum subject ID.
+pub const MAX_SUBJECT_ID: u16 = 8191;
+
+/// Maximum service ID.
+pub const MAX_SERVICE_ID: u16 = 511;
+
+/// Maximum node ID.
+pub const MAX_NODE_ID: u8 = 127;
+
+/// Default transfer priority (nominal).
+const DEFAULT_PRIORITY: u8 = 4;
+
+/// Start of transfer tail bit.
+const START_OF_TRANSFER: u8 = 0x80;
+
+/// End of transfer tail bit.
+const END_OF_TRANSFER: u8 = 0x40;
+
+/// Toggle tail bit.
+const TOGGLE: u8 = 0x20;
+
+/// Transfer-ID modulo.
+const TRANSFER_ID_MODULO: u8 = 32;
+
+/// Payload size of a classic CAN frame, excluding the tail byte.
+const FRAME_PAYLOAD: usize = 7;
+
+/// Serializable Cyphal data type.
+pub trait CyphalType: Sized {
+    /// Deserializes a value from a transfer payload.
+    ///
+    /// Returns `None` if the payload is invalid.
+    fn deserialize(payload: &[u8]) -> Option<Self>;
+
+    /// Serializes the value into a transfer payload.
+    fn serialize(&self, payload: &mut Vec<u8>);
+}
+
+/// Kind and port of a transfer.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TransferKind {
+    /// Message published on a subject.
+    Message {
+        /// Subject ID.
+        subject_id: u16,
+    },
+    /// Service request.
+    Request {
+        /// Service ID.
+        service_id: u16,
+        /// Server node ID.
+        destination: u8,
+    },
+    /// Service response.
+    Response {
+        /// Service ID.
+        service_id: u16,
+        /// Client node ID.
+        destination: u8,
+    },
+}
+
+/// Cyphal transfer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CyphalTransfer {
+    /// Kind and port of the transfer.
+    pub kind: TransferKind,
+
+    /// Priority, from 0 (exceptional) to 7 (optional).
+    pub priority: u8,
+
+    /// Source node ID, or `None` for anonymous messages.
+    ///
+    /// The source of transmitted transfers is set by the transport.
+    pub source: Option<u8>,
+
+    /// Transfer ID.
+    ///
+    /// The transfer ID of transmitted transfers is set by the transport.
+    pub transfer_id: u8,
+
+    /// Serialized payload.
+    pub payload: Vec<u8>,
+}
+
+impl CyphalTransfer {
+    /// Creates a new transfer with the nominal priority.
+    pub fn new(kind: TransferKind, payload: Vec<u8>) -> Self {
+        Self {
+            kind,
+            priority: DEFAULT_PRIORITY,
+            source: None,
+            transfer_id: 0,
+            payload,
+        }
+    }
+
+    /// Creates a new message transfer with a serialized value.
+    pub fn message<T: CyphalType>(subject_id: u16, value: &T) -> Self {
+        let mut payload = Vec::new();
+        value.serialize(&mut payload);
+
+        Self::new(TransferKind::Message { subject_id }, payload)
+    }
+
+    /// Creates a new service request transfer with a serialized value.
+    pub fn request<T: CyphalType>(service_id: u16, destination: u8, value: &T) -> Self {
+        let mut payload = Vec::new();
+        value.serialize(&mut payload);
+
+        Self::new(
+            TransferKind::Request {
+                service_id,
+                destination,
+            },
+            payload,
+        )
+    }
+
+    /// Creates a new service response transfer with a serialized value.
+    pub fn response<T: CyphalType>(service_id: u16, destination: u8, value: &T) -> Self {
+        let mut payload = Vec::new();
+        value.serialize(&mut payload);
+
+        Self::new(
+            TransferKind::Response {
+                service_id,
+                destination,
+            },
+            payload,
+        )
+    }
+
+    /// Sets the priority.
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// Returns a filter deserializing the messages published on a subject, to be
+/// used with [`Output::filter_map_connect`].
+pub fn subject<T: CyphalType>(
+    subject_id: u16,
+) -> impl Fn(&CyphalTransfer) -> Option<T> + Send + Sync + 'static {
+    move |transfer| match transfer.kind {
+        TransferKind::Message { subject_id: id } if id == subject_id => {
+            T::deserialize(&transfer.payload)
+        }
+        _ => None,
+    }
+}
+
+/// Returns a filter deserializing the requests or responses of a service,
+/// together with the source node ID, to be used with
+/// [`Output::filter_map_connect`].
+pub fn service<T: CyphalType>(
+    service_id: u16,
+    is_request: bool,
+) -> impl Fn(&CyphalTransfer) -> Option<(u8, T)> + Send + Sync + 'static {
+    move |transfer| {
+        let id = match transfer.kind {
+            TransferKind::Request { service_id, .. } if is_request => service_id,
+            TransferKind::Response { service_id, .. } if !is_request => service_id,
+            _ => return None,
+        };
+        if id != service_id {
+            return None;
+        }
+
+        Some((transfer.source?, T::deserialize(&transfer.payload)?))
+    }
+}
+
+/// Computes the CRC-16/CCITT-FALSE of the data.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+/// Encodes a 29-bit identifier.
+fn encode_id(transfer: &CyphalTransfer, source: u8) -> u32 {
+    let priority = (transfer.priority as u32 & 0x7) << 26;
+    let source = source as u32 & 0x7F;
+    match transfer.kind {
+        TransferKind::Message { subject_id } => {
+            let anonymous = if transfer.source.is_none() {
+                1 << 24
+            } else {
+                0
+            };
+            priority | anonymous | (0x3 << 21) | ((subject_id as u32 & 0x1FFF) << 8) | source
+        }
+        TransferKind::Request {
+            service_id,
+            destination,
+        } => {
+            priority
+                | (1 << 25)
+                | (1 << 24)
+                | ((service_id as u32 & 0x1FF) << 14)
+                | ((destination as u32 & 0x7F) << 7)
+                | source
+        }
+        TransferKind::Response {
+            service_id,
+            destination,
+        } => {
+            priority
+                | (1 << 25)
+                | ((service_id as u32 & 0x1FF) << 14)
+                | ((destination as u32 & 0x7F) << 7)
+                | source
+        }
+    }
+}
+
+/// Decodes a 29-bit identifier into the transfer kind, priority and source
+/// node ID.
+fn decode_id(id: u32) -> Option<(TransferKind, u8, Option<u8>)> {
+    // The reserved bit 23 is set by legacy UAVCAN v0 frames.
+    if id & (1 << 23) != 0 {
+        return None;
+    }
+    let priority = ((id >> 26) & 0x7) as u8;
+    let source = (id & 0x7F) as u8;
+
+    if id & (1 << 25) == 0 {
+        let subject_id = ((id >> 8) & 0x1FFF) as u16;
+        let source = if id & (1 << 24) != 0 {
+            None
+        } else {
+            Some(source)
+        };
+
+        return Some((TransferKind::Message { subject_id }, priority, source));
+    }
+
+    let service_id = ((id >> 14) & 0x1FF) as u16;
+    let destination = ((id >> 7) & 0x7F) as u8;
+    let kind = if id & (1 << 24) != 0 {
+        TransferKind::Request {
+            service_id,
+            destination,
+        }
+    } else {
+        TransferKind::Response {
+            service_id,
+            destination,
+        }
+    };
+
+    Some((kind, priority, Some(source)))
+}
+
+/// Reassembly session of a multi-frame transfer.
+#[derive(Debug)]
+struct RxSession {
+    /// Transfer ID.
+    transfer_id: u8,
+
+    /// Expected toggle bit.
+    toggle: bool,
+
+    /// Received payload, including the CRC.
+    payload: Vec<u8>,
+}
+
+/// Cyphal/CAN transport model.
+///
+/// This model converts raw CAN frames to Cyphal transfers and vice versa.
+pub struct CyphalTransport {
+    /// CAN frames to be transmitted -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// Received transfers -- output port.
+    pub transfer_out: Output<CyphalTransfer>,
+
+    /// CAN interface.
+    interface: CanInterface,
+
+    /// Local node ID, or `None` for an anonymous node.
+    node_id: Option<u8>,
+
+    /// Service transfers addressed to other nodes are forwarded.
+    promiscuous: bool,
+
+    /// Reassembly sessions, by transfer kind and source node ID.
+    rx_sessions: HashMap<(TransferKind, u8), RxSession>,
+
+    /// Next transfer IDs, by transfer kind.
+    tx_transfer_ids: HashMap<TransferKind, u8>,
+}
+
+impl CyphalTransport {
+    /// Creates a new Cyphal/CAN transport for the provided local node ID.
+    ///
+    /// An anonymous node can only publish single-frame messages.
+    pub fn new(interface: impl Into<CanInterface>, node_id: Option<u8>) -> Self {
+        Self {
+            frame_out: Output::new(),
+            transfer_out: Output::new(),
+            interface: interface.into(),
+            node_id,
+            promiscuous: false,
+            rx_sessions: HashMap::new(),
+            tx_transfer_ids: HashMap::new(),
+        }
+    }
+
+    /// Forwards service transfers addressed to other nodes as well.
+    pub fn with_promiscuous(mut self, promiscuous: bool) -> Self {
+        self.promiscuous = promiscuous;
+        self
+    }
+
+    /// Received CAN frame -- input port.
+    pub async fn frame_in(&mut self, data: CanData) {
+        if data.interface != self.interface {
+            return;
+        }
+        if let Some(transfer) = self.receive(&data.frame) {
+            self.transfer_out.send(transfer).await;
+        }
+    }
+
+    /// Transfer to be transmitted -- input port.
+    ///
+    /// Multi-frame transfers of anonymous nodes are dropped.
+    pub async fn transfer_in(&mut self, transfer: CyphalTransfer) {
+        for frame in self.transfer_frames(transfer) {
+            self.frame_out
+                .send(CanData::new(self.interface, frame))
+                .await;
+        }
+    }
+
+    /// Processes a received CAN frame, returning the transfer it completes, if
+    /// any.
+    fn receive(&mut self, frame: &CanFrame) -> Option<CyphalTransfer> {
+        let (CanFrame::Data(frame), Id::Extended(id)) = (frame, frame.id()) else {
+            return None;
+        };
+        let (kind, priority, source) = decode_id(id.as_raw())?;
+        let (&tail, payload) = frame.data().split_last()?;
+
+        if let TransferKind::Request { destination, .. }
+        | TransferKind::Response { destination, .. } = kind
+        {
+            if !self.promiscuous && Some(destination) != self.node_id {
+                return None;
+            }
+        }
+
+        let transfer_id = tail % TRANSFER_ID_MODULO;
+        let is_start = tail & START_OF_TRANSFER != 0;
+        let is_end = tail & END_OF_TRANSFER != 0;
+        let toggle = tail & TOGGLE != 0;
+
+        let payload = if is_start && is_end {
+            // Single-frame transfers start with the toggle bit set.
+            if !toggle {
+                return None;
+            }
+            payload.to_vec()
+        } else {
+            // Anonymous transfers are single-frame only.
+            let source = source?;
+            let key = (kind, source);
+            if is_start {
+                if !toggle {
+                    return None;
+                }
+                self.rx_sessions.insert(
+                    key,
+                    RxSession {
+                        transfer_id,
+                        toggle: false,
+                        payload: payload.to_vec(),
+                    },
+                );
+                return None;
+            }
+            let session = self.rx_sessions.get_mut(&key)?;
+            if session.transfer_id != transfer_id || session.toggle != toggle {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    "Discarding Cyphal transfer {:?} from node {}: unexpected frame.",
+                    kind, source
+                );
+                self.rx_sessions.remove(&key);
+                return None;
+            }
+            session.payload.extend_from_slice(payload);
+            session.toggle = !toggle;
+            if !is_end {
+                return None;
+            }
+
+            let mut payload = self.rx_sessions.remove(&key).unwrap().payload;
+            if payload.len() < 2 || crc16(&payload) != 0 {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    "Discarding Cyphal transfer {:?} from node {}: CRC error.",
+                    kind, source
+                );
+                return None;
+            }
+            payload.truncate(payload.len() - 2);
+            payload
+        };
+
+        Some(CyphalTransfer {
+            kind,
+            priority,
+            source,
+            transfer_id,
+            payload,
+        })
+    }
+
+    /// Segments a transfer into CAN frames, assigning its source node ID and
+    /// transfer ID.
+    fn transfer_frames(&mut self, mut transfer: CyphalTransfer) -> Vec<CanFrame> {
+        transfer.source = self.node_id;
+        let is_single_frame = transfer.payload.len() <= FRAME_PAYLOAD;
+        let source = match self.node_id {
+            Some(node_id) => node_id,
+            None if is_single_frame && matches!(transfer.kind, TransferKind::Message { .. }) => {
+                // Anonymous transfers use a pseudo node ID derived from the
+                // payload.
+                (crc16(&transfer.payload) & 0x7F) as u8
+            }
+            None => {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    "Dropping Cyphal transfer {:?}: not allowed for an anonymous node.",
+                    transfer.kind
+                );
+                return Vec::new();
+            }
+        };
+
+        let next_transfer_id = self.tx_transfer_ids.entry(transfer.kind).or_default();
+        let transfer_id = *next_transfer_id;
+        *next_transfer_id = (transfer_id + 1) % TRANSFER_ID_MODULO;
+
+        let id = Id::Extended(ExtendedId::new(encode_id(&transfer, source)).unwrap());
+
+        if is_single_frame {
+            let mut data = transfer.payload;
+            data.push(START_OF_TRANSFER | END_OF_TRANSFER | TOGGLE | transfer_id);
+            return vec![CanFrame::new(id, &data).unwrap()];
+        }
+
+        let mut payload = transfer.payload;
+        let crc = crc16(&payload);
+        payload.extend_from_slice(&crc.to_be_bytes());
+
+        let count = payload.len().div_ceil(FRAME_PAYLOAD);
+        let mut toggle = true;
+        payload
+            .chunks(FRAME_PAYLOAD)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut tail = transfer_id;
+                if i == 0 {
+                    tail |= START_OF_TRANSFER;
+                }
+                if i + 1 == count {
+                    tail |= END_OF_TRANSFER;
+                }
+                if toggle {
+                    tail |= TOGGLE;
+                }
+                toggle = !toggle;
+
+                let mut data = chunk.to_vec();
+                data.push(tail);
+                CanFrame::new(id, &data).unwrap()
+            })
+            .collect()
+    }
+}
+
+impl Model for CyphalTransport {}
+
+impl fmt::Debug for CyphalTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CyphalTransport")
+            .field("interface", &self.interface)
+            .field("node_id", &self.node_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opaque test payload.
+    #[derive(Debug, PartialEq)]
+    struct Raw(Vec<u8>);
+
+    impl CyphalType for Raw {
+        fn deserialize(payload: &[u8]) -> Option<Self> {
+            Some(Self(payload.to_vec()))
+        }
+
+        fn serialize(&self, payload: &mut Vec<u8>) {
+            payload.extend_from_slice(&self.0);
+        }
+    }
+
+    fn tails(frames: &[CanFrame]) -> Vec<u8> {
+        frames
+            .iter()
+            .map(|frame| *frame.data().last().unwrap())
+            .collect()
+    }
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len as u8).collect()
+    }
+
+    #[test]
+    fn crc_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(&[]), 0xFFFF);
+
+        // A payload followed by its big-endian CRC has a zero residue.
+        let mut data = b"123456789".to_vec();
+        data.extend_from_slice(&0x29B1u16.to_be_bytes());
+        assert_eq!(crc16(&data), 0);
+    }
+
+    #[test]
+    fn id_encoding() {
+        let message = CyphalTransfer {
+            source: Some(42),
+            ..CyphalTransfer::new(TransferKind::Message { subject_id: 7509 }, Vec::new())
+        };
+        let id = encode_id(&message, 42);
+        assert_eq!(id, 0x107D_552A);
+        assert_eq!(
+            decode_id(id),
+            Some((TransferKind::Message { subject_id: 7509 }, 4, Some(42)))
+        );
+
+        let anonymous = CyphalTransfer::new(TransferKind::Message { subject_id: 10 }, Vec::new());
+        let id = encode_id(&anonymous, 0x55);
+        assert_eq!(
+            decode_id(id),
+            Some((TransferKind::Message { subject_id: 10 }, 4, None))
+        );
+
+        for kind in [
+            TransferKind::Request {
+                service_id: 430,
+                destination: 7,
+            },
+            TransferKind::Response {
+                service_id: 511,
+                destination: 127,
+            },
+        ] {
+            let transfer = CyphalTransfer::new(kind, Vec::new()).with_priority(1);
+            let id = encode_id(&transfer, 42);
+            assert_eq!(decode_id(id), Some((kind, 1, Some(42))));
+        }
+
+        // Legacy UAVCAN v0 frames set the reserved bit 23.
+        assert_eq!(decode_id(id | 1 << 23), None);
+    }
+
+    #[test]
+    fn single_frame_transfer() {
+        let mut tx = CyphalTransport::new("can0", Some(42));
+        let mut rx = CyphalTransport::new("can0", Some(7));
+
+        for transfer_id in 0..2 {
+            let transfer =
+                CyphalTransfer::new(TransferKind::Message { subject_id: 7509 }, payload(7));
+            let frames = tx.transfer_frames(transfer);
+            assert_eq!(frames.len(), 1);
+            assert_eq!(tails(&frames), [0xE0 | transfer_id]);
+
+            let received = rx.receive(&frames[0]).unwrap();
+            assert_eq!(received.kind, TransferKind::Message { subject_id: 7509 });
+            assert_eq!(received.source, Some(42));
+            assert_eq!(received.transfer_id, transfer_id);
+            assert_eq!(received.payload, payload(7));
+        }
+    }
+
+    #[test]
+    fn transfer_id_wraps_around() {
+        let mut tx = CyphalTransport::new("can0", Some(42));
+        let kind = TransferKind::Message { subject_id: 1 };
+        for _ in 0..TRANSFER_ID_MODULO {
+            tx.transfer_frames(CyphalTransfer::new(kind, Vec::new()));
+        }
+        let frames = tx.transfer_frames(CyphalTransfer::new(kind, Vec::new()));
+        assert_eq!(tails(&frames), [0xE0]);
+
+        // Transfer IDs are counted separately for each port.
+        let frames = tx.transfer_frames(CyphalTransfer::new(
+            TransferKind::Message { subject_id: 2 },
+            Vec::new(),
+        ));
+        assert_eq!(tails(&frames), [0xE0]);
+    }
+
+    #[test]
+    fn multi_frame_transfer() {
+        let mut tx = CyphalTransport::new("can0", Some(42));
+        let mut rx = CyphalTransport::new("can0", Some(7));
+
+        // 20 bytes of payload and 2 bytes of CRC span 4 frames.
+        let transfer = CyphalTransfer::message(100, &Raw(payload(20)));
+        let frames = tx.transfer_frames(transfer);
+        assert_eq!(tails(&frames), [0xA0, 0x00, 0x20, 0x40]);
+        let crc = crc16(&payload(20)).to_be_bytes();
+        assert_eq!(frames[3].data(), [crc[1], 0x40]);
+        assert_eq!(frames[2].data()[6], crc[0]);
+
+        let (last, first) = frames.split_last().unwrap();
+        for frame in first {
+            assert_eq!(rx.receive(frame), None);
+        }
+        let received = rx.receive(last).unwrap();
+        assert_eq!(received.source, Some(42));
+        assert_eq!(subject::<Raw>(100)(&received), Some(Raw(payload(20))));
+        assert_eq!(subject::<Raw>(101)(&received), None);
+    }
+
+    #[test]
+    fn unexpected_toggle() {
+        let mut tx = CyphalTransport::new("can0", Some(42));
+        let mut rx = CyphalTransport::new("can0", Some(7));
+
+        let transfer = CyphalTransfer::new(TransferKind::Message { subject_id: 100 }, payload(20));
+        let frames = tx.transfer_frames(transfer);
+
+        // A repeated frame has the wrong toggle bit and aborts the transfer.
+        assert_eq!(rx.receive(&frames[0]), None);
+        assert_eq!(rx.receive(&frames[1]), None);
+        assert_eq!(rx.receive(&frames[1]), None);
+        assert_eq!(rx.receive(&frames[2]), None);
+        assert_eq!(rx.receive(&frames[3]), None);
+        assert!(rx.rx_sessions.is_empty());
+
+        // A start frame with a cleared toggle bit is ignored.
+        let mut data = frames[0].data().to_vec();
+        *data.last_mut().unwrap() &= !TOGGLE;
+        let frame = CanFrame::new(frames[0].id(), &data).unwrap();
+        assert_eq!(rx.receive(&frame), None);
+        assert!(rx.rx_sessions.is_empty());
+    }
+
+    #[test]
+    fn crc_error() {
+        let mut tx = CyphalTransport::new("can0", Some(42));
+        let mut rx = CyphalTransport::new("can0", Some(7));
+
+        let transfer = CyphalTransfer::new(TransferKind::Message { subject_id: 100 }, payload(10));
+        let mut frames = tx.transfer_frames(transfer);
+        let mut data = frames[0].data().to_vec();
+        data[0] ^= 0x01;
+        frames[0] = CanFrame::new(frames[0].id(), &data).unwrap();
+
+        assert_eq!(rx.receive(&frames[0]), None);
+        assert_eq!(rx.receive(&frames[1]), None);
+        assert!(rx.rx_sessions.is_empty());
+    }
+
+    #[test]
+    fn service_destination() {
+        let mut tx = CyphalTransport::new("can0", Some(42));
+        let request = CyphalTransfer::request(430, 7, &Raw(payload(3)));
+        let frames = tx.transfer_frames(request);
+
+        let mut other = CyphalTransport::new("can0", Some(8));
+        assert_eq!(other.receive(&frames[0]), None);
+
+        let mut other = CyphalTransport::new("can0", Some(8)).with_promiscuous(true);
+        assert!(other.receive(&frames[0]).is_some());
+
+        let mut server = CyphalTransport::new("can0", Some(7));
+        let received = server.receive(&frames[0]).unwrap();
+        assert_eq!(
+            service::<Raw>(430, true)(&received),
+            Some((42, Raw(payload(3))))
+        );
+        assert_eq!(service::<Raw>(430, false)(&received), None);
+    }
+
+    #[test]
+    fn anonymous_node() {
+        let mut tx = CyphalTransport::new("can0", None);
+        let mut rx = CyphalTransport::new("can0", Some(7));
+
+        let message = CyphalTransfer::new(TransferKind::Message { subject_id: 100 }, payload(7));
+        let frames = tx.transfer_frames(message);
+        let received = rx.receive(&frames[0]).unwrap();
+        assert_eq!(received.source, None);
+        assert_eq!(received.payload, payload(7));
+
+        // Anonymous nodes can only publish single-frame messages.
+        let message = CyphalTransfer::new(TransferKind::Message { subject_id: 100 }, payload(8));
+        assert!(tx.transfer_frames(message).is_empty());
+        let request = CyphalTransfer::new(
+            TransferKind::Request {
+                service_id: 430,
+                destination: 7,
+            },
+            payload(1),
+        );
+        assert!(tx.transfer_frames(request).is_empty());
+    }
+}
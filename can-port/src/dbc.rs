@@ -0,0 +1,814 @@
+//! DBC signal codec.
+//!
+//! This module contains a parser for the message and signal definitions of
+//! DBC files, and a model decoding received CAN frames into named, scaled
+//! signal values and encoding signal updates into CAN frames.
+//!
+//! Little-endian (Intel) and big-endian (Motorola) signals, signed and
+//! unsigned signals, and simple multiplexing are supported. Value tables,
+//! attributes and comments are ignored.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_can_port::dbc::Dbc;
+//!
+//! let dbc = Dbc::parse(
+//!     r#"
+//! BO_ 2364540158 EEC1: 8 Engine
+//!  SG_ EngineSpeed : 24|16@1+ (0.125,0) [0|8031.875] "rpm" Vector__XXX
+//! "#,
+//! )
+//! .unwrap();
+//!
+//! let message = dbc.message("EEC1").unwrap();
+//! let values = message.decode(&[0, 0, 0, 0x40, 0x1F, 0, 0, 0]);
+//! assert_eq!(values, vec![("EngineSpeed", 1000.0)]);
+//! ```
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use socketcan::{CanFrame, EmbeddedFrame, ExtendedId, Id, StandardId};
+
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use nexosim::model::Model;
+use nexosim::ports::Output;
+
+use crate::{CanData, CanInterface};
+
+/// Extended identifier flag of DBC message identifiers.
+const DBC_EXTENDED: u32 = 0x8000_0000;
+
+/// Name of the pseudo-message holding the signals not mapped to any message.
+const INDEPENDENT_SIGNALS: &str = "VECTOR__INDEPENDENT_SIG_MSG";
+
+/// DBC error.
+#[derive(Debug)]
+pub enum DbcError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// Syntax error at the provided line.
+    Syntax {
+        /// Line number, starting from 1.
+        line: usize,
+        /// Error description.
+        message: String,
+    },
+}
+
+impl From<io::Error> for DbcError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl fmt::Display for DbcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "I/O error: {error}"),
+            Self::Syntax { line, message } => write!(f, "syntax error at line {line}: {message}"),
+        }
+    }
+}
+
+impl Error for DbcError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Byte order of a signal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ByteOrder {
+    /// Little-endian (Intel).
+    LittleEndian,
+    /// Big-endian (Motorola).
+    BigEndian,
+}
+
+/// Multiplexing role of a signal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Multiplexing {
+    /// Signal is always present.
+    None,
+    /// Signal selects the multiplexed signals.
+    Multiplexor,
+    /// Signal is present when the multiplexor has the provided raw value.
+    Multiplexed(u64),
+}
+
+/// Signal definition.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DbcSignal {
+    /// Signal name.
+    pub name: String,
+
+    /// Start bit, as defined in the DBC file.
+    pub start_bit: u16,
+
+    /// Size, in bits.
+    pub size: u8,
+
+    /// Byte order.
+    pub byte_order: ByteOrder,
+
+    /// Signal is signed.
+    pub signed: bool,
+
+    /// Scaling factor.
+    pub factor: f64,
+
+    /// Offset.
+    pub offset: f64,
+
+    /// Minimum physical value.
+    pub min: f64,
+
+    /// Maximum physical value.
+    pub max: f64,
+
+    /// Unit.
+    pub unit: String,
+
+    /// Multiplexing role.
+    pub multiplexing: Multiplexing,
+}
+
+impl DbcSignal {
+    /// Returns the positions of the signal bits in the frame data, from the
+    /// least significant bit.
+    fn bit_positions(&self) -> Vec<usize> {
+        let start = self.start_bit as usize;
+        let mut position = start;
+        let mut positions = Vec::with_capacity(self.size as usize);
+        for i in 0..self.size as usize {
+            match self.byte_order {
+                ByteOrder::LittleEndian => positions.push(start + i),
+                ByteOrder::BigEndian => {
+                    positions.push(position);
+                    position = if position % 8 == 0 {
+                        position + 15
+                    } else {
+                        position - 1
+                    };
+                }
+            }
+        }
+        if self.byte_order == ByteOrder::BigEndian {
+            positions.reverse();
+        }
+
+        positions
+    }
+
+    /// Returns the raw value of the signal.
+    ///
+    /// Bits beyond the frame data are read as zero.
+    pub fn raw(&self, data: &[u8]) -> u64 {
+        self.bit_positions()
+            .into_iter()
+            .enumerate()
+            .fold(0, |raw, (i, position)| {
+                let bit = data
+                    .get(position / 8)
+                    .map_or(0, |byte| (byte >> (position % 8)) & 1);
+                raw | ((bit as u64) << i)
+            })
+    }
+
+    /// Returns the physical value of the signal.
+    pub fn decode(&self, data: &[u8]) -> f64 {
+        let raw = self.raw(data);
+        let value = if self.signed && self.size > 0 && self.size < 64 {
+            let shift = 64 - self.size as u32;
+            ((raw << shift) as i64 >> shift) as f64
+        } else if self.signed {
+            raw as i64 as f64
+        } else {
+            raw as f64
+        };
+
+        value * self.factor + self.offset
+    }
+
+    /// Writes the raw value of the signal.
+    ///
+    /// Bits beyond the frame data are ignored.
+    pub fn set_raw(&self, raw: u64, data: &mut [u8]) {
+        for (i, position) in self.bit_positions().into_iter().enumerate() {
+            if let Some(byte) = data.get_mut(position / 8) {
+                let mask = 1 << (position % 8);
+                if (raw >> i) & 1 != 0 {
+                    *byte |= mask;
+                } else {
+                    *byte &= !mask;
+                }
+            }
+        }
+    }
+
+    /// Writes the physical value of the signal, rounded and saturated to the
+    /// range of the raw value.
+    pub fn encode(&self, value: f64, data: &mut [u8]) {
+        let bits = self.size.min(64) as u32;
+        if bits == 0 {
+            return;
+        }
+        let raw = ((value - self.offset) / self.factor).round();
+        let raw = if self.signed {
+            let max = (1i128 << (bits - 1)) - 1;
+            let min = -(1i128 << (bits - 1));
+            (raw as i128).clamp(min, max) as u64
+        } else {
+            let max = (1u128 << bits) - 1;
+            (raw.max(0.0) as u128).min(max) as u64
+        };
+        self.set_raw(raw, data);
+    }
+}
+
+/// Message definition.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DbcMessage {
+    /// CAN identifier.
+    pub id: Id,
+
+    /// Message name.
+    pub name: String,
+
+    /// Size, in bytes.
+    pub size: u8,
+
+    /// Signal definitions.
+    pub signals: Vec<DbcSignal>,
+}
+
+impl DbcMessage {
+    /// Returns the definition of a signal.
+    pub fn signal(&self, name: &str) -> Option<&DbcSignal> {
+        self.signals.iter().find(|signal| signal.name == name)
+    }
+
+    /// Returns the raw value of the multiplexor, if any.
+    fn multiplexor(&self, data: &[u8]) -> Option<u64> {
+        self.signals
+            .iter()
+            .find(|signal| signal.multiplexing == Multiplexing::Multiplexor)
+            .map(|signal| signal.raw(data))
+    }
+
+    /// Returns `true` if the signal is present in the frame data.
+    fn is_present(signal: &DbcSignal, multiplexor: Option<u64>) -> bool {
+        match signal.multiplexing {
+            Multiplexing::Multiplexed(value) => multiplexor == Some(value),
+            _ => true,
+        }
+    }
+
+    /// Decodes the physical values of the signals present in the frame data.
+    pub fn decode(&self, data: &[u8]) -> Vec<(&str, f64)> {
+        let multiplexor = self.multiplexor(data);
+
+        self.signals
+            .iter()
+            .filter(|signal| Self::is_present(signal, multiplexor))
+            .map(|signal| (signal.name.as_str(), signal.decode(data)))
+            .collect()
+    }
+}
+
+/// Message and signal definitions of a DBC file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Dbc {
+    /// Message definitions.
+    messages: Vec<DbcMessage>,
+}
+
+impl Dbc {
+    /// Reads the definitions of a DBC file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, DbcError> {
+        let bytes = std::fs::read(path)?;
+
+        // DBC files are frequently encoded in Windows-1252.
+        Self::parse(&String::from_utf8_lossy(&bytes))
+    }
+
+    /// Parses the definitions of a DBC file.
+    pub fn parse(source: &str) -> Result<Self, DbcError> {
+        let mut messages: Vec<DbcMessage> = Vec::new();
+        let mut is_ignored = false;
+
+        for (i, line) in source.lines().enumerate() {
+            let syntax = |message: &str| DbcError::Syntax {
+                line: i + 1,
+                message: message.to_string(),
+            };
+            let line = line.trim();
+            if let Some(definition) = line.strip_prefix("BO_ ") {
+                let message = parse_message(definition).ok_or_else(|| syntax("invalid message"))?;
+                is_ignored = message.name == INDEPENDENT_SIGNALS;
+                if !is_ignored {
+                    messages.push(message);
+                }
+            } else if let Some(definition) = line.strip_prefix("SG_ ") {
+                let signal = parse_signal(definition).ok_or_else(|| syntax("invalid signal"))?;
+                if is_ignored {
+                    continue;
+                }
+                messages
+                    .last_mut()
+                    .ok_or_else(|| syntax("signal outside of a message"))?
+                    .signals
+                    .push(signal);
+            } else if !line.is_empty() {
+                is_ignored = true;
+            }
+        }
+
+        Ok(Self { messages })
+    }
+
+    /// Returns the message definitions.
+    pub fn messages(&self) -> &[DbcMessage] {
+        &self.messages
+    }
+
+    /// Returns the definition of a message.
+    pub fn message(&self, name: &str) -> Option<&DbcMessage> {
+        self.messages.iter().find(|message| message.name == name)
+    }
+
+    /// Returns the definition of the message with the provided identifier.
+    pub fn message_by_id(&self, id: Id) -> Option<&DbcMessage> {
+        self.messages.iter().find(|message| message.id == id)
+    }
+}
+
+/// Parses a message definition, e.g. `2364540158 EEC1: 8 Engine`.
+fn parse_message(definition: &str) -> Option<DbcMessage> {
+    let (head, rest) = definition.split_once(':')?;
+    let mut head = head.split_whitespace();
+    let raw_id: u32 = head.next()?.parse().ok()?;
+    let name = head.next()?.to_string();
+    let size = rest.split_whitespace().next()?.parse().ok()?;
+
+    let id = if name == INDEPENDENT_SIGNALS {
+        // The pseudo-message has an out-of-range identifier.
+        Id::Standard(StandardId::ZERO)
+    } else if raw_id & DBC_EXTENDED != 0 {
+        Id::Extended(ExtendedId::new(raw_id & !DBC_EXTENDED)?)
+    } else {
+        Id::Standard(StandardId::new(u16::try_from(raw_id).ok()?)?)
+    };
+
+    Some(DbcMessage {
+        id,
+        name,
+        size,
+        signals: Vec::new(),
+    })
+}
+
+/// Parses a signal definition, e.g.
+/// `EngineSpeed : 24|16@1+ (0.125,0) [0|8031.875] "rpm" Vector__XXX`.
+fn parse_signal(definition: &str) -> Option<DbcSignal> {
+    let (head, rest) = definition.split_once(" :")?;
+    let mut head = head.split_whitespace();
+    let name = head.next()?.to_string();
+    let multiplexing = match head.next() {
+        None => Multiplexing::None,
+        Some("M") => Multiplexing::Multiplexor,
+        Some(mux) => {
+            // Extended multiplexing (`m<n>M`) is handled as simple
+            // multiplexing.
+            let value = mux.strip_prefix('m')?.trim_end_matches('M');
+            Multiplexing::Multiplexed(value.parse().ok()?)
+        }
+    };
+
+    let rest = rest.trim_start();
+    let (layout, rest) = rest.split_once(' ')?;
+    let (start_bit, layout) = layout.split_once('|')?;
+    let (size, layout) = layout.split_once('@')?;
+    let mut layout = layout.chars();
+    let byte_order = match layout.next()? {
+        '0' => ByteOrder::BigEndian,
+        '1' => ByteOrder::LittleEndian,
+        _ => return None,
+    };
+    let signed = match layout.next()? {
+        '-' => true,
+        '+' => false,
+        _ => return None,
+    };
+
+    let rest = rest.trim_start().strip_prefix('(')?;
+    let (scaling, rest) = rest.split_once(')')?;
+    let (factor, offset) = scaling.split_once(',')?;
+    let rest = rest.trim_start().strip_prefix('[')?;
+    let (range, rest) = rest.split_once(']')?;
+    let (min, max) = range.split_once('|')?;
+    let rest = rest.trim_start().strip_prefix('"')?;
+    let (unit, _) = rest.split_once('"')?;
+
+    Some(DbcSignal {
+        name,
+        start_bit: start_bit.trim().parse().ok()?,
+        size: size.trim().parse().ok()?,
+        byte_order,
+        signed,
+        factor: factor.trim().parse().ok()?,
+        offset: offset.trim().parse().ok()?,
+        min: min.trim().parse().ok()?,
+        max: max.trim().parse().ok()?,
+        unit: unit.to_string(),
+        multiplexing,
+    })
+}
+
+/// Physical value of a signal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SignalValue {
+    /// Message name.
+    pub message: String,
+
+    /// Signal name.
+    pub signal: String,
+
+    /// Physical value.
+    pub value: f64,
+}
+
+/// Physical values of the signals of a message.
+///
+/// This type can be converted into a user-defined structure when connecting
+/// the message output, e.g. with [`Output::filter_map_connect`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MessageValues {
+    /// Message name.
+    pub message: String,
+
+    /// Signal names and physical values.
+    pub signals: Vec<(String, f64)>,
+}
+
+impl MessageValues {
+    /// Returns the physical value of a signal.
+    pub fn get(&self, signal: &str) -> Option<f64> {
+        self.signals
+            .iter()
+            .find(|(name, _)| name == signal)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// DBC signal codec model.
+///
+/// This model
+/// * decodes the received CAN frames defined in the DBC file into signal
+///   values, emitted both per signal and per message,
+/// * encodes signal and message updates into CAN frames, keeping the last
+///   transmitted value of the signals which are not updated.
+///
+/// The codec handles a single CAN interface. If the interface is addressed by
+/// name, the CAN port should be configured with `interface_names` enabled.
+pub struct DbcCodec {
+    /// CAN frames to be transmitted -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// Received signal values -- output port.
+    pub signal_out: Output<SignalValue>,
+
+    /// Received message values -- output port.
+    pub message_out: Output<MessageValues>,
+
+    /// CAN interface.
+    interface: CanInterface,
+
+    /// Message and signal definitions.
+    dbc: Dbc,
+
+    /// Data of the last transmitted frames, by message index.
+    tx_data: HashMap<usize, Vec<u8>>,
+}
+
+impl DbcCodec {
+    /// Creates a new codec for the provided definitions.
+    pub fn new(interface: impl Into<CanInterface>, dbc: Dbc) -> Self {
+        Self {
+            frame_out: Output::new(),
+            signal_out: Output::new(),
+            message_out: Output::new(),
+            interface: interface.into(),
+            dbc,
+            tx_data: HashMap::new(),
+        }
+    }
+
+    /// Received CAN frame -- input port.
+    ///
+    /// Frames not defined in the DBC file are ignored.
+    pub async fn frame_in(&mut self, data: CanData) {
+        if data.interface != self.interface {
+            return;
+        }
+        let CanFrame::Data(frame) = data.frame else {
+            return;
+        };
+        let Some(message) = self.dbc.message_by_id(frame.id()) else {
+            return;
+        };
+
+        let signals: Vec<(String, f64)> = message
+            .decode(frame.data())
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        let name = message.name.clone();
+
+        for (signal, value) in &signals {
+            self.signal_out
+                .send(SignalValue {
+                    message: name.clone(),
+                    signal: signal.clone(),
+                    value: *value,
+                })
+                .await;
+        }
+        self.message_out
+            .send(MessageValues {
+                message: name,
+                signals,
+            })
+            .await;
+    }
+
+    /// Signal update to be transmitted -- input port.
+    pub async fn signal_in(&mut self, value: SignalValue) {
+        self.message_in(MessageValues {
+            message: value.message,
+            signals: vec![(value.signal, value.value)],
+        })
+        .await;
+    }
+
+    /// Message update to be transmitted -- input port.
+    ///
+    /// Updates of unknown messages or signals are ignored.
+    pub async fn message_in(&mut self, values: MessageValues) {
+        let Some(index) = self
+            .dbc
+            .messages
+            .iter()
+            .position(|message| message.name == values.message)
+        else {
+            #[cfg(feature = "tracing")]
+            warn!("Ignoring update of the unknown message {}.", values.message);
+            return;
+        };
+        let message = &self.dbc.messages[index];
+        let data = self
+            .tx_data
+            .entry(index)
+            .or_insert_with(|| vec![0; message.size as usize]);
+
+        for (name, value) in &values.signals {
+            let Some(signal) = message.signal(name) else {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    "Ignoring update of the unknown signal {} of message {}.",
+                    name, message.name
+                );
+                continue;
+            };
+            signal.encode(*value, data);
+        }
+
+        let Some(frame) = CanFrame::new(message.id, data) else {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Cannot transmit message {}: {} bytes exceed a CAN frame.",
+                message.name, message.size
+            );
+            return;
+        };
+        self.frame_out
+            .send(CanData::new(self.interface, frame))
+            .await;
+    }
+}
+
+impl Model for DbcCodec {}
+
+impl fmt::Debug for DbcCodec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DbcCodec")
+            .field("interface", &self.interface)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DBC: &str = r#"VERSION ""
+
+NS_ :
+    CM_
+
+BU_: Engine Gateway
+
+BO_ 2364540158 EEC1: 8 Engine
+ SG_ EngineSpeed : 24|16@1+ (0.125,0) [0|8031.875] "rpm" Gateway
+ SG_ EngineTorque : 15|8@0- (1,-125) [-125|125] "%" Gateway
+
+BO_ 1234 Status: 4 Gateway
+ SG_ Mode M : 0|8@1+ (1,0) [0|255] "" Engine
+ SG_ Temperature m0 : 8|16@1- (0.1,0) [-40|125] "degC" Engine
+ SG_ Voltage m1 : 8|16@1+ (0.01,0) [0|24] "V" Engine
+
+BO_ 3221225472 VECTOR__INDEPENDENT_SIG_MSG: 0 Vector__XXX
+ SG_ Orphan : 0|8@1+ (1,0) [0|0] "" Vector__XXX
+
+CM_ SG_ 2364540158 EngineSpeed "Engine speed.";
+"#;
+
+    fn layout_signal(layout: &str) -> DbcSignal {
+        parse_signal(&format!("Signal : {layout} (1,0) [0|0] \"\" Vector__XXX")).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let dbc = Dbc::parse(DBC).unwrap();
+
+        assert_eq!(dbc.messages().len(), 2);
+
+        let eec1 = dbc.message("EEC1").unwrap();
+        assert_eq!(eec1.id, Id::Extended(ExtendedId::new(0x0CF0_04FE).unwrap()));
+        assert_eq!(eec1.size, 8);
+        assert_eq!(
+            eec1.signal("EngineSpeed"),
+            Some(&DbcSignal {
+                name: "EngineSpeed".to_string(),
+                start_bit: 24,
+                size: 16,
+                byte_order: ByteOrder::LittleEndian,
+                signed: false,
+                factor: 0.125,
+                offset: 0.0,
+                min: 0.0,
+                max: 8031.875,
+                unit: "rpm".to_string(),
+                multiplexing: Multiplexing::None,
+            })
+        );
+        let torque = eec1.signal("EngineTorque").unwrap();
+        assert_eq!(torque.byte_order, ByteOrder::BigEndian);
+        assert!(torque.signed);
+        assert_eq!((torque.factor, torque.offset), (1.0, -125.0));
+
+        let status = dbc
+            .message_by_id(Id::Standard(StandardId::new(1234).unwrap()))
+            .unwrap();
+        assert_eq!(status.name, "Status");
+        let multiplexing: Vec<Multiplexing> = status
+            .signals
+            .iter()
+            .map(|signal| signal.multiplexing)
+            .collect();
+        assert_eq!(
+            multiplexing,
+            [
+                Multiplexing::Multiplexor,
+                Multiplexing::Multiplexed(0),
+                Multiplexing::Multiplexed(1)
+            ]
+        );
+
+        assert!(dbc.message(INDEPENDENT_SIGNALS).is_none());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let syntax_line = |source: &str| match Dbc::parse(source) {
+            Err(DbcError::Syntax { line, .. }) => Some(line),
+            _ => None,
+        };
+
+        assert_eq!(syntax_line("\nBO_ 12 Message 8 Node"), Some(2));
+        assert_eq!(syntax_line("BO_ 4096 Message: 8 Node"), Some(1));
+        assert_eq!(
+            syntax_line(" SG_ Signal : 0|8@1+ (1,0) [0|0] \"\" Node"),
+            Some(1)
+        );
+        assert_eq!(
+            syntax_line("BO_ 12 Message: 8 Node\n SG_ Signal : 0|8@2+ (1,0) [0|0] \"\" Node"),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn intel_bit_order() {
+        let signal = layout_signal("12|12@1+");
+        let data = [0x00, 0xC0, 0xAB, 0x00];
+        assert_eq!(signal.raw(&data), 0xABC);
+
+        let mut encoded = [0xFF, 0x0F, 0x00, 0xFF];
+        signal.set_raw(0xABC, &mut encoded);
+        assert_eq!(encoded, [0xFF, 0xCF, 0xAB, 0xFF]);
+    }
+
+    #[test]
+    fn motorola_bit_order() {
+        // The start bit is the most significant bit.
+        let signal = layout_signal("7|16@0+");
+        assert_eq!(signal.raw(&[0x12, 0x34]), 0x1234);
+
+        let signal = layout_signal("3|12@0+");
+        assert_eq!(signal.raw(&[0xFA, 0xBC, 0xFF]), 0xABC);
+
+        let mut encoded = [0xF0, 0x00, 0xFF];
+        signal.set_raw(0xABC, &mut encoded);
+        assert_eq!(encoded, [0xFA, 0xBC, 0xFF]);
+    }
+
+    #[test]
+    fn bits_beyond_data() {
+        let signal = layout_signal("4|8@1+");
+        assert_eq!(signal.raw(&[0xF0]), 0x0F);
+
+        let mut encoded = [0x00];
+        signal.set_raw(0xFF, &mut encoded);
+        assert_eq!(encoded, [0xF0]);
+    }
+
+    #[test]
+    fn signedness() {
+        let unsigned = layout_signal("8|8@1+");
+        let signed = layout_signal("8|8@1-");
+        let data = [0x00, 0xFE];
+        assert_eq!(unsigned.decode(&data), 254.0);
+        assert_eq!(signed.decode(&data), -2.0);
+
+        let signed = layout_signal("0|64@1-");
+        assert_eq!(signed.decode(&[0xFF; 8]), -1.0);
+
+        let mut encoded = [0x00; 2];
+        layout_signal("8|8@1-").encode(-2.0, &mut encoded);
+        assert_eq!(encoded, [0x00, 0xFE]);
+    }
+
+    #[test]
+    fn scaling() {
+        let mut signal = layout_signal("0|8@1+");
+        signal.factor = 0.5;
+        signal.offset = -10.0;
+
+        assert_eq!(signal.decode(&[60]), 20.0);
+
+        let mut encoded = [0x00];
+        signal.encode(20.2, &mut encoded);
+        assert_eq!(encoded, [60]);
+
+        // Out-of-range values are saturated.
+        signal.encode(1000.0, &mut encoded);
+        assert_eq!(encoded, [0xFF]);
+        signal.encode(-100.0, &mut encoded);
+        assert_eq!(encoded, [0x00]);
+
+        signal.signed = true;
+        signal.encode(-1000.0, &mut encoded);
+        assert_eq!(encoded, [0x80]);
+        assert_eq!(signal.decode(&encoded), -74.0);
+    }
+
+    #[test]
+    fn decode_message() {
+        let dbc = Dbc::parse(DBC).unwrap();
+
+        let eec1 = dbc.message("EEC1").unwrap();
+        assert_eq!(
+            eec1.decode(&[0x00, 0x7D, 0x00, 0x40, 0x1F, 0x00, 0x00, 0x00]),
+            [("EngineSpeed", 1000.0), ("EngineTorque", 0.0)]
+        );
+
+        let status = dbc.message("Status").unwrap();
+        assert_eq!(
+            status.decode(&[0x00, 0x9C, 0xFF, 0x00]),
+            [("Mode", 0.0), ("Temperature", -10.0)]
+        );
+        assert_eq!(
+            status.decode(&[0x01, 0xB0, 0x04, 0x00]),
+            [("Mode", 1.0), ("Voltage", 12.0)]
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! CAN gateway.
+//!
+//! This module contains a model emulating a gateway ECU forwarding frames
+//! between CAN interfaces. Each route of the gateway forwards the frames
+//! received on a source interface to a destination interface, optionally:
+//! * restricted to the frames accepted by identifier filters,
+//! * with remapped identifiers,
+//! * after a constant forwarding delay.
+//!
+//! The gateway should be connected to the frame output and input of the CAN
+//! port. Since the gateway addresses the interfaces by name, the CAN port
+//! should be configured with `interface_names` enabled. Interfaces which are
+//! not opened by the CAN port, such as a virtual bus connecting simulated
+//! ECUs, can be addressed by any name not used by the CAN port.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_can_port::gateway::CanGatewayConfig;
+//! use schematic::{ConfigLoader, Format};
+//!
+//! let config = ConfigLoader::<CanGatewayConfig>::new()
+//!     .code(
+//!         r#"
+//! [[routes]]
+//! source = "can0"
+//! destination = "sim"
+//! filters = [{ id = 0x100, mask = 0x700 }]
+//! remap = [{ from = 0x123, to = 0x223 }]
+//! delay = 2
+//!
+//! [[routes]]
+//! source = "sim"
+//! destination = "can0"
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use schematic::Config;
+
+use socketcan::{CanFrame, EmbeddedFrame, ExtendedId, Frame, Id, StandardId};
+
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use nexosim::model::{Context, Model};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+
+use crate::{CanData, CanFilterConfig, CanInterface};
+
+/// CAN gateway model instance config.
+#[derive(Config, Debug)]
+pub struct CanGatewayConfig {
+    /// Forwarding routes.
+    ///
+    /// A frame is forwarded by all routes matching its interface.
+    #[setting(nested)]
+    pub routes: Vec<CanGatewayRouteConfig>,
+}
+
+/// Forwarding route configuration.
+#[derive(Config, Debug)]
+pub struct CanGatewayRouteConfig {
+    /// Source interface name.
+    pub source: String,
+
+    /// Destination interface name.
+    pub destination: String,
+
+    /// Identifier filters.
+    ///
+    /// Filters are matched against the identifiers without flags. Only frames
+    /// accepted by at least one filter are forwarded. A route without filters
+    /// forwards all frames.
+    #[setting(nested)]
+    pub filters: Vec<CanFilterConfig>,
+
+    /// Identifier remapping.
+    #[setting(nested)]
+    pub remap: Vec<CanIdRemapConfig>,
+
+    /// Forwarding delay.
+    ///
+    /// If no value is provided, frames are forwarded immediately.
+    pub delay: Option<ConfigDuration>,
+}
+
+/// Identifier remapping configuration.
+///
+/// Remapped frames keep their identifier format.
+#[derive(Config, Debug)]
+pub struct CanIdRemapConfig {
+    /// Identifier of the received frames.
+    pub from: u32,
+
+    /// Identifier of the forwarded frames.
+    pub to: u32,
+
+    /// Remapping applies to extended identifiers.
+    pub extended: bool,
+}
+
+/// Forwarding route.
+struct Route {
+    /// Source interface.
+    source: CanInterface,
+
+    /// Destination interface.
+    destination: CanInterface,
+
+    /// Identifier filters.
+    filters: Vec<(u32, u32, bool)>,
+
+    /// Identifier remapping, keyed by received identifier and format.
+    remap: HashMap<(u32, bool), u32>,
+
+    /// Forwarding delay.
+    delay: Option<Duration>,
+}
+
+impl Route {
+    /// Creates a route from its configuration.
+    fn new(config: &CanGatewayRouteConfig) -> Self {
+        Self {
+            source: CanInterface::named(&config.source),
+            destination: CanInterface::named(&config.destination),
+            filters: config
+                .filters
+                .iter()
+                .map(|filter| (filter.id, filter.mask, filter.inverted))
+                .collect(),
+            remap: config
+                .remap
+                .iter()
+                .map(|remap| ((remap.from, remap.extended), remap.to))
+                .collect(),
+            delay: config
+                .delay
+                .map(Duration::from)
+                .filter(|delay| !delay.is_zero()),
+        }
+    }
+
+    /// Checks whether the route forwards the frame.
+    fn accepts(&self, data: &CanData) -> bool {
+        if data.interface != self.source {
+            return false;
+        }
+        let id = data.frame.raw_id();
+
+        self.filters.is_empty()
+            || self
+                .filters
+                .iter()
+                .any(|&(filter_id, mask, inverted)| (id & mask == filter_id & mask) != inverted)
+    }
+
+    /// Returns the forwarded frame, or `None` if the remapped identifier is
+    /// not valid.
+    fn forward(&self, frame: CanFrame) -> Option<CanFrame> {
+        let mut frame = frame;
+        let extended = frame.is_extended();
+        if let Some(&to) = self.remap.get(&(frame.raw_id(), extended)) {
+            let id: Id = if extended {
+                ExtendedId::new(to)?.into()
+            } else {
+                StandardId::new(to.try_into().ok()?)?.into()
+            };
+            frame.set_id(id);
+        }
+
+        Some(frame)
+    }
+}
+
+/// CAN gateway model.
+pub struct CanGateway {
+    /// Forwarded frames -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// Forwarding routes.
+    routes: Vec<Route>,
+}
+
+impl CanGateway {
+    /// Creates a new gateway.
+    pub fn new(config: CanGatewayConfig) -> Self {
+        Self {
+            frame_out: Output::new(),
+            routes: config.routes.iter().map(Route::new).collect(),
+        }
+    }
+
+    /// Received frame -- input port.
+    pub async fn frame_in(&mut self, data: CanData, cx: &mut Context<Self>) {
+        let mut forwarded = Vec::new();
+        for route in self.routes.iter().filter(|route| route.accepts(&data)) {
+            let Some(frame) = route.forward(data.frame) else {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    "Frame {:X} cannot be remapped for interface {}, ignoring.",
+                    data.frame.raw_id(),
+                    route.destination
+                );
+                continue;
+            };
+            let data = CanData::new(route.destination, frame);
+            match route.delay {
+                Some(delay) => cx.schedule_event(delay, Self::emit, data).unwrap(),
+                None => forwarded.push(data),
+            }
+        }
+        for data in forwarded {
+            self.frame_out.send(data).await;
+        }
+    }
+
+    /// Emits a delayed frame.
+    async fn emit(&mut self, data: CanData) {
+        self.frame_out.send(data).await;
+    }
+}
+
+impl Model for CanGateway {}
+
+impl fmt::Debug for CanGateway {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CanGateway")
+            .field("routes", &self.routes.len())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,940 @@
+//! J1939 layer.
+//!
+//! This module contains a model implementing the SAE J1939 network layer on
+//! top of the raw CAN frames of a [`CanPort`](crate::CanPort):
+//! * PGN-based addressing of the 29-bit identifiers,
+//! * address claiming, including arbitrary address selection for ECUs whose
+//!   NAME is arbitrary address capable,
+//! * transport protocol reassembly and segmentation of messages longer than
+//!   8 bytes, as broadcast announce messages (TP.BAM) or as connection mode
+//!   data transfers (TP.CM RTS/CTS).
+//!
+//! The layer handles a single CAN interface and should be connected to the
+//! frame output and input of the CAN port. Since the layer addresses its
+//! interface by name, the CAN port should be configured with
+//! `interface_names` enabled.
+//!
+//! Transport protocol timeouts are not enforced: incomplete sessions are
+//! discarded when superseded by a new session between the same nodes.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_can_port::j1939::{J1939Layer, J1939Message};
+//!
+//! // Arbitrary address capable ECU with preferred address 0x80.
+//! let layer = J1939Layer::new("can0", 0x8000_0000_0000_1234, 0x80);
+//!
+//! // Connect `layer.frame_out` to `CanPort::frame_in`, `CanPort::frame_out`
+//! // to `J1939Layer::frame_in` and the message ports to the bench models,
+//! // which send messages such as:
+//! let message = J1939Message::new(61444, vec![0xFF; 8]).with_priority(3);
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use socketcan::{CanFrame, EmbeddedFrame, ExtendedId, Id};
+
+#[cfg(feature = "tracing")]
+use tracing::{info, warn};
+
+use nexosim::model::{Context, InitializedModel, Model};
+use nexosim::ports::Output;
+use nexosim::time::MonotonicTime;
+
+use crate::{CanData, CanInterface};
+
+/// Global destination address.
+pub const GLOBAL_ADDRESS: u8 = 0xFF;
+
+/// Null source address, used by ECUs which could not claim an address.
+pub const NULL_ADDRESS: u8 = 0xFE;
+
+/// Request PGN.
+pub const PGN_REQUEST: u32 = 0xEA00;
+
+/// Address claimed PGN.
+pub const PGN_ADDRESS_CLAIMED: u32 = 0xEE00;
+
+/// Transport protocol connection management PGN.
+pub const PGN_TP_CM: u32 = 0xEC00;
+
+/// Transport protocol data transfer PGN.
+pub const PGN_TP_DT: u32 = 0xEB00;
+
+/// Maximum length of a message sent with the transport protocol.
+pub const MAX_MESSAGE_LEN: usize = 1785;
+
+/// Default priority of messages.
+const DEFAULT_PRIORITY: u8 = 6;
+
+/// Priority of network management and transport protocol messages.
+const CONTROL_PRIORITY: u8 = 7;
+
+/// TP.CM request to send.
+const TP_RTS: u8 = 16;
+
+/// TP.CM clear to send.
+const TP_CTS: u8 = 17;
+
+/// TP.CM end of message acknowledgment.
+const TP_EOMA: u8 = 19;
+
+/// TP.CM broadcast announce message.
+const TP_BAM: u8 = 32;
+
+/// TP.CM connection abort.
+const TP_ABORT: u8 = 255;
+
+/// Range of addresses used for arbitrary address selection.
+const ARBITRARY_ADDRESSES: std::ops::RangeInclusive<u8> = 128..=247;
+
+/// J1939 message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct J1939Message {
+    /// Priority, from 0 (highest) to 7 (lowest).
+    pub priority: u8,
+
+    /// Parameter group number.
+    pub pgn: u32,
+
+    /// Source address.
+    ///
+    /// The source address of transmitted messages is set by the layer.
+    pub source: u8,
+
+    /// Destination address.
+    ///
+    /// Messages with a PDU2 parameter group number are always broadcast to
+    /// the [`GLOBAL_ADDRESS`].
+    pub destination: u8,
+
+    /// Message data.
+    pub data: Vec<u8>,
+}
+
+impl J1939Message {
+    /// Creates a new broadcast message with the default priority.
+    pub fn new(pgn: u32, data: Vec<u8>) -> Self {
+        Self {
+            priority: DEFAULT_PRIORITY,
+            pgn,
+            source: NULL_ADDRESS,
+            destination: GLOBAL_ADDRESS,
+            data,
+        }
+    }
+
+    /// Sets the priority.
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Sets the destination address.
+    pub fn with_destination(mut self, destination: u8) -> Self {
+        self.destination = destination;
+        self
+    }
+
+    /// Returns `true` if the parameter group number is destination specific
+    /// (PDU1 format).
+    pub fn is_pdu1(&self) -> bool {
+        is_pdu1(self.pgn)
+    }
+}
+
+/// Returns `true` if the parameter group number is destination specific.
+fn is_pdu1(pgn: u32) -> bool {
+    (pgn >> 8) & 0xFF < 240
+}
+
+/// Encodes a 29-bit identifier.
+fn encode_id(priority: u8, pgn: u32, source: u8, destination: u8) -> u32 {
+    let pgn = if is_pdu1(pgn) {
+        (pgn & 0x3FF00) | destination as u32
+    } else {
+        pgn & 0x3FFFF
+    };
+
+    ((priority as u32 & 0x7) << 26) | (pgn << 8) | source as u32
+}
+
+/// Decodes a 29-bit identifier into the priority, parameter group number,
+/// source and destination addresses.
+fn decode_id(id: u32) -> (u8, u32, u8, u8) {
+    let priority = ((id >> 26) & 0x7) as u8;
+    let pgn = (id >> 8) & 0x3FFFF;
+    let source = id as u8;
+    if is_pdu1(pgn) {
+        (priority, pgn & 0x3FF00, source, pgn as u8)
+    } else {
+        (priority, pgn, source, GLOBAL_ADDRESS)
+    }
+}
+
+/// Encodes a parameter group number on 3 bytes.
+fn pgn_bytes(pgn: u32) -> [u8; 3] {
+    let [b0, b1, b2, _] = pgn.to_le_bytes();
+    [b0, b1, b2]
+}
+
+/// Decodes a parameter group number from 3 bytes.
+fn pgn_from_bytes(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0])
+}
+
+/// Transport protocol reception session.
+#[derive(Debug)]
+struct RxSession {
+    /// Parameter group number of the message.
+    pgn: u32,
+
+    /// Priority of the connection management message.
+    priority: u8,
+
+    /// Announced message length.
+    size: usize,
+
+    /// Announced number of packets.
+    packets: u8,
+
+    /// Received data.
+    data: Vec<u8>,
+
+    /// Next expected sequence number.
+    next: u8,
+
+    /// Session is a connection mode data transfer.
+    is_connection: bool,
+}
+
+/// Transport protocol transmission session.
+#[derive(Debug)]
+struct TxSession {
+    /// Message being transmitted.
+    message: J1939Message,
+}
+
+/// J1939 layer model.
+///
+/// This model converts raw CAN frames to J1939 messages and vice versa, claims
+/// the source address of the ECU and runs the transport protocol.
+pub struct J1939Layer {
+    /// CAN frames to be transmitted -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// Received J1939 messages -- output port.
+    pub message_out: Output<J1939Message>,
+
+    /// Claimed source address, or `None` if no address could be claimed --
+    /// output port.
+    pub address_out: Output<Option<u8>>,
+
+    /// CAN interface.
+    interface: CanInterface,
+
+    /// ECU NAME.
+    name: u64,
+
+    /// Preferred source address.
+    preferred_address: u8,
+
+    /// Claimed source address.
+    address: Option<u8>,
+
+    /// Messages addressed to other nodes are forwarded.
+    promiscuous: bool,
+
+    /// Time between consecutive broadcast data transfer packets.
+    bam_packet_gap: Duration,
+
+    /// Time at which the last scheduled broadcast packet is sent, if ever
+    /// used.
+    bam_busy_until: Option<MonotonicTime>,
+
+    /// NAMEs of the other nodes, by address.
+    nodes: HashMap<u8, u64>,
+
+    /// Reception sessions, by source and destination address.
+    rx_sessions: HashMap<(u8, u8), RxSession>,
+
+    /// Transmission sessions, by destination address.
+    tx_sessions: HashMap<u8, TxSession>,
+}
+
+impl J1939Layer {
+    /// Creates a new J1939 layer for the ECU with the provided 64-bit NAME,
+    /// claiming the preferred source address at initialization.
+    pub fn new(interface: impl Into<CanInterface>, name: u64, preferred_address: u8) -> Self {
+        Self {
+            frame_out: Output::new(),
+            message_out: Output::new(),
+            address_out: Output::new(),
+            interface: interface.into(),
+            name,
+            preferred_address,
+            address: None,
+            promiscuous: false,
+            bam_packet_gap: Duration::from_millis(50),
+            bam_busy_until: None,
+            nodes: HashMap::new(),
+            rx_sessions: HashMap::new(),
+            tx_sessions: HashMap::new(),
+        }
+    }
+
+    /// Forwards messages addressed to other nodes as well.
+    pub fn with_promiscuous(mut self, promiscuous: bool) -> Self {
+        self.promiscuous = promiscuous;
+        self
+    }
+
+    /// Sets the time between consecutive broadcast data transfer packets.
+    ///
+    /// The J1939 standard requires between 50 and 200 ms. With a zero gap,
+    /// all packets are sent at once.
+    pub fn with_bam_packet_gap(mut self, gap: Duration) -> Self {
+        self.bam_packet_gap = gap;
+        self
+    }
+
+    /// Returns `true` if the ECU NAME is arbitrary address capable.
+    fn is_arbitrary_address_capable(&self) -> bool {
+        self.name >> 63 != 0
+    }
+
+    /// Received CAN frame -- input port.
+    pub async fn frame_in(&mut self, data: CanData) {
+        if data.interface != self.interface {
+            return;
+        }
+        let (CanFrame::Data(frame), Id::Extended(id)) = (data.frame, data.frame.id()) else {
+            return;
+        };
+        let (priority, pgn, source, destination) = decode_id(id.as_raw());
+        let payload = frame.data();
+
+        let is_local = destination == GLOBAL_ADDRESS || Some(destination) == self.address;
+        match pgn {
+            PGN_ADDRESS_CLAIMED if payload.len() == 8 => {
+                let name = u64::from_le_bytes(payload.try_into().unwrap());
+                self.on_address_claimed(source, name).await;
+            }
+            PGN_REQUEST
+                if is_local
+                    && payload.len() >= 3
+                    && pgn_from_bytes(payload) == PGN_ADDRESS_CLAIMED =>
+            {
+                self.send_address_claimed().await;
+            }
+            PGN_TP_CM if payload.len() == 8 => {
+                if is_local || self.promiscuous {
+                    self.on_connection_management(priority, source, destination, payload)
+                        .await;
+                }
+                return;
+            }
+            PGN_TP_DT if payload.len() == 8 => {
+                if is_local || self.promiscuous {
+                    self.on_data_transfer(source, destination, payload).await;
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        if is_local || self.promiscuous {
+            self.message_out
+                .send(J1939Message {
+                    priority,
+                    pgn,
+                    source,
+                    destination,
+                    data: payload.to_vec(),
+                })
+                .await;
+        }
+    }
+
+    /// J1939 message to be transmitted -- input port.
+    ///
+    /// Messages are dropped if no address is claimed, if they are too long or
+    /// if a connection mode transfer to the same destination is in progress.
+    pub async fn message_in(&mut self, mut message: J1939Message, cx: &mut Context<Self>) {
+        let Some(address) = self.address else {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Dropping J1939 message PGN {}: no address claimed.",
+                message.pgn
+            );
+            return;
+        };
+        message.source = address;
+        if !message.is_pdu1() {
+            message.destination = GLOBAL_ADDRESS;
+        }
+
+        if message.data.len() <= 8 {
+            let frame = self.frame(
+                message.priority,
+                message.pgn,
+                message.destination,
+                &message.data,
+            );
+            self.frame_out.send(frame).await;
+            return;
+        }
+        if message.data.len() > MAX_MESSAGE_LEN {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Dropping J1939 message PGN {}: {} bytes exceed the transport protocol capacity.",
+                message.pgn,
+                message.data.len()
+            );
+            return;
+        }
+
+        if message.destination == GLOBAL_ADDRESS {
+            let frames = self.broadcast_frames(&message);
+
+            // Broadcast transfers are queued after the pending ones.
+            let now = cx.time();
+            let start = self.bam_busy_until.filter(|t| *t > now).unwrap_or(now);
+            let count = frames.len() as u32;
+            for (i, frame) in frames.into_iter().enumerate() {
+                let deadline = start + self.bam_packet_gap * i as u32;
+                if deadline > now {
+                    cx.schedule_event(deadline, Self::emit, frame).unwrap();
+                } else {
+                    self.frame_out.send(frame).await;
+                }
+            }
+            self.bam_busy_until = Some(start + self.bam_packet_gap * count);
+        } else {
+            if self.tx_sessions.contains_key(&message.destination) {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    "Dropping J1939 message PGN {}: transfer to {} in progress.",
+                    message.pgn, message.destination
+                );
+                return;
+            }
+            let frame = self.announce_frame(TP_RTS, &message);
+            self.tx_sessions
+                .insert(message.destination, TxSession { message });
+            self.frame_out.send(frame).await;
+        }
+    }
+
+    /// Emits a scheduled frame.
+    async fn emit(&mut self, frame: CanData) {
+        self.frame_out.send(frame).await;
+    }
+
+    /// Handles a transport protocol connection management message.
+    async fn on_connection_management(
+        &mut self,
+        priority: u8,
+        source: u8,
+        destination: u8,
+        payload: &[u8],
+    ) {
+        let pgn = pgn_from_bytes(&payload[5..8]);
+        let size = u16::from_le_bytes([payload[1], payload[2]]) as usize;
+        match payload[0] {
+            TP_BAM | TP_RTS => {
+                let is_connection = payload[0] == TP_RTS;
+                let packets = payload[3];
+                self.rx_sessions.insert(
+                    (source, destination),
+                    RxSession {
+                        pgn,
+                        priority,
+                        size,
+                        packets,
+                        data: Vec::with_capacity(packets as usize * 7),
+                        next: 1,
+                        is_connection,
+                    },
+                );
+                // Only answer requests actually addressed to this node.
+                if is_connection && Some(destination) == self.address {
+                    let [p0, p1, p2] = pgn_bytes(pgn);
+                    let answer = [TP_CTS, packets, 1, 0xFF, 0xFF, p0, p1, p2];
+                    let frame = self.frame(CONTROL_PRIORITY, PGN_TP_CM, source, &answer);
+                    self.frame_out.send(frame).await;
+                }
+            }
+            TP_CTS if Some(destination) == self.address => {
+                let (count, next) = (payload[1] as usize, payload[2] as usize);
+                for frame in self.connection_frames(source, count, next) {
+                    self.frame_out.send(frame).await;
+                }
+            }
+            TP_EOMA if Some(destination) == self.address => {
+                self.tx_sessions.remove(&source);
+            }
+            TP_ABORT => {
+                if Some(destination) == self.address {
+                    #[cfg(feature = "tracing")]
+                    warn!("J1939 transfer of PGN {} aborted by {}.", pgn, source);
+                    self.tx_sessions.remove(&source);
+                }
+                self.rx_sessions.remove(&(source, destination));
+            }
+            _ => {}
+        }
+    }
+
+    /// Handles a transport protocol data transfer packet.
+    async fn on_data_transfer(&mut self, source: u8, destination: u8, payload: &[u8]) {
+        let Some(session) = self.receive_packet(source, destination, payload) else {
+            return;
+        };
+        if session.is_connection && Some(destination) == self.address {
+            let size = (session.size as u16).to_le_bytes();
+            let [p0, p1, p2] = pgn_bytes(session.pgn);
+            let ack = [TP_EOMA, size[0], size[1], session.packets, 0xFF, p0, p1, p2];
+            let frame = self.frame(CONTROL_PRIORITY, PGN_TP_CM, source, &ack);
+            self.frame_out.send(frame).await;
+        }
+        self.message_out
+            .send(J1939Message {
+                priority: session.priority,
+                pgn: session.pgn,
+                source,
+                destination,
+                data: session.data,
+            })
+            .await;
+    }
+
+    /// Adds a data transfer packet to its reception session, returning the
+    /// session once all packets are received.
+    fn receive_packet(&mut self, source: u8, destination: u8, payload: &[u8]) -> Option<RxSession> {
+        let key = (source, destination);
+        let session = self.rx_sessions.get_mut(&key)?;
+        if payload[0] != session.next {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Discarding J1939 transfer of PGN {} from {}: unexpected sequence number.",
+                session.pgn, source
+            );
+            self.rx_sessions.remove(&key);
+            return None;
+        }
+        session.data.extend_from_slice(&payload[1..]);
+        session.next = session.next.wrapping_add(1);
+        if session.data.len() < session.packets as usize * 7 {
+            return None;
+        }
+
+        let mut session = self.rx_sessions.remove(&key)?;
+        session.data.truncate(session.size);
+
+        Some(session)
+    }
+
+    /// Handles an address claim of another node.
+    async fn on_address_claimed(&mut self, source: u8, name: u64) {
+        if name == self.name {
+            return;
+        }
+        self.nodes.retain(|_, node| *node != name);
+        if source != NULL_ADDRESS {
+            self.nodes.insert(source, name);
+        }
+        if Some(source) != self.address {
+            return;
+        }
+
+        // The NAME with the lowest value has the highest priority.
+        if self.name < name {
+            self.send_address_claimed().await;
+            return;
+        }
+        let address = if self.is_arbitrary_address_capable() {
+            ARBITRARY_ADDRESSES
+                .clone()
+                .find(|address| !self.nodes.contains_key(address))
+        } else {
+            None
+        };
+        self.claim(address).await;
+    }
+
+    /// Claims an address, or reports that no address could be claimed.
+    async fn claim(&mut self, address: Option<u8>) {
+        self.address = address;
+        self.tx_sessions.clear();
+        #[cfg(feature = "tracing")]
+        match address {
+            Some(address) => info!("Claiming J1939 address {}.", address),
+            None => warn!("Cannot claim a J1939 address."),
+        }
+        self.send_address_claimed().await;
+        self.address_out.send(address).await;
+    }
+
+    /// Sends the address claimed message, or the cannot claim message if no
+    /// address is claimed.
+    async fn send_address_claimed(&mut self) {
+        let source = self.address.unwrap_or(NULL_ADDRESS);
+        let id = encode_id(
+            DEFAULT_PRIORITY,
+            PGN_ADDRESS_CLAIMED,
+            source,
+            GLOBAL_ADDRESS,
+        );
+        let frame = CanFrame::new(
+            Id::Extended(ExtendedId::new(id).unwrap()),
+            &self.name.to_le_bytes(),
+        )
+        .unwrap();
+        self.frame_out
+            .send(CanData::new(self.interface, frame))
+            .await;
+    }
+
+    /// Creates a frame sent from the claimed address.
+    fn frame(&self, priority: u8, pgn: u32, destination: u8, data: &[u8]) -> CanData {
+        let source = self.address.unwrap_or(NULL_ADDRESS);
+        let id = encode_id(priority, pgn, source, destination);
+        let frame = CanFrame::new(Id::Extended(ExtendedId::new(id).unwrap()), data).unwrap();
+
+        CanData::new(self.interface, frame)
+    }
+
+    /// Creates the connection management frame announcing a multi-packet
+    /// message, as a broadcast announce or a request to send.
+    fn announce_frame(&self, command: u8, message: &J1939Message) -> CanData {
+        let size = (message.data.len() as u16).to_le_bytes();
+        let packets = message.data.len().div_ceil(7) as u8;
+        let [p0, p1, p2] = pgn_bytes(message.pgn);
+        let announce = [command, size[0], size[1], packets, 0xFF, p0, p1, p2];
+
+        self.frame(CONTROL_PRIORITY, PGN_TP_CM, message.destination, &announce)
+    }
+
+    /// Creates the frames of a broadcast transfer.
+    fn broadcast_frames(&self, message: &J1939Message) -> Vec<CanData> {
+        let mut frames = vec![self.announce_frame(TP_BAM, message)];
+        frames.extend(
+            message
+                .data
+                .chunks(7)
+                .enumerate()
+                .map(|(seq, chunk)| self.data_transfer_frame(GLOBAL_ADDRESS, seq as u8 + 1, chunk)),
+        );
+
+        frames
+    }
+
+    /// Creates the data transfer frames of a connection mode transfer cleared
+    /// to send, starting at packet `next`.
+    fn connection_frames(&self, destination: u8, count: usize, next: usize) -> Vec<CanData> {
+        let Some(session) = self.tx_sessions.get(&destination) else {
+            return Vec::new();
+        };
+
+        session
+            .message
+            .data
+            .chunks(7)
+            .enumerate()
+            .skip(next.saturating_sub(1))
+            .take(count)
+            .map(|(seq, chunk)| self.data_transfer_frame(destination, seq as u8 + 1, chunk))
+            .collect()
+    }
+
+    /// Creates a data transfer frame, padding the last packet.
+    fn data_transfer_frame(&self, destination: u8, seq: u8, chunk: &[u8]) -> CanData {
+        let mut packet = [0xFF; 8];
+        packet[0] = seq;
+        packet[1..1 + chunk.len()].copy_from_slice(chunk);
+
+        self.frame(CONTROL_PRIORITY, PGN_TP_DT, destination, &packet)
+    }
+}
+
+impl Model for J1939Layer {
+    async fn init(mut self, _: &mut Context<Self>) -> InitializedModel<Self> {
+        let address = self.preferred_address;
+        self.claim(Some(address)).await;
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for J1939Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("J1939Layer")
+            .field("interface", &self.interface)
+            .field("name", &self.name)
+            .field("address", &self.address)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::pin;
+    use std::task::{Poll, Waker};
+
+    use super::*;
+
+    const NAME_A: u64 = 0x8000_0000_0000_0010;
+    const NAME_B: u64 = 0x8000_0000_0000_0020;
+
+    /// Runs a future which is expected to complete without blocking, such as
+    /// an input of a model whose outputs are not connected.
+    fn ready<F: Future>(future: F) -> F::Output {
+        let mut cx = std::task::Context::from_waker(Waker::noop());
+        match pin!(future).poll(&mut cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("the future should complete immediately"),
+        }
+    }
+
+    fn layer(name: u64, address: u8) -> J1939Layer {
+        let mut layer = J1939Layer::new("can0", name, address);
+        layer.address = Some(address);
+
+        layer
+    }
+
+    fn id_and_data(frame: &CanData) -> ((u8, u32, u8, u8), &[u8]) {
+        let Id::Extended(id) = frame.frame.id() else {
+            panic!("J1939 frames should have an extended identifier");
+        };
+
+        (decode_id(id.as_raw()), frame.frame.data())
+    }
+
+    /// Delivers a frame to a layer, returning the session completed by a data
+    /// transfer packet.
+    fn receive(layer: &mut J1939Layer, frame: &CanData) -> Option<RxSession> {
+        let ((_, pgn, source, destination), payload) = id_and_data(frame);
+        if pgn == PGN_TP_DT {
+            return layer.receive_packet(source, destination, payload);
+        }
+        ready(layer.frame_in(*frame));
+
+        None
+    }
+
+    fn message(destination: u8, len: usize) -> J1939Message {
+        let mut message = J1939Message::new(0xEF00, (0..len as u8).collect());
+        message.source = 0x80;
+        message.destination = destination;
+
+        message
+    }
+
+    #[test]
+    fn id_encoding() {
+        let id = encode_id(3, 0xEF00, 0x80, 0x25);
+        assert_eq!(id, 0x0CEF_2580);
+        assert_eq!(decode_id(id), (3, 0xEF00, 0x80, 0x25));
+
+        // The destination of PDU2 parameter groups is always global.
+        let id = encode_id(6, 61444, 0x00, 0x25);
+        assert_eq!(id, 0x18F0_0400);
+        assert_eq!(decode_id(id), (6, 61444, 0x00, GLOBAL_ADDRESS));
+
+        // Data page.
+        let id = encode_id(7, 0x1EF00, 0x01, 0x02);
+        assert_eq!(decode_id(id), (7, 0x1EF00, 0x01, 0x02));
+    }
+
+    #[test]
+    fn pgn_encoding() {
+        assert!(is_pdu1(PGN_TP_CM));
+        assert!(!is_pdu1(PGN_ADDRESS_CLAIMED + 0x0200));
+        assert_eq!(pgn_bytes(0x1FECA), [0xCA, 0xFE, 0x01]);
+        assert_eq!(pgn_from_bytes(&pgn_bytes(0x1FECA)), 0x1FECA);
+    }
+
+    #[test]
+    fn broadcast_transfer() {
+        let sender = layer(NAME_A, 0x80);
+        let mut receiver = layer(NAME_B, 0x90);
+        let message = message(GLOBAL_ADDRESS, 20);
+
+        let frames = sender.broadcast_frames(&message);
+        assert_eq!(frames.len(), 4);
+        let ((priority, pgn, source, destination), announce) = id_and_data(&frames[0]);
+        assert_eq!(
+            (priority, pgn, source, destination),
+            (CONTROL_PRIORITY, PGN_TP_CM, 0x80, GLOBAL_ADDRESS)
+        );
+        assert_eq!(announce, [TP_BAM, 20, 0, 3, 0xFF, 0x00, 0xEF, 0x00]);
+        // The last packet is padded.
+        assert_eq!(id_and_data(&frames[3]).1, [3, 14, 15, 16, 17, 18, 19, 0xFF]);
+
+        let sessions: Vec<RxSession> = frames
+            .iter()
+            .filter_map(|frame| receive(&mut receiver, frame))
+            .collect();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].pgn, 0xEF00);
+        assert_eq!(sessions[0].data, message.data);
+        assert!(!sessions[0].is_connection);
+        assert!(receiver.rx_sessions.is_empty());
+    }
+
+    #[test]
+    fn connection_transfer() {
+        let mut sender = layer(NAME_A, 0x80);
+        let mut receiver = layer(NAME_B, 0x90);
+        let message = message(0x90, 20);
+
+        let request = sender.announce_frame(TP_RTS, &message);
+        assert_eq!(
+            id_and_data(&request).1,
+            [TP_RTS, 20, 0, 3, 0xFF, 0x00, 0xEF, 0x00]
+        );
+        sender.tx_sessions.insert(
+            0x90,
+            TxSession {
+                message: message.clone(),
+            },
+        );
+        assert!(receive(&mut receiver, &request).is_none());
+        assert!(receiver.rx_sessions[&(0x80, 0x90)].is_connection);
+
+        // Clear to send 2 packets, then the last one.
+        let packets = sender.connection_frames(0x90, 2, 1);
+        assert_eq!(packets.len(), 2);
+        assert!(packets.iter().all(|frame| id_and_data(frame).0.3 == 0x90));
+        assert!(
+            packets
+                .iter()
+                .all(|frame| receive(&mut receiver, frame).is_none())
+        );
+        let packets = sender.connection_frames(0x90, 2, 3);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(id_and_data(&packets[0]).1[0], 3);
+        let session = receive(&mut receiver, &packets[0]).unwrap();
+        assert_eq!(session.data, message.data);
+        assert!(session.is_connection);
+
+        // The acknowledgment closes the transmission session.
+        let ack = receiver.frame(
+            CONTROL_PRIORITY,
+            PGN_TP_CM,
+            0x80,
+            &[TP_EOMA, 20, 0, 3, 0xFF, 0x00, 0xEF, 0x00],
+        );
+        receive(&mut sender, &ack);
+        assert!(sender.tx_sessions.is_empty());
+        assert!(sender.connection_frames(0x90, 1, 1).is_empty());
+    }
+
+    #[test]
+    fn unexpected_sequence_number() {
+        let sender = layer(NAME_A, 0x80);
+        let mut receiver = layer(NAME_B, 0x90);
+        let frames = sender.broadcast_frames(&message(GLOBAL_ADDRESS, 20));
+
+        receive(&mut receiver, &frames[0]);
+        assert!(receive(&mut receiver, &frames[2]).is_none());
+        assert!(receiver.rx_sessions.is_empty());
+
+        // The remaining packets of the discarded session are ignored.
+        assert!(receive(&mut receiver, &frames[3]).is_none());
+    }
+
+    #[test]
+    fn superseded_session() {
+        let sender = layer(NAME_A, 0x80);
+        let mut receiver = layer(NAME_B, 0x90);
+        let first = sender.broadcast_frames(&message(GLOBAL_ADDRESS, 20));
+        let second = message(GLOBAL_ADDRESS, 10);
+
+        receive(&mut receiver, &first[0]);
+        receive(&mut receiver, &first[1]);
+        let sessions: Vec<RxSession> = sender
+            .broadcast_frames(&second)
+            .iter()
+            .filter_map(|frame| receive(&mut receiver, frame))
+            .collect();
+
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].data, second.data);
+    }
+
+    #[test]
+    fn aborted_session() {
+        let mut sender = layer(NAME_A, 0x80);
+        let mut receiver = layer(NAME_B, 0x90);
+        let message = message(0x90, 20);
+
+        sender.tx_sessions.insert(
+            0x90,
+            TxSession {
+                message: message.clone(),
+            },
+        );
+        receive(&mut receiver, &sender.announce_frame(TP_RTS, &message));
+        assert_eq!(receiver.rx_sessions.len(), 1);
+
+        let abort = [TP_ABORT, 1, 0xFF, 0xFF, 0xFF, 0x00, 0xEF, 0x00];
+        receive(
+            &mut sender,
+            &receiver.frame(CONTROL_PRIORITY, PGN_TP_CM, 0x80, &abort),
+        );
+        receive(
+            &mut receiver,
+            &sender.frame(CONTROL_PRIORITY, PGN_TP_CM, 0x90, &abort),
+        );
+
+        assert!(sender.tx_sessions.is_empty());
+        assert!(receiver.rx_sessions.is_empty());
+    }
+
+    #[test]
+    fn address_claim() {
+        let claim = |source: u8, name: u64| {
+            let id = encode_id(
+                DEFAULT_PRIORITY,
+                PGN_ADDRESS_CLAIMED,
+                source,
+                GLOBAL_ADDRESS,
+            );
+            let frame = CanFrame::new(
+                Id::Extended(ExtendedId::new(id).unwrap()),
+                &name.to_le_bytes(),
+            )
+            .unwrap();
+
+            CanData::new(CanInterface::from("can0"), frame)
+        };
+
+        // A contender with a higher NAME loses.
+        let mut ecu = layer(NAME_A, 0x80);
+        ready(ecu.frame_in(claim(0x80, NAME_B)));
+        assert_eq!(ecu.address, Some(0x80));
+
+        // An arbitrary address capable ECU moves to a free address.
+        let mut ecu = layer(NAME_B, 0x80);
+        ready(ecu.frame_in(claim(0x81, 0x01)));
+        ready(ecu.frame_in(claim(0x80, NAME_A)));
+        assert_eq!(ecu.address, Some(0x82));
+
+        // Other ECUs cannot claim an address.
+        let mut ecu = layer(0x10, 0x80);
+        ecu.tx_sessions.insert(
+            0x90,
+            TxSession {
+                message: message(0x90, 20),
+            },
+        );
+        ready(ecu.frame_in(claim(0x80, 0x01)));
+        assert_eq!(ecu.address, None);
+        assert!(ecu.tx_sessions.is_empty());
+    }
+}
@@ -5,31 +5,137 @@
 //!   simulation,
 //! * outputs data from the simulation to the specified CAN ports.
 //!
-//! Note: data sent by the CAN port is injected back into the simulation.
+//! Note: data sent by the CAN port is injected back into the simulation when
+//! `recv_own_msgs` is enabled, in which case it is tagged as an own frame; see
+//! the `loopback` and `recv_own_msgs` configurations. Own frames can also be
+//! used to confirm the transmission of the frames on the bus, with the
+//! timestamp of their actual transmission; see the `tx_confirmations`
+//! configuration.
+//!
+//! Error frames received on the CAN ports are reported on a dedicated output,
+//! except when the interfaces are accessed through a shared port broker. The
+//! controller state and error counters of the interfaces can be queried
+//! periodically through netlink and are reported on another output. Frames
+//! which cannot be transmitted, e.g. when bursts overflow the send buffer of an
+//! interface, are reported on the transmit failure output; see the `tx_policy`
+//! configuration.
+//!
+//! Received frames are forwarded each time the model processes the frames
+//! read by its I/O thread. For hardware-in-the-loop setups, they can instead
+//! be injected at the simulation time matching their wall-clock arrival by
+//! enabling `time_alignment` in the configuration, see the
+//! [`alignment`](nexosim_io_utils::alignment) module.
+//!
+//! CAN interfaces are addressed either by their index in the `interfaces`
+//! configuration or by their name, see [`CanInterface`]. Name addressing is not
+//! affected by changes in the ordering of the configured interfaces. Index
+//! addressing remains the default: unless `interface_names` is enabled,
+//! received data is addressed by [`CanInterface::Index`], which compares equal
+//! to the plain index, and indices convert into addresses, so code written for
+//! index addressing only needs [`CanInterface::as_index`] where the index
+//! itself is used. Interfaces can also be attached and detached while the
+//! simulation runs, e.g. to emulate hot-plugged CAN adapters.
+//!
+//! Higher-layer protocol models to be connected to the CAN port are provided by
+//! the [`j1939`], [`nmea2000`], [`canopen`], [`cyphal`], [`uds`] and [`xcp`]
+//! modules. Other companion models include:
+//! * a model offloading cyclic transmissions to the kernel broadcast manager,
+//!   in the [`bcm`] module,
+//! * a signal codec driven by DBC files, in the [`dbc`] module,
+//! * candump log recording and replay models, in the [`candump`] module,
+//! * a gateway ECU forwarding frames between interfaces, in the [`gateway`]
+//!   module,
+//! * a traffic shaper limiting the rate of the transmitted frames, in the
+//!   [`shaper`] module.
+//!
+//! The CAN interfaces are accessed through SocketCAN by default. Adapters
+//! speaking the SLCAN protocol over a serial device can be used instead by
+//! setting the `backend` configuration to `slcan`, see the `slcan` module
+//! (enabled by the `slcan` feature), and remote CAN buses can be reached over
+//! UDP with the `cannelloni` backend, see the [`cannelloni`] module, or through
+//! a socketcand server with the `socketcand` backend, see the [`socketcand`]
+//! module. Other adapters, such as gs_usb or PCAN devices, can be plugged in by
+//! implementing [`CanBackend`], setting the `backend` configuration to `custom`
+//! and providing a [`CanBackendFactory`] to the model prototype.
+//!
+//! CAN interfaces can be shared by several simulation processes by opening them
+//! in a [`SharedPortBroker`] created with [`shared_port_broker`] and by setting
+//! the `broker_path` configuration of each model to the broker socket path.
+//!
+//! With the `tracing` feature, the events of each model instance are recorded
+//! within a `can_port` span carrying the model name, with fields such as
+//! `interface`, `direction`, `id`, `length` and `data`, so that traces can be
+//! filtered and correlated per link. Payloads are recorded as compact
+//! hexadecimal dumps, see the [`hexdump`](nexosim_io_utils::hexdump) module.
 //!
 //! [NX]: https://github.com/asynchronics/nexosim
 #![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
 #![forbid(unsafe_code)]
 
+pub mod bcm;
+pub mod candump;
+pub mod cannelloni;
+pub mod canopen;
+pub mod cyphal;
+pub mod dbc;
+pub mod gateway;
+pub mod j1939;
+pub mod nmea2000;
+pub mod shaper;
+#[cfg(feature = "slcan")]
+pub mod slcan;
+pub mod socketcand;
+pub mod uds;
+pub mod xcp;
+
+use std::collections::VecDeque;
+use std::error;
 use std::fmt;
 use std::io::{Error, ErrorKind, Result};
 use std::os::unix::{io::AsRawFd, prelude::RawFd};
-use std::time::Duration;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use mio::event::Source;
+use mio::net::UnixStream;
 use mio::{Interest, Registry, Token, unix::SourceFd};
 
-use schematic::Config;
+use schematic::{Config, ConfigEnum, ConfigError, ValidateError, ValidateResult};
+use serde::{Deserialize, Serialize};
 
-use socketcan::{BlockingCan, CanFrame, CanSocket, Error as CanError, Socket};
+use socketcan::{
+    BlockingCan, CanFilter, CanFrame, CanSocket, CanTimestamps, EmbeddedFrame, Error as CanError,
+    ExtendedId, Frame, Id, SOF_TIMESTAMPING_OPT_CMSG, SOF_TIMESTAMPING_RAW_HARDWARE,
+    SOF_TIMESTAMPING_RX_HARDWARE, SOF_TIMESTAMPING_RX_SOFTWARE, SOF_TIMESTAMPING_SOFTWARE, Socket,
+    SocketOptions, StandardId,
+    errors::CanError as CanBusError,
+    nl::{CanInterface as NlInterface, CanState},
+};
 
 #[cfg(feature = "tracing")]
-use tracing::info;
+use tracing::{Span, info, info_span, warn};
 
 use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
-use nexosim::ports::Output;
+use nexosim::ports::{InputFn, Output};
 
-use nexosim_io_utils::port::{IoPort, IoThread};
+use nexosim_io_utils::alignment::{PartialTimeAlignmentConfig, TimeAlignment, TimeAlignmentConfig};
+use nexosim_io_utils::broker::{BrokerClient, BrokerCodec, BrokerFilter, SharedPortBroker};
+use nexosim_io_utils::config;
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::fault::Faulty;
+#[cfg(feature = "tracing")]
+use nexosim_io_utils::hexdump::HexDump;
+use nexosim_io_utils::link::{LinkMonitor, LinkStatus};
+use nexosim_io_utils::metrics::PortMetrics;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus, StallChange};
+use nexosim_io_utils::timestamp::Timestamped;
+
+use crate::cannelloni::CannelloniBackend;
+#[cfg(feature = "slcan")]
+use crate::slcan::SlcanBackend;
+use crate::socketcand::SocketcandBackend;
 
 /// A Socket wrapped for MIO eventing.
 // Taken with changes from socketcan-rs.
@@ -73,202 +179,2529 @@ impl<T: Socket> Source for MioSocket<T> {
     }
 }
 
+/// CAN interface backend.
+///
+/// A backend is a non-blocking MIO source providing access to a single CAN
+/// interface.
+pub trait CanBackend: Source + Send {
+    /// Returns the MIO interest of the backend.
+    ///
+    /// Backends buffering outgoing data should register writable interest, so
+    /// that [`read_frame`](Self::read_frame) is called when the buffered data
+    /// can be written.
+    fn interest(&self) -> Interest {
+        Interest::READABLE
+    }
+
+    /// Reads a frame and its receive timestamps.
+    ///
+    /// This method should return an error of kind [`ErrorKind::WouldBlock`]
+    /// when no frame is available.
+    fn read_frame(&mut self) -> Result<(CanFrame, CanTimestamps)>;
+
+    /// Writes a frame.
+    fn write_frame(&mut self, frame: &CanFrame) -> Result<()>;
+}
+
+/// Factory opening the interfaces of the `custom` backend.
+///
+/// The factory is called with the name of the interface to open.
+pub type CanBackendFactory = dyn Fn(&str) -> Result<Box<dyn CanBackend>> + Send + Sync;
+
+impl CanBackend for MioSocket<CanSocket> {
+    fn read_frame(&mut self) -> Result<(CanFrame, CanTimestamps)> {
+        self.get_ref().read_frame_with_timestamps()
+    }
+
+    fn write_frame(&mut self, frame: &CanFrame) -> Result<()> {
+        self.get_mut_ref().transmit(frame).map_err(|err| match err {
+            CanError::Io(err) => err,
+            CanError::Can(err) => Error::new(ErrorKind::Other, err),
+        })
+    }
+}
+
 /// CAN port model instance config.
+///
+/// The configuration is validated when loaded: the interface list should not
+/// be empty nor contain duplicates, periods, timeouts and the transmit queue
+/// size should not be zero, and `delta` should not exceed `period`.
 #[derive(Config, Debug)]
 pub struct CanPortConfig {
     /// List of CAN interfaces.
-    #[setting(default = vec!["vcan0".into(), "vcan1".into()])]
+    ///
+    /// With the SLCAN backend, interfaces are the paths of the serial devices.
+    /// With the cannelloni backend, interfaces are the addresses of the remote
+    /// instances. With the socketcand backend, interfaces are the server
+    /// addresses followed by the bus names.
+    #[setting(default = vec!["vcan0".into(), "vcan1".into()], validate = validate_interfaces)]
     pub interfaces: Vec<String>,
 
+    /// Backend used to access the CAN interfaces.
+    pub backend: CanBackendKind,
+
+    /// SLCAN backend settings.
+    #[setting(nested)]
+    pub slcan: SlcanConfig,
+
+    /// socketcand backend settings.
+    #[setting(nested)]
+    pub socketcand: SocketcandConfig,
+
     /// Time shift for scheduling events at the present moment.
     ///
     /// If no value is provided, `period` is used.
-    pub delta: Option<u64>,
+    #[setting(validate = validate_delta)]
+    pub delta: Option<ConfigDuration>,
 
     /// Activation period for cyclic activities inside the simulation.
     ///
     /// If no value is provided, cyclic activities are not scheduled
     /// automatically.
-    pub period: Option<u64>,
+    #[setting(validate = validate_non_zero)]
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    #[setting(validate = validate_non_zero)]
+    pub watchdog_timeout: Option<ConfigDuration>,
+
+    /// Maximum time spent transmitting the pending CAN frames when the model
+    /// is dropped.
+    ///
+    /// If no value is provided, the frames not yet transmitted are discarded.
+    pub shutdown_timeout: Option<ConfigDuration>,
+
+    /// Socket path of a shared port broker.
+    ///
+    /// If a value is provided, the CAN interfaces are accessed through the
+    /// broker listening on this socket and `interfaces` is not opened.
+    /// Interface indices then refer to the interfaces of the broker, and
+    /// `interfaces` should list the broker interfaces in order if interfaces
+    /// are addressed by name.
+    pub broker_path: Option<String>,
+
+    /// Filters applied by the shared port broker.
+    ///
+    /// Only frames accepted by at least one filter are forwarded by the broker.
+    /// If no filters are provided, all frames are forwarded. Inverted filters
+    /// are not supported by the broker.
+    #[setting(nested)]
+    pub broker_filters: Vec<CanFilterConfig>,
+
+    /// Error class mask of the received error frames.
+    ///
+    /// Received error frames are reported on the error output. By default the
+    /// mask is `CAN_ERR_MASK` (`0x1FFF_FFFF`) and all error classes are
+    /// reported; the mask can be narrowed to some error classes, or set to zero
+    /// to not receive error frames. Error frames are only received with the
+    /// SocketCAN backend.
+    #[setting(default = 0x1FFF_FFFF)]
+    pub error_mask: u32,
+
+    /// Received frames and errors are addressed by interface name rather than
+    /// by interface index.
+    pub interface_names: bool,
+
+    /// Socket-layer receive timestamps are requested from the kernel.
+    pub rx_timestamps: bool,
+
+    /// Hardware receive timestamps are requested from the kernel.
+    ///
+    /// Hardware timestamps are expressed in the clock domain of the CAN
+    /// adapter and are only available if supported by the adapter. Software
+    /// timestamps taken when the frame enters the network stack are requested
+    /// as well.
+    pub hw_timestamps: bool,
+
+    /// Frames sent by the CAN port are looped back to the other sockets open
+    /// on the same interface (`CAN_RAW_LOOPBACK`).
+    ///
+    /// This option and the following socket options, as well as receive
+    /// filters and timestamps, only apply to the SocketCAN backend.
+    #[setting(default = true)]
+    pub loopback: bool,
+
+    /// Frames sent by the CAN port are received back by the CAN port and
+    /// injected into the simulation (`CAN_RAW_RECV_OWN_MSGS`).
+    ///
+    /// Own frames are only received if `loopback` is enabled, and are tagged
+    /// as such. This option is ignored when the interfaces are accessed
+    /// through a shared port broker.
+    pub recv_own_msgs: bool,
+
+    /// Transmitted frames are reported on the transmit confirmation output
+    /// once sent on the bus.
+    ///
+    /// Confirmations are own frames received back, so `loopback` should be
+    /// enabled. Own frames are then received from the sockets but are only
+    /// forwarded on the frame output if `recv_own_msgs` is enabled. The
+    /// timestamps of the confirmations are the receive timestamps requested
+    /// with `rx_timestamps` or `hw_timestamps`; hardware timestamps give the
+    /// actual transmission instant on the bus.
+    pub tx_confirmations: bool,
+
+    /// Policy applied to the transmitted frames when the send buffer of an
+    /// interface is full.
+    ///
+    /// Frames which cannot be sent are reported on the transmit failure
+    /// output.
+    pub tx_policy: CanTxPolicy,
+
+    /// Maximum number of frames queued per interface by the `queue` transmit
+    /// policy.
+    #[setting(default = 64, validate = validate_non_zero)]
+    pub tx_queue_size: usize,
+
+    /// Receive filters of the CAN interfaces.
+    ///
+    /// Filters are applied by the kernel when the interfaces are opened.
+    /// Interfaces without filters receive all frames.
+    #[setting(nested)]
+    pub filters: Vec<CanInterfaceFilterConfig>,
+
+    /// Bus state query period.
+    ///
+    /// If a value is provided, the state of the interfaces is queried through
+    /// netlink with this period, starting after `delta`, and reported on the
+    /// bus state output. If no value is provided, the bus state is not
+    /// queried automatically.
+    #[setting(validate = validate_non_zero)]
+    pub bus_state_period: Option<ConfigDuration>,
+
+    /// Metrics report period.
+    ///
+    /// If a value is provided, the traffic metrics of the port are reported
+    /// on the metrics output with this period, starting after `delta`. If no
+    /// value is provided, metrics are only reported on request.
+    #[setting(validate = validate_non_zero)]
+    pub metrics_period: Option<ConfigDuration>,
+
+    /// Wall-clock time alignment of the received frames.
+    ///
+    /// If enabled, the received frames are injected at the simulation time
+    /// matching the time at which the I/O thread read them, offset by the
+    /// alignment delay, rather than when they are forwarded. Errors, transmit
+    /// confirmations and failures are not delayed.
+    #[setting(nested)]
+    pub time_alignment: TimeAlignmentConfig,
 }
 
-/// CAN data exchanged inside the simulation.
-#[derive(Clone, Copy, Debug)]
-pub struct CanData {
-    /// CAN interface.
-    pub interface: usize,
+/// CAN interface backend selection.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CanBackendKind {
+    /// Linux SocketCAN interfaces.
+    #[default]
+    #[serde(rename = "socketcan")]
+    SocketCan,
 
-    /// CAN frame.
-    pub frame: CanFrame,
+    /// SLCAN adapters on serial devices.
+    ///
+    /// This backend requires the `slcan` feature.
+    #[serde(rename = "slcan")]
+    Slcan,
+
+    /// Remote CAN buses tunneled over UDP by cannelloni.
+    #[serde(rename = "cannelloni")]
+    Cannelloni,
+
+    /// Remote CAN buses exported by a socketcand server.
+    #[serde(rename = "socketcand")]
+    Socketcand,
+
+    /// Interfaces opened by the backend factory provided to the model
+    /// prototype.
+    #[serde(rename = "custom")]
+    Custom,
 }
 
-struct CanPortInner {
-    sockets: Vec<MioSocket<CanSocket>>,
+/// Transmit policy applied when the send buffer of an interface is full.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CanTxPolicy {
+    /// Frames are queued and sent once the interface is writable again.
+    ///
+    /// Frames are dropped when the queue is full.
+    #[default]
+    Queue,
+
+    /// Frames are dropped.
+    Drop,
 }
 
-impl CanPortInner {
-    fn new(interfaces: &[String]) -> Self {
-        let mut sockets = Vec::with_capacity(interfaces.len());
+/// SLCAN backend configuration.
+#[derive(Config, Debug)]
+pub struct SlcanConfig {
+    /// Baud rate of the serial devices.
+    #[setting(default = 115200, validate = validate_non_zero)]
+    pub baud_rate: u32,
 
-        for interface in interfaces.iter() {
-            let socket = MioSocket::new(CanSocket::open(interface).unwrap());
-            socket.get_ref().set_nonblocking(true).unwrap();
-            sockets.push(socket);
-        }
+    /// CAN bitrate, in bit/s.
+    ///
+    /// Supported bitrates are 10, 20, 50, 100, 125, 250, 500 and 800 kbit/s,
+    /// and 1 Mbit/s.
+    #[setting(default = 500000, validate = validate_slcan_bitrate)]
+    pub bitrate: u32,
+}
 
-        Self { sockets }
-    }
+/// socketcand backend configuration.
+#[derive(Config, Debug)]
+pub struct SocketcandConfig {
+    /// Protocol mode of the server connections.
+    pub mode: SocketcandMode,
+
+    /// Identifiers subscribed to in broadcast manager mode.
+    ///
+    /// Identifiers greater than `0x7FF` are subscribed to as extended
+    /// identifiers.
+    pub subscriptions: Vec<u32>,
 }
 
-impl IoPort<MioSocket<CanSocket>, CanData, CanData> for CanPortInner {
-    fn register(&mut self, registry: &Registry) -> Token {
-        for (i, socket) in self.sockets.iter_mut().enumerate() {
-            registry
-                .register(socket, Token(i), Interest::READABLE)
-                .unwrap();
-        }
-        Token(self.sockets.len())
+/// Protocol mode of a socketcand connection.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SocketcandMode {
+    /// All frames of the bus are received (`rawmode`).
+    #[default]
+    Raw,
+
+    /// Only frames with a subscribed identifier are received (`bcmmode`).
+    Bcm,
+}
+
+impl CanPortConfig {
+    /// Returns the index of the interface with the provided name.
+    pub fn interface_index(&self, name: &str) -> Option<usize> {
+        self.interfaces
+            .iter()
+            .position(|interface| interface == name)
     }
 
-    fn read(&mut self, token: Token) -> Result<CanData> {
-        let Token(i) = token;
-        self.sockets.get(i).map_or(
-            Err(Error::new(ErrorKind::InvalidInput, "Unknown event.")),
-            |socket| {
-                socket.get_ref().read_frame().map(|frame| CanData {
-                    interface: i,
-                    frame,
-                })
-            },
-        )
+    /// Returns the name of the interface with the provided index.
+    pub fn interface_name(&self, index: usize) -> Option<&str> {
+        self.interfaces.get(index).map(String::as_str)
     }
 
-    fn write(&mut self, data: &CanData) -> Result<()> {
-        self.sockets.get_mut(data.interface).map_or(
-            Err(Error::new(ErrorKind::InvalidInput, "Unknown interface.")),
-            |socket| {
-                socket
-                    .get_mut_ref()
-                    .transmit(&data.frame)
-                    .map_err(|err| match err {
-                        CanError::Io(err) => err,
-                        CanError::Can(err) => Error::new(ErrorKind::Other, err),
-                    })
-            },
-        )
+    /// Returns a builder of a CAN port model instance configuration.
+    pub fn builder() -> CanPortConfigBuilder {
+        CanPortConfigBuilder::default()
     }
 }
 
-/// CAN port model.
+/// Builder of a CAN port model instance configuration.
 ///
-/// This model
-/// * listens the specified CAN ports and injects into the simulation values
-///   read from it as CAN frames,
-/// * outputs CAN frames from the simulation to the CAN port.
-pub struct CanPort {
-    /// CAN frame -- output port.
-    pub frame_out: Output<CanData>,
+/// Settings which are not set take their default values. In particular, the
+/// default interfaces are used if no interface is added.
+///
+/// #### Examples
+///
+/// ```
+/// use nexosim_can_port::{CanFilterConfig, CanInterfaceFilterConfig, CanPortConfig};
+///
+/// let config = CanPortConfig::builder()
+///     .interface("can0")
+///     .interface("can1")
+///     .period_ms(10)
+///     .filter(CanInterfaceFilterConfig {
+///         interface: "can0".into(),
+///         filters: vec![CanFilterConfig {
+///             id: 0x100,
+///             mask: 0x700,
+///             inverted: false,
+///         }],
+///         error_mask: None,
+///     })
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(config.interfaces, ["can0", "can1"]);
+/// assert!(config.loopback);
+///
+/// // The configuration is validated.
+/// assert!(CanPortConfig::builder().interface("can0").interface("can0").build().is_err());
+/// ```
+#[derive(Debug, Default)]
+pub struct CanPortConfigBuilder {
+    /// Configuration built.
+    partial: PartialCanPortConfig,
+}
 
-    /// Model instance configuration.
-    config: CanPortConfig,
+impl CanPortConfigBuilder {
+    /// Adds a CAN interface.
+    pub fn interface(mut self, interface: impl Into<String>) -> Self {
+        self.partial
+            .interfaces
+            .get_or_insert_with(Vec::new)
+            .push(interface.into());
+        self
+    }
 
-    /// I/O thread.
-    io_thread: IoThread<CanData, CanData>,
+    /// Sets the backend used to access the CAN interfaces.
+    pub fn backend(mut self, backend: CanBackendKind) -> Self {
+        self.partial.backend = Some(backend);
+        self
+    }
+
+    /// Sets the SLCAN backend settings.
+    pub fn slcan(mut self, slcan: SlcanConfig) -> Self {
+        self.partial.slcan = Some(slcan.into());
+        self
+    }
+
+    /// Sets the socketcand backend settings.
+    pub fn socketcand(mut self, socketcand: SocketcandConfig) -> Self {
+        self.partial.socketcand = Some(socketcand.into());
+        self
+    }
+
+    /// Sets the time shift for scheduling events at the present moment.
+    pub fn delta(mut self, delta: Duration) -> Self {
+        self.partial.delta = Some(delta.into());
+        self
+    }
+
+    /// Sets the time shift for scheduling events at the present moment, in
+    /// milliseconds.
+    pub fn delta_ms(self, delta: u64) -> Self {
+        self.delta(Duration::from_millis(delta))
+    }
+
+    /// Sets the activation period for cyclic activities inside the
+    /// simulation.
+    pub fn period(mut self, period: Duration) -> Self {
+        self.partial.period = Some(period.into());
+        self
+    }
+
+    /// Sets the activation period for cyclic activities inside the
+    /// simulation, in milliseconds.
+    pub fn period_ms(self, period: u64) -> Self {
+        self.period(Duration::from_millis(period))
+    }
+
+    /// Sets the I/O thread watchdog timeout.
+    pub fn watchdog_timeout(mut self, timeout: Duration) -> Self {
+        self.partial.watchdog_timeout = Some(timeout.into());
+        self
+    }
+
+    /// Sets the I/O thread watchdog timeout, in milliseconds.
+    pub fn watchdog_timeout_ms(self, timeout: u64) -> Self {
+        self.watchdog_timeout(Duration::from_millis(timeout))
+    }
+
+    /// Sets the maximum time spent transmitting the pending CAN frames when
+    /// the model is dropped.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.partial.shutdown_timeout = Some(timeout.into());
+        self
+    }
+
+    /// Sets the maximum time spent transmitting the pending CAN frames when
+    /// the model is dropped, in milliseconds.
+    pub fn shutdown_timeout_ms(self, timeout: u64) -> Self {
+        self.shutdown_timeout(Duration::from_millis(timeout))
+    }
+
+    /// Sets the socket path of a shared port broker.
+    pub fn broker_path(mut self, path: impl Into<String>) -> Self {
+        self.partial.broker_path = Some(path.into());
+        self
+    }
+
+    /// Adds a filter applied by the shared port broker.
+    pub fn broker_filter(mut self, filter: CanFilterConfig) -> Self {
+        self.partial
+            .broker_filters
+            .get_or_insert_with(Vec::new)
+            .push(filter.into());
+        self
+    }
+
+    /// Sets the error class mask of the received error frames.
+    pub fn error_mask(mut self, mask: u32) -> Self {
+        self.partial.error_mask = Some(mask);
+        self
+    }
+
+    /// Sets whether received frames and errors are addressed by interface
+    /// name.
+    pub fn interface_names(mut self, enabled: bool) -> Self {
+        self.partial.interface_names = Some(enabled);
+        self
+    }
+
+    /// Sets whether socket-layer receive timestamps are requested.
+    pub fn rx_timestamps(mut self, enabled: bool) -> Self {
+        self.partial.rx_timestamps = Some(enabled);
+        self
+    }
+
+    /// Sets whether hardware receive timestamps are requested.
+    pub fn hw_timestamps(mut self, enabled: bool) -> Self {
+        self.partial.hw_timestamps = Some(enabled);
+        self
+    }
+
+    /// Sets whether sent frames are looped back to the other sockets.
+    pub fn loopback(mut self, enabled: bool) -> Self {
+        self.partial.loopback = Some(enabled);
+        self
+    }
+
+    /// Sets whether sent frames are received back by the CAN port.
+    pub fn recv_own_msgs(mut self, enabled: bool) -> Self {
+        self.partial.recv_own_msgs = Some(enabled);
+        self
+    }
+
+    /// Sets whether transmitted frames are confirmed.
+    pub fn tx_confirmations(mut self, enabled: bool) -> Self {
+        self.partial.tx_confirmations = Some(enabled);
+        self
+    }
+
+    /// Sets the transmit policy.
+    pub fn tx_policy(mut self, policy: CanTxPolicy) -> Self {
+        self.partial.tx_policy = Some(policy);
+        self
+    }
+
+    /// Sets the maximum number of frames queued per interface.
+    pub fn tx_queue_size(mut self, size: usize) -> Self {
+        self.partial.tx_queue_size = Some(size);
+        self
+    }
+
+    /// Adds receive filters of a CAN interface.
+    pub fn filter(mut self, filter: CanInterfaceFilterConfig) -> Self {
+        self.partial
+            .filters
+            .get_or_insert_with(Vec::new)
+            .push(filter.into());
+        self
+    }
+
+    /// Sets the bus state query period.
+    pub fn bus_state_period(mut self, period: Duration) -> Self {
+        self.partial.bus_state_period = Some(period.into());
+        self
+    }
+
+    /// Sets the metrics report period.
+    pub fn metrics_period(mut self, period: Duration) -> Self {
+        self.partial.metrics_period = Some(period.into());
+        self
+    }
+
+    /// Sets the wall-clock time alignment of the received frames.
+    pub fn time_alignment(mut self, time_alignment: TimeAlignmentConfig) -> Self {
+        self.partial.time_alignment = Some(time_alignment.into());
+        self
+    }
+
+    /// Builds the configuration.
+    ///
+    /// An error is returned if the configuration is invalid.
+    pub fn build(self) -> std::result::Result<CanPortConfig, ConfigError> {
+        config::build(self.partial)
+    }
 }
 
-impl CanPort {
-    /// Creates a new CAN port model.
-    fn new(
-        frame_out: Output<CanData>,
-        config: CanPortConfig,
-        io_thread: IoThread<CanData, CanData>,
-    ) -> Self {
+impl From<SlcanConfig> for PartialSlcanConfig {
+    fn from(config: SlcanConfig) -> Self {
         Self {
-            frame_out,
-            config,
-            io_thread,
+            baud_rate: Some(config.baud_rate),
+            bitrate: Some(config.bitrate),
         }
     }
+}
 
-    /// Transmits CAN frame -- input port.
-    pub fn frame_in(&mut self, data: CanData) {
-        #[cfg(feature = "tracing")]
-        info!(
-            "Will transmit CAN frame to the CAN interface {}: {:?}.",
-            self.config.interfaces[data.interface], data.frame
-        );
-        self.io_thread.send(data).unwrap();
+impl From<SocketcandConfig> for PartialSocketcandConfig {
+    fn from(config: SocketcandConfig) -> Self {
+        Self {
+            mode: Some(config.mode),
+            subscriptions: Some(config.subscriptions),
+        }
     }
+}
 
-    /// Forwards the CAN frame received on the serial port.
-    pub async fn process(&mut self) {
-        while let Ok(data) = self.io_thread.try_recv() {
-            #[cfg(feature = "tracing")]
-            info!(
-                "Received CAN frame on the CAN interface {}: {:?}.",
-                self.config.interfaces[data.interface], data.frame
-            );
-            self.frame_out.send(data).await;
+impl From<CanInterfaceFilterConfig> for PartialCanInterfaceFilterConfig {
+    fn from(config: CanInterfaceFilterConfig) -> Self {
+        Self {
+            interface: Some(config.interface),
+            filters: Some(config.filters.into_iter().map(Into::into).collect()),
+            error_mask: config.error_mask,
         }
     }
 }
 
-impl Model for CanPort {
-    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
-        if let Some(period) = self.config.period {
-            let delta = match self.config.delta {
-                Some(delta) => delta,
-                None => period,
-            };
+impl From<CanFilterConfig> for PartialCanFilterConfig {
+    fn from(config: CanFilterConfig) -> Self {
+        Self {
+            id: Some(config.id),
+            mask: Some(config.mask),
+            inverted: Some(config.inverted),
+        }
+    }
+}
 
-            context
-                .schedule_periodic_event(
-                    Duration::from_millis(delta),
-                    Duration::from_millis(period),
-                    Self::process,
-                    (),
-                )
-                .unwrap();
+/// Checks that the interface list is not empty and has no duplicates.
+fn validate_interfaces<D, C>(interfaces: &[String], _: &D, _: &C, _: bool) -> ValidateResult {
+    if interfaces.is_empty() {
+        return Err(ValidateError::new("at least one CAN interface is required"));
+    }
+    for (index, interface) in interfaces.iter().enumerate() {
+        if interfaces[..index].contains(interface) {
+            return Err(ValidateError::new(format!(
+                "CAN interface `{interface}` is listed more than once"
+            )));
         }
+    }
 
-        self.into()
+    Ok(())
+}
+
+/// Checks that `delta` does not exceed `period`.
+fn validate_delta<C>(
+    delta: &ConfigDuration,
+    config: &PartialCanPortConfig,
+    _: &C,
+    _: bool,
+) -> ValidateResult {
+    match config.period {
+        Some(period) if *delta > period => Err(ValidateError::new(format!(
+            "delta ({delta}) should not exceed period ({period})"
+        ))),
+        _ => Ok(()),
     }
 }
 
-impl fmt::Debug for CanPort {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.debug_struct("CanPort").finish_non_exhaustive()
+/// Checks that the SLCAN bitrate is supported.
+#[cfg_attr(not(feature = "slcan"), allow(unused_variables))]
+fn validate_slcan_bitrate<D, C>(bitrate: &u32, _: &D, _: &C, _: bool) -> ValidateResult {
+    #[cfg(feature = "slcan")]
+    if slcan::bitrate_code(*bitrate).is_none() {
+        return Err(ValidateError::new(format!(
+            "unsupported SLCAN bitrate {bitrate} bit/s"
+        )));
     }
+
+    Ok(())
 }
 
-/// CAN port model prototype.
-#[allow(missing_debug_implementations)]
-pub struct ProtoCanPort {
-    /// Received CAN frames -- output port.
-    pub frame_out: Output<CanData>,
+/// Checks that a setting is not zero.
+fn validate_non_zero<T, D, C>(value: &T, _: &D, _: &C, _: bool) -> ValidateResult
+where
+    T: Default + PartialEq,
+{
+    if *value == T::default() {
+        return Err(ValidateError::new("this setting should not be zero"));
+    }
 
-    /// CAN port model instance configuration.
-    config: CanPortConfig,
+    Ok(())
 }
 
-impl ProtoCanPort {
-    /// Creates a new CAN port model prototype.
-    pub fn new(config: CanPortConfig) -> Self {
-        Self {
-            frame_out: Output::default(),
-            config,
+/// CAN interface receive filter configuration.
+#[derive(Config, Debug)]
+pub struct CanInterfaceFilterConfig {
+    /// CAN interface name.
+    pub interface: String,
+
+    /// Identifier filters.
+    ///
+    /// Only frames accepted by at least one filter are received. If no filters
+    /// are provided, all frames are received.
+    #[setting(nested)]
+    pub filters: Vec<CanFilterConfig>,
+
+    /// Error class mask of the received error frames.
+    ///
+    /// If no value is provided, the error mask of the port is used.
+    pub error_mask: Option<u32>,
+}
+
+/// CAN identifier filter configuration.
+///
+/// A frame is accepted if its identifier matches the filter identifier on all
+/// bits set in the mask.
+#[derive(Config, Debug)]
+pub struct CanFilterConfig {
+    /// CAN identifier.
+    pub id: u32,
+
+    /// CAN identifier mask.
+    pub mask: u32,
+
+    /// Filter is inverted, i.e. accepts frames which do not match.
+    pub inverted: bool,
+}
+
+impl From<&CanFilterConfig> for CanFilter {
+    fn from(filter: &CanFilterConfig) -> Self {
+        if filter.inverted {
+            CanFilter::new_inverted(filter.id, filter.mask)
+        } else {
+            CanFilter::new(filter.id, filter.mask)
         }
     }
 }
 
-impl ProtoModel for ProtoCanPort {
-    type Model = CanPort;
+/// Interned CAN interface names, indexed by their identifier.
+///
+/// Interface names are few, so they are leaked and looked up linearly.
+static INTERFACE_NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// CAN interface name.
+///
+/// Names are interned so that interface addresses, and thus [`CanData`], are
+/// `Copy`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct InterfaceName(u32);
+
+impl InterfaceName {
+    /// Creates a CAN interface name.
+    pub fn new(name: &str) -> Self {
+        let mut names = INTERFACE_NAMES.lock().unwrap();
+        let id = match names.iter().position(|&interned| interned == name) {
+            Some(id) => id,
+            None => {
+                names.push(Box::leak(name.into()));
+                names.len() - 1
+            }
+        };
+
+        Self(id as u32)
+    }
+
+    /// Returns the interface name.
+    pub fn as_str(&self) -> &'static str {
+        INTERFACE_NAMES.lock().unwrap()[self.0 as usize]
+    }
+}
 
-    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
-        let interfaces = CanPortInner::new(&self.config.interfaces);
+impl fmt::Display for InterfaceName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// CAN interface address.
+///
+/// Interfaces are addressed either by their index in the `interfaces`
+/// configuration of the CAN port model or by their name.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum CanInterface {
+    /// Interface index.
+    Index(usize),
+    /// Interface name.
+    Name(InterfaceName),
+}
+
+impl CanInterface {
+    /// Creates an interface address from the interface name.
+    pub fn named(name: &str) -> Self {
+        Self::Name(InterfaceName::new(name))
+    }
 
-        Self::Model::new(self.frame_out, self.config, IoThread::new(interfaces))
+    /// Resolves the interface index from the list of configured interfaces.
+    pub fn index(&self, interfaces: &[String]) -> Option<usize> {
+        match self {
+            Self::Index(index) => (*index < interfaces.len()).then_some(*index),
+            Self::Name(name) => interfaces
+                .iter()
+                .position(|interface| interface == name.as_str()),
+        }
+    }
+
+    /// Returns the interface index, if addressed by index.
+    pub fn as_index(&self) -> Option<usize> {
+        match self {
+            Self::Index(index) => Some(*index),
+            Self::Name(_) => None,
+        }
     }
+
+    /// Resolves the interface name from the list of configured interfaces.
+    pub fn name(&self, interfaces: &[String]) -> Option<InterfaceName> {
+        match self {
+            Self::Index(index) => interfaces
+                .get(*index)
+                .map(|interface| InterfaceName::new(interface)),
+            Self::Name(name) => Some(*name),
+        }
+    }
+}
+
+impl From<usize> for CanInterface {
+    fn from(index: usize) -> Self {
+        Self::Index(index)
+    }
+}
+
+/// Interfaces addressed by index compare equal to their index, as did the
+/// plain indices previously used for addressing.
+impl PartialEq<usize> for CanInterface {
+    fn eq(&self, index: &usize) -> bool {
+        self.as_index() == Some(*index)
+    }
+}
+
+impl From<InterfaceName> for CanInterface {
+    fn from(name: InterfaceName) -> Self {
+        Self::Name(name)
+    }
+}
+
+impl From<&str> for CanInterface {
+    fn from(name: &str) -> Self {
+        Self::named(name)
+    }
+}
+
+impl fmt::Display for CanInterface {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "#{index}"),
+            Self::Name(name) => name.fmt(f),
+        }
+    }
+}
+
+/// CAN data exchanged inside the simulation.
+#[derive(Copy, Clone, Debug)]
+pub struct CanData {
+    /// CAN interface.
+    pub interface: CanInterface,
+
+    /// CAN frame.
+    pub frame: CanFrame,
+
+    /// Receive timestamps.
+    ///
+    /// Timestamps are only available for received frames and transmit
+    /// confirmations when enabled in the configuration, and are ignored for
+    /// transmitted frames.
+    pub timestamps: CanTimestamps,
+
+    /// The frame was sent by the CAN port and received back.
+    ///
+    /// Own frames are identified by matching the received frames with the
+    /// frames sent on the same interface which were not yet received back, so
+    /// a frame from another node identical to a pending own frame may be
+    /// tagged as own. This flag is ignored for transmitted frames.
+    pub own: bool,
+}
+
+impl CanData {
+    /// Creates new CAN data without timestamps.
+    ///
+    /// The interface can be provided as an index, e.g. with
+    /// `CanData::new(0, frame)`, or as an [`InterfaceName`].
+    pub fn new(interface: impl Into<CanInterface>, frame: CanFrame) -> Self {
+        Self {
+            interface: interface.into(),
+            frame,
+            timestamps: CanTimestamps::default(),
+            own: false,
+        }
+    }
+}
+
+/// The payload of data frames can be corrupted or truncated, while remote and
+/// error frames have no payload.
+impl Faulty for CanData {
+    fn payload_len(&self) -> usize {
+        match self.frame {
+            CanFrame::Data(frame) => frame.data().len(),
+            _ => 0,
+        }
+    }
+
+    fn flip_bit(&mut self, bit: usize) {
+        self.flip_bits(&[bit]);
+    }
+
+    fn flip_bits(&mut self, bits: &[usize]) {
+        if let CanFrame::Data(frame) = self.frame {
+            let mut data = frame.data().to_vec();
+            for &bit in bits {
+                data[bit / 8] ^= 1 << (bit % 8);
+            }
+            if let Some(frame) = CanFrame::new(frame.id(), &data) {
+                self.frame = frame;
+            }
+        }
+    }
+
+    fn truncate_payload(&mut self, len: usize) {
+        if let CanFrame::Data(frame) = self.frame {
+            let data = &frame.data()[..len.min(frame.data().len())];
+            if let Some(frame) = CanFrame::new(frame.id(), data) {
+                self.frame = frame;
+            }
+        }
+    }
+}
+
+/// CAN error event.
+#[derive(Clone, Debug)]
+pub struct CanErrorEvent {
+    /// CAN interface.
+    pub interface: CanInterface,
+
+    /// Error reported by the error frame.
+    pub error: CanBusError,
+}
+
+/// CAN bus state event.
+///
+/// Values which are not reported by the interface driver, e.g. for virtual
+/// interfaces, are `None`.
+#[derive(Clone, Debug)]
+pub struct CanBusState {
+    /// CAN interface.
+    pub interface: CanInterface,
+
+    /// Controller state.
+    pub state: Option<CanState>,
+
+    /// Transmit error counter.
+    pub tx_errors: Option<u16>,
+
+    /// Receive error counter.
+    pub rx_errors: Option<u16>,
+
+    /// Bitrate, in bits per second.
+    pub bitrate: Option<u32>,
+}
+
+/// CAN frame transmission failure.
+#[derive(Clone, Debug)]
+pub struct CanTxFailure {
+    /// CAN interface.
+    pub interface: CanInterface,
+
+    /// Frame which could not be sent.
+    pub frame: CanFrame,
+
+    /// Cause of the failure.
+    pub cause: CanTxFailureCause,
+
+    /// Number of frames which could not be sent on the interface so far,
+    /// including this one.
+    pub failures: u64,
+}
+
+/// Cause of a CAN frame transmission failure.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CanTxFailureCause {
+    /// The send buffer of the interface is full and the frame was dropped.
+    BufferFull,
+
+    /// The transmit queue of the interface is full and the frame was dropped.
+    QueueFull,
+
+    /// The interface reported an error.
+    Error(String),
+}
+
+/// CAN interface status event.
+#[derive(Clone, Debug)]
+pub struct CanInterfaceStatus {
+    /// CAN interface.
+    pub interface: CanInterface,
+
+    /// Interface state.
+    pub state: CanInterfaceState,
+}
+
+/// CAN interface state.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CanInterfaceState {
+    /// The interface was opened.
+    Attached,
+
+    /// The interface was closed.
+    Detached,
+
+    /// The interface could not be opened, with the error description.
+    Failed(String),
+}
+
+/// CAN port error.
+#[derive(Debug)]
+pub enum CanPortError {
+    /// CAN interface could not be opened.
+    Open {
+        /// CAN interface name.
+        interface: String,
+        /// Error.
+        error: Error,
+    },
+
+    /// Shared port broker could not be reached.
+    Broker(Error),
+
+    /// Shared port broker could not be started.
+    BrokerStart(Error),
+}
+
+impl fmt::Display for CanPortError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Open { interface, error } => {
+                write!(
+                    f,
+                    "CAN interface '{interface}' could not be opened: {error}"
+                )
+            }
+            Self::Broker(error) => write!(f, "shared port broker could not be reached: {error}"),
+            Self::BrokerStart(error) => {
+                write!(f, "shared port broker could not be started: {error}")
+            }
+        }
+    }
+}
+
+impl error::Error for CanPortError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Open { error, .. } => Some(error),
+            Self::Broker(error) | Self::BrokerStart(error) => Some(error),
+        }
+    }
+}
+
+/// Settings applied to the CAN interfaces when opened.
+struct CanBackendSettings {
+    /// Backend of the interfaces.
+    backend: CanBackendKind,
+
+    /// Factory of the custom backend.
+    backend_factory: Option<Arc<CanBackendFactory>>,
+
+    /// Protocol mode of the socketcand connections.
+    socketcand_mode: SocketcandMode,
+
+    /// Identifiers subscribed to in socketcand broadcast manager mode.
+    socketcand_subscriptions: Vec<u32>,
+
+    /// Baud rate of the SLCAN serial devices.
+    #[cfg(feature = "slcan")]
+    slcan_baud_rate: u32,
+
+    /// CAN bitrate of the SLCAN adapters.
+    #[cfg(feature = "slcan")]
+    slcan_bitrate: u32,
+
+    /// Receive filters and error mask by interface name.
+    filters: Vec<(String, Vec<CanFilter>, Option<u32>)>,
+
+    /// Default error mask.
+    error_mask: u32,
+
+    /// Socket-layer receive timestamps are requested.
+    rx_timestamps: bool,
+
+    /// Hardware receive timestamps are requested.
+    hw_timestamps: bool,
+
+    /// Sent frames are looped back.
+    loopback: bool,
+
+    /// Sent frames are received back.
+    recv_own_msgs: bool,
+
+    /// Sent frames are received back for transmit confirmation.
+    tx_confirmations: bool,
+
+    /// Transmit policy.
+    tx_policy: CanTxPolicy,
+
+    /// Maximum number of queued frames per interface.
+    tx_queue_size: usize,
+}
+
+impl CanBackendSettings {
+    fn new(config: &CanPortConfig, backend_factory: Option<Arc<CanBackendFactory>>) -> Self {
+        Self {
+            backend: config.backend,
+            backend_factory,
+            socketcand_mode: config.socketcand.mode,
+            socketcand_subscriptions: config.socketcand.subscriptions.clone(),
+            #[cfg(feature = "slcan")]
+            slcan_baud_rate: config.slcan.baud_rate,
+            #[cfg(feature = "slcan")]
+            slcan_bitrate: config.slcan.bitrate,
+            filters: config
+                .filters
+                .iter()
+                .map(|filter| {
+                    (
+                        filter.interface.clone(),
+                        filter.filters.iter().map(Into::into).collect(),
+                        filter.error_mask,
+                    )
+                })
+                .collect(),
+            error_mask: config.error_mask,
+            rx_timestamps: config.rx_timestamps,
+            hw_timestamps: config.hw_timestamps,
+            loopback: config.loopback,
+            recv_own_msgs: config.recv_own_msgs,
+            tx_confirmations: config.tx_confirmations,
+            tx_policy: config.tx_policy,
+            tx_queue_size: config.tx_queue_size,
+        }
+    }
+
+    /// Opens and configures a CAN interface.
+    fn open(&self, interface: &str) -> Result<Box<dyn CanBackend>> {
+        match self.backend {
+            CanBackendKind::SocketCan => Ok(Box::new(self.open_socket(interface)?)),
+            #[cfg(feature = "slcan")]
+            CanBackendKind::Slcan => Ok(Box::new(SlcanBackend::open(
+                interface,
+                self.slcan_baud_rate,
+                self.slcan_bitrate,
+            )?)),
+            #[cfg(not(feature = "slcan"))]
+            CanBackendKind::Slcan => Err(Error::new(
+                ErrorKind::Unsupported,
+                "SLCAN support is not enabled.",
+            )),
+            CanBackendKind::Cannelloni => Ok(Box::new(CannelloniBackend::open(interface)?)),
+            CanBackendKind::Socketcand => Ok(Box::new(SocketcandBackend::open(
+                interface,
+                self.socketcand_mode,
+                &self.socketcand_subscriptions,
+            )?)),
+            CanBackendKind::Custom => match &self.backend_factory {
+                Some(factory) => factory(interface),
+                None => Err(Error::new(
+                    ErrorKind::Unsupported,
+                    "No custom CAN backend factory provided.",
+                )),
+            },
+        }
+    }
+
+    /// Checks whether sent frames are received back.
+    fn receives_own_frames(&self) -> bool {
+        self.backend == CanBackendKind::SocketCan
+            && self.loopback
+            && (self.recv_own_msgs || self.tx_confirmations)
+    }
+
+    /// Opens the netlink handle of an interface, if supported by the backend.
+    fn open_netlink(&self, interface: &str) -> Option<NlInterface> {
+        match self.backend {
+            CanBackendKind::SocketCan => open_netlink(interface),
+            CanBackendKind::Slcan
+            | CanBackendKind::Cannelloni
+            | CanBackendKind::Socketcand
+            | CanBackendKind::Custom => None,
+        }
+    }
+
+    /// Opens and configures a CAN socket.
+    fn open_socket(&self, interface: &str) -> Result<MioSocket<CanSocket>> {
+        let socket = MioSocket::new(CanSocket::open(interface)?);
+        socket.get_ref().set_nonblocking(true)?;
+        let mut error_mask = self.error_mask;
+        for (_, filters, mask) in self.filters.iter().filter(|f| f.0 == interface) {
+            if !filters.is_empty() {
+                socket.get_ref().set_filters(filters)?;
+            }
+            error_mask = mask.unwrap_or(error_mask);
+        }
+        socket.get_ref().set_error_filter(error_mask)?;
+        if self.rx_timestamps {
+            socket.get_ref().set_recv_timestamp(true)?;
+        }
+        if self.hw_timestamps {
+            socket.get_ref().set_timestamping(
+                SOF_TIMESTAMPING_RX_HARDWARE
+                    | SOF_TIMESTAMPING_RAW_HARDWARE
+                    | SOF_TIMESTAMPING_RX_SOFTWARE
+                    | SOF_TIMESTAMPING_SOFTWARE
+                    | SOF_TIMESTAMPING_OPT_CMSG,
+            )?;
+        }
+        socket.get_ref().set_loopback(self.loopback)?;
+        socket
+            .get_ref()
+            .set_recv_own_msgs(self.recv_own_msgs || self.tx_confirmations)?;
+
+        Ok(socket)
+    }
+}
+
+/// Command processed by the I/O thread of the CAN port.
+enum CanCommand {
+    /// Transmits a frame.
+    Transmit(CanData),
+
+    /// Registers the backend of an opened interface and assigns it the
+    /// provided index.
+    ///
+    /// The backend is moved to the I/O thread, which writes this command with
+    /// [`IoPort::write_owned`].
+    Attach(usize, Box<dyn CanBackend>),
+
+    /// Closes the interface with the provided index.
+    Detach(usize),
+}
+
+/// Maximum number of sent frames awaiting reception per interface.
+const MAX_PENDING_OWN_FRAMES: usize = 256;
+
+/// Waker token of the I/O thread of the CAN port.
+///
+/// Socket tokens are the interface indices.
+const WAKE_TOKEN: Token = Token(usize::MAX);
+
+struct CanPortInner {
+    /// Backends by interface index, `None` for detached interfaces.
+    sockets: Vec<Option<Box<dyn CanBackend>>>,
+
+    /// Sent frames not yet received back, by interface index.
+    ///
+    /// Sent frames are only recorded if they are received back.
+    own_frames: Vec<VecDeque<CanFrame>>,
+
+    /// Frames waiting for the send buffer to be available, by interface
+    /// index.
+    tx_queues: Vec<VecDeque<CanFrame>>,
+
+    /// Number of frames which could not be sent, by interface index.
+    tx_failure_counts: Vec<u64>,
+
+    /// Transmission failure sender, if failures are reported.
+    tx_failures: Option<Sender<CanTxFailure>>,
+
+    /// Socket settings.
+    settings: CanBackendSettings,
+
+    /// MIO registry, available once the sockets are registered.
+    registry: Option<Registry>,
+}
+
+impl CanPortInner {
+    /// Opens the configured interfaces.
+    ///
+    /// Interfaces which could not be opened are detached and returned with
+    /// the corresponding error.
+    fn open(
+        config: &CanPortConfig,
+        backend_factory: Option<Arc<CanBackendFactory>>,
+    ) -> (Self, Vec<(usize, Error)>) {
+        let settings = CanBackendSettings::new(config, backend_factory);
+        let mut failures = Vec::new();
+        let sockets = config
+            .interfaces
+            .iter()
+            .enumerate()
+            .map(|(index, interface)| match settings.open(interface) {
+                Ok(socket) => Some(socket),
+                Err(error) => {
+                    failures.push((index, error));
+                    None
+                }
+            })
+            .collect();
+        let own_frames = vec![VecDeque::new(); config.interfaces.len()];
+        let tx_queues = vec![VecDeque::new(); config.interfaces.len()];
+        let tx_failure_counts = vec![0; config.interfaces.len()];
+
+        (
+            Self {
+                sockets,
+                own_frames,
+                tx_queues,
+                tx_failure_counts,
+                tx_failures: None,
+                settings,
+                registry: None,
+            },
+            failures,
+        )
+    }
+
+    /// Creates the port with all configured interfaces detached.
+    fn detached(config: &CanPortConfig, backend_factory: Option<Arc<CanBackendFactory>>) -> Self {
+        let count = config.interfaces.len();
+
+        Self {
+            sockets: (0..count).map(|_| None).collect(),
+            own_frames: vec![VecDeque::new(); count],
+            tx_queues: vec![VecDeque::new(); count],
+            tx_failure_counts: vec![0; count],
+            tx_failures: None,
+            settings: CanBackendSettings::new(config, backend_factory),
+            registry: None,
+        }
+    }
+
+    /// Opens the configured interfaces, failing if any interface could not be
+    /// opened.
+    fn try_new(
+        config: &CanPortConfig,
+        backend_factory: Option<Arc<CanBackendFactory>>,
+    ) -> std::result::Result<Self, CanPortError> {
+        let (inner, failures) = Self::open(config, backend_factory);
+        match failures.into_iter().next() {
+            Some((index, error)) => Err(CanPortError::Open {
+                interface: config.interfaces[index].clone(),
+                error,
+            }),
+            None => Ok(inner),
+        }
+    }
+
+    /// Returns a receiver of the transmission failures.
+    ///
+    /// Transmission failures are not reported unless this method is called.
+    fn tx_failures(&mut self) -> Receiver<CanTxFailure> {
+        let (sender, receiver) = channel();
+        self.tx_failures = Some(sender);
+
+        receiver
+    }
+
+    /// Registers the sockets in MIO.
+    fn register_sockets(&mut self, registry: &Registry) -> Result<()> {
+        for (i, socket) in self.sockets.iter_mut().enumerate() {
+            if let Some(socket) = socket {
+                let interest = socket.interest();
+                registry.register(socket, Token(i), interest)?;
+            }
+        }
+        self.registry = registry.try_clone().ok();
+
+        Ok(())
+    }
+
+    /// Reads a frame from the socket corresponding to the token.
+    ///
+    /// Queued frames are sent first if possible. Events of detached
+    /// interfaces are ignored.
+    fn read_socket(&mut self, token: Token) -> Result<CanData> {
+        let Token(i) = token;
+        if self.tx_queues.get(i).is_some_and(|queue| !queue.is_empty()) {
+            self.flush_tx_queue(i)?;
+        }
+        match self.sockets.get_mut(i) {
+            Some(Some(socket)) => {
+                let (frame, timestamps) = socket.read_frame()?;
+                let own = self.is_own_frame(i, &frame);
+
+                Ok(CanData {
+                    interface: CanInterface::Index(i),
+                    frame,
+                    timestamps,
+                    own,
+                })
+            }
+            Some(None) => Err(ErrorKind::WouldBlock.into()),
+            None => Err(Error::new(ErrorKind::InvalidInput, "Unknown event.")),
+        }
+    }
+
+    /// Transmits a frame.
+    ///
+    /// Frames which cannot be sent because the send buffer is full are
+    /// handled according to the transmit policy. Transmission failures are
+    /// reported rather than returned.
+    fn transmit(&mut self, data: &CanData) -> Result<()> {
+        let CanInterface::Index(interface) = data.interface else {
+            return Err(Error::new(ErrorKind::InvalidInput, "Unresolved interface."));
+        };
+        let Some(Some(socket)) = self.sockets.get_mut(interface) else {
+            return Err(Error::new(ErrorKind::InvalidInput, "Unknown interface."));
+        };
+        // Queued frames are sent first to preserve the transmission order.
+        if !self.tx_queues[interface].is_empty() {
+            self.enqueue(interface, data.frame);
+            return Ok(());
+        }
+        match socket.write_frame(&data.frame) {
+            Err(err) if is_buffer_full(&err) => match self.settings.tx_policy {
+                CanTxPolicy::Queue => {
+                    self.enqueue(interface, data.frame);
+                    self.set_writable_interest(interface, true)?;
+                }
+                CanTxPolicy::Drop => {
+                    self.report_tx_failure(interface, data.frame, CanTxFailureCause::BufferFull)
+                }
+            },
+            result => self.complete_transmission(interface, data.frame, result),
+        }
+
+        Ok(())
+    }
+
+    /// Sends the queued frames of an interface until the send buffer is
+    /// full.
+    fn flush_tx_queue(&mut self, index: usize) -> Result<()> {
+        while let Some(&frame) = self.tx_queues[index].front() {
+            let Some(Some(socket)) = self.sockets.get_mut(index) else {
+                return Ok(());
+            };
+            match socket.write_frame(&frame) {
+                Err(err) if is_buffer_full(&err) => return Ok(()),
+                result => {
+                    self.tx_queues[index].pop_front();
+                    self.complete_transmission(index, frame, result);
+                }
+            }
+        }
+
+        self.set_writable_interest(index, false)
+    }
+
+    /// Queues a frame, or reports it as dropped if the queue is full.
+    fn enqueue(&mut self, index: usize, frame: CanFrame) {
+        if self.tx_queues[index].len() < self.settings.tx_queue_size {
+            self.tx_queues[index].push_back(frame);
+        } else {
+            self.report_tx_failure(index, frame, CanTxFailureCause::QueueFull);
+        }
+    }
+
+    /// Records a sent frame or reports a transmission error.
+    fn complete_transmission(&mut self, index: usize, frame: CanFrame, result: Result<()>) {
+        match result {
+            Ok(()) if self.settings.receives_own_frames() => {
+                let own_frames = &mut self.own_frames[index];
+                if own_frames.len() == MAX_PENDING_OWN_FRAMES {
+                    own_frames.pop_front();
+                }
+                own_frames.push_back(frame);
+            }
+            Ok(()) => {}
+            Err(err) => {
+                self.report_tx_failure(index, frame, CanTxFailureCause::Error(err.to_string()))
+            }
+        }
+    }
+
+    /// Counts and reports a frame which could not be sent.
+    fn report_tx_failure(&mut self, index: usize, frame: CanFrame, cause: CanTxFailureCause) {
+        self.tx_failure_counts[index] += 1;
+        if let Some(sender) = &self.tx_failures {
+            let _ = sender.send(CanTxFailure {
+                interface: CanInterface::Index(index),
+                frame,
+                cause,
+                failures: self.tx_failure_counts[index],
+            });
+        }
+    }
+
+    /// Adds or removes the writable interest of a socket.
+    fn set_writable_interest(&mut self, index: usize, writable: bool) -> Result<()> {
+        if let (Some(registry), Some(Some(socket))) = (&self.registry, self.sockets.get_mut(index))
+        {
+            let mut interest = socket.interest();
+            if writable {
+                interest = interest.add(Interest::WRITABLE);
+            }
+            registry.reregister(socket, Token(index), interest)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether a received frame was sent on the same interface.
+    ///
+    /// Own frames are received back in order, so that the matching frame and
+    /// the frames sent before it are no longer pending.
+    fn is_own_frame(&mut self, index: usize, frame: &CanFrame) -> bool {
+        let Some(own_frames) = self.own_frames.get_mut(index) else {
+            return false;
+        };
+        let Some(position) = own_frames.iter().position(|own| {
+            own.id_word() == frame.id_word()
+                && own.dlc() == frame.dlc()
+                && own.data() == frame.data()
+        }) else {
+            return false;
+        };
+        own_frames.drain(..=position);
+
+        true
+    }
+
+    /// Registers the backend of an opened interface.
+    ///
+    /// A backend previously assigned to the same index is closed.
+    fn attach(&mut self, index: usize, mut socket: Box<dyn CanBackend>) -> Result<()> {
+        self.detach(index)?;
+        if let Some(registry) = &self.registry {
+            let interest = socket.interest();
+            registry.register(&mut socket, Token(index), interest)?;
+        }
+        if self.sockets.len() <= index {
+            self.sockets.resize_with(index + 1, || None);
+            self.own_frames.resize_with(index + 1, VecDeque::new);
+            self.tx_queues.resize_with(index + 1, VecDeque::new);
+            self.tx_failure_counts.resize(index + 1, 0);
+        }
+        self.sockets[index] = Some(socket);
+
+        Ok(())
+    }
+
+    /// Deregisters and closes the socket of an interface, if any.
+    fn detach(&mut self, index: usize) -> Result<()> {
+        if let Some(own_frames) = self.own_frames.get_mut(index) {
+            own_frames.clear();
+        }
+        if let Some(tx_queue) = self.tx_queues.get_mut(index) {
+            tx_queue.clear();
+        }
+        if let Some(mut socket) = self.sockets.get_mut(index).and_then(Option::take) {
+            if let Some(registry) = &self.registry {
+                registry.deregister(&mut socket)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl IoPort<dyn CanBackend, CanData, CanData> for CanPortInner {
+    fn register(&mut self, registry: &Registry) -> Result<Token> {
+        self.register_sockets(registry)?;
+
+        Ok(Token(self.sockets.len()))
+    }
+
+    fn read(&mut self, token: Token) -> Result<CanData> {
+        self.read_socket(token)
+    }
+
+    fn write(&mut self, data: &CanData) -> Result<()> {
+        self.transmit(data)
+    }
+}
+
+impl IoPort<dyn CanBackend, CanData, CanCommand> for CanPortInner {
+    fn register(&mut self, registry: &Registry) -> Result<Token> {
+        self.register_sockets(registry)?;
+
+        Ok(WAKE_TOKEN)
+    }
+
+    fn read(&mut self, token: Token) -> Result<CanData> {
+        self.read_socket(token)
+    }
+
+    fn write(&mut self, command: &CanCommand) -> Result<()> {
+        match command {
+            CanCommand::Transmit(data) => self.transmit(data),
+            // The backend can only be taken from an owned command.
+            CanCommand::Attach(..) => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Attach command written by reference.",
+            )),
+            CanCommand::Detach(index) => {
+                if let Err(_e) = self.detach(*index) {
+                    #[cfg(feature = "tracing")]
+                    warn!(interface = index, error = %_e, "Failed to detach a CAN interface.");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn write_owned(&mut self, command: CanCommand) -> std::result::Result<(), (CanCommand, Error)> {
+        match command {
+            CanCommand::Attach(index, socket) => {
+                if let Err(_e) = self.attach(index, socket) {
+                    #[cfg(feature = "tracing")]
+                    warn!(interface = index, error = %_e, "Failed to attach a CAN interface.");
+                }
+                Ok(())
+            }
+            command => self.write(&command).map_err(|e| (command, e)),
+        }
+    }
+}
+
+/// Shared port broker client of the CAN port.
+///
+/// Interfaces cannot be attached or detached through the broker.
+struct CanBrokerPort(BrokerClient<CanData, CanBrokerCodec>);
+
+impl CanBrokerPort {
+    /// Connects to the shared port broker.
+    fn connect(config: &CanPortConfig, broker_path: &str) -> Result<Self> {
+        let filters: Vec<_> = config
+            .broker_filters
+            .iter()
+            .map(|filter| BrokerFilter {
+                key: filter.id,
+                mask: filter.mask,
+            })
+            .collect();
+
+        BrokerClient::connect(broker_path, CanBrokerCodec, &filters).map(Self)
+    }
+}
+
+/// Opened I/O port of the CAN port.
+enum CanIo {
+    /// CAN interfaces.
+    Interfaces(CanPortInner),
+
+    /// Shared port broker client.
+    Broker(CanBrokerPort),
+}
+
+impl CanIo {
+    /// Opens the I/O port, failing if any interface could not be opened.
+    fn try_new(
+        config: &CanPortConfig,
+        backend_factory: Option<Arc<CanBackendFactory>>,
+    ) -> std::result::Result<Self, CanPortError> {
+        match &config.broker_path {
+            Some(broker_path) => CanBrokerPort::connect(config, broker_path)
+                .map(Self::Broker)
+                .map_err(CanPortError::Broker),
+            None => CanPortInner::try_new(config, backend_factory).map(Self::Interfaces),
+        }
+    }
+
+    /// Spawns the I/O thread.
+    ///
+    /// The frames read by the I/O thread are stamped with their wall-clock
+    /// receive time. The receiver of the transmission failures is returned as
+    /// well, unless the interfaces are accessed through the broker.
+    fn spawn(self, options: IoThreadOptions) -> (CanIoThread, Option<Receiver<CanTxFailure>>) {
+        let (io_thread, tx_failures) = match self {
+            Self::Interfaces(mut interfaces) => {
+                let tx_failures = interfaces.tx_failures();
+                (
+                    IoThread::try_with_options(Timestamped::new(interfaces), options),
+                    Some(tx_failures),
+                )
+            }
+            Self::Broker(client) => (
+                IoThread::try_with_options(Timestamped::new(client), options),
+                None,
+            ),
+        };
+        let io_thread = io_thread
+            .unwrap_or_else(|e| panic!("Failed to start the I/O thread of the CAN port: {e}."));
+
+        (io_thread, tx_failures)
+    }
+}
+
+impl IoPort<UnixStream, CanData, CanCommand> for CanBrokerPort {
+    fn register(&mut self, registry: &Registry) -> Result<Token> {
+        self.0.register(registry)
+    }
+
+    fn read(&mut self, token: Token) -> Result<CanData> {
+        self.0.read(token)
+    }
+
+    fn write(&mut self, command: &CanCommand) -> Result<()> {
+        match command {
+            CanCommand::Transmit(data) => self.0.write(data),
+            CanCommand::Attach(..) | CanCommand::Detach(_) => Ok(()),
+        }
+    }
+
+    fn writable(&mut self, token: Token) -> Result<()> {
+        self.0.writable(token)
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        self.0.is_write_pending()
+    }
+}
+
+/// Broker codec for CAN data.
+///
+/// Frames are serialized as the interface index (`u32`), a flags byte
+/// (extended identifier, remote frame), the raw identifier (`u32`), the DLC
+/// and the frame data. Error frames and timestamps are not forwarded, and
+/// interfaces must be addressed by index.
+struct CanBrokerCodec;
+
+impl CanBrokerCodec {
+    /// Extended identifier flag.
+    const EXTENDED: u8 = 0x01;
+
+    /// Remote frame flag.
+    const REMOTE: u8 = 0x02;
+}
+
+impl BrokerCodec<CanData> for CanBrokerCodec {
+    fn encode(&self, item: &CanData, buf: &mut Vec<u8>) -> Result<()> {
+        if item.frame.is_error_frame() {
+            return Err(Error::new(ErrorKind::InvalidData, "Error frame."));
+        }
+        let CanInterface::Index(interface) = item.interface else {
+            return Err(Error::new(ErrorKind::InvalidInput, "Unresolved interface."));
+        };
+        let mut flags = 0;
+        if item.frame.is_extended() {
+            flags |= Self::EXTENDED;
+        }
+        if item.frame.is_remote_frame() {
+            flags |= Self::REMOTE;
+        }
+        buf.extend_from_slice(&(interface as u32).to_le_bytes());
+        buf.push(flags);
+        buf.extend_from_slice(&self.key(item).to_le_bytes());
+        buf.push(item.frame.dlc() as u8);
+        buf.extend_from_slice(item.frame.data());
+
+        Ok(())
+    }
+
+    fn decode(&self, buf: &[u8]) -> Result<CanData> {
+        let invalid = || Error::new(ErrorKind::InvalidData, "Invalid CAN frame.");
+        if buf.len() < 10 {
+            return Err(invalid());
+        }
+        let interface = u32::from_le_bytes(buf[..4].try_into().unwrap()) as usize;
+        let flags = buf[4];
+        let raw_id = u32::from_le_bytes(buf[5..9].try_into().unwrap());
+        let dlc = buf[9] as usize;
+
+        let id = if flags & Self::EXTENDED != 0 {
+            Id::Extended(ExtendedId::new(raw_id).ok_or_else(invalid)?)
+        } else {
+            Id::Standard(StandardId::new(raw_id as u16).ok_or_else(invalid)?)
+        };
+        let frame = if flags & Self::REMOTE != 0 {
+            CanFrame::new_remote(id, dlc)
+        } else {
+            CanFrame::new(id, &buf[10..])
+        }
+        .ok_or_else(invalid)?;
+
+        Ok(CanData::new(interface, frame))
+    }
+
+    fn key(&self, item: &CanData) -> u32 {
+        match item.frame.id() {
+            Id::Standard(id) => id.as_raw() as u32,
+            Id::Extended(id) => id.as_raw(),
+        }
+    }
+}
+
+/// Creates a shared port broker owning the configured CAN interfaces and
+/// listening on the provided socket path.
+///
+/// The receive filters of the interfaces are applied by the broker, while the
+/// `broker_path` and `broker_filters` configurations are ignored.
+///
+/// An error is returned if any interface cannot be opened or if the broker
+/// cannot be started.
+pub fn shared_port_broker(
+    config: &CanPortConfig,
+    socket_path: impl AsRef<Path>,
+) -> std::result::Result<SharedPortBroker, CanPortError> {
+    let interfaces = CanPortInner::try_new(config, None)?;
+
+    SharedPortBroker::new(socket_path, interfaces, CanBrokerCodec)
+        .map_err(CanPortError::BrokerStart)
+}
+
+/// I/O thread of the CAN port, reading timestamped frames.
+type CanIoThread = IoThread<(Instant, CanData), CanCommand>;
+
+/// CAN port model.
+///
+/// This model
+/// * listens the specified CAN ports and injects into the simulation values
+///   read from it as CAN frames, either when they are processed or at the
+///   simulation time matching their arrival,
+/// * outputs CAN frames from the simulation to the CAN port,
+/// * reports the errors and the exit of its I/O thread,
+/// * reports the changes of its link status, which is degraded when only
+///   some of its interfaces are attached,
+/// * reports its traffic metrics, periodically or on request.
+pub struct CanPort {
+    /// CAN frame -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// Received CAN error -- output port.
+    pub error_out: Output<CanErrorEvent>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// CAN bus state -- output port.
+    pub bus_state_out: Output<CanBusState>,
+
+    /// Transmitted CAN frames confirmed on the bus -- output port.
+    pub tx_confirm_out: Output<CanData>,
+
+    /// CAN frame transmission failure -- output port.
+    pub tx_failure_out: Output<CanTxFailure>,
+
+    /// CAN interface status -- output port.
+    pub status_out: Output<CanInterfaceStatus>,
+
+    /// Link status change -- output port.
+    pub link_status_out: Output<LinkStatus>,
+
+    /// Traffic metrics -- output port.
+    pub metrics_out: Output<PortMetrics>,
+
+    /// Model instance configuration.
+    config: CanPortConfig,
+
+    /// Names of the interfaces, by index.
+    names: Vec<InterfaceName>,
+
+    /// Socket settings of the attached interfaces.
+    settings: CanBackendSettings,
+
+    /// I/O thread.
+    io_thread: CanIoThread,
+
+    /// Transmission failures reported by the I/O thread.
+    tx_failures: Option<Receiver<CanTxFailure>>,
+
+    /// Netlink handles of the interfaces, `None` for interfaces which could
+    /// not be found.
+    netlink: Vec<Option<NlInterface>>,
+
+    /// Attachment status of the interfaces.
+    attached: Vec<bool>,
+
+    /// Interface status events not yet reported.
+    pending_status: Vec<CanInterfaceStatus>,
+
+    /// Error of the shared port broker connection, if it could not be
+    /// reached.
+    broker_failure: Option<String>,
+
+    /// Link status.
+    link: LinkMonitor,
+
+    /// Traffic metrics.
+    metrics: PortMetrics,
+
+    /// Time alignment of the received frames, if enabled.
+    alignment: Option<TimeAlignment>,
+
+    /// Tracing span of the model instance.
+    #[cfg(feature = "tracing")]
+    span: Span,
+}
+
+impl CanPort {
+    /// Creates a new CAN port model.
+    ///
+    /// Interfaces which could not be opened, or whose shared port broker
+    /// could not be reached, are initially detached.
+    fn new(
+        proto: ProtoCanPort,
+        io_thread: CanIoThread,
+        tx_failures: Option<Receiver<CanTxFailure>>,
+        failures: Vec<(usize, Error)>,
+        broker_failure: Option<String>,
+    ) -> Self {
+        let ProtoCanPort {
+            frame_out,
+            error_out,
+            stalled_out,
+            io_status_out,
+            bus_state_out,
+            tx_confirm_out,
+            tx_failure_out,
+            status_out,
+            link_status_out,
+            metrics_out,
+            config,
+            backend_factory,
+            ..
+        } = proto;
+        let settings = CanBackendSettings::new(&config, backend_factory);
+        let names = config
+            .interfaces
+            .iter()
+            .map(|interface| InterfaceName::new(interface))
+            .collect();
+        let netlink = config
+            .interfaces
+            .iter()
+            .map(|interface| settings.open_netlink(interface))
+            .collect();
+        #[cfg(feature = "tracing")]
+        if let Some(error) = &broker_failure {
+            warn!(error = %error, "Failed to connect to the shared port broker.");
+        }
+        let mut attached = vec![true; config.interfaces.len()];
+        let pending_status = failures
+            .into_iter()
+            .map(|(index, error)| {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    interface = %config.interfaces[index],
+                    error = %error,
+                    "Failed to open a CAN interface."
+                );
+                attached[index] = false;
+                CanInterfaceStatus {
+                    interface: CanInterface::Index(index),
+                    state: CanInterfaceState::Failed(error.to_string()),
+                }
+            })
+            .collect();
+
+        Self {
+            frame_out,
+            error_out,
+            stalled_out,
+            io_status_out,
+            bus_state_out,
+            tx_confirm_out,
+            tx_failure_out,
+            status_out,
+            link_status_out,
+            metrics_out,
+            config,
+            names,
+            settings,
+            io_thread,
+            tx_failures,
+            netlink,
+            attached,
+            pending_status,
+            broker_failure,
+            link: LinkMonitor::new(),
+            metrics: PortMetrics::default(),
+            alignment: None,
+            #[cfg(feature = "tracing")]
+            span: Span::none(),
+        }
+    }
+
+    /// Transmits CAN frame -- input port.
+    ///
+    /// Frames addressed to an unknown or detached interface are dropped, as
+    /// well as frames sent after the I/O thread has exited, which are counted
+    /// as errors in the metrics.
+    pub fn frame_in(&mut self, mut data: CanData) {
+        let Some(index) = data.interface.index(&self.config.interfaces) else {
+            #[cfg(feature = "tracing")]
+            warn!(
+                parent: &self.span,
+                interface = %data.interface,
+                direction = "tx",
+                id = %format_args!("{:X}", data.frame.raw_id()),
+                "Dropping a CAN frame to an unknown CAN interface."
+            );
+            return;
+        };
+        if !self.attached[index] {
+            #[cfg(feature = "tracing")]
+            warn!(
+                parent: &self.span,
+                interface = %data.interface,
+                direction = "tx",
+                id = %format_args!("{:X}", data.frame.raw_id()),
+                "Dropping a CAN frame to a detached CAN interface."
+            );
+            return;
+        }
+        #[cfg(feature = "tracing")]
+        info!(
+            parent: &self.span,
+            interface = %self.config.interfaces[index],
+            direction = "tx",
+            id = %format_args!("{:X}", data.frame.raw_id()),
+            length = data.frame.data().len(),
+            data = %HexDump::new(data.frame.data()),
+            "Will transmit a CAN frame."
+        );
+        data.interface = CanInterface::Index(index);
+        let len = data.payload_len();
+        if let Err(_e) = self.io_thread.send(CanCommand::Transmit(data)) {
+            #[cfg(feature = "tracing")]
+            warn!(
+                parent: &self.span,
+                interface = %self.config.interfaces[index],
+                direction = "tx",
+                error = %_e,
+                "Dropping a CAN frame after the exit of the I/O thread."
+            );
+            self.metrics.record_error();
+            return;
+        }
+        self.metrics.record_out(len);
+    }
+
+    /// Attaches a CAN interface while the simulation runs -- input port.
+    ///
+    /// An interface which is not part of the configured interfaces is
+    /// appended to them once attached and addressed by the next free index,
+    /// while a failure to attach it is reported with its name. A detached
+    /// interface, or a configured interface which could not be opened, keeps
+    /// its index when attached again. Attached interfaces receive the configured filters
+    /// and socket options. The outcome is reported on the status output.
+    ///
+    /// Interfaces cannot be attached when accessed through a shared port
+    /// broker.
+    pub async fn attach_in(&mut self, interface: String) {
+        if self.config.broker_path.is_some() {
+            #[cfg(feature = "tracing")]
+            warn!(
+                parent: &self.span,
+                interface = %interface,
+                "Cannot attach a CAN interface through a shared port broker."
+            );
+            return;
+        }
+        let index = match self.config.interface_index(&interface) {
+            Some(index) if self.attached[index] => {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    parent: &self.span,
+                    interface = %interface,
+                    "CAN interface already attached."
+                );
+                return;
+            }
+            Some(index) => index,
+            None => self.config.interfaces.len(),
+        };
+        let attach = self.settings.open(&interface).and_then(|socket| {
+            self.io_thread
+                .send(CanCommand::Attach(index, socket))
+                .map_err(|e| Error::other(e.to_string()))
+        });
+        let status = match attach {
+            Ok(()) => {
+                #[cfg(feature = "tracing")]
+                info!(
+                    parent: &self.span,
+                    interface = %interface,
+                    "Attaching a CAN interface."
+                );
+                if index == self.config.interfaces.len() {
+                    self.names.push(InterfaceName::new(&interface));
+                    self.config.interfaces.push(interface);
+                    self.netlink.push(None);
+                    self.attached.push(false);
+                }
+                self.netlink[index] = self.settings.open_netlink(&self.config.interfaces[index]);
+                self.attached[index] = true;
+                CanInterfaceStatus {
+                    interface: self.address(CanInterface::Index(index)),
+                    state: CanInterfaceState::Attached,
+                }
+            }
+            Err(error) => {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    parent: &self.span,
+                    interface = %interface,
+                    error = %error,
+                    "Failed to attach a CAN interface."
+                );
+                let interface = if index < self.config.interfaces.len() {
+                    self.address(CanInterface::Index(index))
+                } else {
+                    CanInterface::Name(InterfaceName::new(&interface))
+                };
+                CanInterfaceStatus {
+                    interface,
+                    state: CanInterfaceState::Failed(error.to_string()),
+                }
+            }
+        };
+        self.status_out.send(status).await;
+        self.update_link().await;
+    }
+
+    /// Detaches a CAN interface while the simulation runs -- input port.
+    ///
+    /// Frames received on the interface and not yet processed are still
+    /// forwarded.
+    pub async fn detach_in(&mut self, interface: CanInterface) {
+        if self.config.broker_path.is_some() {
+            #[cfg(feature = "tracing")]
+            warn!(
+                parent: &self.span,
+                interface = %interface,
+                "Cannot detach a CAN interface through a shared port broker."
+            );
+            return;
+        }
+        let Some(index) = interface
+            .index(&self.config.interfaces)
+            .filter(|&index| self.attached[index])
+        else {
+            #[cfg(feature = "tracing")]
+            warn!(
+                parent: &self.span,
+                interface = %interface,
+                "CAN interface not attached."
+            );
+            return;
+        };
+        #[cfg(feature = "tracing")]
+        info!(
+            parent: &self.span,
+            interface = %interface,
+            "Detaching a CAN interface."
+        );
+        self.netlink[index] = None;
+        self.attached[index] = false;
+        // The interface is closed anyway if the I/O thread has exited.
+        let _ = self.io_thread.send(CanCommand::Detach(index));
+        let status = CanInterfaceStatus {
+            interface: self.address(CanInterface::Index(index)),
+            state: CanInterfaceState::Detached,
+        };
+        self.status_out.send(status).await;
+        self.update_link().await;
+    }
+
+    /// Forwards the CAN frames and errors received on the CAN port.
+    ///
+    /// Interfaces which could not be opened when the model was built are
+    /// reported on the status output at the first call. If time alignment is
+    /// enabled, the received frames are scheduled for the simulation time
+    /// matching their arrival, unless this time has passed.
+    pub async fn process(&mut self, _: (), cx: &mut Context<Self>) {
+        for mut status in std::mem::take(&mut self.pending_status) {
+            status.interface = self.address(status.interface);
+            self.status_out.send(status).await;
+        }
+        self.update_link().await;
+        for (arrival, mut data) in self.io_thread.try_recv_all() {
+            data.interface = self.address(data.interface);
+            if let CanFrame::Error(frame) = data.frame {
+                let error = CanErrorEvent {
+                    interface: data.interface,
+                    error: frame.into_error(),
+                };
+                #[cfg(feature = "tracing")]
+                warn!(
+                    parent: &self.span,
+                    interface = %data.interface,
+                    direction = "rx",
+                    error = ?error.error,
+                    "Received a CAN error frame."
+                );
+                self.metrics.record_error();
+                self.error_out.send(error).await;
+                continue;
+            }
+            if data.own {
+                if self.config.tx_confirmations {
+                    self.tx_confirm_out.send(data).await;
+                }
+                if !self.config.recv_own_msgs {
+                    continue;
+                }
+            }
+            #[cfg(feature = "tracing")]
+            info!(
+                parent: &self.span,
+                interface = %data.interface,
+                direction = "rx",
+                id = %format_args!("{:X}", data.frame.raw_id()),
+                length = data.frame.data().len(),
+                data = %HexDump::new(data.frame.data()),
+                own = data.own,
+                "Received a CAN frame."
+            );
+            self.metrics.record_in(data.payload_len());
+            let deadline = self
+                .alignment
+                .as_ref()
+                .and_then(|alignment| alignment.deadline(arrival, cx.time()));
+            match deadline {
+                Some(deadline) => cx
+                    .schedule_event(deadline, Self::forward_frame, data)
+                    .unwrap(),
+                None => self.frame_out.send(data).await,
+            }
+        }
+        while let Some(Ok(mut failure)) = self.tx_failures.as_ref().map(Receiver::try_recv) {
+            failure.interface = self.address(failure.interface);
+            #[cfg(feature = "tracing")]
+            warn!(
+                parent: &self.span,
+                interface = %failure.interface,
+                direction = "tx",
+                id = %format_args!("{:X}", failure.frame.raw_id()),
+                cause = ?failure.cause,
+                "Failed to transmit a CAN frame."
+            );
+            self.metrics.record_error();
+            self.tx_failure_out.send(failure).await;
+        }
+        while let Ok(status) = self.io_thread.try_recv_status() {
+            #[cfg(feature = "tracing")]
+            warn!(
+                parent: &self.span,
+                status = %status,
+                "I/O thread error or exit."
+            );
+            if matches!(
+                status,
+                IoThreadStatus::ReadError(..) | IoThreadStatus::WriteError(..)
+            ) {
+                self.metrics.record_error();
+            }
+            let change = self.link.set_io_status(&status);
+            self.io_status_out.send(status).await;
+            self.report_link(change).await;
+        }
+        self.check_watchdog().await;
+    }
+
+    /// Queries the bus state of the CAN interfaces through netlink -- input
+    /// port.
+    ///
+    /// The state of each interface is reported, whether or not it changed
+    /// since the previous query. Interfaces whose state cannot be queried are
+    /// skipped.
+    pub async fn query_bus_state(&mut self) {
+        for index in 0..self.netlink.len() {
+            let Some(handle) = &self.netlink[index] else {
+                continue;
+            };
+            let details = match handle.details() {
+                Ok(details) => details,
+                Err(_e) => {
+                    #[cfg(feature = "tracing")]
+                    warn!(
+                        parent: &self.span,
+                        interface = %self.config.interfaces[index],
+                        error = %_e,
+                        "Failed to query the bus state of a CAN interface."
+                    );
+                    continue;
+                }
+            };
+            let bus_state = CanBusState {
+                interface: self.address(CanInterface::Index(index)),
+                state: details.can.state,
+                tx_errors: details.can.berr_counter.map(|counter| counter.txerr),
+                rx_errors: details.can.berr_counter.map(|counter| counter.rxerr),
+                bitrate: details.can.bit_timing.map(|timing| timing.bitrate),
+            };
+            self.bus_state_out.send(bus_state).await;
+        }
+    }
+
+    /// Reports the traffic metrics of the port -- input port.
+    pub async fn report_metrics(&mut self) {
+        self.metrics.set_queue_depths(&self.io_thread.stats());
+        self.metrics_out.send(self.metrics).await;
+    }
+
+    /// Forwards a received frame whose injection was delayed.
+    async fn forward_frame(&mut self, data: CanData) {
+        self.frame_out.send(data).await;
+    }
+
+    /// Returns the address of a receiving interface as configured.
+    ///
+    /// Interfaces without a configured name keep their index.
+    fn address(&self, interface: CanInterface) -> CanInterface {
+        match interface {
+            CanInterface::Index(index) if self.config.interface_names => self
+                .names
+                .get(index)
+                .map_or(interface, |name| CanInterface::Name(*name)),
+            interface => interface,
+        }
+    }
+
+    /// Schedules a periodic call of the provided method, first after the
+    /// configured delta or, by default, after one period.
+    fn schedule_periodic<F, S>(
+        &self,
+        context: &mut Context<Self>,
+        period: ConfigDuration,
+        method: F,
+    ) where
+        F: for<'a> InputFn<'a, Self, (), S> + Clone,
+        S: Send + 'static,
+    {
+        let delta = self.config.delta.unwrap_or(period);
+
+        context
+            .schedule_periodic_event(Duration::from(delta), Duration::from(period), method, ())
+            .unwrap();
+    }
+
+    /// Reports a stalled I/O thread once, until it recovers.
+    async fn check_watchdog(&mut self) {
+        let Some(timeout) = self.config.watchdog_timeout else {
+            return;
+        };
+        match self.io_thread.check_stall(*timeout) {
+            Some(StallChange::Stalled(age)) => {
+                #[cfg(feature = "tracing")]
+                warn!(parent: &self.span, age = ?age, "I/O thread stalled.");
+                self.stalled_out.send(age).await;
+                let change = self.link.set_stalled(true);
+                self.report_link(change).await;
+            }
+            Some(StallChange::Recovered) => {
+                let change = self.link.set_stalled(false);
+                self.report_link(change).await;
+            }
+            None => {}
+        }
+    }
+
+    /// Updates the link status from the attachment status of the
+    /// interfaces.
+    async fn update_link(&mut self) {
+        let total = self.attached.len();
+        let attached = self.attached.iter().filter(|&&attached| attached).count();
+        let link = if let Some(error) = &self.broker_failure {
+            LinkStatus::disconnected(format!("shared port broker could not be reached: {error}"))
+        } else if attached == total {
+            LinkStatus::Connected
+        } else if attached == 0 {
+            LinkStatus::disconnected("no CAN interface attached")
+        } else {
+            LinkStatus::degraded(format!("{attached} of {total} CAN interfaces attached"))
+        };
+        let change = self.link.set_link(link);
+        self.report_link(change).await;
+    }
+
+    /// Reports a link status change, if any.
+    async fn report_link(&mut self, change: Option<LinkStatus>) {
+        if let Some(status) = change {
+            #[cfg(feature = "tracing")]
+            info!(parent: &self.span, status = %status, "Link status changed.");
+            self.link_status_out.send(status).await;
+        }
+    }
+}
+
+impl Model for CanPort {
+    async fn init(mut self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        #[cfg(feature = "tracing")]
+        {
+            self.span = info_span!(
+                "can_port",
+                model = context.name(),
+                interfaces = %self.config.interfaces.join(","),
+            );
+        }
+        self.alignment = self
+            .config
+            .time_alignment
+            .alignment(context.time(), self.config.period);
+        if let Some(period) = self.config.period {
+            self.schedule_periodic(context, period, Self::process);
+        }
+        if let Some(period) = self.config.bus_state_period {
+            self.schedule_periodic(context, period, Self::query_bus_state);
+        }
+        if let Some(period) = self.config.metrics_period {
+            self.schedule_periodic(context, period, Self::report_metrics);
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for CanPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CanPort").finish_non_exhaustive()
+    }
+}
+
+/// CAN port model prototype.
+#[allow(missing_debug_implementations)]
+pub struct ProtoCanPort {
+    /// Received CAN frames -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// Received CAN errors -- output port.
+    pub error_out: Output<CanErrorEvent>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// CAN bus state -- output port.
+    pub bus_state_out: Output<CanBusState>,
+
+    /// Transmitted CAN frames confirmed on the bus -- output port.
+    pub tx_confirm_out: Output<CanData>,
+
+    /// CAN frame transmission failure -- output port.
+    pub tx_failure_out: Output<CanTxFailure>,
+
+    /// CAN interface status -- output port.
+    pub status_out: Output<CanInterfaceStatus>,
+
+    /// Link status change -- output port.
+    pub link_status_out: Output<LinkStatus>,
+
+    /// Traffic metrics -- output port.
+    pub metrics_out: Output<PortMetrics>,
+
+    /// CAN port model instance configuration.
+    config: CanPortConfig,
+
+    /// Factory of the custom backend.
+    backend_factory: Option<Arc<CanBackendFactory>>,
+
+    /// I/O port opened by the fallible constructor.
+    io: Option<CanIo>,
+}
+
+impl ProtoCanPort {
+    /// Creates a new CAN port model prototype.
+    ///
+    /// The CAN interfaces are opened when the model is built. Interfaces
+    /// which cannot be opened are reported on the status output and can be
+    /// attached later on. If the shared port broker cannot be reached, all
+    /// configured interfaces are reported as failed and the link is reported
+    /// as disconnected.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the I/O thread cannot be created.
+    pub fn new(config: CanPortConfig) -> Self {
+        Self {
+            frame_out: Output::default(),
+            error_out: Output::default(),
+            stalled_out: Output::default(),
+            io_status_out: Output::default(),
+            bus_state_out: Output::default(),
+            tx_confirm_out: Output::default(),
+            tx_failure_out: Output::default(),
+            status_out: Output::default(),
+            link_status_out: Output::default(),
+            metrics_out: Output::default(),
+            config,
+            backend_factory: None,
+            io: None,
+        }
+    }
+
+    /// Creates a new CAN port model prototype, opening the CAN interfaces or
+    /// connecting to the shared port broker immediately.
+    ///
+    /// An error is returned if any interface cannot be opened or if the
+    /// broker cannot be reached.
+    pub fn try_new(config: CanPortConfig) -> std::result::Result<Self, CanPortError> {
+        Self::new(config).try_open()
+    }
+
+    /// Sets the factory opening the interfaces when the `backend`
+    /// configuration is `custom`.
+    ///
+    /// The factory is also used to open the interfaces attached while the
+    /// simulation runs.
+    pub fn with_backend_factory(
+        mut self,
+        factory: impl Fn(&str) -> Result<Box<dyn CanBackend>> + Send + Sync + 'static,
+    ) -> Self {
+        self.backend_factory = Some(Arc::new(factory));
+        self
+    }
+
+    /// Opens the CAN interfaces or connects to the shared port broker
+    /// immediately rather than when the model is built.
+    ///
+    /// An error is returned if any interface cannot be opened or if the
+    /// broker cannot be reached.
+    pub fn try_open(mut self) -> std::result::Result<Self, CanPortError> {
+        self.io = Some(CanIo::try_new(&self.config, self.backend_factory.clone())?);
+
+        Ok(self)
+    }
+}
+
+impl ProtoModel for ProtoCanPort {
+    type Model = CanPort;
+
+    fn build(mut self, _: &mut BuildContext<Self>) -> Self::Model {
+        let options = IoThreadOptions {
+            heartbeat_period: heartbeat_period(self.config.watchdog_timeout),
+            shutdown_timeout: self.config.shutdown_timeout.map(Duration::from),
+        };
+
+        let mut broker_failure = None;
+        let (io, failures) = match (self.io.take(), &self.config.broker_path) {
+            (Some(io), _) => (io, Vec::new()),
+            (None, Some(broker_path)) => match CanBrokerPort::connect(&self.config, broker_path) {
+                Ok(client) => (CanIo::Broker(client), Vec::new()),
+                Err(error) => {
+                    // The interfaces of the unreachable broker are reported as
+                    // failed, and the frames sent to them are rejected.
+                    let interfaces = CanPortInner::detached(&self.config, None);
+                    let failures = (0..self.config.interfaces.len())
+                        .map(|index| (index, Error::new(error.kind(), error.to_string())))
+                        .collect();
+                    broker_failure = Some(error.to_string());
+                    (CanIo::Interfaces(interfaces), failures)
+                }
+            },
+            (None, None) => {
+                let (interfaces, failures) =
+                    CanPortInner::open(&self.config, self.backend_factory.clone());
+                (CanIo::Interfaces(interfaces), failures)
+            }
+        };
+        let (io_thread, tx_failures) = io.spawn(options);
+
+        Self::Model::new(self, io_thread, tx_failures, failures, broker_failure)
+    }
+}
+
+/// Checks whether a write failed because the send buffer is full.
+fn is_buffer_full(err: &Error) -> bool {
+    err.kind() == ErrorKind::WouldBlock || err.raw_os_error() == Some(libc::ENOBUFS)
+}
+
+/// Opens the netlink handle of an interface.
+fn open_netlink(interface: &str) -> Option<NlInterface> {
+    NlInterface::open(interface)
+        .inspect_err(|_e| {
+            #[cfg(feature = "tracing")]
+            warn!(
+                interface,
+                error = %_e,
+                "Bus state of a CAN interface not available."
+            );
+        })
+        .ok()
+}
+
+/// Returns the I/O thread heartbeat period suitable for the watchdog timeout.
+fn heartbeat_period(watchdog_timeout: Option<ConfigDuration>) -> Option<Duration> {
+    watchdog_timeout.map(|timeout| *timeout / 2)
 }
 
 impl fmt::Debug for ProtoCanPort {
@@ -0,0 +1,481 @@
+//! NMEA 2000 layer.
+//!
+//! This module contains a model implementing the NMEA 2000 specifics on top of
+//! the [`j1939`](crate::j1939) layer:
+//! * fast-packet reassembly and segmentation of messages up to 223 bytes,
+//! * routing of received messages to typed outputs for common navigation
+//!   parameter groups.
+//!
+//! The layer should be connected to the message output and input of a
+//! [`J1939Layer`](crate::j1939::J1939Layer), which handles the CAN frames,
+//! the address claim and the J1939 transport protocol. NMEA 2000 messages are
+//! represented as [`J1939Message`]s.
+//!
+//! Fast-packet parameter groups are not distinguishable on the bus, so the
+//! layer reassembles and segments the parameter groups of a configurable set,
+//! initialized with the common fast-packet parameter groups of
+//! [`FAST_PACKET_PGNS`]. Incomplete fast-packet messages are discarded when
+//! superseded by a new message of the same source and parameter group.
+//!
+//! Physical values of the typed messages are expressed in SI units, with
+//! angles in radians; values not available in a message are `None`.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_can_port::j1939::{J1939Layer, J1939Message};
+//! use nexosim_can_port::nmea2000::Nmea2000Layer;
+//!
+//! let j1939 = J1939Layer::new("can0", 0x8000_0000_0000_1234, 0x80);
+//!
+//! // Handle a proprietary fast-packet parameter group as well.
+//! let nmea2000 = Nmea2000Layer::new().with_fast_packet_pgn(130820);
+//!
+//! // Connect `J1939Layer::message_out` to `Nmea2000Layer::j1939_in` and
+//! // `Nmea2000Layer::j1939_out` to `J1939Layer::message_in`. Bench models
+//! // send messages such as a product information report:
+//! let message = J1939Message::new(126996, vec![0xFF; 134]);
+//! ```
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use nexosim::model::Model;
+use nexosim::ports::Output;
+
+use crate::j1939::J1939Message;
+
+/// Common fast-packet parameter groups.
+pub const FAST_PACKET_PGNS: &[u32] = &[
+    126208, // NMEA request/command/acknowledge group function
+    126464, // PGN list
+    126720, // proprietary fast-packet addressable
+    126983, // alert
+    126985, // alert text
+    126996, // product information
+    126998, // configuration information
+    127233, // man overboard notification
+    127237, // heading/track control
+    127489, // engine parameters, dynamic
+    127496, // trip parameters, vessel
+    127497, // trip parameters, engine
+    127503, // AC input status
+    127506, // DC detailed status
+    128275, // distance log
+    129029, // GNSS position data
+    129038, // AIS class A position report
+    129039, // AIS class B position report
+    129040, // AIS class B extended position report
+    129041, // AIS aids to navigation report
+    129284, // navigation data
+    129285, // navigation route/waypoint information
+    129540, // GNSS satellites in view
+    129794, // AIS class A static and voyage related data
+    129809, // AIS class B static data, part A
+    129810, // AIS class B static data, part B
+    130074, // route and waypoint service, waypoint list
+    130577, // direction data
+];
+
+/// Maximum length of a fast-packet message.
+pub const MAX_FAST_PACKET_LEN: usize = 223;
+
+/// Vessel heading PGN.
+pub const PGN_VESSEL_HEADING: u32 = 127250;
+
+/// Speed PGN.
+pub const PGN_SPEED: u32 = 128259;
+
+/// Position, rapid update PGN.
+pub const PGN_POSITION_RAPID_UPDATE: u32 = 129025;
+
+/// COG and SOG, rapid update PGN.
+pub const PGN_COG_SOG_RAPID_UPDATE: u32 = 129026;
+
+/// Wind data PGN.
+pub const PGN_WIND_DATA: u32 = 130306;
+
+/// Vessel heading (PGN 127250).
+#[derive(Clone, Debug, PartialEq)]
+pub struct VesselHeading {
+    /// Source address.
+    pub source: u8,
+
+    /// Sequence identifier.
+    pub sid: u8,
+
+    /// Heading, in radians.
+    pub heading: Option<f64>,
+
+    /// Magnetic deviation, in radians.
+    pub deviation: Option<f64>,
+
+    /// Magnetic variation, in radians.
+    pub variation: Option<f64>,
+
+    /// Heading reference: 0 for true, 1 for magnetic.
+    pub reference: u8,
+}
+
+/// Speed (PGN 128259).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Speed {
+    /// Source address.
+    pub source: u8,
+
+    /// Sequence identifier.
+    pub sid: u8,
+
+    /// Speed through water, in meters per second.
+    pub water_speed: Option<f64>,
+
+    /// Speed over ground, in meters per second.
+    pub ground_speed: Option<f64>,
+}
+
+/// Position, rapid update (PGN 129025).
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionRapidUpdate {
+    /// Source address.
+    pub source: u8,
+
+    /// Latitude, in degrees.
+    pub latitude: Option<f64>,
+
+    /// Longitude, in degrees.
+    pub longitude: Option<f64>,
+}
+
+/// COG and SOG, rapid update (PGN 129026).
+#[derive(Clone, Debug, PartialEq)]
+pub struct CogSogRapidUpdate {
+    /// Source address.
+    pub source: u8,
+
+    /// Sequence identifier.
+    pub sid: u8,
+
+    /// Course over ground reference: 0 for true, 1 for magnetic.
+    pub reference: u8,
+
+    /// Course over ground, in radians.
+    pub cog: Option<f64>,
+
+    /// Speed over ground, in meters per second.
+    pub sog: Option<f64>,
+}
+
+/// Wind data (PGN 130306).
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindData {
+    /// Source address.
+    pub source: u8,
+
+    /// Sequence identifier.
+    pub sid: u8,
+
+    /// Wind speed, in meters per second.
+    pub speed: Option<f64>,
+
+    /// Wind angle, in radians.
+    pub angle: Option<f64>,
+
+    /// Wind reference, e.g. 2 for apparent wind.
+    pub reference: u8,
+}
+
+/// Fast-packet reception session.
+#[derive(Debug)]
+struct FastPacketSession {
+    /// Sequence counter of the message.
+    sequence: u8,
+
+    /// Announced message length.
+    size: usize,
+
+    /// Next expected frame counter.
+    next: u8,
+
+    /// Received data.
+    data: Vec<u8>,
+}
+
+/// NMEA 2000 layer model.
+///
+/// This model reassembles and segments fast-packet messages exchanged with a
+/// J1939 layer and decodes the received messages of common parameter groups.
+pub struct Nmea2000Layer {
+    /// J1939 messages to be transmitted -- output port.
+    pub j1939_out: Output<J1939Message>,
+
+    /// Received NMEA 2000 messages, with reassembled fast-packet data --
+    /// output port.
+    pub message_out: Output<J1939Message>,
+
+    /// Received vessel headings -- output port.
+    pub heading_out: Output<VesselHeading>,
+
+    /// Received speeds -- output port.
+    pub speed_out: Output<Speed>,
+
+    /// Received position rapid updates -- output port.
+    pub position_out: Output<PositionRapidUpdate>,
+
+    /// Received COG and SOG rapid updates -- output port.
+    pub cog_sog_out: Output<CogSogRapidUpdate>,
+
+    /// Received wind data -- output port.
+    pub wind_out: Output<WindData>,
+
+    /// Fast-packet parameter groups.
+    fast_packet_pgns: HashSet<u32>,
+
+    /// Fast-packet reception sessions, by source address and parameter group.
+    sessions: HashMap<(u8, u32), FastPacketSession>,
+
+    /// Sequence counters of the transmitted fast-packet messages, by parameter
+    /// group.
+    sequences: HashMap<u32, u8>,
+}
+
+impl Nmea2000Layer {
+    /// Creates a new NMEA 2000 layer handling the parameter groups of
+    /// [`FAST_PACKET_PGNS`] as fast-packet messages.
+    pub fn new() -> Self {
+        Self {
+            j1939_out: Output::new(),
+            message_out: Output::new(),
+            heading_out: Output::new(),
+            speed_out: Output::new(),
+            position_out: Output::new(),
+            cog_sog_out: Output::new(),
+            wind_out: Output::new(),
+            fast_packet_pgns: FAST_PACKET_PGNS.iter().copied().collect(),
+            sessions: HashMap::new(),
+            sequences: HashMap::new(),
+        }
+    }
+
+    /// Handles an additional parameter group as fast-packet messages.
+    pub fn with_fast_packet_pgn(mut self, pgn: u32) -> Self {
+        self.fast_packet_pgns.insert(pgn);
+        self
+    }
+
+    /// Received J1939 message -- input port.
+    pub async fn j1939_in(&mut self, message: J1939Message) {
+        if !self.fast_packet_pgns.contains(&message.pgn) {
+            self.route(message).await;
+            return;
+        }
+        if let Some(message) = self.reassemble(message) {
+            self.route(message).await;
+        }
+    }
+
+    /// NMEA 2000 message to be transmitted -- input port.
+    ///
+    /// Messages of fast-packet parameter groups are segmented, and dropped if
+    /// longer than [`MAX_FAST_PACKET_LEN`]. Other messages are forwarded
+    /// as-is, the J1939 layer using the transport protocol for messages longer
+    /// than 8 bytes.
+    pub async fn message_in(&mut self, message: J1939Message) {
+        if !self.fast_packet_pgns.contains(&message.pgn) {
+            self.j1939_out.send(message).await;
+            return;
+        }
+        if message.data.len() > MAX_FAST_PACKET_LEN {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Dropping NMEA 2000 message PGN {}: {} bytes exceed the fast-packet capacity.",
+                message.pgn,
+                message.data.len()
+            );
+            return;
+        }
+
+        let sequence = self.sequences.entry(message.pgn).or_default();
+        let counter = *sequence << 5;
+        *sequence = (*sequence + 1) & 0x7;
+
+        let mut first = vec![counter, message.data.len() as u8];
+        let split = message.data.len().min(6);
+        first.extend_from_slice(&message.data[..split]);
+        let mut frames = vec![first];
+        frames.extend(
+            message.data[split..]
+                .chunks(7)
+                .enumerate()
+                .map(|(i, chunk)| {
+                    let mut frame = vec![counter | (i as u8 + 1)];
+                    frame.extend_from_slice(chunk);
+                    frame
+                }),
+        );
+        for mut data in frames {
+            data.resize(8, 0xFF);
+            let frame = J1939Message {
+                data,
+                ..message.clone()
+            };
+            self.j1939_out.send(frame).await;
+        }
+    }
+
+    /// Adds a fast-packet frame to its reception session and returns the
+    /// complete message, if any.
+    fn reassemble(&mut self, frame: J1939Message) -> Option<J1939Message> {
+        let key = (frame.source, frame.pgn);
+        let (&header, payload) = frame.data.split_first()?;
+        let sequence = header >> 5;
+        let counter = header & 0x1F;
+
+        if counter == 0 {
+            let (&size, payload) = payload.split_first()?;
+            let size = usize::from(size);
+            let mut data = payload.to_vec();
+            if data.len() >= size {
+                data.truncate(size);
+                self.sessions.remove(&key);
+                return Some(J1939Message { data, ..frame });
+            }
+            self.sessions.insert(
+                key,
+                FastPacketSession {
+                    sequence,
+                    size,
+                    next: 1,
+                    data,
+                },
+            );
+            return None;
+        }
+
+        let session = self.sessions.get_mut(&key)?;
+        if session.sequence != sequence || session.next != counter {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Discarding NMEA 2000 fast-packet message PGN {} from {}: unexpected frame.",
+                frame.pgn, frame.source
+            );
+            self.sessions.remove(&key);
+            return None;
+        }
+        session.data.extend_from_slice(payload);
+        session.next += 1;
+        if session.data.len() < session.size {
+            return None;
+        }
+        let mut session = self.sessions.remove(&key)?;
+        session.data.truncate(session.size);
+
+        Some(J1939Message {
+            data: session.data,
+            ..frame
+        })
+    }
+
+    /// Forwards a received message and its decoded content.
+    async fn route(&mut self, message: J1939Message) {
+        let source = message.source;
+        let data = &message.data;
+        match message.pgn {
+            PGN_VESSEL_HEADING if data.len() >= 8 => {
+                let heading = VesselHeading {
+                    source,
+                    sid: data[0],
+                    heading: angle_u16(&data[1..3]),
+                    deviation: angle_i16(&data[3..5]),
+                    variation: angle_i16(&data[5..7]),
+                    reference: data[7] & 0x3,
+                };
+                self.heading_out.send(heading).await;
+            }
+            PGN_SPEED if data.len() >= 5 => {
+                let speed = Speed {
+                    source,
+                    sid: data[0],
+                    water_speed: speed_u16(&data[1..3]),
+                    ground_speed: speed_u16(&data[3..5]),
+                };
+                self.speed_out.send(speed).await;
+            }
+            PGN_POSITION_RAPID_UPDATE if data.len() >= 8 => {
+                let position = PositionRapidUpdate {
+                    source,
+                    latitude: coordinate(&data[0..4]),
+                    longitude: coordinate(&data[4..8]),
+                };
+                self.position_out.send(position).await;
+            }
+            PGN_COG_SOG_RAPID_UPDATE if data.len() >= 6 => {
+                let cog_sog = CogSogRapidUpdate {
+                    source,
+                    sid: data[0],
+                    reference: data[1] & 0x3,
+                    cog: angle_u16(&data[2..4]),
+                    sog: speed_u16(&data[4..6]),
+                };
+                self.cog_sog_out.send(cog_sog).await;
+            }
+            PGN_WIND_DATA if data.len() >= 6 => {
+                let wind = WindData {
+                    source,
+                    sid: data[0],
+                    speed: speed_u16(&data[1..3]),
+                    angle: angle_u16(&data[3..5]),
+                    reference: data[5] & 0x7,
+                };
+                self.wind_out.send(wind).await;
+            }
+            _ => {}
+        }
+        self.message_out.send(message).await;
+    }
+}
+
+impl Default for Nmea2000Layer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Model for Nmea2000Layer {}
+
+impl fmt::Debug for Nmea2000Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Nmea2000Layer")
+            .field("sessions", &self.sessions.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Decodes an unsigned angle with a resolution of 1e-4 rad.
+fn angle_u16(bytes: &[u8]) -> Option<f64> {
+    let raw = u16::from_le_bytes([bytes[0], bytes[1]]);
+
+    (raw < 0xFFFD).then(|| f64::from(raw) * 1e-4)
+}
+
+/// Decodes a signed angle with a resolution of 1e-4 rad.
+fn angle_i16(bytes: &[u8]) -> Option<f64> {
+    let raw = i16::from_le_bytes([bytes[0], bytes[1]]);
+
+    (raw < 0x7FFD).then(|| f64::from(raw) * 1e-4)
+}
+
+/// Decodes a speed with a resolution of 0.01 m/s.
+fn speed_u16(bytes: &[u8]) -> Option<f64> {
+    let raw = u16::from_le_bytes([bytes[0], bytes[1]]);
+
+    (raw < 0xFFFD).then(|| f64::from(raw) * 0.01)
+}
+
+/// Decodes a latitude or longitude with a resolution of 1e-7 degree.
+fn coordinate(bytes: &[u8]) -> Option<f64> {
+    let raw = i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+    (raw < 0x7FFF_FFFD).then(|| f64::from(raw) * 1e-7)
+}
@@ -0,0 +1,347 @@
+//! CAN traffic shaping.
+//!
+//! This module contains a model limiting the rate of the frames transmitted
+//! on CAN interfaces, so that the simulation cannot flood a shared physical
+//! bus, e.g. during hardware-in-the-loop runs. The traffic of each limited
+//! interface is shaped by token buckets limiting:
+//! * the frame rate, in frames per second,
+//! * the bus load, as a percentage of the bitrate.
+//!
+//! Frames exceeding the limits are delayed in a bounded queue and frames
+//! overflowing the queue are dropped. Limits are applied in simulation time.
+//!
+//! The shaper should be connected between the models transmitting frames and
+//! the frame input of the CAN port. Since limits are configured by interface
+//! name, frames should be addressed by name; frames addressed to interfaces
+//! without limits are forwarded immediately.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_can_port::shaper::CanShaperConfig;
+//! use schematic::{ConfigLoader, Format};
+//!
+//! let config = ConfigLoader::<CanShaperConfig>::new()
+//!     .code(
+//!         r#"
+//! [[limits]]
+//! interface = "can0"
+//! frameRate = 1000.0
+//! busLoad = 30.0
+//! bitrate = 250000
+//! burst = 4
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//! ```
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use schematic::Config;
+
+use socketcan::{CanFrame, EmbeddedFrame};
+
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use nexosim::model::{Context, Model};
+use nexosim::ports::Output;
+use nexosim::time::MonotonicTime;
+
+use crate::{CanData, CanInterface};
+
+/// Tolerance on the bucket level, absorbing rounding errors.
+const LEVEL_TOLERANCE: f64 = 1e-9;
+
+/// CAN traffic shaper model instance config.
+#[derive(Config, Debug)]
+pub struct CanShaperConfig {
+    /// Rate limits by interface.
+    #[setting(nested)]
+    pub limits: Vec<CanRateLimitConfig>,
+}
+
+/// Rate limit configuration of an interface.
+#[derive(Config, Debug)]
+pub struct CanRateLimitConfig {
+    /// CAN interface name.
+    pub interface: String,
+
+    /// Maximum frame rate, in frames per second.
+    ///
+    /// If no value is provided, the frame rate is not limited.
+    pub frame_rate: Option<f64>,
+
+    /// Maximum bus load, in percent of the bitrate.
+    ///
+    /// The load of a frame is estimated from its nominal length without stuff
+    /// bits, including the interframe space. If no value is provided, the bus
+    /// load is not limited.
+    pub bus_load: Option<f64>,
+
+    /// Bitrate of the bus, in bit/s.
+    #[setting(default = 500000)]
+    pub bitrate: u32,
+
+    /// Number of frames which can be sent back-to-back before the limits
+    /// apply.
+    #[setting(default = 1)]
+    pub burst: u32,
+
+    /// Maximum number of delayed frames.
+    #[setting(default = 64)]
+    pub queue_size: usize,
+}
+
+/// Token bucket.
+struct TokenBucket {
+    /// Refill rate, in tokens per second.
+    rate: f64,
+
+    /// Bucket capacity.
+    capacity: f64,
+
+    /// Current level.
+    level: f64,
+
+    /// Time of the last refill.
+    last_refill: Option<MonotonicTime>,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket.
+    fn new(rate: f64, capacity: f64) -> Self {
+        Self {
+            rate,
+            capacity,
+            level: capacity,
+            last_refill: None,
+        }
+    }
+
+    /// Refills the bucket up to the current time.
+    fn refill(&mut self, now: MonotonicTime) {
+        if let Some(last_refill) = self.last_refill {
+            let elapsed = now.duration_since(last_refill).as_secs_f64();
+            self.level = (self.level + elapsed * self.rate).min(self.capacity);
+        }
+        self.last_refill = Some(now);
+    }
+
+    /// Returns the time to wait until the bucket holds the requested tokens.
+    fn wait_time(&self, tokens: f64) -> Duration {
+        if self.level + LEVEL_TOLERANCE >= tokens {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((tokens - self.level) / self.rate)
+        }
+    }
+
+    /// Removes tokens from the bucket.
+    fn consume(&mut self, tokens: f64) {
+        self.level = (self.level - tokens).max(0.0);
+    }
+}
+
+/// Rate limiter of an interface.
+struct RateLimiter {
+    /// Limited interface.
+    interface: CanInterface,
+
+    /// Frame rate bucket, counting frames.
+    frames: Option<TokenBucket>,
+
+    /// Bus load bucket, counting bits.
+    bits: Option<TokenBucket>,
+
+    /// Delayed frames.
+    queue: VecDeque<CanData>,
+
+    /// Maximum number of delayed frames.
+    queue_size: usize,
+
+    /// A release of the delayed frames is scheduled.
+    release_scheduled: bool,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter from its configuration.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if a limit or the bitrate is not positive.
+    fn new(config: &CanRateLimitConfig) -> Self {
+        let burst = f64::from(config.burst.max(1));
+        let frames = config.frame_rate.map(|frame_rate| {
+            assert!(frame_rate > 0.0, "the frame rate limit should be positive");
+            TokenBucket::new(frame_rate, burst)
+        });
+        let bits = config.bus_load.map(|bus_load| {
+            assert!(
+                bus_load > 0.0 && config.bitrate > 0,
+                "the bus load limit and the bitrate should be positive"
+            );
+            TokenBucket::new(
+                f64::from(config.bitrate) * bus_load.min(100.0) / 100.0,
+                burst * f64::from(MAX_FRAME_BITS),
+            )
+        });
+
+        Self {
+            interface: CanInterface::named(&config.interface),
+            frames,
+            bits,
+            queue: VecDeque::new(),
+            queue_size: config.queue_size,
+            release_scheduled: false,
+        }
+    }
+
+    /// Returns the time to wait until the frame can be sent.
+    fn wait_time(&mut self, frame: &CanFrame, now: MonotonicTime) -> Duration {
+        let mut wait_time = Duration::ZERO;
+        if let Some(bucket) = &mut self.frames {
+            bucket.refill(now);
+            wait_time = wait_time.max(bucket.wait_time(1.0));
+        }
+        if let Some(bucket) = &mut self.bits {
+            bucket.refill(now);
+            wait_time = wait_time.max(bucket.wait_time(f64::from(frame_bits(frame))));
+        }
+
+        wait_time
+    }
+
+    /// Removes the tokens of a sent frame.
+    fn consume(&mut self, frame: &CanFrame) {
+        if let Some(bucket) = &mut self.frames {
+            bucket.consume(1.0);
+        }
+        if let Some(bucket) = &mut self.bits {
+            bucket.consume(f64::from(frame_bits(frame)));
+        }
+    }
+}
+
+/// Nominal length of the longest classical CAN frame, in bits.
+const MAX_FRAME_BITS: u32 = 67 + 64;
+
+/// Returns the nominal length of a frame without stuff bits, including the
+/// interframe space, in bits.
+fn frame_bits(frame: &CanFrame) -> u32 {
+    let overhead = if frame.is_extended() { 67 } else { 47 };
+    let data_bits = if frame.is_remote_frame() {
+        0
+    } else {
+        8 * frame.data().len() as u32
+    };
+
+    overhead + data_bits
+}
+
+/// CAN traffic shaper model.
+pub struct CanShaper {
+    /// Shaped frames -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// Frames dropped because the queue of their interface was full -- output
+    /// port.
+    pub dropped_out: Output<CanData>,
+
+    /// Rate limiters.
+    limiters: Vec<RateLimiter>,
+}
+
+impl CanShaper {
+    /// Creates a new traffic shaper.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if a limit or a bitrate is not positive.
+    pub fn new(config: CanShaperConfig) -> Self {
+        Self {
+            frame_out: Output::new(),
+            dropped_out: Output::new(),
+            limiters: config.limits.iter().map(RateLimiter::new).collect(),
+        }
+    }
+
+    /// Frame to transmit -- input port.
+    pub async fn frame_in(&mut self, data: CanData, cx: &mut Context<Self>) {
+        let Some(index) = self
+            .limiters
+            .iter()
+            .position(|limiter| limiter.interface == data.interface)
+        else {
+            self.frame_out.send(data).await;
+            return;
+        };
+        let limiter = &mut self.limiters[index];
+        if limiter.queue.is_empty() && limiter.wait_time(&data.frame, cx.time()).is_zero() {
+            limiter.consume(&data.frame);
+            self.frame_out.send(data).await;
+            return;
+        }
+        if limiter.queue.len() >= limiter.queue_size {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Dropping CAN frame to the rate-limited CAN interface {}: {:?}.",
+                data.interface, data.frame
+            );
+            self.dropped_out.send(data).await;
+            return;
+        }
+        limiter.queue.push_back(data);
+        self.schedule_release(index, cx);
+    }
+
+    /// Sends the delayed frames of an interface which are due.
+    async fn release(&mut self, index: usize, cx: &mut Context<Self>) {
+        self.limiters[index].release_scheduled = false;
+        loop {
+            let limiter = &mut self.limiters[index];
+            let Some(data) = limiter.queue.front().cloned() else {
+                return;
+            };
+            if !limiter.wait_time(&data.frame, cx.time()).is_zero() {
+                break;
+            }
+            limiter.consume(&data.frame);
+            limiter.queue.pop_front();
+            self.frame_out.send(data).await;
+        }
+        self.schedule_release(index, cx);
+    }
+
+    /// Schedules the release of the first delayed frame of an interface.
+    fn schedule_release(&mut self, index: usize, cx: &mut Context<Self>) {
+        let limiter = &mut self.limiters[index];
+        if limiter.release_scheduled {
+            return;
+        }
+        let Some(data) = limiter.queue.front().cloned() else {
+            return;
+        };
+        // Deadlines should be strictly in the future.
+        let wait_time = limiter
+            .wait_time(&data.frame, cx.time())
+            .max(Duration::from_nanos(1));
+        limiter.release_scheduled = true;
+        cx.schedule_event(wait_time, Self::release, index).unwrap();
+    }
+}
+
+impl Model for CanShaper {}
+
+impl fmt::Debug for CanShaper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CanShaper")
+            .field("limits", &self.limiters.len())
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,236 @@
+//! SLCAN backend.
+//!
+//! This module contains a [`CanBackend`] implementation speaking the SLCAN
+//! (LAWICEL) ASCII protocol over a serial device, as exposed by many USB CAN
+//! adapters.
+//!
+//! The backend is selected by setting the `backend` configuration of the CAN
+//! port to `slcan`, in which case the configured interfaces are the paths of
+//! the serial devices. Filters, error frames, timestamps and loopback settings
+//! are not supported by SLCAN adapters and are ignored.
+use std::fmt;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use mio::event::Source;
+use mio::{Interest, Registry, Token};
+use mio_serial::{SerialPortBuilderExt, SerialStream};
+
+use socketcan::{CanFrame, CanTimestamps, EmbeddedFrame, ExtendedId, Frame, Id, StandardId};
+
+use crate::CanBackend;
+
+/// Maximum length of an SLCAN line, in bytes.
+///
+/// Received data not terminated within this length is discarded.
+const MAX_LINE_LEN: usize = 64;
+
+/// SLCAN line terminator.
+const CR: u8 = b'\r';
+
+/// SLCAN error reply.
+const BELL: u8 = 0x07;
+
+/// CAN interface accessed through an SLCAN adapter.
+pub struct SlcanBackend {
+    /// Serial device.
+    port: SerialStream,
+
+    /// Received data not yet decoded.
+    rx_buf: Vec<u8>,
+
+    /// Data not yet written to the serial device.
+    tx_buf: Vec<u8>,
+}
+
+impl SlcanBackend {
+    /// Opens the serial device with the provided baud rate, sets the CAN
+    /// bitrate and opens the CAN channel.
+    ///
+    /// Supported CAN bitrates are 10, 20, 50, 100, 125, 250, 500 and
+    /// 800 kbit/s, and 1 Mbit/s.
+    pub fn open(path: &str, baud_rate: u32, bitrate: u32) -> Result<Self> {
+        let code = bitrate_code(bitrate).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported SLCAN bitrate: {bitrate}."),
+            )
+        })?;
+        let port = mio_serial::new(path, baud_rate).open_native_async()?;
+
+        let mut backend = Self {
+            port,
+            rx_buf: Vec::new(),
+            tx_buf: Vec::new(),
+        };
+        // Close the channel in case it was left open, then configure and
+        // open it.
+        backend.tx_buf.extend_from_slice(b"C\r");
+        backend.tx_buf.extend_from_slice(&[b'S', b'0' + code, CR]);
+        backend.tx_buf.extend_from_slice(b"O\r");
+        backend.flush()?;
+
+        Ok(backend)
+    }
+
+    /// Writes as much pending data as possible to the serial device.
+    fn flush(&mut self) -> Result<()> {
+        while !self.tx_buf.is_empty() {
+            match self.port.write(&self.tx_buf) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(len) => {
+                    self.tx_buf.drain(..len);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the next frame from the received data, skipping replies and
+    /// invalid lines.
+    fn next_frame(&mut self) -> Option<CanFrame> {
+        while let Some(end) = self
+            .rx_buf
+            .iter()
+            .position(|&byte| byte == CR || byte == BELL)
+        {
+            let line: Vec<u8> = self.rx_buf.drain(..=end).collect();
+            if let Some(frame) = decode_frame(&line[..end]) {
+                return Some(frame);
+            }
+        }
+        if self.rx_buf.len() > MAX_LINE_LEN {
+            self.rx_buf.clear();
+        }
+
+        None
+    }
+}
+
+impl CanBackend for SlcanBackend {
+    fn interest(&self) -> Interest {
+        Interest::READABLE | Interest::WRITABLE
+    }
+
+    fn read_frame(&mut self) -> Result<(CanFrame, CanTimestamps)> {
+        self.flush()?;
+        let mut buf = [0; 256];
+        loop {
+            if let Some(frame) = self.next_frame() {
+                return Ok((frame, CanTimestamps::default()));
+            }
+            match self.port.read(&mut buf)? {
+                0 => return Err(ErrorKind::WouldBlock.into()),
+                len => self.rx_buf.extend_from_slice(&buf[..len]),
+            }
+        }
+    }
+
+    /// Queues a frame for transmission.
+    ///
+    /// Error frames are ignored.
+    fn write_frame(&mut self, frame: &CanFrame) -> Result<()> {
+        encode_frame(frame, &mut self.tx_buf);
+        self.flush()
+    }
+}
+
+impl Source for SlcanBackend {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        self.port.register(registry, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        self.port.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> Result<()> {
+        self.port.deregister(registry)
+    }
+}
+
+impl Drop for SlcanBackend {
+    fn drop(&mut self) {
+        // Best effort: the adapter may not accept the command immediately.
+        let _ = self.port.write(b"C\r");
+    }
+}
+
+impl fmt::Debug for SlcanBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SlcanBackend").finish_non_exhaustive()
+    }
+}
+
+/// Returns the SLCAN setup code of a CAN bitrate.
+pub(crate) fn bitrate_code(bitrate: u32) -> Option<u8> {
+    match bitrate {
+        10_000 => Some(0),
+        20_000 => Some(1),
+        50_000 => Some(2),
+        100_000 => Some(3),
+        125_000 => Some(4),
+        250_000 => Some(5),
+        500_000 => Some(6),
+        800_000 => Some(7),
+        1_000_000 => Some(8),
+        _ => None,
+    }
+}
+
+/// Decodes a frame line without its terminator.
+///
+/// Trailing data such as adapter timestamps is ignored.
+fn decode_frame(line: &[u8]) -> Option<CanFrame> {
+    let (&kind, rest) = line.split_first()?;
+    let (id_len, extended, remote) = match kind {
+        b't' => (3, false, false),
+        b'T' => (8, true, false),
+        b'r' => (3, false, true),
+        b'R' => (8, true, true),
+        _ => return None,
+    };
+    let text = std::str::from_utf8(rest).ok()?;
+    let raw_id = u32::from_str_radix(text.get(..id_len)?, 16).ok()?;
+    let dlc: usize = text.get(id_len..id_len + 1)?.parse().ok()?;
+    let id: Id = if extended {
+        ExtendedId::new(raw_id)?.into()
+    } else {
+        StandardId::new(raw_id as u16)?.into()
+    };
+    if remote {
+        return CanFrame::new_remote(id, dlc);
+    }
+    let hex = text.get(id_len + 1..id_len + 1 + 2 * dlc)?;
+    let data = (0..dlc)
+        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    CanFrame::new(id, &data)
+}
+
+/// Encodes a frame line, including its terminator.
+fn encode_frame(frame: &CanFrame, buf: &mut Vec<u8>) {
+    let line = match frame {
+        CanFrame::Error(_) => return,
+        CanFrame::Remote(_) if frame.is_extended() => {
+            format!("R{:08X}{}", frame.raw_id(), frame.dlc())
+        }
+        CanFrame::Remote(_) => format!("r{:03X}{}", frame.raw_id(), frame.dlc()),
+        CanFrame::Data(_) => {
+            let mut line = if frame.is_extended() {
+                format!("T{:08X}{}", frame.raw_id(), frame.dlc())
+            } else {
+                format!("t{:03X}{}", frame.raw_id(), frame.dlc())
+            };
+            for byte in frame.data() {
+                line.push_str(&format!("{byte:02X}"));
+            }
+            line
+        }
+    };
+    buf.extend_from_slice(line.as_bytes());
+    buf.push(CR);
+}
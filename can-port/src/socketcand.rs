@@ -0,0 +1,294 @@
+//! socketcand backend.
+//!
+//! This module contains a [`CanBackend`] implementation connecting to a
+//! [socketcand](https://github.com/linux-can/socketcand) server, which exports
+//! the CAN interfaces of a remote machine over TCP with an ASCII protocol.
+//!
+//! The backend is selected by setting the `backend` configuration of the CAN
+//! port to `socketcand`, in which case the configured interfaces have the form
+//! `HOST[:PORT]/BUS`, e.g. `10.0.0.2:29536/can0`. If no port is provided, the
+//! default socketcand port 29536 is used.
+//!
+//! The server connection is switched to raw mode by default, so that all
+//! frames of the bus are received. In broadcast manager mode, only the frames
+//! with a subscribed identifier are received; frames are sent in both modes.
+//! Receive timestamps are provided by the server in raw mode and are reported
+//! as software timestamps, in the wall clock of the server. Filters and
+//! loopback settings are not supported and are ignored. If the server closes
+//! the connection, the interface stays silent until attached again.
+use std::fmt;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Write};
+use std::net::{TcpStream as StdTcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime};
+
+use mio::event::Source;
+use mio::net::TcpStream;
+use mio::{Interest, Registry, Token};
+
+use socketcan::{CanFrame, CanTimestamps, EmbeddedFrame, ExtendedId, Frame, Id, StandardId};
+
+use crate::{CanBackend, SocketcandMode};
+
+/// Default TCP port of socketcand.
+const DEFAULT_PORT: u16 = 29536;
+
+/// Timeout of the connection handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum length of a socketcand message, in bytes.
+///
+/// Received data not terminated within this length is discarded.
+const MAX_MESSAGE_LEN: usize = 256;
+
+/// CAN bus accessed through a socketcand server.
+pub struct SocketcandBackend {
+    /// Server connection.
+    stream: TcpStream,
+
+    /// Received data not yet decoded.
+    rx_buf: Vec<u8>,
+
+    /// Data not yet written to the server.
+    tx_buf: Vec<u8>,
+}
+
+impl SocketcandBackend {
+    /// Connects to a socketcand server, opens the bus and switches to the
+    /// requested mode.
+    ///
+    /// The address has the form `HOST[:PORT]/BUS`, see the module
+    /// documentation. In broadcast manager mode, the provided identifiers are
+    /// subscribed to.
+    pub fn open(address: &str, mode: SocketcandMode, subscriptions: &[u32]) -> Result<Self> {
+        let (host, bus) = address.rsplit_once('/').ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid socketcand address {address}, expected HOST[:PORT]/BUS."),
+            )
+        })?;
+        let server = match host.to_socket_addrs() {
+            Ok(mut addrs) => addrs.next(),
+            Err(_) => (host, DEFAULT_PORT).to_socket_addrs()?.next(),
+        }
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Cannot resolve address {host}."),
+            )
+        })?;
+
+        // The handshake is performed in blocking mode.
+        let stream = StdTcpStream::connect_timeout(&server, HANDSHAKE_TIMEOUT)?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream.try_clone()?;
+        expect(&mut reader, "hi")?;
+        writer.write_all(format!("< open {bus} >").as_bytes())?;
+        expect(&mut reader, "ok")?;
+        match mode {
+            SocketcandMode::Raw => {
+                writer.write_all(b"< rawmode >")?;
+                expect(&mut reader, "ok")?;
+            }
+            SocketcandMode::Bcm => {
+                // The server does not acknowledge subscriptions.
+                for &id in subscriptions {
+                    writer.write_all(format!("< subscribe 0 0 {} >", format_id(id)).as_bytes())?;
+                }
+            }
+        }
+        // Data already buffered by the handshake reader is kept.
+        let rx_buf = reader.buffer().to_vec();
+        stream.set_read_timeout(None)?;
+        stream.set_nonblocking(true)?;
+
+        Ok(Self {
+            stream: TcpStream::from_std(stream),
+            rx_buf,
+            tx_buf: Vec::new(),
+        })
+    }
+
+    /// Writes as much pending data as possible to the server.
+    fn flush(&mut self) -> Result<()> {
+        while !self.tx_buf.is_empty() {
+            match self.stream.write(&self.tx_buf) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(len) => {
+                    self.tx_buf.drain(..len);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the next frame from the received data, skipping other
+    /// messages.
+    fn next_frame(&mut self) -> Option<(CanFrame, CanTimestamps)> {
+        while let Some(end) = self.rx_buf.iter().position(|&byte| byte == b'>') {
+            let message: Vec<u8> = self.rx_buf.drain(..=end).collect();
+            let Ok(message) = std::str::from_utf8(&message) else {
+                continue;
+            };
+            let Some(start) = message.find('<') else {
+                continue;
+            };
+            if let Some(frame) = decode_frame(&message[start + 1..end]) {
+                return Some(frame);
+            }
+        }
+        if self.rx_buf.len() > MAX_MESSAGE_LEN {
+            self.rx_buf.clear();
+        }
+
+        None
+    }
+}
+
+impl CanBackend for SocketcandBackend {
+    fn interest(&self) -> Interest {
+        Interest::READABLE | Interest::WRITABLE
+    }
+
+    fn read_frame(&mut self) -> Result<(CanFrame, CanTimestamps)> {
+        self.flush()?;
+        let mut buf = [0; 1024];
+        loop {
+            if let Some(frame) = self.next_frame() {
+                return Ok(frame);
+            }
+            match self.stream.read(&mut buf)? {
+                // The connection is closed.
+                0 => return Err(ErrorKind::WouldBlock.into()),
+                len => self.rx_buf.extend_from_slice(&buf[..len]),
+            }
+        }
+    }
+
+    /// Queues a frame for transmission.
+    ///
+    /// Error frames are ignored.
+    fn write_frame(&mut self, frame: &CanFrame) -> Result<()> {
+        encode_frame(frame, &mut self.tx_buf);
+        self.flush()
+    }
+}
+
+impl Source for SocketcandBackend {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        self.stream.register(registry, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        self.stream.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> Result<()> {
+        self.stream.deregister(registry)
+    }
+}
+
+impl fmt::Debug for SocketcandBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SocketcandBackend")
+            .field("peer_addr", &self.stream.peer_addr().ok())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Reads a message during the handshake and checks its content.
+fn expect(reader: &mut impl BufRead, expected: &str) -> Result<()> {
+    let mut message = Vec::new();
+    reader.read_until(b'>', &mut message)?;
+    let message = String::from_utf8_lossy(&message);
+    let content = message
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim();
+    if content == expected {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Unexpected socketcand reply: {content}."),
+        ))
+    }
+}
+
+/// Formats an identifier, with 3 hexadecimal digits for standard identifiers
+/// and 8 digits for extended identifiers.
+fn format_id(id: u32) -> String {
+    if id > 0x7FF {
+        format!("{id:08X}")
+    } else {
+        format!("{id:03X}")
+    }
+}
+
+/// Decodes a `frame` message content, e.g. `frame 123 23.424242 11223344`.
+fn decode_frame(content: &str) -> Option<(CanFrame, CanTimestamps)> {
+    let mut fields = content.split_whitespace();
+    if fields.next()? != "frame" {
+        return None;
+    }
+    let id_text = fields.next()?;
+    let raw_id = u32::from_str_radix(id_text, 16).ok()?;
+    let timestamp = fields.next()?;
+    let data_text = fields.next().unwrap_or("");
+    if data_text.len() % 2 != 0 {
+        return None;
+    }
+    let data = (0..data_text.len() / 2)
+        .map(|i| u8::from_str_radix(data_text.get(2 * i..2 * i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    // Identifiers with more than 3 digits are extended.
+    let id: Id = if id_text.len() > 3 {
+        ExtendedId::new(raw_id)?.into()
+    } else {
+        StandardId::new(raw_id as u16)?.into()
+    };
+    let frame = CanFrame::new(id, &data)?;
+
+    let timestamps = CanTimestamps {
+        sw: parse_timestamp(timestamp),
+        ..Default::default()
+    };
+
+    Some((frame, timestamps))
+}
+
+/// Parses a `seconds.microseconds` wall-clock timestamp.
+fn parse_timestamp(timestamp: &str) -> Option<SystemTime> {
+    let (secs, usecs) = timestamp.split_once('.')?;
+    let secs: u64 = secs.parse().ok()?;
+    let usecs: u32 = usecs.parse().ok()?;
+
+    SystemTime::UNIX_EPOCH.checked_add(Duration::new(secs, usecs.checked_mul(1000)?))
+}
+
+/// Encodes a `send` message, e.g. `< send 123 2 11 22 >`.
+fn encode_frame(frame: &CanFrame, buf: &mut Vec<u8>) {
+    if !matches!(frame, CanFrame::Data(_)) {
+        return;
+    }
+    let mut message = format!("< send {} {}", format_id_of(frame), frame.dlc());
+    for byte in frame.data() {
+        message.push_str(&format!(" {byte:02X}"));
+    }
+    message.push_str(" >");
+    buf.extend_from_slice(message.as_bytes());
+}
+
+/// Formats the identifier of a frame according to its format.
+fn format_id_of(frame: &CanFrame) -> String {
+    if frame.is_extended() {
+        format!("{:08X}", frame.raw_id())
+    } else {
+        format!("{:03X}", frame.raw_id())
+    }
+}
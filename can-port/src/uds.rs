@@ -0,0 +1,1371 @@
+//! UDS client and server.
+//!
+//! This module contains models implementing Unified Diagnostic Services
+//! (ISO 14229) on top of the raw CAN frames of a [`CanPort`](crate::CanPort):
+//! * a [`UdsClient`], acting as a tester which issues diagnostic requests
+//!   received from the simulation and reports the correlated responses,
+//! * a [`UdsServer`], emulating the diagnostic interface of an ECU.
+//!
+//! Diagnostic messages are exchanged with the ISO-TP transport protocol
+//! (ISO 15765-2) with normal addressing on classic CAN frames: messages of up
+//! to 4095 bytes are segmented into a first frame and consecutive frames,
+//! paced by the flow control of the receiver.
+//!
+//! The client processes its requests one at a time, in order. A request is
+//! answered either by the positive or negative response of the server, or by a
+//! timeout if the server does not answer within the P2 timeout, or within the
+//! extended P2 timeout after a "response pending" negative response. Requests
+//! with the suppress positive response bit set complete successfully with an
+//! empty response if no negative response is received within the P2 timeout.
+//!
+//! The server handles the diagnostic session control, ECU reset, read and
+//! write data by identifier and tester present services, with the data
+//! identifiers defined by the configuration. Other services, such as routine
+//! control, can be forwarded to the simulation, which should then answer each
+//! forwarded request before the next one is received. The server falls back to
+//! the default session when no request is received within the S3 timeout.
+//!
+//! Both models handle a single CAN interface and should be connected to the
+//! frame output and input of the CAN port. Since the models address their
+//! interface by name, the CAN port should be configured with
+//! `interface_names` enabled.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_can_port::uds::{UdsRequest, UdsServer, UdsServerConfig};
+//!
+//! let config = ConfigLoader::<UdsServerConfig>::new()
+//!     .code(
+//!         r#"
+//! interface = "can0"
+//! requestId = 0x7E0
+//! responseId = 0x7E8
+//! functionalId = 0x7DF
+//! forwardedServices = [0x31]
+//!
+//! [[dids]]
+//! id = 0xF190
+//! data = [0x57, 0x30, 0x4C, 0x30, 0x30, 0x30, 0x30, 0x34, 0x33]
+//!
+//! [[dids]]
+//! id = 0x0100
+//! data = [0, 0]
+//! writable = true
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! let server = UdsServer::new(config);
+//!
+//! // Connect `server.frame_out` to `CanPort::frame_in` and
+//! // `CanPort::frame_out` to `UdsServer::frame_in`. A `UdsClient` sends
+//! // requests such as:
+//! let request = UdsRequest::read_data(0xF190);
+//! ```
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+use schematic::Config;
+
+use socketcan::{CanFrame, EmbeddedFrame, ExtendedId, Id, StandardId};
+
+#[cfg(feature = "tracing")]
+use tracing::{info, warn};
+
+use nexosim::model::{Context, InitializedModel, Model};
+use nexosim::ports::Output;
+use nexosim::simulation::ActionKey;
+use nexosim::time::MonotonicTime;
+
+use nexosim_io_utils::duration::ConfigDuration;
+
+use crate::{CanData, CanInterface};
+
+/// Diagnostic session control service.
+pub const SID_SESSION_CONTROL: u8 = 0x10;
+
+/// ECU reset service.
+pub const SID_ECU_RESET: u8 = 0x11;
+
+/// Read data by identifier service.
+pub const SID_READ_DATA: u8 = 0x22;
+
+/// Write data by identifier service.
+pub const SID_WRITE_DATA: u8 = 0x2E;
+
+/// Routine control service.
+pub const SID_ROUTINE_CONTROL: u8 = 0x31;
+
+/// Tester present service.
+pub const SID_TESTER_PRESENT: u8 = 0x3E;
+
+/// Default diagnostic session.
+pub const DEFAULT_SESSION: u8 = 0x01;
+
+/// Negative response code: service not supported.
+pub const NRC_SERVICE_NOT_SUPPORTED: u8 = 0x11;
+
+/// Negative response code: sub-function not supported.
+pub const NRC_SUB_FUNCTION_NOT_SUPPORTED: u8 = 0x12;
+
+/// Negative response code: incorrect message length or invalid format.
+pub const NRC_INCORRECT_LENGTH: u8 = 0x13;
+
+/// Negative response code: conditions not correct.
+pub const NRC_CONDITIONS_NOT_CORRECT: u8 = 0x22;
+
+/// Negative response code: request out of range.
+pub const NRC_REQUEST_OUT_OF_RANGE: u8 = 0x31;
+
+/// Negative response code: request correctly received, response pending.
+pub const NRC_RESPONSE_PENDING: u8 = 0x78;
+
+/// Negative response service identifier.
+const NEGATIVE_RESPONSE: u8 = 0x7F;
+
+/// Offset between the identifiers of a service and of its positive response.
+const POSITIVE_RESPONSE_OFFSET: u8 = 0x40;
+
+/// Suppress positive response bit of the sub-function byte.
+const SUPPRESS_POSITIVE_RESPONSE: u8 = 0x80;
+
+/// Maximum length of an ISO-TP message.
+pub const MAX_MESSAGE_LEN: usize = 4095;
+
+/// ISO-TP single frame.
+const SINGLE_FRAME: u8 = 0x0;
+
+/// ISO-TP first frame.
+const FIRST_FRAME: u8 = 0x1;
+
+/// ISO-TP consecutive frame.
+const CONSECUTIVE_FRAME: u8 = 0x2;
+
+/// ISO-TP flow control frame.
+const FLOW_CONTROL: u8 = 0x3;
+
+/// Flow control status: continue to send.
+const FLOW_CONTINUE: u8 = 0x0;
+
+/// Flow control status: wait.
+const FLOW_WAIT: u8 = 0x1;
+
+/// Flow control status: overflow.
+const FLOW_OVERFLOW: u8 = 0x2;
+
+/// UDS client model instance configuration.
+#[derive(Config, Debug)]
+pub struct UdsClientConfig {
+    /// CAN interface name.
+    #[setting(default = "vcan0")]
+    pub interface: String,
+
+    /// CAN identifier of the requests.
+    #[setting(default = 0x7E0)]
+    pub request_id: u32,
+
+    /// CAN identifier of the responses.
+    #[setting(default = 0x7E8)]
+    pub response_id: u32,
+
+    /// CAN identifiers are 29-bit extended identifiers.
+    pub extended_ids: bool,
+
+    /// Padding byte of the frames shorter than 8 bytes.
+    ///
+    /// If no value is provided, frames are not padded.
+    pub padding: Option<u8>,
+
+    /// Number of consecutive frames received between flow control frames, or
+    /// 0 for no further flow control.
+    pub block_size: u8,
+
+    /// Minimum time between received consecutive frames, up to 127 ms.
+    ///
+    /// Times below 1 ms are rounded up to a multiple of 100 µs, and longer
+    /// times to a whole number of milliseconds.
+    pub st_min: ConfigDuration,
+
+    /// Time to wait for a response.
+    #[setting(default = ConfigDuration::from_millis(150))]
+    pub p2_timeout: ConfigDuration,
+
+    /// Time to wait for a response after a "response pending" negative
+    /// response.
+    #[setting(default = ConfigDuration::from_millis(5000))]
+    pub p2_extended_timeout: ConfigDuration,
+
+    /// Period of the tester present requests sent while no request is in
+    /// progress.
+    ///
+    /// If no value is provided, tester present requests are not sent
+    /// automatically.
+    pub tester_present_period: Option<ConfigDuration>,
+}
+
+/// UDS server model instance configuration.
+#[derive(Config, Debug)]
+pub struct UdsServerConfig {
+    /// CAN interface name.
+    #[setting(default = "vcan0")]
+    pub interface: String,
+
+    /// CAN identifier of the physically addressed requests.
+    #[setting(default = 0x7E0)]
+    pub request_id: u32,
+
+    /// CAN identifier of the responses.
+    #[setting(default = 0x7E8)]
+    pub response_id: u32,
+
+    /// CAN identifier of the functionally addressed requests, which are
+    /// limited to single frames.
+    ///
+    /// If no value is provided, functionally addressed requests are ignored.
+    pub functional_id: Option<u32>,
+
+    /// CAN identifiers are 29-bit extended identifiers.
+    pub extended_ids: bool,
+
+    /// Padding byte of the frames shorter than 8 bytes.
+    ///
+    /// If no value is provided, frames are not padded.
+    pub padding: Option<u8>,
+
+    /// Number of consecutive frames received between flow control frames, or
+    /// 0 for no further flow control.
+    pub block_size: u8,
+
+    /// Minimum time between received consecutive frames, up to 127 ms.
+    ///
+    /// Times below 1 ms are rounded up to a multiple of 100 µs, and longer
+    /// times to a whole number of milliseconds.
+    pub st_min: ConfigDuration,
+
+    /// Supported diagnostic sessions.
+    ///
+    /// All sessions are supported if the list is empty.
+    pub sessions: Vec<u8>,
+
+    /// Time without request after which a non-default session falls back to
+    /// the default session.
+    #[setting(default = ConfigDuration::from_millis(5000))]
+    pub s3_timeout: ConfigDuration,
+
+    /// P2 timeout advertised in the session control responses, with a
+    /// resolution of 1 ms.
+    #[setting(default = ConfigDuration::from_millis(50))]
+    pub p2_timeout: ConfigDuration,
+
+    /// Extended P2 timeout advertised in the session control responses, with
+    /// a resolution of 10 ms.
+    #[setting(default = ConfigDuration::from_millis(5000))]
+    pub p2_extended_timeout: ConfigDuration,
+
+    /// Data identifiers.
+    #[setting(nested)]
+    pub dids: Vec<UdsDidConfig>,
+
+    /// Identifiers of the services forwarded to the simulation.
+    ///
+    /// Forwarded services take precedence over the services handled by the
+    /// server. Requests for other services are rejected.
+    pub forwarded_services: Vec<u8>,
+}
+
+/// Data identifier configuration.
+#[derive(Config, Debug)]
+pub struct UdsDidConfig {
+    /// Data identifier.
+    pub id: u16,
+
+    /// Initial value.
+    ///
+    /// Data identifiers with an empty initial value accept values of any
+    /// length, other data identifiers only accept values of the same length.
+    pub data: Vec<u8>,
+
+    /// Data identifier can be written by the write data by identifier
+    /// service.
+    pub writable: bool,
+}
+
+/// Diagnostic request.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct UdsRequest {
+    /// Service identifier.
+    pub service: u8,
+
+    /// Request parameters, including the sub-function byte, if any.
+    pub data: Vec<u8>,
+}
+
+impl UdsRequest {
+    /// Creates a new request.
+    pub fn new(service: u8, data: Vec<u8>) -> Self {
+        Self { service, data }
+    }
+
+    /// Creates a diagnostic session control request.
+    pub fn session_control(session: u8) -> Self {
+        Self::new(SID_SESSION_CONTROL, vec![session])
+    }
+
+    /// Creates an ECU reset request.
+    pub fn ecu_reset(reset_type: u8) -> Self {
+        Self::new(SID_ECU_RESET, vec![reset_type])
+    }
+
+    /// Creates a read data by identifier request.
+    pub fn read_data(did: u16) -> Self {
+        Self::new(SID_READ_DATA, did.to_be_bytes().to_vec())
+    }
+
+    /// Creates a write data by identifier request.
+    pub fn write_data(did: u16, data: &[u8]) -> Self {
+        let mut request = Self::new(SID_WRITE_DATA, did.to_be_bytes().to_vec());
+        request.data.extend_from_slice(data);
+
+        request
+    }
+
+    /// Creates a routine control request, e.g. with control type 1 to start
+    /// the routine.
+    pub fn routine_control(control_type: u8, routine: u16, options: &[u8]) -> Self {
+        let [r0, r1] = routine.to_be_bytes();
+        let mut request = Self::new(SID_ROUTINE_CONTROL, vec![control_type, r0, r1]);
+        request.data.extend_from_slice(options);
+
+        request
+    }
+
+    /// Creates a tester present request.
+    pub fn tester_present() -> Self {
+        Self::new(SID_TESTER_PRESENT, vec![0])
+    }
+
+    /// Sets the suppress positive response bit of the sub-function byte.
+    ///
+    /// This has no effect on services without sub-function.
+    pub fn with_suppressed_response(mut self) -> Self {
+        if has_sub_function(self.service) {
+            if let Some(sub_function) = self.data.first_mut() {
+                *sub_function |= SUPPRESS_POSITIVE_RESPONSE;
+            }
+        }
+        self
+    }
+
+    /// Returns `true` if the positive response is suppressed.
+    pub fn is_response_suppressed(&self) -> bool {
+        has_sub_function(self.service)
+            && self
+                .data
+                .first()
+                .is_some_and(|sub_function| sub_function & SUPPRESS_POSITIVE_RESPONSE != 0)
+    }
+
+    /// Encodes the request message.
+    fn encode(&self) -> Vec<u8> {
+        let mut message = Vec::with_capacity(1 + self.data.len());
+        message.push(self.service);
+        message.extend_from_slice(&self.data);
+
+        message
+    }
+}
+
+/// Returns `true` if the requests of a service start with a sub-function
+/// byte.
+fn has_sub_function(service: u8) -> bool {
+    matches!(
+        service,
+        0x10 | 0x11 | 0x19 | 0x27 | 0x28 | 0x29 | 0x31 | 0x3E | 0x83 | 0x84 | 0x85 | 0x86 | 0x87
+    )
+}
+
+/// Diagnostic response.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct UdsResponse {
+    /// Request.
+    pub request: UdsRequest,
+
+    /// Positive response parameters, without the response service identifier,
+    /// or error.
+    pub result: Result<Vec<u8>, UdsError>,
+}
+
+/// Diagnostic request error.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum UdsError {
+    /// Negative response, with its response code.
+    NegativeResponse(u8),
+
+    /// No response within the P2 timeout.
+    Timeout,
+
+    /// Transfer aborted by the receiver, e.g. because the request was too
+    /// long.
+    TransferAborted,
+}
+
+impl fmt::Display for UdsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NegativeResponse(code) => write!(f, "negative response 0x{code:02X}"),
+            Self::Timeout => f.write_str("response timeout"),
+            Self::TransferAborted => f.write_str("transfer aborted"),
+        }
+    }
+}
+
+/// Data identifier value.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct UdsDid {
+    /// Data identifier.
+    pub id: u16,
+
+    /// Value.
+    pub data: Vec<u8>,
+}
+
+/// Action resulting from a received ISO-TP frame.
+#[derive(Debug)]
+enum IsoTpAction {
+    /// Nothing to do.
+    None,
+
+    /// Flow control frame to be sent.
+    FlowControl(CanData),
+
+    /// Block of consecutive frames to be sent, with the minimum time between
+    /// frames, and whether it is the last block of the message.
+    Block(Vec<CanData>, Duration, bool),
+
+    /// Complete message received.
+    Received(Vec<u8>),
+
+    /// Transmission aborted by the receiver.
+    Aborted,
+}
+
+/// ISO-TP reception in progress.
+#[derive(Debug)]
+struct IsoTpRx {
+    /// Announced message length.
+    size: usize,
+
+    /// Received data.
+    data: Vec<u8>,
+
+    /// Next expected sequence number.
+    next: u8,
+
+    /// Consecutive frames expected before the next flow control.
+    remaining: u8,
+}
+
+/// ISO-TP transmission in progress.
+#[derive(Debug)]
+struct IsoTpTx {
+    /// Message being transmitted.
+    message: Vec<u8>,
+
+    /// Offset of the next consecutive frame.
+    offset: usize,
+
+    /// Next sequence number.
+    next: u8,
+}
+
+/// ISO-TP transport on a pair of CAN identifiers.
+#[derive(Debug)]
+struct IsoTp {
+    /// CAN interface.
+    interface: CanInterface,
+
+    /// Identifier of the transmitted frames.
+    tx_id: Id,
+
+    /// Identifier of the received frames.
+    rx_id: Id,
+
+    /// Padding byte of short frames.
+    padding: Option<u8>,
+
+    /// Block size advertised in flow control frames.
+    block_size: u8,
+
+    /// Minimum separation time advertised in flow control frames.
+    st_min: u8,
+
+    /// Reception in progress.
+    rx: Option<IsoTpRx>,
+
+    /// Transmission in progress.
+    tx: Option<IsoTpTx>,
+}
+
+impl IsoTp {
+    /// Creates a new transport.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if an identifier is out of range.
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        interface: &str,
+        tx_id: u32,
+        rx_id: u32,
+        extended_ids: bool,
+        padding: Option<u8>,
+        block_size: u8,
+        st_min: Duration,
+    ) -> Self {
+        Self {
+            interface: CanInterface::named(interface),
+            tx_id: can_id(tx_id, extended_ids),
+            rx_id: can_id(rx_id, extended_ids),
+            padding,
+            block_size,
+            st_min: separation_time_code(st_min),
+            rx: None,
+            tx: None,
+        }
+    }
+
+    /// Returns `true` if no transmission is in progress.
+    fn is_idle(&self) -> bool {
+        self.tx.is_none()
+    }
+
+    /// Cancels the transfers in progress.
+    fn reset(&mut self) {
+        self.rx = None;
+        self.tx = None;
+    }
+
+    /// Returns the payload of a data frame received with the identifier of the
+    /// transport.
+    fn payload(&self, data: &CanData) -> Option<Vec<u8>> {
+        if data.interface != self.interface || data.own {
+            return None;
+        }
+        match data.frame {
+            CanFrame::Data(frame) if frame.id() == self.rx_id => Some(frame.data().to_vec()),
+            _ => None,
+        }
+    }
+
+    /// Starts the transmission of a message, returning the first frame or the
+    /// single frame of the message.
+    ///
+    /// `None` is returned if the message is empty or too long.
+    fn start(&mut self, message: Vec<u8>) -> Option<CanData> {
+        if message.is_empty() || message.len() > MAX_MESSAGE_LEN {
+            return None;
+        }
+        if message.len() <= 7 {
+            self.tx = None;
+            let mut data = vec![(SINGLE_FRAME << 4) | message.len() as u8];
+            data.extend_from_slice(&message);
+            return Some(self.frame(data));
+        }
+        let len = message.len() as u16;
+        let mut data = vec![(FIRST_FRAME << 4) | (len >> 8) as u8, len as u8];
+        data.extend_from_slice(&message[..6]);
+        self.tx = Some(IsoTpTx {
+            message,
+            offset: 6,
+            next: 1,
+        });
+
+        Some(self.frame(data))
+    }
+
+    /// Handles a received frame payload.
+    fn on_frame(&mut self, payload: &[u8]) -> IsoTpAction {
+        let Some(&pci) = payload.first() else {
+            return IsoTpAction::None;
+        };
+        match pci >> 4 {
+            SINGLE_FRAME => {
+                let len = usize::from(pci & 0x0F);
+                if len == 0 || len >= payload.len() {
+                    return IsoTpAction::None;
+                }
+                self.rx = None;
+                IsoTpAction::Received(payload[1..1 + len].to_vec())
+            }
+            FIRST_FRAME if payload.len() == 8 => {
+                let size = usize::from(u16::from_be_bytes([pci & 0x0F, payload[1]]));
+                if size < 8 {
+                    return IsoTpAction::None;
+                }
+                let mut data = Vec::with_capacity(size);
+                data.extend_from_slice(&payload[2..]);
+                self.rx = Some(IsoTpRx {
+                    size,
+                    data,
+                    next: 1,
+                    remaining: self.block_size,
+                });
+                IsoTpAction::FlowControl(self.flow_control(FLOW_CONTINUE))
+            }
+            CONSECUTIVE_FRAME => {
+                let Some(rx) = self.rx.as_mut() else {
+                    return IsoTpAction::None;
+                };
+                if pci & 0x0F != rx.next {
+                    #[cfg(feature = "tracing")]
+                    warn!("Discarding ISO-TP message: unexpected sequence number.");
+                    self.rx = None;
+                    return IsoTpAction::None;
+                }
+                let len = (rx.size - rx.data.len()).min(7).min(payload.len() - 1);
+                rx.data.extend_from_slice(&payload[1..1 + len]);
+                rx.next = (rx.next + 1) & 0x0F;
+                if rx.data.len() >= rx.size {
+                    let rx = self.rx.take().unwrap();
+                    return IsoTpAction::Received(rx.data);
+                }
+                if self.block_size != 0 {
+                    rx.remaining -= 1;
+                    if rx.remaining == 0 {
+                        rx.remaining = self.block_size;
+                        return IsoTpAction::FlowControl(self.flow_control(FLOW_CONTINUE));
+                    }
+                }
+                IsoTpAction::None
+            }
+            FLOW_CONTROL if payload.len() >= 3 => {
+                if self.tx.is_none() {
+                    return IsoTpAction::None;
+                }
+                match pci & 0x0F {
+                    FLOW_CONTINUE => self.next_block(payload[1], separation_time(payload[2])),
+                    FLOW_WAIT => IsoTpAction::None,
+                    FLOW_OVERFLOW => {
+                        self.tx = None;
+                        IsoTpAction::Aborted
+                    }
+                    _ => IsoTpAction::None,
+                }
+            }
+            _ => IsoTpAction::None,
+        }
+    }
+
+    /// Returns the next block of consecutive frames.
+    fn next_block(&mut self, block_size: u8, st_min: Duration) -> IsoTpAction {
+        let Some(mut tx) = self.tx.take() else {
+            return IsoTpAction::None;
+        };
+        let count = match block_size {
+            0 => usize::MAX,
+            block_size => usize::from(block_size),
+        };
+        let mut frames = Vec::new();
+        while tx.offset < tx.message.len() && frames.len() < count {
+            let end = (tx.offset + 7).min(tx.message.len());
+            let mut data = vec![(CONSECUTIVE_FRAME << 4) | tx.next];
+            data.extend_from_slice(&tx.message[tx.offset..end]);
+            frames.push(self.frame(data));
+            tx.offset = end;
+            tx.next = (tx.next + 1) & 0x0F;
+        }
+        let is_last = tx.offset >= tx.message.len();
+        if !is_last {
+            self.tx = Some(tx);
+        }
+
+        IsoTpAction::Block(frames, st_min, is_last)
+    }
+
+    /// Creates a flow control frame.
+    fn flow_control(&self, status: u8) -> CanData {
+        self.frame(vec![
+            (FLOW_CONTROL << 4) | status,
+            self.block_size,
+            self.st_min,
+        ])
+    }
+
+    /// Creates a frame, padded if configured.
+    fn frame(&self, mut data: Vec<u8>) -> CanData {
+        if let Some(padding) = self.padding {
+            data.resize(8, padding);
+        }
+        let frame = CanFrame::new(self.tx_id, &data).unwrap();
+
+        CanData::new(self.interface, frame)
+    }
+}
+
+/// Converts a raw identifier to a CAN identifier.
+///
+/// # Panics
+///
+/// This function panics if the identifier is out of range.
+fn can_id(id: u32, extended: bool) -> Id {
+    let can_id = if extended {
+        ExtendedId::new(id).map(Id::Extended)
+    } else {
+        u16::try_from(id)
+            .ok()
+            .and_then(StandardId::new)
+            .map(Id::Standard)
+    };
+
+    can_id.unwrap_or_else(|| panic!("Invalid UDS CAN identifier 0x{id:X}."))
+}
+
+/// Decodes the minimum separation time of a flow control frame.
+fn separation_time(st_min: u8) -> Duration {
+    match st_min {
+        0x00..=0x7F => Duration::from_millis(st_min.into()),
+        0xF1..=0xF9 => Duration::from_micros(100 * u64::from(st_min - 0xF0)),
+        // Reserved values are interpreted as the longest separation time.
+        _ => Duration::from_millis(0x7F),
+    }
+}
+
+/// Encodes the minimum separation time advertised in flow control frames.
+fn separation_time_code(st_min: Duration) -> u8 {
+    let micros = st_min.as_micros();
+    match micros.div_ceil(100) {
+        0 => 0,
+        count @ 1..=9 => 0xF0 + count as u8,
+        _ => micros.div_ceil(1000).min(0x7F) as u8,
+    }
+}
+
+/// Returns the time at which the last of a block of frames is sent.
+fn block_end(start: MonotonicTime, count: usize, st_min: Duration) -> MonotonicTime {
+    start + st_min * count.saturating_sub(1) as u32
+}
+
+/// UDS client model.
+///
+/// This model sends diagnostic requests to a server and reports their
+/// responses.
+pub struct UdsClient {
+    /// CAN frames to be transmitted -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// Diagnostic response -- output port.
+    pub response_out: Output<UdsResponse>,
+
+    /// Model instance configuration.
+    config: UdsClientConfig,
+
+    /// ISO-TP transport.
+    transport: IsoTp,
+
+    /// Request in progress.
+    request: Option<UdsRequest>,
+
+    /// Pending requests.
+    queue: VecDeque<UdsRequest>,
+
+    /// Response timeout key.
+    timeout: Option<ActionKey>,
+}
+
+impl UdsClient {
+    /// Creates a new UDS client.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a CAN identifier is out of range.
+    pub fn new(config: UdsClientConfig) -> Self {
+        let transport = IsoTp::new(
+            &config.interface,
+            config.request_id,
+            config.response_id,
+            config.extended_ids,
+            config.padding,
+            config.block_size,
+            *config.st_min,
+        );
+
+        Self {
+            frame_out: Output::new(),
+            response_out: Output::new(),
+            config,
+            transport,
+            request: None,
+            queue: VecDeque::new(),
+            timeout: None,
+        }
+    }
+
+    /// Received CAN frame -- input port.
+    pub async fn frame_in(&mut self, data: CanData, cx: &mut Context<Self>) {
+        let Some(payload) = self.transport.payload(&data) else {
+            return;
+        };
+        match self.transport.on_frame(&payload) {
+            IsoTpAction::None => {}
+            IsoTpAction::FlowControl(frame) => self.frame_out.send(frame).await,
+            IsoTpAction::Block(frames, st_min, is_last) => {
+                let end = block_end(cx.time(), frames.len(), st_min);
+                self.send_block(frames, st_min, cx).await;
+                // The P2 timeout starts once the request is fully sent;
+                // meanwhile it bounds the wait for the next flow control.
+                let timeout = Duration::from(self.config.p2_timeout);
+                if is_last {
+                    self.arm_timeout(end + timeout, cx);
+                } else {
+                    self.arm_timeout(cx.time() + timeout, cx);
+                }
+            }
+            IsoTpAction::Received(message) => self.on_response(message, cx).await,
+            IsoTpAction::Aborted => self.complete(Err(UdsError::TransferAborted), cx).await,
+        }
+    }
+
+    /// Diagnostic request -- input port.
+    ///
+    /// Requests are processed one at a time, in order.
+    pub async fn request_in(&mut self, request: UdsRequest, cx: &mut Context<Self>) {
+        self.queue.push_back(request);
+        if self.request.is_none() {
+            self.start_request(cx).await;
+        }
+    }
+
+    /// Sends the next pending request, if any.
+    async fn start_request(&mut self, cx: &mut Context<Self>) {
+        while let Some(request) = self.queue.pop_front() {
+            let Some(frame) = self.transport.start(request.encode()) else {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    "Rejecting UDS request for service 0x{:02X}: invalid length.",
+                    request.service
+                );
+                self.response_out
+                    .send(UdsResponse {
+                        request,
+                        result: Err(UdsError::TransferAborted),
+                    })
+                    .await;
+                continue;
+            };
+            self.request = Some(request);
+            self.frame_out.send(frame).await;
+            let timeout = Duration::from(self.config.p2_timeout);
+            self.arm_timeout(cx.time() + timeout, cx);
+            return;
+        }
+    }
+
+    /// Handles a received response.
+    async fn on_response(&mut self, message: Vec<u8>, cx: &mut Context<Self>) {
+        let Some(service) = self.request.as_ref().map(|request| request.service) else {
+            return;
+        };
+        match message[..] {
+            [NEGATIVE_RESPONSE, sid, NRC_RESPONSE_PENDING, ..] if sid == service => {
+                let timeout = Duration::from(self.config.p2_extended_timeout);
+                self.arm_timeout(cx.time() + timeout, cx);
+            }
+            [NEGATIVE_RESPONSE, sid, code, ..] if sid == service => {
+                self.complete(Err(UdsError::NegativeResponse(code)), cx)
+                    .await;
+            }
+            [sid, ..] if sid == service.wrapping_add(POSITIVE_RESPONSE_OFFSET) => {
+                self.complete(Ok(message[1..].to_vec()), cx).await;
+            }
+            // Responses to other requests are ignored.
+            _ => {}
+        }
+    }
+
+    /// Reports the response to the request in progress and starts the next
+    /// request.
+    async fn complete(&mut self, result: Result<Vec<u8>, UdsError>, cx: &mut Context<Self>) {
+        if let Some(key) = self.timeout.take() {
+            key.cancel();
+        }
+        self.transport.reset();
+        let Some(request) = self.request.take() else {
+            return;
+        };
+        self.response_out
+            .send(UdsResponse { request, result })
+            .await;
+        self.start_request(cx).await;
+    }
+
+    /// Handles a response timeout.
+    async fn response_timeout(&mut self, _: (), cx: &mut Context<Self>) {
+        self.timeout = None;
+        let is_suppressed = self
+            .request
+            .as_ref()
+            .is_some_and(UdsRequest::is_response_suppressed);
+        if is_suppressed {
+            self.complete(Ok(Vec::new()), cx).await;
+        } else {
+            #[cfg(feature = "tracing")]
+            warn!("UDS request timed out.");
+            self.complete(Err(UdsError::Timeout), cx).await;
+        }
+    }
+
+    /// Schedules the response timeout, replacing the current one.
+    fn arm_timeout(&mut self, deadline: MonotonicTime, cx: &mut Context<Self>) {
+        if let Some(key) = self.timeout.take() {
+            key.cancel();
+        }
+        let key = cx
+            .schedule_keyed_event(deadline, Self::response_timeout, ())
+            .unwrap();
+        self.timeout = Some(key);
+    }
+
+    /// Sends a tester present request with suppressed response if no request
+    /// is in progress.
+    async fn send_tester_present(&mut self) {
+        if self.request.is_some() || !self.queue.is_empty() || !self.transport.is_idle() {
+            return;
+        }
+        let request = UdsRequest::tester_present().with_suppressed_response();
+        if let Some(frame) = self.transport.start(request.encode()) {
+            self.frame_out.send(frame).await;
+        }
+    }
+
+    /// Sends a block of consecutive frames, separated by the minimum
+    /// separation time.
+    async fn send_block(&mut self, frames: Vec<CanData>, st_min: Duration, cx: &mut Context<Self>) {
+        let now = cx.time();
+        for (i, frame) in frames.into_iter().enumerate() {
+            if i == 0 || st_min.is_zero() {
+                self.frame_out.send(frame).await;
+            } else {
+                cx.schedule_event(now + st_min * i as u32, Self::emit, frame)
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Emits a scheduled frame.
+    async fn emit(&mut self, frame: CanData) {
+        self.frame_out.send(frame).await;
+    }
+}
+
+impl Model for UdsClient {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.tester_present_period {
+            let period = Duration::from(period);
+            context
+                .schedule_periodic_event(period, period, Self::send_tester_present, ())
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for UdsClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UdsClient")
+            .field("interface", &self.config.interface)
+            .field("request_id", &self.config.request_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Data identifier of the server.
+#[derive(Clone, Debug)]
+struct Did {
+    /// Value.
+    data: Vec<u8>,
+
+    /// Data identifier can be written by clients.
+    writable: bool,
+}
+
+/// UDS server model.
+///
+/// This model answers the diagnostic requests of clients on behalf of an
+/// emulated ECU.
+pub struct UdsServer {
+    /// CAN frames to be transmitted -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// Active diagnostic session, on change -- output port.
+    pub session_out: Output<u8>,
+
+    /// Reset type of the ECU reset requests -- output port.
+    pub reset_out: Output<u8>,
+
+    /// Data identifier written by a client -- output port.
+    pub did_out: Output<UdsDid>,
+
+    /// Request for a forwarded service -- output port.
+    pub request_out: Output<UdsRequest>,
+
+    /// Model instance configuration.
+    config: UdsServerConfig,
+
+    /// ISO-TP transport.
+    transport: IsoTp,
+
+    /// Identifier of the functionally addressed requests.
+    functional_id: Option<Id>,
+
+    /// Active diagnostic session.
+    session: u8,
+
+    /// Data identifiers.
+    dids: HashMap<u16, Did>,
+
+    /// Forwarded request awaiting its response from the simulation.
+    forwarded: Option<UdsRequest>,
+
+    /// S3 timeout key.
+    s3_timeout: Option<ActionKey>,
+}
+
+impl UdsServer {
+    /// Creates a new UDS server.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a CAN identifier is out of range.
+    pub fn new(config: UdsServerConfig) -> Self {
+        let transport = IsoTp::new(
+            &config.interface,
+            config.response_id,
+            config.request_id,
+            config.extended_ids,
+            config.padding,
+            config.block_size,
+            *config.st_min,
+        );
+        let functional_id = config
+            .functional_id
+            .map(|id| can_id(id, config.extended_ids));
+        let dids = Self::initial_dids(&config);
+
+        Self {
+            frame_out: Output::new(),
+            session_out: Output::new(),
+            reset_out: Output::new(),
+            did_out: Output::new(),
+            request_out: Output::new(),
+            config,
+            transport,
+            functional_id,
+            session: DEFAULT_SESSION,
+            dids,
+            forwarded: None,
+            s3_timeout: None,
+        }
+    }
+
+    /// Returns the data identifiers as configured.
+    fn initial_dids(config: &UdsServerConfig) -> HashMap<u16, Did> {
+        config
+            .dids
+            .iter()
+            .map(|did| {
+                (
+                    did.id,
+                    Did {
+                        data: did.data.clone(),
+                        writable: did.writable,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Received CAN frame -- input port.
+    pub async fn frame_in(&mut self, data: CanData, cx: &mut Context<Self>) {
+        if let Some(message) = self.functional_request(&data) {
+            self.on_request(message, cx).await;
+            return;
+        }
+        let Some(payload) = self.transport.payload(&data) else {
+            return;
+        };
+        match self.transport.on_frame(&payload) {
+            IsoTpAction::None => {}
+            IsoTpAction::FlowControl(frame) => self.frame_out.send(frame).await,
+            IsoTpAction::Block(frames, st_min, _) => self.send_block(frames, st_min, cx).await,
+            IsoTpAction::Received(message) => self.on_request(message, cx).await,
+            IsoTpAction::Aborted => {
+                #[cfg(feature = "tracing")]
+                warn!("UDS response transfer aborted by the client.");
+            }
+        }
+    }
+
+    /// Data identifier value -- input port.
+    ///
+    /// Values for unknown data identifiers are ignored.
+    pub async fn did_in(&mut self, did: UdsDid) {
+        if let Some(entry) = self.dids.get_mut(&did.id) {
+            entry.data = did.data;
+        }
+    }
+
+    /// Response to a forwarded request -- input port.
+    ///
+    /// Responses which do not match the forwarded request are ignored, and so
+    /// are timeouts and aborted transfers.
+    pub async fn response_in(&mut self, response: UdsResponse) {
+        if self.forwarded.as_ref() != Some(&response.request) {
+            return;
+        }
+        let request = self.forwarded.take().unwrap();
+        match response.result {
+            Ok(data) => self.respond(&request, &data).await,
+            Err(UdsError::NegativeResponse(code)) => {
+                self.reject(request.service, code).await;
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Returns the message of a functionally addressed single frame request.
+    fn functional_request(&self, data: &CanData) -> Option<Vec<u8>> {
+        let id = self.functional_id?;
+        if data.interface != self.transport.interface || data.own {
+            return None;
+        }
+        let CanFrame::Data(frame) = data.frame else {
+            return None;
+        };
+        let payload = frame.data();
+        let len = usize::from(payload.first()? & 0x0F);
+        if frame.id() != id || payload[0] >> 4 != SINGLE_FRAME || len == 0 || len >= payload.len() {
+            return None;
+        }
+
+        Some(payload[1..1 + len].to_vec())
+    }
+
+    /// Handles a received request.
+    async fn on_request(&mut self, message: Vec<u8>, cx: &mut Context<Self>) {
+        let request = UdsRequest::new(message[0], message[1..].to_vec());
+        if self.session != DEFAULT_SESSION {
+            self.arm_s3_timeout(cx);
+        }
+
+        if self.config.forwarded_services.contains(&request.service) {
+            self.forwarded = Some(request.clone());
+            self.request_out.send(request).await;
+            return;
+        }
+        match request.service {
+            SID_SESSION_CONTROL => self.on_session_control(request, cx).await,
+            SID_ECU_RESET => self.on_ecu_reset(request, cx).await,
+            SID_READ_DATA => self.on_read_data(request).await,
+            SID_WRITE_DATA => self.on_write_data(request).await,
+            SID_TESTER_PRESENT => match request.data[..] {
+                [sub_function] if sub_function & !SUPPRESS_POSITIVE_RESPONSE == 0 => {
+                    self.respond(&request, &[0]).await;
+                }
+                [_] => {
+                    self.reject(request.service, NRC_SUB_FUNCTION_NOT_SUPPORTED)
+                        .await
+                }
+                _ => self.reject(request.service, NRC_INCORRECT_LENGTH).await,
+            },
+            service => self.reject(service, NRC_SERVICE_NOT_SUPPORTED).await,
+        }
+    }
+
+    /// Handles a diagnostic session control request.
+    async fn on_session_control(&mut self, request: UdsRequest, cx: &mut Context<Self>) {
+        let [sub_function] = request.data[..] else {
+            return self.reject(request.service, NRC_INCORRECT_LENGTH).await;
+        };
+        let session = sub_function & !SUPPRESS_POSITIVE_RESPONSE;
+        if session == 0
+            || !(self.config.sessions.is_empty() || self.config.sessions.contains(&session))
+        {
+            return self
+                .reject(request.service, NRC_SUB_FUNCTION_NOT_SUPPORTED)
+                .await;
+        }
+        let p2 = (self.config.p2_timeout.as_millis().min(0xFFFF) as u16).to_be_bytes();
+        let p2_extended =
+            ((self.config.p2_extended_timeout.as_millis() / 10).min(0xFFFF) as u16).to_be_bytes();
+        self.respond(
+            &request,
+            &[session, p2[0], p2[1], p2_extended[0], p2_extended[1]],
+        )
+        .await;
+        self.set_session(session, cx).await;
+    }
+
+    /// Handles an ECU reset request.
+    async fn on_ecu_reset(&mut self, request: UdsRequest, cx: &mut Context<Self>) {
+        let [sub_function] = request.data[..] else {
+            return self.reject(request.service, NRC_INCORRECT_LENGTH).await;
+        };
+        let reset_type = sub_function & !SUPPRESS_POSITIVE_RESPONSE;
+        if reset_type == 0 {
+            return self
+                .reject(request.service, NRC_SUB_FUNCTION_NOT_SUPPORTED)
+                .await;
+        }
+        self.respond(&request, &[reset_type]).await;
+        #[cfg(feature = "tracing")]
+        info!("UDS server reset requested, type {}.", reset_type);
+        self.set_session(DEFAULT_SESSION, cx).await;
+        self.reset_out.send(reset_type).await;
+    }
+
+    /// Handles a read data by identifier request.
+    async fn on_read_data(&mut self, request: UdsRequest) {
+        if request.data.is_empty() || request.data.len() % 2 != 0 {
+            return self.reject(request.service, NRC_INCORRECT_LENGTH).await;
+        }
+        let mut data = Vec::new();
+        for id in request.data.chunks(2) {
+            let Some(did) = self.dids.get(&u16::from_be_bytes([id[0], id[1]])) else {
+                return self.reject(request.service, NRC_REQUEST_OUT_OF_RANGE).await;
+            };
+            data.extend_from_slice(id);
+            data.extend_from_slice(&did.data);
+        }
+        if data.len() >= MAX_MESSAGE_LEN {
+            return self.reject(request.service, NRC_INCORRECT_LENGTH).await;
+        }
+        self.respond(&request, &data).await;
+    }
+
+    /// Handles a write data by identifier request.
+    async fn on_write_data(&mut self, request: UdsRequest) {
+        if request.data.len() < 3 {
+            return self.reject(request.service, NRC_INCORRECT_LENGTH).await;
+        }
+        let id = u16::from_be_bytes([request.data[0], request.data[1]]);
+        let value = &request.data[2..];
+        let code = match self.dids.get_mut(&id) {
+            None => Some(NRC_REQUEST_OUT_OF_RANGE),
+            Some(did) if !did.writable => Some(NRC_REQUEST_OUT_OF_RANGE),
+            Some(did) if !did.data.is_empty() && did.data.len() != value.len() => {
+                Some(NRC_INCORRECT_LENGTH)
+            }
+            Some(did) => {
+                did.data = value.to_vec();
+                None
+            }
+        };
+        if let Some(code) = code {
+            return self.reject(request.service, code).await;
+        }
+        let data = value.to_vec();
+        self.respond(&request, &request.data[..2]).await;
+        self.did_out.send(UdsDid { id, data }).await;
+    }
+
+    /// Changes the active diagnostic session.
+    async fn set_session(&mut self, session: u8, cx: &mut Context<Self>) {
+        if let Some(key) = self.s3_timeout.take() {
+            key.cancel();
+        }
+        if session != DEFAULT_SESSION {
+            self.arm_s3_timeout(cx);
+        }
+        if session == self.session {
+            return;
+        }
+        #[cfg(feature = "tracing")]
+        info!("UDS server entering session 0x{:02X}.", session);
+        self.session = session;
+        self.session_out.send(session).await;
+    }
+
+    /// Schedules the S3 timeout, replacing the current one.
+    fn arm_s3_timeout(&mut self, cx: &mut Context<Self>) {
+        if let Some(key) = self.s3_timeout.take() {
+            key.cancel();
+        }
+        let key = cx
+            .schedule_keyed_event(
+                Duration::from(self.config.s3_timeout),
+                Self::session_timeout,
+                (),
+            )
+            .unwrap();
+        self.s3_timeout = Some(key);
+    }
+
+    /// Falls back to the default session.
+    async fn session_timeout(&mut self, _: (), cx: &mut Context<Self>) {
+        self.s3_timeout = None;
+        self.set_session(DEFAULT_SESSION, cx).await;
+    }
+
+    /// Sends a positive response, unless suppressed.
+    async fn respond(&mut self, request: &UdsRequest, data: &[u8]) {
+        if request.is_response_suppressed() {
+            return;
+        }
+        let mut message = Vec::with_capacity(1 + data.len());
+        message.push(request.service.wrapping_add(POSITIVE_RESPONSE_OFFSET));
+        message.extend_from_slice(data);
+        self.send(message).await;
+    }
+
+    /// Sends a negative response.
+    async fn reject(&mut self, service: u8, code: u8) {
+        self.send(vec![NEGATIVE_RESPONSE, service, code]).await;
+    }
+
+    /// Sends a response message.
+    async fn send(&mut self, message: Vec<u8>) {
+        if let Some(frame) = self.transport.start(message) {
+            self.frame_out.send(frame).await;
+        }
+    }
+
+    /// Sends a block of consecutive frames, separated by the minimum
+    /// separation time.
+    async fn send_block(&mut self, frames: Vec<CanData>, st_min: Duration, cx: &mut Context<Self>) {
+        let now = cx.time();
+        for (i, frame) in frames.into_iter().enumerate() {
+            if i == 0 || st_min.is_zero() {
+                self.frame_out.send(frame).await;
+            } else {
+                cx.schedule_event(now + st_min * i as u32, Self::emit, frame)
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Emits a scheduled frame.
+    async fn emit(&mut self, frame: CanData) {
+        self.frame_out.send(frame).await;
+    }
+}
+
+impl Model for UdsServer {}
+
+impl fmt::Debug for UdsServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UdsServer")
+            .field("interface", &self.config.interface)
+            .field("request_id", &self.config.request_id)
+            .field("session", &self.session)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,1406 @@
+//! XCP master.
+//!
+//! This module contains the [`XcpMaster`] model, which drives the measurement
+//! and calibration of an ECU implementing an XCP slave:
+//! * connection and disconnection,
+//! * polled measurements, read with the upload commands either on request or
+//!   periodically,
+//! * DAQ measurements, with the DAQ lists of the configuration set up and
+//!   started on request, the received DAQ packets being decoded into
+//!   measurement samples,
+//! * calibration writes, with the download commands.
+//!
+//! Commands are processed one at a time, in order, each command sent to the
+//! slave being answered within the T1 timeout.
+//!
+//! The XCP packets are exchanged either over CAN, in which case the model
+//! should be connected to the frame output and input of a
+//! [`CanPort`](crate::CanPort) configured with `interface_names` enabled, or
+//! over UDP, in which case the model should be connected to the data output
+//! and input of an
+//! [`ExternalPort`](nexosim_io_utils::external::ExternalPort) running an
+//! [`XcpUdpPort`].
+//!
+//! The slave is expected to use byte address granularity and absolute ODT
+//! numbers as DAQ packet identifiers. Measurements are packed into the ODTs
+//! of their DAQ list in configuration order.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_can_port::xcp::{XcpCommand, XcpMaster, XcpMasterConfig};
+//!
+//! let config = ConfigLoader::<XcpMasterConfig>::new()
+//!     .code(
+//!         r#"
+//! interface = "can0"
+//! masterId = 0x7F0
+//! slaveId = 0x7F1
+//! pollPeriod = 100
+//!
+//! [[polled]]
+//! name = "engine_speed"
+//! address = 0x2000_0010
+//! size = 2
+//!
+//! [[daqLists]]
+//! event = 1
+//!
+//! [[daqLists.measurements]]
+//! name = "throttle"
+//! address = 0x2000_0020
+//! size = 4
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! let master = XcpMaster::new(config);
+//!
+//! // Connect `master.frame_out` to `CanPort::frame_in` and
+//! // `CanPort::frame_out` to `XcpMaster::frame_in`, then send commands such
+//! // as:
+//! let commands = [XcpCommand::Connect, XcpCommand::StartDaq];
+//! ```
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{ErrorKind, Result as IoResult};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use schematic::{Config, ConfigEnum};
+use serde::{Deserialize, Serialize};
+
+use mio::net::UdpSocket;
+use mio::{Interest, Registry, Token};
+
+use socketcan::{CanFrame, EmbeddedFrame, ExtendedId, Id, StandardId};
+
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use nexosim::model::{Context, InitializedModel, Model};
+use nexosim::ports::Output;
+use nexosim::simulation::ActionKey;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::IoPort;
+
+use crate::{CanData, CanInterface};
+
+/// Error code: command rejected because the slave is busy.
+pub const ERR_CMD_BUSY: u8 = 0x10;
+
+/// Error code: unknown command.
+pub const ERR_CMD_UNKNOWN: u8 = 0x20;
+
+/// Error code: command parameter out of range.
+pub const ERR_OUT_OF_RANGE: u8 = 0x22;
+
+/// Error code: access denied.
+pub const ERR_ACCESS_DENIED: u8 = 0x24;
+
+/// Error code: not enough memory for the DAQ lists.
+pub const ERR_MEMORY_OVERFLOW: u8 = 0x30;
+
+/// Positive response packet identifier.
+const PID_RESPONSE: u8 = 0xFF;
+
+/// Error packet identifier.
+const PID_ERROR: u8 = 0xFE;
+
+/// First packet identifier reserved for the slave to master CTOs.
+const PID_FIRST_CTO: u8 = 0xFC;
+
+/// CONNECT command.
+const CMD_CONNECT: u8 = 0xFF;
+
+/// DISCONNECT command.
+const CMD_DISCONNECT: u8 = 0xFE;
+
+/// SET_MTA command.
+const CMD_SET_MTA: u8 = 0xF6;
+
+/// UPLOAD command.
+const CMD_UPLOAD: u8 = 0xF5;
+
+/// SHORT_UPLOAD command.
+const CMD_SHORT_UPLOAD: u8 = 0xF4;
+
+/// DOWNLOAD command.
+const CMD_DOWNLOAD: u8 = 0xF0;
+
+/// SET_DAQ_PTR command.
+const CMD_SET_DAQ_PTR: u8 = 0xE2;
+
+/// WRITE_DAQ command.
+const CMD_WRITE_DAQ: u8 = 0xE1;
+
+/// SET_DAQ_LIST_MODE command.
+const CMD_SET_DAQ_LIST_MODE: u8 = 0xE0;
+
+/// START_STOP_DAQ_LIST command.
+const CMD_START_STOP_DAQ_LIST: u8 = 0xDE;
+
+/// START_STOP_SYNCH command.
+const CMD_START_STOP_SYNCH: u8 = 0xDD;
+
+/// FREE_DAQ command.
+const CMD_FREE_DAQ: u8 = 0xD6;
+
+/// ALLOC_DAQ command.
+const CMD_ALLOC_DAQ: u8 = 0xD5;
+
+/// ALLOC_ODT command.
+const CMD_ALLOC_ODT: u8 = 0xD4;
+
+/// ALLOC_ODT_ENTRY command.
+const CMD_ALLOC_ODT_ENTRY: u8 = 0xD3;
+
+/// Size of the XCP on Ethernet header.
+const ETHERNET_HEADER_LEN: usize = 4;
+
+/// UDP socket token.
+const SOCKET: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// Maximum size of a received datagram.
+const MAX_DATAGRAM_LEN: usize = 65536;
+
+/// XCP master model instance configuration.
+#[derive(Config, Debug)]
+pub struct XcpMasterConfig {
+    /// Transport layer.
+    pub transport: XcpTransport,
+
+    /// CAN interface name, for the CAN transport.
+    #[setting(default = "vcan0")]
+    pub interface: String,
+
+    /// CAN identifier of the master to slave packets, for the CAN transport.
+    #[setting(default = 0x7F0)]
+    pub master_id: u32,
+
+    /// CAN identifier of the slave to master packets, for the CAN transport.
+    #[setting(default = 0x7F1)]
+    pub slave_id: u32,
+
+    /// CAN identifiers are 29-bit extended identifiers.
+    pub extended_ids: bool,
+
+    /// Padding byte of the CAN frames shorter than 8 bytes.
+    ///
+    /// If no value is provided, frames are not padded.
+    pub padding: Option<u8>,
+
+    /// T1 timeout of the commands.
+    #[setting(default = ConfigDuration::from_millis(100))]
+    pub timeout: ConfigDuration,
+
+    /// Measurements read periodically with the upload commands.
+    #[setting(nested)]
+    pub polled: Vec<XcpMeasurementConfig>,
+
+    /// Period of the polled measurements.
+    ///
+    /// If no value is provided, the polled measurements are only read on
+    /// request.
+    pub poll_period: Option<ConfigDuration>,
+
+    /// DAQ lists.
+    #[setting(nested)]
+    pub daq_lists: Vec<XcpDaqListConfig>,
+}
+
+/// XCP transport layer.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum XcpTransport {
+    /// XCP on CAN.
+    #[default]
+    Can,
+
+    /// XCP on UDP.
+    Udp,
+}
+
+/// Measurement configuration.
+#[derive(Config, Debug)]
+pub struct XcpMeasurementConfig {
+    /// Measurement name.
+    pub name: String,
+
+    /// Address.
+    pub address: u32,
+
+    /// Address extension.
+    pub extension: u8,
+
+    /// Size, in bytes.
+    #[setting(default = 1)]
+    pub size: u8,
+}
+
+/// DAQ list configuration.
+#[derive(Config, Debug)]
+pub struct XcpDaqListConfig {
+    /// Event channel triggering the DAQ list.
+    pub event: u16,
+
+    /// Transmission rate prescaler.
+    #[setting(default = 1)]
+    pub prescaler: u8,
+
+    /// DAQ list priority.
+    pub priority: u8,
+
+    /// Measurements.
+    #[setting(nested)]
+    pub measurements: Vec<XcpMeasurementConfig>,
+}
+
+/// XCP master command.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum XcpCommand {
+    /// Connects to the slave.
+    Connect,
+
+    /// Disconnects from the slave.
+    Disconnect,
+
+    /// Reads a memory block.
+    Upload {
+        /// Address.
+        address: u32,
+        /// Address extension.
+        extension: u8,
+        /// Size, in bytes.
+        size: usize,
+    },
+
+    /// Writes a memory block.
+    Download {
+        /// Address.
+        address: u32,
+        /// Address extension.
+        extension: u8,
+        /// Data.
+        data: Vec<u8>,
+    },
+
+    /// Reads the polled measurements.
+    Poll,
+
+    /// Sets up and starts the DAQ lists.
+    StartDaq,
+
+    /// Stops all DAQ lists.
+    StopDaq,
+}
+
+/// Response to an XCP master command.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct XcpResponse {
+    /// Command.
+    pub command: XcpCommand,
+
+    /// Response parameters of the last command packet, without the packet
+    /// identifier, or the uploaded data, or error.
+    pub result: Result<Vec<u8>, XcpError>,
+}
+
+/// XCP master command error.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum XcpError {
+    /// Error packet, with its error code.
+    Error(u8),
+
+    /// No response within the T1 timeout.
+    Timeout,
+
+    /// Not connected to the slave.
+    NotConnected,
+
+    /// A measurement does not fit in a DAQ packet, or a DAQ list is empty.
+    DaqLayout,
+}
+
+impl fmt::Display for XcpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Error(code) => write!(f, "error 0x{code:02X}"),
+            Self::Timeout => f.write_str("timeout"),
+            Self::NotConnected => f.write_str("not connected"),
+            Self::DaqLayout => f.write_str("invalid DAQ layout"),
+        }
+    }
+}
+
+/// Measurement sample.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct XcpSample {
+    /// Measurement name.
+    pub name: String,
+
+    /// Raw value, in the byte order of the slave.
+    pub data: Vec<u8>,
+}
+
+/// Handling of the positive response to a command packet.
+#[derive(Debug)]
+enum StepKind {
+    /// Response parameters are kept as the command result.
+    Plain,
+
+    /// CONNECT response.
+    Connect,
+
+    /// DISCONNECT response.
+    Disconnect,
+
+    /// Uploaded data of the given size, appended to the command result.
+    Upload(usize),
+
+    /// START_STOP_DAQ_LIST response, with the index of the selected DAQ list.
+    SelectDaq(usize),
+}
+
+/// Command packet.
+#[derive(Debug)]
+struct Step {
+    /// Packet.
+    packet: Vec<u8>,
+
+    /// Response handling.
+    kind: StepKind,
+}
+
+impl Step {
+    /// Creates a command packet.
+    fn new(packet: Vec<u8>, kind: StepKind) -> Self {
+        Self { packet, kind }
+    }
+}
+
+/// Command in progress.
+#[derive(Debug)]
+struct Job {
+    /// Command.
+    command: XcpCommand,
+
+    /// Command packets not yet answered.
+    steps: VecDeque<Step>,
+
+    /// Result data.
+    data: Vec<u8>,
+}
+
+/// DAQ list layout.
+#[derive(Debug)]
+struct DaqList {
+    /// Measurement indices of each ODT.
+    odts: Vec<Vec<usize>>,
+
+    /// Packet identifier of the first ODT, once the list is selected.
+    first_pid: Option<u8>,
+}
+
+/// XCP master model.
+///
+/// This model sends XCP commands to a slave and decodes its measurements.
+pub struct XcpMaster {
+    /// CAN frames to be transmitted, for the CAN transport -- output port.
+    pub frame_out: Output<CanData>,
+
+    /// Datagrams to be transmitted, for the UDP transport -- output port.
+    pub datagram_out: Output<Bytes>,
+
+    /// Command response -- output port.
+    pub response_out: Output<XcpResponse>,
+
+    /// Measurement sample -- output port.
+    pub sample_out: Output<XcpSample>,
+
+    /// Model instance configuration.
+    config: XcpMasterConfig,
+
+    /// CAN interface.
+    interface: CanInterface,
+
+    /// Identifier of the transmitted frames.
+    master_id: Id,
+
+    /// Identifier of the received frames.
+    slave_id: Id,
+
+    /// Slave is connected.
+    is_connected: bool,
+
+    /// Slave uses the big-endian byte order.
+    is_big_endian: bool,
+
+    /// Maximum size of the command packets.
+    max_cto: usize,
+
+    /// Maximum size of the DAQ packets.
+    max_dto: usize,
+
+    /// Counter of the transmitted XCP on Ethernet messages.
+    counter: u16,
+
+    /// Command in progress.
+    job: Option<Job>,
+
+    /// Pending commands.
+    queue: VecDeque<XcpCommand>,
+
+    /// T1 timeout key.
+    timeout: Option<ActionKey>,
+
+    /// DAQ list layouts, once the DAQ lists are set up.
+    daq_lists: Vec<DaqList>,
+}
+
+impl XcpMaster {
+    /// Creates a new XCP master.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a CAN identifier is out of range.
+    pub fn new(config: XcpMasterConfig) -> Self {
+        let interface = CanInterface::named(&config.interface);
+        let master_id = can_id(config.master_id, config.extended_ids);
+        let slave_id = can_id(config.slave_id, config.extended_ids);
+
+        Self {
+            frame_out: Output::new(),
+            datagram_out: Output::new(),
+            response_out: Output::new(),
+            sample_out: Output::new(),
+            config,
+            interface,
+            master_id,
+            slave_id,
+            is_connected: false,
+            is_big_endian: false,
+            max_cto: 8,
+            max_dto: 8,
+            counter: 0,
+            job: None,
+            queue: VecDeque::new(),
+            timeout: None,
+            daq_lists: Vec::new(),
+        }
+    }
+
+    /// Received CAN frame, for the CAN transport -- input port.
+    pub async fn frame_in(&mut self, data: CanData, cx: &mut Context<Self>) {
+        if self.config.transport != XcpTransport::Can
+            || data.interface != self.interface
+            || data.own
+        {
+            return;
+        }
+        let CanFrame::Data(frame) = data.frame else {
+            return;
+        };
+        if frame.id() == self.slave_id {
+            self.on_packet(frame.data(), cx).await;
+        }
+    }
+
+    /// Received datagram, for the UDP transport -- input port.
+    pub async fn datagram_in(&mut self, datagram: Bytes, cx: &mut Context<Self>) {
+        if self.config.transport != XcpTransport::Udp {
+            return;
+        }
+        // A datagram may hold several messages.
+        let mut buf = &datagram[..];
+        while buf.len() >= ETHERNET_HEADER_LEN {
+            let len = usize::from(u16::from_le_bytes([buf[0], buf[1]]));
+            let Some(packet) = buf.get(ETHERNET_HEADER_LEN..ETHERNET_HEADER_LEN + len) else {
+                break;
+            };
+            self.on_packet(packet, cx).await;
+            buf = &buf[ETHERNET_HEADER_LEN + len..];
+        }
+    }
+
+    /// Command -- input port.
+    ///
+    /// Commands are processed one at a time, in order.
+    pub async fn command_in(&mut self, command: XcpCommand, cx: &mut Context<Self>) {
+        self.queue.push_back(command);
+        if self.job.is_none() {
+            self.start_job(cx).await;
+        }
+    }
+
+    /// Queues the reading of the polled measurements, unless already queued.
+    async fn poll(&mut self, _: (), cx: &mut Context<Self>) {
+        if !self.is_connected || self.queue.contains(&XcpCommand::Poll) {
+            return;
+        }
+        self.command_in(XcpCommand::Poll, cx).await;
+    }
+
+    /// Starts the next pending command, if any.
+    async fn start_job(&mut self, cx: &mut Context<Self>) {
+        while let Some(command) = self.queue.pop_front() {
+            match self.steps(&command) {
+                Ok(steps) if steps.is_empty() => {
+                    self.report(command, Ok(Vec::new())).await;
+                }
+                Ok(steps) => {
+                    self.job = Some(Job {
+                        command,
+                        steps,
+                        data: Vec::new(),
+                    });
+                    self.send_step(cx).await;
+                    return;
+                }
+                Err(e) => self.report(command, Err(e)).await,
+            }
+        }
+    }
+
+    /// Returns the command packets of a command.
+    fn steps(&mut self, command: &XcpCommand) -> Result<VecDeque<Step>, XcpError> {
+        if !self.is_connected && !matches!(command, XcpCommand::Connect | XcpCommand::Disconnect) {
+            return Err(XcpError::NotConnected);
+        }
+        let mut steps = VecDeque::new();
+        match command {
+            XcpCommand::Connect => {
+                steps.push_back(Step::new(vec![CMD_CONNECT, 0], StepKind::Connect));
+            }
+            XcpCommand::Disconnect => {
+                steps.push_back(Step::new(vec![CMD_DISCONNECT], StepKind::Disconnect));
+            }
+            XcpCommand::Upload {
+                address,
+                extension,
+                size,
+            } => self.upload_steps(&mut steps, *address, *extension, *size),
+            XcpCommand::Download {
+                address,
+                extension,
+                data,
+            } => {
+                steps.push_back(self.set_mta(*address, *extension));
+                for chunk in data.chunks(self.max_cto - 2) {
+                    let mut packet = vec![CMD_DOWNLOAD, chunk.len() as u8];
+                    packet.extend_from_slice(chunk);
+                    steps.push_back(Step::new(packet, StepKind::Plain));
+                }
+            }
+            XcpCommand::Poll => {
+                for i in 0..self.config.polled.len() {
+                    let measurement = &self.config.polled[i];
+                    let (address, extension, size) = (
+                        measurement.address,
+                        measurement.extension,
+                        usize::from(measurement.size),
+                    );
+                    self.upload_steps(&mut steps, address, extension, size);
+                }
+            }
+            XcpCommand::StartDaq => self.daq_steps(&mut steps)?,
+            XcpCommand::StopDaq => {
+                steps.push_back(Step::new(vec![CMD_START_STOP_SYNCH, 0], StepKind::Plain));
+            }
+        }
+
+        Ok(steps)
+    }
+
+    /// Appends the command packets uploading a memory block.
+    fn upload_steps(&self, steps: &mut VecDeque<Step>, address: u32, extension: u8, size: usize) {
+        let max = self.max_cto - 1;
+        if size <= max {
+            let mut packet = vec![CMD_SHORT_UPLOAD, size as u8, 0, extension];
+            packet.extend_from_slice(&self.dword(address));
+            steps.push_back(Step::new(packet, StepKind::Upload(size)));
+            return;
+        }
+        steps.push_back(self.set_mta(address, extension));
+        let mut remaining = size;
+        while remaining > 0 {
+            let len = remaining.min(max);
+            steps.push_back(Step::new(
+                vec![CMD_UPLOAD, len as u8],
+                StepKind::Upload(len),
+            ));
+            remaining -= len;
+        }
+    }
+
+    /// Returns the SET_MTA command packet.
+    fn set_mta(&self, address: u32, extension: u8) -> Step {
+        let mut packet = vec![CMD_SET_MTA, 0, 0, extension];
+        packet.extend_from_slice(&self.dword(address));
+
+        Step::new(packet, StepKind::Plain)
+    }
+
+    /// Appends the command packets setting up and starting the DAQ lists.
+    fn daq_steps(&mut self, steps: &mut VecDeque<Step>) -> Result<(), XcpError> {
+        // Measurements are packed into ODTs after the packet identifier.
+        let capacity = self.max_dto - 1;
+        let mut daq_lists = Vec::with_capacity(self.config.daq_lists.len());
+        for list in &self.config.daq_lists {
+            let mut odts: Vec<Vec<usize>> = Vec::new();
+            let mut used = capacity;
+            for (i, measurement) in list.measurements.iter().enumerate() {
+                let size = usize::from(measurement.size);
+                if size == 0 || size > capacity {
+                    return Err(XcpError::DaqLayout);
+                }
+                if used + size > capacity {
+                    odts.push(Vec::new());
+                    used = 0;
+                }
+                odts.last_mut().unwrap().push(i);
+                used += size;
+            }
+            if odts.is_empty() {
+                return Err(XcpError::DaqLayout);
+            }
+            daq_lists.push(DaqList {
+                odts,
+                first_pid: None,
+            });
+        }
+
+        steps.push_back(Step::new(vec![CMD_FREE_DAQ], StepKind::Plain));
+        let [c0, c1] = self.word(daq_lists.len() as u16);
+        steps.push_back(Step::new(vec![CMD_ALLOC_DAQ, 0, c0, c1], StepKind::Plain));
+        for (daq, list) in daq_lists.iter().enumerate() {
+            let [d0, d1] = self.word(daq as u16);
+            steps.push_back(Step::new(
+                vec![CMD_ALLOC_ODT, 0, d0, d1, list.odts.len() as u8],
+                StepKind::Plain,
+            ));
+        }
+        for (daq, list) in daq_lists.iter().enumerate() {
+            let [d0, d1] = self.word(daq as u16);
+            for (odt, entries) in list.odts.iter().enumerate() {
+                steps.push_back(Step::new(
+                    vec![
+                        CMD_ALLOC_ODT_ENTRY,
+                        0,
+                        d0,
+                        d1,
+                        odt as u8,
+                        entries.len() as u8,
+                    ],
+                    StepKind::Plain,
+                ));
+            }
+        }
+        for (daq, (list, config)) in daq_lists.iter().zip(&self.config.daq_lists).enumerate() {
+            let [d0, d1] = self.word(daq as u16);
+            for (odt, entries) in list.odts.iter().enumerate() {
+                steps.push_back(Step::new(
+                    vec![CMD_SET_DAQ_PTR, 0, d0, d1, odt as u8, 0],
+                    StepKind::Plain,
+                ));
+                for &i in entries {
+                    let measurement = &config.measurements[i];
+                    let mut packet =
+                        vec![CMD_WRITE_DAQ, 0xFF, measurement.size, measurement.extension];
+                    packet.extend_from_slice(&self.dword(measurement.address));
+                    steps.push_back(Step::new(packet, StepKind::Plain));
+                }
+            }
+            let [e0, e1] = self.word(config.event);
+            steps.push_back(Step::new(
+                vec![
+                    CMD_SET_DAQ_LIST_MODE,
+                    0,
+                    d0,
+                    d1,
+                    e0,
+                    e1,
+                    config.prescaler,
+                    config.priority,
+                ],
+                StepKind::Plain,
+            ));
+            steps.push_back(Step::new(
+                vec![CMD_START_STOP_DAQ_LIST, 2, d0, d1],
+                StepKind::SelectDaq(daq),
+            ));
+        }
+        steps.push_back(Step::new(vec![CMD_START_STOP_SYNCH, 1], StepKind::Plain));
+        self.daq_lists = daq_lists;
+
+        Ok(())
+    }
+
+    /// Handles a packet received from the slave.
+    async fn on_packet(&mut self, packet: &[u8], cx: &mut Context<Self>) {
+        let Some(&pid) = packet.first() else {
+            return;
+        };
+        match pid {
+            PID_RESPONSE => self.on_response(packet, cx).await,
+            PID_ERROR => {
+                let code = packet.get(1).copied().unwrap_or(0);
+                if self.job.is_some() {
+                    self.complete(Err(XcpError::Error(code)), cx).await;
+                }
+            }
+            // Events and service requests are not supported.
+            PID_FIRST_CTO.. => {}
+            _ => self.on_daq_packet(pid, &packet[1..]).await,
+        }
+    }
+
+    /// Handles a positive response.
+    async fn on_response(&mut self, packet: &[u8], cx: &mut Context<Self>) {
+        if self.job.is_none() {
+            return;
+        }
+        match self.apply_response(packet) {
+            Some(result) => self.complete(result, cx).await,
+            None => self.send_step(cx).await,
+        }
+    }
+
+    /// Applies a positive response to the command in progress, returning the
+    /// command result once the command is complete.
+    fn apply_response(&mut self, packet: &[u8]) -> Option<Result<Vec<u8>, XcpError>> {
+        let job = self.job.as_mut()?;
+        let step = job.steps.pop_front()?;
+        match step.kind {
+            StepKind::Plain => job.data = packet[1..].to_vec(),
+            StepKind::Connect => {
+                if packet.len() < 8 {
+                    return Some(Err(XcpError::Error(ERR_OUT_OF_RANGE)));
+                }
+                self.is_big_endian = packet[2] & 0x01 != 0;
+                self.max_cto = usize::from(packet[3]).max(8);
+                let max_dto = [packet[4], packet[5]];
+                self.max_dto = usize::from(if self.is_big_endian {
+                    u16::from_be_bytes(max_dto)
+                } else {
+                    u16::from_le_bytes(max_dto)
+                })
+                .max(8);
+                self.is_connected = true;
+                self.daq_lists.clear();
+                job.data = packet[1..].to_vec();
+            }
+            StepKind::Disconnect => {
+                self.is_connected = false;
+                self.daq_lists.clear();
+                job.data.clear();
+            }
+            StepKind::Upload(len) => {
+                let Some(data) = packet.get(1..1 + len) else {
+                    return Some(Err(XcpError::Error(ERR_OUT_OF_RANGE)));
+                };
+                job.data.extend_from_slice(data);
+            }
+            StepKind::SelectDaq(daq) => {
+                if let (Some(list), Some(&pid)) = (self.daq_lists.get_mut(daq), packet.get(1)) {
+                    list.first_pid = Some(pid);
+                }
+                job.data = packet[1..].to_vec();
+            }
+        }
+        job.steps
+            .is_empty()
+            .then(|| Ok(std::mem::take(&mut job.data)))
+    }
+
+    /// Handles a DAQ packet.
+    async fn on_daq_packet(&mut self, pid: u8, payload: &[u8]) {
+        for sample in self.decode_daq_packet(pid, payload) {
+            self.sample_out.send(sample).await;
+        }
+    }
+
+    /// Decodes the samples of a DAQ packet.
+    fn decode_daq_packet(&self, pid: u8, payload: &[u8]) -> Vec<XcpSample> {
+        let found = self.daq_lists.iter().enumerate().find_map(|(daq, list)| {
+            let first = list.first_pid?;
+            let odt = usize::from(pid.checked_sub(first)?);
+            (odt < list.odts.len()).then_some((daq, odt))
+        });
+        let Some((daq, odt)) = found else {
+            return Vec::new();
+        };
+        let mut offset = 0;
+        let mut samples = Vec::new();
+        for &i in &self.daq_lists[daq].odts[odt] {
+            let measurement = &self.config.daq_lists[daq].measurements[i];
+            let end = offset + usize::from(measurement.size);
+            let Some(data) = payload.get(offset..end) else {
+                #[cfg(feature = "tracing")]
+                warn!("Ignoring short XCP DAQ packet {}.", pid);
+                return Vec::new();
+            };
+            samples.push(XcpSample {
+                name: measurement.name.clone(),
+                data: data.to_vec(),
+            });
+            offset = end;
+        }
+
+        samples
+    }
+
+    /// Reports the result of the command in progress and starts the next
+    /// command.
+    async fn complete(&mut self, result: Result<Vec<u8>, XcpError>, cx: &mut Context<Self>) {
+        if let Some(key) = self.timeout.take() {
+            key.cancel();
+        }
+        let Some(job) = self.job.take() else {
+            return;
+        };
+        self.report(job.command, result).await;
+        self.start_job(cx).await;
+    }
+
+    /// Reports the result of a command.
+    ///
+    /// The data of polled measurements are reported as samples.
+    async fn report(&mut self, command: XcpCommand, result: Result<Vec<u8>, XcpError>) {
+        if let (XcpCommand::Poll, Ok(data)) = (&command, &result) {
+            for sample in self.polled_samples(data) {
+                self.sample_out.send(sample).await;
+            }
+        }
+        self.response_out
+            .send(XcpResponse { command, result })
+            .await;
+    }
+
+    /// Splits the data read by a poll command into measurement samples.
+    fn polled_samples(&self, data: &[u8]) -> Vec<XcpSample> {
+        let mut offset = 0;
+        let mut samples = Vec::with_capacity(self.config.polled.len());
+        for measurement in &self.config.polled {
+            let end = offset + usize::from(measurement.size);
+            samples.push(XcpSample {
+                name: measurement.name.clone(),
+                data: data[offset..end].to_vec(),
+            });
+            offset = end;
+        }
+
+        samples
+    }
+
+    /// Handles a T1 timeout.
+    async fn command_timeout(&mut self, _: (), cx: &mut Context<Self>) {
+        self.timeout = None;
+        #[cfg(feature = "tracing")]
+        warn!("XCP command timed out.");
+        self.complete(Err(XcpError::Timeout), cx).await;
+    }
+
+    /// Sends the next command packet of the command in progress.
+    async fn send_step(&mut self, cx: &mut Context<Self>) {
+        let Some(packet) = self
+            .job
+            .as_ref()
+            .and_then(|job| job.steps.front())
+            .map(|step| step.packet.clone())
+        else {
+            return;
+        };
+        self.arm_timeout(cx);
+        self.send(packet).await;
+    }
+
+    /// Schedules the T1 timeout, replacing the current one.
+    fn arm_timeout(&mut self, cx: &mut Context<Self>) {
+        if let Some(key) = self.timeout.take() {
+            key.cancel();
+        }
+        let key = cx
+            .schedule_keyed_event(
+                Duration::from(self.config.timeout),
+                Self::command_timeout,
+                (),
+            )
+            .unwrap();
+        self.timeout = Some(key);
+    }
+
+    /// Sends a packet with the configured transport.
+    async fn send(&mut self, mut packet: Vec<u8>) {
+        match self.config.transport {
+            XcpTransport::Can => {
+                if let Some(padding) = self.config.padding {
+                    packet.resize(packet.len().max(8), padding);
+                }
+                let Some(frame) = CanFrame::new(self.master_id, &packet) else {
+                    #[cfg(feature = "tracing")]
+                    warn!("Dropping XCP packet: too long for a CAN frame.");
+                    return;
+                };
+                self.frame_out
+                    .send(CanData::new(self.interface, frame))
+                    .await;
+            }
+            XcpTransport::Udp => {
+                let mut datagram = Vec::with_capacity(ETHERNET_HEADER_LEN + packet.len());
+                datagram.extend_from_slice(&(packet.len() as u16).to_le_bytes());
+                datagram.extend_from_slice(&self.counter.to_le_bytes());
+                datagram.extend_from_slice(&packet);
+                self.counter = self.counter.wrapping_add(1);
+                self.datagram_out.send(datagram.into()).await;
+            }
+        }
+    }
+
+    /// Encodes a 16-bit value in the byte order of the slave.
+    fn word(&self, value: u16) -> [u8; 2] {
+        if self.is_big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        }
+    }
+
+    /// Encodes a 32-bit value in the byte order of the slave.
+    fn dword(&self, value: u32) -> [u8; 4] {
+        if self.is_big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        }
+    }
+}
+
+impl Model for XcpMaster {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.poll_period {
+            if !self.config.polled.is_empty() {
+                let period = Duration::from(period);
+                context
+                    .schedule_periodic_event(period, period, Self::poll, ())
+                    .unwrap();
+            }
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for XcpMaster {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("XcpMaster")
+            .field("transport", &self.config.transport)
+            .field("is_connected", &self.is_connected)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Converts a raw identifier to a CAN identifier.
+///
+/// # Panics
+///
+/// This function panics if the identifier is out of range.
+fn can_id(id: u32, extended: bool) -> Id {
+    let can_id = if extended {
+        ExtendedId::new(id).map(Id::Extended)
+    } else {
+        u16::try_from(id)
+            .ok()
+            .and_then(StandardId::new)
+            .map(Id::Standard)
+    };
+
+    can_id.unwrap_or_else(|| panic!("Invalid XCP CAN identifier 0x{id:X}."))
+}
+
+/// UDP socket exchanging XCP on Ethernet datagrams with a slave.
+///
+/// This I/O port is meant to be run by an
+/// [`ExternalPort`](nexosim_io_utils::external::ExternalPort) connected to an
+/// [`XcpMaster`] configured with the UDP transport. Datagrams from other
+/// endpoints than the slave are ignored.
+pub struct XcpUdpPort {
+    /// UDP socket.
+    socket: UdpSocket,
+
+    /// Slave endpoint.
+    slave: SocketAddr,
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+}
+
+impl XcpUdpPort {
+    /// Binds a UDP socket to the local address for the slave endpoint.
+    pub fn bind(local: SocketAddr, slave: SocketAddr) -> IoResult<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(local)?,
+            slave,
+            buffer: vec![0; MAX_DATAGRAM_LEN],
+        })
+    }
+}
+
+impl IoPort<UdpSocket, Bytes, Bytes> for XcpUdpPort {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        registry.register(&mut self.socket, SOCKET, Interest::READABLE)?;
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, _: Token) -> IoResult<Bytes> {
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((len, source)) if source == self.slave => {
+                    return Ok(Bytes::copy_from_slice(&self.buffer[..len]));
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write(&mut self, datagram: &Bytes) -> IoResult<()> {
+        match self.socket.send_to(datagram, self.slave) {
+            Ok(_) => Ok(()),
+            // Datagrams to an unreachable slave are lost.
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl fmt::Debug for XcpUdpPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("XcpUdpPort")
+            .field("slave", &self.slave)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measurement(name: &str, address: u32, size: u8) -> XcpMeasurementConfig {
+        XcpMeasurementConfig {
+            name: name.into(),
+            address,
+            extension: 0,
+            size,
+        }
+    }
+
+    fn master(polled: Vec<XcpMeasurementConfig>, daq_lists: Vec<XcpDaqListConfig>) -> XcpMaster {
+        XcpMaster::new(XcpMasterConfig {
+            transport: XcpTransport::Can,
+            interface: "can0".into(),
+            master_id: 0x7F0,
+            slave_id: 0x7F1,
+            extended_ids: false,
+            padding: None,
+            timeout: ConfigDuration::from_millis(100),
+            polled,
+            poll_period: None,
+            daq_lists,
+        })
+    }
+
+    /// Starts a command, returning its command packets.
+    fn start(master: &mut XcpMaster, command: XcpCommand) -> Result<Vec<Vec<u8>>, XcpError> {
+        let steps = master.steps(&command)?;
+        let packets = steps.iter().map(|step| step.packet.clone()).collect();
+        master.job = Some(Job {
+            command,
+            steps,
+            data: Vec::new(),
+        });
+
+        Ok(packets)
+    }
+
+    /// Answers each command packet of the command in progress with a
+    /// response, returning the command result.
+    fn answer(master: &mut XcpMaster, responses: &[&[u8]]) -> Result<Vec<u8>, XcpError> {
+        let (last, first) = responses.split_last().unwrap();
+        for response in first {
+            assert_eq!(master.apply_response(response), None);
+        }
+        let result = master.apply_response(last).unwrap();
+        master.job = None;
+
+        result
+    }
+
+    fn connect(master: &mut XcpMaster, comm_mode: u8, max_cto: u8, max_dto: [u8; 2]) {
+        let packets = start(master, XcpCommand::Connect).unwrap();
+        assert_eq!(packets, [[CMD_CONNECT, 0]]);
+        let response = [
+            PID_RESPONSE,
+            0x15,
+            comm_mode,
+            max_cto,
+            max_dto[0],
+            max_dto[1],
+            0x01,
+            0x01,
+        ];
+        assert_eq!(answer(master, &[&response]), Ok(response[1..].to_vec()));
+    }
+
+    #[test]
+    fn connection() {
+        let mut master = master(Vec::new(), Vec::new());
+        assert_eq!(
+            master.steps(&XcpCommand::Poll).unwrap_err(),
+            XcpError::NotConnected
+        );
+
+        connect(&mut master, 0x00, 8, [0x08, 0x00]);
+        assert!(master.is_connected);
+        assert!(!master.is_big_endian);
+        assert_eq!((master.max_cto, master.max_dto), (8, 8));
+
+        let packets = start(&mut master, XcpCommand::Disconnect).unwrap();
+        assert_eq!(packets, [[CMD_DISCONNECT]]);
+        assert_eq!(answer(&mut master, &[&[PID_RESPONSE]]), Ok(Vec::new()));
+        assert!(!master.is_connected);
+
+        // The byte order and packet sizes are given by the slave.
+        connect(&mut master, 0x01, 16, [0x00, 0x20]);
+        assert!(master.is_big_endian);
+        assert_eq!((master.max_cto, master.max_dto), (16, 32));
+        assert_eq!(master.dword(0x1234_5678), [0x12, 0x34, 0x56, 0x78]);
+        assert_eq!(master.word(0x1234), [0x12, 0x34]);
+
+        // Short responses are rejected.
+        start(&mut master, XcpCommand::Connect).unwrap();
+        assert_eq!(
+            master.apply_response(&[PID_RESPONSE, 0x15, 0x00]),
+            Some(Err(XcpError::Error(ERR_OUT_OF_RANGE)))
+        );
+    }
+
+    #[test]
+    fn upload() {
+        let mut master = master(Vec::new(), Vec::new());
+        connect(&mut master, 0x00, 8, [0x08, 0x00]);
+
+        let command = XcpCommand::Upload {
+            address: 0x2000_0010,
+            extension: 1,
+            size: 2,
+        };
+        let packets = start(&mut master, command).unwrap();
+        assert_eq!(
+            packets,
+            [[CMD_SHORT_UPLOAD, 2, 0, 1, 0x10, 0x00, 0x00, 0x20]]
+        );
+        assert_eq!(
+            answer(&mut master, &[&[PID_RESPONSE, 0xAA, 0xBB]]),
+            Ok(vec![0xAA, 0xBB])
+        );
+
+        // Blocks longer than a response are read with UPLOAD.
+        let command = XcpCommand::Upload {
+            address: 0x2000_0010,
+            extension: 0,
+            size: 10,
+        };
+        let packets = start(&mut master, command).unwrap();
+        assert_eq!(
+            packets,
+            [
+                vec![CMD_SET_MTA, 0, 0, 0, 0x10, 0x00, 0x00, 0x20],
+                vec![CMD_UPLOAD, 7],
+                vec![CMD_UPLOAD, 3],
+            ]
+        );
+        let result = answer(
+            &mut master,
+            &[
+                &[PID_RESPONSE],
+                &[PID_RESPONSE, 0, 1, 2, 3, 4, 5, 6],
+                &[PID_RESPONSE, 7, 8, 9],
+            ],
+        );
+        assert_eq!(result, Ok((0..10).collect()));
+
+        // Truncated responses are rejected.
+        start(
+            &mut master,
+            XcpCommand::Upload {
+                address: 0,
+                extension: 0,
+                size: 4,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            master.apply_response(&[PID_RESPONSE, 0, 1]),
+            Some(Err(XcpError::Error(ERR_OUT_OF_RANGE)))
+        );
+    }
+
+    #[test]
+    fn download() {
+        let mut master = master(Vec::new(), Vec::new());
+        connect(&mut master, 0x00, 8, [0x08, 0x00]);
+
+        let command = XcpCommand::Download {
+            address: 0x2000_0100,
+            extension: 0,
+            data: (0..10).collect(),
+        };
+        let packets = start(&mut master, command).unwrap();
+        assert_eq!(
+            packets,
+            [
+                vec![CMD_SET_MTA, 0, 0, 0, 0x00, 0x01, 0x00, 0x20],
+                vec![CMD_DOWNLOAD, 6, 0, 1, 2, 3, 4, 5],
+                vec![CMD_DOWNLOAD, 4, 6, 7, 8, 9],
+            ]
+        );
+    }
+
+    #[test]
+    fn poll() {
+        let mut master = master(
+            vec![
+                measurement("engine_speed", 0x2000_0010, 2),
+                measurement("temperature", 0x2000_0020, 1),
+            ],
+            Vec::new(),
+        );
+        connect(&mut master, 0x00, 8, [0x08, 0x00]);
+
+        let packets = start(&mut master, XcpCommand::Poll).unwrap();
+        assert_eq!(packets.len(), 2);
+        let data = answer(
+            &mut master,
+            &[&[PID_RESPONSE, 0x34, 0x12], &[PID_RESPONSE, 0x56]],
+        )
+        .unwrap();
+        assert_eq!(
+            master.polled_samples(&data),
+            [
+                XcpSample {
+                    name: "engine_speed".into(),
+                    data: vec![0x34, 0x12],
+                },
+                XcpSample {
+                    name: "temperature".into(),
+                    data: vec![0x56],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn daq() {
+        let mut master = master(
+            Vec::new(),
+            vec![XcpDaqListConfig {
+                event: 1,
+                prescaler: 1,
+                priority: 0,
+                measurements: vec![
+                    measurement("a", 0x100, 4),
+                    measurement("b", 0x200, 4),
+                    measurement("c", 0x300, 2),
+                ],
+            }],
+        );
+        connect(&mut master, 0x00, 8, [0x08, 0x00]);
+
+        // Each ODT holds up to 7 bytes after the packet identifier.
+        let packets = start(&mut master, XcpCommand::StartDaq).unwrap();
+        assert_eq!(
+            packets,
+            [
+                vec![CMD_FREE_DAQ],
+                vec![CMD_ALLOC_DAQ, 0, 1, 0],
+                vec![CMD_ALLOC_ODT, 0, 0, 0, 2],
+                vec![CMD_ALLOC_ODT_ENTRY, 0, 0, 0, 0, 1],
+                vec![CMD_ALLOC_ODT_ENTRY, 0, 0, 0, 1, 2],
+                vec![CMD_SET_DAQ_PTR, 0, 0, 0, 0, 0],
+                vec![CMD_WRITE_DAQ, 0xFF, 4, 0, 0x00, 0x01, 0, 0],
+                vec![CMD_SET_DAQ_PTR, 0, 0, 0, 1, 0],
+                vec![CMD_WRITE_DAQ, 0xFF, 4, 0, 0x00, 0x02, 0, 0],
+                vec![CMD_WRITE_DAQ, 0xFF, 2, 0, 0x00, 0x03, 0, 0],
+                vec![CMD_SET_DAQ_LIST_MODE, 0, 0, 0, 1, 0, 1, 0],
+                vec![CMD_START_STOP_DAQ_LIST, 2, 0, 0],
+                vec![CMD_START_STOP_SYNCH, 1],
+            ]
+        );
+
+        let mut responses: Vec<&[u8]> = vec![&[PID_RESPONSE]; packets.len()];
+        responses[11] = &[PID_RESPONSE, 0x10];
+        assert_eq!(answer(&mut master, &responses), Ok(Vec::new()));
+        assert_eq!(master.daq_lists[0].first_pid, Some(0x10));
+
+        let sample = |name: &str, data: &[u8]| XcpSample {
+            name: name.into(),
+            data: data.to_vec(),
+        };
+        assert_eq!(
+            master.decode_daq_packet(0x10, &[1, 2, 3, 4]),
+            [sample("a", &[1, 2, 3, 4])]
+        );
+        assert_eq!(
+            master.decode_daq_packet(0x11, &[1, 2, 3, 4, 5, 6]),
+            [sample("b", &[1, 2, 3, 4]), sample("c", &[5, 6])]
+        );
+
+        // Short packets and unknown packet identifiers are ignored.
+        assert!(master.decode_daq_packet(0x11, &[1, 2, 3, 4, 5]).is_empty());
+        assert!(master.decode_daq_packet(0x12, &[0; 7]).is_empty());
+        assert!(master.decode_daq_packet(0x0F, &[0; 7]).is_empty());
+    }
+
+    #[test]
+    fn daq_layout() {
+        let daq_list = |measurements| XcpDaqListConfig {
+            event: 0,
+            prescaler: 1,
+            priority: 0,
+            measurements,
+        };
+
+        let mut master = master(Vec::new(), vec![daq_list(Vec::new())]);
+        connect(&mut master, 0x00, 8, [0x08, 0x00]);
+        assert_eq!(
+            master.steps(&XcpCommand::StartDaq).unwrap_err(),
+            XcpError::DaqLayout
+        );
+
+        // A measurement larger than an ODT does not fit.
+        master.config.daq_lists = vec![daq_list(vec![measurement("a", 0, 8)])];
+        assert_eq!(
+            master.steps(&XcpCommand::StartDaq).unwrap_err(),
+            XcpError::DaqLayout
+        );
+
+        // Larger DAQ packets were negotiated.
+        connect(&mut master, 0x00, 8, [0x10, 0x00]);
+        assert!(master.steps(&XcpCommand::StartDaq).is_ok());
+    }
+}
@@ -0,0 +1,942 @@
+//! Traffic capture recording and replay.
+//!
+//! This module defines a capture file format shared by all port models, and
+//! models and utilities to record the traffic of a bench run and replay it
+//! later, e.g. to re-run a hardware-in-the-loop session deterministically
+//! without the hardware.
+//!
+//! A capture file holds records of the data exchanged on named ports. Each
+//! record is tagged with its port and direction, and stamped with the
+//! simulation time, the wall-clock time, or both:
+//!
+//! * the [`CaptureRecorder`] model records the data sent to its inputs,
+//!   stamped with the simulation time and the wall-clock time of the event,
+//! * the [`CaptureSink`] records the traffic of any I/O port wrapped in a
+//!   [`Tapped`](nexosim_io_utils::tap::Tapped) port, stamped with the
+//!   wall-clock time at which the I/O thread read or wrote it,
+//! * the [`CaptureReplayer`] model injects the recorded data back into a
+//!   simulation, at the recorded simulation times or with the recorded
+//!   wall-clock time offsets,
+//! * the [`CaptureReader`] reads the records of a capture file, e.g. for
+//!   offline analysis.
+//!
+//! #### Format
+//!
+//! A capture file starts with the 8-byte header `NXCAP`, 1, 0, 0 (format
+//! version 1) followed by blocks, all integers being little-endian:
+//!
+//! * a port declaration block contains the block type 1, the port index
+//!   (`u16`), the length (`u16`) and the UTF-8 bytes of the port name,
+//! * a record block contains the block type 2, the port index (`u16`), the
+//!   direction (`u8`, 0 for received and 1 for transmitted data), the
+//!   timestamp flags (`u8`, bit 0 for the simulation time and bit 1 for the
+//!   wall-clock time), the simulation time as seconds (`i64`) and
+//!   nanoseconds (`u32`), the wall-clock time as nanoseconds since the Unix
+//!   epoch (`u64`), the data length (`u32`) and the data bytes.
+//!
+//! Timestamps without their flag are written as zeros. Ports are declared
+//! before their first record.
+//!
+//! #### Examples
+//!
+//! ```
+//! use std::time::SystemTime;
+//!
+//! use nexosim::time::MonotonicTime;
+//! use nexosim_file_port::capture::{CaptureDirection, CaptureReader, CaptureWriter};
+//!
+//! let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+//! let port = writer.add_port("can0").unwrap();
+//! writer
+//!     .write_record(
+//!         port,
+//!         CaptureDirection::Rx,
+//!         Some(MonotonicTime::EPOCH),
+//!         Some(SystemTime::now()),
+//!         &[1, 2, 3],
+//!     )
+//!     .unwrap();
+//! let capture = writer.into_inner();
+//!
+//! let mut reader = CaptureReader::new(capture.as_slice()).unwrap();
+//! let record = reader.next_record().unwrap().unwrap();
+//! assert_eq!(reader.port_name(record.port), Some("can0"));
+//! assert_eq!(record.direction, CaptureDirection::Rx);
+//! assert_eq!(record.simulation_time, Some(MonotonicTime::EPOCH));
+//! assert_eq!(&record.data[..], &[1, 2, 3]);
+//! assert!(reader.next_record().unwrap().is_none());
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use nexosim::model::{Context, InitializedModel, Model};
+use nexosim::ports::Output;
+use nexosim::simulation::ActionKey;
+use nexosim::time::MonotonicTime;
+
+use nexosim_io_utils::tap::TapSink;
+use nexosim_io_utils::teardown::TrackedWriter;
+
+/// Capture file header.
+const HEADER: [u8; 8] = *b"NXCAP\x01\x00\x00";
+
+/// Port declaration block type.
+const PORT_BLOCK: u8 = 1;
+
+/// Record block type.
+const RECORD_BLOCK: u8 = 2;
+
+/// Simulation time flag.
+const SIMULATION_TIME: u8 = 0x01;
+
+/// Wall-clock time flag.
+const WALL_TIME: u8 = 0x02;
+
+/// Size of the fixed part of a record block, after the block type.
+const RECORD_HEADER_LEN: usize = 28;
+
+/// Largest accepted record data.
+const MAX_DATA_LEN: usize = 1 << 28;
+
+/// Direction of captured data, from the point of view of the port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CaptureDirection {
+    /// Data received by the port, i.e. forwarded into the simulation.
+    Rx,
+
+    /// Data transmitted by the port, i.e. sent from the simulation.
+    Tx,
+}
+
+/// Data sent to or replayed from a capture port.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturePacket {
+    /// Index of the capture port.
+    pub port: u16,
+
+    /// Direction of the data.
+    pub direction: CaptureDirection,
+
+    /// Data.
+    pub data: Bytes,
+}
+
+impl CapturePacket {
+    /// Creates a packet received by the specified capture port.
+    pub fn rx(port: u16, data: impl Into<Bytes>) -> Self {
+        Self {
+            port,
+            direction: CaptureDirection::Rx,
+            data: data.into(),
+        }
+    }
+
+    /// Creates a packet transmitted by the specified capture port.
+    pub fn tx(port: u16, data: impl Into<Bytes>) -> Self {
+        Self {
+            port,
+            direction: CaptureDirection::Tx,
+            data: data.into(),
+        }
+    }
+}
+
+/// Record read from a capture file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaptureRecord {
+    /// Index of the capture port.
+    pub port: u16,
+
+    /// Direction of the data.
+    pub direction: CaptureDirection,
+
+    /// Simulation time of the record, if recorded.
+    pub simulation_time: Option<MonotonicTime>,
+
+    /// Wall-clock time of the record, if recorded.
+    pub wall_time: Option<SystemTime>,
+
+    /// Data.
+    pub data: Bytes,
+}
+
+/// Data which can be recorded in a capture file.
+///
+/// Data types of port models outside this crate can implement this trait to
+/// be recorded by a [`CaptureSink`].
+pub trait CaptureData: Sized {
+    /// Encodes the data as recorded bytes.
+    fn encode(&self) -> Bytes;
+
+    /// Decodes recorded bytes, or returns `None` if they are invalid.
+    fn decode(data: Bytes) -> Option<Self>;
+}
+
+impl CaptureData for Bytes {
+    fn encode(&self) -> Bytes {
+        self.clone()
+    }
+
+    fn decode(data: Bytes) -> Option<Self> {
+        Some(data)
+    }
+}
+
+impl CaptureData for Vec<u8> {
+    fn encode(&self) -> Bytes {
+        Bytes::copy_from_slice(self)
+    }
+
+    fn decode(data: Bytes) -> Option<Self> {
+        Some(data.into())
+    }
+}
+
+impl CaptureData for String {
+    fn encode(&self) -> Bytes {
+        Bytes::copy_from_slice(self.as_bytes())
+    }
+
+    fn decode(data: Bytes) -> Option<Self> {
+        String::from_utf8(data.into()).ok()
+    }
+}
+
+/// Capture file writer.
+pub struct CaptureWriter<W: Write> {
+    /// Wrapped writer.
+    writer: W,
+
+    /// Number of declared ports.
+    port_count: u16,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Creates a capture writer and writes the capture file header.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&HEADER)?;
+
+        Ok(Self {
+            writer,
+            port_count: 0,
+        })
+    }
+
+    /// Declares a port and returns its index.
+    ///
+    /// Ports are indexed in declaration order, starting from 0.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the name is longer than 65535 bytes, if all
+    /// port indices are used or if the declaration cannot be written.
+    pub fn add_port(&mut self, name: &str) -> io::Result<u16> {
+        let len = u16::try_from(name.len())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "port name too long"))?;
+        let port = self.port_count;
+        self.port_count = port
+            .checked_add(1)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "too many ports"))?;
+        let mut block = Vec::with_capacity(5 + name.len());
+        block.push(PORT_BLOCK);
+        block.extend_from_slice(&port.to_le_bytes());
+        block.extend_from_slice(&len.to_le_bytes());
+        block.extend_from_slice(name.as_bytes());
+        self.writer.write_all(&block)?;
+
+        Ok(port)
+    }
+
+    /// Writes a record.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the port is not declared, if the data is
+    /// larger than 256 MiB or if the record cannot be written.
+    pub fn write_record(
+        &mut self,
+        port: u16,
+        direction: CaptureDirection,
+        simulation_time: Option<MonotonicTime>,
+        wall_time: Option<SystemTime>,
+        data: &[u8],
+    ) -> io::Result<()> {
+        if port >= self.port_count {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("undeclared capture port {port}"),
+            ));
+        }
+        if data.len() > MAX_DATA_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "record data too large",
+            ));
+        }
+        let mut flags = 0;
+        let (secs, nanos) = match simulation_time {
+            Some(time) => {
+                flags |= SIMULATION_TIME;
+                (time.as_secs(), time.subsec_nanos())
+            }
+            None => (0, 0),
+        };
+        let wall_nanos = match wall_time {
+            Some(time) => {
+                flags |= WALL_TIME;
+                let time = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+                u64::try_from(time.as_nanos()).unwrap_or(u64::MAX)
+            }
+            None => 0,
+        };
+        let mut block = Vec::with_capacity(1 + RECORD_HEADER_LEN + data.len());
+        block.push(RECORD_BLOCK);
+        block.extend_from_slice(&port.to_le_bytes());
+        block.push(match direction {
+            CaptureDirection::Rx => 0,
+            CaptureDirection::Tx => 1,
+        });
+        block.push(flags);
+        block.extend_from_slice(&secs.to_le_bytes());
+        block.extend_from_slice(&nanos.to_le_bytes());
+        block.extend_from_slice(&wall_nanos.to_le_bytes());
+        block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        block.extend_from_slice(data);
+
+        self.writer.write_all(&block)
+    }
+
+    /// Flushes the wrapped writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Returns a reference to the wrapped writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> fmt::Debug for CaptureWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CaptureWriter")
+            .field("port_count", &self.port_count)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Capture file reader.
+pub struct CaptureReader<R: Read> {
+    /// Wrapped reader.
+    reader: R,
+
+    /// Names of the declared ports, by index.
+    ports: HashMap<u16, String>,
+}
+
+impl CaptureReader<BufReader<File>> {
+    /// Opens a capture file.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Creates a capture reader and reads the capture file header.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; HEADER.len()];
+        reader.read_exact(&mut header).map_err(|e| {
+            if e.kind() == ErrorKind::UnexpectedEof {
+                invalid_data("not a capture file")
+            } else {
+                e
+            }
+        })?;
+        if header[..5] != HEADER[..5] {
+            return Err(invalid_data("not a capture file"));
+        }
+        if header[5..] != HEADER[5..] {
+            return Err(invalid_data("unsupported capture file version"));
+        }
+
+        Ok(Self {
+            reader,
+            ports: HashMap::new(),
+        })
+    }
+
+    /// Returns the name of a port declared so far.
+    pub fn port_name(&self, port: u16) -> Option<&str> {
+        self.ports.get(&port).map(String::as_str)
+    }
+
+    /// Reads the next record, or returns `None` at the end of the file.
+    pub fn next_record(&mut self) -> io::Result<Option<CaptureRecord>> {
+        loop {
+            let mut block_type = [0];
+            if !self.read_or_eof(&mut block_type)? {
+                return Ok(None);
+            }
+            match block_type[0] {
+                PORT_BLOCK => {
+                    let mut header = [0; 4];
+                    self.read_all(&mut header)?;
+                    let port = u16::from_le_bytes([header[0], header[1]]);
+                    let mut name = vec![0; usize::from(u16::from_le_bytes([header[2], header[3]]))];
+                    self.read_all(&mut name)?;
+                    let name =
+                        String::from_utf8(name).map_err(|_| invalid_data("invalid port name"))?;
+                    self.ports.insert(port, name);
+                }
+                RECORD_BLOCK => {
+                    let mut header = [0; RECORD_HEADER_LEN];
+                    self.read_all(&mut header)?;
+                    let port = u16::from_le_bytes(header[0..2].try_into().unwrap());
+                    let direction = match header[2] {
+                        0 => CaptureDirection::Rx,
+                        1 => CaptureDirection::Tx,
+                        _ => return Err(invalid_data("invalid record direction")),
+                    };
+                    let flags = header[3];
+                    let secs = i64::from_le_bytes(header[4..12].try_into().unwrap());
+                    let nanos = u32::from_le_bytes(header[12..16].try_into().unwrap());
+                    let wall_nanos = u64::from_le_bytes(header[16..24].try_into().unwrap());
+                    let len = u32::from_le_bytes(header[24..28].try_into().unwrap()) as usize;
+                    if len > MAX_DATA_LEN {
+                        return Err(invalid_data("record data too large"));
+                    }
+                    let mut data = vec![0; len];
+                    self.read_all(&mut data)?;
+                    let simulation_time = if flags & SIMULATION_TIME != 0 {
+                        Some(
+                            MonotonicTime::new(secs, nanos)
+                                .ok_or_else(|| invalid_data("invalid simulation time"))?,
+                        )
+                    } else {
+                        None
+                    };
+                    let wall_time = (flags & WALL_TIME != 0)
+                        .then(|| UNIX_EPOCH + Duration::from_nanos(wall_nanos));
+
+                    return Ok(Some(CaptureRecord {
+                        port,
+                        direction,
+                        simulation_time,
+                        wall_time,
+                        data: data.into(),
+                    }));
+                }
+                _ => return Err(invalid_data("invalid block type")),
+            }
+        }
+    }
+
+    /// Fills the buffer, or returns `false` if the end of the file is reached
+    /// before the first byte.
+    fn read_or_eof(&mut self, buf: &mut [u8]) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => return Err(truncated()),
+                Ok(len) => filled += len,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Fills the buffer.
+    fn read_all(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        if buf.is_empty() || self.read_or_eof(buf)? {
+            Ok(())
+        } else {
+            Err(truncated())
+        }
+    }
+}
+
+impl<R: Read> fmt::Debug for CaptureReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CaptureReader")
+            .field("ports", &self.ports)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns an invalid data error.
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Returns a truncated file error.
+fn truncated() -> io::Error {
+    io::Error::new(ErrorKind::UnexpectedEof, "truncated capture file")
+}
+
+/// Capture writer shared by several sinks.
+pub type SharedCaptureWriter<W> = Arc<Mutex<CaptureWriter<W>>>;
+
+/// Tap sink recording the traffic of an I/O port to a capture file.
+///
+/// The records are stamped with the wall-clock time only, since the I/O
+/// thread has no access to the simulation time. Several sinks can share the
+/// same capture file, e.g. to record the traffic of several ports in a single
+/// capture:
+///
+/// ```no_run
+/// use std::sync::{Arc, Mutex};
+///
+/// use nexosim_file_port::capture::{CaptureSink, CaptureWriter};
+/// use nexosim_io_utils::teardown::TrackedWriter;
+///
+/// let writer = Arc::new(Mutex::new(
+///     CaptureWriter::new(TrackedWriter::create("bench.nxcap").unwrap()).unwrap(),
+/// ));
+/// let can0_sink = CaptureSink::new(&writer, "can0").unwrap();
+/// let uart0_sink = CaptureSink::new(&writer, "uart0").unwrap();
+///
+/// // Wrap the I/O ports in `Tapped` ports recording to the sinks.
+/// # let _ = (can0_sink, uart0_sink);
+/// ```
+pub struct CaptureSink<W: Write> {
+    /// Capture writer.
+    writer: SharedCaptureWriter<W>,
+
+    /// Index of the capture port.
+    port: u16,
+}
+
+impl<W: Write> CaptureSink<W> {
+    /// Creates a sink recording to a new port of the capture writer.
+    pub fn new(writer: &SharedCaptureWriter<W>, name: &str) -> io::Result<Self> {
+        let port = writer.lock().unwrap().add_port(name)?;
+
+        Ok(Self {
+            writer: writer.clone(),
+            port,
+        })
+    }
+
+    /// Returns the index of the capture port.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Writes a record.
+    fn record(&self, direction: CaptureDirection, time: SystemTime, data: &[u8]) -> io::Result<()> {
+        self.writer
+            .lock()
+            .unwrap()
+            .write_record(self.port, direction, None, Some(time), data)
+    }
+}
+
+impl<W, R, T> TapSink<R, T> for CaptureSink<W>
+where
+    W: Write + Send,
+    R: CaptureData,
+    T: CaptureData,
+{
+    fn received(&mut self, time: SystemTime, data: &R) -> io::Result<()> {
+        self.record(CaptureDirection::Rx, time, &data.encode())
+    }
+
+    fn transmitted(&mut self, time: SystemTime, data: &T) -> io::Result<()> {
+        self.record(CaptureDirection::Tx, time, &data.encode())
+    }
+}
+
+impl<W: Write> fmt::Debug for CaptureSink<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CaptureSink")
+            .field("port", &self.port)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Model recording simulation traffic to a capture file.
+///
+/// Records are stamped with both the simulation time and the wall-clock time
+/// at which they are received, on capture ports declared with
+/// [`with_port`](Self::with_port). If no port is declared, records are written
+/// on a single unnamed port.
+///
+/// The capture ports are declared when the model is initialized.
+pub struct CaptureRecorder {
+    /// Capture file.
+    writer: CaptureWriter<TrackedWriter<BufWriter<File>>>,
+
+    /// Names of the capture ports.
+    ports: Vec<String>,
+}
+
+impl CaptureRecorder {
+    /// Creates a new recorder writing to the provided file.
+    ///
+    /// The file is truncated if it already exists.
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            writer: CaptureWriter::new(TrackedWriter::create(path)?)?,
+            ports: Vec::new(),
+        })
+    }
+
+    /// Declares a capture port with the specified name.
+    ///
+    /// Ports are indexed in declaration order, starting from 0.
+    pub fn with_port(mut self, name: &str) -> Self {
+        self.ports.push(name.to_string());
+        self
+    }
+
+    /// Returns the path of the capture file.
+    pub fn path(&self) -> &Path {
+        self.writer.get_ref().path()
+    }
+
+    /// Data received by port 0 -- input port.
+    pub fn rx_in(&mut self, data: Bytes, cx: &mut Context<Self>) {
+        self.record(CapturePacket::rx(0, data), cx.time());
+    }
+
+    /// Data transmitted by port 0 -- input port.
+    pub fn tx_in(&mut self, data: Bytes, cx: &mut Context<Self>) {
+        self.record(CapturePacket::tx(0, data), cx.time());
+    }
+
+    /// Data of the specified port and direction -- input port.
+    pub fn packet_in(&mut self, packet: CapturePacket, cx: &mut Context<Self>) {
+        self.record(packet, cx.time());
+    }
+
+    /// Flushes the capture file -- input port.
+    pub fn flush(&mut self) {
+        if let Err(_e) = self.writer.flush() {
+            #[cfg(feature = "tracing")]
+            warn!("Failed to flush {}: {}.", self.path().display(), _e);
+        }
+    }
+
+    /// Writes a record.
+    fn record(&mut self, packet: CapturePacket, time: MonotonicTime) {
+        let result = self.writer.write_record(
+            packet.port,
+            packet.direction,
+            Some(time),
+            Some(SystemTime::now()),
+            &packet.data,
+        );
+        if let Err(_e) = result {
+            #[cfg(feature = "tracing")]
+            warn!("Failed to record to {}: {}.", self.path().display(), _e);
+        }
+    }
+}
+
+impl Model for CaptureRecorder {
+    async fn init(mut self, _: &mut Context<Self>) -> InitializedModel<Self> {
+        if self.ports.is_empty() {
+            self.ports.push(String::new());
+        }
+        for name in &self.ports {
+            if let Err(_e) = self.writer.add_port(name) {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    "Failed to declare port {} in {}: {}.",
+                    name,
+                    self.writer.get_ref().path().display(),
+                    _e
+                );
+            }
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for CaptureRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CaptureRecorder")
+            .field("path", &self.path())
+            .field("ports", &self.ports)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Clock driving a replay.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReplayClock {
+    /// Records are replayed at their recorded simulation time.
+    ///
+    /// Records without simulation time are skipped.
+    #[default]
+    Simulation,
+
+    /// Records are replayed with their wall-clock time offset relative to the
+    /// first replayed record.
+    ///
+    /// Records without wall-clock time are skipped.
+    Wall,
+}
+
+/// Model replaying a capture file.
+///
+/// The replay starts when the [`start`](Self::start) input is triggered:
+/// * with the [`ReplayClock::Simulation`] clock, each record is emitted at its
+///   recorded simulation time, or immediately if this time is already past,
+///   so that a simulation started at the recorded initial time receives the
+///   recorded data at the same simulation times,
+/// * with the [`ReplayClock::Wall`] clock, the first record is emitted
+///   immediately and the following records with their wall-clock time offset
+///   relative to the first record, multiplied by the time scale.
+///
+/// Records with a time earlier than the one of their predecessor are emitted
+/// immediately. By default, all records are replayed; they can be selected by
+/// port name with [`with_port`](Self::with_port) and by direction with
+/// [`with_direction`](Self::with_direction).
+pub struct CaptureReplayer {
+    /// Replayed records -- output port.
+    pub packet_out: Output<CapturePacket>,
+
+    /// Data of the replayed records -- output port.
+    pub data_out: Output<Bytes>,
+
+    /// Path of the capture file.
+    path: PathBuf,
+
+    /// Capture reader, `None` once the capture is exhausted.
+    reader: Option<CaptureReader<BufReader<File>>>,
+
+    /// The capture reader has been used.
+    started: bool,
+
+    /// Replay clock.
+    clock: ReplayClock,
+
+    /// Time scale applied to the wall-clock time offsets.
+    time_scale: f64,
+
+    /// Names of the replayed ports, if not all.
+    ports: Option<Vec<String>>,
+
+    /// Replayed direction, if not both.
+    direction: Option<CaptureDirection>,
+
+    /// Replay start time and wall-clock time of the first record.
+    origin: Option<(MonotonicTime, SystemTime)>,
+
+    /// Key of the next scheduled record.
+    next_key: Option<ActionKey>,
+}
+
+impl CaptureReplayer {
+    /// Creates a new replayer reading the provided capture file.
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let reader = CaptureReader::open(&path)?;
+
+        Ok(Self {
+            packet_out: Output::default(),
+            data_out: Output::default(),
+            path,
+            reader: Some(reader),
+            started: false,
+            clock: ReplayClock::default(),
+            time_scale: 1.0,
+            ports: None,
+            direction: None,
+            origin: None,
+            next_key: None,
+        })
+    }
+
+    /// Sets the clock driving the replay.
+    pub fn with_clock(mut self, clock: ReplayClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets the time scale applied to the wall-clock time offsets of the
+    /// [`ReplayClock::Wall`] clock.
+    ///
+    /// A scale greater than 1 slows the replay down, a scale smaller than 1
+    /// speeds it up and a zero scale emits all records at once.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the time scale is negative or not finite.
+    pub fn with_time_scale(mut self, time_scale: f64) -> Self {
+        assert!(
+            time_scale.is_finite() && time_scale >= 0.0,
+            "the time scale should be finite and non-negative"
+        );
+        self.time_scale = time_scale;
+        self
+    }
+
+    /// Replays the records of the specified port, by name.
+    ///
+    /// This method can be called several times to replay several ports.
+    pub fn with_port(mut self, name: &str) -> Self {
+        self.ports
+            .get_or_insert_with(Vec::new)
+            .push(name.to_string());
+        self
+    }
+
+    /// Only replays the records of the specified direction.
+    pub fn with_direction(mut self, direction: CaptureDirection) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    /// Returns the path of the capture file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Starts the replay -- input port.
+    ///
+    /// If a replay is in progress, it is restarted from the beginning of the
+    /// capture.
+    pub async fn start(&mut self, _: (), cx: &mut Context<Self>) {
+        self.stop();
+        if self.started {
+            self.reader = CaptureReader::open(&self.path)
+                .inspect_err(|_e| {
+                    #[cfg(feature = "tracing")]
+                    warn!("Failed to reopen {}: {}.", self.path.display(), _e);
+                })
+                .ok();
+        }
+        self.started = true;
+        self.origin = None;
+
+        self.replay(cx).await;
+    }
+
+    /// Stops the replay -- input port.
+    pub fn stop(&mut self) {
+        if let Some(key) = self.next_key.take() {
+            key.cancel();
+        }
+    }
+
+    /// Emits a scheduled record and continues the replay.
+    async fn emit(&mut self, packet: CapturePacket, cx: &mut Context<Self>) {
+        self.next_key = None;
+        self.send(packet).await;
+        self.replay(cx).await;
+    }
+
+    /// Emits the records which are due and schedules the next one.
+    async fn replay(&mut self, cx: &mut Context<Self>) {
+        while let Some(record) = self.next_record() {
+            let deadline = match self.clock {
+                ReplayClock::Simulation => record.simulation_time.unwrap(),
+                ReplayClock::Wall => {
+                    let wall_time = record.wall_time.unwrap();
+                    let (start, t0) = *self.origin.get_or_insert((cx.time(), wall_time));
+                    let offset = wall_time.duration_since(t0).unwrap_or_default();
+
+                    start + offset.mul_f64(self.time_scale)
+                }
+            };
+            let packet = CapturePacket {
+                port: record.port,
+                direction: record.direction,
+                data: record.data,
+            };
+            if deadline > cx.time() {
+                self.schedule(deadline, packet, cx);
+                return;
+            }
+            self.send(packet).await;
+        }
+    }
+
+    /// Sends a record to the outputs.
+    async fn send(&mut self, packet: CapturePacket) {
+        self.data_out.send(packet.data.clone()).await;
+        self.packet_out.send(packet).await;
+    }
+
+    /// Schedules the emission of a record.
+    fn schedule(&mut self, deadline: MonotonicTime, packet: CapturePacket, cx: &mut Context<Self>) {
+        self.next_key = Some(
+            cx.schedule_keyed_event(deadline, Self::emit, packet)
+                .unwrap(),
+        );
+    }
+
+    /// Reads the next replayable record from the capture.
+    fn next_record(&mut self) -> Option<CaptureRecord> {
+        loop {
+            let reader = self.reader.as_mut()?;
+            let record = match reader.next_record() {
+                Ok(Some(record)) => record,
+                Ok(None) => {
+                    self.reader = None;
+                    return None;
+                }
+                Err(_e) => {
+                    #[cfg(feature = "tracing")]
+                    warn!("Failed to read {}: {}.", self.path.display(), _e);
+                    self.reader = None;
+                    return None;
+                }
+            };
+            if let Some(ports) = &self.ports {
+                let name = reader.port_name(record.port);
+                if !ports.iter().any(|port| Some(port.as_str()) == name) {
+                    continue;
+                }
+            }
+            if self
+                .direction
+                .is_some_and(|direction| direction != record.direction)
+            {
+                continue;
+            }
+            let is_timed = match self.clock {
+                ReplayClock::Simulation => record.simulation_time.is_some(),
+                ReplayClock::Wall => record.wall_time.is_some(),
+            };
+            if !is_timed {
+                continue;
+            }
+
+            return Some(record);
+        }
+    }
+}
+
+impl Model for CaptureReplayer {}
+
+impl fmt::Debug for CaptureReplayer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CaptureReplayer")
+            .field("path", &self.path)
+            .field("clock", &self.clock)
+            .field("time_scale", &self.time_scale)
+            .finish_non_exhaustive()
+    }
+}
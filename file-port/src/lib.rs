@@ -0,0 +1,82 @@
+//! File port models for [NeXosim][NX]-based simulations.
+//!
+//! This crate contains models connecting a simulation to files and named
+//! pipes:
+//!
+//! * [`capture`]: recording of the traffic of port models to capture files,
+//!   and replay at the recorded simulation or wall-clock times.
+//! * [`pcap`]: recording of pcapng capture files and replay of pcap and
+//!   pcapng capture files.
+//! * [`sink`]: file sink writing data from the simulation to a file or to a
+//!   named pipe.
+//! * [`source`]: file source following a growing file, like `tail -F`, or
+//!   reading a named pipe, and injecting its data into the simulation.
+//!
+//! [NX]: https://github.com/asynchronics/nexosim
+#![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
+#![forbid(unsafe_code)]
+
+pub mod capture;
+pub mod pcap;
+pub mod sink;
+pub mod source;
+
+use std::fs::{File, OpenOptions};
+use std::io::Result as IoResult;
+use std::path::Path;
+
+/// Opens a file with the provided options, without blocking on named pipes.
+///
+/// On Unix, named pipes are opened in non-blocking mode and for reading and
+/// writing, so that opening them does not wait for a peer and reading them
+/// does not report an end of file when the peer closes them.
+fn open(path: &Path, options: &mut OpenOptions) -> IoResult<File> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        if std::fs::metadata(path).is_ok_and(|metadata| is_fifo(&metadata)) {
+            return OpenOptions::new()
+                .read(true)
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path);
+        }
+    }
+
+    options.open(path)
+}
+
+/// Returns the identity of a file, if supported by the platform.
+///
+/// Files are identified by their device and inode numbers on Unix.
+fn file_id(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        Some((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+
+        None
+    }
+}
+
+/// Checks whether a file is a named pipe.
+fn is_fifo(metadata: &std::fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        metadata.file_type().is_fifo()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+
+        false
+    }
+}
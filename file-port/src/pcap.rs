@@ -0,0 +1,1013 @@
+//! Packet capture recording and replay.
+//!
+//! This module contains a model writing simulation traffic to
+//! [pcapng](https://ietf-opsawg-wg.github.io/draft-ietf-opsawg-pcap/draft-ietf-opsawg-pcapng.html)
+//! capture files, e.g. to analyze a bench run with Wireshark, and a model
+//! replaying the packets of a
+//! [pcap](https://ietf-opsawg-wg.github.io/draft-ietf-opsawg-pcap/draft-ietf-opsawg-pcap.html)
+//! or pcapng capture file into the simulation, e.g. to use a field capture as
+//! a deterministic stimulus.
+//!
+//! Recorded packets are time-stamped with the simulation time, on capture
+//! interfaces with a configurable link type. CAN frames can be recorded in
+//! the SocketCAN format understood by Wireshark with [`socketcan_packet`].
+//!
+//! Replayed packets are emitted with the time offsets of the capture relative
+//! to its first packet, optionally scaled. Packets can be selected by pcapng
+//! interface and, with the `bpf` feature, by a BPF filter expression in the
+//! syntax of `tcpdump`, compiled with libpcap.
+//!
+//! #### Examples
+//!
+//! ```no_run
+//! use nexosim_file_port::pcap::{LinkType, PcapReplayer, PcapngLogger};
+//!
+//! // Connect the packet outputs of the simulated ports to
+//! // `PcapngLogger::interface_packet_in`, mapping them to `PcapngPacket`s
+//! // with the index of the interface.
+//! let logger = PcapngLogger::new("bench.pcapng")
+//!     .unwrap()
+//!     .with_interface("eth0", LinkType::ETHERNET)
+//!     .with_interface("can0", LinkType::CAN_SOCKETCAN);
+//!
+//! // Replay the UDP payloads of a capture of Ethernet/IPv4 packets without IP
+//! // options at twice the original speed, starting when
+//! // `PcapReplayer::start` is scheduled.
+//! let replayer = PcapReplayer::new("capture.pcapng")
+//!     .unwrap()
+//!     .with_payload_offset(42)
+//!     .with_time_scale(0.5);
+//! ```
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[cfg(feature = "bpf")]
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use nexosim::model::{Context, InitializedModel, Model};
+use nexosim::ports::Output;
+use nexosim::simulation::ActionKey;
+use nexosim::time::MonotonicTime;
+
+use nexosim_io_utils::teardown::TrackedWriter;
+
+/// pcap magic number for microsecond timestamps.
+const PCAP_MAGIC_US: u32 = 0xA1B2_C3D4;
+
+/// pcap magic number for nanosecond timestamps.
+const PCAP_MAGIC_NS: u32 = 0xA1B2_3C4D;
+
+/// pcapng Section Header Block type.
+const SECTION_HEADER: u32 = 0x0A0D_0D0A;
+
+/// pcapng byte-order magic number.
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+/// pcapng Interface Description Block type.
+const INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+
+/// pcapng obsolete Packet Block type.
+const PACKET: u32 = 0x0000_0002;
+
+/// pcapng Simple Packet Block type.
+const SIMPLE_PACKET: u32 = 0x0000_0003;
+
+/// pcapng Enhanced Packet Block type.
+const ENHANCED_PACKET: u32 = 0x0000_0006;
+
+/// pcapng `if_name` option code.
+const IF_NAME: u16 = 2;
+
+/// pcapng `if_tsresol` option code.
+const IF_TSRESOL: u16 = 9;
+
+/// pcapng `if_tsoffset` option code.
+const IF_TSOFFSET: u16 = 14;
+
+/// Largest accepted pcapng block.
+const MAX_BLOCK_LEN: usize = 1 << 28;
+
+/// Captured packet.
+struct CapturedPacket {
+    /// Index of the capture interface, always 0 for pcap files.
+    interface: u32,
+
+    /// Link type of the capture interface.
+    #[cfg_attr(not(feature = "bpf"), allow(dead_code))]
+    link_type: u16,
+
+    /// Capture timestamp, since the Unix epoch.
+    timestamp: Duration,
+
+    /// Captured data.
+    data: Vec<u8>,
+}
+
+/// pcapng capture interface.
+struct Interface {
+    /// Link type.
+    link_type: u16,
+
+    /// Timestamp resolution, as a negative power of 10 or, if the most
+    /// significant bit is set, of 2.
+    resolution: u8,
+
+    /// Offset added to the timestamps, in seconds.
+    offset: i64,
+}
+
+/// Capture file format.
+enum Format {
+    /// pcap file.
+    Pcap {
+        /// The timestamps have a nanosecond resolution.
+        nanos: bool,
+
+        /// Link type.
+        link_type: u16,
+    },
+
+    /// pcapng file.
+    Pcapng {
+        /// Interfaces of the current section.
+        interfaces: Vec<Interface>,
+
+        /// Timestamp of the last packet, for Simple Packet Blocks.
+        last_timestamp: Duration,
+    },
+}
+
+/// pcap and pcapng capture file reader.
+struct CaptureReader {
+    /// Capture file.
+    reader: BufReader<File>,
+
+    /// File format.
+    format: Format,
+
+    /// The current section is big-endian.
+    big_endian: bool,
+}
+
+impl CaptureReader {
+    /// Opens a capture file and reads its header.
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let mut this = Self {
+            reader,
+            format: Format::Pcapng {
+                interfaces: Vec::new(),
+                last_timestamp: Duration::ZERO,
+            },
+            big_endian: false,
+        };
+        if u32::from_le_bytes(magic) == SECTION_HEADER {
+            this.read_section_header()?;
+
+            return Ok(this);
+        }
+        let nanos = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAP_MAGIC_US, _) | (_, PCAP_MAGIC_US) => false,
+            (PCAP_MAGIC_NS, _) | (_, PCAP_MAGIC_NS) => true,
+            _ => return Err(invalid_data("not a pcap or pcapng file")),
+        };
+        this.big_endian = u32::from_be_bytes(magic) == PCAP_MAGIC_US
+            || u32::from_be_bytes(magic) == PCAP_MAGIC_NS;
+        let mut header = [0; 20];
+        this.reader.read_exact(&mut header)?;
+        let link_type = this.u32(&header[16..20]) as u16;
+        this.format = Format::Pcap { nanos, link_type };
+
+        Ok(this)
+    }
+
+    /// Returns the link type of the first interface, reading the capture up
+    /// to its description if needed.
+    #[cfg(feature = "bpf")]
+    fn first_link_type(&mut self) -> io::Result<Option<u16>> {
+        loop {
+            match &self.format {
+                Format::Pcap { link_type, .. } => return Ok(Some(*link_type)),
+                Format::Pcapng { interfaces, .. } => {
+                    if let Some(interface) = interfaces.first() {
+                        return Ok(Some(interface.link_type));
+                    }
+                }
+            }
+            let Some((block_type, body)) = self.read_block()? else {
+                return Ok(None);
+            };
+            match block_type {
+                // Packets cannot precede the description of their interface.
+                PACKET | SIMPLE_PACKET | ENHANCED_PACKET => {
+                    return Err(invalid_data("packet before interface description"));
+                }
+                _ => self.read_non_packet_block(block_type, &body)?,
+            }
+        }
+    }
+
+    /// Reads the next packet, or returns `None` at the end of the file.
+    fn next_packet(&mut self) -> io::Result<Option<CapturedPacket>> {
+        if let Format::Pcap { nanos, link_type } = self.format {
+            let mut header = [0; 16];
+            if !self.read_or_eof(&mut header)? {
+                return Ok(None);
+            }
+            let secs = u64::from(self.u32(&header[0..4]));
+            let frac = self.u32(&header[4..8]);
+            let len = self.u32(&header[8..12]) as usize;
+            if len > MAX_BLOCK_LEN {
+                return Err(invalid_data("packet too large"));
+            }
+            let mut data = vec![0; len];
+            self.reader.read_exact(&mut data)?;
+            let timestamp = Duration::from_secs(secs)
+                + if nanos {
+                    Duration::from_nanos(u64::from(frac))
+                } else {
+                    Duration::from_micros(u64::from(frac))
+                };
+
+            return Ok(Some(CapturedPacket {
+                interface: 0,
+                link_type,
+                timestamp,
+                data,
+            }));
+        }
+        loop {
+            let Some((block_type, body)) = self.read_block()? else {
+                return Ok(None);
+            };
+            let (interface, timestamp, data) = match block_type {
+                ENHANCED_PACKET if body.len() >= 20 => {
+                    let interface = self.u32(&body[0..4]);
+                    let timestamp = self.timestamp(interface, &body[4..12])?;
+                    let len = self.u32(&body[12..16]) as usize;
+                    let data = body.get(20..20 + len).ok_or_else(truncated)?;
+
+                    (interface, timestamp, data)
+                }
+                PACKET if body.len() >= 20 => {
+                    let interface = u32::from(self.u16(&body[0..2]));
+                    let timestamp = self.timestamp(interface, &body[4..12])?;
+                    let len = self.u32(&body[12..16]) as usize;
+                    let data = body.get(20..20 + len).ok_or_else(truncated)?;
+
+                    (interface, timestamp, data)
+                }
+                SIMPLE_PACKET if body.len() >= 4 => {
+                    // The captured length is only bounded by the snapshot
+                    // length of the interface and by the padding of the block.
+                    let len = (self.u32(&body[0..4]) as usize).min(body.len() - 4);
+                    let Format::Pcapng { last_timestamp, .. } = self.format else {
+                        unreachable!()
+                    };
+
+                    (0, last_timestamp, &body[4..4 + len])
+                }
+                PACKET | SIMPLE_PACKET | ENHANCED_PACKET => return Err(truncated()),
+                _ => {
+                    self.read_non_packet_block(block_type, &body)?;
+                    continue;
+                }
+            };
+            let Format::Pcapng {
+                interfaces,
+                last_timestamp,
+            } = &mut self.format
+            else {
+                unreachable!()
+            };
+            let link_type = interfaces
+                .get(interface as usize)
+                .ok_or_else(|| invalid_data("packet of an undescribed interface"))?
+                .link_type;
+            *last_timestamp = timestamp;
+
+            return Ok(Some(CapturedPacket {
+                interface,
+                link_type,
+                timestamp,
+                data: data.to_vec(),
+            }));
+        }
+    }
+
+    /// Handles a pcapng block other than a packet block.
+    fn read_non_packet_block(&mut self, block_type: u32, body: &[u8]) -> io::Result<()> {
+        match block_type {
+            SECTION_HEADER => {}
+            INTERFACE_DESCRIPTION if body.len() >= 8 => {
+                let mut interface = Interface {
+                    link_type: self.u16(&body[0..2]),
+                    resolution: 6,
+                    offset: 0,
+                };
+                let mut options = &body[8..];
+                while options.len() >= 4 {
+                    let code = self.u16(&options[0..2]);
+                    let len = usize::from(self.u16(&options[2..4]));
+                    let Some(value) = options.get(4..4 + len) else {
+                        break;
+                    };
+                    match code {
+                        0 => break,
+                        IF_TSRESOL if len == 1 => interface.resolution = value[0],
+                        IF_TSOFFSET if len == 8 => {
+                            let offset = self.u64(value);
+                            interface.offset = offset as i64;
+                        }
+                        _ => {}
+                    }
+                    options = options.get((4 + len).next_multiple_of(4)..).unwrap_or(&[]);
+                }
+                if let Format::Pcapng { interfaces, .. } = &mut self.format {
+                    interfaces.push(interface);
+                }
+            }
+            INTERFACE_DESCRIPTION => return Err(truncated()),
+            // Other blocks are not relevant for the replay.
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Reads the rest of a Section Header Block after its type, setting the
+    /// byte order and resetting the interfaces.
+    fn read_section_header(&mut self) -> io::Result<()> {
+        let mut header = [0; 8];
+        self.reader.read_exact(&mut header)?;
+        self.big_endian = match u32::from_le_bytes(header[4..8].try_into().unwrap()) {
+            BYTE_ORDER_MAGIC => false,
+            magic if magic.swap_bytes() == BYTE_ORDER_MAGIC => true,
+            _ => return Err(invalid_data("invalid pcapng byte-order magic")),
+        };
+        let len = self.u32(&header[0..4]) as usize;
+        if !(28..=MAX_BLOCK_LEN).contains(&len) || len % 4 != 0 {
+            return Err(invalid_data("invalid pcapng block length"));
+        }
+        // The rest of the header and the options are not needed.
+        io::copy(
+            &mut (&mut self.reader).take(len as u64 - 12),
+            &mut io::sink(),
+        )?;
+        self.format = Format::Pcapng {
+            interfaces: Vec::new(),
+            last_timestamp: Duration::ZERO,
+        };
+
+        Ok(())
+    }
+
+    /// Reads the next pcapng block and returns its type and body, or returns
+    /// `None` at the end of the file.
+    fn read_block(&mut self) -> io::Result<Option<(u32, Vec<u8>)>> {
+        let mut header = [0; 8];
+        if !self.read_or_eof(&mut header[..4])? {
+            return Ok(None);
+        }
+        if u32::from_le_bytes(header[..4].try_into().unwrap()) == SECTION_HEADER {
+            self.read_section_header()?;
+
+            return Ok(Some((SECTION_HEADER, Vec::new())));
+        }
+        self.reader.read_exact(&mut header[4..])?;
+        let block_type = self.u32(&header[0..4]);
+        let len = self.u32(&header[4..8]) as usize;
+        if !(12..=MAX_BLOCK_LEN).contains(&len) || len % 4 != 0 {
+            return Err(invalid_data("invalid pcapng block length"));
+        }
+        let mut body = vec![0; len - 8];
+        self.reader.read_exact(&mut body)?;
+        body.truncate(len - 12);
+
+        Ok(Some((block_type, body)))
+    }
+
+    /// Converts a pcapng timestamp of an interface.
+    fn timestamp(&self, interface: u32, bytes: &[u8]) -> io::Result<Duration> {
+        let Format::Pcapng { interfaces, .. } = &self.format else {
+            unreachable!()
+        };
+        let interface = interfaces
+            .get(interface as usize)
+            .ok_or_else(|| invalid_data("packet of an undescribed interface"))?;
+        let ticks = (u64::from(self.u32(&bytes[0..4])) << 32) | u64::from(self.u32(&bytes[4..8]));
+        let exponent = u32::from(interface.resolution & 0x7F);
+        let nanos = if interface.resolution & 0x80 != 0 {
+            (u128::from(ticks) * 1_000_000_000) >> exponent.min(127)
+        } else if exponent <= 9 {
+            u128::from(ticks) * 10u128.pow(9 - exponent)
+        } else {
+            u128::from(ticks) / 10u128.checked_pow(exponent - 9).unwrap_or(u128::MAX)
+        };
+        let nanos = (nanos as i128 + i128::from(interface.offset) * 1_000_000_000).max(0);
+
+        Ok(Duration::from_nanos(
+            u64::try_from(nanos).unwrap_or(u64::MAX),
+        ))
+    }
+
+    /// Fills the buffer, or returns `false` if the end of the file is reached
+    /// before the first byte.
+    fn read_or_eof(&mut self, buf: &mut [u8]) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => return Err(truncated()),
+                Ok(len) => filled += len,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Decodes a 16-bit integer in the byte order of the file.
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = bytes[..2].try_into().unwrap();
+        if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    }
+
+    /// Decodes a 32-bit integer in the byte order of the file.
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = bytes[..4].try_into().unwrap();
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    /// Decodes a 64-bit integer in the byte order of the file.
+    fn u64(&self, bytes: &[u8]) -> u64 {
+        let bytes = bytes[..8].try_into().unwrap();
+        if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        }
+    }
+}
+
+/// Returns an invalid data error.
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Returns a truncated file error.
+fn truncated() -> io::Error {
+    io::Error::new(ErrorKind::UnexpectedEof, "truncated capture file")
+}
+
+/// Link type of a capture interface.
+///
+/// See the [list of link
+/// types](https://www.tcpdump.org/linktypes.html) for the values not defined
+/// as constants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LinkType(pub u16);
+
+impl LinkType {
+    /// Ethernet frames.
+    pub const ETHERNET: Self = Self(1);
+
+    /// Raw IPv4 or IPv6 packets.
+    pub const RAW: Self = Self(101);
+
+    /// Raw IPv4 packets.
+    pub const IPV4: Self = Self(228);
+
+    /// Raw IPv6 packets.
+    pub const IPV6: Self = Self(229);
+
+    /// CAN frames with a SocketCAN header, see [`socketcan_packet`].
+    pub const CAN_SOCKETCAN: Self = Self(227);
+
+    /// First link type reserved for private use, e.g. for the payloads of a
+    /// simulation-specific protocol.
+    pub const USER0: Self = Self(147);
+
+    /// Returns the link type reserved for private use with the specified index,
+    /// from 0 to 15.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the index is greater than 15.
+    pub const fn user(index: u16) -> Self {
+        assert!(index <= 15, "the user link type index should be at most 15");
+
+        Self(Self::USER0.0 + index)
+    }
+}
+
+/// Packet logged on a capture interface.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PcapngPacket {
+    /// Index of the capture interface.
+    pub interface: u32,
+
+    /// Packet data, starting with the header of the interface link type.
+    pub data: Bytes,
+}
+
+impl PcapngPacket {
+    /// Creates a packet logged on the specified capture interface.
+    pub fn new(interface: u32, data: impl Into<Bytes>) -> Self {
+        Self {
+            interface,
+            data: data.into(),
+        }
+    }
+}
+
+/// Encodes a CAN frame for the [`LinkType::CAN_SOCKETCAN`] link type.
+///
+/// The identifier is the SocketCAN `can_id` word, including the extended
+/// frame, remote frame and error flags in its 3 most significant bits. The
+/// frame is encoded as a CAN FD frame with the provided flags (bit rate switch
+/// 0x01, error state indicator 0x02) if `fd_flags` is provided, and as a
+/// classic CAN frame otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use nexosim_file_port::pcap::socketcan_packet;
+///
+/// // Extended data frame 0x18FEF100.
+/// let packet = socketcan_packet(0x9800_0000 | 0x18FE_F100, &[0xFF, 0x00], None);
+/// assert_eq!(&packet[..8], &[0x98, 0xFE, 0xF1, 0x00, 2, 0, 0, 0]);
+/// assert_eq!(packet.len(), 16);
+/// ```
+pub fn socketcan_packet(can_id: u32, data: &[u8], fd_flags: Option<u8>) -> Bytes {
+    const CANFD_FDF: u8 = 0x04;
+
+    let (max_len, flags) = match fd_flags {
+        Some(flags) => (64, flags | CANFD_FDF),
+        None => (8, 0),
+    };
+    let data = &data[..data.len().min(max_len)];
+    // The payload is padded like the frames captured on a SocketCAN
+    // interface.
+    let mut packet = Vec::with_capacity(8 + max_len);
+    packet.extend_from_slice(&can_id.to_be_bytes());
+    packet.extend_from_slice(&[data.len() as u8, flags, 0, 0]);
+    packet.extend_from_slice(data);
+    packet.resize(8 + max_len, 0);
+
+    Bytes::from(packet)
+}
+
+/// Model writing simulation traffic to a pcapng capture file.
+///
+/// Packets are logged with the simulation time as timestamp, with a
+/// nanosecond resolution, on capture interfaces declared with
+/// [`with_interface`](Self::with_interface). If no interface is declared,
+/// packets are logged on a single unnamed interface with the
+/// [`LinkType::USER0`] link type.
+///
+/// The file header is written when the model is initialized.
+pub struct PcapngLogger {
+    /// Capture file.
+    writer: TrackedWriter<BufWriter<File>>,
+
+    /// Names and link types of the capture interfaces.
+    interfaces: Vec<(String, LinkType)>,
+}
+
+impl PcapngLogger {
+    /// Creates a new logger writing to the provided file.
+    ///
+    /// The file is truncated if it already exists.
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            writer: TrackedWriter::create(path)?,
+            interfaces: Vec::new(),
+        })
+    }
+
+    /// Declares a capture interface with the specified name and link type.
+    ///
+    /// Interfaces are indexed in declaration order, starting from 0. The name
+    /// is not written if empty.
+    pub fn with_interface(mut self, name: &str, link_type: LinkType) -> Self {
+        self.interfaces.push((name.to_string(), link_type));
+        self
+    }
+
+    /// Returns the path of the capture file.
+    pub fn path(&self) -> &Path {
+        self.writer.path()
+    }
+
+    /// Packet logged on interface 0 -- input port.
+    pub fn packet_in(&mut self, data: Bytes, cx: &mut Context<Self>) {
+        self.write_packet(0, &data, cx.time());
+    }
+
+    /// Packet logged on the specified interface -- input port.
+    pub fn interface_packet_in(&mut self, packet: PcapngPacket, cx: &mut Context<Self>) {
+        self.write_packet(packet.interface, &packet.data, cx.time());
+    }
+
+    /// Flushes the capture file -- input port.
+    pub fn flush(&mut self) {
+        if let Err(_e) = self.writer.flush() {
+            #[cfg(feature = "tracing")]
+            warn!("Failed to flush {}: {}.", self.writer.path().display(), _e);
+        }
+    }
+
+    /// Writes an Enhanced Packet Block.
+    fn write_packet(&mut self, interface: u32, data: &[u8], time: MonotonicTime) {
+        if interface as usize >= self.interfaces.len() {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Skipping packet of undeclared interface {} for {}.",
+                interface,
+                self.writer.path().display()
+            );
+            return;
+        }
+        let timestamp = u64::try_from(time.as_secs())
+            .unwrap_or(0)
+            .saturating_mul(1_000_000_000)
+            .saturating_add(u64::from(time.subsec_nanos()));
+        let len = u32::try_from(data.len()).unwrap_or(u32::MAX);
+        let mut body = Vec::with_capacity(20 + data.len());
+        body.extend_from_slice(&interface.to_le_bytes());
+        body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        body.extend_from_slice(&len.to_le_bytes());
+        body.extend_from_slice(&len.to_le_bytes());
+        body.extend_from_slice(&data[..len as usize]);
+        self.write_block(ENHANCED_PACKET, &body);
+    }
+
+    /// Writes the Section Header Block and the Interface Description Blocks.
+    fn write_header(&mut self) {
+        let mut body = Vec::with_capacity(16);
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        // The section length is not specified.
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        self.write_block(SECTION_HEADER, &body);
+
+        if self.interfaces.is_empty() {
+            self.interfaces.push((String::new(), LinkType::USER0));
+        }
+        for index in 0..self.interfaces.len() {
+            let (name, link_type) = &self.interfaces[index];
+            let mut body = Vec::new();
+            body.extend_from_slice(&link_type.0.to_le_bytes());
+            body.extend_from_slice(&0u16.to_le_bytes());
+            // Packets are not truncated.
+            body.extend_from_slice(&0u32.to_le_bytes());
+            if !name.is_empty() {
+                push_option(&mut body, IF_NAME, name.as_bytes());
+            }
+            push_option(&mut body, IF_TSRESOL, &[9]);
+            push_option(&mut body, 0, &[]);
+            self.write_block(INTERFACE_DESCRIPTION, &body);
+        }
+    }
+
+    /// Writes a block with the provided type and body.
+    fn write_block(&mut self, block_type: u32, body: &[u8]) {
+        let padding = body.len().next_multiple_of(4) - body.len();
+        let len = u32::try_from(12 + body.len() + padding).unwrap_or(u32::MAX);
+        let result = self
+            .writer
+            .write_all(&block_type.to_le_bytes())
+            .and_then(|_| self.writer.write_all(&len.to_le_bytes()))
+            .and_then(|_| self.writer.write_all(body))
+            .and_then(|_| self.writer.write_all(&[0; 3][..padding]))
+            .and_then(|_| self.writer.write_all(&len.to_le_bytes()));
+        if let Err(_e) = result {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Failed to write to {}: {}.",
+                self.writer.path().display(),
+                _e
+            );
+        }
+    }
+}
+
+/// Appends a pcapng option, padded to 32 bits.
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+impl Model for PcapngLogger {
+    async fn init(mut self, _: &mut Context<Self>) -> InitializedModel<Self> {
+        self.write_header();
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for PcapngLogger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PcapngLogger")
+            .field("path", &self.writer.path())
+            .finish_non_exhaustive()
+    }
+}
+
+/// BPF filter compiled for each link type of the capture.
+#[cfg(feature = "bpf")]
+struct PacketFilter {
+    /// Filter expression.
+    expression: String,
+
+    /// Compiled programs, `None` if the expression does not apply to the link
+    /// type.
+    programs: HashMap<u16, Option<::pcap::BpfProgram>>,
+}
+
+#[cfg(feature = "bpf")]
+impl PacketFilter {
+    /// Compiles the filter expression for a link type.
+    fn compile(expression: &str, link_type: u16) -> Result<::pcap::BpfProgram, ::pcap::Error> {
+        ::pcap::Capture::dead(::pcap::Linktype(i32::from(link_type)))?.compile(expression, true)
+    }
+
+    /// Checks whether a packet matches the filter.
+    fn matches(&mut self, packet: &CapturedPacket) -> bool {
+        let program = self.programs.entry(packet.link_type).or_insert_with(|| {
+            Self::compile(&self.expression, packet.link_type)
+                .inspect_err(|_e| {
+                    #[cfg(feature = "tracing")]
+                    warn!(
+                        "Skipping the packets of link type {}: {}.",
+                        packet.link_type, _e
+                    );
+                })
+                .ok()
+        });
+
+        program
+            .as_ref()
+            .is_some_and(|program| program.filter(&packet.data))
+    }
+}
+
+/// Model replaying a pcap or pcapng capture file.
+///
+/// The replay starts when the [`start`](Self::start) input is triggered. The
+/// first packet of the capture is emitted immediately and the following
+/// packets with their time offset relative to the first packet, multiplied
+/// by the time scale. Packets captured with a timestamp earlier than the one
+/// of their predecessor are emitted immediately.
+///
+/// Packets are emitted with their captured data, without link-layer metadata,
+/// unless a payload offset is set with
+/// [`with_payload_offset`](Self::with_payload_offset).
+pub struct PcapReplayer {
+    /// Replayed packets -- output port.
+    pub packet_out: Output<Bytes>,
+
+    /// Path of the capture file.
+    path: PathBuf,
+
+    /// Capture reader, `None` once the capture is exhausted.
+    reader: Option<CaptureReader>,
+
+    /// The capture reader has been used.
+    started: bool,
+
+    /// Time scale applied to the capture time offsets.
+    time_scale: f64,
+
+    /// Replayed pcapng interface, if not all.
+    interface: Option<u32>,
+
+    /// Number of bytes stripped from the start of the packets.
+    payload_offset: usize,
+
+    /// Packet filter.
+    #[cfg(feature = "bpf")]
+    filter: Option<PacketFilter>,
+
+    /// Replay start time and timestamp of the first packet.
+    origin: Option<(MonotonicTime, Duration)>,
+
+    /// Key of the next scheduled packet.
+    next_key: Option<ActionKey>,
+}
+
+impl PcapReplayer {
+    /// Creates a new replayer reading the provided pcap or pcapng file.
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let reader = CaptureReader::open(&path)?;
+
+        Ok(Self {
+            packet_out: Output::default(),
+            path,
+            reader: Some(reader),
+            started: false,
+            time_scale: 1.0,
+            interface: None,
+            payload_offset: 0,
+            #[cfg(feature = "bpf")]
+            filter: None,
+            origin: None,
+            next_key: None,
+        })
+    }
+
+    /// Sets the time scale applied to the time offsets of the capture.
+    ///
+    /// A scale greater than 1 slows the replay down, a scale smaller than 1
+    /// speeds it up and a zero scale emits all packets at once.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the time scale is negative or not finite.
+    pub fn with_time_scale(mut self, time_scale: f64) -> Self {
+        assert!(
+            time_scale.is_finite() && time_scale >= 0.0,
+            "the time scale should be finite and non-negative"
+        );
+        self.time_scale = time_scale;
+        self
+    }
+
+    /// Only replays the packets captured on the specified pcapng interface,
+    /// by index.
+    ///
+    /// The packets of a pcap file are captured on interface 0.
+    pub fn with_interface(mut self, interface: u32) -> Self {
+        self.interface = Some(interface);
+        self
+    }
+
+    /// Strips the specified number of bytes from the start of the packets,
+    /// e.g. the link, network and transport headers.
+    ///
+    /// Packets shorter than the offset are skipped.
+    pub fn with_payload_offset(mut self, offset: usize) -> Self {
+        self.payload_offset = offset;
+        self
+    }
+
+    /// Only replays the packets matching a BPF filter expression, e.g.
+    /// `udp dst port 5000`.
+    ///
+    /// The expression is compiled for the link type of each capture
+    /// interface, and the packets of interfaces for which it cannot be
+    /// compiled are skipped.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the expression cannot be compiled for the link
+    /// type of the first capture interface.
+    #[cfg(feature = "bpf")]
+    pub fn with_filter(mut self, expression: &str) -> io::Result<Self> {
+        let mut programs = HashMap::new();
+        if let Some(reader) = &mut self.reader {
+            if let Some(link_type) = reader.first_link_type()? {
+                let program = PacketFilter::compile(expression, link_type)
+                    .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+                programs.insert(link_type, Some(program));
+            }
+        }
+        self.filter = Some(PacketFilter {
+            expression: expression.to_string(),
+            programs,
+        });
+
+        Ok(self)
+    }
+
+    /// Returns the path of the capture file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Starts the replay -- input port.
+    ///
+    /// If a replay is in progress, it is restarted from the beginning of the
+    /// capture.
+    pub async fn start(&mut self, _: (), cx: &mut Context<Self>) {
+        self.stop();
+        if self.started {
+            self.reader = CaptureReader::open(&self.path)
+                .inspect_err(|_e| {
+                    #[cfg(feature = "tracing")]
+                    warn!("Failed to reopen {}: {}.", self.path.display(), _e);
+                })
+                .ok();
+        }
+        self.started = true;
+        self.origin = None;
+
+        self.replay(cx).await;
+    }
+
+    /// Stops the replay -- input port.
+    pub fn stop(&mut self) {
+        if let Some(key) = self.next_key.take() {
+            key.cancel();
+        }
+    }
+
+    /// Emits a scheduled packet and continues the replay.
+    async fn emit(&mut self, data: Bytes, cx: &mut Context<Self>) {
+        self.next_key = None;
+        self.packet_out.send(data).await;
+        self.replay(cx).await;
+    }
+
+    /// Emits the packets which are due and schedules the next one.
+    async fn replay(&mut self, cx: &mut Context<Self>) {
+        while let Some((timestamp, data)) = self.next_packet() {
+            let (start, t0) = *self.origin.get_or_insert((cx.time(), timestamp));
+            let offset = timestamp.saturating_sub(t0);
+            let deadline = start + offset.mul_f64(self.time_scale);
+            if deadline > cx.time() {
+                self.schedule(deadline, data, cx);
+                return;
+            }
+            self.packet_out.send(data).await;
+        }
+    }
+
+    /// Schedules the emission of a packet.
+    fn schedule(&mut self, deadline: MonotonicTime, data: Bytes, cx: &mut Context<Self>) {
+        self.next_key = Some(cx.schedule_keyed_event(deadline, Self::emit, data).unwrap());
+    }
+
+    /// Reads the next replayable packet and its timestamp from the capture.
+    fn next_packet(&mut self) -> Option<(Duration, Bytes)> {
+        loop {
+            let packet = match self.reader.as_mut()?.next_packet() {
+                Ok(Some(packet)) => packet,
+                Ok(None) => {
+                    self.reader = None;
+                    return None;
+                }
+                Err(_e) => {
+                    #[cfg(feature = "tracing")]
+                    warn!("Failed to read {}: {}.", self.path.display(), _e);
+                    self.reader = None;
+                    return None;
+                }
+            };
+            if self
+                .interface
+                .is_some_and(|interface| interface != packet.interface)
+            {
+                continue;
+            }
+            #[cfg(feature = "bpf")]
+            if let Some(filter) = &mut self.filter {
+                if !filter.matches(&packet) {
+                    continue;
+                }
+            }
+            if packet.data.len() < self.payload_offset {
+                continue;
+            }
+            let data = Bytes::from(packet.data).slice(self.payload_offset..);
+
+            return Some((packet.timestamp, data));
+        }
+    }
+}
+
+impl Model for PcapReplayer {}
+
+impl fmt::Debug for PcapReplayer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PcapReplayer")
+            .field("path", &self.path)
+            .field("time_scale", &self.time_scale)
+            .finish_non_exhaustive()
+    }
+}
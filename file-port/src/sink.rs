@@ -0,0 +1,307 @@
+//! File sink model.
+//!
+//! This module contains the [`FileSink`] model, which writes the data sent by
+//! the simulation to a file, e.g. to feed a logger or a legacy tool reading a
+//! named pipe.
+//!
+//! The file is created if it does not exist, and is either truncated or
+//! appended to when opened. If `lines` is set, a line terminator is written
+//! after each data item.
+//!
+//! On Unix, named pipes are also supported: they are opened without waiting
+//! for a reader, and the data which cannot be written while the pipe is full
+//! is buffered until its reader catches up.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_file_port::sink::FileSinkConfig;
+//!
+//! let config = ConfigLoader::<FileSinkConfig>::new()
+//!     .code(
+//!         r#"
+//! path = "/tmp/bench.fifo"
+//! lines = true
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert!(!config.append);
+//! ```
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Result as IoResult};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use schematic::Config;
+
+use mio::{Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus, WriteBuffer};
+
+use crate::{is_fifo, open};
+
+/// File sink model instance configuration.
+#[derive(Config, Debug)]
+pub struct FileSinkConfig {
+    /// Path of the file or named pipe.
+    pub path: String,
+
+    /// Append to the file rather than truncate it when opened.
+    #[setting(default = false)]
+    pub append: bool,
+
+    /// Write a line terminator after each data item.
+    #[setting(default = false)]
+    pub lines: bool,
+
+    /// Delay for the first scheduled I/O thread status check.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which the I/O thread status is checked.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time the I/O thread status is checked. If
+    /// no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// Named pipe token.
+const PIPE: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// Written file.
+struct FileSinkInner {
+    /// Path of the file.
+    path: PathBuf,
+
+    /// Append to the file.
+    append: bool,
+
+    /// Write a line terminator after each data item.
+    lines: bool,
+
+    /// Open file, once registered.
+    file: Option<File>,
+
+    /// Data not yet written to a named pipe.
+    tx: WriteBuffer,
+}
+
+impl FileSinkInner {
+    /// Creates a file sink, opening the file once registered.
+    fn new(config: &FileSinkConfig) -> Self {
+        Self {
+            path: PathBuf::from(&config.path),
+            append: config.append,
+            lines: config.lines,
+            file: None,
+            tx: WriteBuffer::new(),
+        }
+    }
+}
+
+impl IoPort<mio::net::TcpStream, (), Bytes> for FileSinkInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        let file = open(
+            &self.path,
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .append(self.append)
+                .truncate(!self.append),
+        )?;
+        if is_fifo(&file.metadata()?) {
+            #[cfg(unix)]
+            {
+                use std::os::fd::AsRawFd;
+
+                registry.register(
+                    &mut mio::unix::SourceFd(&file.as_raw_fd()),
+                    PIPE,
+                    mio::Interest::WRITABLE,
+                )?;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = registry;
+        self.file = Some(file);
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, _: Token) -> IoResult<()> {
+        Err(ErrorKind::WouldBlock.into())
+    }
+
+    fn write(&mut self, data: &Bytes) -> IoResult<()> {
+        let file = self.file.as_mut().ok_or(ErrorKind::NotConnected)?;
+        self.tx.push(data);
+        if self.lines {
+            self.tx.push(b"\n");
+        }
+        self.tx.flush(file)?;
+
+        Ok(())
+    }
+
+    fn writable(&mut self, _: Token) -> IoResult<()> {
+        if let Some(file) = &mut self.file {
+            self.tx.flush(file)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        !self.tx.is_empty()
+    }
+}
+
+/// File sink model.
+///
+/// This model:
+/// * writes the data received on its input to the configured file,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct FileSink {
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: FileSinkConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<(), Bytes>,
+}
+
+impl FileSink {
+    /// Writes data to the file -- input port.
+    pub async fn bytes_in(&mut self, data: Bytes) {
+        let _ = self.io_thread.send(data);
+    }
+
+    /// Forwards the I/O thread status -- input port.
+    pub async fn process(&mut self) {
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for FileSink {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for FileSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FileSink")
+            .field("path", &self.config.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// File sink model prototype.
+pub struct ProtoFileSink {
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// File sink model instance configuration.
+    config: FileSinkConfig,
+}
+
+impl ProtoFileSink {
+    /// Creates a new file sink model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the file cannot be opened or if the I/O
+    /// thread cannot be created.
+    pub fn new(config: FileSinkConfig) -> Self {
+        Self {
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoFileSink {
+    type Model = FileSink;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(FileSinkInner::new(&self.config), options)
+            .unwrap_or_else(|e| panic!("Failed to open the file sink {}: {e}.", self.config.path));
+
+        FileSink {
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoFileSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoFileSink")
+            .field("path", &self.config.path)
+            .finish_non_exhaustive()
+    }
+}
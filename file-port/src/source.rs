@@ -0,0 +1,563 @@
+//! File source model.
+//!
+//! This module contains the [`FileSource`] model, which follows a growing
+//! file like `tail -F` and injects the appended data into the simulation, e.g.
+//! to follow the log of an external tool.
+//!
+//! The file is polled periodically. It is reopened from its start when it is
+//! replaced, e.g. by a log rotation, once the data appended to the previous
+//! file has been read, and is read again from its start when it is truncated.
+//! The file does not need to exist when the model is built: it is opened once
+//! it appears.
+//!
+//! On Unix, named pipes are also supported: they are opened without waiting
+//! for a writer, read as soon as data is available rather than polled, and
+//! successive writers can open and close them without interrupting the
+//! source.
+//!
+//! Data is forwarded by chunks as read, or by lines if `lines` is set, in
+//! which case the line terminators are removed and an incomplete last line is
+//! held back until completed.
+//!
+//! #### Examples
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_file_port::source::FileSourceConfig;
+//!
+//! let config = ConfigLoader::<FileSourceConfig>::new()
+//!     .code(
+//!         r#"
+//! path = "/var/log/bench/telemetry.log"
+//! lines = true
+//! period = 10
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(Duration::from(config.poll_interval), Duration::from_millis(100));
+//! assert!(!config.from_start);
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Result as IoResult, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use schematic::Config;
+
+use mio::{Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus};
+
+use crate::{file_id, is_fifo, open};
+
+/// File source model instance configuration.
+#[derive(Config, Debug)]
+pub struct FileSourceConfig {
+    /// Path of the file or named pipe.
+    pub path: String,
+
+    /// Forward data by lines.
+    #[setting(default = false)]
+    pub lines: bool,
+
+    /// Read the file from its start rather than only the data appended after
+    /// it is first opened.
+    #[setting(default = false)]
+    pub from_start: bool,
+
+    /// Period at which the file is checked for new data, truncation and
+    /// replacement.
+    #[setting(default = ConfigDuration::from_millis(100))]
+    pub poll_interval: ConfigDuration,
+
+    /// Size of the buffer used to read the file.
+    #[setting(default = 4096)]
+    pub buffer_size: usize,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which read data is forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// File source status.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileSourceStatus {
+    /// The file was opened for the first time.
+    Opened,
+
+    /// The file was truncated and is read again from its start.
+    Truncated,
+
+    /// The file was replaced and the new file is read from its start.
+    Replaced,
+
+    /// The file cannot be opened or read, with the error description.
+    ///
+    /// This is only reported once until the file is available again.
+    Unavailable(String),
+}
+
+/// Event read from the file.
+enum FileEvent {
+    /// Read data.
+    Data(Bytes),
+
+    /// Status change.
+    Status(FileSourceStatus),
+}
+
+/// Named pipe token.
+const PIPE: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// Followed file.
+struct FileSourceInner {
+    /// Path of the file.
+    path: PathBuf,
+
+    /// Forward data by lines.
+    lines: bool,
+
+    /// Read the file from its start when first opened.
+    from_start: bool,
+
+    /// File polling period.
+    poll_interval: Duration,
+
+    /// Open file, if any.
+    file: Option<File>,
+
+    /// Identity of the open file, if supported.
+    id: Option<(u64, u64)>,
+
+    /// The open file is a named pipe.
+    is_pipe: bool,
+
+    /// Read position in the open file.
+    position: u64,
+
+    /// A file has already been opened.
+    has_opened: bool,
+
+    /// The file has been reported as unavailable.
+    is_unavailable: bool,
+
+    /// Incomplete line.
+    line: Vec<u8>,
+
+    /// Read buffer.
+    buffer: Vec<u8>,
+
+    /// Events not yet read.
+    events: VecDeque<FileEvent>,
+
+    /// Time of the next poll.
+    next_poll: Instant,
+
+    /// MIO registry, available once the source is registered.
+    registry: Option<Registry>,
+}
+
+impl FileSourceInner {
+    /// Creates a file source, opening the file once registered.
+    fn new(config: &FileSourceConfig) -> Self {
+        Self {
+            path: PathBuf::from(&config.path),
+            lines: config.lines,
+            from_start: config.from_start,
+            poll_interval: (*config.poll_interval).max(Duration::from_millis(1)),
+            file: None,
+            id: None,
+            is_pipe: false,
+            position: 0,
+            has_opened: false,
+            is_unavailable: false,
+            line: Vec::new(),
+            buffer: vec![0; config.buffer_size.max(1)],
+            events: VecDeque::new(),
+            next_poll: Instant::now(),
+            registry: None,
+        }
+    }
+
+    /// Opens the file, reading it from its start unless it is the first file
+    /// opened and `from_start` is not set.
+    fn open(&mut self) {
+        let result = open(&self.path, OpenOptions::new().read(true)).and_then(|mut file| {
+            let metadata = file.metadata()?;
+            let is_pipe = is_fifo(&metadata);
+            let position = if is_pipe || self.has_opened || self.from_start {
+                0
+            } else {
+                file.seek(SeekFrom::End(0))?
+            };
+            if is_pipe {
+                if let Some(registry) = &self.registry {
+                    #[cfg(unix)]
+                    {
+                        use std::os::fd::AsRawFd;
+
+                        registry.register(
+                            &mut mio::unix::SourceFd(&file.as_raw_fd()),
+                            PIPE,
+                            mio::Interest::READABLE,
+                        )?;
+                    }
+                    #[cfg(not(unix))]
+                    let _ = registry;
+                }
+            }
+
+            Ok((file, file_id(&metadata), is_pipe, position))
+        });
+        match result {
+            Ok((file, id, is_pipe, position)) => {
+                self.file = Some(file);
+                self.id = id;
+                self.is_pipe = is_pipe;
+                self.position = position;
+                self.is_unavailable = false;
+                self.line.clear();
+                let status = if self.has_opened {
+                    FileSourceStatus::Replaced
+                } else {
+                    FileSourceStatus::Opened
+                };
+                self.has_opened = true;
+                self.events.push_back(FileEvent::Status(status));
+            }
+            Err(e) => self.unavailable(e.to_string()),
+        }
+    }
+
+    /// Closes the file.
+    fn close(&mut self) {
+        if let Some(file) = self.file.take() {
+            if self.is_pipe {
+                if let Some(registry) = &self.registry {
+                    #[cfg(unix)]
+                    {
+                        use std::os::fd::AsRawFd;
+
+                        let _ = registry.deregister(&mut mio::unix::SourceFd(&file.as_raw_fd()));
+                    }
+                    #[cfg(not(unix))]
+                    let _ = registry;
+                }
+            }
+        }
+        self.id = None;
+        self.is_pipe = false;
+    }
+
+    /// Reports the file as unavailable, once.
+    fn unavailable(&mut self, error: String) {
+        if !self.is_unavailable {
+            self.is_unavailable = true;
+            self.events
+                .push_back(FileEvent::Status(FileSourceStatus::Unavailable(error)));
+        }
+    }
+
+    /// Reads the data available in the file.
+    fn drain(&mut self) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        loop {
+            match file.read(&mut self.buffer) {
+                Ok(0) => return,
+                Ok(len) => {
+                    self.position += len as u64;
+                    let data = &self.buffer[..len];
+                    if !self.lines {
+                        self.events
+                            .push_back(FileEvent::Data(Bytes::copy_from_slice(data)));
+                        continue;
+                    }
+                    for chunk in data.split_inclusive(|&byte| byte == b'\n') {
+                        self.line.extend_from_slice(chunk);
+                        if let Some(b'\n') = self.line.last() {
+                            self.line.pop();
+                            if let Some(b'\r') = self.line.last() {
+                                self.line.pop();
+                            }
+                            let line = std::mem::take(&mut self.line);
+                            self.events.push_back(FileEvent::Data(Bytes::from(line)));
+                        }
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.close();
+                    self.unavailable(e.to_string());
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Reads the appended data and checks whether the file was truncated or
+    /// replaced.
+    fn poll(&mut self) {
+        if self.file.is_none() {
+            self.open();
+        }
+        if self.file.is_none() || self.is_pipe {
+            return;
+        }
+        self.drain();
+        match fs::metadata(&self.path) {
+            Ok(metadata) => {
+                if self.id.is_some() && file_id(&metadata) != self.id {
+                    self.close();
+                    self.open();
+                    self.drain();
+                } else if metadata.len() < self.position {
+                    if let Some(file) = &mut self.file {
+                        if file.seek(SeekFrom::Start(0)).is_ok() {
+                            self.position = 0;
+                            self.line.clear();
+                            self.events
+                                .push_back(FileEvent::Status(FileSourceStatus::Truncated));
+                            self.drain();
+                        }
+                    }
+                } else {
+                    self.is_unavailable = false;
+                }
+            }
+            // The open file is still read until it is replaced.
+            Err(e) => self.unavailable(e.to_string()),
+        }
+    }
+}
+
+impl IoPort<mio::net::TcpStream, FileEvent, ()> for FileSourceInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        self.registry = Some(registry.try_clone()?);
+        self.poll();
+        self.next_poll = Instant::now() + self.poll_interval;
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<FileEvent> {
+        if self.events.is_empty() && token == PIPE {
+            self.drain();
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        // Events queued outside of a read, e.g. when the file is polled, are
+        // forwarded immediately.
+        if !self.events.is_empty() {
+            return Some(Instant::now());
+        }
+        if self.is_pipe {
+            return None;
+        }
+        Some(self.next_poll)
+    }
+
+    fn timeout(&mut self) -> IoResult<FileEvent> {
+        let now = Instant::now();
+        if self.next_poll <= now {
+            self.poll();
+            self.next_poll = now + self.poll_interval;
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn write(&mut self, _: &()) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+/// File source model.
+///
+/// This model:
+/// * follows the configured file and forwards the appended data to the model
+///   output,
+/// * reports the opening, truncation, replacement and unavailability of the
+///   file,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct FileSource {
+    /// Read data -- output port.
+    pub bytes_out: Output<Bytes>,
+
+    /// File status -- output port.
+    pub status_out: Output<FileSourceStatus>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: FileSourceConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<FileEvent, ()>,
+}
+
+impl FileSource {
+    /// Forwards the read data, the file status changes and the I/O thread
+    /// status -- input port.
+    pub async fn process(&mut self) {
+        for event in self.io_thread.try_recv_all() {
+            match event {
+                FileEvent::Data(data) => self.bytes_out.send(data).await,
+                FileEvent::Status(status) => self.status_out.send(status).await,
+            }
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for FileSource {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for FileSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FileSource")
+            .field("path", &self.config.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// File source model prototype.
+pub struct ProtoFileSource {
+    /// Read data -- output port.
+    pub bytes_out: Output<Bytes>,
+
+    /// File status -- output port.
+    pub status_out: Output<FileSourceStatus>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// File source model instance configuration.
+    config: FileSourceConfig,
+}
+
+impl ProtoFileSource {
+    /// Creates a new file source model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the I/O thread cannot be created.
+    pub fn new(config: FileSourceConfig) -> Self {
+        Self {
+            bytes_out: Output::new(),
+            status_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoFileSource {
+    type Model = FileSource;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(FileSourceInner::new(&self.config), options)
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Failed to start the I/O thread of the file source {}: {e}.",
+                    self.config.path
+                )
+            });
+
+        FileSource {
+            bytes_out: self.bytes_out,
+            status_out: self.status_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoFileSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoFileSource")
+            .field("path", &self.config.path)
+            .finish_non_exhaustive()
+    }
+}
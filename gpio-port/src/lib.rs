@@ -0,0 +1,565 @@
+//! GPIO port model for [NeXosim][NX]-based simulations.
+//!
+//! This model
+//! * watches the specified input lines of a GPIO chip, injecting their level
+//!   changes into the simulation,
+//! * drives the specified output lines of the GPIO chip from the simulation,
+//!   e.g. to assert the reset line of a unit under test.
+//!
+//! Lines are accessed with the Linux GPIO character device API and addressed
+//! by their offset on the chip. The levels of the input lines are forwarded
+//! once when the model is built, and then on each edge detected by the kernel
+//! according to the `edge` configuration. Levels are logical levels: if
+//! `active_low` is set, a low physical level is reported and driven as
+//! `true`.
+//!
+//! The model is only available on Linux.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_gpio_port::{GpioBias, GpioEdge, GpioPortConfig};
+//!
+//! let config = ConfigLoader::<GpioPortConfig>::new()
+//!     .code(
+//!         r#"
+//! chip = "gpiochip0"
+//! inputLines = [17, 27]
+//! outputLines = [22]
+//! outputValues = [true]
+//! bias = "pull-up"
+//! period = 10
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.bias, GpioBias::PullUp);
+//! assert_eq!(config.edge, GpioEdge::Both);
+//! ```
+//!
+//! [NX]: https://github.com/asynchronics/nexosim
+#![cfg(target_os = "linux")]
+#![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
+#![forbid(unsafe_code)]
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{ErrorKind, Read, Result, Write};
+use std::sync::mpsc::{Receiver, channel};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gpiod::{Active, Bias, Chip, Drive, Edge, EdgeDetect, Lines, Options};
+use mio::unix::pipe;
+use mio::{Interest, Registry, Token};
+
+use schematic::{Config, ConfigEnum};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus};
+
+/// GPIO port model instance configuration.
+#[derive(Config, Debug)]
+pub struct GpioPortConfig {
+    /// GPIO chip, as a name, e.g. `gpiochip0`, or as a device path, e.g.
+    /// `/dev/gpiochip0`.
+    pub chip: String,
+
+    /// Offsets of the input lines.
+    pub input_lines: Vec<u32>,
+
+    /// Offsets of the output lines.
+    pub output_lines: Vec<u32>,
+
+    /// Initial levels of the output lines, in the order of `output_lines`.
+    ///
+    /// Output lines without initial level are initially inactive.
+    pub output_values: Vec<bool>,
+
+    /// Lines are active low.
+    #[setting(default = false)]
+    pub active_low: bool,
+
+    /// Bias of the lines.
+    pub bias: GpioBias,
+
+    /// Edges of the input lines reported to the simulation.
+    pub edge: GpioEdge,
+
+    /// Drive of the output lines.
+    pub drive: GpioDrive,
+
+    /// Consumer label of the requested lines, as shown by `gpioinfo`.
+    #[setting(default = "nexosim")]
+    pub consumer: String,
+
+    /// Delay for the first scheduled level forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which the level changes of the input lines are forwarded
+    /// into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time level changes are forwarded into
+    /// the simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// Bias of the GPIO lines.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GpioBias {
+    /// Bias left unchanged.
+    #[default]
+    AsIs,
+
+    /// Bias disabled.
+    Disable,
+
+    /// Pull-up bias.
+    PullUp,
+
+    /// Pull-down bias.
+    PullDown,
+}
+
+impl GpioBias {
+    /// Returns the bias to configure, if any.
+    fn bias(self) -> Option<Bias> {
+        match self {
+            Self::AsIs => None,
+            Self::Disable => Some(Bias::Disable),
+            Self::PullUp => Some(Bias::PullUp),
+            Self::PullDown => Some(Bias::PullDown),
+        }
+    }
+}
+
+/// Edges of the GPIO input lines reported to the simulation.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GpioEdge {
+    /// Rising and falling edges.
+    #[default]
+    Both,
+
+    /// Rising edges only.
+    Rising,
+
+    /// Falling edges only.
+    Falling,
+}
+
+impl From<GpioEdge> for EdgeDetect {
+    fn from(edge: GpioEdge) -> Self {
+        match edge {
+            GpioEdge::Both => Self::Both,
+            GpioEdge::Rising => Self::Rising,
+            GpioEdge::Falling => Self::Falling,
+        }
+    }
+}
+
+/// Drive of the GPIO output lines.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GpioDrive {
+    /// Push-pull drive.
+    #[default]
+    PushPull,
+
+    /// Open-drain drive.
+    OpenDrain,
+
+    /// Open-source drive.
+    OpenSource,
+}
+
+impl From<GpioDrive> for Drive {
+    fn from(drive: GpioDrive) -> Self {
+        match drive {
+            GpioDrive::PushPull => Self::PushPull,
+            GpioDrive::OpenDrain => Self::OpenDrain,
+            GpioDrive::OpenSource => Self::OpenSource,
+        }
+    }
+}
+
+/// Logical level of a GPIO line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GpioLevel {
+    /// Offset of the line on the GPIO chip.
+    pub line: u32,
+
+    /// Logical level, `true` if the line is active.
+    pub value: bool,
+}
+
+impl GpioLevel {
+    /// Creates a new line level.
+    pub fn new(line: u32, value: bool) -> Self {
+        Self { line, value }
+    }
+}
+
+/// Edge notification token.
+const EDGES: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// GPIO port.
+struct GpioPortInner {
+    /// Offsets of the output lines.
+    output_lines: Vec<u32>,
+
+    /// Requested output lines, if any.
+    outputs: Option<Lines<gpiod::Output>>,
+
+    /// Current levels of the output lines.
+    output_values: Vec<bool>,
+
+    /// Levels not yet read, starting with the initial levels of the input
+    /// lines.
+    levels: VecDeque<GpioLevel>,
+
+    /// Edge notification pipe and edge receiver, if input lines are
+    /// requested.
+    edges: Option<(pipe::Receiver, Receiver<GpioLevel>)>,
+}
+
+impl GpioPortInner {
+    /// Requests the configured lines of the GPIO chip and spawns the edge
+    /// reader thread, if input lines are requested.
+    ///
+    /// Edges are read by a dedicated thread as the GPIO character device API
+    /// only provides blocking reads. Each edge is sent to the I/O thread and
+    /// notified with a byte written to the notification pipe. Once the model
+    /// is dropped, the edge reader thread exits and releases the input lines
+    /// after the next edge.
+    fn new(config: &GpioPortConfig) -> Result<Self> {
+        let chip = Chip::new(config.chip.as_str())?;
+        let active = if config.active_low {
+            Active::Low
+        } else {
+            Active::High
+        };
+
+        let mut levels = VecDeque::new();
+        let edges = if config.input_lines.is_empty() {
+            None
+        } else {
+            let mut options = Options::input(config.input_lines.clone())
+                .consumer(config.consumer.clone())
+                .active(active)
+                .edge(config.edge.into());
+            if let Some(bias) = config.bias.bias() {
+                options = options.bias(bias);
+            }
+            let mut inputs = chip.request_lines(options)?;
+            let values = inputs.get_values(vec![false; config.input_lines.len()])?;
+            levels.extend(
+                config
+                    .input_lines
+                    .iter()
+                    .zip(values)
+                    .map(|(&line, value)| GpioLevel::new(line, value)),
+            );
+
+            let (mut sender, notifier) = pipe::new()?;
+            let (tx, receiver) = channel();
+            let input_lines = config.input_lines.clone();
+            thread::spawn(move || {
+                loop {
+                    let event = match inputs.read_event() {
+                        Ok(event) => event,
+                        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                        Err(_e) => {
+                            #[cfg(feature = "tracing")]
+                            warn!("Failed to read the edges of the GPIO lines: {}.", _e);
+                            return;
+                        }
+                    };
+                    let Some(&line) = input_lines.get(usize::from(event.line)) else {
+                        continue;
+                    };
+                    let level = GpioLevel::new(line, event.edge == Edge::Rising);
+                    if tx.send(level).is_err() || sender.write_all(&[0]).is_err() {
+                        return;
+                    }
+                }
+            });
+
+            Some((notifier, receiver))
+        };
+
+        let mut output_values = config.output_values.clone();
+        output_values.resize(config.output_lines.len(), false);
+        let outputs = if config.output_lines.is_empty() {
+            None
+        } else {
+            let mut options = Options::output(config.output_lines.clone())
+                .consumer(config.consumer.clone())
+                .active(active)
+                .drive(config.drive.into())
+                .values(&output_values);
+            if let Some(bias) = config.bias.bias() {
+                options = options.bias(bias);
+            }
+
+            Some(chip.request_lines(options)?)
+        };
+
+        Ok(Self {
+            output_lines: config.output_lines.clone(),
+            outputs,
+            output_values,
+            levels,
+            edges,
+        })
+    }
+}
+
+impl IoPort<pipe::Receiver, GpioLevel, GpioLevel> for GpioPortInner {
+    fn register(&mut self, registry: &Registry) -> Result<Token> {
+        if let Some((notifier, _)) = &mut self.edges {
+            registry.register(notifier, EDGES, Interest::READABLE)?;
+        }
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> Result<GpioLevel> {
+        if let Some(level) = self.levels.pop_front() {
+            return Ok(level);
+        }
+        let (notifier, receiver) = match (token, &mut self.edges) {
+            (EDGES, Some(edges)) => edges,
+            _ => return Err(ErrorKind::WouldBlock.into()),
+        };
+        // Notifications are consumed before the edges so that no edge is left
+        // without notification.
+        let mut buf = [0; 64];
+        loop {
+            match notifier.read(&mut buf) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.levels.extend(receiver.try_iter());
+
+        self.levels
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        // The initial levels and the edges read with a notification are
+        // forwarded immediately.
+        if self.levels.is_empty() {
+            None
+        } else {
+            Some(Instant::now())
+        }
+    }
+
+    fn timeout(&mut self) -> Result<GpioLevel> {
+        self.levels
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn write(&mut self, level: &GpioLevel) -> Result<()> {
+        let (Some(outputs), Some(index)) = (
+            &self.outputs,
+            self.output_lines
+                .iter()
+                .position(|&line| line == level.line),
+        ) else {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "Level of GPIO line {} dropped: not an output line.",
+                level.line
+            );
+            return Ok(());
+        };
+        self.output_values[index] = level.value;
+
+        outputs.set_values(&self.output_values)
+    }
+}
+
+/// GPIO port model.
+///
+/// This model:
+/// * forwards the initial levels and the level changes of the input lines to
+///   the level output,
+/// * drives the output lines with the levels from the model input,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct GpioPort {
+    /// Level of an input line -- output port.
+    pub level_out: Output<GpioLevel>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: GpioPortConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<GpioLevel, GpioLevel>,
+}
+
+impl GpioPort {
+    /// Drives an output line -- input port.
+    ///
+    /// Levels of lines which are not output lines are dropped, as well as
+    /// levels sent after the I/O thread has exited.
+    pub async fn level_in(&mut self, level: GpioLevel) {
+        let _line = level.line;
+        if self.io_thread.send(level).is_err() {
+            #[cfg(feature = "tracing")]
+            warn!("Level of GPIO line {} dropped: I/O thread exited.", _line);
+        }
+    }
+
+    /// Forwards the level changes of the input lines and the I/O thread status
+    /// -- input port.
+    pub async fn process(&mut self) {
+        for level in self.io_thread.try_recv_all() {
+            self.level_out.send(level).await;
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for GpioPort {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for GpioPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GpioPort")
+            .field("chip", &self.config.chip)
+            .finish_non_exhaustive()
+    }
+}
+
+/// GPIO port model prototype.
+pub struct ProtoGpioPort {
+    /// Level of an input line -- output port.
+    pub level_out: Output<GpioLevel>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// GPIO port model instance configuration.
+    config: GpioPortConfig,
+}
+
+impl ProtoGpioPort {
+    /// Creates a new GPIO port model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the GPIO lines cannot be requested or if
+    /// the I/O thread cannot be created.
+    pub fn new(config: GpioPortConfig) -> Self {
+        Self {
+            level_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoGpioPort {
+    type Model = GpioPort;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let port = GpioPortInner::new(&self.config).unwrap_or_else(|e| {
+            panic!(
+                "Failed to request the lines of the GPIO chip {}: {e}.",
+                self.config.chip
+            )
+        });
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(port, options)
+            .unwrap_or_else(|e| panic!("Failed to start the I/O thread of the GPIO port: {e}."));
+
+        GpioPort {
+            level_out: self.level_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoGpioPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoGpioPort")
+            .field("chip", &self.config.chip)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,426 @@
+//! I2C port model for [NeXosim][NX]-based simulations.
+//!
+//! This crate contains a model acting as the master of a Linux I2C bus, through
+//! its `/dev/i2c-*` character device, e.g. to drive the sensors of a
+//! hardware-in-the-loop bench from a simulation.
+//!
+//! Each [`I2cTransaction`] sent to the model addresses a single device and
+//! consists of read and write operations, executed as one combined transfer
+//! with repeated start conditions between operations. The outcome of each
+//! transaction is reported as an [`I2cReply`], in the order of the
+//! transactions. Devices with an address above `0x7f` are addressed with 10-bit
+//! addresses.
+//!
+//! A transaction failure, e.g. a device not acknowledging its address, is
+//! reported in the reply and does not stop the model.
+//!
+//! The model is only available on Linux.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_i2c_port::{I2cPortConfig, I2cTransaction};
+//! use nexosim_io_utils::duration::ConfigDuration;
+//!
+//! let config = ConfigLoader::<I2cPortConfig>::new()
+//!     .code(
+//!         r#"
+//! bus = "/dev/i2c-1"
+//! period = 10
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.period, Some(ConfigDuration::from_millis(10)));
+//!
+//! // Reads the two bytes of register 0x0f of the device at address 0x48.
+//! let transaction = I2cTransaction::write_read(0x48, vec![0x0f], 2);
+//! assert_eq!(transaction.operations.len(), 2);
+//! ```
+//!
+//! [NX]: https://github.com/asynchronics/nexosim
+#![cfg(target_os = "linux")]
+#![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
+#![forbid(unsafe_code)]
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use i2cdev::core::{I2CMessage, I2CTransfer};
+use i2cdev::linux::{I2CMessageFlags, LinuxI2CBus, LinuxI2CMessage};
+use mio::{Registry, Token};
+
+use schematic::Config;
+
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus};
+
+/// I2C port model instance configuration.
+#[derive(Config, Debug)]
+pub struct I2cPortConfig {
+    /// Path of the I2C bus character device, e.g. `/dev/i2c-1`.
+    pub bus: String,
+
+    /// Delay for the first scheduled reply forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which the transaction replies are forwarded into the
+    /// simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time transaction replies are forwarded
+    /// into the simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// Operation of an I2C transaction.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum I2cOperation {
+    /// Reads the specified number of bytes from the device.
+    Read(u16),
+
+    /// Writes data to the device.
+    Write(Bytes),
+}
+
+/// I2C transaction addressing a single device.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct I2cTransaction {
+    /// Address of the device.
+    ///
+    /// Addresses above `0x7f` are sent as 10-bit addresses.
+    pub address: u16,
+
+    /// Operations, executed in order as one combined transfer.
+    pub operations: Vec<I2cOperation>,
+}
+
+impl I2cTransaction {
+    /// Creates a new transaction.
+    pub fn new(address: u16, operations: Vec<I2cOperation>) -> Self {
+        Self {
+            address,
+            operations,
+        }
+    }
+
+    /// Creates a transaction reading `len` bytes from a device.
+    pub fn read(address: u16, len: u16) -> Self {
+        Self::new(address, vec![I2cOperation::Read(len)])
+    }
+
+    /// Creates a transaction writing data to a device.
+    pub fn write(address: u16, data: impl Into<Bytes>) -> Self {
+        Self::new(address, vec![I2cOperation::Write(data.into())])
+    }
+
+    /// Creates a combined transaction writing data to a device, typically a
+    /// register address, and then reading `len` bytes from the device.
+    pub fn write_read(address: u16, data: impl Into<Bytes>, len: u16) -> Self {
+        Self::new(
+            address,
+            vec![I2cOperation::Write(data.into()), I2cOperation::Read(len)],
+        )
+    }
+}
+
+/// Outcome of an I2C transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct I2cReply {
+    /// Address of the device.
+    pub address: u16,
+
+    /// Data read by each read operation of the transaction, in order, or the
+    /// error kind and description if the transaction failed.
+    pub result: std::result::Result<Vec<Bytes>, (ErrorKind, String)>,
+}
+
+/// I/O thread waker token.
+const WAKE: Token = Token(0);
+
+/// I2C bus.
+struct I2cPortInner {
+    /// I2C bus character device.
+    bus: LinuxI2CBus,
+
+    /// Replies not yet read.
+    replies: VecDeque<I2cReply>,
+}
+
+impl I2cPortInner {
+    /// Opens the I2C bus character device.
+    fn new(config: &I2cPortConfig) -> Result<Self> {
+        Ok(Self {
+            bus: LinuxI2CBus::new(&config.bus)?,
+            replies: VecDeque::new(),
+        })
+    }
+
+    /// Executes a transaction as one combined transfer.
+    fn transfer(&mut self, transaction: &I2cTransaction) -> Result<Vec<Bytes>> {
+        let flags = || {
+            if transaction.address > 0x7f {
+                I2CMessageFlags::TEN_BIT_ADDRESS
+            } else {
+                I2CMessageFlags::empty()
+            }
+        };
+        let mut buffers: Vec<Vec<u8>> = transaction
+            .operations
+            .iter()
+            .map(|operation| match operation {
+                I2cOperation::Read(len) => vec![0; usize::from(*len)],
+                I2cOperation::Write(_) => Vec::new(),
+            })
+            .collect();
+        let mut messages = transaction
+            .operations
+            .iter()
+            .zip(buffers.iter_mut())
+            .map(|(operation, buffer)| match operation {
+                I2cOperation::Read(_) => Ok(LinuxI2CMessage::read(buffer)
+                    .with_address(transaction.address)
+                    .with_flags(flags() | I2CMessageFlags::READ)),
+                I2cOperation::Write(data) => {
+                    if u16::try_from(data.len()).is_err() {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            "write operation longer than 65535 bytes",
+                        ));
+                    }
+                    Ok(LinuxI2CMessage::write(data)
+                        .with_address(transaction.address)
+                        .with_flags(flags()))
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.bus.transfer(&mut messages)?;
+        drop(messages);
+
+        Ok(transaction
+            .operations
+            .iter()
+            .zip(buffers)
+            .filter(|(operation, _)| matches!(operation, I2cOperation::Read(_)))
+            .map(|(_, buffer)| Bytes::from(buffer))
+            .collect())
+    }
+}
+
+impl IoPort<mio::net::TcpStream, I2cReply, I2cTransaction> for I2cPortInner {
+    fn register(&mut self, _: &Registry) -> Result<Token> {
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, _: Token) -> Result<I2cReply> {
+        self.replies
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn write(&mut self, transaction: &I2cTransaction) -> Result<()> {
+        // Transaction failures are replies rather than I/O thread errors, so
+        // that a missing device does not stop the model.
+        let result = self.transfer(transaction).map_err(|e| {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "I2C transaction with device {:#04x} failed: {}.",
+                transaction.address, e
+            );
+            (e.kind(), e.to_string())
+        });
+        self.replies.push_back(I2cReply {
+            address: transaction.address,
+            result,
+        });
+
+        Ok(())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        // Transfers are blocking, so replies are available immediately.
+        if self.replies.is_empty() {
+            None
+        } else {
+            Some(Instant::now())
+        }
+    }
+
+    fn timeout(&mut self) -> Result<I2cReply> {
+        self.replies
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+}
+
+/// I2C port model.
+///
+/// This model:
+/// * executes the transactions from its input on the I2C bus,
+/// * forwards the outcome of each transaction to the reply output,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct I2cPort {
+    /// Transaction outcome -- output port.
+    pub reply_out: Output<I2cReply>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: I2cPortConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<I2cReply, I2cTransaction>,
+}
+
+impl I2cPort {
+    /// Executes a transaction -- input port.
+    ///
+    /// Transactions sent after the I/O thread has exited are dropped.
+    pub async fn transaction_in(&mut self, transaction: I2cTransaction) {
+        let _address = transaction.address;
+        if self.io_thread.send(transaction).is_err() {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "I2C transaction with device {:#04x} dropped: I/O thread exited.",
+                _address
+            );
+        }
+    }
+
+    /// Forwards the transaction replies and the I/O thread status -- input
+    /// port.
+    pub async fn process(&mut self) {
+        for reply in self.io_thread.try_recv_all() {
+            self.reply_out.send(reply).await;
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for I2cPort {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for I2cPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("I2cPort")
+            .field("bus", &self.config.bus)
+            .finish_non_exhaustive()
+    }
+}
+
+/// I2C port model prototype.
+pub struct ProtoI2cPort {
+    /// Transaction outcome -- output port.
+    pub reply_out: Output<I2cReply>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// I2C port model instance configuration.
+    config: I2cPortConfig,
+}
+
+impl ProtoI2cPort {
+    /// Creates a new I2C port model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the I2C bus cannot be opened or if the
+    /// I/O thread cannot be created.
+    pub fn new(config: I2cPortConfig) -> Self {
+        Self {
+            reply_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoI2cPort {
+    type Model = I2cPort;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let port = I2cPortInner::new(&self.config)
+            .unwrap_or_else(|e| panic!("Failed to open the I2C bus {}: {e}.", self.config.bus));
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(port, options)
+            .unwrap_or_else(|e| panic!("Failed to start the I/O thread of the I2C port: {e}."));
+
+        I2cPort {
+            reply_out: self.reply_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoI2cPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoI2cPort")
+            .field("bus", &self.config.bus)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,50 @@
+//! Benchmark of the data sent to an I/O thread.
+
+use std::hint::black_box;
+use std::io::{ErrorKind, Result as IoResult};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use mio::net::UdpSocket;
+use mio::{Registry, Token};
+
+use nexosim_io_utils::port::{IoPort, IoThread};
+
+/// Number of messages sent per iteration.
+const BATCH: u64 = 10_000;
+
+/// Port discarding the written data.
+struct NullPort;
+
+impl IoPort<UdpSocket, (), u64> for NullPort {
+    fn register(&mut self, _: &Registry) -> IoResult<Token> {
+        Ok(Token(0))
+    }
+
+    fn read(&mut self, _: Token) -> IoResult<()> {
+        Err(ErrorKind::WouldBlock.into())
+    }
+
+    fn write(&mut self, data: &u64) -> IoResult<()> {
+        black_box(data);
+
+        Ok(())
+    }
+}
+
+fn send(c: &mut Criterion) {
+    let mut io_thread = IoThread::new(NullPort);
+
+    let mut group = c.benchmark_group("io_thread");
+    group.throughput(Throughput::Elements(BATCH));
+    group.bench_function("send", |b| {
+        b.iter(|| {
+            for i in 0..BATCH {
+                io_thread.send(i).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, send);
+criterion_main!(benches);
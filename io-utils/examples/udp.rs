@@ -1,23 +1,49 @@
-//! Example: an I/O thread to communicate using UDP.
+//! Example: a simulation communicating with an echo server using UDP.
+//!
+//! This example demonstrates in particular:
+//!
+//! * generic external port model,
+//! * infinite simulation,
+//! * blocking event queue,
+//! * simulation halting,
+//! * system clock.
+//!
+//! ```text
+//!                             ┏━━━━━━━━━━━━━━━━━━━━━━━┓
+//!                             ┃ Simulation            ┃
+//! ┌╌╌╌╌╌╌╌╌╌╌╌╌╌┐             ┃  ┌────────────┐       ┃
+//! ┆ Echo server ┆    data►    ┃  │            │ data  ┃ EventQueue
+//! ┆ thread      ┆◄╌╌╌╌╌╌╌╌╌╌╌╌╌╂╌►│ UDP port   ├───────╂──────────►
+//! ┆             ┆   ◄data     ┃  │            │       ┃
+//! └╌╌╌╌╌╌╌╌╌╌╌╌╌┘   [UDP]     ┃  └────────────┘       ┃
+//!                             ┗━━━━━━━━━━━━━━━━━━━━━━━┛
+//! ```
 
-use std::error::Error;
 use std::io::{ErrorKind, Result as IoResult};
 use std::net::{SocketAddr, UdpSocket as StdUdpSocket};
-use std::thread::{self, sleep};
+use std::thread;
 use std::time::Duration;
 
 use bytes::{Bytes, BytesMut};
 use mio::net::UdpSocket;
 use mio::{Interest, Registry, Token};
+use nexosim::ports::EventQueue;
+use nexosim::simulation::{ExecutionError, Mailbox, SimInit, SimulationError};
+use nexosim::time::{AutoSystemClock, MonotonicTime};
+use nexosim_util::joiners::{SimulationJoiner, ThreadJoiner};
 
-use nexosim_util::joiners::ThreadJoiner;
-
-use nexosim_io_utils::port::{IoPort, IoThread, TryRecvError};
+use nexosim_io_utils::external::{ExternalPort, ExternalPortConfig, ProtoExternalPort};
+use nexosim_io_utils::port::IoPort;
 
 const IO_THREAD_ADDR: &str = "127.0.0.1:34254";
 const ECHO_THREAD_ADDR: &str = "127.0.0.1:34255";
 const BUF_SIZE: usize = 65536;
 
+/// Activation period, in milliseconds, for cyclic activities inside the simulation.
+const PERIOD: u64 = 10;
+/// Reader timeout.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Data to be sent through the interface.
 #[derive(Clone, Debug, PartialEq)]
 struct Data {
@@ -42,11 +68,10 @@ impl Udp {
 }
 
 impl IoPort<UdpSocket, Data, Data> for Udp {
-    fn register(&mut self, registry: &Registry) -> Token {
-        registry
-            .register(&mut self.socket, Token(0), Interest::READABLE)
-            .unwrap();
-        Token(1)
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        registry.register(&mut self.socket, Token(0), Interest::READABLE)?;
+
+        Ok(Token(1))
     }
 
     fn read(&mut self, token: Token) -> IoResult<Data> {
@@ -84,55 +109,88 @@ impl IoPort<UdpSocket, Data, Data> for Udp {
     }
 }
 
-/// Uses I/O thread to send data to echo UDP server.
-fn main() -> Result<(), Box<dyn Error>> {
-    // UDP I/O port.
-    let udp = Udp::new(IO_THREAD_ADDR.parse()?);
-
-    // I/O thread handling I/O port operations.
-    let mut io_thread = IoThread::new(udp);
+/// Uses a simulation to send data to an echo UDP server.
+fn main() -> Result<(), SimulationError> {
+    // ---------------
+    // Bench assembly.
+    // ---------------
 
     // Echo UDP server.
-    let echo_thread = ThreadJoiner::new(thread::spawn(|| -> std::io::Result<Bytes> {
-        let socket = StdUdpSocket::bind(ECHO_THREAD_ADDR)?;
+    let socket = StdUdpSocket::bind(ECHO_THREAD_ADDR).unwrap();
+    let echo_thread = ThreadJoiner::new(thread::spawn(move || -> std::io::Result<Bytes> {
         let mut buf = [0; BUF_SIZE];
         let (len, addr) = socket.recv_from(&mut buf)?;
-        sleep(Duration::from_secs(2));
         socket.send_to(&buf[..len], addr)?;
         Ok(BytesMut::from(&buf[..len]).into())
     }));
 
+    // The UDP port model.
+    let udp = Udp::new(IO_THREAD_ADDR.parse().unwrap());
+    let mut port = ProtoExternalPort::new(udp, get_port_cfg());
+
+    // Mailboxes.
+    let port_mbox = Mailbox::new();
+    let port_addr = port_mbox.address();
+
+    // Model handles for simulation.
+    let observer = EventQueue::new();
+    port.data_out.connect_sink(&observer);
+    let mut observer = observer.into_reader_with_timeout(TIMEOUT);
+
+    // Start time (arbitrary since models do not depend on absolute time).
+    let t0 = MonotonicTime::EPOCH;
+
+    // Assembly and initialization.
+    let (mut simu, scheduler) = SimInit::new()
+        .add_model(port, port_mbox, "udp")
+        .set_clock(AutoSystemClock::new())
+        .init(t0)?;
+
+    // Simulation thread.
+    let simulation_handle = SimulationJoiner::new(
+        scheduler.clone(),
+        thread::spawn(move || {
+            // ---------- Simulation.  ----------
+            simu.step_unbounded()
+        }),
+    );
+
     // Data to be sent.
     let data = Data {
         addr: ECHO_THREAD_ADDR.parse().unwrap(),
         bytes: BytesMut::from([1_u8, 2, 3].as_slice()).into(),
     };
 
-    // Wait to be sure that server has been started, in real-life some
-    // synchronization should be done instead of a sleep.
-    sleep(Duration::from_secs(1));
-
     // Send data via UDP.
-    io_thread.send(data.clone())?;
-
-    // It is not possible to return value from a for loop, so we are using a
-    // counter.
-    let mut counter = 5;
-    // Try to receive data echoed by the server.
-    let echoed = loop {
-        if counter <= 0 {
-            break Err(TryRecvError::Empty);
-        }
-        match io_thread.try_recv() {
-            Ok(data) => break Ok(data),
-            Err(TryRecvError::Empty) => {}
-            Err(error) => break Err(error),
-        }
-        counter -= 1;
-        sleep(Duration::from_secs(1));
-    }?;
+    scheduler.schedule_event(
+        Duration::from_millis(1),
+        ExternalPort::data_in,
+        data.clone(),
+        port_addr,
+    )?;
+
+    // Wait for the data echoed by the server.
+    let echoed = observer
+        .next()
+        .expect("Unexpected timeout or simulation halt!");
+
+    // Stop the simulation.
+    match simulation_handle.halt().unwrap() {
+        Err(ExecutionError::Halted) => {}
+        Err(e) => return Err(e.into()),
+        _ => {}
+    }
 
     assert_eq!(data, echoed);
-    assert_eq!(data.bytes, echo_thread.join().unwrap()?);
+    assert_eq!(data.bytes, echo_thread.join().unwrap().unwrap());
+
     Ok(())
 }
+
+/// Gets UDP port configuration.
+fn get_port_cfg() -> ExternalPortConfig {
+    ExternalPortConfig::builder()
+        .period_ms(PERIOD)
+        .build()
+        .unwrap()
+}
@@ -0,0 +1,172 @@
+//! Wall-clock time alignment.
+//!
+//! This module contains the [`TimeAlignment`] mapping, which converts the
+//! wall-clock receive timestamps taken by an I/O thread into simulation times.
+//! Port models use it to inject the data received from real devices at the
+//! simulation time matching its arrival rather than at their next periodic
+//! activity, so that the inter-arrival timing of the devices is preserved in
+//! the simulated timeline.
+//!
+//! The simulation is expected to be synchronized with the system clock, e.g.
+//! with a `SystemClock`, from its initialization: the wall-clock time at which
+//! a model creates its mapping, when it is initialized, is taken to correspond
+//! to the simulation time at that moment.
+//!
+//! Since the data received between two periodic activities arrived in the past
+//! of the simulation, it is injected with a constant delay, which should be at
+//! least the period of the activities plus their scheduling jitter. Data whose
+//! aligned simulation time has already passed is injected immediately.
+//!
+//! #### Examples
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_io_utils::alignment::TimeAlignmentConfig;
+//! use nexosim_io_utils::duration::ConfigDuration;
+//!
+//! let config = ConfigLoader::<TimeAlignmentConfig>::new()
+//!     .code(
+//!         r#"
+//! enabled = true
+//! delay = "15ms"
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert!(config.enabled);
+//! assert_eq!(config.delay.map(Duration::from), Some(Duration::from_millis(15)));
+//! ```
+
+use std::time::{Duration, Instant, SystemTime};
+
+use schematic::Config;
+
+use nexosim::time::MonotonicTime;
+
+use crate::duration::ConfigDuration;
+
+/// Time alignment configuration.
+#[derive(Clone, Config, Debug)]
+pub struct TimeAlignmentConfig {
+    /// Inject the received data at the simulation time matching its
+    /// wall-clock arrival time.
+    #[setting(default = false)]
+    pub enabled: bool,
+
+    /// Delay between the arrival of the data and its injection into the
+    /// simulation.
+    ///
+    /// If no value is provided, the period of the model is used.
+    pub delay: Option<ConfigDuration>,
+}
+
+impl TimeAlignmentConfig {
+    /// Returns the time alignment mapping, if enabled.
+    ///
+    /// The current wall-clock time is taken to correspond to the simulation
+    /// time `time`. The injection delay defaults to `period`, or to zero if
+    /// no period is provided either.
+    pub fn alignment(
+        &self,
+        time: MonotonicTime,
+        period: Option<ConfigDuration>,
+    ) -> Option<TimeAlignment> {
+        self.enabled.then(|| {
+            let delay = self.delay.or(period).map(Duration::from);
+
+            TimeAlignment::new(time, delay.unwrap_or_default())
+        })
+    }
+}
+
+impl From<TimeAlignmentConfig> for PartialTimeAlignmentConfig {
+    fn from(config: TimeAlignmentConfig) -> Self {
+        Self {
+            enabled: Some(config.enabled),
+            delay: config.delay,
+        }
+    }
+}
+
+/// Mapping from wall-clock arrival times to simulation times.
+#[derive(Clone, Debug)]
+pub struct TimeAlignment {
+    /// Simulation time of reference.
+    time: MonotonicTime,
+
+    /// Monotonic wall-clock time of reference.
+    instant: Instant,
+
+    /// System time of reference.
+    system_time: SystemTime,
+
+    /// Injection delay.
+    delay: Duration,
+}
+
+impl TimeAlignment {
+    /// Creates a mapping taking the current wall-clock time as the simulation
+    /// time `time`, with the specified injection delay.
+    pub fn new(time: MonotonicTime, delay: Duration) -> Self {
+        Self {
+            time,
+            instant: Instant::now(),
+            system_time: SystemTime::now(),
+            delay,
+        }
+    }
+
+    /// Returns the injection delay.
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Returns the simulation time at which data which arrived at the
+    /// specified instant should be injected.
+    ///
+    /// `None` is returned if this time is not later than `now`, in which case
+    /// the data should be injected immediately.
+    pub fn deadline(&self, arrival: Instant, now: MonotonicTime) -> Option<MonotonicTime> {
+        match arrival.checked_duration_since(self.instant) {
+            Some(elapsed) => self.aligned(elapsed, Duration::ZERO, now),
+            None => self.aligned(Duration::ZERO, self.instant - arrival, now),
+        }
+    }
+
+    /// Returns the simulation time at which data which arrived at the
+    /// specified system time should be injected.
+    ///
+    /// `None` is returned if this time is not later than `now`, in which case
+    /// the data should be injected immediately.
+    pub fn deadline_from_system_time(
+        &self,
+        arrival: SystemTime,
+        now: MonotonicTime,
+    ) -> Option<MonotonicTime> {
+        match arrival.duration_since(self.system_time) {
+            Ok(elapsed) => self.aligned(elapsed, Duration::ZERO, now),
+            Err(e) => self.aligned(Duration::ZERO, e.duration(), now),
+        }
+    }
+
+    /// Returns the injection time of data which arrived `elapsed` after or
+    /// `early` before the time of reference, if later than `now`.
+    fn aligned(
+        &self,
+        elapsed: Duration,
+        early: Duration,
+        now: MonotonicTime,
+    ) -> Option<MonotonicTime> {
+        self.time
+            .checked_add(elapsed + self.delay)?
+            .checked_sub(early)
+            .filter(|&deadline| deadline > now)
+    }
+}
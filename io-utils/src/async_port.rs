@@ -0,0 +1,234 @@
+//! Asynchronous ports I/O utilities.
+//!
+//! This module contains a tokio-based alternative to the MIO-based
+//! [`IoThread`](crate::port::IoThread), so that transports which only exist as
+//! async crates can be bridged into a simulation. It is available with the
+//! `tokio` feature.
+//!
+//! The [`AsyncIoThread`] structure spawns a thread running a single-threaded
+//! tokio runtime, which opens and drives an implementor of the
+//! [`AsyncIoPort`] trait. The port is opened by the I/O thread since tokio
+//! I/O resources must be created within a runtime.
+//!
+//! The I/O thread provides the same interface to the model as an
+//! [`IoThread`](crate::port::IoThread):
+//!
+//! * [`AsyncIoThread::try_recv`] that tries to receive data from the external
+//!   port, or [`AsyncIoThread::try_recv_all`] that receives all available
+//!   data at once,
+//! * [`AsyncIoThread::send`] that sends data to the external port,
+//! * [`AsyncIoThread::try_recv_status`] that tries to receive the status of
+//!   the I/O thread.
+//!
+//! #### Examples
+//!
+//! Asynchronous I/O port that uses UDP for communication with the external
+//! world:
+//!
+//! ```
+//! use std::io::Result as IoResult;
+//! use std::net::SocketAddr;
+//!
+//! use tokio::net::UdpSocket;
+//!
+//! use nexosim_io_utils::async_port::{AsyncIoPort, AsyncIoThread};
+//!
+//! /// UDP port.
+//! struct Udp {
+//!     socket: UdpSocket,
+//!     buffer: Vec<u8>,
+//! }
+//!
+//! impl AsyncIoPort<(SocketAddr, Vec<u8>), (SocketAddr, Vec<u8>)> for Udp {
+//!     async fn read(&mut self) -> IoResult<(SocketAddr, Vec<u8>)> {
+//!         let (len, addr) = self.socket.recv_from(&mut self.buffer).await?;
+//!
+//!         Ok((addr, self.buffer[..len].to_vec()))
+//!     }
+//!
+//!     async fn write(&mut self, (addr, data): (SocketAddr, Vec<u8>)) -> IoResult<()> {
+//!         self.socket.send_to(&data, addr).await.map(|_| ())
+//!     }
+//! }
+//!
+//! let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+//! let io_thread = AsyncIoThread::new(move || async move {
+//!     Ok(Udp {
+//!         socket: UdpSocket::bind(addr).await?,
+//!         buffer: vec![0; 256],
+//!     })
+//! });
+//! # let _ = io_thread;
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::io::Result as IoResult;
+use std::sync::mpsc::{Receiver, channel};
+use std::thread;
+
+use tokio::runtime::Builder;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+
+use nexosim_util::joiners::ThreadJoiner;
+
+use crate::port::{IoThreadStatus, SendError, TryRecvError};
+use crate::teardown::IoThreadGuard;
+
+/// Asynchronous I/O port.
+pub trait AsyncIoPort<R, T>
+where
+    R: Send,
+    T: Send,
+{
+    /// Reads data.
+    ///
+    /// The returned future is dropped whenever data is sent to the port, so
+    /// it must be cancel safe. Errors stop the I/O thread; a port which
+    /// reached its end of file should return
+    /// [`ErrorKind::UnexpectedEof`](std::io::ErrorKind::UnexpectedEof).
+    fn read(&mut self) -> impl Future<Output = IoResult<R>> + Send;
+
+    /// Writes data.
+    ///
+    /// Errors stop the I/O thread.
+    fn write(&mut self, data: T) -> impl Future<Output = IoResult<()>> + Send;
+}
+
+/// Asynchronous I/O thread.
+pub struct AsyncIoThread<R, T>
+where
+    R: Send,
+    T: Send,
+{
+    /// Data sender.
+    // This field must precede the thread handle so the channel is closed,
+    // which halts the I/O thread, before join.
+    transmitter: UnboundedSender<T>,
+
+    /// I/O thread handle.
+    _io_thread: ThreadJoiner<()>,
+
+    /// Live I/O thread accounting.
+    // This field must follow the thread handle so it is dropped after join.
+    _guard: IoThreadGuard,
+
+    /// Data receiver.
+    receiver: Receiver<R>,
+
+    /// I/O thread status receiver.
+    status: Receiver<IoThreadStatus>,
+}
+
+impl<R, T> AsyncIoThread<R, T>
+where
+    R: Send + 'static,
+    T: Send + 'static,
+{
+    /// Creates new asynchronous I/O thread, which opens its port with the
+    /// provided function.
+    ///
+    /// A failure to open the port is reported as a read error.
+    pub fn new<F, O, P>(open: F) -> Self
+    where
+        F: FnOnce() -> O + Send + 'static,
+        O: Future<Output = IoResult<P>>,
+        P: AsyncIoPort<R, T>,
+    {
+        let (tx, receiver) = channel();
+        let (transmitter, mut rx) = unbounded_channel();
+        let (status_tx, status) = channel();
+
+        // I/O thread.
+        let io_thread = thread::spawn(move || {
+            let runtime = match Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    let _ = status_tx.send(IoThreadStatus::read_error(e));
+                    let _ = status_tx.send(IoThreadStatus::Exited);
+                    return;
+                }
+            };
+            runtime.block_on(async {
+                let mut port = match open().await {
+                    Ok(port) => port,
+                    Err(e) => {
+                        let _ = status_tx.send(IoThreadStatus::read_error(e));
+                        return;
+                    }
+                };
+                loop {
+                    tokio::select! {
+                        data = rx.recv() => {
+                            // The channel is closed when the model is dropped.
+                            let Some(data) = data else {
+                                break;
+                            };
+                            if let Err(e) = port.write(data).await {
+                                let _ = status_tx.send(IoThreadStatus::write_error(e));
+                                break;
+                            }
+                        }
+                        message = port.read() => match message {
+                            Ok(message) => {
+                                if tx.send(message).is_err() {
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                let _ = status_tx.send(IoThreadStatus::read_error(e));
+                                break;
+                            }
+                        },
+                    }
+                }
+            });
+            let _ = status_tx.send(IoThreadStatus::Exited);
+        });
+        Self {
+            transmitter,
+            _io_thread: ThreadJoiner::new(io_thread),
+            _guard: IoThreadGuard::new(),
+            receiver,
+            status,
+        }
+    }
+
+    /// Tries to receives data from I/O thread.
+    pub fn try_recv(&self) -> Result<R, TryRecvError> {
+        Ok(self.receiver.try_recv()?)
+    }
+
+    /// Receives all data currently available from I/O thread.
+    ///
+    /// The returned vector is empty if no data is available, including once
+    /// the I/O thread has exited.
+    pub fn try_recv_all(&self) -> Vec<R> {
+        self.receiver.try_iter().collect()
+    }
+
+    /// Tries to receive the status of the I/O thread.
+    ///
+    /// Once the I/O thread has exited and all statuses have been received,
+    /// [`TryRecvError::Disconnected`] is returned.
+    pub fn try_recv_status(&self) -> Result<IoThreadStatus, TryRecvError> {
+        Ok(self.status.try_recv()?)
+    }
+
+    /// Sends data to I/O thread.
+    pub fn send(&mut self, data: T) -> Result<(), SendError> {
+        self.transmitter
+            .send(data)
+            .map_err(|_| SendError::Disonnected)
+    }
+}
+
+impl<R, T> fmt::Debug for AsyncIoThread<R, T>
+where
+    R: Send,
+    T: Send,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AsyncIoThread").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,178 @@
+//! Bit error rate emulation.
+//!
+//! This module contains the [`BerChannel`] model, which can be inserted
+//! between two models to flip bits of the items they exchange, e.g. to
+//! exercise the CRC or FEC handling of a decoder chain.
+//!
+//! Bit errors follow the Gilbert–Elliott model: the channel alternates
+//! between a good state and a bad (burst) state, each with its own bit error
+//! rate, and changes state after each bit with a configured probability. With
+//! the default burst start probability of 0, the channel stays in the good
+//! state and bit errors are independent.
+//!
+//! Bit errors are drawn from a random number generator seeded from the
+//! configuration, so that a run can be reproduced exactly from its seed.
+//!
+//! #### Examples
+//!
+//! ```
+//! use bytes::Bytes;
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_io_utils::ber::{BerChannel, BerChannelConfig};
+//!
+//! // Bursts of 100 bits on average, every 1e5 bits on average.
+//! let config = ConfigLoader::<BerChannelConfig>::new()
+//!     .code(
+//!         r#"
+//! seed = 1
+//! bitErrorRate = 1e-7
+//! burstBitErrorRate = 0.1
+//! burstStartProbability = 1e-5
+//! burstEndProbability = 0.01
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! // Connect the output of the sending model to `BerChannel::data_in`, and
+//! // `BerChannel::data_out` to the input of the receiving model.
+//! let channel = BerChannel::<Bytes>::new(config);
+//! # let _ = channel;
+//! ```
+
+use std::fmt;
+
+use schematic::Config;
+
+use nexosim::model::Model;
+use nexosim::ports::Output;
+
+use crate::fault::Faulty;
+use crate::rng::Rng;
+
+/// Bit error rate channel model instance configuration.
+///
+/// Rates and probabilities range from 0 to 1 and apply to each bit.
+#[derive(Clone, Config, Debug)]
+pub struct BerChannelConfig {
+    /// Seed of the random number generator.
+    pub seed: u64,
+
+    /// Bit error rate in the good state.
+    pub bit_error_rate: f64,
+
+    /// Bit error rate in the bad state.
+    #[setting(default = 0.5)]
+    pub burst_bit_error_rate: f64,
+
+    /// Probability of a transition from the good state to the bad state.
+    pub burst_start_probability: f64,
+
+    /// Probability of a transition from the bad state to the good state.
+    #[setting(default = 1.0)]
+    pub burst_end_probability: f64,
+}
+
+/// Bit error rate channel model.
+///
+/// This model forwards the items of its input to its output, flipping bits of
+/// their payload according to the Gilbert–Elliott model. The channel state
+/// is kept from one item to the next, so a burst may span several items.
+pub struct BerChannel<T>
+where
+    T: Faulty + Send + 'static,
+{
+    /// Forwarded item -- output port.
+    pub data_out: Output<T>,
+
+    /// Number of bits flipped in an item, if any -- output port.
+    pub bit_errors_out: Output<u64>,
+
+    /// Model instance configuration.
+    config: BerChannelConfig,
+
+    /// Random number generator.
+    rng: Rng,
+
+    /// The channel is in the bad state.
+    is_bad: bool,
+}
+
+impl<T> BerChannel<T>
+where
+    T: Faulty + Send + 'static,
+{
+    /// Creates a new bit error rate channel model, in the good state.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a rate or a probability is not within 0 and
+    /// 1.
+    pub fn new(config: BerChannelConfig) -> Self {
+        for (name, probability) in [
+            ("bit error rate", config.bit_error_rate),
+            ("burst bit error rate", config.burst_bit_error_rate),
+            ("burst start probability", config.burst_start_probability),
+            ("burst end probability", config.burst_end_probability),
+        ] {
+            assert!(
+                (0.0..=1.0).contains(&probability),
+                "the {name} should be within 0 and 1"
+            );
+        }
+
+        Self {
+            data_out: Output::default(),
+            bit_errors_out: Output::default(),
+            rng: Rng::new(config.seed),
+            config,
+            is_bad: false,
+        }
+    }
+
+    /// Item to forward -- input port.
+    pub async fn data_in(&mut self, mut data: T) {
+        let mut bits = Vec::new();
+        for bit in 0..data.payload_len() * 8 {
+            let bit_error_rate = if self.is_bad {
+                self.config.burst_bit_error_rate
+            } else {
+                self.config.bit_error_rate
+            };
+            if self.rng.chance(bit_error_rate) {
+                bits.push(bit);
+            }
+            let transition_probability = if self.is_bad {
+                self.config.burst_end_probability
+            } else {
+                self.config.burst_start_probability
+            };
+            if self.rng.chance(transition_probability) {
+                self.is_bad = !self.is_bad;
+            }
+        }
+        if !bits.is_empty() {
+            data.flip_bits(&bits);
+            self.bit_errors_out.send(bits.len() as u64).await;
+        }
+        self.data_out.send(data).await;
+    }
+}
+
+impl<T> Model for BerChannel<T> where T: Faulty + Send + 'static {}
+
+impl<T> fmt::Debug for BerChannel<T>
+where
+    T: Faulty + Send + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BerChannel")
+            .field("config", &self.config)
+            .field("is_bad", &self.is_bad)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,287 @@
+//! Blocking ports I/O utilities.
+//!
+//! This module contains an alternative to the MIO-based
+//! [`IoThread`](crate::port::IoThread) for devices which only offer blocking
+//! APIs without a pollable file descriptor, e.g. vendor USB SDKs.
+//!
+//! The [`BlockingIoThread`] structure shares an implementor of the
+//! [`BlockingIoPort`] trait between a dedicated reader thread and a dedicated
+//! writer thread, and provides the same interface to the model as an
+//! [`IoThread`](crate::port::IoThread):
+//!
+//! * [`BlockingIoThread::try_recv`] that tries to receive data from the
+//!   external port, or [`BlockingIoThread::try_recv_all`] that receives all
+//!   available data at once,
+//! * [`BlockingIoThread::send`] that sends data to the external port,
+//! * [`BlockingIoThread::try_recv_status`] that tries to receive the status of
+//!   the I/O threads.
+//!
+//! Since a blocking read cannot be interrupted, the reader thread can only be
+//! joined once [`BlockingIoPort::read`] returns: ports should configure a read
+//! timeout so that the I/O threads are dropped in a timely manner.
+//!
+//! #### Examples
+//!
+//! Blocking I/O port that uses UDP for communication with the external world:
+//!
+//! ```
+//! use std::io::Result as IoResult;
+//! use std::net::UdpSocket;
+//! use std::time::Duration;
+//!
+//! use nexosim_io_utils::blocking::{BlockingIoPort, BlockingIoThread};
+//!
+//! /// UDP port.
+//! struct Udp {
+//!     socket: UdpSocket,
+//! }
+//!
+//! impl BlockingIoPort<Vec<u8>, Vec<u8>> for Udp {
+//!     fn read(&self) -> IoResult<Vec<u8>> {
+//!         let mut buffer = vec![0; 256];
+//!         let len = self.socket.recv(&mut buffer)?;
+//!         buffer.truncate(len);
+//!
+//!         Ok(buffer)
+//!     }
+//!
+//!     fn write(&self, data: &Vec<u8>) -> IoResult<()> {
+//!         self.socket.send(data).map(|_| ())
+//!     }
+//! }
+//!
+//! let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+//! socket.connect(socket.local_addr().unwrap()).unwrap();
+//! socket
+//!     .set_read_timeout(Some(Duration::from_millis(10)))
+//!     .unwrap();
+//!
+//! let mut io_thread = BlockingIoThread::new(Udp { socket });
+//! io_thread.send(vec![1, 2, 3]).unwrap();
+//! ```
+
+use std::fmt;
+use std::io::{ErrorKind, Result as IoResult};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+
+use nexosim_util::joiners::ThreadJoiner;
+
+use crate::port::{IoThreadError, IoThreadStatus, SendError, TryRecvError};
+use crate::teardown::IoThreadGuard;
+
+/// Blocking I/O port.
+///
+/// The port is shared by the reader and the writer threads, so reads and
+/// writes may be called concurrently.
+pub trait BlockingIoPort<R, T>: Send + Sync
+where
+    R: Send,
+    T: Send,
+{
+    /// Reads data, blocking until data is available.
+    ///
+    /// [`ErrorKind::WouldBlock`] or [`ErrorKind::TimedOut`] should be returned
+    /// when no data is available after a read timeout, which lets the reader
+    /// thread exit when the I/O threads are dropped. Other errors stop the I/O
+    /// threads; a port which reached its end of file should return
+    /// [`ErrorKind::UnexpectedEof`].
+    fn read(&self) -> IoResult<R>;
+
+    /// Writes data, blocking until it is written.
+    ///
+    /// Errors stop the I/O threads.
+    fn write(&self, data: &T) -> IoResult<()>;
+}
+
+/// Blocking I/O threads.
+pub struct BlockingIoThread<R, T>
+where
+    R: Send,
+    T: Send,
+{
+    /// Data sender.
+    // This field must precede the thread handles so the channel is closed,
+    // which halts the writer thread, before join.
+    transmitter: Sender<T>,
+
+    /// Reader thread handle.
+    _reader: ThreadJoiner<()>,
+
+    /// Writer thread handle.
+    _writer: ThreadJoiner<()>,
+
+    /// Live I/O thread accounting.
+    // This field must follow the thread handles so it is dropped after join.
+    _guard: IoThreadGuard,
+
+    /// Data receiver.
+    receiver: Receiver<R>,
+
+    /// I/O threads status receiver.
+    status: Receiver<IoThreadStatus>,
+
+    /// Halted flag, set when the model is dropped or an I/O thread stops.
+    is_halted: Arc<AtomicBool>,
+}
+
+impl<R, T> BlockingIoThread<R, T>
+where
+    R: Send + 'static,
+    T: Send + 'static,
+{
+    /// Creates new blocking I/O threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the I/O threads cannot be spawned, see
+    /// [`BlockingIoThread::try_new`].
+    pub fn new<P>(port: P) -> Self
+    where
+        P: BlockingIoPort<R, T> + 'static,
+    {
+        Self::try_new(port).unwrap()
+    }
+
+    /// Creates new blocking I/O threads, or returns an error if a thread
+    /// cannot be spawned.
+    pub fn try_new<P>(port: P) -> Result<Self, IoThreadError>
+    where
+        P: BlockingIoPort<R, T> + 'static,
+    {
+        let (tx, receiver) = channel();
+        let (transmitter, rx) = channel::<T>();
+        let (status_tx, status) = channel();
+
+        let port = Arc::new(port);
+        let is_halted = Arc::new(AtomicBool::new(false));
+        // Number of I/O threads not yet exited.
+        let live = Arc::new(AtomicUsize::new(2));
+
+        // Reader thread.
+        let reader = {
+            let port = port.clone();
+            let is_halted = is_halted.clone();
+            let live = live.clone();
+            let status_tx = status_tx.clone();
+            thread::Builder::new().spawn(move || {
+                while !is_halted.load(Ordering::Relaxed) {
+                    match port.read() {
+                        Ok(message) => {
+                            if tx.send(message).is_err() {
+                                break;
+                            }
+                        }
+                        Err(ref e)
+                            if matches!(
+                                e.kind(),
+                                ErrorKind::WouldBlock
+                                    | ErrorKind::TimedOut
+                                    | ErrorKind::Interrupted
+                            ) => {}
+                        Err(e) => {
+                            let _ = status_tx.send(IoThreadStatus::read_error(e));
+                            break;
+                        }
+                    }
+                }
+                exit(&is_halted, &live, &status_tx);
+            })
+        };
+        let reader = ThreadJoiner::new(reader.map_err(IoThreadError::Spawn)?);
+
+        // Writer thread.
+        let writer = {
+            let is_halted = is_halted.clone();
+            thread::Builder::new().spawn(move || {
+                // The channel is closed when the model is dropped.
+                for data in rx {
+                    if is_halted.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    if let Err(e) = port.write(&data) {
+                        let _ = status_tx.send(IoThreadStatus::write_error(e));
+                        break;
+                    }
+                }
+                exit(&is_halted, &live, &status_tx);
+            })
+        };
+        let writer = match writer {
+            Ok(writer) => ThreadJoiner::new(writer),
+            Err(e) => {
+                // Let the reader thread exit before it is joined.
+                is_halted.store(true, Ordering::Relaxed);
+                return Err(IoThreadError::Spawn(e));
+            }
+        };
+
+        Ok(Self {
+            transmitter,
+            _reader: reader,
+            _writer: writer,
+            _guard: IoThreadGuard::new(),
+            receiver,
+            status,
+            is_halted,
+        })
+    }
+
+    /// Tries to receives data from I/O threads.
+    pub fn try_recv(&self) -> Result<R, TryRecvError> {
+        Ok(self.receiver.try_recv()?)
+    }
+
+    /// Receives all data currently available from I/O threads.
+    ///
+    /// The returned vector is empty if no data is available, including once
+    /// the I/O threads have exited.
+    pub fn try_recv_all(&self) -> Vec<R> {
+        self.receiver.try_iter().collect()
+    }
+
+    /// Tries to receive the status of the I/O threads.
+    ///
+    /// [`IoThreadStatus::Exited`] is sent once both threads have exited; after
+    /// a read error, the writer thread only exits when data is next sent or
+    /// when the I/O threads are dropped. Once all statuses have been received,
+    /// [`TryRecvError::Disconnected`] is returned.
+    pub fn try_recv_status(&self) -> Result<IoThreadStatus, TryRecvError> {
+        Ok(self.status.try_recv()?)
+    }
+
+    /// Sends data to I/O threads.
+    pub fn send(&mut self, data: T) -> Result<(), SendError> {
+        Ok(self.transmitter.send(data)?)
+    }
+}
+
+impl<R, T> Drop for BlockingIoThread<R, T>
+where
+    R: Send,
+    T: Send,
+{
+    fn drop(&mut self) {
+        self.is_halted.store(true, Ordering::Relaxed);
+    }
+}
+
+impl<R, T> fmt::Debug for BlockingIoThread<R, T>
+where
+    R: Send,
+    T: Send,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BlockingIoThread").finish_non_exhaustive()
+    }
+}
+
+/// Halts the other I/O thread and reports the exit of the last one.
+fn exit(is_halted: &AtomicBool, live: &AtomicUsize, status_tx: &Sender<IoThreadStatus>) {
+    is_halted.store(true, Ordering::Relaxed);
+    if live.fetch_sub(1, Ordering::AcqRel) == 1 {
+        let _ = status_tx.send(IoThreadStatus::Exited);
+    }
+}
@@ -0,0 +1,597 @@
+//! Shared port broker.
+//!
+//! Physical devices such as serial ports or CAN adapters can usually be opened
+//! by a single process at a time. A [`SharedPortBroker`] owns such a device and
+//! multiplexes access to it for several simulation processes over a Unix
+//! domain socket:
+//!
+//! * items read from the device are forwarded to every connected client whose
+//!   filters accept them,
+//! * items sent by any client are written to the device.
+//!
+//! On the simulation side, a [`BrokerClient`] is an [`IoPort`] implementation
+//! which can be used in place of the device in an [`IoThread`].
+//!
+//! Items are serialized with a [`BrokerCodec`] and exchanged as messages
+//! prefixed with their length as a little-endian `u32`, followed by a message
+//! kind byte. Items forwarded to a client which does not keep up with them are
+//! dropped once its pending data exceeds a fixed size.
+//!
+//! [`IoThread`]: crate::port::IoThread
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{ErrorKind, Read, Result as IoResult, Write};
+use std::os::unix::net::UnixStream as StdUnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use mio::event::Source;
+use mio::net::{UnixListener, UnixStream};
+use mio::{Events, Interest, Poll, Registry, Token, Waker};
+
+use nexosim_util::joiners::ThreadJoiner;
+
+use crate::port::{IoPort, WriteBuffer, poll_timeout};
+
+/// Data message kind.
+const DATA_MESSAGE: u8 = 0;
+
+/// Subscription message kind.
+const SUBSCRIBE_MESSAGE: u8 = 1;
+
+/// Maximum accepted message size.
+const MAX_MESSAGE_SIZE: usize = 1 << 20;
+
+/// Maximum size of the data not yet sent to a client, beyond which the items
+/// forwarded to the client are dropped.
+const MAX_PENDING_SIZE: usize = 4 * MAX_MESSAGE_SIZE;
+
+/// Item serialization for the broker protocol.
+pub trait BrokerCodec<T>: Send + 'static {
+    /// Serializes an item, appending it to the buffer.
+    ///
+    /// Items which cannot be serialized are not forwarded.
+    fn encode(&self, item: &T, buf: &mut Vec<u8>) -> IoResult<()>;
+
+    /// Deserializes an item.
+    fn decode(&self, buf: &[u8]) -> IoResult<T>;
+
+    /// Returns the key used to filter an item.
+    fn key(&self, _item: &T) -> u32 {
+        0
+    }
+}
+
+/// Client filter.
+///
+/// An item is accepted if its key matches the filter key on all bits set in the
+/// mask. A client without filters accepts all items.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BrokerFilter {
+    /// Filter key.
+    pub key: u32,
+
+    /// Filter mask.
+    pub mask: u32,
+}
+
+impl BrokerFilter {
+    /// Checks whether the filter accepts the key.
+    pub fn accepts(&self, key: u32) -> bool {
+        key & self.mask == self.key & self.mask
+    }
+}
+
+/// Appends a message to the buffer.
+fn put_message(buf: &mut Vec<u8>, kind: u8, payload: &[u8]) {
+    buf.extend_from_slice(&(payload.len() as u32 + 1).to_le_bytes());
+    buf.push(kind);
+    buf.extend_from_slice(payload);
+}
+
+/// Removes the first complete message from the buffer, if any.
+fn take_message(buf: &mut Vec<u8>) -> IoResult<Option<(u8, Vec<u8>)>> {
+    let Some(header) = buf.get(..4) else {
+        return Ok(None);
+    };
+    let len = u32::from_le_bytes(header.try_into().unwrap()) as usize;
+    if len == 0 || len > MAX_MESSAGE_SIZE {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "Invalid broker message length.",
+        ));
+    }
+    if buf.len() < 4 + len {
+        return Ok(None);
+    }
+    let kind = buf[4];
+    let payload = buf[5..4 + len].to_vec();
+    buf.drain(..4 + len);
+
+    Ok(Some((kind, payload)))
+}
+
+/// Reads available data into the buffer until the source would block.
+///
+/// Returns `false` if the peer closed the connection.
+fn fill_buffer<R: Read>(source: &mut R, buf: &mut Vec<u8>) -> IoResult<bool> {
+    let mut chunk = [0; 4096];
+    loop {
+        match source.read(&mut chunk) {
+            Ok(0) => return Ok(false),
+            Ok(len) => buf.extend_from_slice(&chunk[..len]),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => return Ok(true),
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Client connection as seen by the broker.
+struct Connection {
+    /// Client socket.
+    stream: UnixStream,
+
+    /// Client filters.
+    filters: Vec<BrokerFilter>,
+
+    /// Received data not yet processed.
+    rx_buf: Vec<u8>,
+
+    /// Data not yet sent.
+    tx: WriteBuffer,
+}
+
+impl Connection {
+    /// Checks whether the client accepts the key.
+    fn accepts(&self, key: u32) -> bool {
+        self.filters.is_empty() || self.filters.iter().any(|filter| filter.accepts(key))
+    }
+}
+
+/// Shared port broker.
+///
+/// This is a thread guard: the broker thread is spawned by the constructor and
+/// joined on drop.
+pub struct SharedPortBroker {
+    /// Broker thread handle.
+    // This field must precede waker in order for drop to work properly.
+    _broker_thread: ThreadJoiner<()>,
+
+    /// Thread waker.
+    waker: Arc<Waker>,
+
+    /// Broker halted flag.
+    is_halted: Arc<AtomicBool>,
+}
+
+impl SharedPortBroker {
+    /// Creates a new broker owning the port and listening on the provided
+    /// socket path.
+    ///
+    /// A stale socket file at this path is removed. An error is returned if the
+    /// port cannot be registered, if the socket cannot be bound or if the
+    /// broker thread cannot be spawned.
+    pub fn new<S, T, P, C>(socket_path: impl AsRef<Path>, mut port: P, codec: C) -> IoResult<Self>
+    where
+        S: Source + ?Sized,
+        T: Send + 'static,
+        P: IoPort<S, T, T> + Send + 'static,
+        C: BrokerCodec<T>,
+    {
+        let socket_path = socket_path.as_ref().to_path_buf();
+        let _ = std::fs::remove_file(&socket_path);
+
+        let is_halted = Arc::new(AtomicBool::new(false));
+        let broker_is_halted = is_halted.clone();
+
+        let poll = Poll::new()?;
+        let wake = port.register(poll.registry())?;
+        let waker = Arc::new(Waker::new(poll.registry(), wake)?);
+
+        let listener_token = Token(wake.0 + 1);
+        let mut listener = UnixListener::bind(&socket_path)?;
+        poll.registry()
+            .register(&mut listener, listener_token, Interest::READABLE)?;
+
+        let broker = Broker {
+            poll,
+            port,
+            codec,
+            wake,
+            listener,
+            listener_token,
+            connections: HashMap::new(),
+            next_token: listener_token.0 + 1,
+            is_halted: broker_is_halted,
+            socket_path,
+        };
+        let broker_thread = thread::Builder::new().spawn(move || broker.run())?;
+
+        Ok(Self {
+            _broker_thread: ThreadJoiner::new(broker_thread),
+            waker,
+            is_halted,
+        })
+    }
+}
+
+impl Drop for SharedPortBroker {
+    fn drop(&mut self) {
+        self.is_halted.store(true, Ordering::Relaxed);
+        let _ = self.waker.wake();
+    }
+}
+
+impl fmt::Debug for SharedPortBroker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedPortBroker").finish_non_exhaustive()
+    }
+}
+
+/// Broker thread state.
+struct Broker<P, C> {
+    poll: Poll,
+    port: P,
+    codec: C,
+    wake: Token,
+    listener: UnixListener,
+    listener_token: Token,
+    connections: HashMap<Token, Connection>,
+    next_token: usize,
+    is_halted: Arc<AtomicBool>,
+    socket_path: PathBuf,
+}
+
+impl<P, C> Broker<P, C> {
+    /// Runs the broker loop until the broker is halted or the port fails.
+    fn run<S, T>(mut self)
+    where
+        S: Source + ?Sized,
+        T: Send,
+        P: IoPort<S, T, T>,
+        C: BrokerCodec<T>,
+    {
+        let mut events = Events::with_capacity(256);
+        'poll: loop {
+            let timeout = poll_timeout(self.port.deadline(), None);
+            // This call is blocking.
+            if let Err(e) = self.poll.poll(&mut events, timeout) {
+                if e.kind() == ErrorKind::Interrupted {
+                    continue;
+                }
+                break;
+            }
+
+            for event in events.iter() {
+                let token = event.token();
+                if token == self.wake {
+                    if self.is_halted.load(Ordering::Relaxed) {
+                        break 'poll;
+                    }
+                } else if token == self.listener_token {
+                    self.accept();
+                } else if token.0 < self.wake.0 {
+                    if event.is_writable() && self.port.writable(token).is_err() {
+                        break 'poll;
+                    }
+                    if self.forward_from_port(Some(token)).is_err() {
+                        break 'poll;
+                    }
+                } else {
+                    match self.serve(token, event.is_readable(), event.is_writable()) {
+                        Ok(true) => {}
+                        Ok(false) => self.disconnect(token),
+                        // Port write failure.
+                        Err(_) => break 'poll,
+                    }
+                }
+            }
+            if self
+                .port
+                .deadline()
+                .is_some_and(|deadline| deadline <= Instant::now())
+                && self.forward_from_port(None).is_err()
+            {
+                break 'poll;
+            }
+        }
+
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+
+    /// Accepts pending client connections.
+    fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((mut stream, _)) => {
+                    let token = Token(self.next_token);
+                    self.next_token += 1;
+                    if self
+                        .poll
+                        .registry()
+                        .register(&mut stream, token, Interest::READABLE | Interest::WRITABLE)
+                        .is_ok()
+                    {
+                        self.connections.insert(
+                            token,
+                            Connection {
+                                stream,
+                                filters: Vec::new(),
+                                rx_buf: Vec::new(),
+                                tx: WriteBuffer::new(),
+                            },
+                        );
+                    }
+                }
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Removes a client connection.
+    fn disconnect(&mut self, token: Token) {
+        if let Some(mut connection) = self.connections.remove(&token) {
+            let _ = self.poll.registry().deregister(&mut connection.stream);
+        }
+    }
+
+    /// Forwards items read from the port to the clients, or items due at the
+    /// deadline of the port if no token is provided.
+    fn forward_from_port<S, T>(&mut self, token: Option<Token>) -> IoResult<()>
+    where
+        S: Source + ?Sized,
+        T: Send,
+        P: IoPort<S, T, T>,
+        C: BrokerCodec<T>,
+    {
+        let mut payload = Vec::new();
+        let mut message = Vec::new();
+        let mut failed = Vec::new();
+        loop {
+            let item = match token {
+                Some(token) => self.port.read(token),
+                None => self.port.timeout(),
+            };
+            let item = match item {
+                Ok(item) => item,
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            };
+            payload.clear();
+            if self.codec.encode(&item, &mut payload).is_err() {
+                continue;
+            }
+            message.clear();
+            put_message(&mut message, DATA_MESSAGE, &payload);
+            let key = self.codec.key(&item);
+            for (token, connection) in self.connections.iter_mut() {
+                // The item is dropped if the client does not keep up.
+                if !connection.accepts(key)
+                    || connection.tx.len() + message.len() > MAX_PENDING_SIZE
+                {
+                    continue;
+                }
+                if connection
+                    .tx
+                    .write(&mut connection.stream, &message)
+                    .is_err()
+                {
+                    failed.push(*token);
+                }
+            }
+        }
+        for token in failed {
+            self.disconnect(token);
+        }
+
+        Ok(())
+    }
+
+    /// Serves a client connection.
+    ///
+    /// Returns `Ok(false)` if the client should be disconnected and an error if
+    /// writing to the port failed.
+    fn serve<S, T>(&mut self, token: Token, is_readable: bool, is_writable: bool) -> IoResult<bool>
+    where
+        S: Source + ?Sized,
+        T: Send,
+        P: IoPort<S, T, T>,
+        C: BrokerCodec<T>,
+    {
+        let Some(connection) = self.connections.get_mut(&token) else {
+            return Ok(true);
+        };
+        if is_writable && connection.tx.flush(&mut connection.stream).is_err() {
+            return Ok(false);
+        }
+        if !is_readable {
+            return Ok(true);
+        }
+
+        let is_open = matches!(
+            fill_buffer(&mut connection.stream, &mut connection.rx_buf),
+            Ok(true)
+        );
+        loop {
+            let (kind, payload) = match take_message(&mut connection.rx_buf) {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(_) => return Ok(false),
+            };
+            match kind {
+                DATA_MESSAGE => match self.codec.decode(&payload) {
+                    Ok(item) => self.port.write(&item)?,
+                    Err(_) => return Ok(false),
+                },
+                SUBSCRIBE_MESSAGE => {
+                    connection.filters = payload
+                        .chunks_exact(8)
+                        .map(|filter| BrokerFilter {
+                            key: u32::from_le_bytes(filter[..4].try_into().unwrap()),
+                            mask: u32::from_le_bytes(filter[4..].try_into().unwrap()),
+                        })
+                        .collect();
+                }
+                _ => return Ok(false),
+            }
+        }
+
+        Ok(is_open)
+    }
+}
+
+/// Shared port broker client.
+///
+/// This [`IoPort`] implementation exchanges items with a [`SharedPortBroker`]
+/// instead of a physical device.
+pub struct BrokerClient<T, C> {
+    /// Broker connection.
+    stream: UnixStream,
+
+    /// Item codec.
+    codec: C,
+
+    /// Received data not yet decoded.
+    rx_buf: Vec<u8>,
+
+    /// Decoded items not yet read.
+    items: VecDeque<T>,
+
+    /// Data not yet written.
+    tx: WriteBuffer,
+
+    /// MIO registry, available once the client is registered.
+    registry: Option<Registry>,
+}
+
+impl<T, C: BrokerCodec<T>> BrokerClient<T, C> {
+    /// Connects to the broker listening on the provided socket path.
+    ///
+    /// Only items accepted by at least one of the filters are forwarded to the
+    /// client. If no filters are provided, all items are forwarded.
+    pub fn connect(
+        socket_path: impl AsRef<Path>,
+        codec: C,
+        filters: &[BrokerFilter],
+    ) -> IoResult<Self> {
+        let mut stream = StdUnixStream::connect(socket_path)?;
+
+        let mut subscription = Vec::with_capacity(filters.len() * 8);
+        for filter in filters {
+            subscription.extend_from_slice(&filter.key.to_le_bytes());
+            subscription.extend_from_slice(&filter.mask.to_le_bytes());
+        }
+        let mut message = Vec::new();
+        put_message(&mut message, SUBSCRIBE_MESSAGE, &subscription);
+        stream.write_all(&message)?;
+        stream.set_nonblocking(true)?;
+
+        Ok(Self {
+            stream: UnixStream::from_std(stream),
+            codec,
+            rx_buf: Vec::new(),
+            items: VecDeque::new(),
+            tx: WriteBuffer::new(),
+            registry: None,
+        })
+    }
+
+    /// Adds or removes the writable interest of the broker connection.
+    fn set_writable_interest(&mut self, writable: bool) -> IoResult<()> {
+        if let Some(registry) = &self.registry {
+            let interest = if writable {
+                Interest::READABLE | Interest::WRITABLE
+            } else {
+                Interest::READABLE
+            };
+            registry.reregister(&mut self.stream, Token(0), interest)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T, C> IoPort<UnixStream, T, T> for BrokerClient<T, C>
+where
+    T: Send,
+    C: BrokerCodec<T>,
+{
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        registry.register(&mut self.stream, Token(0), Interest::READABLE)?;
+        self.registry = Some(registry.try_clone()?);
+
+        Ok(Token(1))
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<T> {
+        if token != Token(0) {
+            // Unknown event: should never happen.
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "Unknown event.",
+            ));
+        }
+        loop {
+            if let Some(item) = self.items.pop_front() {
+                return Ok(item);
+            }
+            while let Some((kind, payload)) = take_message(&mut self.rx_buf)? {
+                if kind == DATA_MESSAGE {
+                    self.items.push_back(self.codec.decode(&payload)?);
+                }
+            }
+            if !self.items.is_empty() {
+                continue;
+            }
+            let mut chunk = [0; 4096];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(len) => self.rx_buf.extend_from_slice(&chunk[..len]),
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write(&mut self, data: &T) -> IoResult<()> {
+        // Items are delayed until the pending data has been written.
+        if !self.tx.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        let mut payload = Vec::new();
+        self.codec.encode(data, &mut payload)?;
+        let mut message = Vec::with_capacity(payload.len() + 5);
+        put_message(&mut message, DATA_MESSAGE, &payload);
+
+        // The part of the message which cannot be written immediately is
+        // written once the broker connection is writable.
+        if !self.tx.write(&mut self.stream, &message)? {
+            self.set_writable_interest(true)?;
+        }
+
+        Ok(())
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        if token == Token(0) && !self.tx.is_empty() && self.tx.flush(&mut self.stream)? {
+            self.set_writable_interest(false)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        !self.tx.is_empty()
+    }
+}
+
+impl<T, C> fmt::Debug for BrokerClient<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BrokerClient").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,22 @@
+//! Programmatic configuration.
+//!
+//! Model configurations are usually loaded with a schematic `ConfigLoader`
+//! from files or strings. Configuration builders, such as
+//! [`ExternalPortConfig::builder`](crate::external::ExternalPortConfig::builder),
+//! fill in a partial configuration instead, and complete it with [`build`],
+//! so that configurations built programmatically get the same default values
+//! and validation as loaded ones.
+
+use schematic::{Config, ConfigError, PartialConfig};
+
+/// Builds a configuration from a partial configuration.
+///
+/// Unset settings take their default values, and the configuration is
+/// validated as if it was loaded.
+pub fn build<T: Config>(partial: T::Partial) -> Result<T, ConfigError> {
+    let context = Default::default();
+    let partial = partial.finalize(&context)?;
+    partial.validate(&context, true)?;
+
+    Ok(T::from_partial(partial))
+}
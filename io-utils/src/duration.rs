@@ -0,0 +1,137 @@
+//! Duration settings.
+//!
+//! This module contains the [`ConfigDuration`] type used for the duration
+//! settings of model configurations, such as `delta` and `period`. Durations
+//! are written as [humantime] strings, e.g. `"250us"`, `"5ms"` or `"1s"`, or
+//! for compatibility as integer numbers of milliseconds.
+//!
+//! #### Examples
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use schematic::{Config, ConfigLoader, Format};
+//!
+//! use nexosim_io_utils::duration::ConfigDuration;
+//!
+//! #[derive(Config)]
+//! struct TimingConfig {
+//!     delta: Option<ConfigDuration>,
+//!     period: Option<ConfigDuration>,
+//! }
+//!
+//! let config = ConfigLoader::<TimingConfig>::new()
+//!     .code(
+//!         r#"
+//! delta = 5
+//! period = "250us"
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.delta.map(Duration::from), Some(Duration::from_millis(5)));
+//! assert_eq!(config.period.map(Duration::from), Some(Duration::from_micros(250)));
+//! ```
+//!
+//! [humantime]: https://docs.rs/humantime
+
+use std::fmt;
+use std::ops::Deref;
+use std::time::Duration;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// Duration setting.
+///
+/// The duration is deserialized from a [humantime] string such as `"5ms"`,
+/// or from an integer number of milliseconds. It is serialized as a
+/// humantime string.
+///
+/// [humantime]: https://docs.rs/humantime
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConfigDuration(pub Duration);
+
+impl ConfigDuration {
+    /// Creates a duration setting from a number of milliseconds.
+    pub const fn from_millis(millis: u64) -> Self {
+        Self(Duration::from_millis(millis))
+    }
+
+    /// Creates a duration setting from a number of microseconds.
+    pub const fn from_micros(micros: u64) -> Self {
+        Self(Duration::from_micros(micros))
+    }
+}
+
+impl Deref for ConfigDuration {
+    type Target = Duration;
+
+    fn deref(&self) -> &Duration {
+        &self.0
+    }
+}
+
+impl From<Duration> for ConfigDuration {
+    fn from(duration: Duration) -> Self {
+        Self(duration)
+    }
+}
+
+impl From<ConfigDuration> for Duration {
+    fn from(duration: ConfigDuration) -> Self {
+        duration.0
+    }
+}
+
+impl fmt::Display for ConfigDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        humantime::format_duration(self.0).fmt(f)
+    }
+}
+
+impl Serialize for ConfigDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfigDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ConfigDurationVisitor)
+    }
+}
+
+/// Visitor of a duration setting.
+struct ConfigDurationVisitor;
+
+impl Visitor<'_> for ConfigDurationVisitor {
+    type Value = ConfigDuration;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a duration such as \"5ms\" or an integer number of milliseconds"
+        )
+    }
+
+    fn visit_u64<E: de::Error>(self, millis: u64) -> Result<Self::Value, E> {
+        Ok(ConfigDuration::from_millis(millis))
+    }
+
+    fn visit_i64<E: de::Error>(self, millis: i64) -> Result<Self::Value, E> {
+        u64::try_from(millis)
+            .map(ConfigDuration::from_millis)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(millis), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        humantime::parse_duration(value)
+            .map(ConfigDuration)
+            .map_err(|e| E::custom(format!("invalid duration \"{value}\": {e}")))
+    }
+}
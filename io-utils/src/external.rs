@@ -0,0 +1,379 @@
+//! Generic external port model.
+//!
+//! This module contains the [`ExternalPort`] model, which connects any
+//! [`IoPort`] implementation to a simulation, so that a new transport only
+//! needs to implement [`IoPort`] rather than a complete model.
+//!
+//! The model forwards the data read by its I/O thread to its output, either
+//! periodically if `period` is set in the configuration, or each time its
+//! `process` input is called.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_io_utils::duration::ConfigDuration;
+//! use nexosim_io_utils::external::ExternalPortConfig;
+//!
+//! let config = ConfigLoader::<ExternalPortConfig>::new()
+//!     .code(r#"period = "10ms""#, Format::Toml)
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.period, Some(ConfigDuration::from_millis(10)));
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use schematic::{Config, ConfigError};
+
+use mio::event::Source;
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use crate::config;
+use crate::duration::ConfigDuration;
+use crate::link::{LinkMonitor, LinkStatus};
+use crate::port::{IoPort, IoThread, IoThreadError, IoThreadOptions, IoThreadStatus, StallChange};
+
+/// External port model instance configuration.
+#[derive(Clone, Config, Debug)]
+pub struct ExternalPortConfig {
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which data from the port is forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+
+    /// Maximum time spent writing the pending data to the port when the model
+    /// is dropped.
+    ///
+    /// If no value is provided, the data not yet written is discarded.
+    pub shutdown_timeout: Option<ConfigDuration>,
+}
+
+impl ExternalPortConfig {
+    /// Returns a builder of an external port model instance configuration.
+    pub fn builder() -> ExternalPortConfigBuilder {
+        ExternalPortConfigBuilder::default()
+    }
+}
+
+/// Builder of an external port model instance configuration.
+///
+/// Settings which are not set take their default values.
+///
+/// #### Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use nexosim_io_utils::external::ExternalPortConfig;
+///
+/// let config = ExternalPortConfig::builder()
+///     .period(Duration::from_micros(500))
+///     .watchdog_timeout(Duration::from_millis(100))
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(config.delta, None);
+/// assert_eq!(
+///     config.watchdog_timeout.map(Duration::from),
+///     Some(Duration::from_millis(100))
+/// );
+/// ```
+#[derive(Debug, Default)]
+pub struct ExternalPortConfigBuilder {
+    /// Configuration built.
+    partial: PartialExternalPortConfig,
+}
+
+impl ExternalPortConfigBuilder {
+    /// Sets the delay for the first scheduled data forwarding.
+    pub fn delta(mut self, delta: Duration) -> Self {
+        self.partial.delta = Some(delta.into());
+        self
+    }
+
+    /// Sets the delay for the first scheduled data forwarding, in
+    /// milliseconds.
+    pub fn delta_ms(self, delta: u64) -> Self {
+        self.delta(Duration::from_millis(delta))
+    }
+
+    /// Sets the period at which data from the port is forwarded into the
+    /// simulation.
+    pub fn period(mut self, period: Duration) -> Self {
+        self.partial.period = Some(period.into());
+        self
+    }
+
+    /// Sets the period at which data from the port is forwarded into the
+    /// simulation, in milliseconds.
+    pub fn period_ms(self, period: u64) -> Self {
+        self.period(Duration::from_millis(period))
+    }
+
+    /// Sets the I/O thread watchdog timeout.
+    pub fn watchdog_timeout(mut self, timeout: Duration) -> Self {
+        self.partial.watchdog_timeout = Some(timeout.into());
+        self
+    }
+
+    /// Sets the I/O thread watchdog timeout, in milliseconds.
+    pub fn watchdog_timeout_ms(self, timeout: u64) -> Self {
+        self.watchdog_timeout(Duration::from_millis(timeout))
+    }
+
+    /// Sets the maximum time spent writing the pending data when the model is
+    /// dropped.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.partial.shutdown_timeout = Some(timeout.into());
+        self
+    }
+
+    /// Sets the maximum time spent writing the pending data when the model is
+    /// dropped, in milliseconds.
+    pub fn shutdown_timeout_ms(self, timeout: u64) -> Self {
+        self.shutdown_timeout(Duration::from_millis(timeout))
+    }
+
+    /// Builds the configuration.
+    ///
+    /// An error is returned if the configuration is invalid.
+    pub fn build(self) -> Result<ExternalPortConfig, ConfigError> {
+        config::build(self.partial)
+    }
+}
+
+/// Generic external port model.
+///
+/// This model:
+/// * forwards the data read from an I/O port into the simulation,
+/// * forwards data from the model input to the I/O port,
+/// * reports the stalls, the errors and the exit of its I/O thread,
+/// * reports the changes of its link status.
+pub struct ExternalPort<R, T>
+where
+    R: Clone + Send + 'static,
+    T: Send + 'static,
+{
+    /// Data from the port -- output port.
+    pub data_out: Output<R>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Link status change -- output port.
+    pub link_status_out: Output<LinkStatus>,
+
+    /// Model instance configuration.
+    config: ExternalPortConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<R, T>,
+
+    /// Link status.
+    link: LinkMonitor,
+}
+
+impl<R, T> ExternalPort<R, T>
+where
+    R: Clone + Send + 'static,
+    T: Send + 'static,
+{
+    /// Sends data to the port -- input port.
+    ///
+    /// Data sent after the I/O thread has exited is dropped; the exit is
+    /// reported on the I/O status output.
+    pub fn data_in(&mut self, data: T) {
+        let _ = self.io_thread.send(data);
+    }
+
+    /// Forwards the data read from the port and the I/O thread status -- input
+    /// port.
+    pub async fn process(&mut self) {
+        for data in self.io_thread.try_recv_all() {
+            self.data_out.send(data).await;
+        }
+        while let Ok(status) = self.io_thread.try_recv_status() {
+            let change = self.link.set_io_status(&status);
+            self.io_status_out.send(status).await;
+            self.report_link(change).await;
+        }
+        self.check_watchdog().await;
+    }
+
+    /// Reports a stalled I/O thread once, until it recovers.
+    async fn check_watchdog(&mut self) {
+        let Some(timeout) = self.config.watchdog_timeout else {
+            return;
+        };
+        let change = match self.io_thread.check_stall(*timeout) {
+            Some(StallChange::Stalled(age)) => {
+                self.stalled_out.send(age).await;
+                self.link.set_stalled(true)
+            }
+            Some(StallChange::Recovered) => self.link.set_stalled(false),
+            None => None,
+        };
+        self.report_link(change).await;
+    }
+
+    /// Reports a link status change, if any.
+    async fn report_link(&mut self, change: Option<LinkStatus>) {
+        if let Some(status) = change {
+            self.link_status_out.send(status).await;
+        }
+    }
+}
+
+impl<R, T> Model for ExternalPort<R, T>
+where
+    R: Clone + Send + 'static,
+    T: Send + 'static,
+{
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl<R, T> fmt::Debug for ExternalPort<R, T>
+where
+    R: Clone + Send + 'static,
+    T: Send + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExternalPort").finish_non_exhaustive()
+    }
+}
+
+/// I/O thread constructor of a model prototype.
+type SpawnFn<R, T> = dyn FnOnce(IoThreadOptions) -> Result<IoThread<R, T>, IoThreadError> + Send;
+
+/// Generic external port model prototype.
+pub struct ProtoExternalPort<R, T>
+where
+    R: Clone + Send + 'static,
+    T: Send + 'static,
+{
+    /// Data from the port -- output port.
+    pub data_out: Output<R>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Link status change -- output port.
+    pub link_status_out: Output<LinkStatus>,
+
+    /// External port model instance configuration.
+    config: ExternalPortConfig,
+
+    /// I/O thread constructor, called when the model is built.
+    spawn: Box<SpawnFn<R, T>>,
+}
+
+impl<R, T> ProtoExternalPort<R, T>
+where
+    R: Clone + Send + 'static,
+    T: Send + 'static,
+{
+    /// Creates a new external port model prototype.
+    ///
+    /// The I/O thread of the port is spawned when the model is built.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the I/O thread cannot be created.
+    pub fn new<S, P>(port: P, config: ExternalPortConfig) -> Self
+    where
+        S: Source + ?Sized,
+        P: IoPort<S, R, T> + Send + 'static,
+    {
+        Self {
+            data_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            link_status_out: Output::new(),
+            config,
+            spawn: Box::new(move |options| IoThread::try_with_options(port, options)),
+        }
+    }
+}
+
+impl<R, T> ProtoModel for ProtoExternalPort<R, T>
+where
+    R: Clone + Send + 'static,
+    T: Send + 'static,
+{
+    type Model = ExternalPort<R, T>;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            shutdown_timeout: self.config.shutdown_timeout.map(Duration::from),
+        };
+        let io_thread = (self.spawn)(options)
+            .unwrap_or_else(|e| panic!("Failed to start the I/O thread of the port: {e}."));
+
+        ExternalPort {
+            data_out: self.data_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            link_status_out: self.link_status_out,
+            config: self.config,
+            io_thread,
+            link: LinkMonitor::new(),
+        }
+    }
+}
+
+impl<R, T> fmt::Debug for ProtoExternalPort<R, T>
+where
+    R: Clone + Send + 'static,
+    T: Send + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoExternalPort").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,275 @@
+//! Fault injection.
+//!
+//! This module contains the [`FaultInjector`] model, which can be inserted
+//! between two models to impair the items they exchange, e.g. byte buffers or
+//! CAN frames, in robustness test campaigns. Each item can be:
+//! * dropped,
+//! * duplicated,
+//! * reordered, i.e. held back and forwarded after the next item,
+//! * corrupted with bit flips,
+//! * truncated.
+//!
+//! Faults are drawn from a random number generator seeded from the
+//! configuration, so that a run can be reproduced exactly from its seed.
+//!
+//! #### Examples
+//!
+//! ```
+//! use bytes::Bytes;
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_io_utils::fault::{FaultInjector, FaultInjectorConfig};
+//!
+//! let config = ConfigLoader::<FaultInjectorConfig>::new()
+//!     .code(
+//!         r#"
+//! seed = 42
+//! dropProbability = 0.01
+//! corruptProbability = 0.001
+//! bitFlips = 2
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! // Connect the output of the sending model to `FaultInjector::data_in`, and
+//! // `FaultInjector::data_out` to the input of the receiving model.
+//! let injector = FaultInjector::<Bytes>::new(config);
+//! # let _ = injector;
+//! ```
+
+use std::fmt;
+
+use bytes::Bytes;
+
+use schematic::Config;
+
+use nexosim::model::Model;
+use nexosim::ports::Output;
+
+use crate::rng::Rng;
+
+/// Fault injector model instance configuration.
+///
+/// Probabilities range from 0 to 1 and are applied independently to each
+/// item.
+#[derive(Clone, Config, Debug)]
+pub struct FaultInjectorConfig {
+    /// Seed of the random number generator.
+    pub seed: u64,
+
+    /// Probability of dropping an item.
+    pub drop_probability: f64,
+
+    /// Probability of forwarding an item twice.
+    pub duplicate_probability: f64,
+
+    /// Probability of holding an item back until the next item is forwarded.
+    pub reorder_probability: f64,
+
+    /// Probability of flipping bits of an item.
+    pub corrupt_probability: f64,
+
+    /// Number of bits flipped in a corrupted item.
+    ///
+    /// The same bit may be drawn several times, in which case it is flipped
+    /// back.
+    #[setting(default = 1)]
+    pub bit_flips: u32,
+
+    /// Probability of truncating an item to a random shorter length.
+    pub truncate_probability: f64,
+}
+
+/// Fault applied to an item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Fault {
+    /// The item was dropped.
+    Drop,
+
+    /// The item was forwarded twice.
+    Duplicate,
+
+    /// The item was held back until the next item.
+    Reorder,
+
+    /// Bits of the item were flipped.
+    Corrupt,
+
+    /// The item was truncated.
+    Truncate,
+}
+
+/// Item whose payload can be corrupted or truncated.
+///
+/// Items with an empty payload are never corrupted nor truncated.
+pub trait Faulty: Clone {
+    /// Returns the length of the payload, in bytes.
+    fn payload_len(&self) -> usize;
+
+    /// Flips a bit of the payload, numbered from the least significant bit
+    /// of the first byte.
+    fn flip_bit(&mut self, bit: usize);
+
+    /// Flips several bits of the payload.
+    ///
+    /// The default implementation flips the bits one by one.
+    fn flip_bits(&mut self, bits: &[usize]) {
+        for &bit in bits {
+            self.flip_bit(bit);
+        }
+    }
+
+    /// Truncates the payload to a shorter length, in bytes.
+    fn truncate_payload(&mut self, len: usize);
+}
+
+impl Faulty for Bytes {
+    fn payload_len(&self) -> usize {
+        self.len()
+    }
+
+    fn flip_bit(&mut self, bit: usize) {
+        self.flip_bits(&[bit]);
+    }
+
+    fn flip_bits(&mut self, bits: &[usize]) {
+        let mut data = Vec::from(std::mem::take(self));
+        data.flip_bits(bits);
+        *self = data.into();
+    }
+
+    fn truncate_payload(&mut self, len: usize) {
+        self.truncate(len);
+    }
+}
+
+impl Faulty for Vec<u8> {
+    fn payload_len(&self) -> usize {
+        self.len()
+    }
+
+    fn flip_bit(&mut self, bit: usize) {
+        self[bit / 8] ^= 1 << (bit % 8);
+    }
+
+    fn truncate_payload(&mut self, len: usize) {
+        self.truncate(len);
+    }
+}
+
+/// Fault injector model.
+///
+/// This model forwards the items of its input to its output, applying the
+/// faults drawn for each item in this order: drop, corruption, truncation,
+/// reordering and duplication. Each applied fault is reported.
+///
+/// An item held back for reordering is forwarded once, right after the next
+/// forwarded item or when [`flush`](Self::flush) is triggered. Only one item
+/// is held back at a time.
+pub struct FaultInjector<T>
+where
+    T: Faulty + Send + 'static,
+{
+    /// Forwarded item -- output port.
+    pub data_out: Output<T>,
+
+    /// Fault applied to an item -- output port.
+    pub fault_out: Output<Fault>,
+
+    /// Model instance configuration.
+    config: FaultInjectorConfig,
+
+    /// Random number generator.
+    rng: Rng,
+
+    /// Item held back for reordering.
+    held: Option<T>,
+}
+
+impl<T> FaultInjector<T>
+where
+    T: Faulty + Send + 'static,
+{
+    /// Creates a new fault injector model.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a probability is not within 0 and 1.
+    pub fn new(config: FaultInjectorConfig) -> Self {
+        for (name, probability) in [
+            ("drop", config.drop_probability),
+            ("duplicate", config.duplicate_probability),
+            ("reorder", config.reorder_probability),
+            ("corrupt", config.corrupt_probability),
+            ("truncate", config.truncate_probability),
+        ] {
+            assert!(
+                (0.0..=1.0).contains(&probability),
+                "the {name} probability should be within 0 and 1"
+            );
+        }
+
+        Self {
+            data_out: Output::default(),
+            fault_out: Output::default(),
+            rng: Rng::new(config.seed),
+            config,
+            held: None,
+        }
+    }
+
+    /// Item to forward -- input port.
+    pub async fn data_in(&mut self, mut data: T) {
+        if self.rng.chance(self.config.drop_probability) {
+            self.fault_out.send(Fault::Drop).await;
+            return;
+        }
+        let len = data.payload_len();
+        if len > 0 && self.rng.chance(self.config.corrupt_probability) {
+            let bits: Vec<_> = (0..self.config.bit_flips)
+                .map(|_| self.rng.below(len * 8))
+                .collect();
+            data.flip_bits(&bits);
+            self.fault_out.send(Fault::Corrupt).await;
+        }
+        if len > 0 && self.rng.chance(self.config.truncate_probability) {
+            data.truncate_payload(self.rng.below(len));
+            self.fault_out.send(Fault::Truncate).await;
+        }
+        if self.held.is_none() && self.rng.chance(self.config.reorder_probability) {
+            self.fault_out.send(Fault::Reorder).await;
+            self.held = Some(data);
+            return;
+        }
+        if self.rng.chance(self.config.duplicate_probability) {
+            self.fault_out.send(Fault::Duplicate).await;
+            self.data_out.send(data.clone()).await;
+        }
+        self.data_out.send(data).await;
+        self.flush().await;
+    }
+
+    /// Forwards the item held back for reordering, if any -- input port.
+    pub async fn flush(&mut self) {
+        if let Some(data) = self.held.take() {
+            self.data_out.send(data).await;
+        }
+    }
+}
+
+impl<T> Model for FaultInjector<T> where T: Faulty + Send + 'static {}
+
+impl<T> fmt::Debug for FaultInjector<T>
+where
+    T: Faulty + Send + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FaultInjector")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,78 @@
+//! Compact hexadecimal dumps.
+//!
+//! This module contains the [`HexDump`] formatter, which displays binary data
+//! such as `Bytes` payloads as space-separated hexadecimal bytes on a single
+//! line. Long payloads are truncated, along with their total length, so that
+//! they can be recorded as trace or log fields without flooding the output.
+//!
+//! #### Examples
+//!
+//! ```
+//! use bytes::Bytes;
+//!
+//! use nexosim_io_utils::hexdump::HexDump;
+//!
+//! let data = Bytes::from_static(&[0x01, 0xab, 0x7f]);
+//! assert_eq!(HexDump::new(&data).to_string(), "01 ab 7f");
+//!
+//! let data = Bytes::from(vec![0x55; 40]);
+//! assert_eq!(
+//!     HexDump::new(&data).with_limit(4).to_string(),
+//!     "55 55 55 55 ... (40 bytes)"
+//! );
+//! ```
+
+use std::fmt;
+
+/// Default maximum number of bytes displayed.
+pub const DEFAULT_LIMIT: usize = 32;
+
+/// Compact hexadecimal dump of binary data.
+///
+/// At most [`DEFAULT_LIMIT`] bytes are displayed unless another limit is set
+/// with [`HexDump::with_limit`]. Truncated dumps end with the total length of
+/// the data.
+#[derive(Clone, Copy, Debug)]
+pub struct HexDump<'a> {
+    /// Dumped data.
+    data: &'a [u8],
+
+    /// Maximum number of bytes displayed.
+    limit: usize,
+}
+
+impl<'a> HexDump<'a> {
+    /// Creates a hexadecimal dump of the data.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+
+    /// Sets the maximum number of bytes displayed.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let shown = &self.data[..self.data.len().min(self.limit)];
+        for (i, byte) in shown.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        if shown.len() < self.data.len() {
+            if !shown.is_empty() {
+                f.write_str(" ")?;
+            }
+            write!(f, "... ({} bytes)", self.data.len())?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+//! Latency and jitter emulation.
+//!
+//! This module contains the [`LatencyChannel`] model, which can be inserted
+//! between two models to delay the items they exchange by a simulated time,
+//! e.g. to emulate radio or network propagation within an otherwise
+//! instantaneous connection.
+//!
+//! The latency of each item is drawn from a distribution:
+//! * constant,
+//! * uniform between a minimum and a maximum,
+//! * normal, with a mean and a standard deviation (the jitter),
+//! * trace-driven, cycling through recorded latencies.
+//!
+//! Random latencies are drawn from a random number generator seeded from the
+//! configuration, so that a run can be reproduced exactly from its seed.
+//!
+//! #### Examples
+//!
+//! ```
+//! use bytes::Bytes;
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_io_utils::latency::{LatencyChannel, LatencyChannelConfig, LatencyDistribution};
+//!
+//! let config = ConfigLoader::<LatencyChannelConfig>::new()
+//!     .code(
+//!         r#"
+//! seed = 7
+//! distribution = "normal"
+//! latency = 120.0
+//! jitter = 15.0
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.distribution, LatencyDistribution::Normal);
+//! assert!(config.preserve_order);
+//!
+//! // Connect the output of the sending model to `LatencyChannel::data_in`,
+//! // and `LatencyChannel::data_out` to the input of the receiving model.
+//! let channel = LatencyChannel::<Bytes>::new(config);
+//! # let _ = channel;
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use schematic::{Config, ConfigEnum};
+use serde::{Deserialize, Serialize};
+
+use nexosim::model::{Context, Model};
+use nexosim::ports::Output;
+use nexosim::time::MonotonicTime;
+
+use crate::rng::Rng;
+
+/// Latency channel model instance configuration.
+///
+/// Latencies are expressed in milliseconds.
+#[derive(Clone, Config, Debug)]
+pub struct LatencyChannelConfig {
+    /// Seed of the random number generator.
+    pub seed: u64,
+
+    /// Latency distribution.
+    pub distribution: LatencyDistribution,
+
+    /// Constant latency, or mean latency of the normal distribution.
+    pub latency: f64,
+
+    /// Standard deviation of the normal distribution.
+    pub jitter: f64,
+
+    /// Minimum latency of the uniform distribution, and lower bound of the
+    /// normal distribution.
+    pub min_latency: f64,
+
+    /// Maximum latency of the uniform distribution.
+    pub max_latency: f64,
+
+    /// Latencies of the trace distribution, used in turn and cyclically.
+    pub trace: Vec<f64>,
+
+    /// Path of a text file with the latencies of the trace distribution,
+    /// separated by whitespace, used instead of `trace` if provided.
+    pub trace_file: Option<String>,
+
+    /// Items are forwarded in their input order.
+    ///
+    /// If set, an item drawn with a latency shorter than the one of its
+    /// predecessor is delayed until its predecessor is forwarded. Otherwise,
+    /// items may overtake each other.
+    #[setting(default = true)]
+    pub preserve_order: bool,
+}
+
+/// Latency distribution.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LatencyDistribution {
+    /// Constant latency.
+    #[default]
+    Constant,
+
+    /// Latency uniformly distributed between the minimum and the maximum.
+    Uniform,
+
+    /// Normally distributed latency, bounded below by the minimum.
+    Normal,
+
+    /// Latencies of a trace.
+    Trace,
+}
+
+/// Latency channel model.
+///
+/// This model forwards the items of its input to its output, each after a
+/// latency drawn from the configured distribution.
+pub struct LatencyChannel<T>
+where
+    T: Clone + Send + 'static,
+{
+    /// Delayed item -- output port.
+    pub data_out: Output<T>,
+
+    /// Model instance configuration.
+    config: LatencyChannelConfig,
+
+    /// Latencies of the trace distribution.
+    trace: Vec<f64>,
+
+    /// Index of the next trace latency.
+    trace_index: usize,
+
+    /// Random number generator.
+    rng: Rng,
+
+    /// Items in transit, by delivery time and sequence number.
+    in_transit: BTreeMap<(MonotonicTime, u64), T>,
+
+    /// Sequence number of the next item.
+    next_seq: u64,
+
+    /// Delivery time of the last item.
+    last_deadline: Option<MonotonicTime>,
+}
+
+impl<T> LatencyChannel<T>
+where
+    T: Clone + Send + 'static,
+{
+    /// Creates a new latency channel model.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if a latency is negative or not finite, if the
+    /// minimum latency of the uniform distribution exceeds its maximum, or if
+    /// the trace of the trace distribution is empty or its file cannot be
+    /// read.
+    pub fn new(config: LatencyChannelConfig) -> Self {
+        let trace = match &config.trace_file {
+            Some(path) => std::fs::read_to_string(path)
+                .unwrap_or_else(|e| panic!("Failed to read latency trace {path}: {e}."))
+                .split_whitespace()
+                .map(|latency| {
+                    latency
+                        .parse()
+                        .unwrap_or_else(|_| panic!("Invalid latency {latency} in trace {path}."))
+                })
+                .collect(),
+            None => config.trace.clone(),
+        };
+        let mut latencies = vec![config.latency, config.min_latency, config.max_latency];
+        latencies.extend(&trace);
+        assert!(
+            latencies
+                .iter()
+                .all(|latency| latency.is_finite() && *latency >= 0.0)
+                && config.jitter.is_finite()
+                && config.jitter >= 0.0,
+            "latencies should be finite and non-negative"
+        );
+        match config.distribution {
+            LatencyDistribution::Uniform => assert!(
+                config.min_latency <= config.max_latency,
+                "the minimum latency should not exceed the maximum latency"
+            ),
+            LatencyDistribution::Trace => {
+                assert!(!trace.is_empty(), "the latency trace should not be empty")
+            }
+            LatencyDistribution::Constant | LatencyDistribution::Normal => {}
+        }
+
+        Self {
+            data_out: Output::default(),
+            rng: Rng::new(config.seed),
+            config,
+            trace,
+            trace_index: 0,
+            in_transit: BTreeMap::new(),
+            next_seq: 0,
+            last_deadline: None,
+        }
+    }
+
+    /// Item to forward -- input port.
+    pub async fn data_in(&mut self, data: T, cx: &mut Context<Self>) {
+        let latency = Duration::from_secs_f64(self.draw() / 1000.0);
+        let mut deadline = cx.time() + latency;
+        if self.config.preserve_order {
+            if let Some(last_deadline) = self.last_deadline {
+                deadline = deadline.max(last_deadline);
+            }
+            self.last_deadline = Some(deadline);
+        }
+        self.in_transit.insert((deadline, self.next_seq), data);
+        self.next_seq += 1;
+        if deadline > cx.time() {
+            cx.schedule_event(deadline, Self::deliver, ()).unwrap();
+        } else {
+            self.deliver((), cx).await;
+        }
+    }
+
+    /// Forwards the items which are due.
+    async fn deliver(&mut self, _: (), cx: &mut Context<Self>) {
+        while let Some(entry) = self.in_transit.first_entry() {
+            if entry.key().0 > cx.time() {
+                break;
+            }
+            let data = entry.remove();
+            self.data_out.send(data).await;
+        }
+    }
+
+    /// Draws a latency, in milliseconds.
+    fn draw(&mut self) -> f64 {
+        match self.config.distribution {
+            LatencyDistribution::Constant => self.config.latency,
+            LatencyDistribution::Uniform => {
+                let range = self.config.max_latency - self.config.min_latency;
+
+                self.config.min_latency + range * self.rng.uniform()
+            }
+            LatencyDistribution::Normal => {
+                let latency = self.config.latency + self.config.jitter * self.rng.normal();
+
+                latency.max(self.config.min_latency)
+            }
+            LatencyDistribution::Trace => {
+                let latency = self.trace[self.trace_index];
+                self.trace_index = (self.trace_index + 1) % self.trace.len();
+
+                latency
+            }
+        }
+    }
+}
+
+impl<T> Model for LatencyChannel<T> where T: Clone + Send + 'static {}
+
+impl<T> fmt::Debug for LatencyChannel<T>
+where
+    T: Clone + Send + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LatencyChannel")
+            .field("distribution", &self.config.distribution)
+            .field("in_transit", &self.in_transit.len())
+            .finish_non_exhaustive()
+    }
+}
@@ -4,4 +4,25 @@
 #![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
 #![forbid(unsafe_code)]
 
+pub mod alignment;
+#[cfg(feature = "tokio")]
+pub mod async_port;
+pub mod ber;
+pub mod blocking;
+#[cfg(unix)]
+pub mod broker;
+pub mod config;
+pub mod duration;
+pub mod external;
+pub mod fault;
+pub mod hexdump;
+pub mod latency;
+pub mod link;
+pub mod metrics;
+#[cfg(unix)]
+pub mod multi;
 pub mod port;
+mod rng;
+pub mod tap;
+pub mod teardown;
+pub mod timestamp;
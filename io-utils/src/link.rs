@@ -0,0 +1,172 @@
+//! Uniform link status.
+//!
+//! This module contains the [`LinkStatus`] reported by the `link_status_out`
+//! output of port models, so that supervisory models can react to the health
+//! of the external links in the same way for all transports, and the
+//! [`LinkMonitor`], which derives the link status of a port model from the
+//! state of its link and of its I/O thread.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_io_utils::link::{LinkMonitor, LinkStatus};
+//! use nexosim_io_utils::port::IoThreadStatus;
+//!
+//! let mut monitor = LinkMonitor::new();
+//!
+//! // Changes only are reported.
+//! assert_eq!(monitor.set_link(LinkStatus::Connected), None);
+//! assert_eq!(
+//!     monitor.set_stalled(true),
+//!     Some(LinkStatus::Degraded {
+//!         reason: "I/O thread stalled".to_string()
+//!     })
+//! );
+//! assert_eq!(monitor.set_stalled(false), Some(LinkStatus::Connected));
+//!
+//! // A stopped I/O thread disconnects the link for good.
+//! assert!(matches!(
+//!     monitor.set_io_status(&IoThreadStatus::Eof),
+//!     Some(LinkStatus::Disconnected { .. })
+//! ));
+//! assert_eq!(monitor.set_link(LinkStatus::Connected), None);
+//! ```
+
+use std::fmt;
+
+use crate::port::IoThreadStatus;
+
+/// Status of the link between a port model and its external endpoint.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum LinkStatus {
+    /// The link is up.
+    #[default]
+    Connected,
+
+    /// The link is down, with the reason.
+    Disconnected {
+        /// Reason of the disconnection.
+        reason: String,
+    },
+
+    /// The link is up with a degraded service, with the reason, e.g. some
+    /// interfaces of the port are unavailable or its I/O thread is stalled.
+    Degraded {
+        /// Reason of the degradation.
+        reason: String,
+    },
+
+    /// The link is down and is being reestablished.
+    Reconnecting,
+}
+
+impl LinkStatus {
+    /// Creates a disconnected status with the provided reason.
+    pub fn disconnected(reason: impl Into<String>) -> Self {
+        Self::Disconnected {
+            reason: reason.into(),
+        }
+    }
+
+    /// Creates a degraded status with the provided reason.
+    pub fn degraded(reason: impl Into<String>) -> Self {
+        Self::Degraded {
+            reason: reason.into(),
+        }
+    }
+
+    /// Checks whether data can be exchanged over the link, possibly with a
+    /// degraded service.
+    pub fn is_up(&self) -> bool {
+        matches!(self, Self::Connected | Self::Degraded { .. })
+    }
+}
+
+impl fmt::Display for LinkStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Connected => write!(f, "connected"),
+            Self::Disconnected { reason } => write!(f, "disconnected: {reason}"),
+            Self::Degraded { reason } => write!(f, "degraded: {reason}"),
+            Self::Reconnecting => write!(f, "reconnecting"),
+        }
+    }
+}
+
+/// Link status tracker of a port model.
+///
+/// The status combines, by order of precedence:
+/// * the disconnection caused by the exit of the I/O thread, which is final,
+/// * the degradation caused by a stalled I/O thread, if the link is
+///   otherwise connected,
+/// * the status of the link set by the port model.
+///
+/// Each update returns the new status if it changed.
+#[derive(Clone, Debug, Default)]
+pub struct LinkMonitor {
+    /// Status of the link.
+    link: LinkStatus,
+
+    /// The I/O thread is stalled.
+    is_stalled: bool,
+
+    /// Disconnection caused by the I/O thread, if it stopped.
+    stopped: Option<LinkStatus>,
+
+    /// Current status.
+    status: LinkStatus,
+}
+
+impl LinkMonitor {
+    /// Creates a link monitor for a connected link.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current status.
+    pub fn status(&self) -> &LinkStatus {
+        &self.status
+    }
+
+    /// Sets the status of the link.
+    pub fn set_link(&mut self, status: LinkStatus) -> Option<LinkStatus> {
+        self.link = status;
+
+        self.update()
+    }
+
+    /// Sets whether the I/O thread is stalled.
+    pub fn set_stalled(&mut self, is_stalled: bool) -> Option<LinkStatus> {
+        self.is_stalled = is_stalled;
+
+        self.update()
+    }
+
+    /// Applies a status of the I/O thread.
+    ///
+    /// Errors, end of file and exit of the I/O thread disconnect the link.
+    pub fn set_io_status(&mut self, status: &IoThreadStatus) -> Option<LinkStatus> {
+        if self.stopped.is_none() {
+            self.stopped = Some(LinkStatus::disconnected(status.to_string()));
+        }
+
+        self.update()
+    }
+
+    /// Updates the current status, returning it if it changed.
+    fn update(&mut self) -> Option<LinkStatus> {
+        let status = match (&self.stopped, &self.link) {
+            (Some(stopped), _) => stopped.clone(),
+            (None, LinkStatus::Connected) if self.is_stalled => {
+                LinkStatus::degraded("I/O thread stalled")
+            }
+            (None, link) => link.clone(),
+        };
+        if status == self.status {
+            return None;
+        }
+        self.status = status.clone();
+
+        Some(status)
+    }
+}
@@ -0,0 +1,86 @@
+//! Uniform port metrics.
+//!
+//! This module contains the [`PortMetrics`] reported by the `metrics_out`
+//! output of port models, so that a single dashboard model can aggregate the
+//! traffic of all the external links of a bench, whatever their transport.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_io_utils::metrics::PortMetrics;
+//! use nexosim_io_utils::port::IoThreadStats;
+//!
+//! let mut metrics = PortMetrics::default();
+//! metrics.record_in(8);
+//! metrics.record_in(3);
+//! metrics.record_out(5);
+//! metrics.record_error();
+//! metrics.set_queue_depths(&IoThreadStats {
+//!     rx_queue_depth: 2,
+//!     ..IoThreadStats::default()
+//! });
+//!
+//! assert_eq!(metrics.frames_in, 2);
+//! assert_eq!(metrics.bytes_in, 11);
+//! assert_eq!(metrics.bytes_out, 5);
+//! assert_eq!(metrics.errors, 1);
+//! assert_eq!(metrics.rx_queue_depth, 2);
+//! ```
+
+use crate::port::IoThreadStats;
+
+/// Traffic metrics of a port model.
+///
+/// Counters are cumulative since the model was built. Frames are the data
+/// units exchanged by the port, e.g. CAN frames or chunks of a byte stream,
+/// and bytes are their payload bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PortMetrics {
+    /// Frames forwarded into the simulation.
+    pub frames_in: u64,
+
+    /// Frames sent by the simulation to the port.
+    pub frames_out: u64,
+
+    /// Payload bytes forwarded into the simulation.
+    pub bytes_in: u64,
+
+    /// Payload bytes sent by the simulation to the port.
+    pub bytes_out: u64,
+
+    /// Errors reported by the port or by its I/O thread.
+    pub errors: u64,
+
+    /// Messages read by the I/O thread and not yet forwarded into the
+    /// simulation.
+    pub rx_queue_depth: u64,
+
+    /// Messages sent to the I/O thread and not yet written to the port.
+    pub tx_queue_depth: u64,
+}
+
+impl PortMetrics {
+    /// Records a frame forwarded into the simulation, with its payload
+    /// length in bytes.
+    pub fn record_in(&mut self, len: usize) {
+        self.frames_in += 1;
+        self.bytes_in += len as u64;
+    }
+
+    /// Records a frame sent to the port, with its payload length in bytes.
+    pub fn record_out(&mut self, len: usize) {
+        self.frames_out += 1;
+        self.bytes_out += len as u64;
+    }
+
+    /// Records an error.
+    pub fn record_error(&mut self) {
+        self.errors += 1;
+    }
+
+    /// Sets the queue depths from the statistics of the I/O thread.
+    pub fn set_queue_depths(&mut self, stats: &IoThreadStats) {
+        self.rx_queue_depth = stats.rx_queue_depth;
+        self.tx_queue_depth = stats.tx_queue_depth;
+    }
+}
@@ -0,0 +1,425 @@
+//! Multi-port combinator.
+//!
+//! This module contains the [`MultiPort`] combinator, which joins several
+//! heterogeneous [`IoPort`] implementations so they are served by a single
+//! [`IoThread`](crate::port::IoThread), which matters for benches with many
+//! external links running on small targets.
+//!
+//! Each port is registered in a poll instance of its own, so the ports do not
+//! need to agree on their tokens. The data read from the ports is wrapped into
+//! a common tagged type, and the data sent to the combinator is routed to the
+//! port which accepts it.
+//!
+//! #### Examples
+//!
+//! ```
+//! use std::io::Result as IoResult;
+//!
+//! use mio::net::UdpSocket;
+//! use mio::{Interest, Registry, Token};
+//!
+//! use nexosim_io_utils::multi::MultiPort;
+//! use nexosim_io_utils::port::{IoPort, IoThread};
+//!
+//! /// Binary UDP port.
+//! struct Binary {
+//!     socket: UdpSocket,
+//! }
+//!
+//! impl IoPort<UdpSocket, Vec<u8>, Vec<u8>> for Binary {
+//!     fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+//!         registry.register(&mut self.socket, Token(0), Interest::READABLE)?;
+//!
+//!         Ok(Token(1))
+//!     }
+//!
+//!     fn read(&mut self, _: Token) -> IoResult<Vec<u8>> {
+//!         let mut buffer = vec![0; 256];
+//!         let len = self.socket.recv(&mut buffer)?;
+//!         buffer.truncate(len);
+//!
+//!         Ok(buffer)
+//!     }
+//!
+//!     fn write(&mut self, data: &Vec<u8>) -> IoResult<()> {
+//!         self.socket.send(data).map(|_| ())
+//!     }
+//! }
+//!
+//! /// Text UDP port.
+//! struct Text {
+//!     socket: UdpSocket,
+//! }
+//!
+//! impl IoPort<UdpSocket, String, String> for Text {
+//!     fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+//!         registry.register(&mut self.socket, Token(0), Interest::READABLE)?;
+//!
+//!         Ok(Token(1))
+//!     }
+//!
+//!     fn read(&mut self, _: Token) -> IoResult<String> {
+//!         let mut buffer = vec![0; 256];
+//!         let len = self.socket.recv(&mut buffer)?;
+//!
+//!         Ok(String::from_utf8_lossy(&buffer[..len]).into_owned())
+//!     }
+//!
+//!     fn write(&mut self, data: &String) -> IoResult<()> {
+//!         self.socket.send(data.as_bytes()).map(|_| ())
+//!     }
+//! }
+//!
+//! /// Data of the links.
+//! #[derive(Debug, PartialEq)]
+//! enum Link {
+//!     Binary(Vec<u8>),
+//!     Text(String),
+//! }
+//!
+//! let bind = || {
+//!     let socket = UdpSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+//!     socket.connect(socket.local_addr().unwrap()).unwrap();
+//!     socket
+//! };
+//!
+//! let port = MultiPort::new()
+//!     .with_port(Binary { socket: bind() }, Link::Binary, |data| match data {
+//!         Link::Binary(data) => Some(data),
+//!         _ => None,
+//!     })
+//!     .with_port(Text { socket: bind() }, Link::Text, |data| match data {
+//!         Link::Text(data) => Some(data),
+//!         _ => None,
+//!     });
+//!
+//! let mut io_thread: IoThread<Link, Link> = IoThread::new(port);
+//! io_thread.send(Link::Text("hello".into())).unwrap();
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{ErrorKind, Result as IoResult};
+use std::marker::PhantomData;
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+
+use mio::event::Source;
+use mio::unix::SourceFd;
+use mio::{Events, Interest, Poll, Registry, Token};
+
+use crate::port::IoPort;
+
+/// Type-erased port joined by a [`MultiPort`].
+trait Branch<R, T>: Send {
+    /// Registers the port in MIO.
+    fn register(&mut self, registry: &Registry) -> IoResult<()>;
+
+    /// Reads the data corresponding to token.
+    fn read(&mut self, token: Token) -> IoResult<R>;
+
+    /// Writes data, or returns `None` if the data is not for this port.
+    fn write(&mut self, data: &T) -> Option<IoResult<()>>;
+
+    /// Resumes the pending writes once writable.
+    fn writable(&mut self, token: Token) -> IoResult<()>;
+
+    /// Returns the next deadline of the port.
+    fn deadline(&mut self) -> Option<Instant>;
+
+    /// Handles an elapsed deadline.
+    fn timeout(&mut self) -> IoResult<R>;
+
+    /// Checks whether data is still to be written.
+    fn is_write_pending(&mut self) -> bool;
+}
+
+/// Port joined by a [`MultiPort`], with its data conversions.
+struct Joined<S: ?Sized, P, RP, TP, F, G> {
+    /// Port.
+    port: P,
+
+    /// Read data conversion.
+    wrap: F,
+
+    /// Written data selection.
+    unwrap: G,
+
+    /// Data not yet accepted by the port.
+    pending: VecDeque<TP>,
+
+    _phantom: PhantomData<fn(&S, RP)>,
+}
+
+impl<S, P, R, T, RP, TP, F, G> Branch<R, T> for Joined<S, P, RP, TP, F, G>
+where
+    S: Source + ?Sized,
+    P: IoPort<S, RP, TP> + Send,
+    RP: Send,
+    TP: Clone + Send,
+    F: Fn(RP) -> R + Send,
+    G: Fn(&T) -> Option<&TP> + Send,
+{
+    fn register(&mut self, registry: &Registry) -> IoResult<()> {
+        // The port is never woken up, so its waker token is not used.
+        self.port.register(registry).map(|_| ())
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<R> {
+        self.port.read(token).map(&self.wrap)
+    }
+
+    fn write(&mut self, data: &T) -> Option<IoResult<()>> {
+        let data = (self.unwrap)(data)?;
+        if !self.pending.is_empty() {
+            self.pending.push_back(data.clone());
+
+            return Some(Ok(()));
+        }
+
+        match self.port.write(data) {
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                self.pending.push_back(data.clone());
+
+                Some(Ok(()))
+            }
+            result => Some(result),
+        }
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        self.port.writable(token)?;
+        while let Some(data) = self.pending.front() {
+            match self.port.write(data) {
+                Ok(()) => {
+                    self.pending.pop_front();
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        self.port.deadline()
+    }
+
+    fn timeout(&mut self) -> IoResult<R> {
+        self.port.timeout().map(&self.wrap)
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        !self.pending.is_empty() || self.port.is_write_pending()
+    }
+}
+
+/// Port joined by a [`MultiPort`], with its poll instance.
+struct Member<R, T> {
+    /// Port.
+    port: Box<dyn Branch<R, T>>,
+
+    /// Poll instance of the port, created at registration.
+    poll: Option<Poll>,
+
+    /// Events of the port.
+    events: Events,
+
+    /// Tokens of the events to be read.
+    tokens: VecDeque<Token>,
+}
+
+impl<R, T> Member<R, T> {
+    /// Polls the events of the port without blocking, resuming the pending
+    /// writes and queuing the tokens to be read.
+    ///
+    /// Returns whether events were received.
+    fn poll_events(&mut self) -> IoResult<bool> {
+        let Some(poll) = self.poll.as_mut() else {
+            return Ok(false);
+        };
+        loop {
+            match poll.poll(&mut self.events, Some(Duration::ZERO)) {
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+                Ok(()) => break,
+            }
+        }
+        for event in self.events.iter() {
+            let token = event.token();
+            if event.is_writable() {
+                self.port.writable(token)?;
+            }
+            if !self.tokens.contains(&token) {
+                self.tokens.push_back(token);
+            }
+        }
+
+        Ok(!self.events.is_empty())
+    }
+}
+
+/// I/O port joining several heterogeneous I/O ports.
+///
+/// The port joined at index `i` is assigned token `i`, and the waker token is
+/// the number of joined ports. The data read from a port is converted to `R`,
+/// and the data of type `T` is written to the first port which accepts it;
+/// data accepted by no port is discarded.
+pub struct MultiPort<R, T> {
+    /// Joined ports.
+    members: Vec<Member<R, T>>,
+}
+
+impl<R, T> MultiPort<R, T>
+where
+    R: Send + 'static,
+    T: Send + 'static,
+{
+    /// Creates a combinator without ports.
+    pub fn new() -> Self {
+        Self {
+            members: Vec::new(),
+        }
+    }
+
+    /// Joins a port.
+    ///
+    /// The data read from the port is converted with `wrap`, typically a
+    /// variant of a tagged enum, and the data for which `unwrap` returns a
+    /// value is written to the port. Data which cannot be written immediately
+    /// is cloned and kept until the port is writable.
+    pub fn with_port<S, P, RP, TP, F, G>(mut self, port: P, wrap: F, unwrap: G) -> Self
+    where
+        S: Source + ?Sized + 'static,
+        P: IoPort<S, RP, TP> + Send + 'static,
+        RP: Send + 'static,
+        TP: Clone + Send + 'static,
+        F: Fn(RP) -> R + Send + 'static,
+        G: Fn(&T) -> Option<&TP> + Send + 'static,
+    {
+        self.members.push(Member {
+            port: Box::new(Joined {
+                port,
+                wrap,
+                unwrap,
+                pending: VecDeque::new(),
+                _phantom: PhantomData,
+            }),
+            poll: None,
+            events: Events::with_capacity(64),
+            tokens: VecDeque::new(),
+        });
+
+        self
+    }
+}
+
+impl<R, T> Default for MultiPort<R, T>
+where
+    R: Send + 'static,
+    T: Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R, T> IoPort<SourceFd<'static>, R, T> for MultiPort<R, T>
+where
+    R: Send,
+    T: Send,
+{
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        for (index, member) in self.members.iter_mut().enumerate() {
+            let poll = Poll::new()?;
+            member.port.register(poll.registry())?;
+            registry.register(
+                &mut SourceFd(&poll.as_raw_fd()),
+                Token(index),
+                Interest::READABLE,
+            )?;
+            member.poll = Some(poll);
+        }
+
+        Ok(Token(self.members.len()))
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<R> {
+        let Some(member) = self.members.get_mut(token.0) else {
+            // Unknown event: should never happen.
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "Unknown event.",
+            ));
+        };
+        loop {
+            while let Some(&token) = member.tokens.front() {
+                match member.port.read(token) {
+                    Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                        member.tokens.pop_front();
+                    }
+                    result => return result,
+                }
+            }
+            if !member.poll_events()? {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+        }
+    }
+
+    fn write(&mut self, data: &T) -> IoResult<()> {
+        for member in &mut self.members {
+            if let Some(result) = member.port.write(data) {
+                return result;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        self.members
+            .iter_mut()
+            .filter_map(|member| member.port.deadline())
+            .min()
+    }
+
+    fn timeout(&mut self) -> IoResult<R> {
+        let now = Instant::now();
+        for member in &mut self.members {
+            if member.port.deadline().is_none_or(|deadline| deadline > now) {
+                continue;
+            }
+            match member.port.timeout() {
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+                result => return result,
+            }
+        }
+
+        Err(ErrorKind::WouldBlock.into())
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        // The writable events of the ports are only received when their poll
+        // instances are polled, so the pending writes are resumed here while
+        // the I/O thread flushes them on shutdown.
+        let mut is_pending = false;
+        for member in &mut self.members {
+            if member.port.is_write_pending() {
+                let _ = member.poll_events();
+                is_pending |= member.port.is_write_pending();
+            }
+        }
+
+        is_pending
+    }
+}
+
+impl<R, T> fmt::Debug for MultiPort<R, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MultiPort")
+            .field("ports", &self.members.len())
+            .finish_non_exhaustive()
+    }
+}
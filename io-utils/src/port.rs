@@ -13,12 +13,57 @@
 //! external thread from the model:
 //!
 //! * [`IoThread::try_recv`] that tries to receive data from the external port,
-//! * [`IoThread::send`] that sends data to the external port.
+//!   or [`IoThread::try_recv_all`] that receives all available data at once,
+//! * [`IoThread::send`] that sends data to the external port, also available
+//!   from several models through cloneable [`IoSender`] handles, see
+//!   [`IoThread::sender`].
+//!
+//! The I/O thread stops when the port fails or reaches its end of file. The
+//! model can learn about it, and about the exit of the I/O thread, with
+//! [`IoThread::try_recv_status`], or forward these statuses along with the
+//! stalls of the I/O thread to its outputs with [`IoThread::forward_status`].
+//!
+//! The I/O thread also maintains a heartbeat which can be used by the model to
+//! detect a stalled I/O loop, see [`IoThread::check_stall`],
+//! [`IoThread::heartbeat_age`] and [`IoThreadOptions::heartbeat_period`], and
+//! counts the messages it reads and writes, see [`IoThread::stats`].
 //!
 //! The [`IoThread`] constructor accepts an implementor of the [`IoPort`]
 //! trait. This trait allows registering of the I/O port in MIO and
 //! reading/writing data.
 //!
+//! An [`IoPort`] implementor can also be connected to a simulation without a
+//! dedicated model with the generic
+//! [`ExternalPort`](crate::external::ExternalPort) model, the data it reads
+//! can be stamped with its receive time by wrapping it in a
+//! [`Timestamped`](crate::timestamp::Timestamped) port, and its traffic can be
+//! recorded by wrapping it in a [`Tapped`](crate::tap::Tapped) port. Several
+//! ports can be served by a single I/O thread by joining them with a
+//! [`MultiPort`](crate::multi::MultiPort) (Unix only).
+//!
+//! # Timers
+//!
+//! Ports which need real-time deadlines, e.g. to detect the end of a frame
+//! after an idle gap or to send keepalives, can return their next deadline
+//! from [`IoPort::deadline`]. The I/O thread then wakes up at the deadline
+//! and calls [`IoPort::timeout`], without involving the simulation
+//! scheduler.
+//!
+//! # Partial writes
+//!
+//! A port which cannot accept a message yet, e.g. because the send buffer of
+//! its device is full, can return [`ErrorKind::WouldBlock`] from
+//! [`IoPort::write`] after adding the writable interest to its source: the
+//! message and the following ones are then kept by the I/O thread and written
+//! again once the port is writable. Ports writing byte streams can instead
+//! keep the unwritten part of a message in a [`WriteBuffer`] and resume the
+//! write from [`IoPort::writable`].
+//!
+//! When the I/O thread is dropped, the data not yet written is discarded,
+//! unless [`IoThreadOptions::shutdown_timeout`] is set: the I/O thread then
+//! keeps writing the pending data, including the data reported by
+//! [`IoPort::is_write_pending`], until done or until the timeout elapses.
+//!
 //! #### Examples
 //!
 //! I/O port that uses UDP for communication with the external world:
@@ -57,11 +102,10 @@
 //! }
 //!
 //! impl IoPort<UdpSocket, Data, Data> for Udp {
-//!     fn register(&mut self, registry: &Registry) -> Token {
-//!         registry
-//!             .register(&mut self.socket, Token(0), Interest::READABLE)
-//!             .unwrap();
-//!         Token(1)
+//!     fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+//!         registry.register(&mut self.socket, Token(0), Interest::READABLE)?;
+//!
+//!         Ok(Token(1))
 //!     }
 //!
 //!     fn read(&mut self, token: Token) -> IoResult<Data> {
@@ -100,21 +144,26 @@
 //! }
 //! ```
 
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt;
-use std::io::{ErrorKind, Result as IoResult};
+use std::io::{ErrorKind, Result as IoResult, Write};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc::{
     Receiver, SendError as MpscSendError, Sender, TryRecvError as MpscTryRecvError, channel,
 };
 use std::thread;
+use std::time::{Duration, Instant};
 
 use mio::event::Source;
 use mio::{Events, Poll, Registry, Token, Waker};
 
+use nexosim::ports::Output;
 use nexosim_util::joiners::ThreadJoiner;
 
+use crate::teardown::IoThreadGuard;
+
 /// I/O port(s) usable by MIO.
 pub trait IoPort<S, R, T>
 where
@@ -124,14 +173,144 @@ where
 {
     /// Registers port(s) in MIO.
     ///
-    /// This function should return waker token.
-    fn register(&mut self, registry: &Registry) -> Token;
+    /// This function should return waker token, or an error if the port(s)
+    /// cannot be registered.
+    fn register(&mut self, registry: &Registry) -> IoResult<Token>;
 
     /// Reads data corresponding to token.
+    ///
+    /// Errors other than [`ErrorKind::WouldBlock`] stop the I/O thread. A port
+    /// which reached its end of file should return
+    /// [`ErrorKind::UnexpectedEof`].
     fn read(&mut self, token: Token) -> IoResult<R>;
 
     /// Writes data.
+    ///
+    /// If [`ErrorKind::WouldBlock`] is returned, the I/O thread writes the
+    /// data again after the next writable event, while other errors stop the
+    /// I/O thread.
     fn write(&mut self, data: &T) -> IoResult<()>;
+
+    /// Writes data, taking ownership of it.
+    ///
+    /// This function is called by the I/O thread in place of
+    /// [`IoPort::write`], and can be implemented by ports which keep the data,
+    /// e.g. a resource moved through the channel. The data is handed back
+    /// along with the error, so that it can be written again if
+    /// [`ErrorKind::WouldBlock`] is returned. The default implementation
+    /// calls [`IoPort::write`].
+    fn write_owned(&mut self, data: T) -> Result<(), (T, std::io::Error)> {
+        self.write(&data).map_err(|e| (data, e))
+    }
+
+    /// Resumes the pending writes of the port(s) corresponding to token once
+    /// writable.
+    ///
+    /// This function is called for each writable event, before the data
+    /// corresponding to the token is read. The default implementation does
+    /// nothing.
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        let _ = token;
+
+        Ok(())
+    }
+
+    /// Returns the next deadline of the port(s), if any.
+    ///
+    /// This function is called before each poll, and the I/O thread wakes up
+    /// at the returned deadline if no event occurs before. The default
+    /// implementation returns `None`.
+    fn deadline(&mut self) -> Option<Instant> {
+        None
+    }
+
+    /// Handles an elapsed deadline.
+    ///
+    /// This function is called repeatedly once the deadline returned by
+    /// [`IoPort::deadline`] has elapsed, and the returned data is forwarded as
+    /// read data. [`ErrorKind::WouldBlock`] should be returned once done, and
+    /// the deadline should then be moved or cleared. Other errors stop the
+    /// I/O thread. The default implementation returns
+    /// [`ErrorKind::WouldBlock`].
+    fn timeout(&mut self) -> IoResult<R> {
+        Err(ErrorKind::WouldBlock.into())
+    }
+
+    /// Checks whether data accepted by [`IoPort::write`] is still to be
+    /// written by the port(s), e.g. from a [`WriteBuffer`].
+    ///
+    /// This function is used to flush the pending writes on shutdown. The
+    /// default implementation returns `false`.
+    fn is_write_pending(&mut self) -> bool {
+        false
+    }
+}
+
+/// Write buffer resuming partial writes.
+///
+/// Data which cannot be written immediately is kept in the buffer and written
+/// by [`WriteBuffer::flush`], typically when the writer becomes writable.
+/// The writable interest of the writer should be registered while the buffer
+/// is not empty.
+#[derive(Debug, Default)]
+pub struct WriteBuffer {
+    /// Data not yet written.
+    buf: Vec<u8>,
+}
+
+impl WriteBuffer {
+    /// Creates an empty write buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks whether all data has been written.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Returns the number of bytes not yet written.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Discards the data not yet written.
+    pub fn clear(&mut self) {
+        self.buf.clear();
+    }
+
+    /// Appends data to be written by the next flush.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Writes data after the pending data, keeping the part which cannot be
+    /// written immediately.
+    ///
+    /// Returns whether all data has been written.
+    pub fn write<W: Write + ?Sized>(&mut self, writer: &mut W, data: &[u8]) -> IoResult<bool> {
+        self.push(data);
+        self.flush(writer)
+    }
+
+    /// Writes the pending data until the writer would block.
+    ///
+    /// Returns whether all data has been written.
+    pub fn flush<W: Write + ?Sized>(&mut self, writer: &mut W) -> IoResult<bool> {
+        while !self.buf.is_empty() {
+            match writer.write(&self.buf) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(len) => {
+                    self.buf.drain(..len);
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(true)
+    }
 }
 
 /// Send error.
@@ -204,6 +383,153 @@ impl fmt::Display for TryRecvError {
 
 impl Error for TryRecvError {}
 
+/// Status of an I/O thread.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IoThreadStatus {
+    /// Reading from the port failed, with the error kind and description.
+    ///
+    /// The I/O thread stops after this error.
+    ReadError(ErrorKind, String),
+
+    /// Writing to the port failed, with the error kind and description.
+    ///
+    /// The I/O thread stops after this error.
+    WriteError(ErrorKind, String),
+
+    /// The port reached its end of file.
+    ///
+    /// The I/O thread stops after this event.
+    Eof,
+
+    /// The I/O thread exited.
+    ///
+    /// This is the last status sent by the I/O thread.
+    Exited,
+}
+
+impl IoThreadStatus {
+    /// Creates the status corresponding to a read error.
+    pub(crate) fn read_error(error: std::io::Error) -> Self {
+        match error.kind() {
+            ErrorKind::UnexpectedEof => Self::Eof,
+            kind => Self::ReadError(kind, error.to_string()),
+        }
+    }
+
+    /// Creates the status corresponding to a write error.
+    pub(crate) fn write_error(error: std::io::Error) -> Self {
+        Self::WriteError(error.kind(), error.to_string())
+    }
+}
+
+impl fmt::Display for IoThreadStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ReadError(kind, error) => write!(f, "read error ({kind}): {error}"),
+            Self::WriteError(kind, error) => write!(f, "write error ({kind}): {error}"),
+            Self::Eof => write!(f, "end of file"),
+            Self::Exited => write!(f, "I/O thread exited"),
+        }
+    }
+}
+
+/// I/O thread creation error.
+#[derive(Debug)]
+pub enum IoThreadError {
+    /// MIO poll instance could not be created.
+    Poll(std::io::Error),
+
+    /// Port(s) could not be registered.
+    Register(std::io::Error),
+
+    /// Waker could not be created.
+    Waker(std::io::Error),
+
+    /// Thread could not be spawned.
+    Spawn(std::io::Error),
+}
+
+impl fmt::Display for IoThreadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Poll(error) => write!(f, "poll instance could not be created: {error}"),
+            Self::Register(error) => write!(f, "port could not be registered: {error}"),
+            Self::Waker(error) => write!(f, "waker could not be created: {error}"),
+            Self::Spawn(error) => write!(f, "I/O thread could not be spawned: {error}"),
+        }
+    }
+}
+
+impl Error for IoThreadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Poll(error) | Self::Register(error) | Self::Waker(error) | Self::Spawn(error) => {
+                Some(error)
+            }
+        }
+    }
+}
+
+/// I/O thread options.
+#[derive(Clone, Debug, Default)]
+pub struct IoThreadOptions {
+    /// Period at which the I/O thread updates its heartbeat when idle.
+    ///
+    /// If no value is provided, the heartbeat is only updated when the I/O
+    /// thread processes events.
+    pub heartbeat_period: Option<Duration>,
+
+    /// Maximum time spent writing the pending data when the I/O thread is
+    /// dropped.
+    ///
+    /// If no value is provided, the data not yet written is discarded.
+    pub shutdown_timeout: Option<Duration>,
+}
+
+/// I/O thread statistics.
+///
+/// Messages are counted as exchanged between the model and the I/O thread,
+/// whatever their content.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct IoThreadStats {
+    /// Messages read from the port(s) since the I/O thread was created.
+    pub read: u64,
+
+    /// Messages written to the port(s) since the I/O thread was created.
+    pub written: u64,
+
+    /// Messages read from the port(s) and not yet received by the model.
+    pub rx_queue_depth: u64,
+
+    /// Messages sent by the model and not yet written to the port(s).
+    pub tx_queue_depth: u64,
+}
+
+/// Change of the stall state of an I/O thread.
+///
+/// See [`IoThread::check_stall`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StallChange {
+    /// The I/O thread stalled, with the time elapsed since its last heartbeat.
+    Stalled(Duration),
+
+    /// The I/O thread recovered from a stall.
+    Recovered,
+}
+
+/// Message counters shared with the I/O thread.
+#[derive(Debug, Default)]
+struct IoCounters {
+    /// Messages read from the port(s).
+    read: AtomicU64,
+
+    /// Messages sent to the I/O thread.
+    sent: AtomicU64,
+
+    /// Messages written to the port(s).
+    written: AtomicU64,
+}
+
 /// I/O thread.
 pub struct IoThread<R, T>
 where
@@ -214,17 +540,36 @@ where
     // This field must precede waker in order for drop to work properly.
     _io_thread: ThreadJoiner<()>,
 
+    /// Live I/O thread accounting.
+    // This field must follow the thread handle so it is dropped after join.
+    _guard: IoThreadGuard,
+
     /// Data receiver.
     receiver: Receiver<R>,
 
     /// Data sender.
-    transmitter: Sender<T>,
+    sender: IoSender<T>,
 
-    /// Thread waker.
-    waker: Arc<Waker>,
+    /// I/O thread status receiver.
+    status: Receiver<IoThreadStatus>,
 
     /// Simulation halted flag.
     is_halted: Arc<AtomicBool>,
+
+    /// I/O thread start time.
+    start: Instant,
+
+    /// Time of the last I/O thread heartbeat, in nanoseconds since start.
+    heartbeat: Arc<AtomicU64>,
+
+    /// Stalled I/O thread flag, as last checked by the model.
+    is_stalled: bool,
+
+    /// Message counters.
+    counters: Arc<IoCounters>,
+
+    /// Messages received from the I/O thread.
+    consumed: AtomicU64,
 }
 
 impl<R, T> IoThread<R, T>
@@ -233,43 +578,132 @@ where
     T: Send + 'static,
 {
     /// Creates new I/O thread.
-    pub fn new<S, P>(mut port: P) -> Self
+    ///
+    /// # Panics
+    ///
+    /// Panics if the I/O thread cannot be created, see [`IoThread::try_new`].
+    pub fn new<S, P>(port: P) -> Self
+    where
+        S: Source + ?Sized,
+        P: IoPort<S, R, T> + Send + 'static,
+    {
+        Self::try_new(port).unwrap()
+    }
+
+    /// Creates new I/O thread with the specified options.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the I/O thread cannot be created, see
+    /// [`IoThread::try_with_options`].
+    pub fn with_options<S, P>(port: P, options: IoThreadOptions) -> Self
+    where
+        S: Source + ?Sized,
+        P: IoPort<S, R, T> + Send + 'static,
+    {
+        Self::try_with_options(port, options).unwrap()
+    }
+
+    /// Creates new I/O thread, or returns an error if the port(s) cannot be
+    /// registered or the thread cannot be spawned.
+    pub fn try_new<S, P>(port: P) -> Result<Self, IoThreadError>
+    where
+        S: Source + ?Sized,
+        P: IoPort<S, R, T> + Send + 'static,
+    {
+        Self::try_with_options(port, IoThreadOptions::default())
+    }
+
+    /// Creates new I/O thread with the specified options, or returns an error
+    /// if the port(s) cannot be registered or the thread cannot be spawned.
+    pub fn try_with_options<S, P>(
+        mut port: P,
+        options: IoThreadOptions,
+    ) -> Result<Self, IoThreadError>
     where
         S: Source + ?Sized,
         P: IoPort<S, R, T> + Send + 'static,
     {
         let (tx, receiver) = channel();
         let (transmitter, rx) = channel();
+        let (status_tx, status) = channel();
 
         let is_halted = Arc::new(AtomicBool::new(false));
         let io_is_halted = is_halted.clone();
+        let wake_pending = Arc::new(AtomicBool::new(false));
+        let io_wake_pending = wake_pending.clone();
+
+        let start = Instant::now();
+        let heartbeat = Arc::new(AtomicU64::new(0));
+        let io_heartbeat = heartbeat.clone();
+        let counters = Arc::new(IoCounters::default());
+        let io_counters = counters.clone();
 
-        let mut poll = Poll::new().unwrap();
-        let wake = port.register(poll.registry());
-        let waker = Arc::new(Waker::new(poll.registry(), wake).unwrap());
+        let mut poll = Poll::new().map_err(IoThreadError::Poll)?;
+        let wake = port
+            .register(poll.registry())
+            .map_err(IoThreadError::Register)?;
+        let waker = Arc::new(Waker::new(poll.registry(), wake).map_err(IoThreadError::Waker)?);
 
         // I/O thread.
-        let io_thread = thread::spawn(move || {
+        let io_thread = thread::Builder::new().spawn(move || {
             let mut events = Events::with_capacity(256);
+            // Data not yet accepted by the port.
+            let mut pending = VecDeque::new();
             'poll: loop {
+                let timeout = poll_timeout(port.deadline(), options.heartbeat_period);
                 // This call is blocking.
-                poll.poll(&mut events, None).unwrap();
+                if let Err(e) = poll.poll(&mut events, timeout) {
+                    if e.kind() == ErrorKind::Interrupted {
+                        continue;
+                    }
+                    let _ = status_tx.send(IoThreadStatus::read_error(e));
+                    break;
+                }
+                io_heartbeat.store(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
 
                 for event in events.iter() {
                     let token = event.token();
                     if token == wake {
                         if io_is_halted.load(Ordering::Relaxed) {
+                            if let Some(timeout) = options.shutdown_timeout {
+                                pending.extend(rx.try_iter());
+                                let deadline = Instant::now() + timeout;
+                                if let Err(e) = flush_pending(
+                                    &mut poll,
+                                    &mut events,
+                                    &mut port,
+                                    &mut pending,
+                                    &io_counters,
+                                    deadline,
+                                ) {
+                                    let _ = status_tx.send(IoThreadStatus::write_error(e));
+                                }
+                            }
+                            break 'poll;
+                        }
+                        // The flag is cleared before the channel is drained so
+                        // that data sent afterwards triggers a new wake-up.
+                        io_wake_pending.store(false, Ordering::SeqCst);
+                        pending.extend(rx.try_iter());
+                        if let Err(e) = write_pending(&mut port, &mut pending, &io_counters) {
+                            let _ = status_tx.send(IoThreadStatus::write_error(e));
                             break 'poll;
                         }
-                        while let Ok(data) = rx.try_recv() {
-                            if port.write(&data).is_err() {
+                    } else {
+                        if event.is_writable() {
+                            if let Err(e) = port
+                                .writable(token)
+                                .and_then(|_| write_pending(&mut port, &mut pending, &io_counters))
+                            {
+                                let _ = status_tx.send(IoThreadStatus::write_error(e));
                                 break 'poll;
                             }
                         }
-                    } else {
                         loop {
                             match port.read(token) {
                                 Ok(message) => {
+                                    io_counters.read.fetch_add(1, Ordering::Relaxed);
                                     if tx.send(message).is_err() {
                                         break 'poll;
                                     }
@@ -277,35 +711,325 @@ where
                                 Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
                                     break;
                                 }
-                                _ => break 'poll,
+                                Err(e) => {
+                                    let _ = status_tx.send(IoThreadStatus::read_error(e));
+                                    break 'poll;
+                                }
+                            }
+                        }
+                    }
+                }
+                if port
+                    .deadline()
+                    .is_some_and(|deadline| deadline <= Instant::now())
+                {
+                    loop {
+                        match port.timeout() {
+                            Ok(message) => {
+                                io_counters.read.fetch_add(1, Ordering::Relaxed);
+                                if tx.send(message).is_err() {
+                                    break 'poll;
+                                }
+                            }
+                            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                                break;
+                            }
+                            Err(e) => {
+                                let _ = status_tx.send(IoThreadStatus::read_error(e));
+                                break 'poll;
                             }
                         }
                     }
                 }
             }
+            let _ = status_tx.send(IoThreadStatus::Exited);
         });
-        Self {
+        let io_thread = io_thread.map_err(IoThreadError::Spawn)?;
+
+        Ok(Self {
             _io_thread: ThreadJoiner::new(io_thread),
+            _guard: IoThreadGuard::new(),
             receiver,
-            transmitter,
-            waker,
+            sender: IoSender {
+                transmitter,
+                waker,
+                wake_pending,
+                counters: counters.clone(),
+            },
+            status,
             is_halted,
-        }
+            start,
+            heartbeat,
+            is_stalled: false,
+            counters,
+            consumed: AtomicU64::new(0),
+        })
     }
 
     /// Tries to receives data from I/O thread.
     pub fn try_recv(&self) -> Result<R, TryRecvError> {
-        Ok(self.receiver.try_recv()?)
+        let data = self.receiver.try_recv()?;
+        self.consumed.fetch_add(1, Ordering::Relaxed);
+
+        Ok(data)
+    }
+
+    /// Receives all data currently available from I/O thread.
+    ///
+    /// The returned vector is empty if no data is available, including once
+    /// the I/O thread has exited.
+    pub fn try_recv_all(&self) -> Vec<R> {
+        let data: Vec<R> = self.receiver.try_iter().collect();
+        self.consumed
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+
+        data
+    }
+
+    /// Tries to receive the status of the I/O thread.
+    ///
+    /// Once the I/O thread has exited and all statuses have been received,
+    /// [`TryRecvError::Disconnected`] is returned.
+    pub fn try_recv_status(&self) -> Result<IoThreadStatus, TryRecvError> {
+        Ok(self.status.try_recv()?)
     }
 
     /// Sends data to I/O thread.
+    ///
+    /// An error is returned once the I/O thread has exited. Port models
+    /// usually drop the data in this case, since the exit is reported by
+    /// [`IoThread::try_recv_status`]. See [`IoSender::send`].
     pub fn send(&mut self, data: T) -> Result<(), SendError> {
+        self.sender.send(data)
+    }
+
+    /// Returns a sender handle to the I/O thread.
+    ///
+    /// Sender handles can be cloned and handed over to several models which
+    /// transmit to the same port. Sending fails once the I/O thread has
+    /// exited, in particular after the [`IoThread`] is dropped.
+    pub fn sender(&self) -> IoSender<T> {
+        self.sender.clone()
+    }
+
+    /// Returns the time elapsed since the last I/O thread heartbeat.
+    ///
+    /// An I/O thread is considered stalled if this value exceeds the heartbeat
+    /// period by a significant margin.
+    pub fn heartbeat_age(&self) -> Duration {
+        let heartbeat = Duration::from_nanos(self.heartbeat.load(Ordering::Relaxed));
+
+        self.start.elapsed().saturating_sub(heartbeat)
+    }
+
+    /// Checks whether the I/O thread is stalled.
+    ///
+    /// The I/O thread is considered stalled when its heartbeat is older than
+    /// `timeout`. A change is only returned on the first check after the I/O
+    /// thread stalled or recovered, so that a stall is reported once until it
+    /// recovers.
+    pub fn check_stall(&mut self, timeout: Duration) -> Option<StallChange> {
+        let age = self.heartbeat_age();
+        let is_stalled = age > timeout;
+        if is_stalled == self.is_stalled {
+            return None;
+        }
+        self.is_stalled = is_stalled;
+
+        Some(if is_stalled {
+            StallChange::Stalled(age)
+        } else {
+            StallChange::Recovered
+        })
+    }
+
+    /// Forwards the statuses of the I/O thread and reports its stall once,
+    /// until it recovers.
+    ///
+    /// This is the status reporting of port models with an I/O status output
+    /// and a stall output. The stall is only checked if a watchdog timeout is
+    /// provided, see [`IoThread::check_stall`].
+    pub async fn forward_status(
+        &mut self,
+        watchdog_timeout: Option<Duration>,
+        io_status_out: &mut Output<IoThreadStatus>,
+        stalled_out: &mut Output<Duration>,
+    ) {
+        while let Ok(status) = self.try_recv_status() {
+            io_status_out.send(status).await;
+        }
+        if let Some(StallChange::Stalled(age)) =
+            watchdog_timeout.and_then(|timeout| self.check_stall(timeout))
+        {
+            stalled_out.send(age).await;
+        }
+    }
+
+    /// Returns `true` if the I/O thread was stalled on the last check.
+    ///
+    /// See [`IoThread::check_stall`].
+    pub fn is_stalled(&self) -> bool {
+        self.is_stalled
+    }
+
+    /// Returns the message statistics of the I/O thread.
+    ///
+    /// Messages sent through [`IoSender`] handles are included.
+    pub fn stats(&self) -> IoThreadStats {
+        let read = self.counters.read.load(Ordering::Relaxed);
+        let sent = self.counters.sent.load(Ordering::Relaxed);
+        let written = self.counters.written.load(Ordering::Relaxed);
+        let consumed = self.consumed.load(Ordering::Relaxed);
+
+        IoThreadStats {
+            read,
+            written,
+            rx_queue_depth: read.saturating_sub(consumed),
+            tx_queue_depth: sent.saturating_sub(written),
+        }
+    }
+}
+
+/// Cloneable sender handle to an I/O thread.
+pub struct IoSender<T>
+where
+    T: Send,
+{
+    /// Data sender.
+    transmitter: Sender<T>,
+
+    /// Thread waker.
+    waker: Arc<Waker>,
+
+    /// Wake-up requested and not yet handled by the I/O thread.
+    wake_pending: Arc<AtomicBool>,
+
+    /// Message counters.
+    counters: Arc<IoCounters>,
+}
+
+impl<T> IoSender<T>
+where
+    T: Send,
+{
+    /// Sends data to I/O thread.
+    ///
+    /// The I/O thread is only woken up if a previous wake-up has already been
+    /// handled, which saves a system call per message at high rates.
+    pub fn send(&self, data: T) -> Result<(), SendError> {
         self.transmitter.send(data)?;
-        self.waker.wake()?;
+        self.counters.sent.fetch_add(1, Ordering::Relaxed);
+        if !self.wake_pending.swap(true, Ordering::SeqCst) {
+            self.waker.wake().inspect_err(|_| {
+                self.wake_pending.store(false, Ordering::SeqCst);
+            })?;
+        }
         Ok(())
     }
 }
 
+impl<T> Clone for IoSender<T>
+where
+    T: Send,
+{
+    fn clone(&self) -> Self {
+        Self {
+            transmitter: self.transmitter.clone(),
+            waker: self.waker.clone(),
+            wake_pending: self.wake_pending.clone(),
+            counters: self.counters.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for IoSender<T>
+where
+    T: Send,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IoSender").finish_non_exhaustive()
+    }
+}
+
+/// Returns the poll timeout until the deadline of the port(s), bounded by the
+/// optional period.
+pub(crate) fn poll_timeout(
+    deadline: Option<Instant>,
+    period: Option<Duration>,
+) -> Option<Duration> {
+    let timeout = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+    match (timeout, period) {
+        (Some(timeout), Some(period)) => Some(timeout.min(period)),
+        (timeout, period) => timeout.or(period),
+    }
+}
+
+/// Writes the pending data until the port would block.
+fn write_pending<S, R, T, P>(
+    port: &mut P,
+    pending: &mut VecDeque<T>,
+    counters: &IoCounters,
+) -> IoResult<()>
+where
+    S: Source + ?Sized,
+    R: Send,
+    T: Send,
+    P: IoPort<S, R, T>,
+{
+    while let Some(data) = pending.pop_front() {
+        match port.write_owned(data) {
+            Ok(()) => {
+                counters.written.fetch_add(1, Ordering::Relaxed);
+            }
+            Err((data, e)) if e.kind() == ErrorKind::WouldBlock => {
+                pending.push_front(data);
+                break;
+            }
+            Err((_, e)) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the pending data, waiting for the port to be writable, until all
+/// data has been written or until the deadline.
+fn flush_pending<S, R, T, P>(
+    poll: &mut Poll,
+    events: &mut Events,
+    port: &mut P,
+    pending: &mut VecDeque<T>,
+    counters: &IoCounters,
+    deadline: Instant,
+) -> IoResult<()>
+where
+    S: Source + ?Sized,
+    R: Send,
+    T: Send,
+    P: IoPort<S, R, T>,
+{
+    loop {
+        write_pending(port, pending, counters)?;
+        if pending.is_empty() && !port.is_write_pending() {
+            return Ok(());
+        }
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if timeout.is_zero() {
+            return Ok(());
+        }
+        match poll.poll(events, Some(timeout)) {
+            Err(e) if e.kind() != ErrorKind::Interrupted => return Err(e),
+            _ => {}
+        }
+        for event in events.iter() {
+            if event.is_writable() {
+                port.writable(event.token())?;
+            }
+        }
+    }
+}
+
 impl<R, T> Drop for IoThread<R, T>
 where
     R: Send,
@@ -313,7 +1037,7 @@ where
 {
     fn drop(&mut self) {
         self.is_halted.store(true, Ordering::Relaxed);
-        let _ = self.waker.wake();
+        let _ = self.sender.waker.wake();
     }
 }
 
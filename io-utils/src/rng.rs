@@ -0,0 +1,49 @@
+//! Seeded random number generation for impairment models.
+
+/// SplitMix64 random number generator.
+pub(crate) struct Rng {
+    /// Generator state.
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a generator from a seed.
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns a random integer.
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random number uniformly distributed in [0, 1).
+    pub(crate) fn uniform(&mut self) -> f64 {
+        // 53 bits of precision.
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a random number with a standard normal distribution.
+    pub(crate) fn normal(&mut self) -> f64 {
+        // Box-Muller transform, with a first sample in (0, 1].
+        let u1 = 1.0 - self.uniform();
+        let u2 = self.uniform();
+
+        (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+    }
+
+    /// Returns `true` with the provided probability.
+    pub(crate) fn chance(&mut self, probability: f64) -> bool {
+        self.uniform() < probability
+    }
+
+    /// Returns a random integer lower than `bound`, which should not be 0.
+    pub(crate) fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
@@ -0,0 +1,263 @@
+//! Traffic recording.
+//!
+//! This module contains the [`Tapped`] I/O port wrapper, which records each
+//! item read from and written to a port, together with its wall-clock time, to
+//! a [`TapSink`]. Since it wraps any [`IoPort`], the traffic of all transports
+//! can be captured in the same way without modifying their models.
+//!
+//! Two sinks are provided:
+//!
+//! * [`Sender`] sends [`TapRecord`]s to a channel,
+//! * [`WriterSink`] writes one line per item to a writer, e.g. a
+//!   [`TrackedWriter`](crate::teardown::TrackedWriter) to a file.
+//!
+//! #### Examples
+//!
+//! ```
+//! use std::io::Result as IoResult;
+//! use std::sync::mpsc::channel;
+//!
+//! use mio::net::UdpSocket;
+//! use mio::{Interest, Registry, Token};
+//!
+//! use nexosim_io_utils::port::{IoPort, IoThread};
+//! use nexosim_io_utils::tap::{TapRecord, Tapped};
+//!
+//! /// UDP port.
+//! struct Udp {
+//!     socket: UdpSocket,
+//! }
+//!
+//! impl IoPort<UdpSocket, Vec<u8>, Vec<u8>> for Udp {
+//!     fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+//!         registry.register(&mut self.socket, Token(0), Interest::READABLE)?;
+//!
+//!         Ok(Token(1))
+//!     }
+//!
+//!     fn read(&mut self, _: Token) -> IoResult<Vec<u8>> {
+//!         let mut buffer = vec![0; 256];
+//!         let len = self.socket.recv(&mut buffer)?;
+//!         buffer.truncate(len);
+//!
+//!         Ok(buffer)
+//!     }
+//!
+//!     fn write(&mut self, data: &Vec<u8>) -> IoResult<()> {
+//!         self.socket.send(data).map(|_| ())
+//!     }
+//! }
+//!
+//! let socket = UdpSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+//! socket.connect(socket.local_addr().unwrap()).unwrap();
+//!
+//! let (tap_tx, tap_rx) = channel();
+//! let mut io_thread: IoThread<Vec<u8>, Vec<u8>> =
+//!     IoThread::new(Tapped::new(Udp { socket }, tap_tx));
+//!
+//! io_thread.send(vec![1, 2, 3]).unwrap();
+//!
+//! match tap_rx.recv().unwrap() {
+//!     TapRecord::Tx(_, data) => assert_eq!(data, vec![1, 2, 3]),
+//!     TapRecord::Rx(..) => panic!("unexpected received data"),
+//! }
+//! ```
+
+use std::fmt;
+use std::io::{Result as IoResult, Write};
+use std::sync::mpsc::Sender;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use mio::event::Source;
+use mio::{Registry, Token};
+
+use crate::port::IoPort;
+
+/// Recorded item, with its wall-clock time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TapRecord<R, T> {
+    /// Item read from the port.
+    Rx(SystemTime, R),
+    /// Item written to the port.
+    Tx(SystemTime, T),
+}
+
+/// Destination of the recorded traffic.
+///
+/// Sink errors stop the I/O thread and are reported as a read or write error
+/// of the port.
+pub trait TapSink<R, T>: Send {
+    /// Records an item read from the port.
+    fn received(&mut self, time: SystemTime, data: &R) -> IoResult<()>;
+
+    /// Records an item written to the port.
+    fn transmitted(&mut self, time: SystemTime, data: &T) -> IoResult<()>;
+}
+
+/// Channel sink.
+///
+/// Recording silently stops once the receiver is dropped.
+impl<R, T> TapSink<R, T> for Sender<TapRecord<R, T>>
+where
+    R: Clone + Send,
+    T: Clone + Send,
+{
+    fn received(&mut self, time: SystemTime, data: &R) -> IoResult<()> {
+        let _ = self.send(TapRecord::Rx(time, data.clone()));
+
+        Ok(())
+    }
+
+    fn transmitted(&mut self, time: SystemTime, data: &T) -> IoResult<()> {
+        let _ = self.send(TapRecord::Tx(time, data.clone()));
+
+        Ok(())
+    }
+}
+
+/// Writer sink.
+///
+/// Each item is written on its own line as the number of seconds elapsed since
+/// the Unix epoch, the direction (`rx` or `tx`) and the debug representation
+/// of the item, e.g.:
+///
+/// ```text
+/// 1718000000.123456789 tx [1, 2, 3]
+/// ```
+pub struct WriterSink<W> {
+    /// Wrapped writer.
+    writer: W,
+}
+
+impl<W: Write> WriterSink<W> {
+    /// Creates a sink writing to the provided writer.
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Writes a record line.
+    fn write_line(
+        &mut self,
+        time: SystemTime,
+        direction: &str,
+        data: &dyn fmt::Debug,
+    ) -> IoResult<()> {
+        let time = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        writeln!(
+            self.writer,
+            "{}.{:09} {} {:?}",
+            time.as_secs(),
+            time.subsec_nanos(),
+            direction,
+            data
+        )
+    }
+}
+
+impl<W, R, T> TapSink<R, T> for WriterSink<W>
+where
+    W: Write + Send,
+    R: fmt::Debug,
+    T: fmt::Debug,
+{
+    fn received(&mut self, time: SystemTime, data: &R) -> IoResult<()> {
+        self.write_line(time, "rx", data)
+    }
+
+    fn transmitted(&mut self, time: SystemTime, data: &T) -> IoResult<()> {
+        self.write_line(time, "tx", data)
+    }
+}
+
+impl<W> fmt::Debug for WriterSink<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WriterSink").finish_non_exhaustive()
+    }
+}
+
+/// I/O port wrapper recording its traffic to a sink.
+///
+/// The data read from the wrapped port, including the data returned at its
+/// deadlines, is recorded when it is forwarded to the model. Data sent to the
+/// port is recorded once it is accepted by the wrapped port, which for ports
+/// buffering partial writes may precede its actual transmission.
+#[derive(Debug)]
+pub struct Tapped<P, K> {
+    /// Wrapped port.
+    port: P,
+
+    /// Traffic sink.
+    sink: K,
+}
+
+impl<P, K> Tapped<P, K> {
+    /// Wraps an I/O port, recording its traffic to the provided sink.
+    pub fn new(port: P, sink: K) -> Self {
+        Self { port, sink }
+    }
+
+    /// Returns a reference to the wrapped port.
+    pub fn get_ref(&self) -> &P {
+        &self.port
+    }
+
+    /// Returns the wrapped port and the sink.
+    pub fn into_inner(self) -> (P, K) {
+        (self.port, self.sink)
+    }
+
+    /// Records the result of a read.
+    fn record_rx<R, T>(&mut self, data: IoResult<R>) -> IoResult<R>
+    where
+        K: TapSink<R, T>,
+    {
+        let data = data?;
+        self.sink.received(SystemTime::now(), &data)?;
+
+        Ok(data)
+    }
+}
+
+impl<S, R, T, P, K> IoPort<S, R, T> for Tapped<P, K>
+where
+    S: Source + ?Sized,
+    R: Send,
+    T: Send,
+    P: IoPort<S, R, T>,
+    K: TapSink<R, T>,
+{
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        self.port.register(registry)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<R> {
+        let data = self.port.read(token);
+
+        self.record_rx::<R, T>(data)
+    }
+
+    fn write(&mut self, data: &T) -> IoResult<()> {
+        self.port.write(data)?;
+
+        self.sink.transmitted(SystemTime::now(), data)
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        self.port.writable(token)
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        self.port.deadline()
+    }
+
+    fn timeout(&mut self) -> IoResult<R> {
+        let data = self.port.timeout();
+
+        self.record_rx::<R, T>(data)
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        self.port.is_write_pending()
+    }
+}
@@ -0,0 +1,302 @@
+//! Teardown verification utilities.
+//!
+//! This module contains helpers to verify that a simulation bench releases
+//! all its resources once dropped, which matters when a bench is initialized
+//! and torn down repeatedly within a single process, e.g. in parameter
+//! sweeps.
+//!
+//! A [`TeardownCheck`] takes a snapshot of the process resources before the
+//! bench is built and reports, after the bench is dropped:
+//!
+//! * [`IoThread`](crate::port::IoThread)s which were not joined,
+//! * file descriptors which were not closed (Linux only, by diffing the
+//!   content of `/proc/self/fd`),
+//! * recorder files written through a [`TrackedWriter`] which were not closed
+//!   or could not be flushed.
+//!
+//! Since these resources are process-wide, checks are only reliable when no
+//! other bench runs concurrently in the same process.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_io_utils::teardown::TeardownCheck;
+//!
+//! let check = TeardownCheck::new();
+//!
+//! // Build, run and drop the bench.
+//!
+//! check.assert_clean();
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Result as IoResult, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of I/O threads not yet joined.
+static LIVE_IO_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of tracked writers not yet dropped.
+static OPEN_WRITERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Files of tracked writers which could not be flushed when dropped.
+static UNFLUSHED_FILES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Guard accounting for a live I/O thread.
+///
+/// This guard should be dropped after the thread is joined.
+pub(crate) struct IoThreadGuard;
+
+impl IoThreadGuard {
+    pub(crate) fn new() -> Self {
+        LIVE_IO_THREADS.fetch_add(1, Ordering::Relaxed);
+        Self
+    }
+}
+
+impl Drop for IoThreadGuard {
+    fn drop(&mut self) {
+        LIVE_IO_THREADS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Writer flushed on drop and accounted for by [`TeardownCheck`].
+///
+/// Recorder models should write their files through this type so that files
+/// left open or not flushed at teardown are reported.
+pub struct TrackedWriter<W: Write> {
+    /// Wrapped writer.
+    inner: W,
+
+    /// Path of the written file.
+    path: PathBuf,
+}
+
+impl<W: Write> TrackedWriter<W> {
+    /// Wraps a writer to the file at the provided path.
+    pub fn new(inner: W, path: impl AsRef<Path>) -> Self {
+        OPEN_WRITERS.fetch_add(1, Ordering::Relaxed);
+
+        Self {
+            inner,
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Returns the path of the written file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl TrackedWriter<BufWriter<File>> {
+    /// Creates a buffered writer to a new file at the provided path.
+    pub fn create(path: impl AsRef<Path>) -> IoResult<Self> {
+        let file = File::create(path.as_ref())?;
+
+        Ok(Self::new(BufWriter::new(file), path))
+    }
+}
+
+impl<W: Write> Write for TrackedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for TrackedWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.flush().is_err() {
+            UNFLUSHED_FILES
+                .lock()
+                .unwrap()
+                .push(std::mem::take(&mut self.path));
+        }
+        OPEN_WRITERS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl<W: Write> fmt::Debug for TrackedWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TrackedWriter")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Open file descriptor.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OpenFd {
+    /// File descriptor number.
+    pub fd: i32,
+
+    /// Target of the file descriptor.
+    pub target: PathBuf,
+}
+
+/// Resources leaked by a bench, as reported by [`TeardownCheck::verify`].
+#[derive(Clone, Debug, Default)]
+pub struct TeardownReport {
+    /// Number of I/O threads not joined.
+    pub live_io_threads: usize,
+
+    /// Tracked writers not dropped.
+    pub open_writers: usize,
+
+    /// Files of tracked writers which could not be flushed.
+    pub unflushed_files: Vec<PathBuf>,
+
+    /// File descriptors not closed.
+    pub leaked_fds: Vec<OpenFd>,
+}
+
+impl TeardownReport {
+    /// Returns `true` if no leak was detected.
+    pub fn is_clean(&self) -> bool {
+        self.live_io_threads == 0
+            && self.open_writers == 0
+            && self.unflushed_files.is_empty()
+            && self.leaked_fds.is_empty()
+    }
+}
+
+impl fmt::Display for TeardownReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "resources leaked at teardown:")?;
+        if self.live_io_threads != 0 {
+            write!(f, " {} I/O thread(s) not joined;", self.live_io_threads)?;
+        }
+        if self.open_writers != 0 {
+            write!(f, " {} recorder file(s) not closed;", self.open_writers)?;
+        }
+        for path in &self.unflushed_files {
+            write!(f, " file {} not flushed;", path.display())?;
+        }
+        for fd in &self.leaked_fds {
+            write!(f, " fd {} ({}) not closed;", fd.fd, fd.target.display())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for TeardownReport {}
+
+/// Snapshot of the process resources taken before a bench is built.
+#[derive(Debug)]
+pub struct TeardownCheck {
+    /// Number of live I/O threads.
+    live_io_threads: usize,
+
+    /// Number of open tracked writers.
+    open_writers: usize,
+
+    /// Number of files which could not be flushed.
+    unflushed_files: usize,
+
+    /// Open file descriptors, if available on this platform.
+    fds: Option<Vec<OpenFd>>,
+}
+
+impl TeardownCheck {
+    /// Takes a snapshot of the process resources.
+    pub fn new() -> Self {
+        Self {
+            live_io_threads: LIVE_IO_THREADS.load(Ordering::Relaxed),
+            open_writers: OPEN_WRITERS.load(Ordering::Relaxed),
+            unflushed_files: UNFLUSHED_FILES.lock().unwrap().len(),
+            fds: open_fds(),
+        }
+    }
+
+    /// Compares the process resources with the snapshot.
+    pub fn verify(&self) -> Result<(), TeardownReport> {
+        let leaked_fds = match (&self.fds, open_fds()) {
+            (Some(before), Some(after)) => after
+                .into_iter()
+                .filter(|fd| !before.contains(fd))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let report = TeardownReport {
+            live_io_threads: LIVE_IO_THREADS
+                .load(Ordering::Relaxed)
+                .saturating_sub(self.live_io_threads),
+            open_writers: OPEN_WRITERS
+                .load(Ordering::Relaxed)
+                .saturating_sub(self.open_writers),
+            unflushed_files: UNFLUSHED_FILES
+                .lock()
+                .unwrap()
+                .get(self.unflushed_files..)
+                .unwrap_or_default()
+                .to_vec(),
+            leaked_fds,
+        };
+
+        if report.is_clean() {
+            Ok(())
+        } else {
+            Err(report)
+        }
+    }
+
+    /// Compares the process resources with the snapshot.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if any resource leak is detected.
+    pub fn assert_clean(&self) {
+        if let Err(report) = self.verify() {
+            panic!("{report}");
+        }
+    }
+}
+
+impl Default for TeardownCheck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lists the open file descriptors of the process.
+#[cfg(target_os = "linux")]
+fn open_fds() -> Option<Vec<OpenFd>> {
+    let dir = Path::new("/proc/self/fd");
+    let entries = std::fs::read_dir(dir)
+        .ok()?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<IoResult<Vec<_>>>()
+        .ok()?;
+
+    let mut fds = Vec::new();
+    for name in entries {
+        let Some(fd) = name.to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+
+        // The descriptor used to read the directory is closed by now and
+        // cannot be resolved.
+        let Ok(target) = std::fs::read_link(dir.join(&name)) else {
+            continue;
+        };
+        fds.push(OpenFd { fd, target });
+    }
+
+    Some(fds)
+}
+
+/// Lists the open file descriptors of the process.
+#[cfg(not(target_os = "linux"))]
+fn open_fds() -> Option<Vec<OpenFd>> {
+    None
+}
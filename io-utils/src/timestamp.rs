@@ -0,0 +1,126 @@
+//! Receive-side timestamping.
+//!
+//! This module contains the [`Timestamped`] I/O port wrapper, which stamps the
+//! data read from a port with the wall-clock time at which it was read by the
+//! I/O thread, before it enters the channel to the model. The arrival time of
+//! the data thus does not depend on the polling period of the model.
+//!
+//! #### Examples
+//!
+//! ```
+//! use std::io::{ErrorKind, Result as IoResult};
+//! use std::time::Instant;
+//!
+//! use mio::net::UdpSocket;
+//! use mio::{Interest, Registry, Token};
+//!
+//! use nexosim_io_utils::port::{IoPort, IoThread};
+//! use nexosim_io_utils::timestamp::Timestamped;
+//!
+//! /// UDP port.
+//! struct Udp {
+//!     socket: UdpSocket,
+//! }
+//!
+//! impl IoPort<UdpSocket, Vec<u8>, Vec<u8>> for Udp {
+//!     fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+//!         registry.register(&mut self.socket, Token(0), Interest::READABLE)?;
+//!
+//!         Ok(Token(1))
+//!     }
+//!
+//!     fn read(&mut self, _: Token) -> IoResult<Vec<u8>> {
+//!         let mut buffer = vec![0; 256];
+//!         let len = self.socket.recv(&mut buffer)?;
+//!         buffer.truncate(len);
+//!
+//!         Ok(buffer)
+//!     }
+//!
+//!     fn write(&mut self, data: &Vec<u8>) -> IoResult<()> {
+//!         self.socket.send(data).map(|_| ())
+//!     }
+//! }
+//!
+//! let udp = Udp {
+//!     socket: UdpSocket::bind("127.0.0.1:0".parse().unwrap()).unwrap(),
+//! };
+//! let io_thread: IoThread<(Instant, Vec<u8>), Vec<u8>> = IoThread::new(Timestamped::new(udp));
+//! # let _ = io_thread;
+//! ```
+
+use std::io::Result as IoResult;
+use std::time::Instant;
+
+use mio::event::Source;
+use mio::{Registry, Token};
+
+use crate::port::IoPort;
+
+/// I/O port wrapper stamping the read data with its monotonic wall-clock
+/// receive time.
+///
+/// The data read from the wrapped port, including the data returned at its
+/// deadlines, is forwarded as `(Instant, R)` pairs.
+#[derive(Debug)]
+pub struct Timestamped<P> {
+    /// Wrapped port.
+    port: P,
+}
+
+impl<P> Timestamped<P> {
+    /// Wraps an I/O port.
+    pub fn new(port: P) -> Self {
+        Self { port }
+    }
+
+    /// Returns a reference to the wrapped port.
+    pub fn get_ref(&self) -> &P {
+        &self.port
+    }
+
+    /// Returns the wrapped port.
+    pub fn into_inner(self) -> P {
+        self.port
+    }
+}
+
+impl<S, R, T, P> IoPort<S, (Instant, R), T> for Timestamped<P>
+where
+    S: Source + ?Sized,
+    R: Send,
+    T: Send,
+    P: IoPort<S, R, T>,
+{
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        self.port.register(registry)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<(Instant, R)> {
+        self.port.read(token).map(|data| (Instant::now(), data))
+    }
+
+    fn write(&mut self, data: &T) -> IoResult<()> {
+        self.port.write(data)
+    }
+
+    fn write_owned(&mut self, data: T) -> Result<(), (T, std::io::Error)> {
+        self.port.write_owned(data)
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        self.port.writable(token)
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        self.port.deadline()
+    }
+
+    fn timeout(&mut self) -> IoResult<(Instant, R)> {
+        self.port.timeout().map(|data| (Instant::now(), data))
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        self.port.is_write_pending()
+    }
+}
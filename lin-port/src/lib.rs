@@ -0,0 +1,771 @@
+//! LIN port model for [NeXosim][NX]-based simulations.
+//!
+//! This crate contains a model acting as the master or as a slave node of a
+//! LIN bus:
+//! * in master mode, the headers of the configured schedule table are
+//!   transmitted in turn, each slot lasting its configured delay, and
+//!   additional headers can be requested from the simulation, e.g. for
+//!   sporadic frames,
+//! * the responses of the frames published by the node are set from the
+//!   simulation and transmitted after their headers,
+//! * the responses seen on the bus, including the responses of the node, are
+//!   forwarded to the simulation, together with the parity, checksum and
+//!   missing response errors.
+//!
+//! Frames are described by their identifier, response length and checksum
+//! model, classic (LIN 1.x) or enhanced (LIN 2.x). The diagnostic frames,
+//! with identifiers `0x3c` and `0x3d`, always use the classic checksum.
+//!
+//! The bus is accessed through one of the following backends, selected by the
+//! `backend` configuration:
+//! * `sllin` (default): a network interface of the sllin line discipline,
+//!   which exchanges LIN frames as SocketCAN frames and computes the checksums
+//!   in the kernel; the master or slave mode of the interface is set when the
+//!   line discipline is attached, and should match the `mode` configuration,
+//! * `uart`: a serial device connected to a LIN transceiver, in which case the
+//!   breaks are generated and the frames are decoded by the model; the
+//!   transceiver is expected to echo the transmitted bytes, as LIN
+//!   transceivers do.
+//!
+//! Parity and missing response errors are only detected by the `uart` backend,
+//! the sllin line discipline dropping invalid frames.
+//!
+//! The model is only available on Linux.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_lin_port::{LinBackendKind, LinChecksum, LinMode, LinPortConfig};
+//!
+//! let config = ConfigLoader::<LinPortConfig>::new()
+//!     .code(
+//!         r#"
+//! backend = "uart"
+//! interface = "/dev/ttyUSB0"
+//! period = 10
+//!
+//! [[frames]]
+//! id = 0x10
+//! len = 2
+//! publish = true
+//! data = [0, 0]
+//!
+//! [[frames]]
+//! id = 0x21
+//! len = 4
+//! checksum = "classic"
+//!
+//! [[schedule]]
+//! id = 0x10
+//! delay = 10
+//!
+//! [[schedule]]
+//! id = 0x21
+//! delay = 20
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.backend, LinBackendKind::Uart);
+//! assert_eq!(config.mode, LinMode::Master);
+//! assert_eq!(config.frames[0].checksum, LinChecksum::Enhanced);
+//! assert_eq!(config.baud_rate, 19200);
+//! ```
+//!
+//! [NX]: https://github.com/asynchronics/nexosim
+#![cfg(target_os = "linux")]
+#![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
+#![forbid(unsafe_code)]
+
+mod sllin;
+mod uart;
+
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::time::{Duration, Instant};
+
+use mio::event::Source;
+use mio::{Interest, Registry, Token};
+
+use schematic::{Config, ConfigEnum};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus};
+
+use crate::sllin::SllinBackend;
+use crate::uart::UartBackend;
+
+/// Largest LIN frame identifier.
+const MAX_ID: u8 = 0x3f;
+
+/// LIN port model instance configuration.
+#[derive(Config, Debug)]
+pub struct LinPortConfig {
+    /// Backend used to access the LIN bus.
+    pub backend: LinBackendKind,
+
+    /// LIN interface: the name of the sllin network interface, e.g. `sllin0`,
+    /// or the path of the serial device, e.g. `/dev/ttyUSB0`, depending on the
+    /// backend.
+    pub interface: String,
+
+    /// Baud rate of the serial device, for the `uart` backend.
+    #[setting(default = 19200)]
+    pub baud_rate: u32,
+
+    /// Maximum time between the reception of a header and the end of its
+    /// response, for the `uart` backend.
+    ///
+    /// Responses not completed within this time are reported as missing.
+    #[setting(default = ConfigDuration::from_millis(10))]
+    pub response_timeout: ConfigDuration,
+
+    /// Role of the node on the LIN bus.
+    pub mode: LinMode,
+
+    /// Frames known by the node.
+    #[setting(nested)]
+    pub frames: Vec<LinFrameConfig>,
+
+    /// Schedule table, run continuously in master mode.
+    ///
+    /// Each slot refers to a configured frame.
+    #[setting(nested)]
+    pub schedule: Vec<LinSlotConfig>,
+
+    /// Delay for the first scheduled frame forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which the received frames are forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time received frames are forwarded into
+    /// the simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// LIN frame configuration.
+#[derive(Config, Debug)]
+pub struct LinFrameConfig {
+    /// Frame identifier, from 0 to 63.
+    pub id: u8,
+
+    /// Response length, in bytes, from 1 to 8.
+    #[setting(default = 8)]
+    pub len: u8,
+
+    /// Checksum model of the response.
+    pub checksum: LinChecksum,
+
+    /// Response is published by the node.
+    pub publish: bool,
+
+    /// Initial response of a published frame.
+    ///
+    /// If no data is provided, the response is not transmitted until it is
+    /// set from the simulation.
+    pub data: Vec<u8>,
+}
+
+/// Schedule table slot configuration.
+#[derive(Config, Debug)]
+pub struct LinSlotConfig {
+    /// Identifier of the frame.
+    pub id: u8,
+
+    /// Slot duration, i.e. delay until the next header.
+    pub delay: ConfigDuration,
+}
+
+/// LIN port backend.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinBackendKind {
+    /// Network interface of the sllin line discipline.
+    #[default]
+    Sllin,
+
+    /// Serial device connected to a LIN transceiver.
+    Uart,
+}
+
+/// Role of the node on the LIN bus.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinMode {
+    /// Master node, transmitting the headers.
+    #[default]
+    Master,
+
+    /// Slave node, answering the headers of its published frames.
+    Slave,
+}
+
+/// Checksum model of a LIN frame.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinChecksum {
+    /// Classic checksum, over the data bytes only (LIN 1.x).
+    Classic,
+
+    /// Enhanced checksum, over the protected identifier and the data bytes
+    /// (LIN 2.x).
+    #[default]
+    Enhanced,
+}
+
+impl LinChecksum {
+    /// Checks whether the checksum of a frame covers its protected
+    /// identifier.
+    ///
+    /// The diagnostic frames always use the classic checksum.
+    fn is_enhanced(self, id: u8) -> bool {
+        self == Self::Enhanced && id != 0x3c && id != 0x3d
+    }
+
+    /// Computes the checksum of the response of a frame.
+    pub fn checksum(self, id: u8, data: &[u8]) -> u8 {
+        let mut sum = if self.is_enhanced(id) {
+            u16::from(protected_id(id))
+        } else {
+            0
+        };
+        for &byte in data {
+            sum += u16::from(byte);
+            if sum > 0xff {
+                sum -= 0xff;
+            }
+        }
+
+        !(sum as u8)
+    }
+}
+
+/// Returns the protected identifier of a frame, i.e. its identifier with
+/// parity bits.
+pub fn protected_id(id: u8) -> u8 {
+    let id = id & MAX_ID;
+    let bit = |n: u8| (id >> n) & 1;
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+
+    id | (p0 << 6) | (p1 << 7)
+}
+
+/// LIN frame.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LinFrame {
+    /// Frame identifier.
+    pub id: u8,
+
+    /// Response data.
+    pub data: Vec<u8>,
+}
+
+impl LinFrame {
+    /// Creates a new LIN frame.
+    pub fn new(id: u8, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            id,
+            data: data.into(),
+        }
+    }
+}
+
+/// LIN bus error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LinError {
+    /// Identifier of the frame.
+    pub id: u8,
+
+    /// Kind of error.
+    pub kind: LinErrorKind,
+}
+
+/// Kind of LIN bus error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinErrorKind {
+    /// Parity bits of the protected identifier are invalid.
+    Parity,
+
+    /// Response checksum is invalid.
+    Checksum,
+
+    /// Response is missing or incomplete.
+    NoResponse,
+}
+
+/// Frame known by the node.
+#[derive(Clone, Debug)]
+struct LinFrameEntry {
+    /// Response length.
+    len: u8,
+
+    /// Checksum model.
+    checksum: LinChecksum,
+
+    /// Response is published by the node.
+    publish: bool,
+
+    /// Response of a published frame, if set.
+    data: Option<Vec<u8>>,
+}
+
+/// Frames known by the node, indexed by identifier.
+type LinFrameTable = Vec<Option<LinFrameEntry>>;
+
+/// Event read from the LIN bus.
+#[derive(Debug)]
+enum LinEvent {
+    /// Received frame.
+    Frame(LinFrame),
+
+    /// Bus error.
+    Error(LinError),
+}
+
+/// Command to the LIN port.
+#[derive(Debug)]
+enum LinCommand {
+    /// Sets the response of a published frame.
+    Response(LinFrame),
+
+    /// Transmits a header.
+    Header(u8),
+}
+
+/// LIN bus backend.
+///
+/// A backend is a non-blocking MIO source providing access to a LIN bus.
+trait LinBackend: Source + Send {
+    /// Returns the MIO interest of the backend.
+    fn interest(&self) -> Interest {
+        Interest::READABLE
+    }
+
+    /// Transmits a header, followed by the response if it is published by the
+    /// node.
+    fn send_header(&mut self, id: u8, response: Option<&[u8]>) -> Result<()>;
+
+    /// Sets the response of a published frame.
+    fn set_response(&mut self, id: u8, data: &[u8]) -> Result<()>;
+
+    /// Reads an event.
+    ///
+    /// This method should return an error of kind [`ErrorKind::WouldBlock`]
+    /// when no event is available.
+    fn read_event(&mut self) -> Result<LinEvent>;
+
+    /// Resumes the pending writes once writable.
+    fn writable(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Returns the next deadline of the backend, if any.
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    /// Handles an elapsed deadline, returning the resulting event, if any.
+    fn timeout(&mut self) -> Option<LinEvent> {
+        None
+    }
+}
+
+/// Backend token.
+const BACKEND: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// LIN port.
+struct LinPortInner {
+    /// LIN bus backend.
+    backend: Box<dyn LinBackend>,
+
+    /// Frames known by the node.
+    frames: LinFrameTable,
+
+    /// Schedule table slots, with their durations.
+    schedule: Vec<(u8, Duration)>,
+
+    /// Node is the master node.
+    is_master: bool,
+
+    /// Index of the next slot.
+    slot: usize,
+
+    /// Start of the next slot, once the schedule table runs.
+    next_slot: Option<Instant>,
+}
+
+impl LinPortInner {
+    /// Checks the configuration and opens the LIN bus backend.
+    fn new(config: &LinPortConfig) -> Result<Self> {
+        let invalid = |msg: String| Error::new(ErrorKind::InvalidInput, msg);
+
+        let mut frames: LinFrameTable = vec![None; usize::from(MAX_ID) + 1];
+        for frame in &config.frames {
+            if frame.id > MAX_ID {
+                return Err(invalid(format!("Invalid LIN identifier {}.", frame.id)));
+            }
+            if !(1..=8).contains(&frame.len) {
+                return Err(invalid(format!(
+                    "Invalid response length of LIN frame {}.",
+                    frame.id
+                )));
+            }
+            if !frame.data.is_empty() && frame.data.len() != usize::from(frame.len) {
+                return Err(invalid(format!(
+                    "Invalid initial response of LIN frame {}.",
+                    frame.id
+                )));
+            }
+            frames[usize::from(frame.id)] = Some(LinFrameEntry {
+                len: frame.len,
+                checksum: frame.checksum,
+                publish: frame.publish,
+                data: (frame.publish && !frame.data.is_empty()).then(|| frame.data.clone()),
+            });
+        }
+        let schedule = config
+            .schedule
+            .iter()
+            .map(|slot| match frames.get(usize::from(slot.id)) {
+                Some(Some(_)) if !slot.delay.is_zero() => Ok((slot.id, Duration::from(slot.delay))),
+                Some(Some(_)) => Err(invalid(format!(
+                    "Invalid delay of the slot of LIN frame {} in the schedule table.",
+                    slot.id
+                ))),
+                _ => Err(invalid(format!(
+                    "Unknown LIN frame {} in the schedule table.",
+                    slot.id
+                ))),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let is_master = config.mode == LinMode::Master;
+
+        let backend: Box<dyn LinBackend> = match config.backend {
+            LinBackendKind::Sllin => Box::new(SllinBackend::open(&config.interface, &frames)?),
+            LinBackendKind::Uart => Box::new(UartBackend::open(
+                &config.interface,
+                config.baud_rate,
+                Duration::from(config.response_timeout),
+                frames.clone(),
+                !is_master,
+            )?),
+        };
+
+        Ok(Self {
+            backend,
+            frames,
+            schedule,
+            is_master,
+            slot: 0,
+            next_slot: None,
+        })
+    }
+
+    /// Transmits a header, followed by the response if it is published by the
+    /// node.
+    fn transmit(&mut self, id: u8) -> Result<()> {
+        let Some(Some(frame)) = self.frames.get(usize::from(id)) else {
+            #[cfg(feature = "tracing")]
+            warn!("Header of unknown LIN frame {} dropped.", id);
+            return Ok(());
+        };
+        let response = if frame.publish {
+            frame.data.as_deref()
+        } else {
+            None
+        };
+        match self.backend.send_header(id, response) {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                #[cfg(feature = "tracing")]
+                warn!("Header of LIN frame {} dropped: bus busy.", id);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+impl IoPort<dyn LinBackend, LinEvent, LinCommand> for LinPortInner {
+    fn register(&mut self, registry: &Registry) -> Result<Token> {
+        let interest = self.backend.interest();
+        registry.register(self.backend.as_mut(), BACKEND, interest)?;
+        if self.is_master && !self.schedule.is_empty() {
+            self.next_slot = Some(Instant::now());
+        }
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, _: Token) -> Result<LinEvent> {
+        self.backend.read_event()
+    }
+
+    fn write(&mut self, command: &LinCommand) -> Result<()> {
+        match command {
+            LinCommand::Response(response) => {
+                let frame = match self.frames.get_mut(usize::from(response.id)) {
+                    Some(Some(frame))
+                        if frame.publish && response.data.len() == usize::from(frame.len) =>
+                    {
+                        frame
+                    }
+                    _ => {
+                        #[cfg(feature = "tracing")]
+                        warn!(
+                            "Response of LIN frame {} dropped: not a published frame of this length.",
+                            response.id
+                        );
+                        return Ok(());
+                    }
+                };
+                frame.data = Some(response.data.clone());
+
+                self.backend.set_response(response.id, &response.data)
+            }
+            LinCommand::Header(id) => {
+                if !self.is_master {
+                    #[cfg(feature = "tracing")]
+                    warn!("Header of LIN frame {} dropped: not a master node.", id);
+                    return Ok(());
+                }
+
+                self.transmit(*id)
+            }
+        }
+    }
+
+    fn writable(&mut self, _: Token) -> Result<()> {
+        self.backend.writable()
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        match (self.next_slot, self.backend.deadline()) {
+            (Some(slot), Some(deadline)) => Some(slot.min(deadline)),
+            (slot, deadline) => slot.or(deadline),
+        }
+    }
+
+    fn timeout(&mut self) -> Result<LinEvent> {
+        let now = Instant::now();
+        if self
+            .backend
+            .deadline()
+            .is_some_and(|deadline| deadline <= now)
+        {
+            if let Some(event) = self.backend.timeout() {
+                return Ok(event);
+            }
+        }
+        if let Some(next_slot) = self.next_slot.filter(|&next_slot| next_slot <= now) {
+            let (id, delay) = self.schedule[self.slot];
+            self.slot = (self.slot + 1) % self.schedule.len();
+            // Slots are kept aligned with the schedule start, unless the
+            // thread lags behind by more than a slot.
+            self.next_slot = Some((next_slot + delay).max(now));
+            self.transmit(id)?;
+        }
+
+        Err(ErrorKind::WouldBlock.into())
+    }
+}
+
+/// LIN port model.
+///
+/// This model:
+/// * runs the schedule table in master mode,
+/// * transmits the headers and responses from its inputs,
+/// * forwards the frames and errors seen on the LIN bus to its outputs,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct LinPort {
+    /// Received frame -- output port.
+    pub frame_out: Output<LinFrame>,
+
+    /// LIN bus error -- output port.
+    pub error_out: Output<LinError>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: LinPortConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<LinEvent, LinCommand>,
+}
+
+impl LinPort {
+    /// Sets the response of a published frame -- input port.
+    ///
+    /// Responses of frames which are not published by the node or which do
+    /// not have the configured length are dropped, as well as responses sent
+    /// after the I/O thread has exited.
+    pub async fn response_in(&mut self, frame: LinFrame) {
+        let _id = frame.id;
+        if self.io_thread.send(LinCommand::Response(frame)).is_err() {
+            #[cfg(feature = "tracing")]
+            warn!("Response of LIN frame {} dropped: I/O thread exited.", _id);
+        }
+    }
+
+    /// Transmits the header of a frame outside the schedule table -- input
+    /// port.
+    ///
+    /// Headers are dropped in slave mode, as well as headers sent after the
+    /// I/O thread has exited.
+    pub async fn header_in(&mut self, id: u8) {
+        if self.io_thread.send(LinCommand::Header(id)).is_err() {
+            #[cfg(feature = "tracing")]
+            warn!("Header of LIN frame {} dropped: I/O thread exited.", id);
+        }
+    }
+
+    /// Forwards the received frames, the bus errors and the I/O thread status
+    /// -- input port.
+    pub async fn process(&mut self) {
+        for event in self.io_thread.try_recv_all() {
+            match event {
+                LinEvent::Frame(frame) => self.frame_out.send(frame).await,
+                LinEvent::Error(error) => self.error_out.send(error).await,
+            }
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for LinPort {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for LinPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LinPort")
+            .field("interface", &self.config.interface)
+            .finish_non_exhaustive()
+    }
+}
+
+/// LIN port model prototype.
+pub struct ProtoLinPort {
+    /// Received frame -- output port.
+    pub frame_out: Output<LinFrame>,
+
+    /// LIN bus error -- output port.
+    pub error_out: Output<LinError>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// LIN port model instance configuration.
+    config: LinPortConfig,
+}
+
+impl ProtoLinPort {
+    /// Creates a new LIN port model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the configuration is invalid, if the LIN
+    /// interface cannot be opened or if the I/O thread cannot be created.
+    pub fn new(config: LinPortConfig) -> Self {
+        Self {
+            frame_out: Output::new(),
+            error_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoLinPort {
+    type Model = LinPort;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let port = LinPortInner::new(&self.config).unwrap_or_else(|e| {
+            panic!(
+                "Failed to open the LIN interface {}: {e}.",
+                self.config.interface
+            )
+        });
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(port, options)
+            .unwrap_or_else(|e| panic!("Failed to start the I/O thread of the LIN port: {e}."));
+
+        LinPort {
+            frame_out: self.frame_out,
+            error_out: self.error_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoLinPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoLinPort")
+            .field("interface", &self.config.interface)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,134 @@
+//! sllin backend.
+//!
+//! The sllin line discipline exposes a LIN bus attached to a serial device as
+//! a SocketCAN network interface:
+//! * a remote frame transmits the header of the frame with the same
+//!   identifier, and a data frame also transmits its data as the response,
+//! * the received responses are data frames with the identifier of the frame,
+//! * control frames, with an extended identifier, configure the frame cache of
+//!   the interface, i.e. the checksum model of each frame and the responses
+//!   answered by the interface.
+//!
+//! The checksums are computed and verified by the line discipline.
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
+use std::os::unix::io::AsRawFd;
+
+use mio::event::Source;
+use mio::{Interest, Registry, Token, unix::SourceFd};
+
+use socketcan::{CanFrame, CanSocket, EmbeddedFrame, ExtendedId, Frame, Socket, StandardId};
+
+use crate::{LinBackend, LinEvent, LinFrame, LinFrameTable, MAX_ID};
+
+/// Frame cache flag of control frames: the interface answers the header with
+/// the cached response.
+const LIN_CACHE_RESPONSE: u32 = 1 << 6;
+
+/// Frame cache flag of control frames: the frame uses the enhanced checksum.
+const LIN_CHECKSUM_EXTENDED: u32 = 1 << 7;
+
+/// LIN bus accessed through a sllin network interface.
+pub(crate) struct SllinBackend {
+    /// CAN socket bound to the interface.
+    socket: CanSocket,
+
+    /// Frames known by the node.
+    frames: LinFrameTable,
+}
+
+impl SllinBackend {
+    /// Opens the interface and configures its frame cache.
+    pub(crate) fn open(interface: &str, frames: &LinFrameTable) -> Result<Self> {
+        let socket = CanSocket::open(interface)?;
+        socket.set_nonblocking(true)?;
+
+        let backend = Self {
+            socket,
+            frames: frames.clone(),
+        };
+        for (id, frame) in frames.iter().enumerate() {
+            if let Some(frame) = frame {
+                backend.configure(id as u8, frame.data.as_deref())?;
+            }
+        }
+
+        Ok(backend)
+    }
+
+    /// Configures the frame cache entry of a frame, with its cached response,
+    /// if any.
+    fn configure(&self, id: u8, response: Option<&[u8]>) -> Result<()> {
+        let Some(Some(frame)) = self.frames.get(usize::from(id)) else {
+            return Ok(());
+        };
+        let mut raw_id = u32::from(id);
+        if frame.checksum.is_enhanced(id) {
+            raw_id |= LIN_CHECKSUM_EXTENDED;
+        }
+        if response.is_some() {
+            raw_id |= LIN_CACHE_RESPONSE;
+        }
+        let frame = ExtendedId::new(raw_id)
+            .and_then(|raw_id| CanFrame::new(raw_id, response.unwrap_or_default()))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Invalid sllin control frame."))?;
+
+        self.socket.write_frame(&frame)
+    }
+}
+
+impl LinBackend for SllinBackend {
+    fn send_header(&mut self, id: u8, response: Option<&[u8]>) -> Result<()> {
+        let len = match self.frames.get(usize::from(id)) {
+            Some(Some(frame)) => usize::from(frame.len),
+            _ => 0,
+        };
+        let raw_id = StandardId::new(u16::from(id & MAX_ID)).unwrap();
+        let frame = match response {
+            Some(data) => CanFrame::new(raw_id, data),
+            None => CanFrame::new_remote(raw_id, len),
+        }
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Invalid LIN frame."))?;
+
+        self.socket.write_frame(&frame)
+    }
+
+    fn set_response(&mut self, id: u8, data: &[u8]) -> Result<()> {
+        self.configure(id, Some(data))
+    }
+
+    fn read_event(&mut self) -> Result<LinEvent> {
+        loop {
+            match self.socket.read_frame()? {
+                CanFrame::Data(frame) if !frame.is_extended() => {
+                    return Ok(LinEvent::Frame(LinFrame::new(
+                        frame.raw_id() as u8 & MAX_ID,
+                        frame.data(),
+                    )));
+                }
+                // Remote, control and error frames are not responses.
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Source for SllinBackend {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        SourceFd(&self.socket.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        SourceFd(&self.socket.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> Result<()> {
+        SourceFd(&self.socket.as_raw_fd()).deregister(registry)
+    }
+}
+
+impl fmt::Debug for SllinBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SllinBackend").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,274 @@
+//! UART backend.
+//!
+//! The LIN bus is accessed through a serial device connected to a LIN
+//! transceiver. Headers are transmitted as a break, generated by holding the
+//! line low, followed by the sync byte and the protected identifier.
+//!
+//! As the transceiver echoes the transmitted bytes, all the traffic of the bus
+//! is decoded from the received bytes, in which a break appears as a null
+//! byte: a header is a null byte followed by the sync byte and a protected
+//! identifier, and the response of a known frame is expected to be complete
+//! within the response timeout.
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{ErrorKind, Read, Result, Write};
+use std::mem;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mio::event::Source;
+use mio::{Interest, Registry, Token};
+use mio_serial::{SerialPort, SerialPortBuilderExt, SerialStream};
+
+use nexosim_io_utils::port::WriteBuffer;
+
+use crate::{
+    LinBackend, LinError, LinErrorKind, LinEvent, LinFrame, LinFrameTable, MAX_ID, protected_id,
+};
+
+/// Sync byte.
+const SYNC: u8 = 0x55;
+
+/// Minimum break length, in bit times.
+const BREAK_BITS: u32 = 13;
+
+/// Decoding state of the received bytes.
+#[derive(Debug)]
+enum RxState {
+    /// Waiting for a break.
+    Idle,
+
+    /// Break received, waiting for the sync byte.
+    Break,
+
+    /// Sync byte received, waiting for the protected identifier.
+    Sync,
+
+    /// Header received, waiting for the response data and checksum.
+    Response {
+        /// Frame identifier.
+        id: u8,
+
+        /// Response bytes received so far.
+        data: Vec<u8>,
+    },
+}
+
+/// LIN bus accessed through a serial device.
+pub(crate) struct UartBackend {
+    /// Serial device.
+    port: SerialStream,
+
+    /// Frames known by the node.
+    frames: LinFrameTable,
+
+    /// Node answers the headers of its published frames.
+    is_slave: bool,
+
+    /// Duration of one bit.
+    bit_time: Duration,
+
+    /// Maximum time between a header and the end of its response.
+    response_timeout: Duration,
+
+    /// Decoding state.
+    state: RxState,
+
+    /// Deadline of the current frame, while a frame is being received.
+    deadline: Option<Instant>,
+
+    /// Decoded events not yet read.
+    events: VecDeque<LinEvent>,
+
+    /// Data not yet written to the serial device.
+    tx: WriteBuffer,
+}
+
+impl UartBackend {
+    /// Opens the serial device.
+    pub(crate) fn open(
+        path: &str,
+        baud_rate: u32,
+        response_timeout: Duration,
+        frames: LinFrameTable,
+        is_slave: bool,
+    ) -> Result<Self> {
+        let port = mio_serial::new(path, baud_rate).open_native_async()?;
+
+        Ok(Self {
+            port,
+            frames,
+            is_slave,
+            bit_time: Duration::from_secs(1) / baud_rate.max(1),
+            response_timeout,
+            state: RxState::Idle,
+            deadline: None,
+            events: VecDeque::new(),
+            tx: WriteBuffer::new(),
+        })
+    }
+
+    /// Decodes a received byte.
+    fn receive(&mut self, byte: u8) -> Result<()> {
+        self.state = match mem::replace(&mut self.state, RxState::Idle) {
+            RxState::Idle | RxState::Break if byte == 0 => {
+                self.deadline = Some(Instant::now() + self.response_timeout);
+                RxState::Break
+            }
+            RxState::Idle => RxState::Idle,
+            RxState::Break if byte == SYNC => RxState::Sync,
+            RxState::Break => RxState::Idle,
+            RxState::Sync => {
+                let id = byte & MAX_ID;
+                match &self.frames[usize::from(id)] {
+                    _ if protected_id(id) != byte => {
+                        self.events.push_back(LinEvent::Error(LinError {
+                            id,
+                            kind: LinErrorKind::Parity,
+                        }));
+                        RxState::Idle
+                    }
+                    Some(frame) => {
+                        if self.is_slave && frame.publish {
+                            if let Some(data) = &frame.data {
+                                self.tx.push(data);
+                                self.tx.push(&[frame.checksum.checksum(id, data)]);
+                                self.tx.flush(&mut self.port)?;
+                            }
+                        }
+                        self.deadline = Some(Instant::now() + self.response_timeout);
+                        RxState::Response {
+                            id,
+                            data: Vec::with_capacity(usize::from(frame.len) + 1),
+                        }
+                    }
+                    None => RxState::Idle,
+                }
+            }
+            RxState::Response { id, mut data } => {
+                let Some(frame) = &self.frames[usize::from(id)] else {
+                    return Ok(());
+                };
+                data.push(byte);
+                if data.len() <= usize::from(frame.len) {
+                    RxState::Response { id, data }
+                } else {
+                    let checksum = data.pop().unwrap();
+                    let event = if checksum == frame.checksum.checksum(id, &data) {
+                        LinEvent::Frame(LinFrame::new(id, data))
+                    } else {
+                        LinEvent::Error(LinError {
+                            id,
+                            kind: LinErrorKind::Checksum,
+                        })
+                    };
+                    self.events.push_back(event);
+                    RxState::Idle
+                }
+            }
+        };
+        if matches!(self.state, RxState::Idle) {
+            self.deadline = None;
+        }
+
+        Ok(())
+    }
+}
+
+impl LinBackend for UartBackend {
+    fn interest(&self) -> Interest {
+        Interest::READABLE | Interest::WRITABLE
+    }
+
+    /// Transmits a header, unless the previous transmission is still pending.
+    ///
+    /// This method blocks until the previous transmission is complete and
+    /// during the break.
+    fn send_header(&mut self, id: u8, response: Option<&[u8]>) -> Result<()> {
+        if !self.tx.flush(&mut self.port)? {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        self.port.flush()?;
+        self.port.set_break()?;
+        thread::sleep(self.bit_time * BREAK_BITS);
+        self.port.clear_break()?;
+        // Break delimiter.
+        thread::sleep(self.bit_time);
+
+        self.tx.push(&[SYNC, protected_id(id)]);
+        if let (Some(data), Some(Some(frame))) = (response, self.frames.get(usize::from(id))) {
+            self.tx.push(data);
+            self.tx.push(&[frame.checksum.checksum(id, data)]);
+        }
+        self.tx.flush(&mut self.port)?;
+
+        Ok(())
+    }
+
+    fn set_response(&mut self, id: u8, data: &[u8]) -> Result<()> {
+        if let Some(Some(frame)) = self.frames.get_mut(usize::from(id)) {
+            frame.data = Some(data.to_vec());
+        }
+
+        Ok(())
+    }
+
+    fn read_event(&mut self) -> Result<LinEvent> {
+        let mut buf = [0; 256];
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            match self.port.read(&mut buf)? {
+                0 => return Err(ErrorKind::WouldBlock.into()),
+                len => {
+                    for &byte in &buf[..len] {
+                        self.receive(byte)?;
+                    }
+                }
+            }
+        }
+    }
+
+    fn writable(&mut self) -> Result<()> {
+        self.tx.flush(&mut self.port)?;
+
+        Ok(())
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Reports a missing or incomplete response.
+    fn timeout(&mut self) -> Option<LinEvent> {
+        self.deadline = None;
+        match mem::replace(&mut self.state, RxState::Idle) {
+            RxState::Response { id, .. } => Some(LinEvent::Error(LinError {
+                id,
+                kind: LinErrorKind::NoResponse,
+            })),
+            _ => None,
+        }
+    }
+}
+
+impl Source for UartBackend {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        self.port.register(registry, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> Result<()> {
+        self.port.reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> Result<()> {
+        self.port.deregister(registry)
+    }
+}
+
+impl fmt::Debug for UartBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UartBackend").finish_non_exhaustive()
+    }
+}
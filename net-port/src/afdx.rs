@@ -0,0 +1,619 @@
+//! AFDX end system layer.
+//!
+//! This module contains a model implementing the virtual link layer of an
+//! AFDX (ARINC 664 part 7) end system on top of two redundant Ethernet
+//! networks, e.g. two TUN/TAP port models set up as TAP interfaces, so that
+//! integrated modular avionics benches can be represented.
+//!
+//! Messages are exchanged with the simulation by virtual link, as the payload
+//! of AFDX frames, typically an IPv4 packet carrying the UDP datagram of a
+//! communication port. On transmission, the layer:
+//! * builds the Ethernet header from the virtual link identifier, the
+//!   configured source identifier and the network identifier,
+//! * appends the sequence number of the virtual link, padding the frame to the
+//!   minimum Ethernet frame size,
+//! * regulates the traffic of each virtual link, sending at most one frame per
+//!   bandwidth allocation gap (BAG) and delaying the other frames in a bounded
+//!   queue,
+//! * sends each frame on the networks of its virtual link.
+//!
+//! On reception, frames of each network are policed against the BAG and the
+//! maximum frame size of their virtual link and checked for integrity with
+//! their sequence number. When a virtual link uses both networks, redundancy
+//! management then forwards the first copy of each frame and discards the
+//! other copy. Frames of unknown virtual links are ignored, and the other
+//! discarded frames are reported as faults. Frames are exchanged without frame
+//! check sequence, as with TAP interfaces.
+//!
+//! BAG, jitter and skew values are applied in simulation time.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::afdx::{AfdxConfig, AfdxEndSystem, AfdxNetworks};
+//!
+//! let config = ConfigLoader::<AfdxConfig>::new()
+//!     .code(
+//!         r#"
+//! sourceId = 0x0102
+//! skewMax = 500
+//!
+//! [[virtualLinks]]
+//! id = 100
+//! bag = 8
+//! maxFrameSize = 512
+//! maxJitter = 200
+//!
+//! [[virtualLinks]]
+//! id = 101
+//! bag = 32
+//! networks = "a"
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.virtual_links[1].networks, AfdxNetworks::A);
+//!
+//! // Connect `frame_a_out` and `frame_b_out` to the frame inputs of the ports
+//! // of networks A and B, the frame outputs of the ports to `frame_a_in` and
+//! // `frame_b_in`, and the message ports to the partitions.
+//! let end_system = AfdxEndSystem::new(config);
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use schematic::{Config, ConfigEnum};
+use serde::{Deserialize, Serialize};
+
+use nexosim::model::{Context, Model};
+use nexosim::ports::Output;
+use nexosim::time::MonotonicTime;
+
+use nexosim_io_utils::duration::ConfigDuration;
+
+/// Constant field of the destination MAC address.
+const DESTINATION_PREFIX: [u8; 4] = [0x03, 0x00, 0x00, 0x00];
+
+/// Constant field of the source MAC address.
+const SOURCE_PREFIX: [u8; 3] = [0x02, 0x00, 0x00];
+
+/// EtherType of IPv4 payloads.
+const ETHERTYPE_IPV4: u16 = 0x0800;
+
+/// Size of the Ethernet header.
+const HEADER_LEN: usize = 14;
+
+/// Minimum Ethernet frame size, without frame check sequence.
+const MIN_FRAME_LEN: usize = 60;
+
+/// Size of the frame check sequence, included in the maximum frame size.
+const FCS_LEN: usize = 4;
+
+/// Minimum value of the maximum frame size.
+const MIN_MAX_FRAME_SIZE: usize = 64;
+
+/// Maximum value of the maximum frame size.
+const MAX_MAX_FRAME_SIZE: usize = 1518;
+
+/// AFDX end system model instance config.
+#[derive(Config, Debug)]
+pub struct AfdxConfig {
+    /// User-defined identifier of the source MAC addresses.
+    pub source_id: u16,
+
+    /// Maximum delay between the two copies of a frame received on both
+    /// networks, in microseconds.
+    ///
+    /// A copy received later than this delay after the first copy is
+    /// forwarded again.
+    #[setting(default = 1000)]
+    pub skew_max: u64,
+
+    /// Virtual links of the end system.
+    #[setting(nested)]
+    pub virtual_links: Vec<AfdxVirtualLinkConfig>,
+}
+
+/// Virtual link configuration.
+#[derive(Config, Debug)]
+pub struct AfdxVirtualLinkConfig {
+    /// Virtual link identifier.
+    pub id: u16,
+
+    /// Bandwidth allocation gap.
+    ///
+    /// The BAG should be a power of two from 1 to 128 ms.
+    #[setting(default = ConfigDuration::from_millis(1))]
+    pub bag: ConfigDuration,
+
+    /// Maximum frame size, frame check sequence included, in bytes.
+    #[setting(default = 1518)]
+    pub max_frame_size: usize,
+
+    /// Maximum jitter tolerated by the BAG policing of received frames, in
+    /// microseconds.
+    pub max_jitter: u64,
+
+    /// Networks used by the virtual link.
+    pub networks: AfdxNetworks,
+
+    /// Maximum number of frames delayed by the traffic regulation.
+    #[setting(default = 64)]
+    pub queue_size: usize,
+}
+
+/// Networks used by a virtual link.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AfdxNetworks {
+    /// Network A only.
+    A,
+
+    /// Network B only.
+    B,
+
+    /// Both networks, with redundancy management.
+    #[default]
+    Both,
+}
+
+impl AfdxNetworks {
+    /// Checks whether the network is used.
+    fn contains(self, network: AfdxNetwork) -> bool {
+        matches!(
+            (self, network),
+            (Self::Both, _) | (Self::A, AfdxNetwork::A) | (Self::B, AfdxNetwork::B)
+        )
+    }
+}
+
+/// AFDX network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AfdxNetwork {
+    /// Network A.
+    A,
+    /// Network B.
+    B,
+}
+
+impl AfdxNetwork {
+    /// Returns the interface identifier of the source MAC addresses.
+    fn interface_id(self) -> u8 {
+        match self {
+            Self::A => 0b001 << 5,
+            Self::B => 0b010 << 5,
+        }
+    }
+}
+
+/// Message exchanged inside the simulation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AfdxMessage {
+    /// Virtual link identifier.
+    pub vl_id: u16,
+
+    /// Payload of the frame.
+    ///
+    /// Padding is removed from received IPv4 packets.
+    pub payload: Bytes,
+}
+
+/// Fault detected by the end system.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AfdxFault {
+    /// Virtual link identifier.
+    pub vl_id: u16,
+
+    /// Network of the received frame, or `None` for transmitted messages.
+    pub network: Option<AfdxNetwork>,
+
+    /// Fault kind.
+    pub kind: AfdxFaultKind,
+}
+
+/// Kind of fault detected by the end system.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AfdxFaultKind {
+    /// The frame exceeds the maximum frame size of its virtual link and was
+    /// discarded.
+    FrameTooLong,
+    /// The regulation queue of the virtual link was full and the message was
+    /// discarded.
+    QueueOverflow,
+    /// The frame was received earlier than allowed by the BAG of its virtual
+    /// link and was discarded.
+    BagViolation,
+    /// The sequence number of the frame is not consistent with the previous
+    /// frame of its virtual link on the same network, and the frame was
+    /// discarded.
+    SequenceError,
+}
+
+/// Returns the sequence number following another one.
+///
+/// Sequence number 0 is only used after a reset of the transmitter.
+fn next_sequence_number(sn: u8) -> u8 {
+    match sn {
+        255 => 1,
+        sn => sn + 1,
+    }
+}
+
+/// Returns the number of increments from a sequence number to another one.
+fn sequence_distance(from: u8, to: u8) -> u8 {
+    match (from, to) {
+        (_, 0) => 0,
+        (0, to) => to,
+        (from, to) => ((u16::from(to) + 255 - u16::from(from)) % 255) as u8,
+    }
+}
+
+/// Reception state of a virtual link on a network.
+#[derive(Default)]
+struct ReceiveState {
+    /// Arrival time of the last accepted frame.
+    last_arrival: Option<MonotonicTime>,
+
+    /// Sequence number of the last accepted frame.
+    last_sn: Option<u8>,
+}
+
+/// Virtual link state.
+struct VirtualLink {
+    /// Virtual link identifier.
+    id: u16,
+
+    /// Bandwidth allocation gap.
+    bag: Duration,
+
+    /// Maximum frame size, frame check sequence included.
+    max_frame_size: usize,
+
+    /// Maximum jitter tolerated by the BAG policing.
+    max_jitter: Duration,
+
+    /// Networks used by the virtual link.
+    networks: AfdxNetworks,
+
+    /// Maximum number of delayed frames.
+    queue_size: usize,
+
+    /// Sequence number of the next transmitted frame.
+    tx_sn: u8,
+
+    /// Earliest time of the next transmission.
+    next_tx: Option<MonotonicTime>,
+
+    /// Frames delayed by the regulation.
+    queue: VecDeque<Bytes>,
+
+    /// A release of the delayed frames is scheduled.
+    release_scheduled: bool,
+
+    /// Reception states of networks A and B.
+    rx: [ReceiveState; 2],
+
+    /// Sequence number and time of the last forwarded frame.
+    last_forwarded: Option<(u8, MonotonicTime)>,
+}
+
+impl VirtualLink {
+    /// Creates a virtual link from its configuration.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the BAG or the maximum frame size is invalid.
+    fn new(config: &AfdxVirtualLinkConfig) -> Self {
+        let bag_ms = config.bag.as_millis();
+        assert!(
+            Duration::from_millis(bag_ms as u64) == *config.bag
+                && bag_ms.is_power_of_two()
+                && bag_ms <= 128,
+            "the BAG of virtual link {} should be a power of two from 1 to 128 ms",
+            config.id
+        );
+        assert!(
+            (MIN_MAX_FRAME_SIZE..=MAX_MAX_FRAME_SIZE).contains(&config.max_frame_size),
+            "the maximum frame size of virtual link {} should be from {} to {} bytes",
+            config.id,
+            MIN_MAX_FRAME_SIZE,
+            MAX_MAX_FRAME_SIZE
+        );
+
+        Self {
+            id: config.id,
+            bag: Duration::from(config.bag),
+            max_frame_size: config.max_frame_size,
+            max_jitter: Duration::from_micros(config.max_jitter),
+            networks: config.networks,
+            queue_size: config.queue_size,
+            tx_sn: 0,
+            next_tx: None,
+            queue: VecDeque::new(),
+            release_scheduled: false,
+            rx: Default::default(),
+            last_forwarded: None,
+        }
+    }
+
+    /// Returns the time to wait until the next transmission.
+    fn wait_time(&self, now: MonotonicTime) -> Duration {
+        match self.next_tx {
+            Some(next_tx) if next_tx > now => next_tx.duration_since(now),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Polices a received frame and checks its integrity.
+    fn check(
+        &mut self,
+        network: AfdxNetwork,
+        frame_len: usize,
+        sn: u8,
+        now: MonotonicTime,
+    ) -> Result<(), AfdxFaultKind> {
+        if frame_len + FCS_LEN > self.max_frame_size {
+            return Err(AfdxFaultKind::FrameTooLong);
+        }
+        let state = &mut self.rx[network as usize];
+        if let Some(last_arrival) = state.last_arrival {
+            if now.duration_since(last_arrival) + self.max_jitter < self.bag {
+                return Err(AfdxFaultKind::BagViolation);
+            }
+        }
+        if let Some(last_sn) = state.last_sn {
+            if sn != 0 && !matches!(sequence_distance(last_sn, sn), 1 | 2) {
+                // Resynchronize on the next frame.
+                state.last_sn = Some(sn);
+                state.last_arrival = Some(now);
+                return Err(AfdxFaultKind::SequenceError);
+            }
+        }
+        state.last_arrival = Some(now);
+        state.last_sn = Some(sn);
+
+        Ok(())
+    }
+
+    /// Checks whether a frame accepted on a network is the first copy of the
+    /// frame, and records it as forwarded if so.
+    fn is_first_copy(&mut self, sn: u8, now: MonotonicTime, skew_max: Duration) -> bool {
+        if self.networks != AfdxNetworks::Both {
+            return true;
+        }
+        if let Some((last_sn, last_time)) = self.last_forwarded {
+            let is_newer = sn == 0 || (1..128).contains(&sequence_distance(last_sn, sn));
+            if !is_newer && now.duration_since(last_time) <= skew_max {
+                return false;
+            }
+        }
+        self.last_forwarded = Some((sn, now));
+
+        true
+    }
+}
+
+/// AFDX end system model.
+///
+/// This model:
+/// * encodes the messages from the message input into AFDX frames, regulated
+///   according to the BAG of their virtual link, and sends them to the
+///   networks of their virtual link,
+/// * checks the AFDX frames received from networks A and B, removes the
+///   redundant copies and forwards their messages to the message output,
+/// * reports the discarded frames and messages to the fault output.
+pub struct AfdxEndSystem {
+    /// Frames to be sent on network A -- output port.
+    pub frame_a_out: Output<Bytes>,
+
+    /// Frames to be sent on network B -- output port.
+    pub frame_b_out: Output<Bytes>,
+
+    /// Received messages -- output port.
+    pub message_out: Output<AfdxMessage>,
+
+    /// Discarded frames and messages -- output port.
+    pub fault_out: Output<AfdxFault>,
+
+    /// User-defined identifier of the source MAC addresses.
+    source_id: u16,
+
+    /// Maximum delay between the two copies of a frame.
+    skew_max: Duration,
+
+    /// Virtual links.
+    virtual_links: Vec<VirtualLink>,
+}
+
+impl AfdxEndSystem {
+    /// Creates a new AFDX end system.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the BAG or the maximum frame size of a virtual
+    /// link is invalid.
+    pub fn new(config: AfdxConfig) -> Self {
+        Self {
+            frame_a_out: Output::new(),
+            frame_b_out: Output::new(),
+            message_out: Output::new(),
+            fault_out: Output::new(),
+            source_id: config.source_id,
+            skew_max: Duration::from_micros(config.skew_max),
+            virtual_links: config.virtual_links.iter().map(VirtualLink::new).collect(),
+        }
+    }
+
+    /// Message to be sent -- input port.
+    ///
+    /// Messages of unknown virtual links are discarded.
+    pub async fn message_in(&mut self, message: AfdxMessage, cx: &mut Context<Self>) {
+        let Some(index) = self.index(message.vl_id) else {
+            return;
+        };
+        let vl = &mut self.virtual_links[index];
+        let frame_len = (HEADER_LEN + message.payload.len() + 1).max(MIN_FRAME_LEN);
+        let fault = if frame_len + FCS_LEN > vl.max_frame_size {
+            AfdxFaultKind::FrameTooLong
+        } else if vl.queue.is_empty() && vl.wait_time(cx.time()).is_zero() {
+            self.transmit(index, message.payload, cx.time()).await;
+            return;
+        } else if vl.queue.len() >= vl.queue_size {
+            AfdxFaultKind::QueueOverflow
+        } else {
+            vl.queue.push_back(message.payload);
+            self.schedule_release(index, cx);
+            return;
+        };
+        self.fault_out
+            .send(AfdxFault {
+                vl_id: message.vl_id,
+                network: None,
+                kind: fault,
+            })
+            .await;
+    }
+
+    /// Frame received on network A -- input port.
+    pub async fn frame_a_in(&mut self, frame: Bytes, cx: &mut Context<Self>) {
+        self.receive(AfdxNetwork::A, frame, cx.time()).await;
+    }
+
+    /// Frame received on network B -- input port.
+    pub async fn frame_b_in(&mut self, frame: Bytes, cx: &mut Context<Self>) {
+        self.receive(AfdxNetwork::B, frame, cx.time()).await;
+    }
+
+    /// Returns the index of a virtual link.
+    fn index(&self, vl_id: u16) -> Option<usize> {
+        self.virtual_links.iter().position(|vl| vl.id == vl_id)
+    }
+
+    /// Checks a received frame and forwards its message if it is the first
+    /// valid copy.
+    async fn receive(&mut self, network: AfdxNetwork, frame: Bytes, now: MonotonicTime) {
+        if frame.len() < HEADER_LEN + 1 || frame[..4] != DESTINATION_PREFIX {
+            return;
+        }
+        let vl_id = u16::from_be_bytes([frame[4], frame[5]]);
+        let Some(index) = self.index(vl_id) else {
+            return;
+        };
+        let vl = &mut self.virtual_links[index];
+        if !vl.networks.contains(network) {
+            return;
+        }
+        let sn = frame[frame.len() - 1];
+        if let Err(kind) = vl.check(network, frame.len(), sn, now) {
+            self.fault_out
+                .send(AfdxFault {
+                    vl_id,
+                    network: Some(network),
+                    kind,
+                })
+                .await;
+            return;
+        }
+        if !vl.is_first_copy(sn, now, self.skew_max) {
+            return;
+        }
+        let ethertype = u16::from_be_bytes([frame[12], frame[13]]);
+        let mut payload = frame.slice(HEADER_LEN..frame.len() - 1);
+        // Remove the padding of IPv4 packets, using their total length.
+        if ethertype == ETHERTYPE_IPV4 && payload.len() >= 4 {
+            let total_len = usize::from(u16::from_be_bytes([payload[2], payload[3]]));
+            if total_len < payload.len() {
+                payload.truncate(total_len);
+            }
+        }
+
+        self.message_out.send(AfdxMessage { vl_id, payload }).await;
+    }
+
+    /// Encodes a frame and sends it on the networks of its virtual link.
+    async fn transmit(&mut self, index: usize, payload: Bytes, now: MonotonicTime) {
+        let vl = &mut self.virtual_links[index];
+        let sn = vl.tx_sn;
+        vl.tx_sn = next_sequence_number(sn);
+        vl.next_tx = Some(now + vl.bag);
+        let vl_id = vl.id;
+        let networks = vl.networks;
+
+        for (network, output) in [
+            (AfdxNetwork::A, &mut self.frame_a_out),
+            (AfdxNetwork::B, &mut self.frame_b_out),
+        ] {
+            if networks.contains(network) {
+                let frame = encode_frame(vl_id, self.source_id, network, &payload, sn);
+                output.send(frame).await;
+            }
+        }
+    }
+
+    /// Sends the delayed frame of a virtual link.
+    async fn release(&mut self, index: usize, cx: &mut Context<Self>) {
+        let vl = &mut self.virtual_links[index];
+        vl.release_scheduled = false;
+        if !vl.wait_time(cx.time()).is_zero() {
+            self.schedule_release(index, cx);
+            return;
+        }
+        let Some(payload) = vl.queue.pop_front() else {
+            return;
+        };
+        self.transmit(index, payload, cx.time()).await;
+        self.schedule_release(index, cx);
+    }
+
+    /// Schedules the release of the first delayed frame of a virtual link.
+    fn schedule_release(&mut self, index: usize, cx: &mut Context<Self>) {
+        let vl = &mut self.virtual_links[index];
+        if vl.release_scheduled || vl.queue.is_empty() {
+            return;
+        }
+        // Deadlines should be strictly in the future.
+        let wait_time = vl.wait_time(cx.time()).max(Duration::from_nanos(1));
+        vl.release_scheduled = true;
+        cx.schedule_event(wait_time, Self::release, index).unwrap();
+    }
+}
+
+/// Encodes an AFDX frame.
+///
+/// The payload is assumed to be an IPv4 packet.
+fn encode_frame(vl_id: u16, source_id: u16, network: AfdxNetwork, payload: &[u8], sn: u8) -> Bytes {
+    let len = (HEADER_LEN + payload.len() + 1).max(MIN_FRAME_LEN);
+    let mut frame = BytesMut::with_capacity(len);
+    frame.put_slice(&DESTINATION_PREFIX);
+    frame.put_u16(vl_id);
+    frame.put_slice(&SOURCE_PREFIX);
+    frame.put_u16(source_id);
+    frame.put_u8(network.interface_id());
+    frame.put_u16(ETHERTYPE_IPV4);
+    frame.put_slice(payload);
+    frame.resize(len - 1, 0);
+    frame.put_u8(sn);
+
+    frame.freeze()
+}
+
+impl Model for AfdxEndSystem {}
+
+impl fmt::Debug for AfdxEndSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AfdxEndSystem")
+            .field("source_id", &self.source_id)
+            .field("virtual_links", &self.virtual_links.len())
+            .finish_non_exhaustive()
+    }
+}
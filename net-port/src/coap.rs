@@ -0,0 +1,1431 @@
+//! CoAP bridge model.
+//!
+//! This module contains the [`CoapBridge`] model, a CoAP endpoint over UDP
+//! which lets constrained devices exchange data with a simulation, acting both
+//! as a server and as a client.
+//!
+//! As a server, the bridge serves the resources declared in the configuration:
+//!
+//! * `GET` on a served resource returns the last payload sent to the model
+//!   input for this resource, or an empty payload if none was sent yet,
+//! * `GET` with the Observe option registers or deregisters the client as an
+//!   observer of the resource, each new payload being then notified to its
+//!   observers,
+//! * `PUT` or `POST` on a command resource forwards the payload to the model
+//!   output, tagged with the resource path, and returns `2.04 Changed`.
+//!
+//! As a client, the bridge sends the requests received on its input to remote
+//! endpoints, and forwards their responses to the model output. Requests with
+//! the Observe option register the bridge as an observer of a remote resource,
+//! in which case all subsequent notifications are forwarded as responses to
+//! the same request. Remote resources can also be observed from the start by
+//! declaring them in the configuration.
+//!
+//! Requests and notifications sent as confirmable messages are retransmitted
+//! with an exponential back-off, without randomization of the initial
+//! timeout. Clients which reset a notification or do not acknowledge a
+//! confirmable notification are removed from the observers. Duplicate
+//! messages are detected and the responses to duplicate confirmable requests
+//! are retransmitted.
+//!
+//! Resource paths are written without leading slash, e.g. `sensors/temp`.
+//! Block-wise transfers are not supported.
+//!
+//! #### Examples
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::coap::CoapBridgeConfig;
+//!
+//! let config = ConfigLoader::<CoapBridgeConfig>::new()
+//!     .code(
+//!         r#"
+//! localAddress = "0.0.0.0:5683"
+//! resources = ["sensors/temperature"]
+//! commands = ["actuators/valve"]
+//! period = 10
+//!
+//! [[observe]]
+//! address = "192.168.1.30:5683"
+//! path = "status"
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(Duration::from(config.ack_timeout), Duration::from_secs(2));
+//! ```
+//!
+//! With this configuration, `coap-client -m get -s 60
+//! coap://127.0.0.1/sensors/temperature` observes the temperature published
+//! by the simulation for 60 seconds.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result as IoResult};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+
+use schematic::Config;
+
+use mio::net::UdpSocket;
+use mio::{Interest, Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus};
+
+/// CoAP bridge model instance configuration.
+#[derive(Config, Debug)]
+pub struct CoapBridgeConfig {
+    /// Local address, as `HOST:PORT`, to bind to.
+    #[setting(default = "0.0.0.0:5683")]
+    pub local_address: String,
+
+    /// Paths of the observable resources served to clients.
+    pub resources: Vec<String>,
+
+    /// Paths of the resources accepting `PUT` and `POST` requests from
+    /// clients.
+    pub commands: Vec<String>,
+
+    /// Remote resources observed from the start.
+    #[setting(nested)]
+    pub observe: Vec<CoapObserveConfig>,
+
+    /// Notifications are sent as confirmable messages.
+    pub confirmable_notifications: bool,
+
+    /// Initial acknowledgement timeout of confirmable messages.
+    #[setting(default = ConfigDuration::from_millis(2000))]
+    pub ack_timeout: ConfigDuration,
+
+    /// Maximum number of retransmissions of confirmable messages.
+    #[setting(default = 4)]
+    pub max_retransmit: u32,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which received commands and responses are forwarded into the
+    /// simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// Remote resource observation configuration.
+#[derive(Config, Debug)]
+pub struct CoapObserveConfig {
+    /// Address of the remote endpoint, as `HOST:PORT`.
+    pub address: String,
+
+    /// Resource path.
+    pub path: String,
+}
+
+/// Payload tagged with the path of its resource.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoapData {
+    /// Resource path.
+    pub path: String,
+
+    /// Payload.
+    pub payload: Bytes,
+}
+
+impl CoapData {
+    /// Creates a new tagged payload.
+    pub fn new(path: impl Into<String>, payload: Bytes) -> Self {
+        Self {
+            path: path.into(),
+            payload,
+        }
+    }
+}
+
+/// CoAP request method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CoapMethod {
+    /// `GET` method.
+    Get,
+
+    /// `POST` method.
+    Post,
+
+    /// `PUT` method.
+    Put,
+
+    /// `DELETE` method.
+    Delete,
+}
+
+impl CoapMethod {
+    /// Returns the method code.
+    fn code(self) -> u8 {
+        match self {
+            Self::Get => 0x01,
+            Self::Post => 0x02,
+            Self::Put => 0x03,
+            Self::Delete => 0x04,
+        }
+    }
+}
+
+/// CoAP request to a remote endpoint.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoapRequest {
+    /// Address of the remote endpoint, as `HOST:PORT`.
+    pub address: String,
+
+    /// Method.
+    pub method: CoapMethod,
+
+    /// Resource path.
+    pub path: String,
+
+    /// Payload.
+    pub payload: Bytes,
+
+    /// Value of the Observe option: `0` to register and `1` to deregister.
+    pub observe: Option<u32>,
+}
+
+impl CoapRequest {
+    /// Creates a new request without Observe option.
+    pub fn new(
+        address: impl Into<String>,
+        method: CoapMethod,
+        path: impl Into<String>,
+        payload: Bytes,
+    ) -> Self {
+        Self {
+            address: address.into(),
+            method,
+            path: path.into(),
+            payload,
+            observe: None,
+        }
+    }
+
+    /// Creates a `GET` request.
+    pub fn get(address: impl Into<String>, path: impl Into<String>) -> Self {
+        Self::new(address, CoapMethod::Get, path, Bytes::new())
+    }
+
+    /// Creates a `POST` request.
+    pub fn post(address: impl Into<String>, path: impl Into<String>, payload: Bytes) -> Self {
+        Self::new(address, CoapMethod::Post, path, payload)
+    }
+
+    /// Creates a `PUT` request.
+    pub fn put(address: impl Into<String>, path: impl Into<String>, payload: Bytes) -> Self {
+        Self::new(address, CoapMethod::Put, path, payload)
+    }
+
+    /// Creates a `DELETE` request.
+    pub fn delete(address: impl Into<String>, path: impl Into<String>) -> Self {
+        Self::new(address, CoapMethod::Delete, path, Bytes::new())
+    }
+
+    /// Creates a `GET` request registering an observation.
+    pub fn observe(address: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            observe: Some(0),
+            ..Self::get(address, path)
+        }
+    }
+
+    /// Creates a `GET` request cancelling an observation.
+    pub fn cancel_observe(address: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            observe: Some(1),
+            ..Self::get(address, path)
+        }
+    }
+}
+
+/// CoAP response code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CoapCode(pub u8);
+
+impl CoapCode {
+    /// `2.01 Created`.
+    pub const CREATED: Self = Self(0x41);
+    /// `2.02 Deleted`.
+    pub const DELETED: Self = Self(0x42);
+    /// `2.03 Valid`.
+    pub const VALID: Self = Self(0x43);
+    /// `2.04 Changed`.
+    pub const CHANGED: Self = Self(0x44);
+    /// `2.05 Content`.
+    pub const CONTENT: Self = Self(0x45);
+    /// `4.00 Bad Request`.
+    pub const BAD_REQUEST: Self = Self(0x80);
+    /// `4.02 Bad Option`.
+    pub const BAD_OPTION: Self = Self(0x82);
+    /// `4.04 Not Found`.
+    pub const NOT_FOUND: Self = Self(0x84);
+    /// `4.05 Method Not Allowed`.
+    pub const METHOD_NOT_ALLOWED: Self = Self(0x85);
+
+    /// Returns the code class, e.g. 2 for `2.05`.
+    pub fn class(self) -> u8 {
+        self.0 >> 5
+    }
+
+    /// Returns the code detail, e.g. 5 for `2.05`.
+    pub fn detail(self) -> u8 {
+        self.0 & 0x1F
+    }
+
+    /// Returns `true` for a success response code.
+    pub fn is_success(self) -> bool {
+        self.class() == 2
+    }
+}
+
+impl fmt::Display for CoapCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:02}", self.class(), self.detail())
+    }
+}
+
+/// Content of a CoAP response or notification.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoapContent {
+    /// Response code.
+    pub code: CoapCode,
+
+    /// Value of the Observe option, if any.
+    pub observe: Option<u32>,
+
+    /// Payload.
+    pub payload: Bytes,
+}
+
+/// Response to a CoAP request, or notification of an observed resource.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoapResponse {
+    /// Request.
+    pub request: CoapRequest,
+
+    /// Response content, or error.
+    pub result: Result<CoapContent, CoapError>,
+}
+
+/// CoAP request error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CoapError {
+    /// The request was not acknowledged after the last retransmission.
+    Timeout,
+
+    /// The request was reset by the remote endpoint.
+    Reset,
+
+    /// The address of the remote endpoint cannot be resolved.
+    InvalidAddress,
+}
+
+impl fmt::Display for CoapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Timeout => f.write_str("timeout"),
+            Self::Reset => f.write_str("reset by the remote endpoint"),
+            Self::InvalidAddress => f.write_str("invalid address"),
+        }
+    }
+}
+
+/// Maximum size of a received datagram.
+const MAX_DATAGRAM_LEN: usize = 65536;
+
+/// Number of received messages remembered for duplicate detection.
+const DEDUPLICATION_CAPACITY: usize = 64;
+
+/// Observe option number.
+const OPTION_OBSERVE: u16 = 6;
+
+/// Uri-Path option number.
+const OPTION_URI_PATH: u16 = 11;
+
+/// Critical options accepted and ignored by the server: Uri-Host, Uri-Port,
+/// Uri-Query and Accept.
+const IGNORED_CRITICAL_OPTIONS: [u16; 4] = [3, 7, 15, 17];
+
+/// Payload marker.
+const PAYLOAD_MARKER: u8 = 0xFF;
+
+/// Socket token.
+const SOCKET: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// CoAP message type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MessageType {
+    /// Confirmable message.
+    Confirmable,
+
+    /// Non-confirmable message.
+    NonConfirmable,
+
+    /// Acknowledgement.
+    Acknowledgement,
+
+    /// Reset.
+    Reset,
+}
+
+/// CoAP message.
+#[derive(Debug)]
+struct Message {
+    /// Message type.
+    kind: MessageType,
+
+    /// Request method or response code.
+    code: u8,
+
+    /// Message ID.
+    id: u16,
+
+    /// Token.
+    token: Vec<u8>,
+
+    /// Options, with their numbers.
+    options: Vec<(u16, Vec<u8>)>,
+
+    /// Payload.
+    payload: Bytes,
+}
+
+impl Message {
+    /// Creates a message without options nor payload.
+    fn new(kind: MessageType, code: u8, id: u16, token: &[u8]) -> Self {
+        Self {
+            kind,
+            code,
+            id,
+            token: token.to_vec(),
+            options: Vec::new(),
+            payload: Bytes::new(),
+        }
+    }
+
+    /// Creates an empty message, used for acknowledgements and resets.
+    fn empty(kind: MessageType, id: u16) -> Self {
+        Self::new(kind, 0, id, &[])
+    }
+
+    /// Adds an option.
+    fn with_option(mut self, number: u16, value: Vec<u8>) -> Self {
+        self.options.push((number, value));
+        self
+    }
+
+    /// Adds the Uri-Path options of a path.
+    fn with_path(mut self, path: &str) -> Self {
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            self.options
+                .push((OPTION_URI_PATH, segment.as_bytes().to_vec()));
+        }
+        self
+    }
+
+    /// Sets the payload.
+    fn with_payload(mut self, payload: Bytes) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Returns the value of the first option with the given number.
+    fn option(&self, number: u16) -> Option<&[u8]> {
+        self.options
+            .iter()
+            .find(|(n, _)| *n == number)
+            .map(|(_, value)| &value[..])
+    }
+
+    /// Returns the value of the Observe option, if any.
+    fn observe(&self) -> Option<u32> {
+        self.option(OPTION_OBSERVE).map(decode_uint)
+    }
+
+    /// Returns the path of the Uri-Path options.
+    fn path(&self) -> String {
+        let segments: Vec<_> = self
+            .options
+            .iter()
+            .filter(|(number, _)| *number == OPTION_URI_PATH)
+            .map(|(_, value)| String::from_utf8_lossy(value))
+            .collect();
+
+        segments.join("/")
+    }
+
+    /// Serializes the message.
+    fn encode(&self) -> Vec<u8> {
+        let kind = match self.kind {
+            MessageType::Confirmable => 0,
+            MessageType::NonConfirmable => 1,
+            MessageType::Acknowledgement => 2,
+            MessageType::Reset => 3,
+        };
+        let mut bytes = vec![0x40 | (kind << 4) | self.token.len() as u8, self.code];
+        bytes.extend_from_slice(&self.id.to_be_bytes());
+        bytes.extend_from_slice(&self.token);
+
+        let mut options: Vec<_> = self.options.iter().collect();
+        options.sort_by_key(|(number, _)| *number);
+        let mut previous = 0;
+        for (number, value) in options {
+            let (delta, delta_ext) = option_nibble(number - previous);
+            let (len, len_ext) = option_nibble(value.len() as u16);
+            bytes.push((delta << 4) | len);
+            bytes.extend_from_slice(&delta_ext);
+            bytes.extend_from_slice(&len_ext);
+            bytes.extend_from_slice(value);
+            previous = *number;
+        }
+        if !self.payload.is_empty() {
+            bytes.push(PAYLOAD_MARKER);
+            bytes.extend_from_slice(&self.payload);
+        }
+
+        bytes
+    }
+
+    /// Deserializes a message.
+    ///
+    /// Returns `None` if the message is malformed.
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let (&[first, code, id_hi, id_lo], mut rest) = bytes.split_first_chunk::<4>()?;
+        if first >> 6 != 1 {
+            return None;
+        }
+        let kind = match (first >> 4) & 0x03 {
+            0 => MessageType::Confirmable,
+            1 => MessageType::NonConfirmable,
+            2 => MessageType::Acknowledgement,
+            _ => MessageType::Reset,
+        };
+        let token_len = usize::from(first & 0x0F);
+        if token_len > 8 {
+            return None;
+        }
+        let token = rest.get(..token_len)?.to_vec();
+        rest = &rest[token_len..];
+
+        let mut options = Vec::new();
+        let mut number = 0u16;
+        let mut payload = Bytes::new();
+        while let Some((&byte, tail)) = rest.split_first() {
+            rest = tail;
+            if byte == PAYLOAD_MARKER {
+                if rest.is_empty() {
+                    return None;
+                }
+                payload = Bytes::copy_from_slice(rest);
+                break;
+            }
+            let delta = read_option_nibble(byte >> 4, &mut rest)?;
+            let len = usize::from(read_option_nibble(byte & 0x0F, &mut rest)?);
+            number = number.checked_add(delta)?;
+            let value = rest.get(..len)?.to_vec();
+            rest = &rest[len..];
+            options.push((number, value));
+        }
+
+        Some(Self {
+            kind,
+            code,
+            id: u16::from_be_bytes([id_hi, id_lo]),
+            token,
+            options,
+            payload,
+        })
+    }
+}
+
+/// Returns the 4-bit field and the extended bytes of an option delta or
+/// length.
+fn option_nibble(value: u16) -> (u8, Vec<u8>) {
+    match value {
+        0..13 => (value as u8, Vec::new()),
+        13..269 => (13, vec![(value - 13) as u8]),
+        _ => (14, (value - 269).to_be_bytes().to_vec()),
+    }
+}
+
+/// Reads an option delta or length from its 4-bit field and extended bytes.
+fn read_option_nibble(nibble: u8, rest: &mut &[u8]) -> Option<u16> {
+    match nibble {
+        13 => {
+            let (&byte, tail) = rest.split_first()?;
+            *rest = tail;
+            Some(u16::from(byte) + 13)
+        }
+        14 => {
+            let (&bytes, tail) = rest.split_first_chunk::<2>()?;
+            *rest = tail;
+            u16::from_be_bytes(bytes).checked_add(269)
+        }
+        15 => None,
+        _ => Some(u16::from(nibble)),
+    }
+}
+
+/// Encodes an unsigned integer option value on the minimal number of bytes.
+fn encode_uint(value: u32) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&byte| byte == 0).count();
+
+    bytes[skip..].to_vec()
+}
+
+/// Decodes an unsigned integer option value.
+fn decode_uint(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .take(4)
+        .fold(0, |value, &byte| (value << 8) | u32::from(byte))
+}
+
+/// Returns `true` if a notification numbered `new` is fresher than the one
+/// numbered `last`.
+fn is_fresher(new: u32, last: u32) -> bool {
+    const HALF: u32 = 1 << 23;
+
+    (last < new && new - last < HALF) || (last > new && last - new > HALF)
+}
+
+/// Normalizes a resource path.
+fn normalize(path: &str) -> String {
+    path.trim_matches('/').to_string()
+}
+
+/// Data forwarded into the simulation.
+#[derive(Debug)]
+enum Incoming {
+    /// Command from a client.
+    Command(CoapData),
+
+    /// Response from a remote endpoint.
+    Response(CoapResponse),
+}
+
+/// Data sent from the simulation.
+#[derive(Debug)]
+enum Outgoing {
+    /// New payload of a served resource.
+    Resource(CoapData),
+
+    /// Request to a remote endpoint.
+    Request(CoapRequest),
+}
+
+/// Observer of a served resource.
+struct Observer {
+    /// Client endpoint.
+    endpoint: SocketAddr,
+
+    /// Token of the observation.
+    token: Vec<u8>,
+
+    /// Message ID of the last notification.
+    last_id: u16,
+}
+
+/// Served resource.
+struct Resource {
+    /// Resource path.
+    path: String,
+
+    /// Last payload.
+    payload: Bytes,
+
+    /// Sequence number of the last notification.
+    sequence: u32,
+
+    /// Observers.
+    observers: Vec<Observer>,
+}
+
+/// Request to a remote endpoint awaiting its response.
+struct Exchange {
+    /// Remote endpoint.
+    endpoint: SocketAddr,
+
+    /// Token.
+    token: Vec<u8>,
+
+    /// Request.
+    request: CoapRequest,
+
+    /// Observe option value of the last notification, once the observation
+    /// is established.
+    last_observe: Option<u32>,
+}
+
+/// Sender of a confirmable message.
+#[derive(Clone, Debug, PartialEq)]
+enum PendingSource {
+    /// Request, with its token.
+    Request(Vec<u8>),
+
+    /// Notification, with the index of its resource.
+    Notification(usize),
+}
+
+/// Confirmable message awaiting its acknowledgement.
+struct Pending {
+    /// Remote endpoint.
+    endpoint: SocketAddr,
+
+    /// Message ID.
+    id: u16,
+
+    /// Serialized message.
+    packet: Vec<u8>,
+
+    /// Sender of the message.
+    source: PendingSource,
+
+    /// Number of retransmissions so far.
+    retransmissions: u32,
+
+    /// Current retransmission timeout.
+    timeout: Duration,
+
+    /// Time of the next retransmission.
+    deadline: Instant,
+}
+
+/// Received message remembered for duplicate detection.
+struct Received {
+    /// Remote endpoint.
+    endpoint: SocketAddr,
+
+    /// Message ID.
+    id: u16,
+
+    /// Reply to the message, if confirmable.
+    reply: Option<Vec<u8>>,
+}
+
+/// CoAP bridge port.
+struct CoapBridgeInner {
+    /// UDP socket.
+    socket: UdpSocket,
+
+    /// Served resources.
+    resources: Vec<Resource>,
+
+    /// Command resource paths.
+    commands: Vec<String>,
+
+    /// Requests sent once registered.
+    initial_requests: Vec<CoapRequest>,
+
+    /// Notifications are sent as confirmable messages.
+    confirmable_notifications: bool,
+
+    /// Initial acknowledgement timeout.
+    ack_timeout: Duration,
+
+    /// Maximum number of retransmissions.
+    max_retransmit: u32,
+
+    /// Next message ID.
+    next_id: u16,
+
+    /// Next token.
+    next_token: u32,
+
+    /// Requests awaiting their response, and established observations.
+    exchanges: Vec<Exchange>,
+
+    /// Confirmable messages awaiting their acknowledgement.
+    pending: Vec<Pending>,
+
+    /// Last received messages.
+    received: VecDeque<Received>,
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+
+    /// Commands and responses not yet read.
+    events: VecDeque<Incoming>,
+}
+
+impl CoapBridgeInner {
+    /// Binds the local address.
+    fn new(config: &CoapBridgeConfig) -> IoResult<Self> {
+        let socket = UdpSocket::bind(resolve(&config.local_address)?)?;
+        // Message IDs should not be predictable across restarts.
+        let seed = std::process::id()
+            ^ SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .subsec_nanos();
+
+        Ok(Self {
+            socket,
+            resources: config
+                .resources
+                .iter()
+                .map(|path| Resource {
+                    path: normalize(path),
+                    payload: Bytes::new(),
+                    sequence: 0,
+                    observers: Vec::new(),
+                })
+                .collect(),
+            commands: config.commands.iter().map(|path| normalize(path)).collect(),
+            initial_requests: config
+                .observe
+                .iter()
+                .map(|observe| CoapRequest::observe(&observe.address, &observe.path))
+                .collect(),
+            confirmable_notifications: config.confirmable_notifications,
+            ack_timeout: Duration::from(config.ack_timeout),
+            max_retransmit: config.max_retransmit,
+            next_id: seed as u16,
+            next_token: seed.rotate_left(16),
+            exchanges: Vec::new(),
+            pending: Vec::new(),
+            received: VecDeque::new(),
+            buffer: vec![0; MAX_DATAGRAM_LEN],
+            events: VecDeque::new(),
+        })
+    }
+
+    /// Returns a new message ID.
+    fn message_id(&mut self) -> u16 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        id
+    }
+
+    /// Sends a datagram.
+    ///
+    /// Datagrams which cannot be sent are lost, confirmable messages being
+    /// retransmitted.
+    fn send(&self, endpoint: SocketAddr, packet: &[u8]) {
+        let _ = self.socket.send_to(packet, endpoint);
+    }
+
+    /// Sends a confirmable message and schedules its retransmission.
+    fn send_confirmable(&mut self, endpoint: SocketAddr, message: &Message, source: PendingSource) {
+        let packet = message.encode();
+        self.send(endpoint, &packet);
+        self.pending.push(Pending {
+            endpoint,
+            id: message.id,
+            packet,
+            source,
+            retransmissions: 0,
+            timeout: self.ack_timeout,
+            deadline: Instant::now() + self.ack_timeout,
+        });
+    }
+
+    /// Sends a request to a remote endpoint.
+    fn request(&mut self, request: CoapRequest) {
+        let Ok(endpoint) = resolve(&request.address) else {
+            self.events.push_back(Incoming::Response(CoapResponse {
+                request,
+                result: Err(CoapError::InvalidAddress),
+            }));
+            return;
+        };
+        let path = normalize(&request.path);
+        // Observations are re-registered or cancelled with their token.
+        let existing = request.observe.and_then(|_| {
+            self.exchanges.iter().position(|exchange| {
+                exchange.endpoint == endpoint
+                    && exchange.request.observe == Some(0)
+                    && normalize(&exchange.request.path) == path
+            })
+        });
+        let token = match existing {
+            Some(index) => {
+                let exchange = self.exchanges.swap_remove(index);
+                self.pending.retain(|pending| {
+                    pending.source != PendingSource::Request(exchange.token.clone())
+                });
+                exchange.token
+            }
+            None => {
+                let token = self.next_token;
+                self.next_token = self.next_token.wrapping_add(1);
+                token.to_be_bytes().to_vec()
+            }
+        };
+
+        let id = self.message_id();
+        let mut message = Message::new(MessageType::Confirmable, request.method.code(), id, &token)
+            .with_path(&path)
+            .with_payload(request.payload.clone());
+        if let Some(observe) = request.observe {
+            message = message.with_option(OPTION_OBSERVE, encode_uint(observe));
+        }
+        self.exchanges.push(Exchange {
+            endpoint,
+            token: token.clone(),
+            request,
+            last_observe: None,
+        });
+        self.send_confirmable(endpoint, &message, PendingSource::Request(token));
+    }
+
+    /// Updates the payload of a served resource and notifies its observers.
+    fn publish(&mut self, data: &CoapData) {
+        let path = normalize(&data.path);
+        let Some(index) = self.resources.iter().position(|r| r.path == path) else {
+            return;
+        };
+        let resource = &mut self.resources[index];
+        resource.payload = data.payload.clone();
+        resource.sequence = (resource.sequence + 1) & 0xFF_FFFF;
+        let sequence = resource.sequence;
+
+        for observer in 0..self.resources[index].observers.len() {
+            let id = self.message_id();
+            let observer = &mut self.resources[index].observers[observer];
+            observer.last_id = id;
+            let endpoint = observer.endpoint;
+            let kind = if self.confirmable_notifications {
+                MessageType::Confirmable
+            } else {
+                MessageType::NonConfirmable
+            };
+            let message = Message::new(kind, CoapCode::CONTENT.0, id, &observer.token)
+                .with_option(OPTION_OBSERVE, encode_uint(sequence))
+                .with_payload(data.payload.clone());
+            if kind == MessageType::Confirmable {
+                // The outstanding notification, if any, is superseded.
+                let source = PendingSource::Notification(index);
+                self.pending
+                    .retain(|pending| pending.endpoint != endpoint || pending.source != source);
+                self.send_confirmable(endpoint, &message, source);
+            } else {
+                self.send(endpoint, &message.encode());
+            }
+        }
+    }
+
+    /// Handles a received datagram.
+    fn receive(&mut self, endpoint: SocketAddr, datagram: &[u8]) {
+        let Some(message) = Message::decode(datagram) else {
+            // Malformed confirmable messages are rejected.
+            if let [first, _, id_hi, id_lo, ..] = *datagram {
+                if first >> 4 == 0x04 {
+                    let id = u16::from_be_bytes([id_hi, id_lo]);
+                    self.send(endpoint, &Message::empty(MessageType::Reset, id).encode());
+                }
+            }
+            return;
+        };
+
+        if matches!(
+            message.kind,
+            MessageType::Confirmable | MessageType::NonConfirmable
+        ) {
+            if let Some(received) = self
+                .received
+                .iter()
+                .find(|received| received.endpoint == endpoint && received.id == message.id)
+            {
+                if let Some(reply) = &received.reply {
+                    self.send(endpoint, reply);
+                }
+                return;
+            }
+        }
+
+        let reply = match (message.kind, message.code) {
+            // Ping.
+            (MessageType::Confirmable, 0) => Some(Message::empty(MessageType::Reset, message.id)),
+            (MessageType::NonConfirmable, 0) => None,
+            (MessageType::Acknowledgement, _) => {
+                self.acknowledge(endpoint, message.id);
+                if message.code >= 0x40 {
+                    self.on_response(endpoint, &message);
+                }
+                None
+            }
+            (MessageType::Reset, _) => {
+                self.reset(endpoint, message.id);
+                None
+            }
+            (_, 0x01..0x20) => Some(self.on_request(endpoint, &message)),
+            (_, 0x40..0xC0) => self.on_response(endpoint, &message),
+            (MessageType::Confirmable, _) => Some(Message::empty(MessageType::Reset, message.id)),
+            _ => None,
+        };
+
+        let reply = reply.map(|reply| reply.encode());
+        if let Some(reply) = &reply {
+            self.send(endpoint, reply);
+        }
+        if message.kind != MessageType::Acknowledgement && message.kind != MessageType::Reset {
+            if self.received.len() == DEDUPLICATION_CAPACITY {
+                self.received.pop_front();
+            }
+            self.received.push_back(Received {
+                endpoint,
+                id: message.id,
+                reply: reply.filter(|_| message.kind == MessageType::Confirmable),
+            });
+        }
+    }
+
+    /// Handles a request from a client and returns the response.
+    fn on_request(&mut self, endpoint: SocketAddr, request: &Message) -> Message {
+        let code = self.serve(endpoint, request);
+        let (kind, id) = match request.kind {
+            MessageType::Confirmable => (MessageType::Acknowledgement, request.id),
+            _ => (MessageType::NonConfirmable, self.message_id()),
+        };
+        let mut response = Message::new(kind, code.0, id, &request.token);
+
+        if code == CoapCode::CONTENT {
+            let path = request.path();
+            let resource = self.resources.iter().find(|r| r.path == path).unwrap();
+            let is_observed = resource
+                .observers
+                .iter()
+                .any(|o| o.endpoint == endpoint && o.token == request.token);
+            if is_observed {
+                response = response.with_option(OPTION_OBSERVE, encode_uint(resource.sequence));
+            }
+            response = response.with_payload(resource.payload.clone());
+        }
+
+        response
+    }
+
+    /// Serves a request and returns the response code.
+    fn serve(&mut self, endpoint: SocketAddr, request: &Message) -> CoapCode {
+        let has_unknown_critical_option = request.options.iter().any(|(number, _)| {
+            number % 2 == 1
+                && *number != OPTION_URI_PATH
+                && !IGNORED_CRITICAL_OPTIONS.contains(number)
+        });
+        if has_unknown_critical_option {
+            return CoapCode::BAD_OPTION;
+        }
+        let path = request.path();
+        let resource = self.resources.iter().position(|r| r.path == path);
+        let is_command = self.commands.contains(&path);
+
+        match (request.code, resource) {
+            // GET.
+            (0x01, Some(index)) => {
+                let observers = &mut self.resources[index].observers;
+                let existing = observers.iter().position(|observer| {
+                    observer.endpoint == endpoint && observer.token == request.token
+                });
+                match (request.observe(), existing) {
+                    (Some(0), None) => observers.push(Observer {
+                        endpoint,
+                        token: request.token.clone(),
+                        last_id: request.id,
+                    }),
+                    (Some(1), Some(existing)) | (None, Some(existing)) => {
+                        observers.swap_remove(existing);
+                    }
+                    _ => {}
+                }
+                CoapCode::CONTENT
+            }
+            // POST and PUT.
+            (0x02 | 0x03, _) if is_command => {
+                self.events.push_back(Incoming::Command(CoapData::new(
+                    path,
+                    request.payload.clone(),
+                )));
+                CoapCode::CHANGED
+            }
+            (_, Some(_)) => CoapCode::METHOD_NOT_ALLOWED,
+            (_, None) if is_command => CoapCode::METHOD_NOT_ALLOWED,
+            _ => CoapCode::NOT_FOUND,
+        }
+    }
+
+    /// Handles a response from a remote endpoint and returns the reply, if
+    /// any.
+    fn on_response(&mut self, endpoint: SocketAddr, response: &Message) -> Option<Message> {
+        let Some(index) = self
+            .exchanges
+            .iter()
+            .position(|e| e.endpoint == endpoint && e.token == response.token)
+        else {
+            // Unexpected responses and notifications of cancelled
+            // observations are rejected.
+            return (response.kind != MessageType::Acknowledgement)
+                .then(|| Message::empty(MessageType::Reset, response.id));
+        };
+        let reply = (response.kind == MessageType::Confirmable)
+            .then(|| Message::empty(MessageType::Acknowledgement, response.id));
+
+        let code = CoapCode(response.code);
+        let observe = response.observe();
+        let exchange = &mut self.exchanges[index];
+        let is_observation =
+            exchange.request.observe == Some(0) && code.is_success() && observe.is_some();
+        if let (Some(last), Some(observe)) = (exchange.last_observe, observe) {
+            if !is_fresher(observe, last) {
+                // Reordered notification.
+                return reply;
+            }
+        }
+        let request = if is_observation {
+            exchange.last_observe = observe;
+            exchange.request.clone()
+        } else {
+            let exchange = self.exchanges.swap_remove(index);
+            self.pending
+                .retain(|pending| pending.source != PendingSource::Request(exchange.token.clone()));
+            exchange.request
+        };
+        self.events.push_back(Incoming::Response(CoapResponse {
+            request,
+            result: Ok(CoapContent {
+                code,
+                observe,
+                payload: response.payload.clone(),
+            }),
+        }));
+
+        reply
+    }
+
+    /// Handles the acknowledgement of a confirmable message.
+    fn acknowledge(&mut self, endpoint: SocketAddr, id: u16) {
+        self.pending
+            .retain(|pending| pending.endpoint != endpoint || pending.id != id);
+    }
+
+    /// Handles the reset of a message.
+    fn reset(&mut self, endpoint: SocketAddr, id: u16) {
+        if let Some(index) = self
+            .pending
+            .iter()
+            .position(|pending| pending.endpoint == endpoint && pending.id == id)
+        {
+            let pending = self.pending.swap_remove(index);
+            self.fail(pending.endpoint, pending.source, CoapError::Reset);
+            return;
+        }
+        // Reset of a non-confirmable notification.
+        for resource in &mut self.resources {
+            resource
+                .observers
+                .retain(|observer| observer.endpoint != endpoint || observer.last_id != id);
+        }
+    }
+
+    /// Handles an unacknowledged or reset confirmable message.
+    fn fail(&mut self, endpoint: SocketAddr, source: PendingSource, error: CoapError) {
+        match source {
+            PendingSource::Request(token) => {
+                if let Some(index) = self
+                    .exchanges
+                    .iter()
+                    .position(|e| e.endpoint == endpoint && e.token == token)
+                {
+                    let exchange = self.exchanges.swap_remove(index);
+                    self.events.push_back(Incoming::Response(CoapResponse {
+                        request: exchange.request,
+                        result: Err(error),
+                    }));
+                }
+            }
+            PendingSource::Notification(index) => {
+                self.resources[index]
+                    .observers
+                    .retain(|observer| observer.endpoint != endpoint);
+            }
+        }
+    }
+}
+
+impl IoPort<UdpSocket, Incoming, Outgoing> for CoapBridgeInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        registry.register(&mut self.socket, SOCKET, Interest::READABLE)?;
+        for request in std::mem::take(&mut self.initial_requests) {
+            self.request(request);
+        }
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<Incoming> {
+        if token != SOCKET {
+            // Unknown event: should never happen.
+            return Err(Error::new(ErrorKind::InvalidInput, "Unknown event."));
+        }
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((len, endpoint)) => {
+                    let datagram = self.buffer[..len].to_vec();
+                    self.receive(endpoint, &datagram);
+                }
+                // ICMP errors of previously sent datagrams.
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write(&mut self, data: &Outgoing) -> IoResult<()> {
+        match data {
+            Outgoing::Resource(data) => self.publish(data),
+            Outgoing::Request(request) => self.request(request.clone()),
+        }
+
+        Ok(())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        // Errors of requests sent by the simulation are forwarded at once.
+        if !self.events.is_empty() {
+            return Some(Instant::now());
+        }
+
+        self.pending.iter().map(|pending| pending.deadline).min()
+    }
+
+    fn timeout(&mut self) -> IoResult<Incoming> {
+        let now = Instant::now();
+        let mut index = 0;
+        while index < self.pending.len() {
+            let pending = &mut self.pending[index];
+            if pending.deadline > now {
+                index += 1;
+            } else if pending.retransmissions < self.max_retransmit {
+                pending.retransmissions += 1;
+                pending.timeout *= 2;
+                pending.deadline = now + pending.timeout;
+                let (endpoint, packet) = (pending.endpoint, pending.packet.clone());
+                self.send(endpoint, &packet);
+                index += 1;
+            } else {
+                let pending = self.pending.swap_remove(index);
+                self.fail(pending.endpoint, pending.source, CoapError::Timeout);
+            }
+        }
+
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+}
+
+/// Resolves a socket address.
+fn resolve(address: &str) -> IoResult<SocketAddr> {
+    address.to_socket_addrs()?.next().ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Cannot resolve address {address}."),
+        )
+    })
+}
+
+/// CoAP bridge model.
+///
+/// This model:
+/// * serves the payloads sent to the model input to the clients reading or
+///   observing its resources,
+/// * forwards the payloads put or posted to its command resources to the model
+///   output,
+/// * sends the requests of the model input to remote endpoints and forwards
+///   their responses and notifications to the model output,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct CoapBridge {
+    /// Payload put or posted to a command resource -- output port.
+    pub command_out: Output<CoapData>,
+
+    /// Response or notification from a remote endpoint -- output port.
+    pub response_out: Output<CoapResponse>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: CoapBridgeConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<Incoming, Outgoing>,
+}
+
+impl CoapBridge {
+    /// Publishes the payload of a served resource and notifies its observers
+    /// -- input port.
+    ///
+    /// Payloads for a resource which is not declared in the configuration are
+    /// discarded.
+    pub async fn resource_in(&mut self, data: CoapData) {
+        let _ = self.io_thread.send(Outgoing::Resource(data));
+    }
+
+    /// Sends a request to a remote endpoint -- input port.
+    pub async fn request_in(&mut self, request: CoapRequest) {
+        let _ = self.io_thread.send(Outgoing::Request(request));
+    }
+
+    /// Forwards the commands, the responses and the I/O thread status -- input
+    /// port.
+    pub async fn process(&mut self) {
+        for event in self.io_thread.try_recv_all() {
+            match event {
+                Incoming::Command(data) => self.command_out.send(data).await,
+                Incoming::Response(response) => self.response_out.send(response).await,
+            }
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for CoapBridge {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for CoapBridge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CoapBridge")
+            .field("local_address", &self.config.local_address)
+            .finish_non_exhaustive()
+    }
+}
+
+/// CoAP bridge model prototype.
+pub struct ProtoCoapBridge {
+    /// Payload put or posted to a command resource -- output port.
+    pub command_out: Output<CoapData>,
+
+    /// Response or notification from a remote endpoint -- output port.
+    pub response_out: Output<CoapResponse>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// CoAP bridge model instance configuration.
+    config: CoapBridgeConfig,
+}
+
+impl ProtoCoapBridge {
+    /// Creates a new CoAP bridge model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the local address cannot be bound or if
+    /// the I/O thread cannot be created.
+    pub fn new(config: CoapBridgeConfig) -> Self {
+        Self {
+            command_out: Output::new(),
+            response_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoCoapBridge {
+    type Model = CoapBridge;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let port = CoapBridgeInner::new(&self.config).unwrap_or_else(|e| {
+            panic!(
+                "Failed to bind the CoAP bridge to {}: {e}.",
+                self.config.local_address
+            )
+        });
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(port, options).unwrap_or_else(|e| {
+            panic!(
+                "Failed to start the I/O thread of the CoAP bridge on {}: {e}.",
+                self.config.local_address
+            )
+        });
+
+        CoapBridge {
+            command_out: self.command_out,
+            response_out: self.response_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoCoapBridge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoCoapBridge")
+            .field("local_address", &self.config.local_address)
+            .finish_non_exhaustive()
+    }
+}
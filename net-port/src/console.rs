@@ -0,0 +1,843 @@
+//! Interactive console model.
+//!
+//! This module contains the [`Console`] model, which accepts Telnet or raw TCP
+//! connections from operators:
+//! * each line entered in a session is forwarded into the simulation as a
+//!   [`ConsoleCommand`], split into a command name and its arguments,
+//! * text sent by the simulation is written either to all sessions or to a
+//!   single session, e.g. in reply to a command.
+//!
+//! With the Telnet mode, option negotiations are declined so that clients stay
+//! in their default line mode with local echo, and the Telnet commands are
+//! removed from the input. With the raw mode, the input is used as-is, which
+//! suits clients such as `nc`.
+//!
+//! Arguments are separated by whitespace, and may be enclosed in double quotes
+//! to contain whitespace. Empty lines are ignored, and end of transmission
+//! (`Ctrl-D`) on an empty line closes the session.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::console::{ConsoleConfig, ConsoleMode};
+//!
+//! let config = ConfigLoader::<ConsoleConfig>::new()
+//!     .code(
+//!         r#"
+//! address = "127.0.0.1:2323"
+//! banner = "Bench console"
+//! prompt = "bench> "
+//! period = 50
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.mode, ConsoleMode::Telnet);
+//! assert_eq!(config.max_sessions, 8);
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Error, ErrorKind, Read, Result as IoResult, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use schematic::{Config, ConfigEnum};
+use serde::{Deserialize, Serialize};
+
+use mio::net::{TcpListener, TcpStream};
+use mio::{Interest, Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus, WriteBuffer};
+
+/// Listener token.
+const LISTENER: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// Token of the first session.
+const FIRST_SESSION: usize = 2;
+
+/// Telnet "interpret as command" byte.
+const IAC: u8 = 255;
+
+/// Telnet `DONT` command.
+const DONT: u8 = 254;
+
+/// Telnet `DO` command.
+const DO: u8 = 253;
+
+/// Telnet `WONT` command.
+const WONT: u8 = 252;
+
+/// Telnet `WILL` command.
+const WILL: u8 = 251;
+
+/// Telnet subnegotiation begin command.
+const SB: u8 = 250;
+
+/// Telnet subnegotiation end command.
+const SE: u8 = 240;
+
+/// Telnet interrupt process command.
+const IP: u8 = 244;
+
+/// Console model instance configuration.
+#[derive(Config, Debug)]
+pub struct ConsoleConfig {
+    /// Local address to listen on, as `HOST:PORT`.
+    #[setting(default = "0.0.0.0:2323")]
+    pub address: String,
+
+    /// Protocol of the sessions.
+    pub mode: ConsoleMode,
+
+    /// Text written when a session is opened.
+    pub banner: Option<String>,
+
+    /// Prompt written when a session is opened and after each line entered.
+    pub prompt: Option<String>,
+
+    /// Maximum number of simultaneous sessions.
+    ///
+    /// Connections beyond this number are closed immediately.
+    #[setting(default = 8)]
+    pub max_sessions: usize,
+
+    /// Maximum length of a line, in bytes.
+    ///
+    /// Input beyond this length is discarded until the end of the line.
+    #[setting(default = 1024)]
+    pub max_line_length: usize,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which commands are forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// Protocol of the console sessions.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConsoleMode {
+    /// Telnet protocol.
+    #[default]
+    Telnet,
+
+    /// Raw TCP.
+    Raw,
+}
+
+/// Command entered in a console session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConsoleCommand {
+    /// Session identifier.
+    pub session: u64,
+
+    /// Command name, i.e. the first word of the line.
+    pub name: String,
+
+    /// Command arguments.
+    pub args: Vec<String>,
+}
+
+/// Text written to a single console session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConsoleReply {
+    /// Session identifier.
+    pub session: u64,
+
+    /// Text, a line ending being appended if missing.
+    pub text: String,
+}
+
+/// Opening or closing of a console session.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConsoleSession {
+    /// Session identifier.
+    pub session: u64,
+
+    /// Address of the operator.
+    pub peer: SocketAddr,
+
+    /// The session opened, or closed otherwise.
+    pub is_open: bool,
+}
+
+/// Event read from the sessions.
+enum ConsoleEvent {
+    /// Entered command.
+    Command(ConsoleCommand),
+
+    /// Opened or closed session.
+    Session(ConsoleSession),
+}
+
+/// Request to the sessions.
+enum ConsoleRequest {
+    /// Text to write to a session, or to all sessions if `None`.
+    Print(Option<u64>, String),
+
+    /// Session to close.
+    Close(u64),
+}
+
+/// Telnet decoder state.
+#[derive(Clone, Copy)]
+enum TelnetState {
+    /// Data bytes.
+    Data,
+
+    /// Command byte expected.
+    Command,
+
+    /// Option byte of a negotiation command expected.
+    Option(u8),
+
+    /// Subnegotiation bytes.
+    Subnegotiation,
+
+    /// Command byte within a subnegotiation expected.
+    SubnegotiationCommand,
+}
+
+/// Console session.
+struct Session {
+    /// Session identifier.
+    id: u64,
+
+    /// Address of the operator.
+    peer: SocketAddr,
+
+    /// Connection.
+    stream: TcpStream,
+
+    /// Bytes not yet written.
+    tx_buf: WriteBuffer,
+
+    /// Telnet decoder state.
+    state: TelnetState,
+
+    /// Current line.
+    line: Vec<u8>,
+
+    /// The previous byte ended a line with a carriage return.
+    is_after_cr: bool,
+}
+
+/// Console port.
+struct ConsoleInner {
+    /// Local address to listen on.
+    address: String,
+
+    /// Protocol of the sessions.
+    mode: ConsoleMode,
+
+    /// Text written when a session is opened.
+    banner: Option<String>,
+
+    /// Prompt.
+    prompt: Option<String>,
+
+    /// Maximum number of simultaneous sessions.
+    max_sessions: usize,
+
+    /// Maximum length of a line.
+    max_line_length: usize,
+
+    /// Listener, once registered.
+    listener: Option<TcpListener>,
+
+    /// Sessions, indexed by token.
+    sessions: Vec<Option<Session>>,
+
+    /// Identifier of the next session.
+    next_id: u64,
+
+    /// Decoded events not yet read.
+    events: VecDeque<ConsoleEvent>,
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+
+    /// MIO registry, available once the port is registered.
+    registry: Option<Registry>,
+}
+
+impl ConsoleInner {
+    /// Creates a console port, listening once registered.
+    fn new(config: &ConsoleConfig) -> Self {
+        Self {
+            address: config.address.clone(),
+            mode: config.mode,
+            banner: config.banner.clone(),
+            prompt: config.prompt.clone(),
+            max_sessions: config.max_sessions,
+            max_line_length: config.max_line_length,
+            listener: None,
+            sessions: Vec::new(),
+            next_id: 0,
+            events: VecDeque::new(),
+            buffer: vec![0; 4096],
+            registry: None,
+        }
+    }
+
+    /// Accepts the pending connections.
+    fn accept(&mut self) -> IoResult<()> {
+        loop {
+            let Some(listener) = &self.listener else {
+                return Ok(());
+            };
+            let (mut stream, peer) = match listener.accept() {
+                Ok(connection) => connection,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::Interrupted | ErrorKind::ConnectionAborted
+                    ) =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if self.sessions.iter().flatten().count() >= self.max_sessions {
+                let _ = stream.write(b"Too many sessions.\r\n");
+                continue;
+            }
+            let index = match self.sessions.iter().position(Option::is_none) {
+                Some(index) => index,
+                None => {
+                    self.sessions.push(None);
+                    self.sessions.len() - 1
+                }
+            };
+            if let Some(registry) = &self.registry {
+                if registry
+                    .register(
+                        &mut stream,
+                        Token(FIRST_SESSION + index),
+                        Interest::READABLE | Interest::WRITABLE,
+                    )
+                    .is_err()
+                {
+                    continue;
+                }
+            }
+            let id = self.next_id;
+            self.next_id += 1;
+            self.sessions[index] = Some(Session {
+                id,
+                peer,
+                stream,
+                tx_buf: WriteBuffer::new(),
+                state: TelnetState::Data,
+                line: Vec::new(),
+                is_after_cr: false,
+            });
+            self.events.push_back(ConsoleEvent::Session(ConsoleSession {
+                session: id,
+                peer,
+                is_open: true,
+            }));
+            if let Some(banner) = &self.banner {
+                let banner = to_lines(banner);
+                self.send(index, banner.as_bytes());
+            }
+            self.send_prompt(index);
+            // Commands may have been received with the connection.
+            self.receive(index);
+        }
+    }
+
+    /// Reads and decodes the input of a session.
+    fn receive(&mut self, index: usize) {
+        loop {
+            let Some(session) = self.sessions.get_mut(index).and_then(Option::as_mut) else {
+                return;
+            };
+            let len = match session.stream.read(&mut self.buffer) {
+                Ok(0) => {
+                    self.close(index);
+                    return;
+                }
+                Ok(len) => len,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.close(index);
+                    return;
+                }
+            };
+            for i in 0..len {
+                let byte = self.buffer[i];
+                if !self.decode(index, byte) {
+                    self.close(index);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Decodes a byte of a session, returning `false` if the session is to
+    /// be closed.
+    fn decode(&mut self, index: usize, byte: u8) -> bool {
+        let Some(session) = self.sessions[index].as_mut() else {
+            return false;
+        };
+        match session.state {
+            TelnetState::Data if byte == IAC && self.mode == ConsoleMode::Telnet => {
+                session.state = TelnetState::Command;
+                return true;
+            }
+            TelnetState::Data => {}
+            TelnetState::Command => {
+                session.state = TelnetState::Data;
+                match byte {
+                    // Escaped data byte, handled below.
+                    IAC => {}
+                    WILL..=DONT => {
+                        session.state = TelnetState::Option(byte);
+                        return true;
+                    }
+                    SB => {
+                        session.state = TelnetState::Subnegotiation;
+                        return true;
+                    }
+                    IP => {
+                        session.line.clear();
+                        return true;
+                    }
+                    _ => return true,
+                }
+            }
+            TelnetState::Option(command) => {
+                session.state = TelnetState::Data;
+                // All options are declined, and refusals need no answer.
+                let answer = match command {
+                    DO => WONT,
+                    WILL => DONT,
+                    _ => return true,
+                };
+                self.send(index, &[IAC, answer, byte]);
+                return true;
+            }
+            TelnetState::Subnegotiation => {
+                if byte == IAC {
+                    session.state = TelnetState::SubnegotiationCommand;
+                }
+                return true;
+            }
+            TelnetState::SubnegotiationCommand => {
+                session.state = if byte == SE {
+                    TelnetState::Data
+                } else {
+                    TelnetState::Subnegotiation
+                };
+                return true;
+            }
+        }
+
+        let is_after_cr = std::mem::take(&mut session.is_after_cr);
+        match byte {
+            b'\r' => {
+                session.is_after_cr = true;
+                self.end_line(index);
+            }
+            // Line feed or null byte of a CR LF or CR NUL line ending.
+            b'\n' | 0 if is_after_cr => {}
+            b'\n' => self.end_line(index),
+            // Backspace or delete removes the last character.
+            0x08 | 0x7f => {
+                while let Some(byte) = session.line.pop() {
+                    if byte & 0xc0 != 0x80 {
+                        break;
+                    }
+                }
+            }
+            // End of transmission.
+            0x04 => return !session.line.is_empty(),
+            byte if byte < 0x20 && byte != b'\t' => {}
+            byte => {
+                if session.line.len() < self.max_line_length {
+                    session.line.push(byte);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Forwards the current line of a session as a command.
+    fn end_line(&mut self, index: usize) {
+        let Some(session) = self.sessions[index].as_mut() else {
+            return;
+        };
+        let line = std::mem::take(&mut session.line);
+        let mut words = split(&String::from_utf8_lossy(&line)).into_iter();
+        if let Some(name) = words.next() {
+            self.events.push_back(ConsoleEvent::Command(ConsoleCommand {
+                session: session.id,
+                name,
+                args: words.collect(),
+            }));
+        }
+        self.send_prompt(index);
+    }
+
+    /// Writes the prompt to a session, if any.
+    fn send_prompt(&mut self, index: usize) {
+        if let Some(prompt) = &self.prompt {
+            let prompt = prompt.clone();
+            self.send(index, prompt.as_bytes());
+        }
+    }
+
+    /// Writes bytes to a session, closing it on error.
+    fn send(&mut self, index: usize, data: &[u8]) {
+        let Some(session) = self.sessions[index].as_mut() else {
+            return;
+        };
+        if session.tx_buf.write(&mut session.stream, data).is_err() {
+            self.close(index);
+        }
+    }
+
+    /// Writes the pending bytes of a session.
+    fn resume_write(&mut self, index: usize) {
+        let Some(session) = self.sessions.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        if session.tx_buf.flush(&mut session.stream).is_err() {
+            self.close(index);
+        }
+    }
+
+    /// Closes a session.
+    fn close(&mut self, index: usize) {
+        let Some(mut session) = self.sessions[index].take() else {
+            return;
+        };
+        if let Some(registry) = &self.registry {
+            let _ = registry.deregister(&mut session.stream);
+        }
+        self.events.push_back(ConsoleEvent::Session(ConsoleSession {
+            session: session.id,
+            peer: session.peer,
+            is_open: false,
+        }));
+    }
+
+    /// Returns the index of a session.
+    fn find(&self, id: u64) -> Option<usize> {
+        self.sessions
+            .iter()
+            .position(|session| session.as_ref().is_some_and(|session| session.id == id))
+    }
+}
+
+impl IoPort<TcpListener, ConsoleEvent, ConsoleRequest> for ConsoleInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        let address = self.address.to_socket_addrs()?.next().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Cannot resolve address {}.", self.address),
+            )
+        })?;
+        let mut listener = TcpListener::bind(address)?;
+        registry.register(&mut listener, LISTENER, Interest::READABLE)?;
+        self.listener = Some(listener);
+        self.registry = Some(registry.try_clone()?);
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<ConsoleEvent> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            if token == LISTENER {
+                self.accept()?;
+            } else if let Some(index) = token.0.checked_sub(FIRST_SESSION) {
+                self.receive(index);
+            }
+            if self.events.is_empty() {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+        }
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        if let Some(index) = token.0.checked_sub(FIRST_SESSION) {
+            self.resume_write(index);
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, request: &ConsoleRequest) -> IoResult<()> {
+        match request {
+            ConsoleRequest::Print(Some(id), text) => {
+                if let Some(index) = self.find(*id) {
+                    self.send(index, to_lines(text).as_bytes());
+                }
+            }
+            ConsoleRequest::Print(None, text) => {
+                let text = to_lines(text);
+                for index in 0..self.sessions.len() {
+                    self.send(index, text.as_bytes());
+                }
+            }
+            ConsoleRequest::Close(id) => {
+                if let Some(index) = self.find(*id) {
+                    self.resume_write(index);
+                    self.close(index);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        // Events queued outside of a read, e.g. a session closed on write,
+        // are forwarded immediately.
+        (!self.events.is_empty()).then(Instant::now)
+    }
+
+    fn timeout(&mut self) -> IoResult<ConsoleEvent> {
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        self.sessions
+            .iter()
+            .flatten()
+            .any(|session| !session.tx_buf.is_empty())
+    }
+}
+
+/// Converts text to network line endings, terminating its last line.
+fn to_lines(text: &str) -> String {
+    let mut lines = text.lines().collect::<Vec<_>>().join("\r\n");
+    lines.push_str("\r\n");
+
+    lines
+}
+
+/// Splits a line into whitespace-separated words, double quotes enclosing
+/// words with whitespace.
+fn split(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut is_quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                is_quoted = !is_quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !is_quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+
+    words
+}
+
+/// Interactive console model.
+///
+/// This model:
+/// * forwards the commands entered in the console sessions,
+/// * writes text to all sessions or to a single session,
+/// * reports the opening and closing of the sessions,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct Console {
+    /// Entered command -- output port.
+    pub command_out: Output<ConsoleCommand>,
+
+    /// Opened or closed session -- output port.
+    pub session_out: Output<ConsoleSession>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: ConsoleConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<ConsoleEvent, ConsoleRequest>,
+}
+
+impl Console {
+    /// Text for all sessions -- input port.
+    pub async fn print_in(&mut self, text: String) {
+        let _ = self.io_thread.send(ConsoleRequest::Print(None, text));
+    }
+
+    /// Text for a single session -- input port.
+    pub async fn reply_in(&mut self, reply: ConsoleReply) {
+        let _ = self
+            .io_thread
+            .send(ConsoleRequest::Print(Some(reply.session), reply.text));
+    }
+
+    /// Session to close -- input port.
+    pub async fn close_in(&mut self, session: u64) {
+        let _ = self.io_thread.send(ConsoleRequest::Close(session));
+    }
+
+    /// Forwards the commands, the session events and the I/O thread status
+    /// -- input port.
+    pub async fn process(&mut self) {
+        for event in self.io_thread.try_recv_all() {
+            match event {
+                ConsoleEvent::Command(command) => self.command_out.send(command).await,
+                ConsoleEvent::Session(session) => self.session_out.send(session).await,
+            }
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for Console {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for Console {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Console")
+            .field("address", &self.config.address)
+            .field("mode", &self.config.mode)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Interactive console model prototype.
+pub struct ProtoConsole {
+    /// Entered command -- output port.
+    pub command_out: Output<ConsoleCommand>,
+
+    /// Opened or closed session -- output port.
+    pub session_out: Output<ConsoleSession>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Console model instance configuration.
+    config: ConsoleConfig,
+}
+
+impl ProtoConsole {
+    /// Creates a new console model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the address cannot be listened on or if
+    /// the I/O thread cannot be created.
+    pub fn new(config: ConsoleConfig) -> Self {
+        Self {
+            command_out: Output::new(),
+            session_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoConsole {
+    type Model = Console;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(ConsoleInner::new(&self.config), options)
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Failed to start the I/O thread of the console on {}: {e}.",
+                    self.config.address
+                )
+            });
+
+        Console {
+            command_out: self.command_out,
+            session_out: self.session_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoConsole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoConsole")
+            .field("address", &self.config.address)
+            .field("mode", &self.config.mode)
+            .finish_non_exhaustive()
+    }
+}
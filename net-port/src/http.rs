@@ -0,0 +1,763 @@
+//! HTTP bridge model.
+//!
+//! This module contains the [`HttpBridge`] model, a small HTTP server which
+//! lets operators inject data into a running bench and observe its outputs
+//! from scripts or `curl`. It is available with the `http` feature.
+//!
+//! Data is exchanged on named channels, declared in the configuration:
+//!
+//! * `POST /inputs/{channel}` forwards the request body to the model output,
+//!   tagged with the channel name, and returns `204 No Content`; the model
+//!   output can be routed to the inputs of other models by channel with
+//!   filtered connections,
+//! * `GET /outputs/{channel}` returns the last data sent to the model input
+//!   for this channel, or `204 No Content` if no data was sent yet,
+//! * `GET /outputs/{channel}?after={sequence}` returns the oldest retained
+//!   data more recent than the provided sequence number, waiting up to
+//!   `long_poll_timeout` for new data, after which `204 No Content` is
+//!   returned.
+//!
+//! The data returned for an output channel is numbered from 1 with the
+//! `X-Sequence` header; the `history` last data of each channel are retained,
+//! so that a client polling in a loop with the last received sequence number
+//! does not miss data unless it falls behind by more than `history`.
+//!
+//! Connections are closed after each response.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::http::HttpBridgeConfig;
+//!
+//! let config = ConfigLoader::<HttpBridgeConfig>::new()
+//!     .code(
+//!         r#"
+//! listenAddress = "127.0.0.1:8000"
+//! inputs = ["command"]
+//! outputs = ["telemetry", "events"]
+//! period = 10
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.history, 16);
+//! ```
+//!
+//! With this configuration, `curl --data-binary @cmd.bin
+//! http://127.0.0.1:8000/inputs/command` injects a command and `curl
+//! http://127.0.0.1:8000/outputs/telemetry?after=41` waits for the telemetry
+//! following the 41st one.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Error, ErrorKind, Read, Result as IoResult};
+use std::net::ToSocketAddrs;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use schematic::Config;
+
+use mio::net::{TcpListener, TcpStream};
+use mio::{Interest, Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus, WriteBuffer};
+
+/// HTTP bridge model instance configuration.
+#[derive(Config, Debug)]
+pub struct HttpBridgeConfig {
+    /// Local address, as `HOST:PORT`, to listen on.
+    pub listen_address: String,
+
+    /// Names of the channels data can be posted to.
+    pub inputs: Vec<String>,
+
+    /// Names of the channels data can be read from.
+    pub outputs: Vec<String>,
+
+    /// Number of data retained for each output channel.
+    #[setting(default = 16)]
+    pub history: usize,
+
+    /// Maximum time a long-polling request waits for new data.
+    #[setting(default = ConfigDuration::from_millis(30000))]
+    pub long_poll_timeout: ConfigDuration,
+
+    /// Maximum size of a request, including its headers, in bytes.
+    #[setting(default = 65536)]
+    pub max_request_size: usize,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which posted data is forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// Data tagged with the name of its channel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpData {
+    /// Channel name.
+    pub channel: String,
+
+    /// Raw bytes.
+    pub bytes: Bytes,
+}
+
+impl HttpData {
+    /// Creates new tagged data.
+    pub fn new(channel: impl Into<String>, bytes: Bytes) -> Self {
+        Self {
+            channel: channel.into(),
+            bytes,
+        }
+    }
+}
+
+/// Maximum number of request headers.
+const MAX_HEADERS: usize = 32;
+
+/// Listener token.
+const LISTENER: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// Token of the first client.
+const FIRST_CLIENT: usize = 2;
+
+/// Output channel.
+struct Channel {
+    /// Channel name.
+    name: String,
+
+    /// Sequence number of the last data.
+    sequence: u64,
+
+    /// Retained data, with their sequence numbers.
+    history: VecDeque<(u64, Bytes)>,
+}
+
+/// Long-polling request.
+struct Poll {
+    /// Output channel index.
+    channel: usize,
+
+    /// Sequence number of the last data received by the client.
+    after: u64,
+
+    /// Time at which the request is answered without data.
+    deadline: Instant,
+}
+
+/// Client connection.
+struct Client {
+    /// Connection.
+    stream: TcpStream,
+
+    /// Received request bytes.
+    request: Vec<u8>,
+
+    /// Pending long-polling request, if any.
+    poll: Option<Poll>,
+
+    /// Response bytes not yet written.
+    response: WriteBuffer,
+
+    /// A response has been queued, the connection is closed once it is
+    /// written.
+    is_answered: bool,
+}
+
+/// HTTP response.
+struct Response {
+    /// Status code and reason phrase.
+    status: &'static str,
+
+    /// Sequence number of the returned data, if any.
+    sequence: Option<u64>,
+
+    /// Response body.
+    body: Bytes,
+}
+
+impl Response {
+    /// Creates a response without body.
+    fn empty(status: &'static str) -> Self {
+        Self {
+            status,
+            sequence: None,
+            body: Bytes::new(),
+        }
+    }
+
+    /// Creates a response with the numbered data of an output channel.
+    fn data(sequence: u64, body: Bytes) -> Self {
+        Self {
+            status: "200 OK",
+            sequence: Some(sequence),
+            body,
+        }
+    }
+
+    /// Serializes the response.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut head = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            self.body.len()
+        );
+        if let Some(sequence) = self.sequence {
+            head.push_str("Content-Type: application/octet-stream\r\n");
+            head.push_str(&format!("X-Sequence: {sequence}\r\n"));
+        }
+        head.push_str("\r\n");
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(&self.body);
+
+        bytes
+    }
+}
+
+/// HTTP bridge port.
+struct HttpBridgeInner {
+    /// Local address to listen on.
+    address: String,
+
+    /// Listener, once registered.
+    listener: Option<TcpListener>,
+
+    /// Clients, indexed by token.
+    clients: Vec<Option<Client>>,
+
+    /// Input channel names.
+    inputs: Vec<String>,
+
+    /// Output channels.
+    outputs: Vec<Channel>,
+
+    /// Number of data retained for each output channel.
+    history: usize,
+
+    /// Maximum long-polling time.
+    long_poll_timeout: Duration,
+
+    /// Maximum request size.
+    max_request_size: usize,
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+
+    /// Posted data not yet read.
+    events: VecDeque<HttpData>,
+
+    /// MIO registry, available once the port is registered.
+    registry: Option<Registry>,
+}
+
+impl HttpBridgeInner {
+    /// Creates an HTTP bridge port, listening once registered.
+    fn new(config: &HttpBridgeConfig) -> Self {
+        Self {
+            address: config.listen_address.clone(),
+            listener: None,
+            clients: Vec::new(),
+            inputs: config.inputs.clone(),
+            outputs: config
+                .outputs
+                .iter()
+                .map(|name| Channel {
+                    name: name.clone(),
+                    sequence: 0,
+                    history: VecDeque::new(),
+                })
+                .collect(),
+            history: config.history.max(1),
+            long_poll_timeout: Duration::from(config.long_poll_timeout),
+            max_request_size: config.max_request_size,
+            buffer: vec![0; 4096],
+            events: VecDeque::new(),
+            registry: None,
+        }
+    }
+
+    /// Accepts the pending client connections.
+    fn accept(&mut self) -> IoResult<()> {
+        loop {
+            let Some(listener) = &self.listener else {
+                return Ok(());
+            };
+            let mut stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::Interrupted | ErrorKind::ConnectionAborted
+                    ) =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let index = match self.clients.iter().position(Option::is_none) {
+                Some(index) => index,
+                None => {
+                    self.clients.push(None);
+                    self.clients.len() - 1
+                }
+            };
+            if let Some(registry) = &self.registry {
+                if registry
+                    .register(
+                        &mut stream,
+                        Token(FIRST_CLIENT + index),
+                        Interest::READABLE | Interest::WRITABLE,
+                    )
+                    .is_err()
+                {
+                    continue;
+                }
+            }
+            self.clients[index] = Some(Client {
+                stream,
+                request: Vec::new(),
+                poll: None,
+                response: WriteBuffer::new(),
+                is_answered: false,
+            });
+            // The request may have been received with the connection.
+            self.receive(index);
+        }
+    }
+
+    /// Reads the request of a client and handles it once complete.
+    fn receive(&mut self, index: usize) {
+        let Some(client) = self.clients.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        let mut is_eof = false;
+        loop {
+            match client.stream.read(&mut self.buffer) {
+                // The client may only have shut down its writing side after
+                // sending its request.
+                Ok(0) => {
+                    if client.request.is_empty() || client.is_answered || client.poll.is_some() {
+                        self.clients[index] = None;
+                        return;
+                    }
+                    is_eof = true;
+                    break;
+                }
+                Ok(len) => client.request.extend_from_slice(&self.buffer[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.clients[index] = None;
+                    return;
+                }
+            }
+        }
+        if client.is_answered || client.poll.is_some() {
+            return;
+        }
+        if client.request.len() > self.max_request_size {
+            self.respond(index, Response::empty("413 Payload Too Large"));
+            return;
+        }
+        let request = std::mem::take(&mut client.request);
+        match self.handle(index, &request) {
+            Some(Some(response)) => self.respond(index, response),
+            Some(None) => {}
+            // Incomplete request.
+            None if is_eof => self.clients[index] = None,
+            None => {
+                if let Some(client) = &mut self.clients[index] {
+                    client.request = request;
+                }
+            }
+        }
+    }
+
+    /// Handles a request.
+    ///
+    /// Returns `None` if the request is incomplete, or the response unless the
+    /// client waits for new data.
+    fn handle(&mut self, index: usize, request: &[u8]) -> Option<Option<Response>> {
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut parsed = httparse::Request::new(&mut headers);
+        let head_len = match parsed.parse(request) {
+            Ok(httparse::Status::Complete(len)) => len,
+            Ok(httparse::Status::Partial) => return None,
+            Err(_) => return Some(Some(Response::empty("400 Bad Request"))),
+        };
+        let mut content_length = 0;
+        for header in parsed.headers.iter() {
+            if header.name.eq_ignore_ascii_case("content-length") {
+                match std::str::from_utf8(header.value)
+                    .ok()
+                    .and_then(|value| value.trim().parse::<usize>().ok())
+                {
+                    Some(len) => content_length = len,
+                    None => return Some(Some(Response::empty("400 Bad Request"))),
+                }
+            } else if header.name.eq_ignore_ascii_case("transfer-encoding") {
+                return Some(Some(Response::empty("411 Length Required")));
+            }
+        }
+        if head_len + content_length > self.max_request_size {
+            return Some(Some(Response::empty("413 Payload Too Large")));
+        }
+        if request.len() < head_len + content_length {
+            return None;
+        }
+        let body = &request[head_len..head_len + content_length];
+        let (path, query) = match parsed.path.unwrap_or_default().split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (parsed.path.unwrap_or_default(), None),
+        };
+        let method = parsed.method.unwrap_or_default();
+
+        if let Some(channel) = path.strip_prefix("/inputs/") {
+            if !self.inputs.iter().any(|name| name == channel) {
+                return Some(Some(Response::empty("404 Not Found")));
+            }
+            if method != "POST" {
+                return Some(Some(Response::empty("405 Method Not Allowed")));
+            }
+            self.events
+                .push_back(HttpData::new(channel, Bytes::copy_from_slice(body)));
+
+            return Some(Some(Response::empty("204 No Content")));
+        }
+        if let Some(channel) = path.strip_prefix("/outputs/") {
+            let Some(channel) = self.outputs.iter().position(|c| c.name == channel) else {
+                return Some(Some(Response::empty("404 Not Found")));
+            };
+            if method != "GET" {
+                return Some(Some(Response::empty("405 Method Not Allowed")));
+            }
+            let after = match query.map(parse_after) {
+                None => None,
+                Some(Some(after)) => Some(after),
+                Some(None) => return Some(Some(Response::empty("400 Bad Request"))),
+            };
+            let output = &self.outputs[channel];
+            let Some(after) = after else {
+                return Some(Some(match output.history.back() {
+                    Some((sequence, data)) => Response::data(*sequence, data.clone()),
+                    None => Response::empty("204 No Content"),
+                }));
+            };
+            if let Some((sequence, data)) = output.history.iter().find(|(s, _)| *s > after) {
+                return Some(Some(Response::data(*sequence, data.clone())));
+            }
+            if let Some(client) = &mut self.clients[index] {
+                client.poll = Some(Poll {
+                    channel,
+                    after,
+                    deadline: Instant::now() + self.long_poll_timeout,
+                });
+            }
+
+            return Some(None);
+        }
+
+        Some(Some(Response::empty("404 Not Found")))
+    }
+
+    /// Queues the response to a client and writes it.
+    fn respond(&mut self, index: usize, response: Response) {
+        let Some(client) = self.clients.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        client.poll = None;
+        client.is_answered = true;
+        client.response.push(&response.to_bytes());
+        self.resume_write(index);
+    }
+
+    /// Writes the pending response of a client, closing the connection once
+    /// it is written.
+    fn resume_write(&mut self, index: usize) {
+        let Some(client) = self.clients.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        if !client.is_answered {
+            return;
+        }
+        match client.response.flush(&mut client.stream) {
+            Ok(false) => {}
+            Ok(true) | Err(_) => {
+                let mut client = self.clients[index].take().unwrap();
+                if let Some(registry) = &self.registry {
+                    let _ = registry.deregister(&mut client.stream);
+                }
+            }
+        }
+    }
+}
+
+impl IoPort<TcpListener, HttpData, HttpData> for HttpBridgeInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        let address = self.address.to_socket_addrs()?.next().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Cannot resolve address {}.", self.address),
+            )
+        })?;
+        let mut listener = TcpListener::bind(address)?;
+        registry.register(&mut listener, LISTENER, Interest::READABLE)?;
+        self.listener = Some(listener);
+        self.registry = Some(registry.try_clone()?);
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<HttpData> {
+        if self.events.is_empty() {
+            if token == LISTENER {
+                self.accept()?;
+            } else if let Some(index) = token.0.checked_sub(FIRST_CLIENT) {
+                self.receive(index);
+            }
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        if let Some(index) = token.0.checked_sub(FIRST_CLIENT) {
+            self.resume_write(index);
+        }
+
+        Ok(())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        self.clients
+            .iter()
+            .flatten()
+            .filter_map(|client| client.poll.as_ref().map(|poll| poll.deadline))
+            .min()
+    }
+
+    fn timeout(&mut self) -> IoResult<HttpData> {
+        let now = Instant::now();
+        for index in 0..self.clients.len() {
+            let is_expired = self.clients[index]
+                .as_ref()
+                .and_then(|client| client.poll.as_ref())
+                .is_some_and(|poll| poll.deadline <= now);
+            if is_expired {
+                self.respond(index, Response::empty("204 No Content"));
+            }
+        }
+
+        Err(ErrorKind::WouldBlock.into())
+    }
+
+    fn write(&mut self, data: &HttpData) -> IoResult<()> {
+        // Data for undeclared channels is discarded.
+        let Some(channel) = self.outputs.iter().position(|c| c.name == data.channel) else {
+            return Ok(());
+        };
+        let output = &mut self.outputs[channel];
+        output.sequence += 1;
+        if output.history.len() == self.history {
+            output.history.pop_front();
+        }
+        output
+            .history
+            .push_back((output.sequence, data.bytes.clone()));
+        let sequence = output.sequence;
+
+        for index in 0..self.clients.len() {
+            let is_waiting = self.clients[index]
+                .as_ref()
+                .and_then(|client| client.poll.as_ref())
+                .is_some_and(|poll| poll.channel == channel && poll.after < sequence);
+            if is_waiting {
+                self.respond(index, Response::data(sequence, data.bytes.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the `after` parameter of a query string.
+fn parse_after(query: &str) -> Option<u64> {
+    query
+        .split('&')
+        .find_map(|parameter| parameter.strip_prefix("after="))
+        .and_then(|after| after.parse().ok())
+}
+
+/// HTTP bridge model.
+///
+/// This model:
+/// * forwards the data posted to its input channels to the model output,
+/// * serves the data sent to the model input to the clients polling its output
+///   channels,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct HttpBridge {
+    /// Data posted to an input channel -- output port.
+    pub data_out: Output<HttpData>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: HttpBridgeConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<HttpData, HttpData>,
+}
+
+impl HttpBridge {
+    /// Publishes data on an output channel -- input port.
+    ///
+    /// Data for a channel which is not declared in the configuration is
+    /// discarded.
+    pub async fn data_in(&mut self, data: HttpData) {
+        let _ = self.io_thread.send(data);
+    }
+
+    /// Forwards the posted data and the I/O thread status -- input port.
+    pub async fn process(&mut self) {
+        for data in self.io_thread.try_recv_all() {
+            self.data_out.send(data).await;
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for HttpBridge {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for HttpBridge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpBridge")
+            .field("listen_address", &self.config.listen_address)
+            .finish_non_exhaustive()
+    }
+}
+
+/// HTTP bridge model prototype.
+pub struct ProtoHttpBridge {
+    /// Data posted to an input channel -- output port.
+    pub data_out: Output<HttpData>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// HTTP bridge model instance configuration.
+    config: HttpBridgeConfig,
+}
+
+impl ProtoHttpBridge {
+    /// Creates a new HTTP bridge model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the listen address cannot be bound or if
+    /// the I/O thread cannot be created.
+    pub fn new(config: HttpBridgeConfig) -> Self {
+        Self {
+            data_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoHttpBridge {
+    type Model = HttpBridge;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(HttpBridgeInner::new(&self.config), options)
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Failed to start the I/O thread of the HTTP bridge on {}: {e}.",
+                    self.config.listen_address
+                )
+            });
+
+        HttpBridge {
+            data_out: self.data_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoHttpBridge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoHttpBridge")
+            .field("listen_address", &self.config.listen_address)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,71 @@
+//! Network port models for [NeXosim][NX]-based simulations.
+//!
+//! This crate contains models connecting a simulation to network endpoints:
+//!
+//! * [`afdx`]: AFDX end system layer on top of two redundant Ethernet
+//!   networks.
+//! * [`coap`]: CoAP client and server over UDP, serving observable resources
+//!   and commands, and observing the resources of remote endpoints.
+//! * [`console`]: interactive Telnet or raw TCP console, forwarding the
+//!   commands of operators and writing text back to their sessions.
+//! * [`http`]: HTTP server injecting and observing data on named channels,
+//!   available with the `http` feature.
+//! * [`mavlink`]: MAVLink endpoint with heartbeats, message intervals and
+//!   parameter protocol, over a serial link or UDP.
+//! * [`modbus`]: Modbus TCP client layer on top of a TCP connection, and
+//!   Modbus TCP server serving simulated coils and registers.
+//! * [`opcua`]: OPC UA server exposing simulation variables as nodes and
+//!   forwarding the writes of clients, available with the `opcua` feature.
+//! * [`prometheus`]: Prometheus exporter serving counters and gauges fed by
+//!   other models, available with the `prometheus` feature.
+//! * [`ptp`]: PTP time source following a PTP master, with a simulation clock
+//!   paced on the time of the master.
+//! * [`rmap`]: RMAP initiator and target layer on top of SpaceWire packets.
+//! * [`sle`]: CCSDS SLE user of the RAF or FCLTU service on top of a TCP
+//!   connection to a ground-station provider.
+//! * [`someip`]: SOME/IP port with service discovery, exchanging method calls
+//!   and event notifications with automotive Ethernet ECUs.
+//! * [`spacewire`]: SpaceWire-over-UDP port, exchanging SpaceWire packets with a
+//!   SpaceWire bridge.
+//! * [`tcp`]: TCP client port, reconnecting automatically to its server.
+//! * [`tuntap`]: TUN/TAP virtual network interface port, available on Linux
+//!   with the `tuntap` feature.
+//! * [`vsock`]: vsock port exchanging bytes with virtual machines, available
+//!   on Linux with the `vsock` feature.
+//! * [`websocket`]: WebSocket client or server port, available with the
+//!   `websocket` feature.
+//! * [`yamcs`]: YAMCS bridge injecting telemetry into and receiving
+//!   telecommands from the TCP or UDP data links of a YAMCS instance.
+//! * [`zeromq`]: ZeroMQ PUB, SUB, REQ or REP socket port, available on Unix
+//!   with the `zeromq` feature.
+//!
+//! [NX]: https://github.com/asynchronics/nexosim
+#![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
+#![forbid(unsafe_code)]
+
+pub mod afdx;
+pub mod coap;
+pub mod console;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod mavlink;
+pub mod modbus;
+#[cfg(feature = "opcua")]
+pub mod opcua;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+pub mod ptp;
+pub mod rmap;
+pub mod sle;
+pub mod someip;
+pub mod spacewire;
+pub mod tcp;
+#[cfg(all(target_os = "linux", feature = "tuntap"))]
+pub mod tuntap;
+#[cfg(all(target_os = "linux", feature = "vsock"))]
+pub mod vsock;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+pub mod yamcs;
+#[cfg(all(unix, feature = "zeromq"))]
+pub mod zeromq;
@@ -0,0 +1,1481 @@
+//! MAVLink endpoint.
+//!
+//! This module contains the [`MavlinkEndpoint`] model, a MAVLink component
+//! which lets the simulation appear as a ground control station or as a
+//! companion computer on a MAVLink link:
+//! * heartbeat production, and monitoring of the heartbeats of the other
+//!   components, which are reported as discovered or lost,
+//! * routing of the received messages on their system and component IDs,
+//!   messages addressed to other components being discarded,
+//! * message intervals: the streamed messages fed by the simulation are sent
+//!   at the intervals requested by the other components with the
+//!   `MAV_CMD_SET_MESSAGE_INTERVAL` and `MAV_CMD_REQUEST_MESSAGE` commands,
+//!   and message intervals are requested from the discovered components as
+//!   configured,
+//! * parameter protocol: the parameters of the configuration are served to
+//!   the other components, and the parameters of the other components can be
+//!   listed, read and set.
+//!
+//! The MAVLink frames are exchanged as bytes, either over a serial link, in
+//! which case the model should be connected to the byte output and input of a
+//! serial port, or over UDP, in which case the model should be connected to
+//! the data output and input of an
+//! [`ExternalPort`](nexosim_io_utils::external::ExternalPort) running a
+//! [`MavlinkUdpPort`].
+//!
+//! Both MAVLink 1 and MAVLink 2 frames are received, signed MAVLink 2 frames
+//! being accepted without signature verification. Frames are sent with the
+//! configured protocol version. The checksum of a frame depends on its
+//! message definition, so that only the frames of the messages handled by the
+//! endpoint and of the messages declared in the configuration are received.
+//!
+//! Parameter values are encoded in the `float` field of the parameter
+//! messages by value conversion, rather than bytewise.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::mavlink::{MavlinkCommand, MavlinkEndpoint, MavlinkEndpointConfig};
+//!
+//! // Companion computer streaming ATTITUDE (30) and serving a parameter.
+//! let config = ConfigLoader::<MavlinkEndpointConfig>::new()
+//!     .code(
+//!         r#"
+//! systemId = 1
+//! componentId = 191
+//! mavType = 18
+//!
+//! [[messages]]
+//! id = 30
+//! crcExtra = 39
+//!
+//! [[streams]]
+//! messageId = 30
+//! interval = 100
+//!
+//! [[parameters]]
+//! name = "SIM_GAIN"
+//! value = 0.5
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! let endpoint = MavlinkEndpoint::new(config);
+//!
+//! // Connect `endpoint.bytes_out` to `SerialPort::bytes_in` and
+//! // `SerialPort::bytes_out` to `MavlinkEndpoint::bytes_in`, then feed the
+//! // streamed messages and send commands such as:
+//! let command = MavlinkCommand::RequestParameters {
+//!     target_system: 1,
+//!     target_component: 1,
+//! };
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{ErrorKind, Result as IoResult};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use schematic::{Config, ConfigEnum};
+use serde::{Deserialize, Serialize};
+
+use mio::net::UdpSocket;
+use mio::{Interest, Registry, Token};
+
+use nexosim::model::{Context, InitializedModel, Model};
+use nexosim::ports::Output;
+use nexosim::simulation::ActionKey;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::IoPort;
+
+/// MAV_RESULT_ACCEPTED command result.
+pub const MAV_RESULT_ACCEPTED: u8 = 0;
+
+/// MAV_RESULT_DENIED command result.
+pub const MAV_RESULT_DENIED: u8 = 2;
+
+/// MAV_RESULT_UNSUPPORTED command result.
+pub const MAV_RESULT_UNSUPPORTED: u8 = 3;
+
+/// MAV_CMD_SET_MESSAGE_INTERVAL command.
+pub const MAV_CMD_SET_MESSAGE_INTERVAL: u16 = 511;
+
+/// MAV_CMD_REQUEST_MESSAGE command.
+pub const MAV_CMD_REQUEST_MESSAGE: u16 = 512;
+
+/// MAVLink 1 start marker.
+const STX_V1: u8 = 0xFE;
+
+/// MAVLink 2 start marker.
+const STX_V2: u8 = 0xFD;
+
+/// MAVLink 1 header size, start marker included.
+const HEADER_LEN_V1: usize = 6;
+
+/// MAVLink 2 header size, start marker included.
+const HEADER_LEN_V2: usize = 10;
+
+/// MAVLink 2 signature size.
+const SIGNATURE_LEN: usize = 13;
+
+/// Incompatibility flag of the signed MAVLink 2 frames.
+const INCOMPAT_SIGNED: u8 = 0x01;
+
+/// MAVLink version field of the heartbeats.
+const MAVLINK_VERSION: u8 = 3;
+
+/// HEARTBEAT message.
+const MSG_HEARTBEAT: u32 = 0;
+
+/// PARAM_REQUEST_READ message.
+const MSG_PARAM_REQUEST_READ: u32 = 20;
+
+/// PARAM_REQUEST_LIST message.
+const MSG_PARAM_REQUEST_LIST: u32 = 21;
+
+/// PARAM_VALUE message.
+const MSG_PARAM_VALUE: u32 = 22;
+
+/// PARAM_SET message.
+const MSG_PARAM_SET: u32 = 23;
+
+/// COMMAND_LONG message.
+const MSG_COMMAND_LONG: u32 = 76;
+
+/// COMMAND_ACK message.
+const MSG_COMMAND_ACK: u32 = 77;
+
+/// Definitions of the messages handled by the endpoint: message ID, CRC
+/// extra byte and offsets of the target system and component fields.
+const HANDLED_MESSAGES: [(u32, MessageInfo); 7] = [
+    (MSG_HEARTBEAT, MessageInfo::new(50, None)),
+    (MSG_PARAM_REQUEST_READ, MessageInfo::new(214, Some((2, 3)))),
+    (MSG_PARAM_REQUEST_LIST, MessageInfo::new(159, Some((0, 1)))),
+    (MSG_PARAM_VALUE, MessageInfo::new(220, None)),
+    (MSG_PARAM_SET, MessageInfo::new(168, Some((4, 5)))),
+    (MSG_COMMAND_LONG, MessageInfo::new(152, Some((30, 31)))),
+    (MSG_COMMAND_ACK, MessageInfo::new(143, Some((8, 9)))),
+];
+
+/// Size of the parameter names.
+const PARAM_ID_LEN: usize = 16;
+
+/// UDP socket token.
+const SOCKET: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// Maximum size of a received datagram.
+const MAX_DATAGRAM_LEN: usize = 65536;
+
+/// MAVLink endpoint model instance configuration.
+#[derive(Config, Debug)]
+pub struct MavlinkEndpointConfig {
+    /// System ID of the endpoint.
+    #[setting(default = 255)]
+    pub system_id: u8,
+
+    /// Component ID of the endpoint.
+    #[setting(default = 190)]
+    pub component_id: u8,
+
+    /// MAV_TYPE of the endpoint, sent in its heartbeats.
+    ///
+    /// The default is MAV_TYPE_GCS.
+    #[setting(default = 6)]
+    pub mav_type: u8,
+
+    /// MAV_AUTOPILOT of the endpoint, sent in its heartbeats.
+    ///
+    /// The default is MAV_AUTOPILOT_INVALID.
+    #[setting(default = 8)]
+    pub autopilot: u8,
+
+    /// MAV_STATE of the endpoint, sent in its heartbeats.
+    ///
+    /// The default is MAV_STATE_ACTIVE.
+    #[setting(default = 4)]
+    pub system_status: u8,
+
+    /// Protocol version of the sent frames.
+    pub version: MavlinkVersion,
+
+    /// Heartbeat period.
+    ///
+    /// No heartbeat is sent if the period is zero.
+    #[setting(default = ConfigDuration::from_millis(1000))]
+    pub heartbeat_period: ConfigDuration,
+
+    /// Time without heartbeat after which a component is reported as lost.
+    #[setting(default = ConfigDuration::from_millis(3000))]
+    pub heartbeat_timeout: ConfigDuration,
+
+    /// Definitions of the messages exchanged with the simulation.
+    #[setting(nested)]
+    pub messages: Vec<MavlinkMessageConfig>,
+
+    /// Messages streamed by the endpoint.
+    #[setting(nested)]
+    pub streams: Vec<MavlinkStreamConfig>,
+
+    /// Message intervals requested from the discovered components.
+    #[setting(nested)]
+    pub message_intervals: Vec<MavlinkIntervalConfig>,
+
+    /// Parameters served by the endpoint.
+    #[setting(nested)]
+    pub parameters: Vec<MavlinkParameterConfig>,
+}
+
+/// MAVLink protocol version.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MavlinkVersion {
+    /// MAVLink 1.
+    #[serde(rename = "v1")]
+    V1,
+
+    /// MAVLink 2.
+    #[default]
+    #[serde(rename = "v2")]
+    V2,
+}
+
+/// Message definition.
+#[derive(Config, Debug)]
+pub struct MavlinkMessageConfig {
+    /// Message ID.
+    pub id: u32,
+
+    /// CRC extra byte of the message definition.
+    pub crc_extra: u8,
+
+    /// Offset of the target system field in the payload, for addressed
+    /// messages.
+    pub target_system_offset: Option<usize>,
+
+    /// Offset of the target component field in the payload, for addressed
+    /// messages.
+    pub target_component_offset: Option<usize>,
+}
+
+/// Streamed message configuration.
+#[derive(Config, Debug)]
+pub struct MavlinkStreamConfig {
+    /// Message ID.
+    pub message_id: u32,
+
+    /// Default interval.
+    ///
+    /// If no value is provided, the message is only streamed on request.
+    pub interval: Option<ConfigDuration>,
+}
+
+/// Requested message interval configuration.
+#[derive(Config, Debug)]
+pub struct MavlinkIntervalConfig {
+    /// System ID of the component.
+    pub target_system: u8,
+
+    /// Component ID of the component, or 0 for all the components of the
+    /// system.
+    pub target_component: u8,
+
+    /// Message ID.
+    pub message_id: u32,
+
+    /// Interval.
+    pub interval: ConfigDuration,
+}
+
+/// Parameter configuration.
+#[derive(Config, Debug)]
+pub struct MavlinkParameterConfig {
+    /// Parameter name, at most 16 characters long.
+    pub name: String,
+
+    /// Initial value.
+    pub value: f32,
+
+    /// Parameter type.
+    pub param_type: MavlinkParamType,
+}
+
+/// MAV_PARAM_TYPE of a parameter.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MavlinkParamType {
+    /// 8-bit unsigned integer.
+    #[serde(rename = "uint8")]
+    Uint8,
+
+    /// 8-bit signed integer.
+    #[serde(rename = "int8")]
+    Int8,
+
+    /// 16-bit unsigned integer.
+    #[serde(rename = "uint16")]
+    Uint16,
+
+    /// 16-bit signed integer.
+    #[serde(rename = "int16")]
+    Int16,
+
+    /// 32-bit unsigned integer.
+    #[serde(rename = "uint32")]
+    Uint32,
+
+    /// 32-bit signed integer.
+    #[serde(rename = "int32")]
+    Int32,
+
+    /// 32-bit float.
+    #[default]
+    #[serde(rename = "real32")]
+    Real32,
+}
+
+impl MavlinkParamType {
+    /// Returns the MAV_PARAM_TYPE code.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Uint8 => 1,
+            Self::Int8 => 2,
+            Self::Uint16 => 3,
+            Self::Int16 => 4,
+            Self::Uint32 => 5,
+            Self::Int32 => 6,
+            Self::Real32 => 9,
+        }
+    }
+
+    /// Decodes a MAV_PARAM_TYPE code.
+    ///
+    /// Unsupported codes are decoded as 32-bit floats.
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            1 => Self::Uint8,
+            2 => Self::Int8,
+            3 => Self::Uint16,
+            4 => Self::Int16,
+            5 => Self::Uint32,
+            6 => Self::Int32,
+            _ => Self::Real32,
+        }
+    }
+
+    /// Converts a value to the range and resolution of the type.
+    fn convert(self, value: f32) -> f32 {
+        match self {
+            Self::Uint8 => value as u8 as f32,
+            Self::Int8 => value as i8 as f32,
+            Self::Uint16 => value as u16 as f32,
+            Self::Int16 => value as i16 as f32,
+            Self::Uint32 => value as u32 as f32,
+            Self::Int32 => value as i32 as f32,
+            Self::Real32 => value,
+        }
+    }
+}
+
+/// MAVLink message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MavlinkMessage {
+    /// System ID of the sender.
+    pub system_id: u8,
+
+    /// Component ID of the sender.
+    pub component_id: u8,
+
+    /// Message ID.
+    pub message_id: u32,
+
+    /// Payload.
+    ///
+    /// The trailing zero bytes of the payloads of MAVLink 2 frames may be
+    /// truncated.
+    pub payload: Bytes,
+}
+
+impl MavlinkMessage {
+    /// Creates a new message to be sent by the endpoint.
+    ///
+    /// The sender IDs are set to 0, since the IDs of the endpoint are used
+    /// for the sent messages.
+    pub fn new(message_id: u32, payload: impl Into<Bytes>) -> Self {
+        Self {
+            system_id: 0,
+            component_id: 0,
+            message_id,
+            payload: payload.into(),
+        }
+    }
+}
+
+/// Heartbeat content.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MavlinkHeartbeat {
+    /// MAV_TYPE.
+    pub mav_type: u8,
+
+    /// MAV_AUTOPILOT.
+    pub autopilot: u8,
+
+    /// MAV_MODE_FLAG bitmap.
+    pub base_mode: u8,
+
+    /// Autopilot specific mode.
+    pub custom_mode: u32,
+
+    /// MAV_STATE.
+    pub system_status: u8,
+}
+
+impl MavlinkHeartbeat {
+    /// Encodes the HEARTBEAT payload.
+    fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(9);
+        payload.put_u32_le(self.custom_mode);
+        payload.put_u8(self.mav_type);
+        payload.put_u8(self.autopilot);
+        payload.put_u8(self.base_mode);
+        payload.put_u8(self.system_status);
+        payload.put_u8(MAVLINK_VERSION);
+
+        payload
+    }
+
+    /// Decodes a HEARTBEAT payload.
+    fn decode(payload: &[u8]) -> Self {
+        let mut payload = &padded::<9>(payload)[..];
+
+        let custom_mode = payload.get_u32_le();
+        Self {
+            mav_type: payload.get_u8(),
+            autopilot: payload.get_u8(),
+            base_mode: payload.get_u8(),
+            custom_mode,
+            system_status: payload.get_u8(),
+        }
+    }
+}
+
+/// State of a monitored component.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MavlinkPeerState {
+    /// System ID.
+    pub system_id: u8,
+
+    /// Component ID.
+    pub component_id: u8,
+
+    /// Last received heartbeat, or `None` if the component was lost.
+    pub heartbeat: Option<MavlinkHeartbeat>,
+}
+
+/// Parameter value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MavlinkParameter {
+    /// Parameter name.
+    pub name: String,
+
+    /// Value.
+    pub value: f32,
+
+    /// Parameter type.
+    pub param_type: MavlinkParamType,
+}
+
+/// Parameter of another component.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MavlinkRemoteParameter {
+    /// System ID of the component.
+    pub system_id: u8,
+
+    /// Component ID of the component.
+    pub component_id: u8,
+
+    /// Parameter index.
+    pub index: u16,
+
+    /// Number of parameters of the component.
+    pub count: u16,
+
+    /// Parameter value.
+    pub parameter: MavlinkParameter,
+}
+
+/// Message interval.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MavlinkInterval {
+    /// Default interval of the message.
+    Default,
+
+    /// Message disabled.
+    Disabled,
+
+    /// Interval.
+    Every(Duration),
+}
+
+impl MavlinkInterval {
+    /// Encodes the interval as a command parameter, in microseconds.
+    fn to_param(self) -> f32 {
+        match self {
+            Self::Default => 0.0,
+            Self::Disabled => -1.0,
+            Self::Every(interval) => interval.as_micros() as f32,
+        }
+    }
+
+    /// Decodes a command parameter, in microseconds.
+    fn from_param(param: f32) -> Self {
+        if param < 0.0 {
+            Self::Disabled
+        } else if param == 0.0 {
+            Self::Default
+        } else {
+            Self::Every(Duration::from_micros(param as u64))
+        }
+    }
+}
+
+/// Command addressed to another component.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MavlinkCommand {
+    /// Requests all the parameters of the component.
+    RequestParameters {
+        /// System ID of the component.
+        target_system: u8,
+
+        /// Component ID of the component.
+        target_component: u8,
+    },
+
+    /// Reads a parameter of the component.
+    ReadParameter {
+        /// System ID of the component.
+        target_system: u8,
+
+        /// Component ID of the component.
+        target_component: u8,
+
+        /// Parameter name.
+        name: String,
+    },
+
+    /// Sets a parameter of the component.
+    SetParameter {
+        /// System ID of the component.
+        target_system: u8,
+
+        /// Component ID of the component.
+        target_component: u8,
+
+        /// Parameter value.
+        parameter: MavlinkParameter,
+    },
+
+    /// Sets the interval of a message streamed by the component.
+    SetMessageInterval {
+        /// System ID of the component.
+        target_system: u8,
+
+        /// Component ID of the component.
+        target_component: u8,
+
+        /// Message ID.
+        message_id: u32,
+
+        /// Interval.
+        interval: MavlinkInterval,
+    },
+
+    /// Requests a message from the component.
+    RequestMessage {
+        /// System ID of the component.
+        target_system: u8,
+
+        /// Component ID of the component.
+        target_component: u8,
+
+        /// Message ID.
+        message_id: u32,
+    },
+
+    /// Sends a COMMAND_LONG to the component.
+    Long {
+        /// System ID of the component.
+        target_system: u8,
+
+        /// Component ID of the component.
+        target_component: u8,
+
+        /// MAV_CMD command.
+        command: u16,
+
+        /// Command parameters.
+        params: [f32; 7],
+    },
+}
+
+/// Command acknowledgement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MavlinkCommandAck {
+    /// System ID of the component.
+    pub system_id: u8,
+
+    /// Component ID of the component.
+    pub component_id: u8,
+
+    /// MAV_CMD command.
+    pub command: u16,
+
+    /// MAV_RESULT result.
+    pub result: u8,
+}
+
+/// Message definition.
+#[derive(Clone, Copy, Debug)]
+struct MessageInfo {
+    /// CRC extra byte.
+    crc_extra: u8,
+
+    /// Offsets of the target system and component fields.
+    targets: (Option<usize>, Option<usize>),
+}
+
+impl MessageInfo {
+    /// Creates a message definition.
+    const fn new(crc_extra: u8, targets: Option<(usize, usize)>) -> Self {
+        let targets = match targets {
+            Some((system, component)) => (Some(system), Some(component)),
+            None => (None, None),
+        };
+
+        Self { crc_extra, targets }
+    }
+}
+
+/// Streamed message.
+#[derive(Debug, Default)]
+struct Stream {
+    /// Default interval.
+    default: Option<Duration>,
+
+    /// Latest payload fed by the simulation.
+    payload: Option<Bytes>,
+
+    /// Periodic sending key.
+    key: Option<ActionKey>,
+}
+
+/// Parameter served by the endpoint.
+#[derive(Debug)]
+struct Parameter {
+    /// Parameter name.
+    name: String,
+
+    /// Value.
+    value: f32,
+
+    /// Parameter type.
+    param_type: MavlinkParamType,
+}
+
+/// MAVLink endpoint model.
+///
+/// This model:
+/// * sends heartbeats and reports the state of the other components,
+/// * sends the messages of the simulation, and streams its streamed messages
+///   at their requested intervals,
+/// * serves its parameters, and reports the parameters set by the other
+///   components,
+/// * sends the commands of the simulation, and reports the parameters and
+///   command acknowledgements received from the other components,
+/// * forwards the other received messages addressed to it.
+pub struct MavlinkEndpoint {
+    /// Encoded MAVLink frames -- output port.
+    pub bytes_out: Output<Bytes>,
+
+    /// Received message -- output port.
+    pub message_out: Output<MavlinkMessage>,
+
+    /// State of the other components -- output port.
+    pub peer_out: Output<MavlinkPeerState>,
+
+    /// Parameter set by another component -- output port.
+    pub parameter_out: Output<MavlinkParameter>,
+
+    /// Parameter of another component -- output port.
+    pub remote_parameter_out: Output<MavlinkRemoteParameter>,
+
+    /// Command acknowledgement -- output port.
+    pub ack_out: Output<MavlinkCommandAck>,
+
+    /// Model instance configuration.
+    config: MavlinkEndpointConfig,
+
+    /// Content of the heartbeats.
+    heartbeat: MavlinkHeartbeat,
+
+    /// Message definitions, by message ID.
+    messages: HashMap<u32, MessageInfo>,
+
+    /// Streamed messages, by message ID.
+    streams: HashMap<u32, Stream>,
+
+    /// Parameters.
+    parameters: Vec<Parameter>,
+
+    /// Heartbeat timeout keys, by system and component ID.
+    peers: HashMap<(u8, u8), ActionKey>,
+
+    /// Sequence number of the next frame.
+    sequence: u8,
+
+    /// Received bytes not yet decoded.
+    buffer: BytesMut,
+}
+
+impl MavlinkEndpoint {
+    /// Creates a new MAVLink endpoint.
+    pub fn new(config: MavlinkEndpointConfig) -> Self {
+        let heartbeat = MavlinkHeartbeat {
+            mav_type: config.mav_type,
+            autopilot: config.autopilot,
+            base_mode: 0,
+            custom_mode: 0,
+            system_status: config.system_status,
+        };
+        let mut messages: HashMap<_, _> = config
+            .messages
+            .iter()
+            .map(|message| {
+                (
+                    message.id,
+                    MessageInfo {
+                        crc_extra: message.crc_extra,
+                        targets: (
+                            message.target_system_offset,
+                            message.target_component_offset,
+                        ),
+                    },
+                )
+            })
+            .collect();
+        messages.extend(HANDLED_MESSAGES);
+        let streams = config
+            .streams
+            .iter()
+            .map(|stream| {
+                (
+                    stream.message_id,
+                    Stream {
+                        default: stream.interval.map(Duration::from),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        let parameters = config
+            .parameters
+            .iter()
+            .map(|parameter| Parameter {
+                name: parameter.name.clone(),
+                value: parameter.param_type.convert(parameter.value),
+                param_type: parameter.param_type,
+            })
+            .collect();
+
+        Self {
+            bytes_out: Output::new(),
+            message_out: Output::new(),
+            peer_out: Output::new(),
+            parameter_out: Output::new(),
+            remote_parameter_out: Output::new(),
+            ack_out: Output::new(),
+            config,
+            heartbeat,
+            messages,
+            streams,
+            parameters,
+            peers: HashMap::new(),
+            sequence: 0,
+            buffer: BytesMut::new(),
+        }
+    }
+
+    /// Received bytes -- input port.
+    pub async fn bytes_in(&mut self, data: Bytes, cx: &mut Context<Self>) {
+        self.buffer.extend_from_slice(&data);
+        while let Some(message) = self.next_message() {
+            self.on_message(message, cx).await;
+        }
+    }
+
+    /// Message to be sent -- input port.
+    ///
+    /// Messages which are not declared in the configuration are discarded.
+    pub async fn message_in(&mut self, message: MavlinkMessage) {
+        self.send(message.message_id, &message.payload).await;
+    }
+
+    /// Latest value of a streamed message -- input port.
+    ///
+    /// Messages which are not declared as streamed are discarded.
+    pub async fn stream_in(&mut self, message: MavlinkMessage) {
+        if let Some(stream) = self.streams.get_mut(&message.message_id) {
+            stream.payload = Some(message.payload);
+        }
+    }
+
+    /// Heartbeat content -- input port.
+    pub async fn heartbeat_in(&mut self, heartbeat: MavlinkHeartbeat) {
+        self.heartbeat = heartbeat;
+    }
+
+    /// Parameter value -- input port.
+    ///
+    /// The new value is broadcast to the other components. Parameters which
+    /// are not declared in the configuration are discarded.
+    pub async fn parameter_in(&mut self, parameter: MavlinkParameter) {
+        if let Some(index) = self.parameter_index(&parameter.name) {
+            let local = &mut self.parameters[index];
+            local.value = local.param_type.convert(parameter.value);
+            self.send_parameter(index).await;
+        }
+    }
+
+    /// Command -- input port.
+    pub async fn command_in(&mut self, command: MavlinkCommand) {
+        match command {
+            MavlinkCommand::RequestParameters {
+                target_system,
+                target_component,
+            } => {
+                self.send(MSG_PARAM_REQUEST_LIST, &[target_system, target_component])
+                    .await
+            }
+            MavlinkCommand::ReadParameter {
+                target_system,
+                target_component,
+                name,
+            } => {
+                let mut payload = Vec::with_capacity(20);
+                payload.put_i16_le(-1);
+                payload.put_u8(target_system);
+                payload.put_u8(target_component);
+                payload.put_slice(&param_id(&name));
+                self.send(MSG_PARAM_REQUEST_READ, &payload).await;
+            }
+            MavlinkCommand::SetParameter {
+                target_system,
+                target_component,
+                parameter,
+            } => {
+                let mut payload = Vec::with_capacity(23);
+                payload.put_f32_le(parameter.value);
+                payload.put_u8(target_system);
+                payload.put_u8(target_component);
+                payload.put_slice(&param_id(&parameter.name));
+                payload.put_u8(parameter.param_type.code());
+                self.send(MSG_PARAM_SET, &payload).await;
+            }
+            MavlinkCommand::SetMessageInterval {
+                target_system,
+                target_component,
+                message_id,
+                interval,
+            } => {
+                let mut params = [0.0; 7];
+                params[0] = message_id as f32;
+                params[1] = interval.to_param();
+                self.send_command(
+                    target_system,
+                    target_component,
+                    MAV_CMD_SET_MESSAGE_INTERVAL,
+                    params,
+                )
+                .await;
+            }
+            MavlinkCommand::RequestMessage {
+                target_system,
+                target_component,
+                message_id,
+            } => {
+                let mut params = [0.0; 7];
+                params[0] = message_id as f32;
+                self.send_command(
+                    target_system,
+                    target_component,
+                    MAV_CMD_REQUEST_MESSAGE,
+                    params,
+                )
+                .await;
+            }
+            MavlinkCommand::Long {
+                target_system,
+                target_component,
+                command,
+                params,
+            } => {
+                self.send_command(target_system, target_component, command, params)
+                    .await
+            }
+        }
+    }
+
+    /// Sends the heartbeat.
+    async fn send_heartbeat(&mut self) {
+        let payload = self.heartbeat.encode();
+        self.send(MSG_HEARTBEAT, &payload).await;
+    }
+
+    /// Sends the latest value of a streamed message.
+    async fn send_stream(&mut self, message_id: u32) {
+        if let Some(payload) = self
+            .streams
+            .get(&message_id)
+            .and_then(|stream| stream.payload.clone())
+        {
+            self.send(message_id, &payload).await;
+        }
+    }
+
+    /// Reports a heartbeat timeout.
+    async fn heartbeat_timeout(&mut self, (system_id, component_id): (u8, u8)) {
+        self.peers.remove(&(system_id, component_id));
+
+        self.peer_out
+            .send(MavlinkPeerState {
+                system_id,
+                component_id,
+                heartbeat: None,
+            })
+            .await;
+    }
+
+    /// Decodes the next complete frame of the receive buffer.
+    ///
+    /// Frames with an invalid checksum, of unknown messages, of unsupported
+    /// incompatibility flags or sent by the endpoint itself are discarded.
+    fn next_message(&mut self) -> Option<MavlinkMessage> {
+        loop {
+            let Some(start) = self
+                .buffer
+                .iter()
+                .position(|&byte| byte == STX_V1 || byte == STX_V2)
+            else {
+                self.buffer.clear();
+                return None;
+            };
+            self.buffer.advance(start);
+
+            let v2 = self.buffer[0] == STX_V2;
+            let header_len = if v2 { HEADER_LEN_V2 } else { HEADER_LEN_V1 };
+            if self.buffer.len() < header_len {
+                return None;
+            }
+            let payload_len = self.buffer[1] as usize;
+            let signed = v2 && self.buffer[2] & INCOMPAT_SIGNED != 0;
+            let crc_offset = header_len + payload_len;
+            let frame_len = crc_offset + 2 + if signed { SIGNATURE_LEN } else { 0 };
+            if self.buffer.len() < frame_len {
+                return None;
+            }
+
+            let (system_id, component_id, message_id) = if v2 {
+                let id = &self.buffer[7..10];
+                (
+                    self.buffer[5],
+                    self.buffer[6],
+                    u32::from_le_bytes([id[0], id[1], id[2], 0]),
+                )
+            } else {
+                (self.buffer[3], self.buffer[4], self.buffer[5] as u32)
+            };
+            let valid = self.messages.get(&message_id).is_some_and(|info| {
+                let crc = crc(&self.buffer[1..crc_offset], info.crc_extra);
+                crc.to_le_bytes() == self.buffer[crc_offset..crc_offset + 2]
+            });
+            if !valid {
+                // Resynchronize on the next start marker.
+                self.buffer.advance(1);
+                continue;
+            }
+
+            let unsupported = v2 && self.buffer[2] & !INCOMPAT_SIGNED != 0;
+            let frame = self.buffer.split_to(frame_len).freeze();
+            if unsupported
+                || (system_id == self.config.system_id && component_id == self.config.component_id)
+            {
+                continue;
+            }
+
+            return Some(MavlinkMessage {
+                system_id,
+                component_id,
+                message_id,
+                payload: frame.slice(header_len..crc_offset),
+            });
+        }
+    }
+
+    /// Handles a received message.
+    async fn on_message(&mut self, message: MavlinkMessage, cx: &mut Context<Self>) {
+        if !self.is_addressed(&message) {
+            return;
+        }
+        let payload = &message.payload;
+
+        match message.message_id {
+            MSG_HEARTBEAT => self.on_heartbeat(&message, cx).await,
+            MSG_PARAM_REQUEST_LIST => {
+                for index in 0..self.parameters.len() {
+                    self.send_parameter(index).await;
+                }
+            }
+            MSG_PARAM_REQUEST_READ => {
+                let payload = padded::<20>(payload);
+                let index = i16::from_le_bytes([payload[0], payload[1]]);
+                let index = if index < 0 {
+                    self.parameter_index(&param_name(&payload[4..20]))
+                } else {
+                    Some(index as usize).filter(|&index| index < self.parameters.len())
+                };
+                if let Some(index) = index {
+                    self.send_parameter(index).await;
+                }
+            }
+            MSG_PARAM_SET => {
+                let payload = padded::<23>(payload);
+                let value = f32::from_le_bytes(payload[0..4].try_into().unwrap());
+                let Some(index) = self.parameter_index(&param_name(&payload[6..22])) else {
+                    return;
+                };
+                let parameter = &mut self.parameters[index];
+                parameter.value = parameter.param_type.convert(value);
+                let parameter = MavlinkParameter {
+                    name: parameter.name.clone(),
+                    value: parameter.value,
+                    param_type: parameter.param_type,
+                };
+                self.send_parameter(index).await;
+                self.parameter_out.send(parameter).await;
+            }
+            MSG_PARAM_VALUE => {
+                let payload = padded::<25>(payload);
+                let mut fields = &payload[..];
+                let value = fields.get_f32_le();
+                let count = fields.get_u16_le();
+                let index = fields.get_u16_le();
+                self.remote_parameter_out
+                    .send(MavlinkRemoteParameter {
+                        system_id: message.system_id,
+                        component_id: message.component_id,
+                        index,
+                        count,
+                        parameter: MavlinkParameter {
+                            name: param_name(&payload[8..24]),
+                            value,
+                            param_type: MavlinkParamType::from_code(payload[24]),
+                        },
+                    })
+                    .await;
+            }
+            MSG_COMMAND_LONG => {
+                let payload = padded::<33>(payload);
+                let mut fields = &payload[..];
+                let mut params = [0.0; 7];
+                for param in &mut params {
+                    *param = fields.get_f32_le();
+                }
+                let command = fields.get_u16_le();
+                let result = match command {
+                    MAV_CMD_SET_MESSAGE_INTERVAL => self.set_interval(
+                        params[0] as u32,
+                        MavlinkInterval::from_param(params[1]),
+                        cx,
+                    ),
+                    MAV_CMD_REQUEST_MESSAGE => self.request_message(params[0] as u32).await,
+                    _ => {
+                        self.message_out.send(message).await;
+                        return;
+                    }
+                };
+                self.send_ack(&message, command, result).await;
+            }
+            MSG_COMMAND_ACK => {
+                let payload = padded::<3>(payload);
+                self.ack_out
+                    .send(MavlinkCommandAck {
+                        system_id: message.system_id,
+                        component_id: message.component_id,
+                        command: u16::from_le_bytes([payload[0], payload[1]]),
+                        result: payload[2],
+                    })
+                    .await;
+            }
+            _ => self.message_out.send(message).await,
+        }
+    }
+
+    /// Handles a received heartbeat.
+    async fn on_heartbeat(&mut self, message: &MavlinkMessage, cx: &mut Context<Self>) {
+        let peer = (message.system_id, message.component_id);
+        let discovered = match self.peers.remove(&peer) {
+            Some(key) => {
+                key.cancel();
+                false
+            }
+            None => true,
+        };
+        let timeout = Duration::from(self.config.heartbeat_timeout);
+        let key = cx
+            .schedule_keyed_event(timeout, Self::heartbeat_timeout, peer)
+            .unwrap();
+        self.peers.insert(peer, key);
+
+        if discovered {
+            let requests: Vec<_> = self
+                .config
+                .message_intervals
+                .iter()
+                .filter(|request| {
+                    request.target_system == peer.0
+                        && (request.target_component == 0 || request.target_component == peer.1)
+                })
+                .map(|request| (request.message_id, request.interval))
+                .collect();
+            for (message_id, interval) in requests {
+                let mut params = [0.0; 7];
+                params[0] = message_id as f32;
+                params[1] = MavlinkInterval::Every(interval.into()).to_param();
+                self.send_command(peer.0, peer.1, MAV_CMD_SET_MESSAGE_INTERVAL, params)
+                    .await;
+            }
+        }
+
+        self.peer_out
+            .send(MavlinkPeerState {
+                system_id: peer.0,
+                component_id: peer.1,
+                heartbeat: Some(MavlinkHeartbeat::decode(&message.payload)),
+            })
+            .await;
+    }
+
+    /// Sets the interval of a streamed message and returns the MAV_RESULT.
+    fn set_interval(
+        &mut self,
+        message_id: u32,
+        interval: MavlinkInterval,
+        cx: &mut Context<Self>,
+    ) -> u8 {
+        let Some(stream) = self.streams.get_mut(&message_id) else {
+            return MAV_RESULT_UNSUPPORTED;
+        };
+        if let Some(key) = stream.key.take() {
+            key.cancel();
+        }
+        let interval = match interval {
+            MavlinkInterval::Default => stream.default,
+            MavlinkInterval::Disabled => None,
+            MavlinkInterval::Every(interval) => Some(interval),
+        };
+        if let Some(interval) = interval {
+            if interval.is_zero() {
+                return MAV_RESULT_DENIED;
+            }
+            stream.key = Some(
+                cx.schedule_keyed_periodic_event(interval, interval, Self::send_stream, message_id)
+                    .unwrap(),
+            );
+        }
+
+        MAV_RESULT_ACCEPTED
+    }
+
+    /// Sends a requested message once and returns the MAV_RESULT.
+    async fn request_message(&mut self, message_id: u32) -> u8 {
+        if message_id == MSG_HEARTBEAT {
+            self.send_heartbeat().await;
+
+            return MAV_RESULT_ACCEPTED;
+        }
+        match self
+            .streams
+            .get(&message_id)
+            .and_then(|stream| stream.payload.clone())
+        {
+            Some(payload) => {
+                self.send(message_id, &payload).await;
+
+                MAV_RESULT_ACCEPTED
+            }
+            None => MAV_RESULT_UNSUPPORTED,
+        }
+    }
+
+    /// Checks whether a message is broadcast or addressed to the endpoint.
+    fn is_addressed(&self, message: &MavlinkMessage) -> bool {
+        let Some(info) = self.messages.get(&message.message_id) else {
+            return false;
+        };
+        // Truncated target fields are zero, i.e. broadcast.
+        let target = |offset: Option<usize>| {
+            offset
+                .and_then(|offset| message.payload.get(offset).copied())
+                .unwrap_or(0)
+        };
+        let (system, component) = (target(info.targets.0), target(info.targets.1));
+
+        (system == 0 || system == self.config.system_id)
+            && (component == 0 || component == self.config.component_id)
+    }
+
+    /// Returns the index of a parameter.
+    fn parameter_index(&self, name: &str) -> Option<usize> {
+        self.parameters
+            .iter()
+            .position(|parameter| parameter.name == name)
+    }
+
+    /// Broadcasts the value of a parameter.
+    async fn send_parameter(&mut self, index: usize) {
+        let parameter = &self.parameters[index];
+        let mut payload = Vec::with_capacity(25);
+        payload.put_f32_le(parameter.value);
+        payload.put_u16_le(self.parameters.len() as u16);
+        payload.put_u16_le(index as u16);
+        payload.put_slice(&param_id(&parameter.name));
+        payload.put_u8(parameter.param_type.code());
+        self.send(MSG_PARAM_VALUE, &payload).await;
+    }
+
+    /// Sends a COMMAND_LONG.
+    async fn send_command(
+        &mut self,
+        target_system: u8,
+        target_component: u8,
+        command: u16,
+        params: [f32; 7],
+    ) {
+        let mut payload = Vec::with_capacity(33);
+        for param in params {
+            payload.put_f32_le(param);
+        }
+        payload.put_u16_le(command);
+        payload.put_u8(target_system);
+        payload.put_u8(target_component);
+        payload.put_u8(0);
+        self.send(MSG_COMMAND_LONG, &payload).await;
+    }
+
+    /// Acknowledges a received COMMAND_LONG.
+    async fn send_ack(&mut self, message: &MavlinkMessage, command: u16, result: u8) {
+        let mut payload = Vec::with_capacity(10);
+        payload.put_u16_le(command);
+        payload.put_u8(result);
+        payload.put_u8(0);
+        payload.put_i32_le(0);
+        payload.put_u8(message.system_id);
+        payload.put_u8(message.component_id);
+        self.send(MSG_COMMAND_ACK, &payload).await;
+    }
+
+    /// Encodes and sends a frame.
+    ///
+    /// Messages without definition, and messages with a 24-bit ID over
+    /// MAVLink 1, are discarded. Payloads are truncated to 255 bytes.
+    async fn send(&mut self, message_id: u32, payload: &[u8]) {
+        let Some(info) = self.messages.get(&message_id) else {
+            return;
+        };
+        let mut payload = &payload[..payload.len().min(u8::MAX as usize)];
+
+        let mut frame = BytesMut::with_capacity(HEADER_LEN_V2 + payload.len() + 2);
+        match self.config.version {
+            MavlinkVersion::V1 => {
+                let Ok(message_id) = u8::try_from(message_id) else {
+                    return;
+                };
+                frame.put_u8(STX_V1);
+                frame.put_u8(payload.len() as u8);
+                frame.put_u8(self.sequence);
+                frame.put_u8(self.config.system_id);
+                frame.put_u8(self.config.component_id);
+                frame.put_u8(message_id);
+            }
+            MavlinkVersion::V2 => {
+                // Trailing zero bytes are truncated, keeping at least one byte.
+                while let [head @ .., 0] = payload {
+                    if head.is_empty() {
+                        break;
+                    }
+                    payload = head;
+                }
+                frame.put_u8(STX_V2);
+                frame.put_u8(payload.len() as u8);
+                frame.put_u8(0);
+                frame.put_u8(0);
+                frame.put_u8(self.sequence);
+                frame.put_u8(self.config.system_id);
+                frame.put_u8(self.config.component_id);
+                frame.put_slice(&message_id.to_le_bytes()[..3]);
+            }
+        }
+        frame.put_slice(payload);
+        let crc = crc(&frame[1..], info.crc_extra);
+        frame.put_u16_le(crc);
+        self.sequence = self.sequence.wrapping_add(1);
+
+        self.bytes_out.send(frame.freeze()).await;
+    }
+}
+
+impl Model for MavlinkEndpoint {
+    async fn init(mut self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if !self.config.heartbeat_period.is_zero() {
+            let period = Duration::from(self.config.heartbeat_period);
+            context
+                .schedule_periodic_event(period, period, Self::send_heartbeat, ())
+                .unwrap();
+        }
+        let streams: Vec<_> = self.streams.keys().copied().collect();
+        for message_id in streams {
+            self.set_interval(message_id, MavlinkInterval::Default, context);
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for MavlinkEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MavlinkEndpoint")
+            .field("system_id", &self.config.system_id)
+            .field("component_id", &self.config.component_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Computes the MAVLink checksum of a frame, start marker excluded.
+fn crc(data: &[u8], crc_extra: u8) -> u16 {
+    data.iter()
+        .chain(&[crc_extra])
+        .fold(0xFFFF, |crc: u16, &byte| {
+            let tmp = byte ^ crc as u8;
+            let tmp = tmp ^ (tmp << 4);
+            (crc >> 8) ^ ((tmp as u16) << 8) ^ ((tmp as u16) << 3) ^ ((tmp as u16) >> 4)
+        })
+}
+
+/// Zero-extends a possibly truncated payload.
+fn padded<const N: usize>(payload: &[u8]) -> [u8; N] {
+    let mut padded = [0; N];
+    let len = payload.len().min(N);
+    padded[..len].copy_from_slice(&payload[..len]);
+
+    padded
+}
+
+/// Encodes a parameter name.
+fn param_id(name: &str) -> [u8; PARAM_ID_LEN] {
+    padded(name.as_bytes())
+}
+
+/// Decodes a parameter name.
+fn param_name(id: &[u8]) -> String {
+    let len = id.iter().position(|&byte| byte == 0).unwrap_or(id.len());
+
+    String::from_utf8_lossy(&id[..len]).into_owned()
+}
+
+/// MAVLink UDP I/O port.
+///
+/// This I/O port is meant to be run by an
+/// [`ExternalPort`](nexosim_io_utils::external::ExternalPort) connected to a
+/// [`MavlinkEndpoint`]. Without remote endpoint, frames are sent to the
+/// source of the last received datagram, as a ground control station
+/// listening for vehicles would do.
+pub struct MavlinkUdpPort {
+    /// UDP socket.
+    socket: UdpSocket,
+
+    /// Remote endpoint.
+    remote: Option<SocketAddr>,
+
+    /// The remote endpoint is the source of the last received datagram.
+    learn: bool,
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+}
+
+impl MavlinkUdpPort {
+    /// Binds a UDP socket to the local address, for the remote endpoint if
+    /// any.
+    pub fn bind(local: SocketAddr, remote: Option<SocketAddr>) -> IoResult<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(local)?,
+            remote,
+            learn: remote.is_none(),
+            buffer: vec![0; MAX_DATAGRAM_LEN],
+        })
+    }
+}
+
+impl IoPort<UdpSocket, Bytes, Bytes> for MavlinkUdpPort {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        registry.register(&mut self.socket, SOCKET, Interest::READABLE)?;
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, _: Token) -> IoResult<Bytes> {
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((len, source)) => {
+                    if self.learn {
+                        self.remote = Some(source);
+                    }
+                    return Ok(Bytes::copy_from_slice(&self.buffer[..len]));
+                }
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write(&mut self, frame: &Bytes) -> IoResult<()> {
+        // Frames sent before any datagram was received are lost.
+        let Some(remote) = self.remote else {
+            return Ok(());
+        };
+        match self.socket.send_to(frame, remote) {
+            Ok(_) => Ok(()),
+            // Frames to an unreachable endpoint are lost.
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl fmt::Debug for MavlinkUdpPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MavlinkUdpPort")
+            .field("remote", &self.remote)
+            .finish_non_exhaustive()
+    }
+}
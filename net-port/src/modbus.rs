@@ -0,0 +1,1744 @@
+//! Modbus TCP models.
+//!
+//! This module contains:
+//! * the [`ModbusClient`] layer, issuing the Modbus requests of the simulation
+//!   on top of the byte stream of a [`TcpClient`](crate::tcp::TcpClient) and
+//!   forwarding the replies of the server,
+//! * the [`ModbusServer`] model, listening for Modbus TCP clients and serving
+//!   the coils, discrete inputs, holding registers and input registers held by
+//!   the model, which are updated by the simulation and written by the
+//!   clients.
+//!
+//! The read coils, read discrete inputs, read holding registers, read input
+//! registers, write single coil, write single register, write multiple coils
+//! and write multiple registers functions are supported. Requests with other
+//! function codes are answered by the server with an illegal function
+//! exception.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::modbus::{ModbusClient, ModbusRequest, ModbusServerConfig};
+//!
+//! // Server exposing 16 coils and 100 holding registers on port 5020.
+//! let config = ConfigLoader::<ModbusServerConfig>::new()
+//!     .code(
+//!         r#"
+//! listenAddress = "0.0.0.0:5020"
+//! coils = 16
+//! holdingRegisters = 100
+//! period = 10
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.input_registers, 0);
+//!
+//! // Client layer: connect `bytes_out` to `TcpClient::bytes_in`,
+//! // `TcpClient::bytes_out` to `bytes_in` and `TcpClient::status_out` to
+//! // `status_in`, then send requests such as:
+//! let client = ModbusClient::new().with_unit_id(1);
+//! let request = ModbusRequest::ReadHoldingRegisters {
+//!     address: 0,
+//!     count: 10,
+//! };
+//! ```
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io::{ErrorKind, Read, Result as IoResult};
+use std::net::ToSocketAddrs;
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use schematic::Config;
+
+use mio::net::{TcpListener, TcpStream};
+use mio::{Interest, Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus, WriteBuffer};
+
+use crate::tcp::TcpStatus;
+
+/// Maximum number of bits read by a request.
+pub const MAX_READ_BITS: u16 = 2000;
+
+/// Maximum number of registers read by a request.
+pub const MAX_READ_REGISTERS: u16 = 125;
+
+/// Maximum number of coils written by a request.
+pub const MAX_WRITE_BITS: u16 = 1968;
+
+/// Maximum number of registers written by a request.
+pub const MAX_WRITE_REGISTERS: u16 = 123;
+
+/// Read coils function code.
+const READ_COILS: u8 = 0x01;
+
+/// Read discrete inputs function code.
+const READ_DISCRETE_INPUTS: u8 = 0x02;
+
+/// Read holding registers function code.
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+
+/// Read input registers function code.
+const READ_INPUT_REGISTERS: u8 = 0x04;
+
+/// Write single coil function code.
+const WRITE_SINGLE_COIL: u8 = 0x05;
+
+/// Write single register function code.
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+
+/// Write multiple coils function code.
+const WRITE_MULTIPLE_COILS: u8 = 0x0F;
+
+/// Write multiple registers function code.
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+/// Exception flag of the function codes.
+const EXCEPTION_FLAG: u8 = 0x80;
+
+/// Coil value `ON` of the write single coil function.
+const COIL_ON: u16 = 0xFF00;
+
+/// Modbus protocol identifier of the MBAP header.
+const PROTOCOL_ID: u16 = 0;
+
+/// Size of the MBAP header, unit identifier included.
+const MBAP_LEN: usize = 7;
+
+/// Maximum size of a PDU.
+const MAX_PDU_LEN: usize = 253;
+
+/// Modbus request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModbusRequest {
+    /// Reads coils.
+    ReadCoils {
+        /// Address of the first coil.
+        address: u16,
+        /// Number of coils, at most [`MAX_READ_BITS`].
+        count: u16,
+    },
+    /// Reads discrete inputs.
+    ReadDiscreteInputs {
+        /// Address of the first input.
+        address: u16,
+        /// Number of inputs, at most [`MAX_READ_BITS`].
+        count: u16,
+    },
+    /// Reads holding registers.
+    ReadHoldingRegisters {
+        /// Address of the first register.
+        address: u16,
+        /// Number of registers, at most [`MAX_READ_REGISTERS`].
+        count: u16,
+    },
+    /// Reads input registers.
+    ReadInputRegisters {
+        /// Address of the first register.
+        address: u16,
+        /// Number of registers, at most [`MAX_READ_REGISTERS`].
+        count: u16,
+    },
+    /// Writes a coil.
+    WriteSingleCoil {
+        /// Address of the coil.
+        address: u16,
+        /// Coil value.
+        value: bool,
+    },
+    /// Writes a holding register.
+    WriteSingleRegister {
+        /// Address of the register.
+        address: u16,
+        /// Register value.
+        value: u16,
+    },
+    /// Writes coils.
+    WriteMultipleCoils {
+        /// Address of the first coil.
+        address: u16,
+        /// Coil values, at most [`MAX_WRITE_BITS`].
+        values: Vec<bool>,
+    },
+    /// Writes holding registers.
+    WriteMultipleRegisters {
+        /// Address of the first register.
+        address: u16,
+        /// Register values, at most [`MAX_WRITE_REGISTERS`].
+        values: Vec<u16>,
+    },
+}
+
+impl ModbusRequest {
+    /// Returns the function code of the request.
+    pub fn function_code(&self) -> u8 {
+        match self {
+            Self::ReadCoils { .. } => READ_COILS,
+            Self::ReadDiscreteInputs { .. } => READ_DISCRETE_INPUTS,
+            Self::ReadHoldingRegisters { .. } => READ_HOLDING_REGISTERS,
+            Self::ReadInputRegisters { .. } => READ_INPUT_REGISTERS,
+            Self::WriteSingleCoil { .. } => WRITE_SINGLE_COIL,
+            Self::WriteSingleRegister { .. } => WRITE_SINGLE_REGISTER,
+            Self::WriteMultipleCoils { .. } => WRITE_MULTIPLE_COILS,
+            Self::WriteMultipleRegisters { .. } => WRITE_MULTIPLE_REGISTERS,
+        }
+    }
+
+    /// Encodes the PDU of the request.
+    ///
+    /// Counts are not checked, so that servers can be tested with invalid
+    /// requests; the number of values written is however truncated to the
+    /// maximum size of a PDU.
+    pub fn encode(&self) -> Bytes {
+        let mut pdu = BytesMut::with_capacity(MAX_PDU_LEN);
+        pdu.put_u8(self.function_code());
+        match self {
+            Self::ReadCoils { address, count }
+            | Self::ReadDiscreteInputs { address, count }
+            | Self::ReadHoldingRegisters { address, count }
+            | Self::ReadInputRegisters { address, count } => {
+                pdu.put_u16(*address);
+                pdu.put_u16(*count);
+            }
+            Self::WriteSingleCoil { address, value } => {
+                pdu.put_u16(*address);
+                pdu.put_u16(if *value { COIL_ON } else { 0 });
+            }
+            Self::WriteSingleRegister { address, value } => {
+                pdu.put_u16(*address);
+                pdu.put_u16(*value);
+            }
+            Self::WriteMultipleCoils { address, values } => {
+                let values = &values[..values.len().min(8 * (MAX_PDU_LEN - 6))];
+                pdu.put_u16(*address);
+                pdu.put_u16(values.len() as u16);
+                pdu.put_u8(values.len().div_ceil(8) as u8);
+                put_bits(&mut pdu, values);
+            }
+            Self::WriteMultipleRegisters { address, values } => {
+                let values = &values[..values.len().min((MAX_PDU_LEN - 6) / 2)];
+                pdu.put_u16(*address);
+                pdu.put_u16(values.len() as u16);
+                pdu.put_u8(2 * values.len() as u8);
+                for value in values {
+                    pdu.put_u16(*value);
+                }
+            }
+        }
+
+        pdu.freeze()
+    }
+
+    /// Decodes the PDU of a request.
+    ///
+    /// The exception to be returned by a server is returned if the function
+    /// code is not supported or if the request is malformed.
+    pub fn decode(pdu: &[u8]) -> Result<Self, ModbusException> {
+        let (&function, data) = pdu.split_first().ok_or(ModbusException::IllegalFunction)?;
+        let field = |i: usize| -> Result<u16, ModbusException> {
+            data.get(2 * i..2 * i + 2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+                .ok_or(ModbusException::IllegalDataValue)
+        };
+        let read_count = |max: u16| -> Result<u16, ModbusException> {
+            match field(1)? {
+                count @ 1.. if count <= max => Ok(count),
+                _ => Err(ModbusException::IllegalDataValue),
+            }
+        };
+
+        match function {
+            READ_COILS => Ok(Self::ReadCoils {
+                address: field(0)?,
+                count: read_count(MAX_READ_BITS)?,
+            }),
+            READ_DISCRETE_INPUTS => Ok(Self::ReadDiscreteInputs {
+                address: field(0)?,
+                count: read_count(MAX_READ_BITS)?,
+            }),
+            READ_HOLDING_REGISTERS => Ok(Self::ReadHoldingRegisters {
+                address: field(0)?,
+                count: read_count(MAX_READ_REGISTERS)?,
+            }),
+            READ_INPUT_REGISTERS => Ok(Self::ReadInputRegisters {
+                address: field(0)?,
+                count: read_count(MAX_READ_REGISTERS)?,
+            }),
+            WRITE_SINGLE_COIL => Ok(Self::WriteSingleCoil {
+                address: field(0)?,
+                value: match field(1)? {
+                    COIL_ON => true,
+                    0 => false,
+                    _ => return Err(ModbusException::IllegalDataValue),
+                },
+            }),
+            WRITE_SINGLE_REGISTER => Ok(Self::WriteSingleRegister {
+                address: field(0)?,
+                value: field(1)?,
+            }),
+            WRITE_MULTIPLE_COILS => {
+                let count = read_count(MAX_WRITE_BITS)?;
+                let bytes = &data[4..];
+                if bytes.first().map(|&len| usize::from(len))
+                    != Some(usize::from(count).div_ceil(8))
+                    || bytes.len() < 1 + usize::from(count).div_ceil(8)
+                {
+                    return Err(ModbusException::IllegalDataValue);
+                }
+                let mut values = get_bits(&bytes[1..]);
+                values.truncate(usize::from(count));
+
+                Ok(Self::WriteMultipleCoils {
+                    address: field(0)?,
+                    values,
+                })
+            }
+            WRITE_MULTIPLE_REGISTERS => {
+                let count = read_count(MAX_WRITE_REGISTERS)?;
+                let bytes = &data[4..];
+                if bytes.first().map(|&len| usize::from(len)) != Some(2 * usize::from(count))
+                    || bytes.len() < 1 + 2 * usize::from(count)
+                {
+                    return Err(ModbusException::IllegalDataValue);
+                }
+
+                Ok(Self::WriteMultipleRegisters {
+                    address: field(0)?,
+                    values: get_registers(&bytes[1..1 + 2 * usize::from(count)]),
+                })
+            }
+            _ => Err(ModbusException::IllegalFunction),
+        }
+    }
+}
+
+/// Modbus response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModbusResponse {
+    /// Coil values.
+    ReadCoils(Vec<bool>),
+    /// Discrete input values.
+    ReadDiscreteInputs(Vec<bool>),
+    /// Holding register values.
+    ReadHoldingRegisters(Vec<u16>),
+    /// Input register values.
+    ReadInputRegisters(Vec<u16>),
+    /// Written coil.
+    WriteSingleCoil {
+        /// Address of the coil.
+        address: u16,
+        /// Coil value.
+        value: bool,
+    },
+    /// Written holding register.
+    WriteSingleRegister {
+        /// Address of the register.
+        address: u16,
+        /// Register value.
+        value: u16,
+    },
+    /// Written coils.
+    WriteMultipleCoils {
+        /// Address of the first coil.
+        address: u16,
+        /// Number of coils.
+        count: u16,
+    },
+    /// Written holding registers.
+    WriteMultipleRegisters {
+        /// Address of the first register.
+        address: u16,
+        /// Number of registers.
+        count: u16,
+    },
+}
+
+impl ModbusResponse {
+    /// Returns the function code of the response.
+    pub fn function_code(&self) -> u8 {
+        match self {
+            Self::ReadCoils(_) => READ_COILS,
+            Self::ReadDiscreteInputs(_) => READ_DISCRETE_INPUTS,
+            Self::ReadHoldingRegisters(_) => READ_HOLDING_REGISTERS,
+            Self::ReadInputRegisters(_) => READ_INPUT_REGISTERS,
+            Self::WriteSingleCoil { .. } => WRITE_SINGLE_COIL,
+            Self::WriteSingleRegister { .. } => WRITE_SINGLE_REGISTER,
+            Self::WriteMultipleCoils { .. } => WRITE_MULTIPLE_COILS,
+            Self::WriteMultipleRegisters { .. } => WRITE_MULTIPLE_REGISTERS,
+        }
+    }
+
+    /// Encodes the PDU of the response.
+    pub fn encode(&self) -> Bytes {
+        let mut pdu = BytesMut::with_capacity(MAX_PDU_LEN);
+        pdu.put_u8(self.function_code());
+        match self {
+            Self::ReadCoils(values) | Self::ReadDiscreteInputs(values) => {
+                let values = &values[..values.len().min(usize::from(MAX_READ_BITS))];
+                pdu.put_u8(values.len().div_ceil(8) as u8);
+                put_bits(&mut pdu, values);
+            }
+            Self::ReadHoldingRegisters(values) | Self::ReadInputRegisters(values) => {
+                let values = &values[..values.len().min(usize::from(MAX_READ_REGISTERS))];
+                pdu.put_u8(2 * values.len() as u8);
+                for value in values {
+                    pdu.put_u16(*value);
+                }
+            }
+            Self::WriteSingleCoil { address, value } => {
+                pdu.put_u16(*address);
+                pdu.put_u16(if *value { COIL_ON } else { 0 });
+            }
+            Self::WriteSingleRegister { address, value } => {
+                pdu.put_u16(*address);
+                pdu.put_u16(*value);
+            }
+            Self::WriteMultipleCoils { address, count }
+            | Self::WriteMultipleRegisters { address, count } => {
+                pdu.put_u16(*address);
+                pdu.put_u16(*count);
+            }
+        }
+
+        pdu.freeze()
+    }
+
+    /// Decodes the PDU of a response.
+    ///
+    /// Bit values are decoded by whole bytes, so the values beyond the
+    /// requested count are zero padding.
+    pub fn decode(pdu: &[u8]) -> Result<Self, ModbusError> {
+        let (&function, data) = pdu.split_first().ok_or(ModbusError::InvalidResponse)?;
+        if function & EXCEPTION_FLAG != 0 {
+            return match data {
+                [code] => Err(ModbusError::Exception(ModbusException::from_code(*code))),
+                _ => Err(ModbusError::InvalidResponse),
+            };
+        }
+        let field = |i: usize| -> Result<u16, ModbusError> {
+            data.get(2 * i..2 * i + 2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+                .ok_or(ModbusError::InvalidResponse)
+        };
+        let payload = || -> Result<&[u8], ModbusError> {
+            match data.split_first() {
+                Some((&len, bytes)) if bytes.len() == usize::from(len) => Ok(bytes),
+                _ => Err(ModbusError::InvalidResponse),
+            }
+        };
+
+        match function {
+            READ_COILS => Ok(Self::ReadCoils(get_bits(payload()?))),
+            READ_DISCRETE_INPUTS => Ok(Self::ReadDiscreteInputs(get_bits(payload()?))),
+            READ_HOLDING_REGISTERS => Ok(Self::ReadHoldingRegisters(get_registers(payload()?))),
+            READ_INPUT_REGISTERS => Ok(Self::ReadInputRegisters(get_registers(payload()?))),
+            WRITE_SINGLE_COIL => Ok(Self::WriteSingleCoil {
+                address: field(0)?,
+                value: field(1)? == COIL_ON,
+            }),
+            WRITE_SINGLE_REGISTER => Ok(Self::WriteSingleRegister {
+                address: field(0)?,
+                value: field(1)?,
+            }),
+            WRITE_MULTIPLE_COILS => Ok(Self::WriteMultipleCoils {
+                address: field(0)?,
+                count: field(1)?,
+            }),
+            WRITE_MULTIPLE_REGISTERS => Ok(Self::WriteMultipleRegisters {
+                address: field(0)?,
+                count: field(1)?,
+            }),
+            _ => Err(ModbusError::InvalidResponse),
+        }
+    }
+}
+
+/// Modbus exception.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ModbusException {
+    /// The function code is not supported.
+    IllegalFunction,
+    /// The addressed data is out of range.
+    IllegalDataAddress,
+    /// A value of the request is invalid.
+    IllegalDataValue,
+    /// The server failed to handle the request.
+    ServerDeviceFailure,
+    /// Other exception, with its code.
+    Other(u8),
+}
+
+impl ModbusException {
+    /// Returns the exception code.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::IllegalFunction => 0x01,
+            Self::IllegalDataAddress => 0x02,
+            Self::IllegalDataValue => 0x03,
+            Self::ServerDeviceFailure => 0x04,
+            Self::Other(code) => code,
+        }
+    }
+
+    /// Returns the exception of a code.
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            0x01 => Self::IllegalFunction,
+            0x02 => Self::IllegalDataAddress,
+            0x03 => Self::IllegalDataValue,
+            0x04 => Self::ServerDeviceFailure,
+            code => Self::Other(code),
+        }
+    }
+
+    /// Encodes the exception response PDU to a function.
+    pub fn encode(self, function_code: u8) -> Bytes {
+        Bytes::from(vec![function_code | EXCEPTION_FLAG, self.code()])
+    }
+}
+
+/// Modbus request failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModbusError {
+    /// The server answered with an exception.
+    Exception(ModbusException),
+    /// The response is malformed or does not match the request.
+    InvalidResponse,
+    /// No response was received in time.
+    Timeout,
+    /// The connection was lost before the response was received.
+    Disconnected,
+}
+
+impl fmt::Display for ModbusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Exception(exception) => {
+                write!(f, "exception {:#04x} ({exception:?})", exception.code())
+            }
+            Self::InvalidResponse => write!(f, "invalid response"),
+            Self::Timeout => write!(f, "response timeout"),
+            Self::Disconnected => write!(f, "connection lost"),
+        }
+    }
+}
+
+impl Error for ModbusError {}
+
+/// Appends bit values, packed least significant bit first.
+fn put_bits(buf: &mut BytesMut, values: &[bool]) {
+    for chunk in values.chunks(8) {
+        let byte = chunk
+            .iter()
+            .enumerate()
+            .fold(0u8, |byte, (i, &value)| byte | (u8::from(value) << i));
+        buf.put_u8(byte);
+    }
+}
+
+/// Returns the bit values of bytes, packed least significant bit first.
+fn get_bits(bytes: &[u8]) -> Vec<bool> {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| byte & (1 << i) != 0))
+        .collect()
+}
+
+/// Returns the big-endian register values of bytes.
+fn get_registers(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .collect()
+}
+
+/// Encodes an ADU.
+fn encode_adu(transaction_id: u16, unit_id: u8, pdu: &[u8]) -> Bytes {
+    let mut adu = BytesMut::with_capacity(MBAP_LEN + pdu.len());
+    adu.put_u16(transaction_id);
+    adu.put_u16(PROTOCOL_ID);
+    adu.put_u16(pdu.len() as u16 + 1);
+    adu.put_u8(unit_id);
+    adu.put_slice(pdu);
+
+    adu.freeze()
+}
+
+/// Returns the length of the ADU at the start of a buffer.
+///
+/// Returns `Ok(None)` if the header is incomplete, or an error if the header is
+/// invalid, in which case the stream cannot be resynchronized.
+fn adu_len(buf: &[u8]) -> Result<Option<usize>, ()> {
+    if buf.len() < MBAP_LEN {
+        return Ok(None);
+    }
+    let protocol_id = u16::from_be_bytes([buf[2], buf[3]]);
+    let len = usize::from(u16::from_be_bytes([buf[4], buf[5]]));
+    if protocol_id != PROTOCOL_ID || !(2..=MAX_PDU_LEN + 1).contains(&len) {
+        return Err(());
+    }
+
+    Ok(Some(6 + len))
+}
+
+/// Reply to a Modbus request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModbusReply {
+    /// Transaction identifier of the request.
+    pub transaction_id: u16,
+
+    /// Request.
+    pub request: ModbusRequest,
+
+    /// Response of the server, or failure.
+    pub result: Result<ModbusResponse, ModbusError>,
+}
+
+/// Request awaiting its response.
+struct PendingRequest {
+    /// Transaction identifier.
+    transaction_id: u16,
+
+    /// Request.
+    request: ModbusRequest,
+}
+
+/// Modbus TCP client layer.
+///
+/// This model:
+/// * encodes the requests from the request input and sends them to the TCP
+///   connection, with a new transaction identifier,
+/// * decodes the responses received from the TCP connection and forwards them
+///   with their request to the reply output,
+/// * fails the pending requests when the connection is lost or, if a timeout
+///   is set, when no response is received in time.
+///
+/// Bit values of read responses are truncated to the requested count.
+pub struct ModbusClient {
+    /// Request ADUs to be sent -- output port.
+    pub bytes_out: Output<Bytes>,
+
+    /// Replies to the requests -- output port.
+    pub reply_out: Output<ModbusReply>,
+
+    /// Unit identifier of the requests.
+    unit_id: u8,
+
+    /// Response timeout, if any.
+    timeout: Option<Duration>,
+
+    /// Transaction identifier of the next request.
+    transaction_id: u16,
+
+    /// Requests awaiting their response.
+    pending: Vec<PendingRequest>,
+
+    /// Received bytes not yet decoded.
+    buffer: Vec<u8>,
+}
+
+impl ModbusClient {
+    /// Creates a new Modbus TCP client layer.
+    ///
+    /// Requests are sent with unit identifier `0xFF` and without timeout.
+    pub fn new() -> Self {
+        Self {
+            bytes_out: Output::new(),
+            reply_out: Output::new(),
+            unit_id: 0xFF,
+            timeout: None,
+            transaction_id: 0,
+            pending: Vec::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Sets the unit identifier of the requests, e.g. to address a device
+    /// behind a gateway.
+    pub fn with_unit_id(mut self, unit_id: u8) -> Self {
+        self.unit_id = unit_id;
+        self
+    }
+
+    /// Sets the time after which a request without response fails, in
+    /// simulation time.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Request to be sent -- input port.
+    pub async fn request_in(&mut self, request: ModbusRequest, cx: &mut Context<Self>) {
+        let transaction_id = self.transaction_id;
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        let adu = encode_adu(transaction_id, self.unit_id, &request.encode());
+        self.pending.push(PendingRequest {
+            transaction_id,
+            request,
+        });
+        if let Some(timeout) = self.timeout {
+            cx.schedule_event(timeout, Self::expire, transaction_id)
+                .unwrap();
+        }
+        self.bytes_out.send(adu).await;
+    }
+
+    /// Bytes received from the TCP connection -- input port.
+    pub async fn bytes_in(&mut self, data: Bytes) {
+        self.buffer.extend_from_slice(&data);
+        loop {
+            let len = match adu_len(&self.buffer) {
+                Ok(Some(len)) if self.buffer.len() >= len => len,
+                Ok(_) => return,
+                Err(()) => {
+                    self.buffer.clear();
+                    return;
+                }
+            };
+            let adu: Vec<u8> = self.buffer.drain(..len).collect();
+            let transaction_id = u16::from_be_bytes([adu[0], adu[1]]);
+            let Some(index) = self
+                .pending
+                .iter()
+                .position(|pending| pending.transaction_id == transaction_id)
+            else {
+                continue;
+            };
+            let pending = self.pending.remove(index);
+            let result = match_response(&pending.request, &adu[MBAP_LEN..]);
+            self.reply_out
+                .send(ModbusReply {
+                    transaction_id,
+                    request: pending.request,
+                    result,
+                })
+                .await;
+        }
+    }
+
+    /// TCP connection status -- input port.
+    ///
+    /// Pending requests fail when the connection is lost.
+    pub async fn status_in(&mut self, status: TcpStatus) {
+        self.buffer.clear();
+        if let TcpStatus::Disconnected(_) = status {
+            for pending in std::mem::take(&mut self.pending) {
+                self.reply_out
+                    .send(ModbusReply {
+                        transaction_id: pending.transaction_id,
+                        request: pending.request,
+                        result: Err(ModbusError::Disconnected),
+                    })
+                    .await;
+            }
+        }
+    }
+
+    /// Fails a request if it is still pending.
+    async fn expire(&mut self, transaction_id: u16) {
+        let Some(index) = self
+            .pending
+            .iter()
+            .position(|pending| pending.transaction_id == transaction_id)
+        else {
+            return;
+        };
+        let pending = self.pending.remove(index);
+        self.reply_out
+            .send(ModbusReply {
+                transaction_id,
+                request: pending.request,
+                result: Err(ModbusError::Timeout),
+            })
+            .await;
+    }
+}
+
+/// Decodes the response to a request.
+fn match_response(request: &ModbusRequest, pdu: &[u8]) -> Result<ModbusResponse, ModbusError> {
+    if pdu.first().map(|function| function & !EXCEPTION_FLAG) != Some(request.function_code()) {
+        return Err(ModbusError::InvalidResponse);
+    }
+    let mut response = ModbusResponse::decode(pdu)?;
+    match (request, &mut response) {
+        (
+            ModbusRequest::ReadCoils { count, .. }
+            | ModbusRequest::ReadDiscreteInputs { count, .. },
+            ModbusResponse::ReadCoils(values) | ModbusResponse::ReadDiscreteInputs(values),
+        ) => {
+            if values.len() < usize::from(*count) {
+                return Err(ModbusError::InvalidResponse);
+            }
+            values.truncate(usize::from(*count));
+        }
+        (
+            ModbusRequest::ReadHoldingRegisters { count, .. }
+            | ModbusRequest::ReadInputRegisters { count, .. },
+            ModbusResponse::ReadHoldingRegisters(values)
+            | ModbusResponse::ReadInputRegisters(values),
+        ) if values.len() != usize::from(*count) => {
+            return Err(ModbusError::InvalidResponse);
+        }
+        _ => {}
+    }
+
+    Ok(response)
+}
+
+impl Default for ModbusClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Model for ModbusClient {}
+
+impl fmt::Debug for ModbusClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ModbusClient")
+            .field("unit_id", &self.unit_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Modbus TCP server model instance configuration.
+#[derive(Config, Debug)]
+pub struct ModbusServerConfig {
+    /// Local address, as `HOST:PORT`, to listen on.
+    pub listen_address: String,
+
+    /// Unit identifier of the server.
+    ///
+    /// If a value is provided, requests addressed to other units are not
+    /// answered. Otherwise, all requests are answered.
+    pub unit_id: Option<u8>,
+
+    /// Number of coils, from address 0.
+    pub coils: usize,
+
+    /// Number of discrete inputs, from address 0.
+    pub discrete_inputs: usize,
+
+    /// Number of holding registers, from address 0.
+    pub holding_registers: usize,
+
+    /// Number of input registers, from address 0.
+    pub input_registers: usize,
+
+    /// Delay for the first scheduled request handling.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which received requests are handled.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time requests are handled. If no value is
+    /// provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// Coil or discrete input values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModbusBits {
+    /// Address of the first value.
+    pub address: u16,
+
+    /// Values.
+    pub values: Vec<bool>,
+}
+
+/// Holding or input register values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModbusRegisters {
+    /// Address of the first value.
+    pub address: u16,
+
+    /// Values.
+    pub values: Vec<u16>,
+}
+
+/// Listener token.
+const LISTENER: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// Token of the first client connection.
+const FIRST_CLIENT: usize = 2;
+
+/// Request received by the server.
+struct ServerRequest {
+    /// Client slot.
+    index: usize,
+
+    /// Client connection identifier.
+    id: u64,
+
+    /// Transaction identifier.
+    transaction_id: u16,
+
+    /// Unit identifier.
+    unit_id: u8,
+
+    /// Request PDU.
+    pdu: Bytes,
+}
+
+/// Response sent by the server.
+struct ServerResponse {
+    /// Client slot.
+    index: usize,
+
+    /// Client connection identifier.
+    id: u64,
+
+    /// Response ADU.
+    adu: Bytes,
+}
+
+/// Client connection.
+struct Client {
+    /// Connection identifier, distinguishing successive connections in the
+    /// same slot.
+    id: u64,
+
+    /// Connection.
+    stream: TcpStream,
+
+    /// Received bytes not yet decoded.
+    request: Vec<u8>,
+
+    /// Response bytes not yet written.
+    response: WriteBuffer,
+}
+
+/// Modbus TCP server port.
+struct ModbusServerInner {
+    /// Local address to listen on.
+    address: String,
+
+    /// Listener, once registered.
+    listener: Option<TcpListener>,
+
+    /// Clients, indexed by token.
+    clients: Vec<Option<Client>>,
+
+    /// Identifier of the next connection.
+    next_id: u64,
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+
+    /// Received requests not yet read.
+    events: VecDeque<ServerRequest>,
+
+    /// MIO registry, available once the port is registered.
+    registry: Option<Registry>,
+}
+
+impl ModbusServerInner {
+    /// Creates a Modbus TCP server port, listening once registered.
+    fn new(config: &ModbusServerConfig) -> Self {
+        Self {
+            address: config.listen_address.clone(),
+            listener: None,
+            clients: Vec::new(),
+            next_id: 0,
+            buffer: vec![0; 4096],
+            events: VecDeque::new(),
+            registry: None,
+        }
+    }
+
+    /// Accepts the pending client connections.
+    fn accept(&mut self) -> IoResult<()> {
+        loop {
+            let Some(listener) = &self.listener else {
+                return Ok(());
+            };
+            let mut stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::Interrupted | ErrorKind::ConnectionAborted
+                    ) =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let index = match self.clients.iter().position(Option::is_none) {
+                Some(index) => index,
+                None => {
+                    self.clients.push(None);
+                    self.clients.len() - 1
+                }
+            };
+            if let Some(registry) = &self.registry {
+                if registry
+                    .register(
+                        &mut stream,
+                        Token(FIRST_CLIENT + index),
+                        Interest::READABLE | Interest::WRITABLE,
+                    )
+                    .is_err()
+                {
+                    continue;
+                }
+            }
+            let _ = stream.set_nodelay(true);
+            self.clients[index] = Some(Client {
+                id: self.next_id,
+                stream,
+                request: Vec::new(),
+                response: WriteBuffer::new(),
+            });
+            self.next_id += 1;
+            // Requests may have been received with the connection.
+            self.receive(index);
+        }
+    }
+
+    /// Reads the requests of a client.
+    ///
+    /// The connection is closed when the client closes it, on errors and on
+    /// invalid headers.
+    fn receive(&mut self, index: usize) {
+        let Some(client) = self.clients.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        loop {
+            match client.stream.read(&mut self.buffer) {
+                Ok(0) => {
+                    self.close(index);
+                    return;
+                }
+                Ok(len) => client.request.extend_from_slice(&self.buffer[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.close(index);
+                    return;
+                }
+            }
+        }
+        loop {
+            let len = match adu_len(&client.request) {
+                Ok(Some(len)) if client.request.len() >= len => len,
+                Ok(_) => return,
+                Err(()) => {
+                    self.close(index);
+                    return;
+                }
+            };
+            let adu: Vec<u8> = client.request.drain(..len).collect();
+            self.events.push_back(ServerRequest {
+                index,
+                id: client.id,
+                transaction_id: u16::from_be_bytes([adu[0], adu[1]]),
+                unit_id: adu[6],
+                pdu: Bytes::copy_from_slice(&adu[MBAP_LEN..]),
+            });
+        }
+    }
+
+    /// Writes the pending responses of a client.
+    fn resume_write(&mut self, index: usize) {
+        let Some(client) = self.clients.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        if client.response.flush(&mut client.stream).is_err() {
+            self.close(index);
+        }
+    }
+
+    /// Closes a client connection.
+    fn close(&mut self, index: usize) {
+        if let Some(mut client) = self.clients[index].take() {
+            if let Some(registry) = &self.registry {
+                let _ = registry.deregister(&mut client.stream);
+            }
+        }
+    }
+}
+
+impl IoPort<TcpListener, ServerRequest, ServerResponse> for ModbusServerInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        let address = self.address.to_socket_addrs()?.next().ok_or_else(|| {
+            std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Cannot resolve address {}.", self.address),
+            )
+        })?;
+        let mut listener = TcpListener::bind(address)?;
+        registry.register(&mut listener, LISTENER, Interest::READABLE)?;
+        self.listener = Some(listener);
+        self.registry = Some(registry.try_clone()?);
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<ServerRequest> {
+        if self.events.is_empty() {
+            if token == LISTENER {
+                self.accept()?;
+            } else if let Some(index) = token.0.checked_sub(FIRST_CLIENT) {
+                self.receive(index);
+            }
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        if let Some(index) = token.0.checked_sub(FIRST_CLIENT) {
+            self.resume_write(index);
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, response: &ServerResponse) -> IoResult<()> {
+        // Responses to closed connections are discarded.
+        let Some(client) = self
+            .clients
+            .get_mut(response.index)
+            .and_then(Option::as_mut)
+            .filter(|client| client.id == response.id)
+        else {
+            return Ok(());
+        };
+        client.response.push(&response.adu);
+        self.resume_write(response.index);
+
+        Ok(())
+    }
+}
+
+/// Modbus TCP server model.
+///
+/// This model:
+/// * answers the requests of the Modbus TCP clients from its coils, discrete
+///   inputs, holding registers and input registers,
+/// * forwards the coils and holding registers written by the clients to the
+///   model outputs,
+/// * updates its coils, discrete inputs, holding registers and input
+///   registers from the model inputs,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+///
+/// Requests are answered when they are handled by [`process`](Self::process).
+/// Requests addressing values beyond the configured counts are answered with
+/// an illegal data address exception.
+pub struct ModbusServer {
+    /// Coils written by a client -- output port.
+    pub coils_out: Output<ModbusBits>,
+
+    /// Holding registers written by a client -- output port.
+    pub holding_registers_out: Output<ModbusRegisters>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: ModbusServerConfig,
+
+    /// Coils.
+    coils: Vec<bool>,
+
+    /// Discrete inputs.
+    discrete_inputs: Vec<bool>,
+
+    /// Holding registers.
+    holding_registers: Vec<u16>,
+
+    /// Input registers.
+    input_registers: Vec<u16>,
+
+    /// I/O thread.
+    io_thread: IoThread<ServerRequest, ServerResponse>,
+}
+
+impl ModbusServer {
+    /// Updates coils -- input port.
+    ///
+    /// Values beyond the configured count are ignored.
+    pub async fn coils_in(&mut self, bits: ModbusBits) {
+        update(&mut self.coils, bits.address, &bits.values);
+    }
+
+    /// Updates discrete inputs -- input port.
+    ///
+    /// Values beyond the configured count are ignored.
+    pub async fn discrete_inputs_in(&mut self, bits: ModbusBits) {
+        update(&mut self.discrete_inputs, bits.address, &bits.values);
+    }
+
+    /// Updates holding registers -- input port.
+    ///
+    /// Values beyond the configured count are ignored.
+    pub async fn holding_registers_in(&mut self, registers: ModbusRegisters) {
+        update(
+            &mut self.holding_registers,
+            registers.address,
+            &registers.values,
+        );
+    }
+
+    /// Updates input registers -- input port.
+    ///
+    /// Values beyond the configured count are ignored.
+    pub async fn input_registers_in(&mut self, registers: ModbusRegisters) {
+        update(
+            &mut self.input_registers,
+            registers.address,
+            &registers.values,
+        );
+    }
+
+    /// Answers the received requests and forwards the I/O thread status --
+    /// input port.
+    pub async fn process(&mut self) {
+        for request in self.io_thread.try_recv_all() {
+            if self
+                .config
+                .unit_id
+                .is_some_and(|unit_id| unit_id != request.unit_id)
+            {
+                continue;
+            }
+            let pdu = self.serve(&request.pdu).await;
+            let adu = encode_adu(request.transaction_id, request.unit_id, &pdu);
+            let _ = self.io_thread.send(ServerResponse {
+                index: request.index,
+                id: request.id,
+                adu,
+            });
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+
+    /// Handles a request and returns the response PDU.
+    async fn serve(&mut self, pdu: &[u8]) -> Bytes {
+        let function_code = pdu.first().copied().unwrap_or_default();
+        let request = match ModbusRequest::decode(pdu) {
+            Ok(request) => request,
+            Err(exception) => return exception.encode(function_code),
+        };
+        let response = match request {
+            ModbusRequest::ReadCoils { address, count } => {
+                read(&self.coils, address, count).map(ModbusResponse::ReadCoils)
+            }
+            ModbusRequest::ReadDiscreteInputs { address, count } => {
+                read(&self.discrete_inputs, address, count).map(ModbusResponse::ReadDiscreteInputs)
+            }
+            ModbusRequest::ReadHoldingRegisters { address, count } => {
+                read(&self.holding_registers, address, count)
+                    .map(ModbusResponse::ReadHoldingRegisters)
+            }
+            ModbusRequest::ReadInputRegisters { address, count } => {
+                read(&self.input_registers, address, count).map(ModbusResponse::ReadInputRegisters)
+            }
+            ModbusRequest::WriteSingleCoil { address, value } => {
+                write(&mut self.coils, address, &[value])
+                    .map(|()| ModbusResponse::WriteSingleCoil { address, value })
+            }
+            ModbusRequest::WriteSingleRegister { address, value } => {
+                write(&mut self.holding_registers, address, &[value])
+                    .map(|()| ModbusResponse::WriteSingleRegister { address, value })
+            }
+            ModbusRequest::WriteMultipleCoils {
+                address,
+                ref values,
+            } => write(&mut self.coils, address, values).map(|()| {
+                ModbusResponse::WriteMultipleCoils {
+                    address,
+                    count: values.len() as u16,
+                }
+            }),
+            ModbusRequest::WriteMultipleRegisters {
+                address,
+                ref values,
+            } => write(&mut self.holding_registers, address, values).map(|()| {
+                ModbusResponse::WriteMultipleRegisters {
+                    address,
+                    count: values.len() as u16,
+                }
+            }),
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(exception) => return exception.encode(function_code),
+        };
+        match request {
+            ModbusRequest::WriteSingleCoil { address, value } => {
+                self.coils_out
+                    .send(ModbusBits {
+                        address,
+                        values: vec![value],
+                    })
+                    .await;
+            }
+            ModbusRequest::WriteMultipleCoils { address, values } => {
+                self.coils_out.send(ModbusBits { address, values }).await;
+            }
+            ModbusRequest::WriteSingleRegister { address, value } => {
+                self.holding_registers_out
+                    .send(ModbusRegisters {
+                        address,
+                        values: vec![value],
+                    })
+                    .await;
+            }
+            ModbusRequest::WriteMultipleRegisters { address, values } => {
+                self.holding_registers_out
+                    .send(ModbusRegisters { address, values })
+                    .await;
+            }
+            _ => {}
+        }
+
+        response.encode()
+    }
+}
+
+/// Updates the values of a table, ignoring the values beyond its end.
+fn update<T: Copy>(table: &mut [T], address: u16, values: &[T]) {
+    let start = usize::from(address).min(table.len());
+    let end = (start + values.len()).min(table.len());
+    table[start..end].copy_from_slice(&values[..end - start]);
+}
+
+/// Reads the values of a table.
+fn read<T: Copy>(table: &[T], address: u16, count: u16) -> Result<Vec<T>, ModbusException> {
+    table
+        .get(usize::from(address)..usize::from(address) + usize::from(count))
+        .map(<[T]>::to_vec)
+        .ok_or(ModbusException::IllegalDataAddress)
+}
+
+/// Writes the values of a table.
+fn write<T: Copy>(table: &mut [T], address: u16, values: &[T]) -> Result<(), ModbusException> {
+    table
+        .get_mut(usize::from(address)..usize::from(address) + values.len())
+        .ok_or(ModbusException::IllegalDataAddress)?
+        .copy_from_slice(values);
+
+    Ok(())
+}
+
+impl Model for ModbusServer {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for ModbusServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ModbusServer")
+            .field("listen_address", &self.config.listen_address)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Modbus TCP server model prototype.
+pub struct ProtoModbusServer {
+    /// Coils written by a client -- output port.
+    pub coils_out: Output<ModbusBits>,
+
+    /// Holding registers written by a client -- output port.
+    pub holding_registers_out: Output<ModbusRegisters>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Modbus TCP server model instance configuration.
+    config: ModbusServerConfig,
+}
+
+impl ProtoModbusServer {
+    /// Creates a new Modbus TCP server model prototype.
+    ///
+    /// All values are initially zero.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the listen address cannot be bound or if
+    /// the I/O thread cannot be created.
+    pub fn new(config: ModbusServerConfig) -> Self {
+        Self {
+            coils_out: Output::new(),
+            holding_registers_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoModbusServer {
+    type Model = ModbusServer;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        /// Number of addressable values.
+        const MAX_COUNT: usize = 1 << 16;
+
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(ModbusServerInner::new(&self.config), options)
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Failed to start the I/O thread of the Modbus server on {}: {e}.",
+                    self.config.listen_address
+                )
+            });
+
+        ModbusServer {
+            coils_out: self.coils_out,
+            holding_registers_out: self.holding_registers_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            coils: vec![false; self.config.coils.min(MAX_COUNT)],
+            discrete_inputs: vec![false; self.config.discrete_inputs.min(MAX_COUNT)],
+            holding_registers: vec![0; self.config.holding_registers.min(MAX_COUNT)],
+            input_registers: vec![0; self.config.input_registers.min(MAX_COUNT)],
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoModbusServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoModbusServer")
+            .field("listen_address", &self.config.listen_address)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coils(pattern: &[u8]) -> Vec<bool> {
+        pattern.iter().map(|&bit| bit != 0).collect()
+    }
+
+    #[test]
+    fn request_encoding() {
+        let request = ModbusRequest::ReadHoldingRegisters {
+            address: 0x006B,
+            count: 3,
+        };
+        assert_eq!(&request.encode()[..], [0x03, 0x00, 0x6B, 0x00, 0x03]);
+
+        let request = ModbusRequest::WriteMultipleCoils {
+            address: 0x0013,
+            values: coils(&[1, 0, 1, 1, 0, 0, 1, 1, 1, 0]),
+        };
+        assert_eq!(
+            &request.encode()[..],
+            [0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01]
+        );
+
+        let request = ModbusRequest::WriteSingleCoil {
+            address: 0x00AC,
+            value: true,
+        };
+        assert_eq!(&request.encode()[..], [0x05, 0x00, 0xAC, 0xFF, 0x00]);
+    }
+
+    #[test]
+    fn request_round_trip() {
+        let requests = [
+            ModbusRequest::ReadCoils {
+                address: 1,
+                count: MAX_READ_BITS,
+            },
+            ModbusRequest::ReadDiscreteInputs {
+                address: 2,
+                count: 1,
+            },
+            ModbusRequest::ReadHoldingRegisters {
+                address: 3,
+                count: MAX_READ_REGISTERS,
+            },
+            ModbusRequest::ReadInputRegisters {
+                address: 4,
+                count: 10,
+            },
+            ModbusRequest::WriteSingleCoil {
+                address: 5,
+                value: false,
+            },
+            ModbusRequest::WriteSingleRegister {
+                address: 6,
+                value: 0xBEEF,
+            },
+            ModbusRequest::WriteMultipleCoils {
+                address: 7,
+                values: coils(&[1, 1, 0, 1, 0, 0, 0, 0, 1]),
+            },
+            ModbusRequest::WriteMultipleRegisters {
+                address: 8,
+                values: vec![0x0102, 0x0304, 0xFFFF],
+            },
+        ];
+
+        for request in requests {
+            assert_eq!(ModbusRequest::decode(&request.encode()), Ok(request));
+        }
+    }
+
+    #[test]
+    fn request_decode_exceptions() {
+        let decode = |pdu: &[u8]| ModbusRequest::decode(pdu).unwrap_err();
+
+        assert_eq!(decode(&[]), ModbusException::IllegalFunction);
+        assert_eq!(
+            decode(&[0x2B, 0x0E, 0x01, 0x00]),
+            ModbusException::IllegalFunction
+        );
+        // Truncated request.
+        assert_eq!(
+            decode(&[0x03, 0x00, 0x6B]),
+            ModbusException::IllegalDataValue
+        );
+        // Counts out of range.
+        assert_eq!(
+            decode(&[0x01, 0x00, 0x00, 0x00, 0x00]),
+            ModbusException::IllegalDataValue
+        );
+        assert_eq!(
+            decode(&[0x03, 0x00, 0x00, 0x00, MAX_READ_REGISTERS as u8 + 1]),
+            ModbusException::IllegalDataValue
+        );
+        // Invalid coil value.
+        assert_eq!(
+            decode(&[0x05, 0x00, 0x00, 0x12, 0x34]),
+            ModbusException::IllegalDataValue
+        );
+        // Byte count inconsistent with the count, or missing values.
+        assert_eq!(
+            decode(&[0x0F, 0x00, 0x00, 0x00, 0x0A, 0x01, 0xCD]),
+            ModbusException::IllegalDataValue
+        );
+        assert_eq!(
+            decode(&[0x10, 0x00, 0x00, 0x00, 0x02, 0x04, 0x00, 0x01]),
+            ModbusException::IllegalDataValue
+        );
+    }
+
+    #[test]
+    fn response_round_trip() {
+        let responses = [
+            ModbusResponse::ReadCoils(coils(&[1, 0, 1, 1, 0, 0, 1, 1])),
+            ModbusResponse::ReadDiscreteInputs(coils(&[0, 0, 0, 0, 0, 0, 0, 1])),
+            ModbusResponse::ReadHoldingRegisters(vec![0x022B, 0x0000, 0x0064]),
+            ModbusResponse::ReadInputRegisters(vec![0x000A]),
+            ModbusResponse::WriteSingleCoil {
+                address: 0x00AC,
+                value: true,
+            },
+            ModbusResponse::WriteSingleRegister {
+                address: 0x0001,
+                value: 0x0003,
+            },
+            ModbusResponse::WriteMultipleCoils {
+                address: 0x0013,
+                count: 10,
+            },
+            ModbusResponse::WriteMultipleRegisters {
+                address: 0x0001,
+                count: 2,
+            },
+        ];
+
+        for response in responses {
+            assert_eq!(ModbusResponse::decode(&response.encode()), Ok(response));
+        }
+
+        // Bit values are padded to whole bytes.
+        let response = ModbusResponse::ReadCoils(coils(&[1, 0, 1]));
+        assert_eq!(&response.encode()[..], [0x01, 0x01, 0x05]);
+        assert_eq!(
+            ModbusResponse::decode(&response.encode()),
+            Ok(ModbusResponse::ReadCoils(coils(&[1, 0, 1, 0, 0, 0, 0, 0])))
+        );
+    }
+
+    #[test]
+    fn response_decode_errors() {
+        let decode = |pdu: &[u8]| ModbusResponse::decode(pdu).unwrap_err();
+
+        assert_eq!(decode(&[]), ModbusError::InvalidResponse);
+        assert_eq!(decode(&[0x2B, 0x00]), ModbusError::InvalidResponse);
+        // Byte count inconsistent with the payload.
+        assert_eq!(
+            decode(&[0x03, 0x04, 0x00, 0x01]),
+            ModbusError::InvalidResponse
+        );
+        assert_eq!(decode(&[0x06, 0x00, 0x01]), ModbusError::InvalidResponse);
+    }
+
+    #[test]
+    fn exception_responses() {
+        let pdu = ModbusException::IllegalDataAddress.encode(READ_HOLDING_REGISTERS);
+        assert_eq!(&pdu[..], [0x83, 0x02]);
+        assert_eq!(
+            ModbusResponse::decode(&pdu),
+            Err(ModbusError::Exception(ModbusException::IllegalDataAddress))
+        );
+
+        for code in 0..=u8::MAX {
+            assert_eq!(ModbusException::from_code(code).code(), code);
+        }
+        assert_eq!(
+            ModbusResponse::decode(&[0x90, 0x0B]),
+            Err(ModbusError::Exception(ModbusException::Other(0x0B)))
+        );
+
+        // Exception responses carry a single code.
+        assert_eq!(
+            ModbusResponse::decode(&[0x83]),
+            Err(ModbusError::InvalidResponse)
+        );
+        assert_eq!(
+            ModbusResponse::decode(&[0x83, 0x02, 0x00]),
+            Err(ModbusError::InvalidResponse)
+        );
+    }
+
+    #[test]
+    fn response_matching() {
+        let read_coils = ModbusRequest::ReadCoils {
+            address: 0,
+            count: 3,
+        };
+        let read_registers = ModbusRequest::ReadHoldingRegisters {
+            address: 0,
+            count: 2,
+        };
+
+        // Padding bits are removed.
+        assert_eq!(
+            match_response(&read_coils, &[0x01, 0x01, 0x05]),
+            Ok(ModbusResponse::ReadCoils(coils(&[1, 0, 1])))
+        );
+        assert_eq!(
+            match_response(&read_registers, &[0x03, 0x04, 0x00, 0x01, 0x00, 0x02]),
+            Ok(ModbusResponse::ReadHoldingRegisters(vec![1, 2]))
+        );
+        assert_eq!(
+            match_response(&read_registers, &[0x83, 0x04]),
+            Err(ModbusError::Exception(ModbusException::ServerDeviceFailure))
+        );
+
+        // Responses to another function or with the wrong count.
+        assert_eq!(
+            match_response(&read_coils, &[0x03, 0x02, 0x00, 0x01]),
+            Err(ModbusError::InvalidResponse)
+        );
+        assert_eq!(
+            match_response(&read_coils, &[0x82, 0x01]),
+            Err(ModbusError::InvalidResponse)
+        );
+        assert_eq!(
+            match_response(&read_registers, &[0x03, 0x02, 0x00, 0x01]),
+            Err(ModbusError::InvalidResponse)
+        );
+    }
+
+    #[test]
+    fn adu_framing() {
+        let adu = encode_adu(0x1234, 0x11, &[0x03, 0x00, 0x6B, 0x00, 0x03]);
+        assert_eq!(
+            &adu[..],
+            [
+                0x12, 0x34, 0x00, 0x00, 0x00, 0x06, 0x11, 0x03, 0x00, 0x6B, 0x00, 0x03
+            ]
+        );
+        assert_eq!(adu_len(&adu), Ok(Some(adu.len())));
+        assert_eq!(adu_len(&adu[..MBAP_LEN - 1]), Ok(None));
+
+        let mut invalid = adu.to_vec();
+        invalid[3] = 0x01;
+        assert_eq!(adu_len(&invalid), Err(()));
+        let mut invalid = adu.to_vec();
+        invalid[5] = 0x01;
+        assert_eq!(adu_len(&invalid), Err(()));
+    }
+
+    #[test]
+    fn table_access() {
+        let mut table = [0u16; 4];
+
+        assert_eq!(write(&mut table, 1, &[1, 2]), Ok(()));
+        assert_eq!(read(&table, 0, 4), Ok(vec![0, 1, 2, 0]));
+        assert_eq!(
+            write(&mut table, 3, &[3, 4]),
+            Err(ModbusException::IllegalDataAddress)
+        );
+        assert_eq!(read(&table, 2, 3), Err(ModbusException::IllegalDataAddress));
+
+        // Updates from the bench are truncated instead.
+        update(&mut table, 3, &[3, 4]);
+        update(&mut table, 10, &[5]);
+        assert_eq!(table, [0, 1, 2, 3]);
+    }
+}
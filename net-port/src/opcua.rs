@@ -0,0 +1,616 @@
+//! OPC UA server model.
+//!
+//! This module contains the [`OpcUaServer`] model, an OPC UA server exposing
+//! simulation variables as nodes, so that plant-level applications can read
+//! and subscribe to them, and mapping the writes of OPC UA clients to
+//! simulation inputs. It is available with the `opcua` feature.
+//!
+//! The variables declared in the configuration are added as nodes of a folder
+//! of the `Objects` folder, with string node identifiers equal to their names
+//! in the namespace of the simulation, e.g. `ns=2;s=tank.level`. Each value
+//! sent to the model input updates its node and is notified to the clients
+//! subscribed to it. Values written by clients to writable variables update
+//! their nodes and are forwarded to the model output. Values with another type
+//! than the configured type of their variable are rejected.
+//!
+//! The server exposes a single endpoint without security, accepting anonymous
+//! users.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::opcua::{OpcUaDataType, OpcUaServerConfig};
+//!
+//! let config = ConfigLoader::<OpcUaServerConfig>::new()
+//!     .code(
+//!         r#"
+//! port = 4840
+//! period = 100
+//!
+//! [[variables]]
+//! name = "tank.level"
+//!
+//! [[variables]]
+//! name = "pump.enabled"
+//! dataType = "boolean"
+//! writable = true
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.variables[0].data_type, OpcUaDataType::Double);
+//! ```
+
+use std::fmt;
+use std::io::{Error, ErrorKind, Result as IoResult};
+use std::sync::Arc;
+use std::time::Duration;
+
+use schematic::{Config, ConfigEnum};
+use serde::{Deserialize, Serialize};
+
+use opcua::server::ServerBuilder;
+use opcua::server::ServerHandle;
+use opcua::server::address_space::VariableBuilder;
+use opcua::server::diagnostics::NamespaceMetadata;
+use opcua::server::node_manager::memory::{SimpleNodeManager, simple_node_manager};
+use opcua::types::{ByteString, DataTypeId, DataValue, NodeId, StatusCode, UAString, Variant};
+
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+use tokio::task::JoinHandle;
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::async_port::{AsyncIoPort, AsyncIoThread};
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::IoThreadStatus;
+
+/// OPC UA server model instance configuration.
+#[derive(Config, Debug)]
+pub struct OpcUaServerConfig {
+    /// Local host name or address to listen on.
+    #[setting(default = "0.0.0.0")]
+    pub host: String,
+
+    /// TCP port to listen on.
+    #[setting(default = 4840)]
+    pub port: u16,
+
+    /// Application name.
+    #[setting(default = "NeXosim")]
+    pub application_name: String,
+
+    /// Application URI.
+    #[setting(default = "urn:nexosim")]
+    pub application_uri: String,
+
+    /// Namespace URI of the variables.
+    #[setting(default = "urn:nexosim:simulation")]
+    pub namespace: String,
+
+    /// Name of the folder of the variables.
+    #[setting(default = "Simulation")]
+    pub folder: String,
+
+    /// Directory of the certificate store, created if needed.
+    #[setting(default = "pki")]
+    pub pki_dir: String,
+
+    /// Variables.
+    #[setting(nested)]
+    pub variables: Vec<OpcUaVariableConfig>,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which written values are forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+}
+
+/// Variable configuration.
+#[derive(Config, Debug)]
+pub struct OpcUaVariableConfig {
+    /// Variable name, used as node identifier, browse name and display name.
+    pub name: String,
+
+    /// Data type.
+    pub data_type: OpcUaDataType,
+
+    /// Clients can write the variable.
+    pub writable: bool,
+}
+
+/// OPC UA data type of a variable.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OpcUaDataType {
+    /// Boolean.
+    #[serde(rename = "boolean")]
+    Boolean,
+
+    /// Signed 32-bit integer.
+    #[serde(rename = "int32")]
+    Int32,
+
+    /// Signed 64-bit integer.
+    #[serde(rename = "int64")]
+    Int64,
+
+    /// Unsigned 32-bit integer.
+    #[serde(rename = "uint32")]
+    UInt32,
+
+    /// Unsigned 64-bit integer.
+    #[serde(rename = "uint64")]
+    UInt64,
+
+    /// Single-precision float.
+    #[serde(rename = "float")]
+    Float,
+
+    /// Double-precision float.
+    #[default]
+    #[serde(rename = "double")]
+    Double,
+
+    /// String.
+    #[serde(rename = "string")]
+    String,
+
+    /// Byte string.
+    #[serde(rename = "bytestring")]
+    ByteString,
+}
+
+impl OpcUaDataType {
+    /// Returns the OPC UA data type identifier.
+    fn id(self) -> DataTypeId {
+        match self {
+            Self::Boolean => DataTypeId::Boolean,
+            Self::Int32 => DataTypeId::Int32,
+            Self::Int64 => DataTypeId::Int64,
+            Self::UInt32 => DataTypeId::UInt32,
+            Self::UInt64 => DataTypeId::UInt64,
+            Self::Float => DataTypeId::Float,
+            Self::Double => DataTypeId::Double,
+            Self::String => DataTypeId::String,
+            Self::ByteString => DataTypeId::ByteString,
+        }
+    }
+
+    /// Returns the default value of the data type.
+    fn default_value(self) -> OpcUaValue {
+        match self {
+            Self::Boolean => OpcUaValue::Boolean(false),
+            Self::Int32 => OpcUaValue::Int32(0),
+            Self::Int64 => OpcUaValue::Int64(0),
+            Self::UInt32 => OpcUaValue::UInt32(0),
+            Self::UInt64 => OpcUaValue::UInt64(0),
+            Self::Float => OpcUaValue::Float(0.0),
+            Self::Double => OpcUaValue::Double(0.0),
+            Self::String => OpcUaValue::String(String::new()),
+            Self::ByteString => OpcUaValue::ByteString(Vec::new()),
+        }
+    }
+}
+
+/// Value of a variable.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OpcUaValue {
+    /// Boolean.
+    Boolean(bool),
+
+    /// Signed 32-bit integer.
+    Int32(i32),
+
+    /// Signed 64-bit integer.
+    Int64(i64),
+
+    /// Unsigned 32-bit integer.
+    UInt32(u32),
+
+    /// Unsigned 64-bit integer.
+    UInt64(u64),
+
+    /// Single-precision float.
+    Float(f32),
+
+    /// Double-precision float.
+    Double(f64),
+
+    /// String.
+    String(String),
+
+    /// Byte string.
+    ByteString(Vec<u8>),
+}
+
+impl OpcUaValue {
+    /// Returns the data type of the value.
+    pub fn data_type(&self) -> OpcUaDataType {
+        match self {
+            Self::Boolean(_) => OpcUaDataType::Boolean,
+            Self::Int32(_) => OpcUaDataType::Int32,
+            Self::Int64(_) => OpcUaDataType::Int64,
+            Self::UInt32(_) => OpcUaDataType::UInt32,
+            Self::UInt64(_) => OpcUaDataType::UInt64,
+            Self::Float(_) => OpcUaDataType::Float,
+            Self::Double(_) => OpcUaDataType::Double,
+            Self::String(_) => OpcUaDataType::String,
+            Self::ByteString(_) => OpcUaDataType::ByteString,
+        }
+    }
+
+    /// Converts an OPC UA variant.
+    ///
+    /// Returns `None` for variants without equivalent value.
+    fn from_variant(variant: Variant) -> Option<Self> {
+        Some(match variant {
+            Variant::Boolean(value) => Self::Boolean(value),
+            Variant::Int32(value) => Self::Int32(value),
+            Variant::Int64(value) => Self::Int64(value),
+            Variant::UInt32(value) => Self::UInt32(value),
+            Variant::UInt64(value) => Self::UInt64(value),
+            Variant::Float(value) => Self::Float(value),
+            Variant::Double(value) => Self::Double(value),
+            Variant::String(value) => Self::String(value.value().clone().unwrap_or_default()),
+            Variant::ByteString(value) => Self::ByteString(value.value.unwrap_or_default()),
+            _ => return None,
+        })
+    }
+
+    /// Converts to an OPC UA variant.
+    fn into_variant(self) -> Variant {
+        match self {
+            Self::Boolean(value) => Variant::Boolean(value),
+            Self::Int32(value) => Variant::Int32(value),
+            Self::Int64(value) => Variant::Int64(value),
+            Self::UInt32(value) => Variant::UInt32(value),
+            Self::UInt64(value) => Variant::UInt64(value),
+            Self::Float(value) => Variant::Float(value),
+            Self::Double(value) => Variant::Double(value),
+            Self::String(value) => Variant::String(UAString::from(value)),
+            Self::ByteString(value) => Variant::ByteString(ByteString::from(value)),
+        }
+    }
+}
+
+/// Value of a variable, with its name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpcUaVariable {
+    /// Variable name.
+    pub name: String,
+
+    /// Value.
+    pub value: OpcUaValue,
+}
+
+impl OpcUaVariable {
+    /// Creates a new variable value.
+    pub fn new(name: impl Into<String>, value: OpcUaValue) -> Self {
+        Self {
+            name: name.into(),
+            value,
+        }
+    }
+}
+
+/// Variable node.
+struct Node {
+    /// Variable name.
+    name: String,
+
+    /// Node identifier.
+    id: NodeId,
+
+    /// Data type.
+    data_type: OpcUaDataType,
+}
+
+/// OPC UA server port.
+struct OpcUaServerInner {
+    /// Server handle.
+    handle: ServerHandle,
+
+    /// Node manager of the variables.
+    manager: Arc<SimpleNodeManager>,
+
+    /// Variable nodes.
+    nodes: Vec<Node>,
+
+    /// Values written by clients.
+    writes: UnboundedReceiver<OpcUaVariable>,
+
+    /// Server task.
+    server: JoinHandle<Result<(), String>>,
+}
+
+impl OpcUaServerInner {
+    /// Builds the address space and starts the server.
+    async fn open(config: OpcUaServerConfig) -> IoResult<Self> {
+        let (server, handle) = ServerBuilder::new_anonymous(&config.application_name)
+            .application_uri(&config.application_uri)
+            .host(&config.host)
+            .port(config.port)
+            .pki_dir(&config.pki_dir)
+            .with_node_manager(simple_node_manager(
+                NamespaceMetadata {
+                    namespace_uri: config.namespace.clone(),
+                    ..Default::default()
+                },
+                "nexosim",
+            ))
+            .build()
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        let manager = handle
+            .node_managers()
+            .get_of_type::<SimpleNodeManager>()
+            .unwrap();
+        let namespace = handle.get_namespace_index(&config.namespace).unwrap();
+
+        let (writes_tx, writes) = unbounded_channel();
+        let folder = NodeId::new(namespace, config.folder.clone());
+        let mut nodes = Vec::with_capacity(config.variables.len());
+        {
+            let mut address_space = manager.address_space().write();
+            address_space.add_folder(
+                &folder,
+                config.folder.as_str(),
+                config.folder.as_str(),
+                &NodeId::objects_folder_id(),
+            );
+            for variable in &config.variables {
+                let id = NodeId::new(namespace, variable.name.clone());
+                let mut builder =
+                    VariableBuilder::new(&id, variable.name.as_str(), variable.name.as_str())
+                        .data_type(variable.data_type.id())
+                        .value(variable.data_type.default_value().into_variant())
+                        .organized_by(&folder);
+                if variable.writable {
+                    builder = builder.writable();
+                    let (name, data_type, writes_tx) =
+                        (variable.name.clone(), variable.data_type, writes_tx.clone());
+                    manager
+                        .inner()
+                        .add_write_callback(id.clone(), move |value: DataValue, _| {
+                            let Some(value) = value.value.and_then(OpcUaValue::from_variant) else {
+                                return StatusCode::BadTypeMismatch;
+                            };
+                            if value.data_type() != data_type {
+                                return StatusCode::BadTypeMismatch;
+                            }
+                            let _ = writes_tx.send(OpcUaVariable::new(name.clone(), value));
+
+                            StatusCode::Good
+                        });
+                }
+                builder.insert(&mut *address_space);
+                nodes.push(Node {
+                    name: variable.name.clone(),
+                    id,
+                    data_type: variable.data_type,
+                });
+            }
+        }
+
+        let listener = TcpListener::bind((config.host.as_str(), config.port)).await?;
+        let server = tokio::spawn(server.run_with(listener));
+
+        Ok(Self {
+            handle,
+            manager,
+            nodes,
+            writes,
+            server,
+        })
+    }
+
+    /// Updates the value of a variable node and notifies its subscribers.
+    ///
+    /// Values of unknown variables or with another type than their variable
+    /// are discarded.
+    fn set_value(&self, variable: OpcUaVariable) {
+        let Some(node) = self.nodes.iter().find(|node| node.name == variable.name) else {
+            return;
+        };
+        if variable.value.data_type() != node.data_type {
+            return;
+        }
+        let _ = self.manager.set_value(
+            self.handle.subscriptions(),
+            &node.id,
+            None,
+            DataValue::new_now(variable.value.into_variant()),
+        );
+    }
+}
+
+impl AsyncIoPort<OpcUaVariable, OpcUaVariable> for OpcUaServerInner {
+    async fn read(&mut self) -> IoResult<OpcUaVariable> {
+        tokio::select! {
+            write = self.writes.recv() => {
+                // The sender of each writable variable is kept by its
+                // callback.
+                let write = write.ok_or_else(|| Error::from(ErrorKind::UnexpectedEof))?;
+                // Written values are not stored by the write callbacks.
+                self.set_value(write.clone());
+
+                Ok(write)
+            }
+            result = &mut self.server => {
+                let message = match result {
+                    Ok(Ok(())) => "The OPC UA server has stopped.".to_string(),
+                    Ok(Err(e)) => e,
+                    Err(e) => e.to_string(),
+                };
+
+                Err(Error::other(message))
+            }
+        }
+    }
+
+    async fn write(&mut self, variable: OpcUaVariable) -> IoResult<()> {
+        self.set_value(variable);
+
+        Ok(())
+    }
+}
+
+impl Drop for OpcUaServerInner {
+    fn drop(&mut self) {
+        self.handle.cancel();
+    }
+}
+
+/// OPC UA server model.
+///
+/// This model:
+/// * publishes the values sent to the model input to the OPC UA clients,
+/// * forwards the values written by OPC UA clients to the model output,
+/// * reports the errors and the exit of its I/O thread.
+pub struct OpcUaServer {
+    /// Value written by a client -- output port.
+    pub write_out: Output<OpcUaVariable>,
+
+    /// I/O thread error or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: OpcUaServerConfig,
+
+    /// I/O thread.
+    io_thread: AsyncIoThread<OpcUaVariable, OpcUaVariable>,
+}
+
+impl OpcUaServer {
+    /// Publishes the value of a variable -- input port.
+    ///
+    /// Values of variables which are not declared in the configuration or with
+    /// another type than their variable are discarded.
+    pub async fn value_in(&mut self, variable: OpcUaVariable) {
+        let _ = self.io_thread.send(variable);
+    }
+
+    /// Forwards the written values and the I/O thread status -- input port.
+    pub async fn process(&mut self) {
+        for variable in self.io_thread.try_recv_all() {
+            self.write_out.send(variable).await;
+        }
+        while let Ok(status) = self.io_thread.try_recv_status() {
+            self.io_status_out.send(status).await;
+        }
+    }
+}
+
+impl Model for OpcUaServer {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for OpcUaServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OpcUaServer")
+            .field("host", &self.config.host)
+            .field("port", &self.config.port)
+            .finish_non_exhaustive()
+    }
+}
+
+/// OPC UA server model prototype.
+pub struct ProtoOpcUaServer {
+    /// Value written by a client -- output port.
+    pub write_out: Output<OpcUaVariable>,
+
+    /// I/O thread error or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// OPC UA server model instance configuration.
+    config: OpcUaServerConfig,
+}
+
+impl ProtoOpcUaServer {
+    /// Creates a new OPC UA server model prototype.
+    ///
+    /// Failures to build the address space or to listen on the configured
+    /// port are reported as I/O thread errors.
+    pub fn new(config: OpcUaServerConfig) -> Self {
+        Self {
+            write_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoOpcUaServer {
+    type Model = OpcUaServer;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let config = OpcUaServerConfig {
+            host: self.config.host.clone(),
+            port: self.config.port,
+            application_name: self.config.application_name.clone(),
+            application_uri: self.config.application_uri.clone(),
+            namespace: self.config.namespace.clone(),
+            folder: self.config.folder.clone(),
+            pki_dir: self.config.pki_dir.clone(),
+            variables: self
+                .config
+                .variables
+                .iter()
+                .map(|variable| OpcUaVariableConfig {
+                    name: variable.name.clone(),
+                    data_type: variable.data_type,
+                    writable: variable.writable,
+                })
+                .collect(),
+            delta: self.config.delta,
+            period: self.config.period,
+        };
+        let io_thread = AsyncIoThread::new(move || OpcUaServerInner::open(config));
+
+        OpcUaServer {
+            write_out: self.write_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoOpcUaServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoOpcUaServer")
+            .field("host", &self.config.host)
+            .field("port", &self.config.port)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,691 @@
+//! Prometheus exporter model.
+//!
+//! This module contains the [`PrometheusExporter`] model, which aggregates
+//! counters and gauges fed by other models, e.g. port statistics or decoder
+//! error counts, and exposes them on an HTTP `/metrics` endpoint in the
+//! Prometheus text format, so that long runs can be monitored with standard
+//! tooling. It is available with the `prometheus` feature.
+//!
+//! Each metric value sent to the model updates the time series identified by
+//! the metric name and labels:
+//!
+//! * values sent to [`PrometheusExporter::increment_in`] are added to a
+//!   counter,
+//! * values sent to [`PrometheusExporter::counter_in`] replace the value of a
+//!   counter, which is convenient for statistics reported as running totals,
+//! * values sent to [`PrometheusExporter::gauge_in`] replace the value of a
+//!   gauge.
+//!
+//! Metric and label names must be valid Prometheus names, and a metric name is
+//! either a counter or a gauge, as set by its first update. Invalid updates,
+//! negative counter increments and updates of a metric with the other type are
+//! discarded. The names of all metrics can be prefixed with a namespace.
+//!
+//! `GET /metrics` returns the last value of all time series; other paths
+//! return `404 Not Found`. Connections are closed after each response.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::prometheus::{Metric, PrometheusExporterConfig};
+//!
+//! let config = ConfigLoader::<PrometheusExporterConfig>::new()
+//!     .code(
+//!         r#"
+//! listenAddress = "0.0.0.0:9464"
+//! namespace = "bench"
+//! period = 1000
+//!
+//! [[metrics]]
+//! name = "can_frames_total"
+//! help = "CAN frames received from the bus."
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.namespace.as_deref(), Some("bench"));
+//!
+//! // Update sent by the CAN port statistics to `PrometheusExporter::counter_in`:
+//! let metric = Metric::new("can_frames_total", 1024.0).with_label("interface", "can0");
+//! ```
+//!
+//! With this configuration, the counter is exported as:
+//!
+//! ```text
+//! # HELP bench_can_frames_total CAN frames received from the bus.
+//! # TYPE bench_can_frames_total counter
+//! bench_can_frames_total{interface="can0"} 1024
+//! ```
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::{self, Write as _};
+use std::io::{Error, ErrorKind, Read, Result as IoResult};
+use std::net::ToSocketAddrs;
+use std::time::Duration;
+
+use schematic::Config;
+
+use mio::net::{TcpListener, TcpStream};
+use mio::{Interest, Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus, WriteBuffer};
+
+/// Prometheus exporter model instance configuration.
+#[derive(Config, Debug)]
+pub struct PrometheusExporterConfig {
+    /// Local address, as `HOST:PORT`, to listen on.
+    #[setting(default = "0.0.0.0:9464")]
+    pub listen_address: String,
+
+    /// Prefix of the metric names, separated from the names by an underscore.
+    pub namespace: Option<String>,
+
+    /// Metric descriptions.
+    #[setting(nested)]
+    pub metrics: Vec<MetricConfig>,
+
+    /// Maximum size of a request, including its headers, in bytes.
+    #[setting(default = 8192)]
+    pub max_request_size: usize,
+
+    /// Delay for the first scheduled I/O thread status check.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which the I/O thread status is checked.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time the I/O thread status is checked. If
+    /// no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// Metric description.
+#[derive(Config, Debug)]
+pub struct MetricConfig {
+    /// Metric name, without namespace.
+    pub name: String,
+
+    /// Help text.
+    pub help: String,
+}
+
+/// Metric value, with its name and labels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metric {
+    /// Metric name, without namespace.
+    pub name: String,
+
+    /// Labels, as name and value pairs.
+    pub labels: Vec<(String, String)>,
+
+    /// Value.
+    pub value: f64,
+}
+
+impl Metric {
+    /// Creates a new metric value without labels.
+    pub fn new(name: impl Into<String>, value: f64) -> Self {
+        Self {
+            name: name.into(),
+            labels: Vec::new(),
+            value,
+        }
+    }
+
+    /// Adds a label.
+    pub fn with_label(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.labels.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// Maximum number of request headers.
+const MAX_HEADERS: usize = 32;
+
+/// Listener token.
+const LISTENER: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// Token of the first client.
+const FIRST_CLIENT: usize = 2;
+
+/// Metric type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MetricKind {
+    /// Counter.
+    Counter,
+
+    /// Gauge.
+    Gauge,
+}
+
+/// Metric update.
+#[derive(Debug)]
+enum Update {
+    /// Counter increment.
+    Increment(Metric),
+
+    /// Counter value.
+    Counter(Metric),
+
+    /// Gauge value.
+    Gauge(Metric),
+}
+
+/// Metric family.
+struct Family {
+    /// Metric type.
+    kind: MetricKind,
+
+    /// Values, by sorted labels.
+    series: BTreeMap<Vec<(String, String)>, f64>,
+}
+
+/// Client connection.
+struct Client {
+    /// Connection.
+    stream: TcpStream,
+
+    /// Received request bytes.
+    request: Vec<u8>,
+
+    /// Response bytes not yet written.
+    response: WriteBuffer,
+
+    /// A response has been queued, the connection is closed once it is
+    /// written.
+    is_answered: bool,
+}
+
+/// Prometheus exporter port.
+struct PrometheusExporterInner {
+    /// Local address to listen on.
+    address: String,
+
+    /// Listener, once registered.
+    listener: Option<TcpListener>,
+
+    /// Clients, indexed by token.
+    clients: Vec<Option<Client>>,
+
+    /// Prefix of the metric names.
+    prefix: String,
+
+    /// Help texts, by metric name.
+    help: BTreeMap<String, String>,
+
+    /// Metric families, by metric name.
+    families: BTreeMap<String, Family>,
+
+    /// Maximum request size.
+    max_request_size: usize,
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+
+    /// MIO registry, available once the port is registered.
+    registry: Option<Registry>,
+}
+
+impl PrometheusExporterInner {
+    /// Creates a Prometheus exporter port, listening once registered.
+    fn new(config: &PrometheusExporterConfig) -> Self {
+        Self {
+            address: config.listen_address.clone(),
+            listener: None,
+            clients: Vec::new(),
+            prefix: config
+                .namespace
+                .as_ref()
+                .map(|namespace| format!("{namespace}_"))
+                .unwrap_or_default(),
+            help: config
+                .metrics
+                .iter()
+                .map(|metric| (metric.name.clone(), metric.help.clone()))
+                .collect(),
+            families: BTreeMap::new(),
+            max_request_size: config.max_request_size,
+            buffer: vec![0; 4096],
+            registry: None,
+        }
+    }
+
+    /// Applies a metric update.
+    fn update(&mut self, update: &Update) {
+        let (metric, kind) = match update {
+            Update::Increment(metric) | Update::Counter(metric) => (metric, MetricKind::Counter),
+            Update::Gauge(metric) => (metric, MetricKind::Gauge),
+        };
+        if !is_valid_name(&metric.name, true)
+            || !metric
+                .labels
+                .iter()
+                .all(|(name, _)| is_valid_name(name, false))
+        {
+            return;
+        }
+        if matches!(update, Update::Increment(_)) && (metric.value.is_nan() || metric.value < 0.0) {
+            return;
+        }
+        let family = self
+            .families
+            .entry(metric.name.clone())
+            .or_insert_with(|| Family {
+                kind,
+                series: BTreeMap::new(),
+            });
+        if family.kind != kind {
+            return;
+        }
+        let mut labels = metric.labels.clone();
+        labels.sort();
+        labels.dedup_by(|a, b| a.0 == b.0);
+        let value = family.series.entry(labels).or_insert(0.0);
+        match update {
+            Update::Increment(_) => *value += metric.value,
+            Update::Counter(_) | Update::Gauge(_) => *value = metric.value,
+        }
+    }
+
+    /// Serializes all metrics in the Prometheus text format.
+    fn exposition(&self) -> String {
+        let mut text = String::new();
+        for (name, family) in &self.families {
+            let help = self.help.get(name);
+            let name = format!("{}{}", self.prefix, name);
+            if let Some(help) = help {
+                let help = help.replace('\\', "\\\\").replace('\n', "\\n");
+                let _ = writeln!(text, "# HELP {name} {help}");
+            }
+            let kind = match family.kind {
+                MetricKind::Counter => "counter",
+                MetricKind::Gauge => "gauge",
+            };
+            let _ = writeln!(text, "# TYPE {name} {kind}");
+            for (labels, value) in &family.series {
+                text.push_str(&name);
+                if !labels.is_empty() {
+                    let labels: Vec<_> = labels
+                        .iter()
+                        .map(|(name, value)| {
+                            let value = value
+                                .replace('\\', "\\\\")
+                                .replace('"', "\\\"")
+                                .replace('\n', "\\n");
+                            format!("{name}=\"{value}\"")
+                        })
+                        .collect();
+                    let _ = write!(text, "{{{}}}", labels.join(","));
+                }
+                let _ = writeln!(text, " {}", format_value(*value));
+            }
+        }
+
+        text
+    }
+
+    /// Accepts the pending client connections.
+    fn accept(&mut self) -> IoResult<()> {
+        loop {
+            let Some(listener) = &self.listener else {
+                return Ok(());
+            };
+            let mut stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::Interrupted | ErrorKind::ConnectionAborted
+                    ) =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let index = match self.clients.iter().position(Option::is_none) {
+                Some(index) => index,
+                None => {
+                    self.clients.push(None);
+                    self.clients.len() - 1
+                }
+            };
+            if let Some(registry) = &self.registry {
+                if registry
+                    .register(
+                        &mut stream,
+                        Token(FIRST_CLIENT + index),
+                        Interest::READABLE | Interest::WRITABLE,
+                    )
+                    .is_err()
+                {
+                    continue;
+                }
+            }
+            self.clients[index] = Some(Client {
+                stream,
+                request: Vec::new(),
+                response: WriteBuffer::new(),
+                is_answered: false,
+            });
+            // The request may have been received with the connection.
+            self.receive(index);
+        }
+    }
+
+    /// Reads the request of a client and answers it once complete.
+    fn receive(&mut self, index: usize) {
+        let Some(client) = self.clients.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        loop {
+            match client.stream.read(&mut self.buffer) {
+                Ok(0) => {
+                    if !client.is_answered {
+                        self.clients[index] = None;
+                    }
+                    return;
+                }
+                Ok(len) => client.request.extend_from_slice(&self.buffer[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.clients[index] = None;
+                    return;
+                }
+            }
+        }
+        if client.is_answered {
+            return;
+        }
+        if client.request.len() > self.max_request_size {
+            self.respond(index, "413 Payload Too Large", "");
+            return;
+        }
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut parsed = httparse::Request::new(&mut headers);
+        match parsed.parse(&client.request) {
+            Ok(httparse::Status::Complete(_)) => {}
+            Ok(httparse::Status::Partial) => return,
+            Err(_) => {
+                self.respond(index, "400 Bad Request", "");
+                return;
+            }
+        }
+        let path = parsed.path.unwrap_or_default();
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        match (parsed.method.unwrap_or_default(), path) {
+            ("GET", "/metrics") => {
+                let body = self.exposition();
+                self.respond(index, "200 OK", &body);
+            }
+            (_, "/metrics") => self.respond(index, "405 Method Not Allowed", ""),
+            _ => self.respond(index, "404 Not Found", ""),
+        }
+    }
+
+    /// Queues the response to a client and writes it.
+    fn respond(&mut self, index: usize, status: &str, body: &str) {
+        let Some(client) = self.clients.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            status,
+            body.len()
+        );
+        if !body.is_empty() {
+            response.push_str("Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n");
+        }
+        response.push_str("\r\n");
+        response.push_str(body);
+        client.is_answered = true;
+        client.response.push(response.as_bytes());
+        self.resume_write(index);
+    }
+
+    /// Writes the pending response of a client, closing the connection once
+    /// it is written.
+    fn resume_write(&mut self, index: usize) {
+        let Some(client) = self.clients.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        if !client.is_answered {
+            return;
+        }
+        match client.response.flush(&mut client.stream) {
+            Ok(false) => {}
+            Ok(true) | Err(_) => {
+                let mut client = self.clients[index].take().unwrap();
+                if let Some(registry) = &self.registry {
+                    let _ = registry.deregister(&mut client.stream);
+                }
+            }
+        }
+    }
+}
+
+impl IoPort<TcpListener, Infallible, Update> for PrometheusExporterInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        let address = self.address.to_socket_addrs()?.next().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Cannot resolve address {}.", self.address),
+            )
+        })?;
+        let mut listener = TcpListener::bind(address)?;
+        registry.register(&mut listener, LISTENER, Interest::READABLE)?;
+        self.listener = Some(listener);
+        self.registry = Some(registry.try_clone()?);
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<Infallible> {
+        if token == LISTENER {
+            self.accept()?;
+        } else if let Some(index) = token.0.checked_sub(FIRST_CLIENT) {
+            self.receive(index);
+        }
+
+        Err(ErrorKind::WouldBlock.into())
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        if let Some(index) = token.0.checked_sub(FIRST_CLIENT) {
+            self.resume_write(index);
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, update: &Update) -> IoResult<()> {
+        self.update(update);
+
+        Ok(())
+    }
+}
+
+/// Checks a metric or label name.
+fn is_valid_name(name: &str, allow_colon: bool) -> bool {
+    let is_valid_char =
+        |c: char| c.is_ascii_alphanumeric() || c == '_' || (allow_colon && c == ':');
+
+    name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+        && name.chars().all(is_valid_char)
+        && !name.starts_with("__")
+}
+
+/// Formats a sample value.
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".into()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.into()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Prometheus exporter model.
+///
+/// This model:
+/// * aggregates the counters and gauges sent to its inputs,
+/// * serves their last values on its `/metrics` endpoint,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct PrometheusExporter {
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: PrometheusExporterConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<Infallible, Update>,
+}
+
+impl PrometheusExporter {
+    /// Adds a value to a counter -- input port.
+    pub async fn increment_in(&mut self, metric: Metric) {
+        let _ = self.io_thread.send(Update::Increment(metric));
+    }
+
+    /// Sets the value of a counter -- input port.
+    pub async fn counter_in(&mut self, metric: Metric) {
+        let _ = self.io_thread.send(Update::Counter(metric));
+    }
+
+    /// Sets the value of a gauge -- input port.
+    pub async fn gauge_in(&mut self, metric: Metric) {
+        let _ = self.io_thread.send(Update::Gauge(metric));
+    }
+
+    /// Forwards the I/O thread status -- input port.
+    pub async fn process(&mut self) {
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for PrometheusExporter {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for PrometheusExporter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PrometheusExporter")
+            .field("listen_address", &self.config.listen_address)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Prometheus exporter model prototype.
+pub struct ProtoPrometheusExporter {
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Prometheus exporter model instance configuration.
+    config: PrometheusExporterConfig,
+}
+
+impl ProtoPrometheusExporter {
+    /// Creates a new Prometheus exporter model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the listen address cannot be bound or if
+    /// the I/O thread cannot be created.
+    pub fn new(config: PrometheusExporterConfig) -> Self {
+        Self {
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoPrometheusExporter {
+    type Model = PrometheusExporter;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread =
+            IoThread::try_with_options(PrometheusExporterInner::new(&self.config), options)
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "Failed to start the I/O thread of the Prometheus exporter on {}: {e}.",
+                        self.config.listen_address
+                    )
+                });
+
+        PrometheusExporter {
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoPrometheusExporter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoPrometheusExporter")
+            .field("listen_address", &self.config.listen_address)
+            .finish_non_exhaustive()
+    }
+}
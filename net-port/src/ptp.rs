@@ -0,0 +1,885 @@
+//! PTP time source model.
+//!
+//! This module contains the [`PtpTimeSource`] model, which follows a PTP
+//! (IEEE 1588-2008) master over UDP/IPv4 as a listen-only ordinary clock, and
+//! the [`PtpClock`] simulation clock, which paces the simulation on the time of
+//! the master rather than on the system clock.
+//!
+//! The time source joins the PTP primary multicast group `224.0.1.129` on the
+//! event and general ports. It locks onto the first master whose `Sync`
+//! messages are received, or onto the configured master address, and
+//! estimates for each synchronization:
+//! * the path delay to the master, measured with `Delay_Req` messages sent in
+//!   unicast to the master, as in the hybrid delay request-response mechanism,
+//! * the time of the master at the reception of the synchronization,
+//! * the rate of the master clock relative to the local monotonic clock,
+//! * the offset of the system clock from the master.
+//!
+//! One-step and two-step masters are supported. The best master clock
+//! algorithm is not implemented, and the time source does not act as a master
+//! itself.
+//!
+//! Messages are timestamped in software upon reception by the I/O thread, so
+//! the accuracy is that of the host scheduler, typically tens of
+//! microseconds. PTP hardware clocks (`/dev/ptp*`) are not read, since this
+//! requires device-specific I/O controls; a PHC synchronized by `ptp4l` can be
+//! exposed to this time source through `phc2sys` and the system clock
+//! instead.
+//!
+//! The [`PtpClock`] obtained from the model prototype with
+//! [`ProtoPtpTimeSource::clock`] or [`ProtoPtpTimeSource::auto_clock`] maps
+//! simulation time to PTP time and extrapolates the time of the master from the
+//! last synchronization and rate estimate, so it keeps running if the master
+//! is lost. Until the first synchronization, it follows the system clock.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::ptp::{ProtoPtpTimeSource, PtpConfig};
+//!
+//! let config = ConfigLoader::<PtpConfig>::new()
+//!     .code(
+//!         r#"
+//! interfaceAddress = "192.168.1.10"
+//! domain = 0
+//! period = 100
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.event_port, 319);
+//!
+//! // The clock is passed to the simulation builder with `set_clock`, while
+//! // the model prototype is added to the bench.
+//! let ptp = ProtoPtpTimeSource::new(config);
+//! let clock = ptp.auto_clock();
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result as IoResult};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use schematic::Config;
+
+use mio::net::UdpSocket;
+use mio::{Interest, Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+use nexosim::time::{Clock, MonotonicTime, SyncStatus};
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus};
+
+/// PTP primary multicast group.
+const PTP_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 1, 129);
+
+/// Size of the PTP message header.
+const HEADER_LEN: usize = 34;
+
+/// Size of `Sync`, `Delay_Req` and `Follow_Up` messages.
+const TIMESTAMP_MESSAGE_LEN: usize = 44;
+
+/// Size of `Delay_Resp` messages.
+const DELAY_RESP_LEN: usize = 54;
+
+/// `Sync` message type.
+const SYNC: u8 = 0x0;
+
+/// `Delay_Req` message type.
+const DELAY_REQ: u8 = 0x1;
+
+/// `Follow_Up` message type.
+const FOLLOW_UP: u8 = 0x8;
+
+/// `Delay_Resp` message type.
+const DELAY_RESP: u8 = 0x9;
+
+/// `Announce` message type.
+const ANNOUNCE: u8 = 0xB;
+
+/// Two-step flag of the flag field.
+const TWO_STEP_FLAG: u16 = 0x0200;
+
+/// Current UTC offset valid flag of the flag field.
+const UTC_OFFSET_VALID_FLAG: u16 = 0x0004;
+
+/// Offset of TAI from UTC assumed until announced by the master, in seconds.
+const DEFAULT_UTC_OFFSET: i16 = 37;
+
+/// Maximum time slept by the clock before the estimate is checked again.
+const MAX_SLEEP: Duration = Duration::from_millis(100);
+
+/// Nanoseconds in a second.
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+/// PTP time source model instance configuration.
+#[derive(Config, Debug)]
+pub struct PtpConfig {
+    /// Address of the local interface joining the PTP multicast group.
+    #[setting(default = "0.0.0.0")]
+    pub interface_address: String,
+
+    /// PTP domain number.
+    #[setting(default = 0)]
+    pub domain: u8,
+
+    /// UDP port of the event messages.
+    #[setting(default = 319)]
+    pub event_port: u16,
+
+    /// UDP port of the general messages.
+    #[setting(default = 320)]
+    pub general_port: u16,
+
+    /// IP address of the master to follow.
+    ///
+    /// If no value is provided, the first master whose synchronizations are
+    /// received is followed.
+    pub master_address: Option<String>,
+
+    /// Measure the path delay to the master.
+    ///
+    /// If false, the path delay is assumed to be zero, which is mostly useful
+    /// when the master does not answer delay requests.
+    #[setting(default = true)]
+    pub delay_requests: bool,
+
+    /// Number of synchronizations over which the rate of the master clock is
+    /// estimated.
+    #[setting(default = 16)]
+    pub rate_window: usize,
+
+    /// Delay for the first scheduled synchronization forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which synchronizations are forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time synchronizations are forwarded. If
+    /// no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// Synchronization to the PTP master.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PtpSync {
+    /// Clock identity of the master.
+    pub master_identity: u64,
+
+    /// Sequence identifier of the `Sync` message.
+    pub sequence_id: u16,
+
+    /// Time of the master at the reception of the `Sync` message, i.e. its
+    /// origin timestamp corrected for the path delay.
+    pub master_time: MonotonicTime,
+
+    /// Offset of the system clock from the master, in nanoseconds.
+    ///
+    /// The system clock is converted from UTC to the TAI time scale of PTP with
+    /// the offset announced by the master.
+    pub offset: i64,
+
+    /// Last measured path delay to the master.
+    pub path_delay: Duration,
+
+    /// Rate of the master clock relative to the local monotonic clock.
+    pub rate: f64,
+}
+
+/// Estimate of the time of the master.
+#[derive(Clone, Copy, Debug)]
+struct Estimate {
+    /// Local time of the last synchronization.
+    local: Instant,
+
+    /// Time of the master at the last synchronization, in nanoseconds.
+    master: i128,
+
+    /// Rate of the master clock relative to the local clock.
+    rate: f64,
+}
+
+/// State shared by the time source and its clocks.
+#[derive(Debug)]
+struct PtpState {
+    /// Estimate of the time of the master, once synchronized.
+    estimate: Option<Estimate>,
+
+    /// Offset of TAI from UTC, in seconds.
+    utc_offset: i16,
+}
+
+impl PtpState {
+    /// Returns the estimated time of the master, in nanoseconds.
+    ///
+    /// The system clock is used until the first synchronization.
+    fn master_time(&self) -> i128 {
+        match self.estimate {
+            Some(estimate) => {
+                let elapsed = Instant::now().saturating_duration_since(estimate.local);
+                estimate.master + (elapsed.as_nanos() as f64 * estimate.rate) as i128
+            }
+            None => system_time(self.utc_offset),
+        }
+    }
+
+    /// Returns the estimated rate of the master clock.
+    fn rate(&self) -> f64 {
+        self.estimate.map_or(1.0, |estimate| estimate.rate)
+    }
+}
+
+/// Returns the time of the system clock in the TAI time scale, in
+/// nanoseconds.
+fn system_time(utc_offset: i16) -> i128 {
+    let since_epoch = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(duration) => duration.as_nanos() as i128,
+        Err(e) => -(e.duration().as_nanos() as i128),
+    };
+
+    since_epoch + i128::from(utc_offset) * NANOS_PER_SEC
+}
+
+/// Converts a time to nanoseconds since the epoch.
+fn to_nanos(time: MonotonicTime) -> i128 {
+    i128::from(time.as_secs()) * NANOS_PER_SEC + i128::from(time.subsec_nanos())
+}
+
+/// Converts nanoseconds since the epoch to a time.
+fn from_nanos(nanos: i128) -> MonotonicTime {
+    let secs = nanos.div_euclid(NANOS_PER_SEC);
+    let secs = secs.clamp(i128::from(i64::MIN), i128::from(i64::MAX)) as i64;
+
+    MonotonicTime::new(secs, nanos.rem_euclid(NANOS_PER_SEC) as u32).unwrap()
+}
+
+/// Simulation clock following a PTP master.
+///
+/// The clock maps a reference simulation time to a reference PTP time, and
+/// waits until the estimated time of the master reaches the PTP time of each
+/// deadline. A deadline which has already elapsed when the clock is
+/// synchronized is reported as out of sync.
+///
+/// The clock is obtained from [`ProtoPtpTimeSource::clock`] or
+/// [`ProtoPtpTimeSource::auto_clock`], and follows the system clock until the
+/// time source is synchronized.
+pub struct PtpClock {
+    /// State shared with the time source.
+    state: Arc<Mutex<PtpState>>,
+
+    /// Reference simulation time and corresponding PTP time in nanoseconds,
+    /// once set.
+    reference: Option<(MonotonicTime, i128)>,
+}
+
+impl PtpClock {
+    /// Returns the estimated time of the master.
+    pub fn ptp_time(&self) -> MonotonicTime {
+        from_nanos(self.state.lock().unwrap().master_time())
+    }
+}
+
+impl Clock for PtpClock {
+    fn synchronize(&mut self, deadline: MonotonicTime) -> SyncStatus {
+        let Some((simulation_ref, ptp_ref)) = self.reference else {
+            // The first deadline of an automatic clock defines the reference.
+            self.reference = Some((deadline, self.state.lock().unwrap().master_time()));
+
+            return SyncStatus::Synchronized;
+        };
+        let target = ptp_ref + to_nanos(deadline) - to_nanos(simulation_ref);
+
+        let mut is_first = true;
+        loop {
+            let (now, rate) = {
+                let state = self.state.lock().unwrap();
+                (state.master_time(), state.rate())
+            };
+            if now >= target {
+                return match now - target {
+                    lag @ 1.. if is_first => {
+                        SyncStatus::OutOfSync(Duration::from_nanos(lag as u64))
+                    }
+                    _ => SyncStatus::Synchronized,
+                };
+            }
+            is_first = false;
+            let wait = Duration::from_nanos(((target - now) as f64 / rate).ceil() as u64);
+            thread::sleep(wait.min(MAX_SLEEP));
+        }
+    }
+}
+
+impl fmt::Debug for PtpClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PtpClock").finish_non_exhaustive()
+    }
+}
+
+/// Event socket token.
+const EVENT: Token = Token(0);
+
+/// General socket token.
+const GENERAL: Token = Token(1);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(2);
+
+/// PTP message header.
+struct Header {
+    /// Message type.
+    message_type: u8,
+
+    /// Flag field.
+    flags: u16,
+
+    /// Correction field, in nanoseconds.
+    correction: i128,
+
+    /// Source port identity: clock identity and port number.
+    source: (u64, u16),
+
+    /// Sequence identifier.
+    sequence_id: u16,
+}
+
+impl Header {
+    /// Decodes a message header of a domain.
+    ///
+    /// `None` is returned for truncated messages, messages of other versions
+    /// and messages of other domains.
+    fn decode(message: &[u8], domain: u8) -> Option<Self> {
+        if message.len() < HEADER_LEN || message[1] & 0x0F != 2 || message[4] != domain {
+            return None;
+        }
+
+        Some(Self {
+            message_type: message[0] & 0x0F,
+            flags: u16::from_be_bytes([message[6], message[7]]),
+            correction: i128::from(i64::from_be_bytes(message[8..16].try_into().unwrap()) >> 16),
+            source: (
+                u64::from_be_bytes(message[20..28].try_into().unwrap()),
+                u16::from_be_bytes([message[28], message[29]]),
+            ),
+            sequence_id: u16::from_be_bytes([message[30], message[31]]),
+        })
+    }
+}
+
+/// Decodes a PTP timestamp, in nanoseconds.
+fn decode_timestamp(bytes: &[u8]) -> i128 {
+    let mut secs = [0; 8];
+    secs[2..].copy_from_slice(&bytes[..6]);
+    let secs = u64::from_be_bytes(secs);
+    let nanos = u32::from_be_bytes(bytes[6..10].try_into().unwrap());
+
+    i128::from(secs) * NANOS_PER_SEC + i128::from(nanos)
+}
+
+/// Two-step synchronization awaiting its `Follow_Up` message.
+struct PendingSync {
+    /// Sequence identifier.
+    sequence_id: u16,
+
+    /// Correction of the `Sync` message, in nanoseconds.
+    correction: i128,
+
+    /// Local reception time.
+    received: Instant,
+}
+
+/// Delay request awaiting its response.
+struct PendingDelay {
+    /// Sequence identifier of the `Delay_Req` message.
+    sequence_id: u16,
+
+    /// Origin time of the master of the last synchronization, in nanoseconds.
+    sync_origin: i128,
+
+    /// Local reception time of the last synchronization.
+    sync_received: Instant,
+
+    /// Local transmission time of the `Delay_Req` message.
+    sent: Instant,
+}
+
+/// PTP listen-only ordinary clock.
+struct PtpInner {
+    /// Event message socket.
+    event: UdpSocket,
+
+    /// General message socket.
+    general: UdpSocket,
+
+    /// PTP domain.
+    domain: u8,
+
+    /// Event port of the master.
+    event_port: u16,
+
+    /// Configured master address, if any.
+    master_address: Option<IpAddr>,
+
+    /// Measure the path delay.
+    delay_requests: bool,
+
+    /// Number of synchronizations of the rate estimate.
+    rate_window: usize,
+
+    /// Followed master: clock identity, port number and address.
+    master: Option<((u64, u16), IpAddr)>,
+
+    /// Local port identity.
+    identity: u64,
+
+    /// Sequence identifier of the next delay request.
+    delay_sequence_id: u16,
+
+    /// Two-step synchronization awaiting its follow-up.
+    pending_sync: Option<PendingSync>,
+
+    /// Delay request awaiting its response.
+    pending_delay: Option<PendingDelay>,
+
+    /// Last measured path delay, in nanoseconds.
+    path_delay: i128,
+
+    /// Recent synchronizations, as local reception times and origin times of
+    /// the master, so that the rate is not biased by path delay changes.
+    history: VecDeque<(Instant, i128)>,
+
+    /// State shared with the clocks.
+    state: Arc<Mutex<PtpState>>,
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+}
+
+impl PtpInner {
+    /// Binds the event and general ports and joins the multicast group.
+    fn new(config: &PtpConfig, state: Arc<Mutex<PtpState>>) -> IoResult<Self> {
+        let interface: Ipv4Addr = config.interface_address.parse().map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid interface address {}.", config.interface_address),
+            )
+        })?;
+        let master_address = config
+            .master_address
+            .as_ref()
+            .map(|address| {
+                address.parse().map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Invalid master address {address}."),
+                    )
+                })
+            })
+            .transpose()?;
+        let bind = |port| -> IoResult<UdpSocket> {
+            let socket = UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port).into())?;
+            socket.join_multicast_v4(&PTP_GROUP, &interface)?;
+
+            Ok(socket)
+        };
+        // The identity only needs to be unique among the slaves of the master.
+        let identity = 0x0200_00FF_FE00_0000 | u64::from(std::process::id() & 0xFF_FFFF);
+
+        Ok(Self {
+            event: bind(config.event_port)?,
+            general: bind(config.general_port)?,
+            domain: config.domain,
+            event_port: config.event_port,
+            master_address,
+            delay_requests: config.delay_requests,
+            rate_window: config.rate_window.max(2),
+            master: None,
+            identity,
+            delay_sequence_id: 0,
+            pending_sync: None,
+            pending_delay: None,
+            path_delay: 0,
+            history: VecDeque::new(),
+            state,
+            buffer: vec![0; 1500],
+        })
+    }
+
+    /// Handles a message, returning the resulting synchronization if any.
+    fn handle(&mut self, len: usize, source: SocketAddr, received: Instant) -> Option<PtpSync> {
+        let message = &self.buffer[..len];
+        let header = Header::decode(message, self.domain)?;
+        let from_master = match self.master {
+            Some((identity, address)) => identity == header.source && address == source.ip(),
+            // Only a synchronization locks the time source onto a master.
+            None => {
+                header.message_type == SYNC
+                    && self
+                        .master_address
+                        .is_none_or(|address| address == source.ip())
+            }
+        };
+        if !from_master {
+            return None;
+        }
+
+        match header.message_type {
+            SYNC if message.len() >= TIMESTAMP_MESSAGE_LEN => {
+                self.master = Some((header.source, source.ip()));
+                if header.flags & TWO_STEP_FLAG != 0 {
+                    self.pending_sync = Some(PendingSync {
+                        sequence_id: header.sequence_id,
+                        correction: header.correction,
+                        received,
+                    });
+                    return None;
+                }
+                let origin = decode_timestamp(&message[34..44]) + header.correction;
+
+                self.synchronize(header.source.0, header.sequence_id, origin, received)
+            }
+            FOLLOW_UP if message.len() >= TIMESTAMP_MESSAGE_LEN => {
+                let pending = self
+                    .pending_sync
+                    .take_if(|pending| pending.sequence_id == header.sequence_id)?;
+                let origin =
+                    decode_timestamp(&message[34..44]) + pending.correction + header.correction;
+
+                self.synchronize(
+                    header.source.0,
+                    header.sequence_id,
+                    origin,
+                    pending.received,
+                )
+            }
+            DELAY_RESP if message.len() >= DELAY_RESP_LEN => {
+                let requester = u64::from_be_bytes(message[44..52].try_into().unwrap());
+                if requester != self.identity {
+                    return None;
+                }
+                let pending = self
+                    .pending_delay
+                    .take_if(|pending| pending.sequence_id == header.sequence_id)?;
+                let master_receive = decode_timestamp(&message[34..44]) - header.correction;
+                // The round trip is measured in the master time scale and the
+                // turnaround in the local time scale, so the offset between
+                // the clocks cancels out.
+                let round_trip = master_receive - pending.sync_origin;
+                let turnaround = pending.sent.duration_since(pending.sync_received);
+                self.path_delay = ((round_trip - turnaround.as_nanos() as i128) / 2).max(0);
+
+                None
+            }
+            ANNOUNCE if message.len() >= 46 => {
+                if header.flags & UTC_OFFSET_VALID_FLAG != 0 {
+                    self.state.lock().unwrap().utc_offset =
+                        i16::from_be_bytes([message[44], message[45]]);
+                }
+
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Updates the estimate with a synchronization and requests the path
+    /// delay.
+    fn synchronize(
+        &mut self,
+        master_identity: u64,
+        sequence_id: u16,
+        origin: i128,
+        received: Instant,
+    ) -> Option<PtpSync> {
+        let master_time = origin + self.path_delay;
+        if self.history.len() == self.rate_window {
+            self.history.pop_front();
+        }
+        self.history.push_back((received, origin));
+        let rate = match (self.history.front(), self.history.back()) {
+            (Some(&(local_0, master_0)), Some(&(local_n, master_n))) if local_n > local_0 => {
+                (master_n - master_0) as f64 / local_n.duration_since(local_0).as_nanos() as f64
+            }
+            _ => 1.0,
+        };
+        let utc_offset = {
+            let mut state = self.state.lock().unwrap();
+            state.estimate = Some(Estimate {
+                local: received,
+                master: master_time,
+                rate,
+            });
+            state.utc_offset
+        };
+        let elapsed = Instant::now().duration_since(received).as_nanos() as i128;
+        let offset = system_time(utc_offset) - elapsed - master_time;
+
+        if self.delay_requests {
+            self.request_delay(origin, received);
+        }
+
+        Some(PtpSync {
+            master_identity,
+            sequence_id,
+            master_time: from_nanos(master_time),
+            offset: offset.clamp(i128::from(i64::MIN), i128::from(i64::MAX)) as i64,
+            path_delay: Duration::from_nanos(self.path_delay as u64),
+            rate,
+        })
+    }
+
+    /// Sends a delay request to the master.
+    fn request_delay(&mut self, sync_origin: i128, sync_received: Instant) {
+        let Some((_, address)) = self.master else {
+            return;
+        };
+        let sequence_id = self.delay_sequence_id;
+        self.delay_sequence_id = self.delay_sequence_id.wrapping_add(1);
+
+        let mut message = [0; TIMESTAMP_MESSAGE_LEN];
+        message[0] = DELAY_REQ;
+        message[1] = 2;
+        message[2..4].copy_from_slice(&(TIMESTAMP_MESSAGE_LEN as u16).to_be_bytes());
+        message[4] = self.domain;
+        message[20..28].copy_from_slice(&self.identity.to_be_bytes());
+        message[28..30].copy_from_slice(&1u16.to_be_bytes());
+        message[30..32].copy_from_slice(&sequence_id.to_be_bytes());
+        message[32] = 0x01;
+        message[33] = 0x7F;
+
+        // A lost request is only a missed path delay measurement.
+        if self
+            .event
+            .send_to(&message, SocketAddr::new(address, self.event_port))
+            .is_ok()
+        {
+            self.pending_delay = Some(PendingDelay {
+                sequence_id,
+                sync_origin,
+                sync_received,
+                sent: Instant::now(),
+            });
+        }
+    }
+}
+
+impl IoPort<UdpSocket, PtpSync, ()> for PtpInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        registry.register(&mut self.event, EVENT, Interest::READABLE)?;
+        registry.register(&mut self.general, GENERAL, Interest::READABLE)?;
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<PtpSync> {
+        loop {
+            let result = match token {
+                EVENT => self.event.recv_from(&mut self.buffer),
+                GENERAL => self.general.recv_from(&mut self.buffer),
+                // Unknown event: should never happen.
+                _ => return Err(Error::new(ErrorKind::InvalidInput, "Unknown event.")),
+            };
+            let received = Instant::now();
+            match result {
+                Ok((len, source)) => {
+                    if let Some(sync) = self.handle(len, source, received) {
+                        return Ok(sync);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write(&mut self, _: &()) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+/// PTP time source model.
+///
+/// This model:
+/// * forwards the synchronizations to the PTP master to the model output,
+/// * updates the estimate of the time of the master used by its clocks,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+///
+/// The estimate used by the clocks is updated by the I/O thread as soon as
+/// synchronizations are received, independently of their forwarding into the
+/// simulation.
+pub struct PtpTimeSource {
+    /// Synchronization to the master -- output port.
+    pub sync_out: Output<PtpSync>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: PtpConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<PtpSync, ()>,
+}
+
+impl PtpTimeSource {
+    /// Forwards the synchronizations and the I/O thread status -- input port.
+    pub async fn process(&mut self) {
+        for sync in self.io_thread.try_recv_all() {
+            self.sync_out.send(sync).await;
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for PtpTimeSource {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for PtpTimeSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PtpTimeSource")
+            .field("domain", &self.config.domain)
+            .finish_non_exhaustive()
+    }
+}
+
+/// PTP time source model prototype.
+pub struct ProtoPtpTimeSource {
+    /// Synchronization to the master -- output port.
+    pub sync_out: Output<PtpSync>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// PTP time source model instance configuration.
+    config: PtpConfig,
+
+    /// State shared with the clocks.
+    state: Arc<Mutex<PtpState>>,
+}
+
+impl ProtoPtpTimeSource {
+    /// Creates a new PTP time source model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the PTP ports cannot be bound, if the
+    /// multicast group cannot be joined or if the I/O thread cannot be
+    /// created.
+    pub fn new(config: PtpConfig) -> Self {
+        Self {
+            sync_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+            state: Arc::new(Mutex::new(PtpState {
+                estimate: None,
+                utc_offset: DEFAULT_UTC_OFFSET,
+            })),
+        }
+    }
+
+    /// Returns a clock following the master, with the reference simulation
+    /// time corresponding to the reference PTP time.
+    pub fn clock(&self, simulation_ref: MonotonicTime, ptp_ref: MonotonicTime) -> PtpClock {
+        PtpClock {
+            state: self.state.clone(),
+            reference: Some((simulation_ref, to_nanos(ptp_ref))),
+        }
+    }
+
+    /// Returns a clock following the master, with the simulation time of its
+    /// first synchronization corresponding to the PTP time at that moment.
+    pub fn auto_clock(&self) -> PtpClock {
+        PtpClock {
+            state: self.state.clone(),
+            reference: None,
+        }
+    }
+}
+
+impl ProtoModel for ProtoPtpTimeSource {
+    type Model = PtpTimeSource;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let port = PtpInner::new(&self.config, self.state).unwrap_or_else(|e| {
+            panic!(
+                "Failed to open the PTP ports {} and {}: {e}.",
+                self.config.event_port, self.config.general_port
+            )
+        });
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(port, options).unwrap_or_else(|e| {
+            panic!("Failed to start the I/O thread of the PTP time source: {e}.")
+        });
+
+        PtpTimeSource {
+            sync_out: self.sync_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoPtpTimeSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoPtpTimeSource")
+            .field("domain", &self.config.domain)
+            .finish_non_exhaustive()
+    }
+}
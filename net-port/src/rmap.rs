@@ -0,0 +1,688 @@
+//! RMAP layer.
+//!
+//! This module contains a model implementing the SpaceWire Remote Memory
+//! Access Protocol (ECSS-E-ST-50-52C) on top of the SpaceWire packets of a
+//! [`SpaceWirePort`](crate::spacewire::SpaceWirePort), both as:
+//! * an initiator, sending the commands of the simulation and forwarding the
+//!   replies it receives,
+//! * a target, forwarding the commands it receives and sending the replies of
+//!   the simulation, built with [`RmapReply::to`].
+//!
+//! Write, read and read-modify-write commands are encoded and decoded with
+//! their header and data CRCs. Packets with an invalid header are discarded;
+//! commands with an invalid data CRC or data length are answered by the layer
+//! with the corresponding error status, if a reply is requested. Commands and
+//! replies addressed to another logical address are discarded.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_net_port::rmap::{RmapCommand, RmapLayer};
+//!
+//! let layer = RmapLayer::new(0xFE);
+//!
+//! // Connect `layer.packet_out` to `SpaceWirePort::packet_in`,
+//! // `SpaceWirePort::packet_out` to `RmapLayer::packet_in` and the command
+//! // and reply ports to the bench models, which send commands such as:
+//! let command = RmapCommand::write(0x42, 0x4000_0000, vec![1, 2, 3, 4])
+//!     .with_target_address(vec![3, 1])
+//!     .with_reply_address(vec![2, 4])
+//!     .with_transaction_id(7);
+//! ```
+
+use std::error::Error;
+use std::fmt;
+
+use bytes::Bytes;
+
+use nexosim::model::Model;
+use nexosim::ports::Output;
+
+/// RMAP protocol identifier.
+pub const PROTOCOL_ID: u8 = 0x01;
+
+/// Default logical address of SpaceWire nodes.
+pub const DEFAULT_LOGICAL_ADDRESS: u8 = 0xFE;
+
+/// Maximum length of a reply address.
+pub const MAX_REPLY_ADDRESS_LEN: usize = 12;
+
+/// Maximum data length.
+pub const MAX_DATA_LEN: usize = 0xFF_FFFF;
+
+/// Reply status: command executed successfully.
+pub const STATUS_SUCCESS: u8 = 0;
+
+/// Reply status: invalid data CRC.
+pub const STATUS_INVALID_DATA_CRC: u8 = 4;
+
+/// Reply status: early end of packet.
+pub const STATUS_EARLY_EOP: u8 = 5;
+
+/// Reply status: too much data.
+pub const STATUS_TOO_MUCH_DATA: u8 = 6;
+
+/// Instruction flag: command packet.
+const COMMAND: u8 = 0x40;
+
+/// Instruction flag: write operation.
+const WRITE: u8 = 0x20;
+
+/// Instruction flag: verify data before writing.
+const VERIFY: u8 = 0x10;
+
+/// Instruction flag: reply requested.
+const REPLY: u8 = 0x08;
+
+/// Instruction flag: increment address.
+const INCREMENT: u8 = 0x04;
+
+/// Instruction mask of the reply address length, in 4-byte units.
+const REPLY_ADDRESS_LEN: u8 = 0x03;
+
+/// Length of a command header after the reply address, header CRC included.
+const COMMAND_TAIL_LEN: usize = 12;
+
+/// RMAP command.
+///
+/// A command with the write flag clear and the verify flag set is a
+/// read-modify-write command, whose data holds the written value followed by
+/// its mask.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RmapCommand {
+    /// SpaceWire path address leading to the target.
+    ///
+    /// This address is empty for received commands, since it is consumed by
+    /// the SpaceWire network.
+    pub target_address: Vec<u8>,
+
+    /// Target logical address.
+    pub target_logical_address: u8,
+
+    /// Destination key.
+    pub key: u8,
+
+    /// SpaceWire path address leading back to the initiator, up to 12 bytes.
+    pub reply_address: Vec<u8>,
+
+    /// Initiator logical address.
+    pub initiator_logical_address: u8,
+
+    /// Transaction identifier.
+    pub transaction_id: u16,
+
+    /// Extended address.
+    pub extended_address: u8,
+
+    /// Memory address.
+    pub address: u32,
+
+    /// Write operation.
+    pub is_write: bool,
+
+    /// Verify data before writing.
+    pub verify: bool,
+
+    /// Reply requested.
+    pub reply: bool,
+
+    /// Increment the address.
+    pub increment: bool,
+
+    /// Data length.
+    ///
+    /// This is the length of the data of write and read-modify-write
+    /// commands, and the length of the read data of read commands.
+    pub length: u32,
+
+    /// Command data, empty for read commands.
+    pub data: Vec<u8>,
+}
+
+impl RmapCommand {
+    /// Creates a write command with a reply and an incrementing address, from
+    /// the default logical address.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the data is longer than [`MAX_DATA_LEN`].
+    pub fn write(target_logical_address: u8, address: u32, data: Vec<u8>) -> Self {
+        assert!(data.len() <= MAX_DATA_LEN, "RMAP data too long");
+
+        Self {
+            is_write: true,
+            length: data.len() as u32,
+            data,
+            ..Self::read(target_logical_address, address, 0)
+        }
+    }
+
+    /// Creates a read command with an incrementing address, from the default
+    /// logical address.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length is greater than [`MAX_DATA_LEN`].
+    pub fn read(target_logical_address: u8, address: u32, length: u32) -> Self {
+        assert!(length as usize <= MAX_DATA_LEN, "RMAP data too long");
+
+        Self {
+            target_address: Vec::new(),
+            target_logical_address,
+            key: 0,
+            reply_address: Vec::new(),
+            initiator_logical_address: DEFAULT_LOGICAL_ADDRESS,
+            transaction_id: 0,
+            extended_address: 0,
+            address,
+            is_write: false,
+            verify: false,
+            reply: true,
+            increment: true,
+            length,
+            data: Vec::new(),
+        }
+    }
+
+    /// Sets the SpaceWire path address leading to the target.
+    pub fn with_target_address(mut self, target_address: Vec<u8>) -> Self {
+        self.target_address = target_address;
+        self
+    }
+
+    /// Sets the destination key.
+    pub fn with_key(mut self, key: u8) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Sets the SpaceWire path address leading back to the initiator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the address is longer than [`MAX_REPLY_ADDRESS_LEN`].
+    pub fn with_reply_address(mut self, reply_address: Vec<u8>) -> Self {
+        assert!(
+            reply_address.len() <= MAX_REPLY_ADDRESS_LEN,
+            "RMAP reply address too long"
+        );
+        self.reply_address = reply_address;
+        self
+    }
+
+    /// Sets the initiator logical address.
+    pub fn with_initiator_logical_address(mut self, initiator_logical_address: u8) -> Self {
+        self.initiator_logical_address = initiator_logical_address;
+        self
+    }
+
+    /// Sets the transaction identifier.
+    pub fn with_transaction_id(mut self, transaction_id: u16) -> Self {
+        self.transaction_id = transaction_id;
+        self
+    }
+
+    /// Sets the extended address.
+    pub fn with_extended_address(mut self, extended_address: u8) -> Self {
+        self.extended_address = extended_address;
+        self
+    }
+
+    /// Sets whether the data is verified before writing.
+    pub fn with_verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Sets whether a reply is requested.
+    pub fn with_reply(mut self, reply: bool) -> Self {
+        self.reply = reply;
+        self
+    }
+
+    /// Sets whether the address is incremented.
+    pub fn with_increment(mut self, increment: bool) -> Self {
+        self.increment = increment;
+        self
+    }
+
+    /// Returns `true` if the command carries data.
+    fn has_data(&self) -> bool {
+        self.is_write || self.verify
+    }
+
+    /// Returns the instruction of the command.
+    fn instruction(&self) -> u8 {
+        COMMAND
+            | flags(self.is_write, self.verify, self.reply, self.increment)
+            | self.reply_address.len().div_ceil(4) as u8
+    }
+
+    /// Encodes the command into a SpaceWire packet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reply address is longer than [`MAX_REPLY_ADDRESS_LEN`] or
+    /// if the data is longer than [`MAX_DATA_LEN`].
+    pub fn encode(&self) -> Bytes {
+        assert!(
+            self.reply_address.len() <= MAX_REPLY_ADDRESS_LEN,
+            "RMAP reply address too long"
+        );
+        assert!(self.data.len() <= MAX_DATA_LEN, "RMAP data too long");
+
+        let mut packet = self.target_address.clone();
+        let header = packet.len();
+        packet.extend_from_slice(&[
+            self.target_logical_address,
+            PROTOCOL_ID,
+            self.instruction(),
+            self.key,
+        ]);
+        let padding = self.reply_address.len().div_ceil(4) * 4 - self.reply_address.len();
+        packet.extend(std::iter::repeat_n(0, padding));
+        packet.extend_from_slice(&self.reply_address);
+        packet.push(self.initiator_logical_address);
+        packet.extend_from_slice(&self.transaction_id.to_be_bytes());
+        packet.push(self.extended_address);
+        packet.extend_from_slice(&self.address.to_be_bytes());
+        packet.extend_from_slice(&self.length.to_be_bytes()[1..]);
+        packet.push(crc(&packet[header..]));
+        if self.has_data() {
+            packet.extend_from_slice(&self.data);
+            packet.push(crc(&self.data));
+        }
+
+        packet.into()
+    }
+}
+
+/// RMAP reply.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RmapReply {
+    /// SpaceWire path address leading back to the initiator.
+    ///
+    /// This address is empty for received replies, since it is consumed by
+    /// the SpaceWire network.
+    pub reply_address: Vec<u8>,
+
+    /// Initiator logical address.
+    pub initiator_logical_address: u8,
+
+    /// Target logical address.
+    pub target_logical_address: u8,
+
+    /// Transaction identifier.
+    pub transaction_id: u16,
+
+    /// Reply to a write command.
+    pub is_write: bool,
+
+    /// The command verified its data before writing.
+    pub verify: bool,
+
+    /// The command incremented its address.
+    pub increment: bool,
+
+    /// Status, [`STATUS_SUCCESS`] if the command was executed.
+    pub status: u8,
+
+    /// Read data, empty for the replies to write commands.
+    pub data: Vec<u8>,
+}
+
+impl RmapReply {
+    /// Creates the reply to a command, with the read data for read and
+    /// read-modify-write commands.
+    ///
+    /// Leading zeros of the reply address of the command, which pad the
+    /// address in the command, are removed.
+    pub fn to(command: &RmapCommand, status: u8, data: Vec<u8>) -> Self {
+        let padding = command
+            .reply_address
+            .iter()
+            .take_while(|&&byte| byte == 0)
+            .count();
+
+        Self {
+            reply_address: command.reply_address[padding..].to_vec(),
+            initiator_logical_address: command.initiator_logical_address,
+            target_logical_address: command.target_logical_address,
+            transaction_id: command.transaction_id,
+            is_write: command.is_write,
+            verify: command.verify,
+            increment: command.increment,
+            status,
+            data: if command.is_write { Vec::new() } else { data },
+        }
+    }
+
+    /// Encodes the reply into a SpaceWire packet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reply address is longer than [`MAX_REPLY_ADDRESS_LEN`] or
+    /// if the data is longer than [`MAX_DATA_LEN`].
+    pub fn encode(&self) -> Bytes {
+        assert!(
+            self.reply_address.len() <= MAX_REPLY_ADDRESS_LEN,
+            "RMAP reply address too long"
+        );
+        assert!(self.data.len() <= MAX_DATA_LEN, "RMAP data too long");
+
+        let mut packet = self.reply_address.clone();
+        let header = packet.len();
+        let instruction = flags(self.is_write, self.verify, true, self.increment)
+            | self.reply_address.len().div_ceil(4) as u8;
+        packet.extend_from_slice(&[
+            self.initiator_logical_address,
+            PROTOCOL_ID,
+            instruction,
+            self.status,
+            self.target_logical_address,
+        ]);
+        packet.extend_from_slice(&self.transaction_id.to_be_bytes());
+        if self.is_write {
+            packet.push(crc(&packet[header..]));
+        } else {
+            packet.push(0);
+            packet.extend_from_slice(&(self.data.len() as u32).to_be_bytes()[1..]);
+            packet.push(crc(&packet[header..]));
+            packet.extend_from_slice(&self.data);
+            packet.push(crc(&self.data));
+        }
+
+        packet.into()
+    }
+}
+
+/// Decoded RMAP packet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RmapPacket {
+    /// Command.
+    Command(RmapCommand),
+    /// Reply.
+    Reply(RmapReply),
+}
+
+impl RmapPacket {
+    /// Decodes a SpaceWire packet starting with its logical address.
+    ///
+    /// If the header is valid but the data is not, the error holds the
+    /// decoded packet, with the received data.
+    pub fn decode(packet: &[u8]) -> Result<Self, RmapError> {
+        if packet.len() < 3 {
+            return Err(RmapError::Truncated);
+        }
+        if packet[1] != PROTOCOL_ID {
+            return Err(RmapError::UnknownProtocol(packet[1]));
+        }
+        let instruction = packet[2];
+        if instruction & 0x80 != 0 {
+            return Err(RmapError::InvalidInstruction(instruction));
+        }
+        let (is_write, verify, reply, increment) = (
+            instruction & WRITE != 0,
+            instruction & VERIFY != 0,
+            instruction & REPLY != 0,
+            instruction & INCREMENT != 0,
+        );
+        if instruction & COMMAND == 0 {
+            return decode_reply(packet, is_write, verify, increment).map(Self::Reply);
+        }
+
+        let reply_address_len = usize::from(instruction & REPLY_ADDRESS_LEN) * 4;
+        let header_len = 4 + reply_address_len + COMMAND_TAIL_LEN;
+        let header = packet.get(..header_len).ok_or(RmapError::Truncated)?;
+        if crc(header) != 0 {
+            return Err(RmapError::HeaderCrc);
+        }
+        let tail = &header[4 + reply_address_len..];
+        let reply_address = &header[4..4 + reply_address_len];
+        let padding = reply_address.iter().take_while(|&&byte| byte == 0).count();
+        let mut command = RmapCommand {
+            target_address: Vec::new(),
+            target_logical_address: packet[0],
+            key: packet[3],
+            reply_address: reply_address[padding..].to_vec(),
+            initiator_logical_address: tail[0],
+            transaction_id: u16::from_be_bytes([tail[1], tail[2]]),
+            extended_address: tail[3],
+            address: u32::from_be_bytes([tail[4], tail[5], tail[6], tail[7]]),
+            is_write,
+            verify,
+            reply,
+            increment,
+            length: u32::from_be_bytes([0, tail[8], tail[9], tail[10]]),
+            data: Vec::new(),
+        };
+        if !command.has_data() {
+            return Ok(Self::Command(command));
+        }
+
+        let data = &packet[header_len..];
+        let result = check_data(data, command.length as usize);
+        command.data = data[..data.len().saturating_sub(1)].to_vec();
+        match result {
+            Ok(()) => Ok(Self::Command(command)),
+            Err(error) => Err(RmapError::Data(Box::new(Self::Command(command)), error)),
+        }
+    }
+}
+
+/// Decodes a reply.
+fn decode_reply(
+    packet: &[u8],
+    is_write: bool,
+    verify: bool,
+    increment: bool,
+) -> Result<RmapReply, RmapError> {
+    let header_len = if is_write { 8 } else { 12 };
+    let header = packet.get(..header_len).ok_or(RmapError::Truncated)?;
+    if crc(header) != 0 {
+        return Err(RmapError::HeaderCrc);
+    }
+    let mut reply = RmapReply {
+        reply_address: Vec::new(),
+        initiator_logical_address: header[0],
+        target_logical_address: header[4],
+        transaction_id: u16::from_be_bytes([header[5], header[6]]),
+        is_write,
+        verify,
+        increment,
+        status: header[3],
+        data: Vec::new(),
+    };
+    if is_write {
+        return Ok(reply);
+    }
+
+    let length = u32::from_be_bytes([0, header[8], header[9], header[10]]) as usize;
+    let data = &packet[header_len..];
+    let result = check_data(data, length);
+    reply.data = data[..data.len().saturating_sub(1)].to_vec();
+    match result {
+        Ok(()) => Ok(reply),
+        Err(error) => Err(RmapError::Data(Box::new(RmapPacket::Reply(reply)), error)),
+    }
+}
+
+/// Checks data followed by its CRC, returning the status of the error if it
+/// is invalid.
+fn check_data(data: &[u8], length: usize) -> Result<(), u8> {
+    if data.len() < length + 1 {
+        return Err(STATUS_EARLY_EOP);
+    }
+    if data.len() > length + 1 {
+        return Err(STATUS_TOO_MUCH_DATA);
+    }
+    if crc(data) != 0 {
+        return Err(STATUS_INVALID_DATA_CRC);
+    }
+
+    Ok(())
+}
+
+/// Returns the operation flags of an instruction.
+fn flags(is_write: bool, verify: bool, reply: bool, increment: bool) -> u8 {
+    let mut flags = 0;
+    if is_write {
+        flags |= WRITE;
+    }
+    if verify {
+        flags |= VERIFY;
+    }
+    if reply {
+        flags |= REPLY;
+    }
+    if increment {
+        flags |= INCREMENT;
+    }
+
+    flags
+}
+
+/// Computes the RMAP CRC-8 of the data.
+///
+/// The CRC of data followed by its CRC is zero.
+pub fn crc(data: &[u8]) -> u8 {
+    data.iter().fold(0, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xE0
+            } else {
+                crc >> 1
+            };
+        }
+        crc
+    })
+}
+
+/// RMAP decoding error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RmapError {
+    /// The packet is shorter than its header.
+    Truncated,
+    /// The packet has another protocol identifier.
+    UnknownProtocol(u8),
+    /// The instruction is invalid.
+    InvalidInstruction(u8),
+    /// The header CRC is invalid.
+    HeaderCrc,
+    /// The data is invalid, with the decoded packet and the reply status of
+    /// the error.
+    Data(Box<RmapPacket>, u8),
+}
+
+impl fmt::Display for RmapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "truncated RMAP header"),
+            Self::UnknownProtocol(id) => write!(f, "unknown protocol identifier {id}"),
+            Self::InvalidInstruction(instruction) => {
+                write!(f, "invalid RMAP instruction {instruction:#04x}")
+            }
+            Self::HeaderCrc => write!(f, "invalid RMAP header CRC"),
+            Self::Data(_, STATUS_EARLY_EOP) => write!(f, "RMAP data shorter than its length"),
+            Self::Data(_, STATUS_TOO_MUCH_DATA) => write!(f, "RMAP data longer than its length"),
+            Self::Data(..) => write!(f, "invalid RMAP data CRC"),
+        }
+    }
+}
+
+impl Error for RmapError {}
+
+/// RMAP layer model.
+///
+/// This model converts SpaceWire packets to RMAP commands and replies and vice
+/// versa.
+pub struct RmapLayer {
+    /// SpaceWire packets to be transmitted -- output port.
+    pub packet_out: Output<Bytes>,
+
+    /// Received commands -- output port.
+    pub command_out: Output<RmapCommand>,
+
+    /// Received replies -- output port.
+    pub reply_out: Output<RmapReply>,
+
+    /// Logical address of the node.
+    logical_address: u8,
+
+    /// Packets addressed to other logical addresses are forwarded.
+    promiscuous: bool,
+}
+
+impl RmapLayer {
+    /// Creates a new RMAP layer for the node with the provided logical
+    /// address.
+    pub fn new(logical_address: u8) -> Self {
+        Self {
+            packet_out: Output::new(),
+            command_out: Output::new(),
+            reply_out: Output::new(),
+            logical_address,
+            promiscuous: false,
+        }
+    }
+
+    /// Forwards commands and replies addressed to other logical addresses as
+    /// well.
+    pub fn with_promiscuous(mut self, promiscuous: bool) -> Self {
+        self.promiscuous = promiscuous;
+        self
+    }
+
+    /// Received SpaceWire packet -- input port.
+    pub async fn packet_in(&mut self, packet: Bytes) {
+        match RmapPacket::decode(&packet) {
+            Ok(RmapPacket::Command(command)) => {
+                if self.accepts(command.target_logical_address) {
+                    self.command_out.send(command).await;
+                }
+            }
+            Ok(RmapPacket::Reply(reply)) => {
+                if self.accepts(reply.initiator_logical_address) {
+                    self.reply_out.send(reply).await;
+                }
+            }
+            // Commands with invalid data are not executed, but answered.
+            Err(RmapError::Data(packet, status)) => {
+                if let RmapPacket::Command(command) = *packet {
+                    if command.reply && self.accepts(command.target_logical_address) {
+                        let reply = RmapReply::to(&command, status, Vec::new());
+                        self.packet_out.send(reply.encode()).await;
+                    }
+                }
+            }
+            Err(_) => {}
+        }
+    }
+
+    /// Command to be sent -- input port.
+    pub async fn command_in(&mut self, command: RmapCommand) {
+        self.packet_out.send(command.encode()).await;
+    }
+
+    /// Reply to be sent -- input port.
+    pub async fn reply_in(&mut self, reply: RmapReply) {
+        self.packet_out.send(reply.encode()).await;
+    }
+
+    /// Returns `true` if packets addressed to the logical address are
+    /// forwarded.
+    fn accepts(&self, logical_address: u8) -> bool {
+        self.promiscuous || logical_address == self.logical_address
+    }
+}
+
+impl Model for RmapLayer {}
+
+impl fmt::Debug for RmapLayer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RmapLayer")
+            .field("logical_address", &self.logical_address)
+            .finish_non_exhaustive()
+    }
+}
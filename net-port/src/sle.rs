@@ -0,0 +1,937 @@
+//! CCSDS SLE user.
+//!
+//! This module contains the [`SleUser`] layer, a CCSDS Space Link Extension
+//! user of either the Return All Frames (RAF) or the Forward CLTU (FCLTU)
+//! service, which connects the simulation to a ground-station provider or to
+//! an SLE test server:
+//! * RAF: the transfer frames delivered by the provider are forwarded to the
+//!   frame outputs, e.g. to feed a telemetry decoder,
+//! * FCLTU: the CLTUs from the simulation are transferred to the provider,
+//!   and their acceptance and radiation are reported.
+//!
+//! The layer runs on top of the byte stream of a
+//! [`TcpClient`](crate::tcp::TcpClient) connected to the provider, using the
+//! ISP1 transport mapping. On connection, the service instance is bound and,
+//! if `auto_start` is set, started; it can otherwise be driven with the
+//! command input. Operations are invoked without credentials, i.e. with
+//! authentication disabled.
+//!
+//! Heartbeats are sent at the configured interval, in simulation time. The
+//! heartbeats of the provider are not monitored.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::sle::{SleService, SleUser, SleUserConfig};
+//!
+//! let config = ConfigLoader::<SleUserConfig>::new()
+//!     .code(
+//!         r#"
+//! service = "raf"
+//! initiatorId = "SLE_USER"
+//! responderPort = "RAF_PORT"
+//! serviceInstance = "sagr=1.spack=VST-PASS0001.rsl-fg=1.raf=onlt1"
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.service, SleService::Raf);
+//!
+//! // Connect `user.bytes_out` to `TcpClient::bytes_in`,
+//! // `TcpClient::bytes_out` to `SleUser::bytes_in` and `TcpClient::status_out`
+//! // to `SleUser::status_in`, and `SleUser::data_out` to the frame decoder.
+//! let user = SleUser::new(config);
+//! ```
+
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use schematic::{Config, ConfigEnum};
+use serde::{Deserialize, Serialize};
+
+use nexosim::model::{Context, InitializedModel, Model};
+use nexosim::ports::Output;
+
+use crate::tcp::TcpStatus;
+
+/// ISP1 message type of the SLE PDUs.
+const TML_PDU: u8 = 0x01;
+
+/// ISP1 message type of the context message.
+const TML_CONTEXT: u8 = 0x02;
+
+/// ISP1 message type of the heartbeats.
+const TML_HEARTBEAT: u8 = 0x03;
+
+/// ISP1 message header size.
+const TML_HEADER_LEN: usize = 8;
+
+/// ISP1 protocol identifier and version of the context message.
+const TML_PROTOCOL: [u8; 8] = *b"ISP1\0\0\0\x01";
+
+/// Bind invocation tag.
+const BIND_INVOCATION: u32 = 100;
+
+/// Bind return tag.
+const BIND_RETURN: u32 = 101;
+
+/// Unbind invocation tag.
+const UNBIND_INVOCATION: u32 = 102;
+
+/// Unbind return tag.
+const UNBIND_RETURN: u32 = 103;
+
+/// Peer abort tag.
+const PEER_ABORT: u32 = 104;
+
+/// Start invocation tag.
+const START_INVOCATION: u32 = 0;
+
+/// Start return tag.
+const START_RETURN: u32 = 1;
+
+/// Stop invocation tag.
+const STOP_INVOCATION: u32 = 2;
+
+/// Stop return tag.
+const STOP_RETURN: u32 = 3;
+
+/// RAF transfer buffer tag.
+const RAF_TRANSFER_BUFFER: u32 = 8;
+
+/// CLTU transfer data invocation tag.
+const CLTU_TRANSFER_DATA: u32 = 10;
+
+/// CLTU transfer data return tag.
+const CLTU_TRANSFER_DATA_RETURN: u32 = 11;
+
+/// CLTU asynchronous notification tag.
+const CLTU_ASYNC_NOTIFY: u32 = 12;
+
+/// Unused credentials: `[0] NULL`.
+const NO_CREDENTIALS: [u8; 2] = [0x80, 0x00];
+
+/// Undefined conditional time: `[0] NULL`.
+const UNDEFINED_TIME: [u8; 2] = [0x80, 0x00];
+
+/// Days from 1958-01-01, the CCSDS epoch, to 1970-01-01.
+const CCSDS_EPOCH_DAYS: u64 = 4383;
+
+/// Object identifiers of the service instance identifier attributes.
+const SI_ATTRIBUTES: [(&str, &[u64]); 10] = [
+    ("cltu", &[1, 3, 112, 4, 3, 1, 2, 7]),
+    ("fsp", &[1, 3, 112, 4, 3, 1, 2, 10]),
+    ("fsl-fg", &[1, 3, 112, 4, 3, 1, 2, 14]),
+    ("raf", &[1, 3, 112, 4, 3, 1, 2, 22]),
+    ("rsl-fg", &[1, 3, 112, 4, 3, 1, 2, 38]),
+    ("rcfsh", &[1, 3, 112, 4, 3, 1, 2, 44]),
+    ("rcf", &[1, 3, 112, 4, 3, 1, 2, 46]),
+    ("rocf", &[1, 3, 112, 4, 3, 1, 2, 49]),
+    ("sagr", &[1, 3, 112, 4, 3, 1, 2, 52]),
+    ("spack", &[1, 3, 112, 4, 3, 1, 2, 53]),
+];
+
+/// SLE user model instance configuration.
+#[derive(Config, Debug)]
+pub struct SleUserConfig {
+    /// SLE service.
+    pub service: SleService,
+
+    /// Authority identifier of the user.
+    #[setting(default = "user")]
+    pub initiator_id: String,
+
+    /// Logical port of the provider.
+    #[setting(default = "port")]
+    pub responder_port: String,
+
+    /// Service instance identifier, e.g.
+    /// `sagr=1.spack=VST-PASS0001.rsl-fg=1.raf=onlt1`.
+    pub service_instance: String,
+
+    /// Version of the service.
+    #[setting(default = 2)]
+    pub version: u16,
+
+    /// The service instance is bound and started on connection.
+    #[setting(default = true)]
+    pub auto_start: bool,
+
+    /// Quality of the frames requested from the RAF service.
+    pub frame_quality: SleRequestedQuality,
+
+    /// Identifier of the first CLTU transferred to the FCLTU service.
+    pub first_cltu_id: u32,
+
+    /// Heartbeat interval, in seconds.
+    #[setting(default = 25)]
+    pub heartbeat_interval: u16,
+
+    /// Dead factor announced to the provider.
+    #[setting(default = 5)]
+    pub dead_factor: u16,
+}
+
+/// SLE service.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SleService {
+    /// Return All Frames.
+    #[default]
+    Raf,
+
+    /// Forward CLTU.
+    Fcltu,
+}
+
+impl SleService {
+    /// Returns the application identifier of the bind invocation.
+    fn application_id(self) -> i64 {
+        match self {
+            Self::Raf => 0,
+            Self::Fcltu => 16,
+        }
+    }
+}
+
+/// Quality of the frames requested from the RAF service.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SleRequestedQuality {
+    /// Good frames only.
+    Good,
+
+    /// Erred frames only.
+    Erred,
+
+    /// All frames.
+    #[default]
+    All,
+}
+
+/// Quality of a delivered frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleFrameQuality {
+    /// Good frame.
+    Good,
+
+    /// Erred frame.
+    Erred,
+
+    /// Undetermined quality.
+    Undetermined,
+}
+
+/// Frame delivered by the RAF service.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SleFrame {
+    /// Earth receive time.
+    pub earth_receive_time: SystemTime,
+
+    /// Frame quality.
+    pub quality: SleFrameQuality,
+
+    /// Data link continuity: 0 if no frame was lost before the frame, the
+    /// number of lost frames if known, or -1 if unknown.
+    pub continuity: i64,
+
+    /// Frame data.
+    pub data: Bytes,
+}
+
+/// SLE command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleCommand {
+    /// Binds the service instance.
+    Bind,
+
+    /// Starts the service instance.
+    Start,
+
+    /// Stops the service instance.
+    Stop,
+
+    /// Unbinds the service instance.
+    Unbind,
+}
+
+/// CLTU notification of the FCLTU service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CltuNotification {
+    /// A CLTU was radiated.
+    Radiated,
+
+    /// A CLTU expired before radiation.
+    Expired,
+
+    /// Production was interrupted.
+    ProductionInterrupted,
+
+    /// Production was halted.
+    ProductionHalted,
+
+    /// Production is operational.
+    ProductionOperational,
+
+    /// The CLTU buffer is empty.
+    BufferEmpty,
+
+    /// Other notification, with its tag number.
+    Other(u32),
+}
+
+/// SLE event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleEvent {
+    /// The service instance was bound.
+    Bound,
+
+    /// The bind was rejected, with the diagnostic of the provider.
+    BindRejected(i64),
+
+    /// The service instance was started.
+    Started,
+
+    /// The start was rejected, with the diagnostic of the provider.
+    StartRejected(i64),
+
+    /// The service instance was stopped.
+    Stopped,
+
+    /// The service instance was unbound.
+    Unbound,
+
+    /// The association was aborted by the provider, or lost with the
+    /// connection, with the diagnostic of the provider if any.
+    Aborted(Option<i64>),
+
+    /// A CLTU was accepted by the provider.
+    CltuAccepted {
+        /// CLTU identifier.
+        id: u32,
+
+        /// Available CLTU buffer size, in bytes.
+        buffer_available: u64,
+    },
+
+    /// A CLTU was rejected by the provider.
+    CltuRejected {
+        /// CLTU identifier.
+        id: u32,
+
+        /// Diagnostic of the provider.
+        diagnostic: i64,
+    },
+
+    /// CLTU notification.
+    Cltu(CltuNotification),
+}
+
+/// State of the association.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Not bound.
+    Unbound,
+
+    /// Bind invoked.
+    Binding,
+
+    /// Bound.
+    Ready,
+
+    /// Start invoked.
+    Starting,
+
+    /// Started.
+    Active,
+
+    /// Stop invoked.
+    Stopping,
+
+    /// Unbind invoked.
+    Unbinding,
+}
+
+/// SLE user layer.
+///
+/// This model:
+/// * binds, starts, stops and unbinds the service instance, and reports the
+///   outcome of each operation,
+/// * forwards the frames delivered by the RAF service,
+/// * transfers the CLTUs to the FCLTU service, and reports their acceptance
+///   and the notifications of the provider.
+pub struct SleUser {
+    /// ISP1 messages to be sent -- output port.
+    pub bytes_out: Output<Bytes>,
+
+    /// Annotated frame delivered by the RAF service -- output port.
+    pub frame_out: Output<SleFrame>,
+
+    /// Data of the frame delivered by the RAF service -- output port.
+    pub data_out: Output<Bytes>,
+
+    /// SLE event -- output port.
+    pub event_out: Output<SleEvent>,
+
+    /// Model instance configuration.
+    config: SleUserConfig,
+
+    /// Encoded service instance identifier.
+    service_instance: Vec<u8>,
+
+    /// State of the association.
+    state: State,
+
+    /// The TCP connection is established.
+    connected: bool,
+
+    /// Invoke identifier of the next operation.
+    invoke_id: i64,
+
+    /// Identifier of the next CLTU.
+    cltu_id: u32,
+
+    /// Received bytes not yet decoded.
+    buffer: BytesMut,
+}
+
+impl SleUser {
+    /// Creates a new SLE user layer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the service instance identifier is not a list of known
+    /// `attribute=value` pairs separated by dots.
+    pub fn new(config: SleUserConfig) -> Self {
+        let service_instance =
+            encode_service_instance(&config.service_instance).unwrap_or_else(|| {
+                panic!(
+                    "Invalid service instance identifier {}.",
+                    config.service_instance
+                )
+            });
+        let cltu_id = config.first_cltu_id;
+
+        Self {
+            bytes_out: Output::new(),
+            frame_out: Output::new(),
+            data_out: Output::new(),
+            event_out: Output::new(),
+            config,
+            service_instance,
+            state: State::Unbound,
+            connected: false,
+            invoke_id: 0,
+            cltu_id,
+            buffer: BytesMut::new(),
+        }
+    }
+
+    /// Bytes received from the TCP connection -- input port.
+    pub async fn bytes_in(&mut self, data: Bytes) {
+        self.buffer.extend_from_slice(&data);
+        while self.buffer.len() >= TML_HEADER_LEN {
+            let len = u32::from_be_bytes(self.buffer[4..8].try_into().unwrap()) as usize;
+            if self.buffer.len() < TML_HEADER_LEN + len {
+                return;
+            }
+            let message = self.buffer.split_to(TML_HEADER_LEN + len).freeze();
+            if message[0] == TML_PDU {
+                self.on_pdu(&message[TML_HEADER_LEN..]).await;
+            }
+        }
+    }
+
+    /// TCP connection status -- input port.
+    ///
+    /// The association is lost with the connection.
+    pub async fn status_in(&mut self, status: TcpStatus) {
+        self.buffer.clear();
+        match status {
+            TcpStatus::Connected => {
+                self.connected = true;
+                let mut context = BytesMut::with_capacity(TML_HEADER_LEN + 12);
+                context.put_slice(&[TML_CONTEXT, 0, 0, 0]);
+                context.put_u32(12);
+                context.put_slice(&TML_PROTOCOL);
+                context.put_u16(self.config.heartbeat_interval);
+                context.put_u16(self.config.dead_factor);
+                self.bytes_out.send(context.freeze()).await;
+                if self.config.auto_start {
+                    self.bind().await;
+                }
+            }
+            TcpStatus::Disconnected(_) => {
+                self.connected = false;
+                if self.state != State::Unbound {
+                    self.state = State::Unbound;
+                    self.event_out.send(SleEvent::Aborted(None)).await;
+                }
+            }
+        }
+    }
+
+    /// SLE command -- input port.
+    ///
+    /// Commands which are invalid in the current state are ignored.
+    pub async fn command_in(&mut self, command: SleCommand) {
+        match (command, self.state) {
+            (SleCommand::Bind, State::Unbound) => self.bind().await,
+            (SleCommand::Start, State::Ready) => self.start().await,
+            (SleCommand::Stop, State::Active) => {
+                let mut content = NO_CREDENTIALS.to_vec();
+                content.extend(integer(self.next_invoke_id()));
+                self.state = State::Stopping;
+                self.send_pdu(constructed(STOP_INVOCATION), &content).await;
+            }
+            (SleCommand::Unbind, State::Ready) => {
+                let mut content = NO_CREDENTIALS.to_vec();
+                // Unbind reason: end.
+                content.extend(integer(0));
+                self.state = State::Unbinding;
+                self.send_pdu(constructed(UNBIND_INVOCATION), &content)
+                    .await;
+            }
+            _ => {}
+        }
+    }
+
+    /// CLTU to be transferred -- input port.
+    ///
+    /// CLTUs are discarded unless the FCLTU service instance is started.
+    pub async fn cltu_in(&mut self, cltu: Bytes) {
+        if self.config.service != SleService::Fcltu || self.state != State::Active {
+            return;
+        }
+        let mut content = NO_CREDENTIALS.to_vec();
+        content.extend(integer(self.next_invoke_id()));
+        content.extend(integer(self.cltu_id as i64));
+        content.extend(UNDEFINED_TIME);
+        content.extend(UNDEFINED_TIME);
+        // No delay after the CLTU, with radiation notification.
+        content.extend(integer(0));
+        content.extend(integer(0));
+        content.extend(tlv(&[0x04], &cltu));
+        self.cltu_id = self.cltu_id.wrapping_add(1);
+        self.send_pdu(constructed(CLTU_TRANSFER_DATA), &content)
+            .await;
+    }
+
+    /// Sends a heartbeat.
+    async fn send_heartbeat(&mut self) {
+        if self.connected {
+            self.bytes_out
+                .send(Bytes::from_static(&[TML_HEARTBEAT, 0, 0, 0, 0, 0, 0, 0]))
+                .await;
+        }
+    }
+
+    /// Invokes the bind operation.
+    async fn bind(&mut self) {
+        let mut content = NO_CREDENTIALS.to_vec();
+        content.extend(tlv(&[0x1A], self.config.initiator_id.as_bytes()));
+        content.extend(tlv(&[0x1A], self.config.responder_port.as_bytes()));
+        content.extend(integer(self.config.service.application_id()));
+        content.extend(integer(self.config.version as i64));
+        content.extend(&self.service_instance);
+        self.state = State::Binding;
+        self.send_pdu(constructed(BIND_INVOCATION), &content).await;
+    }
+
+    /// Invokes the start operation.
+    async fn start(&mut self) {
+        let mut content = NO_CREDENTIALS.to_vec();
+        content.extend(integer(self.next_invoke_id()));
+        match self.config.service {
+            SleService::Raf => {
+                content.extend(UNDEFINED_TIME);
+                content.extend(UNDEFINED_TIME);
+                content.extend(integer(match self.config.frame_quality {
+                    SleRequestedQuality::Good => 0,
+                    SleRequestedQuality::Erred => 1,
+                    SleRequestedQuality::All => 2,
+                }));
+            }
+            SleService::Fcltu => content.extend(integer(self.cltu_id as i64)),
+        }
+        self.state = State::Starting;
+        self.send_pdu(constructed(START_INVOCATION), &content).await;
+    }
+
+    /// Handles a received PDU.
+    ///
+    /// Malformed or unexpected PDUs are ignored.
+    async fn on_pdu(&mut self, pdu: &[u8]) {
+        let Some((tag, content, _)) = parse_tlv(pdu) else {
+            return;
+        };
+        let fields: Vec<_> = Fields(content).collect();
+        // Result of a return: `[0]` if positive, `[1]` with diagnostic if
+        // negative.
+        let result = |index: usize| {
+            fields
+                .get(index)
+                .map(|&(tag, content)| (tag.number == 0, first_integer(tag, content).unwrap_or(-1)))
+        };
+
+        match tag.number {
+            BIND_RETURN if self.state == State::Binding => {
+                let Some((positive, diagnostic)) = result(2) else {
+                    return;
+                };
+                if positive {
+                    self.state = State::Ready;
+                    self.event_out.send(SleEvent::Bound).await;
+                    if self.config.auto_start {
+                        self.start().await;
+                    }
+                } else {
+                    self.state = State::Unbound;
+                    self.event_out
+                        .send(SleEvent::BindRejected(diagnostic))
+                        .await;
+                }
+            }
+            UNBIND_RETURN if self.state == State::Unbinding => {
+                self.state = State::Unbound;
+                self.event_out.send(SleEvent::Unbound).await;
+            }
+            START_RETURN if self.state == State::Starting => {
+                let Some((positive, diagnostic)) = result(2) else {
+                    return;
+                };
+                if positive {
+                    self.state = State::Active;
+                    self.event_out.send(SleEvent::Started).await;
+                } else {
+                    self.state = State::Ready;
+                    self.event_out
+                        .send(SleEvent::StartRejected(diagnostic))
+                        .await;
+                }
+            }
+            STOP_RETURN if self.state == State::Stopping => {
+                // A rejected stop leaves the service instance started.
+                let Some((positive, _)) = result(2) else {
+                    return;
+                };
+                if positive {
+                    self.state = State::Ready;
+                    self.event_out.send(SleEvent::Stopped).await;
+                } else {
+                    self.state = State::Active;
+                }
+            }
+            PEER_ABORT => {
+                self.state = State::Unbound;
+                self.event_out
+                    .send(SleEvent::Aborted(Some(decode_integer(content))))
+                    .await;
+            }
+            RAF_TRANSFER_BUFFER if self.config.service == SleService::Raf => {
+                for (tag, content) in Fields(content) {
+                    // Sync notifications are ignored.
+                    if tag.number == 0 {
+                        if let Some(frame) = decode_frame(content) {
+                            self.frame_out.send(frame.clone()).await;
+                            self.data_out.send(frame.data).await;
+                        }
+                    }
+                }
+            }
+            CLTU_TRANSFER_DATA_RETURN if self.config.service == SleService::Fcltu => {
+                let (Some(&(_, id)), Some(&(_, available)), Some((positive, diagnostic))) =
+                    (fields.get(2), fields.get(3), result(4))
+                else {
+                    return;
+                };
+                let id = decode_integer(id) as u32;
+                let event = if positive {
+                    SleEvent::CltuAccepted {
+                        id,
+                        buffer_available: decode_integer(available) as u64,
+                    }
+                } else {
+                    SleEvent::CltuRejected { id, diagnostic }
+                };
+                self.event_out.send(event).await;
+            }
+            CLTU_ASYNC_NOTIFY if self.config.service == SleService::Fcltu => {
+                let Some(&(tag, _)) = fields.get(1) else {
+                    return;
+                };
+                let notification = match tag.number {
+                    0 => CltuNotification::Radiated,
+                    1 => CltuNotification::Expired,
+                    2 => CltuNotification::ProductionInterrupted,
+                    3 => CltuNotification::ProductionHalted,
+                    4 => CltuNotification::ProductionOperational,
+                    5 => CltuNotification::BufferEmpty,
+                    number => CltuNotification::Other(number),
+                };
+                self.event_out.send(SleEvent::Cltu(notification)).await;
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns a new invoke identifier.
+    fn next_invoke_id(&mut self) -> i64 {
+        let invoke_id = self.invoke_id;
+        self.invoke_id = (self.invoke_id + 1) % (i32::MAX as i64);
+
+        invoke_id
+    }
+
+    /// Sends a PDU.
+    async fn send_pdu(&mut self, tag: Vec<u8>, content: &[u8]) {
+        let pdu = tlv(&tag, content);
+        let mut message = BytesMut::with_capacity(TML_HEADER_LEN + pdu.len());
+        message.put_slice(&[TML_PDU, 0, 0, 0]);
+        message.put_u32(pdu.len() as u32);
+        message.put_slice(&pdu);
+
+        self.bytes_out.send(message.freeze()).await;
+    }
+}
+
+impl Model for SleUser {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if self.config.heartbeat_interval != 0 {
+            let period = Duration::from_secs(self.config.heartbeat_interval as u64);
+            context
+                .schedule_periodic_event(period, period, Self::send_heartbeat, ())
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for SleUser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SleUser")
+            .field("service", &self.config.service)
+            .field("service_instance", &self.config.service_instance)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Decodes the annotated frame of a RAF transfer data invocation.
+fn decode_frame(content: &[u8]) -> Option<SleFrame> {
+    let mut fields = Fields(content);
+    let _credentials = fields.next()?;
+    let (_, time) = fields.next()?;
+    let _antenna_id = fields.next()?;
+    let (_, continuity) = fields.next()?;
+    let (_, quality) = fields.next()?;
+    let _private_annotation = fields.next()?;
+    let (_, data) = fields.next()?;
+
+    Some(SleFrame {
+        earth_receive_time: decode_time(time)?,
+        quality: match decode_integer(quality) {
+            0 => SleFrameQuality::Good,
+            1 => SleFrameQuality::Erred,
+            _ => SleFrameQuality::Undetermined,
+        },
+        continuity: decode_integer(continuity),
+        data: Bytes::copy_from_slice(data),
+    })
+}
+
+/// Decodes a CCSDS day segmented time, with microsecond or picosecond
+/// resolution.
+fn decode_time(time: &[u8]) -> Option<SystemTime> {
+    let days = u16::from_be_bytes(time.get(0..2)?.try_into().unwrap()) as u64;
+    let millis = u32::from_be_bytes(time.get(2..6)?.try_into().unwrap()) as u64;
+    let sub_millis = match time.len() {
+        8 => Duration::from_micros(u16::from_be_bytes([time[6], time[7]]) as u64),
+        10 => {
+            Duration::from_nanos(u32::from_be_bytes(time[6..10].try_into().unwrap()) as u64 / 1000)
+        }
+        _ => return None,
+    };
+    let since_epoch = Duration::from_secs(days.checked_sub(CCSDS_EPOCH_DAYS)? * 86400)
+        + Duration::from_millis(millis)
+        + sub_millis;
+
+    Some(SystemTime::UNIX_EPOCH + since_epoch)
+}
+
+/// Encodes a service instance identifier.
+fn encode_service_instance(identifier: &str) -> Option<Vec<u8>> {
+    let mut attributes = Vec::new();
+    for attribute in identifier.split('.') {
+        let (name, value) = attribute.split_once('=')?;
+        let (_, oid) = SI_ATTRIBUTES.iter().find(|(known, _)| *known == name)?;
+        let mut pair = object_identifier(oid);
+        pair.extend(tlv(&[0x1A], value.as_bytes()));
+        attributes.extend(tlv(&[0x31], &tlv(&[0x30], &pair)));
+    }
+
+    Some(tlv(&[0x30], &attributes))
+}
+
+/// BER tag.
+#[derive(Clone, Copy, Debug)]
+struct Tag {
+    /// Tag number.
+    number: u32,
+
+    /// The value is constructed.
+    constructed: bool,
+}
+
+/// Iterator over the BER fields of a constructed value.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = (Tag, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (tag, content, rest) = parse_tlv(self.0)?;
+        self.0 = rest;
+
+        Some((tag, content))
+    }
+}
+
+/// Parses a BER value with definite length.
+///
+/// Returns the tag, the content and the remaining bytes.
+fn parse_tlv(data: &[u8]) -> Option<(Tag, &[u8], &[u8])> {
+    let (&first, mut data) = data.split_first()?;
+    let constructed = first & 0x20 != 0;
+    let mut number = (first & 0x1F) as u32;
+    if number == 0x1F {
+        number = 0;
+        loop {
+            let (&byte, rest) = data.split_first()?;
+            data = rest;
+            number = number.checked_mul(128)? | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+    }
+    let (&first, mut data) = data.split_first()?;
+    let len = if first & 0x80 == 0 {
+        first as usize
+    } else {
+        let count = (first & 0x7F) as usize;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let (len, rest) = data.split_at_checked(count)?;
+        data = rest;
+        len.iter().fold(0, |len, &byte| (len << 8) | byte as usize)
+    };
+    let (content, rest) = data.split_at_checked(len)?;
+
+    Some((
+        Tag {
+            number,
+            constructed,
+        },
+        content,
+        rest,
+    ))
+}
+
+/// Returns the first integer of a value, looking into constructed values.
+fn first_integer(tag: Tag, content: &[u8]) -> Option<i64> {
+    if !tag.constructed {
+        return Some(decode_integer(content));
+    }
+    let (tag, content, _) = parse_tlv(content)?;
+
+    first_integer(tag, content)
+}
+
+/// Decodes the content of a BER integer.
+fn decode_integer(content: &[u8]) -> i64 {
+    let Some(&first) = content.first() else {
+        return 0;
+    };
+    let init = if first & 0x80 != 0 { -1 } else { 0 };
+
+    content
+        .iter()
+        .take(8)
+        .fold(init, |value, &byte| (value << 8) | byte as i64)
+}
+
+/// Encodes a BER value with definite length.
+fn tlv(tag: &[u8], content: &[u8]) -> Vec<u8> {
+    let mut value = tag.to_vec();
+    match content.len() {
+        len @ 0..0x80 => value.push(len as u8),
+        len => {
+            let bytes = (len as u32).to_be_bytes();
+            let skip = bytes.iter().take_while(|&&byte| byte == 0).count();
+            value.push(0x80 | (4 - skip) as u8);
+            value.extend(&bytes[skip..]);
+        }
+    }
+    value.extend(content);
+
+    value
+}
+
+/// Encodes a context-specific constructed tag.
+fn constructed(number: u32) -> Vec<u8> {
+    if number < 0x1F {
+        return vec![0xA0 | number as u8];
+    }
+    let mut tag = vec![0xBF];
+    tag.extend(base128(number as u64));
+
+    tag
+}
+
+/// Encodes a BER integer.
+fn integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut skip = 0;
+    while skip < 7
+        && ((bytes[skip] == 0 && bytes[skip + 1] & 0x80 == 0)
+            || (bytes[skip] == 0xFF && bytes[skip + 1] & 0x80 != 0))
+    {
+        skip += 1;
+    }
+
+    tlv(&[0x02], &bytes[skip..])
+}
+
+/// Encodes a BER object identifier.
+fn object_identifier(arcs: &[u64]) -> Vec<u8> {
+    let mut content = base128(arcs[0] * 40 + arcs[1]);
+    for &arc in &arcs[2..] {
+        content.extend(base128(arc));
+    }
+
+    tlv(&[0x06], &content)
+}
+
+/// Encodes a number in base 128, most significant group first.
+fn base128(mut value: u64) -> Vec<u8> {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    value >>= 7;
+    while value != 0 {
+        bytes.push(0x80 | (value & 0x7F) as u8);
+        value >>= 7;
+    }
+    bytes.reverse();
+
+    bytes
+}
@@ -0,0 +1,2089 @@
+//! SOME/IP port model.
+//!
+//! This module contains the [`SomeIpPort`] model, which connects a simulation
+//! to automotive Ethernet ECUs speaking SOME/IP, both as:
+//! * a server, offering the configured services, forwarding the method
+//!   requests it receives and sending the responses and event notifications of
+//!   the simulation,
+//! * a client, looking for the required services, subscribing to their event
+//!   groups, sending the method requests of the simulation and forwarding the
+//!   responses and notifications it receives.
+//!
+//! Services are discovered with SOME/IP-SD over UDP: the offered services are
+//! announced cyclically on the SD multicast group and on request, the required
+//! services are looked for until they are offered, and their event groups are
+//! subscribed to each time they are offered. Service availability and
+//! subscription changes are reported on a dedicated output.
+//!
+//! Methods and events are exchanged over UDP or TCP, depending on the
+//! `transport` configuration. Over UDP, messages are sent from and received on
+//! the local endpoint; over TCP, the local endpoint accepts the connections of
+//! the clients and connections to the offered services are opened when
+//! needed.
+//!
+//! Notifications from the simulation are sent to the subscribers of all the
+//! offered event groups containing the event. Responses from the simulation,
+//! built with [`SomeIpMessage::response_to`] or [`SomeIpMessage::error_to`],
+//! are routed to the client of the matching request. Requests to a service
+//! which is not available are answered by the port with an error message with
+//! return code [`E_NOT_REACHABLE`].
+//!
+//! Segmented (SOME/IP-TP) messages and multicast event groups are not
+//! supported.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::someip::{SomeIpConfig, SomeIpMessage, SomeIpTransport};
+//!
+//! let config = ConfigLoader::<SomeIpConfig>::new()
+//!     .code(
+//!         r#"
+//! localAddress = "192.168.1.10:30509"
+//! period = 10
+//!
+//! [[offered]]
+//! service = 0x1234
+//! instance = 1
+//!
+//! [[offered.eventgroups]]
+//! id = 1
+//! events = [0x8001, 0x8002]
+//!
+//! [[required]]
+//! service = 0x5678
+//! eventgroups = [1]
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.transport, SomeIpTransport::Udp);
+//! assert_eq!(config.sd_port, 30490);
+//! assert_eq!(config.required[0].instance, 0xFFFF);
+//!
+//! // Calls method 0x0001 of service 0x5678 once it is available.
+//! let request = SomeIpMessage::request(0x5678, 0x0001, vec![1, 2, 3]).with_session(1);
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Error, ErrorKind, Read, Result as IoResult};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use schematic::{Config, ConfigEnum};
+use serde::{Deserialize, Serialize};
+
+use mio::net::{TcpListener, TcpStream, UdpSocket};
+use mio::{Interest, Registry, Token};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus, WriteBuffer};
+
+/// Return code: no error.
+pub const E_OK: u8 = 0x00;
+
+/// Return code: unspecified error.
+pub const E_NOT_OK: u8 = 0x01;
+
+/// Return code: unknown service.
+pub const E_UNKNOWN_SERVICE: u8 = 0x02;
+
+/// Return code: unknown method.
+pub const E_UNKNOWN_METHOD: u8 = 0x03;
+
+/// Return code: service not reachable.
+pub const E_NOT_REACHABLE: u8 = 0x05;
+
+/// SOME/IP protocol version.
+const PROTOCOL_VERSION: u8 = 1;
+
+/// Size of the SOME/IP header.
+const HEADER_LEN: usize = 16;
+
+/// Size of the header fields covered by the length field.
+const LENGTH_OFFSET: usize = 8;
+
+/// SOME/IP-SD service identifier.
+const SD_SERVICE: u16 = 0xFFFF;
+
+/// SOME/IP-SD method identifier.
+const SD_METHOD: u16 = 0x8100;
+
+/// SOME/IP-SD reboot flag.
+const SD_REBOOT: u8 = 0x80;
+
+/// SOME/IP-SD unicast flag.
+const SD_UNICAST: u8 = 0x40;
+
+/// Size of a SOME/IP-SD entry.
+const SD_ENTRY_LEN: usize = 16;
+
+/// `FindService` entry type.
+const FIND_SERVICE: u8 = 0x00;
+
+/// `OfferService` entry type.
+const OFFER_SERVICE: u8 = 0x01;
+
+/// `SubscribeEventgroup` entry type.
+const SUBSCRIBE_EVENTGROUP: u8 = 0x06;
+
+/// `SubscribeEventgroupAck` entry type.
+const SUBSCRIBE_EVENTGROUP_ACK: u8 = 0x07;
+
+/// IPv4 endpoint option type.
+const IPV4_ENDPOINT: u8 = 0x04;
+
+/// Size of an IPv4 endpoint option.
+const IPV4_ENDPOINT_LEN: usize = 12;
+
+/// Any instance or major version.
+const ANY: u16 = 0xFFFF;
+
+/// Maximum number of requests awaiting a response from the simulation.
+///
+/// The oldest requests are forgotten beyond this number.
+const MAX_PENDING_REQUESTS: usize = 1024;
+
+/// Maximum size of a received datagram.
+const MAX_DATAGRAM_LEN: usize = 65536;
+
+/// SOME/IP-SD unicast socket token.
+const SD: Token = Token(0);
+
+/// SOME/IP-SD multicast socket token.
+const SD_MULTICAST: Token = Token(1);
+
+/// UDP socket token.
+const UDP: Token = Token(2);
+
+/// TCP listener token.
+const LISTENER: Token = Token(3);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(4);
+
+/// Token of the first TCP connection.
+const FIRST_CONNECTION: usize = 5;
+
+/// SOME/IP port model instance configuration.
+#[derive(Config, Debug)]
+pub struct SomeIpConfig {
+    /// Local unicast endpoint, as `IP:PORT`, for methods and events.
+    ///
+    /// The IPv4 address is advertised to the other nodes, and should not be
+    /// the unspecified address.
+    pub local_address: String,
+
+    /// Transport protocol of methods and events.
+    pub transport: SomeIpTransport,
+
+    /// SOME/IP-SD multicast group.
+    #[setting(default = "224.224.224.245")]
+    pub sd_group: String,
+
+    /// SOME/IP-SD port.
+    #[setting(default = 30490)]
+    pub sd_port: u16,
+
+    /// Period of the cyclic offers and of the searches for the required
+    /// services.
+    #[setting(default = ConfigDuration::from_millis(1000))]
+    pub sd_period: ConfigDuration,
+
+    /// Lifetime of the offers and subscriptions, in seconds.
+    #[setting(default = 3)]
+    pub ttl: u32,
+
+    /// Offered services.
+    #[setting(nested)]
+    pub offered: Vec<SomeIpOfferedConfig>,
+
+    /// Required services.
+    #[setting(nested)]
+    pub required: Vec<SomeIpRequiredConfig>,
+
+    /// Delay for the first scheduled message forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which the received messages are forwarded into the
+    /// simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time messages are forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// Offered service configuration.
+#[derive(Config, Debug)]
+pub struct SomeIpOfferedConfig {
+    /// Service identifier.
+    pub service: u16,
+
+    /// Instance identifier.
+    #[setting(default = 1)]
+    pub instance: u16,
+
+    /// Major version of the service interface.
+    #[setting(default = 1)]
+    pub major_version: u8,
+
+    /// Minor version of the service interface.
+    pub minor_version: u32,
+
+    /// Event groups of the service.
+    #[setting(nested)]
+    pub eventgroups: Vec<SomeIpEventgroupConfig>,
+}
+
+/// Event group configuration.
+#[derive(Config, Debug)]
+pub struct SomeIpEventgroupConfig {
+    /// Event group identifier.
+    pub id: u16,
+
+    /// Identifiers of the events of the group.
+    pub events: Vec<u16>,
+}
+
+/// Required service configuration.
+#[derive(Config, Debug)]
+pub struct SomeIpRequiredConfig {
+    /// Service identifier.
+    pub service: u16,
+
+    /// Instance identifier, `0xFFFF` for any instance.
+    #[setting(default = 0xFFFF)]
+    pub instance: u16,
+
+    /// Major version of the service interface, `0xFF` for any version.
+    #[setting(default = 0xFF)]
+    pub major_version: u8,
+
+    /// Event groups subscribed to.
+    pub eventgroups: Vec<u16>,
+}
+
+/// Transport protocol of methods and events.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SomeIpTransport {
+    /// UDP.
+    #[default]
+    Udp,
+
+    /// TCP.
+    Tcp,
+}
+
+impl SomeIpTransport {
+    /// Returns the IP protocol number of the transport.
+    fn protocol(self) -> u8 {
+        match self {
+            Self::Udp => 0x11,
+            Self::Tcp => 0x06,
+        }
+    }
+}
+
+/// SOME/IP message type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SomeIpMessageType {
+    /// Request expecting a response.
+    Request,
+
+    /// Fire-and-forget request.
+    RequestNoReturn,
+
+    /// Event notification.
+    Notification,
+
+    /// Response.
+    Response,
+
+    /// Error response.
+    Error,
+}
+
+impl SomeIpMessageType {
+    /// Returns the encoded message type.
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Request => 0x00,
+            Self::RequestNoReturn => 0x01,
+            Self::Notification => 0x02,
+            Self::Response => 0x80,
+            Self::Error => 0x81,
+        }
+    }
+
+    /// Decodes a message type.
+    fn from_u8(message_type: u8) -> Option<Self> {
+        match message_type {
+            0x00 => Some(Self::Request),
+            0x01 => Some(Self::RequestNoReturn),
+            0x02 => Some(Self::Notification),
+            0x80 => Some(Self::Response),
+            0x81 => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+/// SOME/IP message.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SomeIpMessage {
+    /// Service identifier.
+    pub service: u16,
+
+    /// Method or event identifier.
+    pub method: u16,
+
+    /// Client identifier.
+    pub client: u16,
+
+    /// Session identifier.
+    pub session: u16,
+
+    /// Major version of the service interface.
+    pub interface_version: u8,
+
+    /// Message type.
+    pub message_type: SomeIpMessageType,
+
+    /// Return code.
+    pub return_code: u8,
+
+    /// Payload.
+    pub payload: Bytes,
+}
+
+impl SomeIpMessage {
+    /// Creates a message with interface version 1, client and session
+    /// identifiers 0 and return code [`E_OK`].
+    pub fn new(
+        service: u16,
+        method: u16,
+        message_type: SomeIpMessageType,
+        payload: impl Into<Bytes>,
+    ) -> Self {
+        Self {
+            service,
+            method,
+            client: 0,
+            session: 0,
+            interface_version: 1,
+            message_type,
+            return_code: E_OK,
+            payload: payload.into(),
+        }
+    }
+
+    /// Creates a request expecting a response.
+    pub fn request(service: u16, method: u16, payload: impl Into<Bytes>) -> Self {
+        Self::new(service, method, SomeIpMessageType::Request, payload)
+    }
+
+    /// Creates an event notification.
+    pub fn notification(service: u16, event: u16, payload: impl Into<Bytes>) -> Self {
+        Self::new(service, event, SomeIpMessageType::Notification, payload)
+    }
+
+    /// Creates the response to a request.
+    pub fn response_to(request: &Self, payload: impl Into<Bytes>) -> Self {
+        Self {
+            message_type: SomeIpMessageType::Response,
+            return_code: E_OK,
+            payload: payload.into(),
+            ..request.clone()
+        }
+    }
+
+    /// Creates the error response to a request, with the provided return
+    /// code.
+    pub fn error_to(request: &Self, return_code: u8) -> Self {
+        Self {
+            message_type: SomeIpMessageType::Error,
+            return_code,
+            payload: Bytes::new(),
+            ..request.clone()
+        }
+    }
+
+    /// Sets the client identifier.
+    pub fn with_client(mut self, client: u16) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Sets the session identifier.
+    pub fn with_session(mut self, session: u16) -> Self {
+        self.session = session;
+        self
+    }
+
+    /// Sets the major version of the service interface.
+    pub fn with_interface_version(mut self, interface_version: u8) -> Self {
+        self.interface_version = interface_version;
+        self
+    }
+
+    /// Encodes the message, header included.
+    pub fn encode(&self) -> Bytes {
+        let mut buf = Vec::with_capacity(HEADER_LEN + self.payload.len());
+        encode_header(
+            &mut buf,
+            self.service,
+            self.method,
+            self.client,
+            self.session,
+            self.interface_version,
+            self.message_type.to_u8(),
+            self.return_code,
+            self.payload.len(),
+        );
+        buf.extend_from_slice(&self.payload);
+
+        buf.into()
+    }
+
+    /// Decodes the message at the start of a buffer, returning the message and
+    /// its encoded size.
+    ///
+    /// `None` is returned if the buffer does not start with a complete and
+    /// valid message. Segmented messages are not supported.
+    pub fn decode(buf: &[u8]) -> Option<(Self, usize)> {
+        let len = message_len(buf)?.ok()?;
+        if buf.len() < len || buf[12] != PROTOCOL_VERSION {
+            return None;
+        }
+        let message = Self {
+            service: u16::from_be_bytes([buf[0], buf[1]]),
+            method: u16::from_be_bytes([buf[2], buf[3]]),
+            client: u16::from_be_bytes([buf[8], buf[9]]),
+            session: u16::from_be_bytes([buf[10], buf[11]]),
+            interface_version: buf[13],
+            message_type: SomeIpMessageType::from_u8(buf[14])?,
+            return_code: buf[15],
+            payload: Bytes::copy_from_slice(&buf[HEADER_LEN..len]),
+        };
+
+        Some((message, len))
+    }
+}
+
+/// Service discovery event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SomeIpServiceEvent {
+    /// Required service offered by a server.
+    Available {
+        /// Service identifier.
+        service: u16,
+        /// Instance identifier.
+        instance: u16,
+        /// Endpoint of the server.
+        endpoint: SocketAddr,
+    },
+
+    /// Required service no longer offered, or offer expired.
+    Unavailable {
+        /// Service identifier.
+        service: u16,
+        /// Instance identifier.
+        instance: u16,
+    },
+
+    /// Subscription to an event group acknowledged.
+    Subscribed {
+        /// Service identifier.
+        service: u16,
+        /// Instance identifier.
+        instance: u16,
+        /// Event group identifier.
+        eventgroup: u16,
+    },
+
+    /// Subscription to an event group rejected.
+    SubscriptionRejected {
+        /// Service identifier.
+        service: u16,
+        /// Instance identifier.
+        instance: u16,
+        /// Event group identifier.
+        eventgroup: u16,
+    },
+
+    /// Client subscribed to an offered event group.
+    SubscriberAdded {
+        /// Service identifier.
+        service: u16,
+        /// Instance identifier.
+        instance: u16,
+        /// Event group identifier.
+        eventgroup: u16,
+        /// Endpoint of the subscriber.
+        endpoint: SocketAddr,
+    },
+
+    /// Client unsubscribed from an offered event group, or subscription
+    /// expired.
+    SubscriberRemoved {
+        /// Service identifier.
+        service: u16,
+        /// Instance identifier.
+        instance: u16,
+        /// Event group identifier.
+        eventgroup: u16,
+        /// Endpoint of the subscriber.
+        endpoint: SocketAddr,
+    },
+}
+
+/// Event forwarded to the simulation.
+#[derive(Debug)]
+enum SomeIpEvent {
+    /// Received message.
+    Message(SomeIpMessage),
+
+    /// Service discovery event.
+    Service(SomeIpServiceEvent),
+}
+
+/// Peer of a received request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Peer {
+    /// UDP endpoint.
+    Udp(SocketAddr),
+
+    /// TCP connection, with its index and identifier.
+    Tcp(usize, u64),
+}
+
+/// Request awaiting a response from the simulation.
+#[derive(Debug)]
+struct PendingRequest {
+    /// Service, method, client and session identifiers.
+    key: (u16, u16, u16, u16),
+
+    /// Peer to respond to.
+    peer: Peer,
+}
+
+/// TCP connection.
+#[derive(Debug)]
+struct Connection {
+    /// Connection identifier.
+    id: u64,
+
+    /// TCP stream.
+    stream: TcpStream,
+
+    /// Remote endpoint.
+    peer: SocketAddr,
+
+    /// Received bytes not yet decoded.
+    rx: Vec<u8>,
+
+    /// Bytes not yet written.
+    tx: WriteBuffer,
+}
+
+/// Subscriber of an offered event group.
+#[derive(Debug)]
+struct Subscriber {
+    /// Event group identifier.
+    eventgroup: u16,
+
+    /// Endpoint of the subscriber.
+    endpoint: SocketAddr,
+
+    /// Expiry of the subscription.
+    expiry: Instant,
+}
+
+/// Offered service.
+#[derive(Debug)]
+struct OfferedService {
+    /// Service identifier.
+    service: u16,
+
+    /// Instance identifier.
+    instance: u16,
+
+    /// Major version.
+    major_version: u8,
+
+    /// Minor version.
+    minor_version: u32,
+
+    /// Event groups, with their events.
+    eventgroups: Vec<(u16, Vec<u16>)>,
+
+    /// Subscribers.
+    subscribers: Vec<Subscriber>,
+}
+
+/// Server of a required service.
+#[derive(Debug)]
+struct RemoteService {
+    /// Instance identifier.
+    instance: u16,
+
+    /// Endpoint for methods and events.
+    endpoint: SocketAddr,
+
+    /// Expiry of the offer.
+    expiry: Instant,
+
+    /// Event groups whose subscription has been acknowledged.
+    subscribed: Vec<u16>,
+}
+
+/// Required service.
+#[derive(Debug)]
+struct RequiredService {
+    /// Service identifier.
+    service: u16,
+
+    /// Instance identifier, or any.
+    instance: u16,
+
+    /// Major version, or any.
+    major_version: u8,
+
+    /// Event groups subscribed to.
+    eventgroups: Vec<u16>,
+
+    /// Server, while the service is offered.
+    remote: Option<RemoteService>,
+}
+
+/// SOME/IP-SD entry.
+#[derive(Debug)]
+struct SdEntry {
+    /// Entry type.
+    kind: u8,
+
+    /// Service identifier.
+    service: u16,
+
+    /// Instance identifier.
+    instance: u16,
+
+    /// Major version.
+    major_version: u8,
+
+    /// Time to live, in seconds.
+    ttl: u32,
+
+    /// Event group identifier, for event group entries.
+    eventgroup: u16,
+
+    /// Endpoints referenced by the entry.
+    endpoints: Vec<(SocketAddr, u8)>,
+}
+
+/// SOME/IP port.
+struct SomeIpInner {
+    /// Local unicast endpoint.
+    local: SocketAddrV4,
+
+    /// Transport of methods and events.
+    transport: SomeIpTransport,
+
+    /// SOME/IP-SD unicast socket.
+    sd: UdpSocket,
+
+    /// SOME/IP-SD multicast socket.
+    sd_multicast: UdpSocket,
+
+    /// SOME/IP-SD multicast endpoint.
+    sd_group: SocketAddr,
+
+    /// Session identifier of the next SOME/IP-SD message.
+    sd_session: u16,
+
+    /// The session identifier has not wrapped around since startup.
+    sd_reboot: bool,
+
+    /// Period of the cyclic offers and searches.
+    sd_period: Duration,
+
+    /// Next cyclic offer and search.
+    next_sd: Option<Instant>,
+
+    /// Lifetime of the offers and subscriptions, in seconds.
+    ttl: u32,
+
+    /// UDP socket.
+    udp: UdpSocket,
+
+    /// TCP listener, for the TCP transport.
+    listener: Option<TcpListener>,
+
+    /// TCP connections, indexed by token.
+    connections: Vec<Option<Connection>>,
+
+    /// Identifier of the next connection.
+    next_id: u64,
+
+    /// Offered services.
+    offered: Vec<OfferedService>,
+
+    /// Required services.
+    required: Vec<RequiredService>,
+
+    /// Requests awaiting a response from the simulation, oldest first.
+    requests: VecDeque<PendingRequest>,
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+
+    /// Events not yet read.
+    events: VecDeque<SomeIpEvent>,
+
+    /// MIO registry, available once the port is registered.
+    registry: Option<Registry>,
+}
+
+impl SomeIpInner {
+    /// Binds the SOME/IP-SD and unicast sockets.
+    fn new(config: &SomeIpConfig) -> IoResult<Self> {
+        let local: SocketAddrV4 = config.local_address.parse().map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid local address {}.", config.local_address),
+            )
+        })?;
+        let group: Ipv4Addr = config.sd_group.parse().map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid SOME/IP-SD multicast group {}.", config.sd_group),
+            )
+        })?;
+
+        // The SD port is shared with the other SOME/IP stacks of the host:
+        // unicast messages are received on the local address, and multicast
+        // messages on the group address.
+        let sd = sd_socket(SocketAddrV4::new(*local.ip(), config.sd_port))?;
+        sd.set_multicast_if_v4(local.ip())?;
+        let sd_multicast = sd_socket(SocketAddrV4::new(group, config.sd_port))?;
+        sd_multicast.join_multicast_v4(&group, local.ip())?;
+
+        let offered = config
+            .offered
+            .iter()
+            .map(|offered| OfferedService {
+                service: offered.service,
+                instance: offered.instance,
+                major_version: offered.major_version,
+                minor_version: offered.minor_version,
+                eventgroups: offered
+                    .eventgroups
+                    .iter()
+                    .map(|eventgroup| (eventgroup.id, eventgroup.events.clone()))
+                    .collect(),
+                subscribers: Vec::new(),
+            })
+            .collect();
+        let required = config
+            .required
+            .iter()
+            .map(|required| RequiredService {
+                service: required.service,
+                instance: required.instance,
+                major_version: required.major_version,
+                eventgroups: required.eventgroups.clone(),
+                remote: None,
+            })
+            .collect();
+
+        Ok(Self {
+            local,
+            transport: config.transport,
+            sd: UdpSocket::from_std(sd.into()),
+            sd_multicast: UdpSocket::from_std(sd_multicast.into()),
+            sd_group: SocketAddrV4::new(group, config.sd_port).into(),
+            sd_session: 1,
+            sd_reboot: true,
+            sd_period: (*config.sd_period).max(Duration::from_millis(1)),
+            next_sd: None,
+            ttl: config.ttl,
+            udp: UdpSocket::bind(local.into())?,
+            listener: None,
+            connections: Vec::new(),
+            next_id: 0,
+            offered,
+            required,
+            requests: VecDeque::new(),
+            buffer: vec![0; MAX_DATAGRAM_LEN],
+            events: VecDeque::new(),
+            registry: None,
+        })
+    }
+
+    /// Sends a SOME/IP-SD message.
+    ///
+    /// Each entry is provided with the endpoint option it references, if any.
+    fn send_sd(&mut self, entries: &[([u8; SD_ENTRY_LEN], Option<SocketAddr>)], to: SocketAddr) {
+        if entries.is_empty() {
+            return;
+        }
+        let message = encode_sd(entries, self.sd_session, self.sd_reboot, self.transport);
+
+        self.sd_session = match self.sd_session.checked_add(1) {
+            Some(session) => session,
+            None => {
+                self.sd_reboot = false;
+                1
+            }
+        };
+        // A lost SD message is recovered by the next cycle.
+        let _ = self.sd.send_to(&message, to);
+    }
+
+    /// Returns the offer entries of the offered services matching a search,
+    /// or of all offered services.
+    fn offers(&self, search: Option<&SdEntry>) -> Vec<([u8; SD_ENTRY_LEN], Option<SocketAddr>)> {
+        self.offered
+            .iter()
+            .filter(|offered| {
+                search.is_none_or(|search| {
+                    search.service == offered.service
+                        && (search.instance == ANY || search.instance == offered.instance)
+                        && (search.major_version == 0xFF
+                            || search.major_version == offered.major_version)
+                })
+            })
+            .map(|offered| {
+                (
+                    service_entry(
+                        OFFER_SERVICE,
+                        offered.service,
+                        offered.instance,
+                        offered.major_version,
+                        self.ttl,
+                        offered.minor_version,
+                    ),
+                    Some(SocketAddr::V4(self.local)),
+                )
+            })
+            .collect()
+    }
+
+    /// Runs the cyclic offers and searches and expires the offers and
+    /// subscriptions.
+    fn cycle(&mut self) {
+        let now = Instant::now();
+        let offers = self.offers(None);
+        let finds: Vec<_> = self
+            .required
+            .iter()
+            .filter(|required| required.remote.is_none())
+            .map(|required| {
+                (
+                    service_entry(
+                        FIND_SERVICE,
+                        required.service,
+                        required.instance,
+                        required.major_version,
+                        self.ttl,
+                        u32::MAX,
+                    ),
+                    None,
+                )
+            })
+            .collect();
+        self.send_sd(&[offers, finds].concat(), self.sd_group);
+
+        for required in &mut self.required {
+            if let Some(remote) = required.remote.take_if(|remote| remote.expiry <= now) {
+                self.events
+                    .push_back(SomeIpEvent::Service(SomeIpServiceEvent::Unavailable {
+                        service: required.service,
+                        instance: remote.instance,
+                    }));
+            }
+        }
+        for offered in &mut self.offered {
+            offered.subscribers.retain(|subscriber| {
+                if subscriber.expiry > now {
+                    return true;
+                }
+                self.events.push_back(SomeIpEvent::Service(
+                    SomeIpServiceEvent::SubscriberRemoved {
+                        service: offered.service,
+                        instance: offered.instance,
+                        eventgroup: subscriber.eventgroup,
+                        endpoint: subscriber.endpoint,
+                    },
+                ));
+                false
+            });
+        }
+    }
+
+    /// Handles a SOME/IP-SD message.
+    fn handle_sd(&mut self, message: &[u8], source: SocketAddr) {
+        // Own multicast messages are looped back.
+        if source == SocketAddr::V4(SocketAddrV4::new(*self.local.ip(), self.sd_group.port())) {
+            return;
+        }
+        let Some(entries) = decode_sd(message) else {
+            return;
+        };
+        let now = Instant::now();
+        let mut replies = Vec::new();
+        for entry in entries {
+            match entry.kind {
+                FIND_SERVICE => replies.extend(self.offers(Some(&entry))),
+                OFFER_SERVICE => replies.extend(self.handle_offer(&entry, now)),
+                SUBSCRIBE_EVENTGROUP => replies.extend(self.handle_subscribe(&entry, now)),
+                SUBSCRIBE_EVENTGROUP_ACK => self.handle_subscribe_ack(&entry),
+                _ => {}
+            }
+        }
+        self.send_sd(&replies, source);
+    }
+
+    /// Handles an offer or a stopped offer, returning the subscriptions to
+    /// send.
+    fn handle_offer(
+        &mut self,
+        entry: &SdEntry,
+        now: Instant,
+    ) -> Vec<([u8; SD_ENTRY_LEN], Option<SocketAddr>)> {
+        let protocol = self.transport.protocol();
+        let mut subscriptions = Vec::new();
+        for index in 0..self.required.len() {
+            let required = &mut self.required[index];
+            if required.service != entry.service
+                || (required.instance != ANY && required.instance != entry.instance)
+                || (required.major_version != 0xFF && required.major_version != entry.major_version)
+                || required
+                    .remote
+                    .as_ref()
+                    .is_some_and(|remote| remote.instance != entry.instance)
+            {
+                continue;
+            }
+            if entry.ttl == 0 {
+                if required.remote.take().is_some() {
+                    self.events
+                        .push_back(SomeIpEvent::Service(SomeIpServiceEvent::Unavailable {
+                            service: entry.service,
+                            instance: entry.instance,
+                        }));
+                }
+                continue;
+            }
+            let Some(&(endpoint, _)) = entry
+                .endpoints
+                .iter()
+                .find(|(_, endpoint_protocol)| *endpoint_protocol == protocol)
+            else {
+                continue;
+            };
+            let expiry = now + Duration::from_secs(u64::from(entry.ttl));
+            match &mut required.remote {
+                Some(remote) if remote.endpoint == endpoint => remote.expiry = expiry,
+                remote => {
+                    *remote = Some(RemoteService {
+                        instance: entry.instance,
+                        endpoint,
+                        expiry,
+                        subscribed: Vec::new(),
+                    });
+                    self.events
+                        .push_back(SomeIpEvent::Service(SomeIpServiceEvent::Available {
+                            service: entry.service,
+                            instance: entry.instance,
+                            endpoint,
+                        }));
+                }
+            }
+            if required.eventgroups.is_empty() {
+                continue;
+            }
+            // Subscriptions are renewed with each offer.
+            let eventgroups = required.eventgroups.clone();
+            let Some(subscriber) = self.subscriber_endpoint(endpoint) else {
+                continue;
+            };
+            for eventgroup in eventgroups {
+                subscriptions.push((
+                    eventgroup_entry(
+                        SUBSCRIBE_EVENTGROUP,
+                        entry.service,
+                        entry.instance,
+                        entry.major_version,
+                        self.ttl,
+                        eventgroup,
+                    ),
+                    Some(subscriber),
+                ));
+            }
+        }
+
+        subscriptions
+    }
+
+    /// Handles a subscription or a stopped subscription, returning the
+    /// acknowledgements to send.
+    fn handle_subscribe(
+        &mut self,
+        entry: &SdEntry,
+        now: Instant,
+    ) -> Vec<([u8; SD_ENTRY_LEN], Option<SocketAddr>)> {
+        let protocol = self.transport.protocol();
+        let endpoint = entry
+            .endpoints
+            .iter()
+            .find(|(_, endpoint_protocol)| *endpoint_protocol == protocol)
+            .map(|&(endpoint, _)| endpoint);
+        let offered = self.offered.iter_mut().find(|offered| {
+            offered.service == entry.service
+                && offered.instance == entry.instance
+                && offered.major_version == entry.major_version
+                && offered
+                    .eventgroups
+                    .iter()
+                    .any(|(eventgroup, _)| *eventgroup == entry.eventgroup)
+        });
+        let (Some(offered), Some(endpoint)) = (offered, endpoint) else {
+            // Unknown event groups and subscriptions without endpoint are
+            // rejected.
+            if entry.ttl == 0 {
+                return Vec::new();
+            }
+            return vec![(
+                eventgroup_entry(
+                    SUBSCRIBE_EVENTGROUP_ACK,
+                    entry.service,
+                    entry.instance,
+                    entry.major_version,
+                    0,
+                    entry.eventgroup,
+                ),
+                None,
+            )];
+        };
+
+        let position = offered.subscribers.iter().position(|subscriber| {
+            subscriber.eventgroup == entry.eventgroup && subscriber.endpoint == endpoint
+        });
+        if entry.ttl == 0 {
+            if let Some(position) = position {
+                offered.subscribers.remove(position);
+                self.events.push_back(SomeIpEvent::Service(
+                    SomeIpServiceEvent::SubscriberRemoved {
+                        service: offered.service,
+                        instance: offered.instance,
+                        eventgroup: entry.eventgroup,
+                        endpoint,
+                    },
+                ));
+            }
+            return Vec::new();
+        }
+        let expiry = now + Duration::from_secs(u64::from(entry.ttl));
+        match position {
+            Some(position) => offered.subscribers[position].expiry = expiry,
+            None => {
+                offered.subscribers.push(Subscriber {
+                    eventgroup: entry.eventgroup,
+                    endpoint,
+                    expiry,
+                });
+                self.events
+                    .push_back(SomeIpEvent::Service(SomeIpServiceEvent::SubscriberAdded {
+                        service: offered.service,
+                        instance: offered.instance,
+                        eventgroup: entry.eventgroup,
+                        endpoint,
+                    }));
+            }
+        }
+
+        vec![(
+            eventgroup_entry(
+                SUBSCRIBE_EVENTGROUP_ACK,
+                entry.service,
+                entry.instance,
+                entry.major_version,
+                entry.ttl,
+                entry.eventgroup,
+            ),
+            None,
+        )]
+    }
+
+    /// Handles a subscription acknowledgement or rejection.
+    fn handle_subscribe_ack(&mut self, entry: &SdEntry) {
+        for required in &mut self.required {
+            if required.service != entry.service {
+                continue;
+            }
+            let Some(remote) = required
+                .remote
+                .as_mut()
+                .filter(|remote| remote.instance == entry.instance)
+            else {
+                continue;
+            };
+            let position = remote
+                .subscribed
+                .iter()
+                .position(|&eventgroup| eventgroup == entry.eventgroup);
+            let event = match (entry.ttl, position) {
+                (0, position) => {
+                    if let Some(position) = position {
+                        remote.subscribed.remove(position);
+                    }
+                    SomeIpServiceEvent::SubscriptionRejected {
+                        service: entry.service,
+                        instance: entry.instance,
+                        eventgroup: entry.eventgroup,
+                    }
+                }
+                (_, None) => {
+                    remote.subscribed.push(entry.eventgroup);
+                    SomeIpServiceEvent::Subscribed {
+                        service: entry.service,
+                        instance: entry.instance,
+                        eventgroup: entry.eventgroup,
+                    }
+                }
+                (_, Some(_)) => continue,
+            };
+            self.events.push_back(SomeIpEvent::Service(event));
+        }
+    }
+
+    /// Returns the local endpoint of the subscriptions to a server, opening a
+    /// connection to the server for the TCP transport.
+    fn subscriber_endpoint(&mut self, server: SocketAddr) -> Option<SocketAddr> {
+        match self.transport {
+            SomeIpTransport::Udp => Some(SocketAddr::V4(self.local)),
+            SomeIpTransport::Tcp => {
+                let index = self.connection_to(server)?;
+                self.connections[index]
+                    .as_ref()
+                    .and_then(|connection| connection.stream.local_addr().ok())
+            }
+        }
+    }
+
+    /// Returns the index of a connection to an endpoint, connecting if
+    /// needed.
+    fn connection_to(&mut self, endpoint: SocketAddr) -> Option<usize> {
+        if let Some(index) = self.connections.iter().position(|connection| {
+            connection
+                .as_ref()
+                .is_some_and(|connection| connection.peer == endpoint)
+        }) {
+            return Some(index);
+        }
+        let stream = TcpStream::connect(endpoint).ok()?;
+
+        self.add_connection(stream, endpoint)
+    }
+
+    /// Registers a connection, returning its index.
+    fn add_connection(&mut self, mut stream: TcpStream, peer: SocketAddr) -> Option<usize> {
+        let index = match self.connections.iter().position(Option::is_none) {
+            Some(index) => index,
+            None => {
+                self.connections.push(None);
+                self.connections.len() - 1
+            }
+        };
+        if let Some(registry) = &self.registry {
+            registry
+                .register(
+                    &mut stream,
+                    Token(FIRST_CONNECTION + index),
+                    Interest::READABLE | Interest::WRITABLE,
+                )
+                .ok()?;
+        }
+        let _ = stream.set_nodelay(true);
+        self.connections[index] = Some(Connection {
+            id: self.next_id,
+            stream,
+            peer,
+            rx: Vec::new(),
+            tx: WriteBuffer::new(),
+        });
+        self.next_id += 1;
+
+        Some(index)
+    }
+
+    /// Accepts the pending client connections.
+    fn accept(&mut self) -> IoResult<()> {
+        loop {
+            let Some(listener) = &self.listener else {
+                return Ok(());
+            };
+            let (stream, peer) = match listener.accept() {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::Interrupted | ErrorKind::ConnectionAborted
+                    ) =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if let Some(index) = self.add_connection(stream, peer) {
+                // Messages may have been received with the connection.
+                self.receive(index);
+            }
+        }
+    }
+
+    /// Reads the messages of a connection.
+    ///
+    /// The connection is closed when the peer closes it, on errors and on
+    /// invalid headers.
+    fn receive(&mut self, index: usize) {
+        let Some(connection) = self.connections.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        loop {
+            match connection.stream.read(&mut self.buffer) {
+                Ok(0) => {
+                    self.close(index);
+                    return;
+                }
+                Ok(len) => connection.rx.extend_from_slice(&self.buffer[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.close(index);
+                    return;
+                }
+            }
+        }
+        let peer = Peer::Tcp(index, connection.id);
+        let mut messages = Vec::new();
+        loop {
+            let len = match message_len(&connection.rx) {
+                Some(Ok(len)) if connection.rx.len() >= len => len,
+                Some(Ok(_)) | None => break,
+                Some(Err(())) => {
+                    self.close(index);
+                    return;
+                }
+            };
+            // Unsupported messages are skipped.
+            if let Some((message, _)) = SomeIpMessage::decode(&connection.rx[..len]) {
+                messages.push(message);
+            }
+            connection.rx.drain(..len);
+        }
+        for message in messages {
+            self.handle_message(message, peer);
+        }
+    }
+
+    /// Writes the pending data of a connection.
+    fn resume_write(&mut self, index: usize) {
+        let Some(connection) = self.connections.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        if connection.tx.flush(&mut connection.stream).is_err() {
+            self.close(index);
+        }
+    }
+
+    /// Closes a connection.
+    fn close(&mut self, index: usize) {
+        if let Some(mut connection) = self.connections[index].take() {
+            if let Some(registry) = &self.registry {
+                let _ = registry.deregister(&mut connection.stream);
+            }
+        }
+    }
+
+    /// Sends a message to an endpoint with the configured transport.
+    fn send_to(&mut self, message: &SomeIpMessage, endpoint: SocketAddr) {
+        match self.transport {
+            SomeIpTransport::Udp => {
+                // Datagrams are sent on a best-effort basis.
+                let _ = self.udp.send_to(&message.encode(), endpoint);
+            }
+            SomeIpTransport::Tcp => {
+                if let Some(index) = self.connection_to(endpoint) {
+                    self.send_on(message, index);
+                }
+            }
+        }
+    }
+
+    /// Sends a message on a connection.
+    fn send_on(&mut self, message: &SomeIpMessage, index: usize) {
+        if let Some(connection) = self.connections.get_mut(index).and_then(Option::as_mut) {
+            connection.tx.push(&message.encode());
+            self.resume_write(index);
+        }
+    }
+
+    /// Handles a received message.
+    ///
+    /// Requests to services which are not offered are answered with an error.
+    fn handle_message(&mut self, message: SomeIpMessage, peer: Peer) {
+        match message.message_type {
+            SomeIpMessageType::Request | SomeIpMessageType::RequestNoReturn => {
+                if !self
+                    .offered
+                    .iter()
+                    .any(|offered| offered.service == message.service)
+                {
+                    if message.message_type == SomeIpMessageType::Request {
+                        self.respond(&SomeIpMessage::error_to(&message, E_UNKNOWN_SERVICE), peer);
+                    }
+                    return;
+                }
+                if message.message_type == SomeIpMessageType::Request {
+                    if self.requests.len() >= MAX_PENDING_REQUESTS {
+                        self.requests.pop_front();
+                    }
+                    self.requests.push_back(PendingRequest {
+                        key: request_key(&message),
+                        peer,
+                    });
+                }
+            }
+            SomeIpMessageType::Notification
+            | SomeIpMessageType::Response
+            | SomeIpMessageType::Error => {}
+        }
+        self.events.push_back(SomeIpEvent::Message(message));
+    }
+
+    /// Sends a response to the peer of a request.
+    fn respond(&mut self, response: &SomeIpMessage, peer: Peer) {
+        match peer {
+            Peer::Udp(endpoint) => {
+                let _ = self.udp.send_to(&response.encode(), endpoint);
+            }
+            Peer::Tcp(index, id) => {
+                if self
+                    .connections
+                    .get(index)
+                    .and_then(Option::as_ref)
+                    .is_some_and(|connection| connection.id == id)
+                {
+                    self.send_on(response, index);
+                }
+            }
+        }
+    }
+
+    /// Reads the datagrams of the UDP socket.
+    fn receive_udp(&mut self) -> IoResult<()> {
+        loop {
+            let (len, source) = match self.udp.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::Interrupted | ErrorKind::ConnectionRefused
+                    ) =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            // A datagram may hold several messages.
+            let mut messages = Vec::new();
+            let mut offset = 0;
+            while let Some((message, message_len)) =
+                SomeIpMessage::decode(&self.buffer[offset..len])
+            {
+                messages.push(message);
+                offset += message_len;
+            }
+            for message in messages {
+                self.handle_message(message, Peer::Udp(source));
+            }
+        }
+    }
+
+    /// Reads the datagrams of a SOME/IP-SD socket.
+    fn receive_sd(&mut self, token: Token) -> IoResult<()> {
+        loop {
+            let socket = if token == SD {
+                &self.sd
+            } else {
+                &self.sd_multicast
+            };
+            let (len, source) = match socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::Interrupted | ErrorKind::ConnectionRefused
+                    ) =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let message = self.buffer[..len].to_vec();
+            self.handle_sd(&message, source);
+        }
+    }
+}
+
+impl IoPort<UdpSocket, SomeIpEvent, SomeIpMessage> for SomeIpInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        registry.register(&mut self.sd, SD, Interest::READABLE)?;
+        registry.register(&mut self.sd_multicast, SD_MULTICAST, Interest::READABLE)?;
+        registry.register(&mut self.udp, UDP, Interest::READABLE)?;
+        if self.transport == SomeIpTransport::Tcp {
+            let mut listener = TcpListener::bind(self.local.into())?;
+            registry.register(&mut listener, LISTENER, Interest::READABLE)?;
+            self.listener = Some(listener);
+        }
+        self.registry = Some(registry.try_clone()?);
+        self.next_sd = Some(Instant::now());
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<SomeIpEvent> {
+        if self.events.is_empty() {
+            match token {
+                SD | SD_MULTICAST => self.receive_sd(token)?,
+                UDP => self.receive_udp()?,
+                LISTENER => self.accept()?,
+                Token(token) => {
+                    if let Some(index) = token.checked_sub(FIRST_CONNECTION) {
+                        self.receive(index);
+                    }
+                }
+            }
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        if let Some(index) = token.0.checked_sub(FIRST_CONNECTION) {
+            self.resume_write(index);
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, message: &SomeIpMessage) -> IoResult<()> {
+        match message.message_type {
+            SomeIpMessageType::Request | SomeIpMessageType::RequestNoReturn => {
+                let endpoint = self
+                    .required
+                    .iter()
+                    .filter(|required| required.service == message.service)
+                    .find_map(|required| required.remote.as_ref())
+                    .map(|remote| remote.endpoint);
+                match endpoint {
+                    Some(endpoint) => self.send_to(message, endpoint),
+                    None if message.message_type == SomeIpMessageType::Request => {
+                        self.events
+                            .push_back(SomeIpEvent::Message(SomeIpMessage::error_to(
+                                message,
+                                E_NOT_REACHABLE,
+                            )));
+                    }
+                    None => {}
+                }
+            }
+            SomeIpMessageType::Response | SomeIpMessageType::Error => {
+                // Responses to unknown or forgotten requests are discarded.
+                let key = request_key(message);
+                if let Some(position) = self.requests.iter().position(|request| request.key == key)
+                {
+                    let request = self.requests.remove(position).unwrap();
+                    self.respond(message, request.peer);
+                }
+            }
+            SomeIpMessageType::Notification => {
+                let mut endpoints: Vec<SocketAddr> = Vec::new();
+                for offered in self
+                    .offered
+                    .iter()
+                    .filter(|offered| offered.service == message.service)
+                {
+                    for subscriber in &offered.subscribers {
+                        let has_event = offered.eventgroups.iter().any(|(eventgroup, events)| {
+                            *eventgroup == subscriber.eventgroup && events.contains(&message.method)
+                        });
+                        if has_event && !endpoints.contains(&subscriber.endpoint) {
+                            endpoints.push(subscriber.endpoint);
+                        }
+                    }
+                }
+                for endpoint in endpoints {
+                    self.send_to(message, endpoint);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        // Events queued outside of a read, e.g. unreachable services, are
+        // forwarded immediately.
+        if !self.events.is_empty() {
+            return Some(Instant::now());
+        }
+        self.next_sd
+    }
+
+    fn timeout(&mut self) -> IoResult<SomeIpEvent> {
+        let now = Instant::now();
+        if let Some(next_sd) = self.next_sd.filter(|&next_sd| next_sd <= now) {
+            self.cycle();
+            self.next_sd = Some((next_sd + self.sd_period).max(now));
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        self.connections
+            .iter()
+            .flatten()
+            .any(|connection| !connection.tx.is_empty())
+    }
+}
+
+/// Creates a non-blocking SOME/IP-SD socket shared with the other stacks of
+/// the host.
+fn sd_socket(address: SocketAddrV4) -> IoResult<Socket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::V4(address).into())?;
+
+    Ok(socket)
+}
+
+/// Returns the key matching a response to its request.
+fn request_key(message: &SomeIpMessage) -> (u16, u16, u16, u16) {
+    (
+        message.service,
+        message.method,
+        message.client,
+        message.session,
+    )
+}
+
+/// Returns the size of the message at the start of a buffer, if its length
+/// field is available, or an error if the length field is invalid.
+fn message_len(buf: &[u8]) -> Option<Result<usize, ()>> {
+    let length = u32::from_be_bytes(buf.get(4..8)?.try_into().unwrap()) as usize;
+    if length < LENGTH_OFFSET {
+        return Some(Err(()));
+    }
+
+    Some(Ok(LENGTH_OFFSET + length))
+}
+
+/// Appends a SOME/IP header.
+#[allow(clippy::too_many_arguments)]
+fn encode_header(
+    buf: &mut Vec<u8>,
+    service: u16,
+    method: u16,
+    client: u16,
+    session: u16,
+    interface_version: u8,
+    message_type: u8,
+    return_code: u8,
+    payload_len: usize,
+) {
+    buf.extend_from_slice(&service.to_be_bytes());
+    buf.extend_from_slice(&method.to_be_bytes());
+    buf.extend_from_slice(&((LENGTH_OFFSET + payload_len) as u32).to_be_bytes());
+    buf.extend_from_slice(&client.to_be_bytes());
+    buf.extend_from_slice(&session.to_be_bytes());
+    buf.extend_from_slice(&[
+        PROTOCOL_VERSION,
+        interface_version,
+        message_type,
+        return_code,
+    ]);
+}
+
+/// Encodes a service entry, without option.
+fn service_entry(
+    kind: u8,
+    service: u16,
+    instance: u16,
+    major_version: u8,
+    ttl: u32,
+    minor_version: u32,
+) -> [u8; SD_ENTRY_LEN] {
+    let mut entry = [0; SD_ENTRY_LEN];
+    entry[0] = kind;
+    entry[4..6].copy_from_slice(&service.to_be_bytes());
+    entry[6..8].copy_from_slice(&instance.to_be_bytes());
+    entry[8] = major_version;
+    entry[9..12].copy_from_slice(&ttl.min(0xFF_FFFF).to_be_bytes()[1..]);
+    entry[12..16].copy_from_slice(&minor_version.to_be_bytes());
+
+    entry
+}
+
+/// Encodes an event group entry, without option.
+fn eventgroup_entry(
+    kind: u8,
+    service: u16,
+    instance: u16,
+    major_version: u8,
+    ttl: u32,
+    eventgroup: u16,
+) -> [u8; SD_ENTRY_LEN] {
+    let mut entry = service_entry(kind, service, instance, major_version, ttl, 0);
+    entry[14..16].copy_from_slice(&eventgroup.to_be_bytes());
+
+    entry
+}
+
+/// Encodes an IPv4 endpoint option.
+fn endpoint_option(endpoint: SocketAddrV4, transport: SomeIpTransport) -> [u8; IPV4_ENDPOINT_LEN] {
+    let mut option = [0; IPV4_ENDPOINT_LEN];
+    option[0..2].copy_from_slice(&((IPV4_ENDPOINT_LEN - 3) as u16).to_be_bytes());
+    option[2] = IPV4_ENDPOINT;
+    option[4..8].copy_from_slice(&endpoint.ip().octets());
+    option[9] = transport.protocol();
+    option[10..12].copy_from_slice(&endpoint.port().to_be_bytes());
+
+    option
+}
+
+/// Encodes a SOME/IP-SD message.
+///
+/// Each entry is provided with the endpoint option it references, if any.
+fn encode_sd(
+    entries: &[([u8; SD_ENTRY_LEN], Option<SocketAddr>)],
+    session: u16,
+    reboot: bool,
+    transport: SomeIpTransport,
+) -> Vec<u8> {
+    let mut options = Vec::new();
+    let mut encoded_entries = Vec::with_capacity(entries.len() * SD_ENTRY_LEN);
+    for (entry, endpoint) in entries {
+        let mut entry = *entry;
+        if let Some(SocketAddr::V4(endpoint)) = endpoint {
+            entry[1] = (options.len() / IPV4_ENDPOINT_LEN) as u8;
+            entry[3] = 0x10;
+            options.extend_from_slice(&endpoint_option(*endpoint, transport));
+        }
+        encoded_entries.extend_from_slice(&entry);
+    }
+
+    let payload_len = 12 + encoded_entries.len() + options.len();
+    let mut message = Vec::with_capacity(HEADER_LEN + payload_len);
+    encode_header(
+        &mut message,
+        SD_SERVICE,
+        SD_METHOD,
+        0,
+        session,
+        1,
+        SomeIpMessageType::Notification.to_u8(),
+        E_OK,
+        payload_len,
+    );
+    let flags = if reboot { SD_REBOOT } else { 0 } | SD_UNICAST;
+    message.extend_from_slice(&[flags, 0, 0, 0]);
+    message.extend_from_slice(&(encoded_entries.len() as u32).to_be_bytes());
+    message.extend_from_slice(&encoded_entries);
+    message.extend_from_slice(&(options.len() as u32).to_be_bytes());
+    message.extend_from_slice(&options);
+
+    message
+}
+
+/// Decodes the entries of a SOME/IP-SD message, with their IPv4 endpoints.
+fn decode_sd(message: &[u8]) -> Option<Vec<SdEntry>> {
+    let (header, len) = SomeIpMessage::decode(message)?;
+    if header.service != SD_SERVICE || header.method != SD_METHOD || len != message.len() {
+        return None;
+    }
+    let payload = &header.payload[..];
+    let entries_len = u32::from_be_bytes(payload.get(4..8)?.try_into().unwrap()) as usize;
+    let entries = payload.get(8..8 + entries_len)?;
+    let options_offset = 8 + entries_len;
+    let options_len = u32::from_be_bytes(
+        payload
+            .get(options_offset..options_offset + 4)?
+            .try_into()
+            .unwrap(),
+    ) as usize;
+    let mut options_buf = payload.get(options_offset + 4..options_offset + 4 + options_len)?;
+
+    // Options which are not IPv4 endpoints are kept as placeholders so that
+    // the option indices remain valid.
+    let mut options = Vec::new();
+    while options_buf.len() >= 3 {
+        let len = 3 + usize::from(u16::from_be_bytes([options_buf[0], options_buf[1]]));
+        let option = options_buf.get(..len)?;
+        options.push(
+            (option[2] == IPV4_ENDPOINT && len == IPV4_ENDPOINT_LEN).then(|| {
+                let ip = Ipv4Addr::new(option[4], option[5], option[6], option[7]);
+                let port = u16::from_be_bytes([option[10], option[11]]);
+                (SocketAddr::V4(SocketAddrV4::new(ip, port)), option[9])
+            }),
+        );
+        options_buf = &options_buf[len..];
+    }
+
+    let entries = entries
+        .chunks_exact(SD_ENTRY_LEN)
+        .map(|entry| {
+            let runs = [
+                (usize::from(entry[1]), usize::from(entry[3] >> 4)),
+                (usize::from(entry[2]), usize::from(entry[3] & 0x0F)),
+            ];
+            let endpoints = runs
+                .iter()
+                .flat_map(|&(index, count)| options.iter().skip(index).take(count))
+                .flatten()
+                .copied()
+                .collect();
+
+            SdEntry {
+                kind: entry[0],
+                service: u16::from_be_bytes([entry[4], entry[5]]),
+                instance: u16::from_be_bytes([entry[6], entry[7]]),
+                major_version: entry[8],
+                ttl: u32::from_be_bytes([0, entry[9], entry[10], entry[11]]),
+                eventgroup: u16::from_be_bytes([entry[14], entry[15]]),
+                endpoints,
+            }
+        })
+        .collect();
+
+    Some(entries)
+}
+
+/// SOME/IP port model.
+///
+/// This model:
+/// * announces the offered services and discovers the required services,
+/// * forwards the received requests, responses and notifications to the
+///   message output,
+/// * sends the requests, responses and notifications from the message input,
+/// * reports the service availability and subscription changes,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct SomeIpPort {
+    /// Received message -- output port.
+    pub message_out: Output<SomeIpMessage>,
+
+    /// Service availability or subscription change -- output port.
+    pub service_out: Output<SomeIpServiceEvent>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: SomeIpConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<SomeIpEvent, SomeIpMessage>,
+}
+
+impl SomeIpPort {
+    /// Sends a request, a response or a notification -- input port.
+    ///
+    /// Requests are sent to the server of the service, responses to the client
+    /// of the matching request and notifications to the subscribers of the
+    /// event.
+    pub async fn message_in(&mut self, message: SomeIpMessage) {
+        let _ = self.io_thread.send(message);
+    }
+
+    /// Forwards the received messages, the service discovery events and the
+    /// I/O thread status -- input port.
+    pub async fn process(&mut self) {
+        for event in self.io_thread.try_recv_all() {
+            match event {
+                SomeIpEvent::Message(message) => self.message_out.send(message).await,
+                SomeIpEvent::Service(event) => self.service_out.send(event).await,
+            }
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for SomeIpPort {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for SomeIpPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SomeIpPort")
+            .field("local_address", &self.config.local_address)
+            .finish_non_exhaustive()
+    }
+}
+
+/// SOME/IP port model prototype.
+pub struct ProtoSomeIpPort {
+    /// Received message -- output port.
+    pub message_out: Output<SomeIpMessage>,
+
+    /// Service availability or subscription change -- output port.
+    pub service_out: Output<SomeIpServiceEvent>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// SOME/IP port model instance configuration.
+    config: SomeIpConfig,
+}
+
+impl ProtoSomeIpPort {
+    /// Creates a new SOME/IP port model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the sockets cannot be bound or if the I/O
+    /// thread cannot be created.
+    pub fn new(config: SomeIpConfig) -> Self {
+        Self {
+            message_out: Output::new(),
+            service_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoSomeIpPort {
+    type Model = SomeIpPort;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let port = SomeIpInner::new(&self.config).unwrap_or_else(|e| {
+            panic!(
+                "Failed to bind the SOME/IP port {}: {e}.",
+                self.config.local_address
+            )
+        });
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(port, options)
+            .unwrap_or_else(|e| panic!("Failed to start the I/O thread of the SOME/IP port: {e}."));
+
+        SomeIpPort {
+            message_out: self.message_out,
+            service_out: self.service_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoSomeIpPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoSomeIpPort")
+            .field("local_address", &self.config.local_address)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_round_trip() {
+        let message = SomeIpMessage::request(0x1234, 0x0421, vec![1, 2, 3])
+            .with_client(0x00ab)
+            .with_session(7)
+            .with_interface_version(2);
+        let encoded = message.encode();
+
+        assert_eq!(encoded.len(), HEADER_LEN + 3);
+        assert_eq!(&encoded[4..8], &11u32.to_be_bytes());
+        assert_eq!(
+            SomeIpMessage::decode(&encoded),
+            Some((message, HEADER_LEN + 3))
+        );
+    }
+
+    #[test]
+    fn message_decode_first_of_stream() {
+        let request = SomeIpMessage::request(0x1234, 0x0001, vec![0xaa]);
+        let response = SomeIpMessage::response_to(&request, vec![0xbb, 0xcc]);
+        let mut stream = request.encode().to_vec();
+        stream.extend_from_slice(&response.encode());
+
+        let (decoded, len) = SomeIpMessage::decode(&stream).unwrap();
+        assert_eq!(decoded, request);
+        assert_eq!(SomeIpMessage::decode(&stream[len..]), Some((response, 18)));
+    }
+
+    #[test]
+    fn message_error_response() {
+        let request = SomeIpMessage::request(0x1234, 0x0001, vec![0xaa]).with_session(3);
+        let error = SomeIpMessage::error_to(&request, E_UNKNOWN_METHOD);
+        let (decoded, _) = SomeIpMessage::decode(&error.encode()).unwrap();
+
+        assert_eq!(decoded.message_type, SomeIpMessageType::Error);
+        assert_eq!(decoded.return_code, E_UNKNOWN_METHOD);
+        assert_eq!(decoded.session, 3);
+        assert!(decoded.payload.is_empty());
+    }
+
+    #[test]
+    fn message_decode_invalid() {
+        let encoded = SomeIpMessage::notification(0x1234, 0x8001, vec![1, 2]).encode();
+
+        // Truncated header and payload.
+        assert_eq!(SomeIpMessage::decode(&encoded[..6]), None);
+        assert_eq!(SomeIpMessage::decode(&encoded[..encoded.len() - 1]), None);
+
+        // Length field shorter than the header remainder.
+        let mut invalid = encoded.to_vec();
+        invalid[4..8].copy_from_slice(&4u32.to_be_bytes());
+        assert_eq!(SomeIpMessage::decode(&invalid), None);
+
+        // Unsupported protocol version.
+        let mut invalid = encoded.to_vec();
+        invalid[12] = PROTOCOL_VERSION + 1;
+        assert_eq!(SomeIpMessage::decode(&invalid), None);
+
+        // Unknown message type.
+        let mut invalid = encoded.to_vec();
+        invalid[14] = 0x42;
+        assert_eq!(SomeIpMessage::decode(&invalid), None);
+    }
+
+    #[test]
+    fn sd_entries_round_trip() {
+        let endpoint = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 10), 30509));
+        let entries = [
+            (
+                service_entry(OFFER_SERVICE, 0x1234, 0x0001, 1, 3, 0),
+                Some(endpoint),
+            ),
+            (
+                eventgroup_entry(SUBSCRIBE_EVENTGROUP_ACK, 0x1234, 0x0001, 1, 3, 0x0010),
+                None,
+            ),
+        ];
+        let message = encode_sd(&entries, 5, true, SomeIpTransport::Tcp);
+
+        let (header, _) = SomeIpMessage::decode(&message).unwrap();
+        assert_eq!(header.session, 5);
+        assert_eq!(header.payload[0], SD_REBOOT | SD_UNICAST);
+
+        let decoded = decode_sd(&message).unwrap();
+        assert_eq!(decoded.len(), 2);
+
+        assert_eq!(decoded[0].kind, OFFER_SERVICE);
+        assert_eq!(decoded[0].service, 0x1234);
+        assert_eq!(decoded[0].instance, 0x0001);
+        assert_eq!(decoded[0].major_version, 1);
+        assert_eq!(decoded[0].ttl, 3);
+        assert_eq!(
+            decoded[0].endpoints,
+            vec![(endpoint, SomeIpTransport::Tcp.protocol())]
+        );
+
+        assert_eq!(decoded[1].kind, SUBSCRIBE_EVENTGROUP_ACK);
+        assert_eq!(decoded[1].eventgroup, 0x0010);
+        assert!(decoded[1].endpoints.is_empty());
+    }
+
+    #[test]
+    fn sd_entry_ttl_saturates() {
+        let entries = [(
+            service_entry(FIND_SERVICE, ANY, ANY, 0xFF, u32::MAX, 0),
+            None,
+        )];
+        let decoded = decode_sd(&encode_sd(&entries, 1, false, SomeIpTransport::Udp)).unwrap();
+
+        assert_eq!(decoded[0].ttl, 0xFF_FFFF);
+        assert_eq!(decoded[0].service, ANY);
+    }
+
+    #[test]
+    fn sd_decode_rejects_other_services() {
+        let message = SomeIpMessage::notification(0x1234, SD_METHOD, vec![0; 12]).encode();
+
+        assert!(decode_sd(&message).is_none());
+    }
+}
@@ -0,0 +1,316 @@
+//! SpaceWire-over-UDP port model.
+//!
+//! This module contains the [`SpaceWirePort`] model, which exchanges SpaceWire
+//! packets with a SpaceWire-to-Ethernet bridge, such as the SpaceWire EGSE of
+//! a data-handling bench, over UDP.
+//!
+//! Each datagram carries a single SpaceWire packet, from its first address
+//! byte to its last data byte; the end-of-packet marker is implied by the end
+//! of the datagram. Datagrams are only exchanged with the configured remote
+//! address.
+//!
+//! The RMAP protocol can be run on top of this port with the
+//! [`RmapLayer`](crate::rmap::RmapLayer) model.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::spacewire::SpaceWireConfig;
+//!
+//! let config = ConfigLoader::<SpaceWireConfig>::new()
+//!     .code(
+//!         r#"
+//! localAddress = "0.0.0.0:10000"
+//! remoteAddress = "192.168.1.20:10000"
+//! period = 10
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.remote_address, "192.168.1.20:10000");
+//! ```
+
+use std::fmt;
+use std::io::{Error, ErrorKind, Result as IoResult};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use schematic::Config;
+
+use mio::net::UdpSocket;
+use mio::{Interest, Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus};
+
+/// SpaceWire-over-UDP port model instance configuration.
+#[derive(Config, Debug)]
+pub struct SpaceWireConfig {
+    /// Local address, as `HOST:PORT`, to bind to.
+    pub local_address: String,
+
+    /// Address of the SpaceWire bridge, as `HOST:PORT`.
+    pub remote_address: String,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which received packets are forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+
+    /// Maximum time spent sending the pending packets when the model is
+    /// dropped.
+    ///
+    /// If no value is provided, the packets not yet sent are discarded.
+    pub shutdown_timeout: Option<ConfigDuration>,
+}
+
+/// Maximum size of a received datagram.
+const MAX_DATAGRAM_LEN: usize = 65536;
+
+/// Socket token.
+const SOCKET: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// SpaceWire-over-UDP port.
+struct SpaceWireInner {
+    /// UDP socket connected to the bridge.
+    socket: UdpSocket,
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+}
+
+impl SpaceWireInner {
+    /// Binds the local address and connects to the bridge.
+    fn new(config: &SpaceWireConfig) -> IoResult<Self> {
+        let socket = UdpSocket::bind(resolve(&config.local_address)?)?;
+        socket.connect(resolve(&config.remote_address)?)?;
+
+        Ok(Self {
+            socket,
+            buffer: vec![0; MAX_DATAGRAM_LEN],
+        })
+    }
+}
+
+impl IoPort<UdpSocket, Bytes, Bytes> for SpaceWireInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        registry.register(
+            &mut self.socket,
+            SOCKET,
+            Interest::READABLE | Interest::WRITABLE,
+        )?;
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<Bytes> {
+        if token != SOCKET {
+            // Unknown event: should never happen.
+            return Err(Error::new(ErrorKind::InvalidInput, "Unknown event."));
+        }
+        loop {
+            match self.socket.recv(&mut self.buffer) {
+                // Empty datagrams carry no packet.
+                Ok(0) => {}
+                Ok(len) => return Ok(Bytes::copy_from_slice(&self.buffer[..len])),
+                // The bridge may not be listening yet.
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write(&mut self, packet: &Bytes) -> IoResult<()> {
+        match self.socket.send(packet) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Resolves a socket address of the configuration.
+fn resolve(address: &str) -> IoResult<SocketAddr> {
+    address.to_socket_addrs()?.next().ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Cannot resolve address {address}."),
+        )
+    })
+}
+
+/// SpaceWire-over-UDP port model.
+///
+/// This model:
+/// * forwards the SpaceWire packets received from the bridge to the model
+///   output,
+/// * sends the SpaceWire packets from the model input to the bridge,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct SpaceWirePort {
+    /// Packets from the bridge -- output port.
+    pub packet_out: Output<Bytes>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: SpaceWireConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<Bytes, Bytes>,
+}
+
+impl SpaceWirePort {
+    /// Sends a packet to the bridge -- input port.
+    pub async fn packet_in(&mut self, packet: Bytes) {
+        let _ = self.io_thread.send(packet);
+    }
+
+    /// Forwards the packets from the bridge and the I/O thread status --
+    /// input port.
+    pub async fn process(&mut self) {
+        for packet in self.io_thread.try_recv_all() {
+            self.packet_out.send(packet).await;
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for SpaceWirePort {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for SpaceWirePort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SpaceWirePort")
+            .field("remote_address", &self.config.remote_address)
+            .finish_non_exhaustive()
+    }
+}
+
+/// SpaceWire-over-UDP port model prototype.
+pub struct ProtoSpaceWirePort {
+    /// Packets from the bridge -- output port.
+    pub packet_out: Output<Bytes>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// SpaceWire-over-UDP port model instance configuration.
+    config: SpaceWireConfig,
+}
+
+impl ProtoSpaceWirePort {
+    /// Creates a new SpaceWire-over-UDP port model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the local address cannot be bound, if the
+    /// remote address cannot be resolved or if the I/O thread cannot be
+    /// created.
+    pub fn new(config: SpaceWireConfig) -> Self {
+        Self {
+            packet_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoSpaceWirePort {
+    type Model = SpaceWirePort;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let port = SpaceWireInner::new(&self.config).unwrap_or_else(|e| {
+            panic!(
+                "Failed to open the SpaceWire port to {}: {e}.",
+                self.config.remote_address
+            )
+        });
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            shutdown_timeout: self.config.shutdown_timeout.map(Duration::from),
+        };
+        let io_thread = IoThread::try_with_options(port, options).unwrap_or_else(|e| {
+            panic!(
+                "Failed to start the I/O thread of the SpaceWire port to {}: {e}.",
+                self.config.remote_address
+            )
+        });
+
+        SpaceWirePort {
+            packet_out: self.packet_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoSpaceWirePort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoSpaceWirePort")
+            .field("remote_address", &self.config.remote_address)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,609 @@
+//! TCP client port model.
+//!
+//! This module contains the [`TcpClient`] model, which connects to a TCP
+//! server, such as the endpoint of a ground-segment or EGSE equipment, and
+//! streams bytes both ways:
+//! * data received from the server is injected into the simulation,
+//! * data from the simulation is sent to the server.
+//!
+//! The connection is established by the I/O thread of the model, and each
+//! established connection and each disconnection is reported on a status
+//! output. If `reconnect_delay` is set in the configuration, the connection is
+//! reattempted after a disconnection or a failed attempt, with a delay
+//! doubling after each failed attempt.
+//!
+//! Data sent while the connection is being established is written once it is
+//! established, while data sent while disconnected is discarded.
+//!
+//! #### Examples
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::tcp::TcpClientConfig;
+//!
+//! let config = ConfigLoader::<TcpClientConfig>::new()
+//!     .code(
+//!         r#"
+//! address = "10.0.0.2:5000"
+//! period = 10
+//! reconnectDelay = "500ms"
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(
+//!     config.reconnect_delay.map(Duration::from),
+//!     Some(Duration::from_millis(500))
+//! );
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Error, ErrorKind, Read, Result as IoResult};
+use std::net::ToSocketAddrs;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use schematic::Config;
+
+use mio::net::TcpStream;
+use mio::{Interest, Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus, WriteBuffer};
+
+/// TCP client model instance configuration.
+#[derive(Config, Debug)]
+pub struct TcpClientConfig {
+    /// Server address, as `HOST:PORT`.
+    ///
+    /// The host name is resolved at each connection attempt.
+    pub address: String,
+
+    /// Disable Nagle's algorithm on the connection.
+    #[setting(default = true)]
+    pub nodelay: bool,
+
+    /// Internal buffer size.
+    ///
+    /// Input is read and forwarded to the simulation by blocks up to buffer
+    /// size.
+    #[setting(default = 4096)]
+    pub buffer_size: usize,
+
+    /// Initial delay before reconnecting after a disconnection or a failed
+    /// connection attempt.
+    ///
+    /// The delay doubles after each failed attempt, up to
+    /// `reconnect_max_delay`. If no value is provided, the connection is only
+    /// attempted once.
+    pub reconnect_delay: Option<ConfigDuration>,
+
+    /// Maximum delay between connection attempts.
+    #[setting(default = ConfigDuration::from_millis(10000))]
+    pub reconnect_max_delay: ConfigDuration,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which data from the server is forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+
+    /// Maximum time spent writing the pending data to the server when the
+    /// model is dropped.
+    ///
+    /// If no value is provided, the data not yet written is discarded.
+    pub shutdown_timeout: Option<ConfigDuration>,
+}
+
+/// TCP connection status.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TcpStatus {
+    /// The connection to the server was established.
+    Connected,
+
+    /// The connection to the server was lost or could not be established,
+    /// with the error description.
+    Disconnected(String),
+}
+
+/// Event read from the connection.
+enum TcpEvent {
+    /// Received data.
+    Data(Bytes),
+
+    /// Connection status change.
+    Status(TcpStatus),
+}
+
+/// State of the connection.
+enum Connection {
+    /// No connection, waiting for the next attempt, if any.
+    Idle,
+
+    /// Connection being established.
+    Connecting(TcpStream),
+
+    /// Established connection.
+    Connected(TcpStream),
+}
+
+/// Connection token.
+const STREAM: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// TCP client port.
+struct TcpClientInner {
+    /// Server address.
+    address: String,
+
+    /// Disable Nagle's algorithm.
+    nodelay: bool,
+
+    /// Connection.
+    connection: Connection,
+
+    /// Read buffer.
+    buffer: Vec<u8>,
+
+    /// Data not yet written.
+    tx_buf: WriteBuffer,
+
+    /// Decoded events not yet read.
+    events: VecDeque<TcpEvent>,
+
+    /// Initial reconnection delay, if enabled.
+    reconnect_delay: Option<Duration>,
+
+    /// Maximum reconnection delay.
+    reconnect_max_delay: Duration,
+
+    /// Delay before the next attempt after a failure.
+    delay: Duration,
+
+    /// Time of the next connection attempt, if any.
+    retry_at: Option<Instant>,
+
+    /// The connection was last reported as established.
+    ///
+    /// This is `None` until the first status is reported.
+    is_up: Option<bool>,
+
+    /// MIO registry, available once the port is registered.
+    registry: Option<Registry>,
+}
+
+impl TcpClientInner {
+    /// Creates a TCP client port, connecting once registered.
+    fn new(config: &TcpClientConfig) -> Self {
+        let reconnect_delay = config.reconnect_delay.map(Duration::from);
+        let reconnect_max_delay = Duration::from(config.reconnect_max_delay);
+
+        // Until read_buf (RFC 2930) is stabilized we need an initialized
+        // buffer.
+        Self {
+            address: config.address.clone(),
+            nodelay: config.nodelay,
+            connection: Connection::Idle,
+            buffer: vec![0; config.buffer_size.max(1)],
+            tx_buf: WriteBuffer::new(),
+            events: VecDeque::new(),
+            reconnect_delay,
+            reconnect_max_delay: reconnect_max_delay.max(reconnect_delay.unwrap_or_default()),
+            delay: reconnect_delay.unwrap_or_default(),
+            retry_at: None,
+            is_up: None,
+            registry: None,
+        }
+    }
+
+    /// Starts a connection attempt.
+    fn connect(&mut self) {
+        if let Err(e) = self.try_connect() {
+            self.disconnect(e);
+        }
+    }
+
+    /// Starts a non-blocking connection to the server.
+    fn try_connect(&mut self) -> IoResult<()> {
+        let server = self.address.to_socket_addrs()?.next().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Cannot resolve address {}.", self.address),
+            )
+        })?;
+        let mut stream = TcpStream::connect(server)?;
+        if let Some(registry) = &self.registry {
+            registry.register(&mut stream, STREAM, Interest::READABLE | Interest::WRITABLE)?;
+        }
+        self.connection = Connection::Connecting(stream);
+
+        Ok(())
+    }
+
+    /// Completes the connection if it has been established.
+    ///
+    /// Returns whether the connection is no longer being established.
+    fn check_connected(&mut self) -> bool {
+        let Connection::Connecting(stream) = &mut self.connection else {
+            return true;
+        };
+        let result = match stream.take_error() {
+            Ok(Some(e)) | Err(e) => Err(e),
+            Ok(None) => match stream.peer_addr() {
+                Ok(_) => Ok(()),
+                Err(e) if e.kind() == ErrorKind::NotConnected => return false,
+                Err(e) => Err(e),
+            },
+        };
+        let result = result.and_then(|_| {
+            stream.set_nodelay(self.nodelay)?;
+            // The data sent while connecting is written once writable.
+            if self.tx_buf.is_empty() {
+                if let Some(registry) = &self.registry {
+                    registry.reregister(stream, STREAM, Interest::READABLE)?;
+                }
+            }
+
+            Ok(())
+        });
+        match result {
+            Ok(()) => {
+                let Connection::Connecting(stream) =
+                    std::mem::replace(&mut self.connection, Connection::Idle)
+                else {
+                    unreachable!()
+                };
+                self.connection = Connection::Connected(stream);
+                self.delay = self.reconnect_delay.unwrap_or_default();
+                self.is_up = Some(true);
+                self.events
+                    .push_back(TcpEvent::Status(TcpStatus::Connected));
+            }
+            Err(e) => self.disconnect(e),
+        }
+
+        true
+    }
+
+    /// Closes the connection after an error and schedules the next attempt,
+    /// if enabled.
+    ///
+    /// The disconnection is only reported once, until the connection is
+    /// established again.
+    fn disconnect(&mut self, error: Error) {
+        if let (
+            Connection::Connecting(mut stream) | Connection::Connected(mut stream),
+            Some(registry),
+        ) = (
+            std::mem::replace(&mut self.connection, Connection::Idle),
+            &self.registry,
+        ) {
+            let _ = registry.deregister(&mut stream);
+        }
+        self.tx_buf.clear();
+        if self.is_up != Some(false) {
+            self.is_up = Some(false);
+            self.events
+                .push_back(TcpEvent::Status(TcpStatus::Disconnected(error.to_string())));
+        }
+        if self.reconnect_delay.is_some() {
+            self.retry_at = Some(Instant::now() + self.delay);
+            self.delay = (self.delay * 2).min(self.reconnect_max_delay);
+        }
+    }
+
+    /// Reads the next event from the connection.
+    fn read_event(&mut self) -> IoResult<TcpEvent> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            let stream = match &mut self.connection {
+                Connection::Idle => return Err(ErrorKind::WouldBlock.into()),
+                Connection::Connecting(_) => {
+                    if !self.check_connected() {
+                        return Err(ErrorKind::WouldBlock.into());
+                    }
+                    continue;
+                }
+                Connection::Connected(stream) => stream,
+            };
+            match stream.read(&mut self.buffer) {
+                Ok(0) => self.disconnect(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "Connection closed by the server.",
+                )),
+                Ok(len) => return Ok(TcpEvent::Data(Bytes::copy_from_slice(&self.buffer[..len]))),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Err(e),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => self.disconnect(e),
+            }
+        }
+    }
+
+    /// Resumes the write of the data not yet written.
+    fn resume_write(&mut self) -> IoResult<()> {
+        if !self.check_connected() {
+            return Ok(());
+        }
+        let Connection::Connected(stream) = &mut self.connection else {
+            return Ok(());
+        };
+        if self.tx_buf.is_empty() {
+            return Ok(());
+        }
+        match self.tx_buf.flush(stream) {
+            Ok(true) => {
+                if let Some(registry) = &self.registry {
+                    registry.reregister(stream, STREAM, Interest::READABLE)?;
+                }
+            }
+            Ok(false) => {}
+            Err(e) => self.disconnect(e),
+        }
+
+        Ok(())
+    }
+}
+
+impl IoPort<TcpStream, TcpEvent, Bytes> for TcpClientInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        self.registry = Some(registry.try_clone()?);
+        self.connect();
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<TcpEvent> {
+        if token != STREAM {
+            // Unknown event: should never happen.
+            return Err(Error::new(ErrorKind::InvalidInput, "Unknown event."));
+        }
+        self.read_event()
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        if token == STREAM {
+            self.resume_write()?;
+        }
+
+        Ok(())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        // Events queued outside of a read, e.g. a failed connection attempt,
+        // are forwarded immediately.
+        if !self.events.is_empty() {
+            return Some(Instant::now());
+        }
+        self.retry_at
+    }
+
+    fn timeout(&mut self) -> IoResult<TcpEvent> {
+        if self
+            .retry_at
+            .is_some_and(|retry_at| retry_at <= Instant::now())
+        {
+            self.retry_at = None;
+            self.connect();
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        !self.tx_buf.is_empty()
+    }
+
+    fn write(&mut self, data: &Bytes) -> IoResult<()> {
+        let stream = match &mut self.connection {
+            // Data is dropped while disconnected.
+            Connection::Idle => return Ok(()),
+            Connection::Connecting(_) => {
+                self.tx_buf.push(data);
+                return Ok(());
+            }
+            Connection::Connected(stream) => stream,
+        };
+        // Data is delayed until the pending data has been written.
+        if !self.tx_buf.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        // The part of the data which cannot be written immediately is written
+        // once the connection is writable.
+        match self.tx_buf.write(stream, data) {
+            Ok(true) => Ok(()),
+            Ok(false) => match &self.registry {
+                Some(registry) => {
+                    registry.reregister(stream, STREAM, Interest::READABLE | Interest::WRITABLE)
+                }
+                None => Ok(()),
+            },
+            Err(e) => {
+                self.disconnect(e);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// TCP client model.
+///
+/// This model:
+/// * connects to the configured server and forwards the received data to the
+///   model output,
+/// * forwards data from the model input to the server,
+/// * reports the connections and disconnections,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct TcpClient {
+    /// Data from the server -- output port.
+    pub bytes_out: Output<Bytes>,
+
+    /// Connection status -- output port.
+    pub status_out: Output<TcpStatus>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: TcpClientConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<TcpEvent, Bytes>,
+}
+
+impl TcpClient {
+    /// Sends data to the server -- input port.
+    pub async fn bytes_in(&mut self, data: Bytes) {
+        let _ = self.io_thread.send(data);
+    }
+
+    /// Forwards the data received from the server, the connection status
+    /// changes and the I/O thread status -- input port.
+    pub async fn process(&mut self) {
+        for event in self.io_thread.try_recv_all() {
+            match event {
+                TcpEvent::Data(data) => self.bytes_out.send(data).await,
+                TcpEvent::Status(status) => self.status_out.send(status).await,
+            }
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for TcpClient {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for TcpClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TcpClient")
+            .field("address", &self.config.address)
+            .finish_non_exhaustive()
+    }
+}
+
+/// TCP client model prototype.
+pub struct ProtoTcpClient {
+    /// Data from the server -- output port.
+    pub bytes_out: Output<Bytes>,
+
+    /// Connection status -- output port.
+    pub status_out: Output<TcpStatus>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// TCP client model instance configuration.
+    config: TcpClientConfig,
+}
+
+impl ProtoTcpClient {
+    /// Creates a new TCP client model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the I/O thread cannot be created.
+    pub fn new(config: TcpClientConfig) -> Self {
+        Self {
+            bytes_out: Output::new(),
+            status_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoTcpClient {
+    type Model = TcpClient;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            shutdown_timeout: self.config.shutdown_timeout.map(Duration::from),
+        };
+        let io_thread = IoThread::try_with_options(TcpClientInner::new(&self.config), options)
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Failed to start the I/O thread of the TCP client to {}: {e}.",
+                    self.config.address
+                )
+            });
+
+        TcpClient {
+            bytes_out: self.bytes_out,
+            status_out: self.status_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoTcpClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoTcpClient")
+            .field("address", &self.config.address)
+            .finish_non_exhaustive()
+    }
+}
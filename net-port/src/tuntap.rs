@@ -0,0 +1,356 @@
+//! TUN/TAP port model.
+//!
+//! This module contains the [`TunTapPort`] model, which creates a virtual
+//! network interface in the kernel and exchanges its packets with the
+//! simulation, so that simulated network equipment can be tested with the full
+//! network stack of the host. It is available on Linux with the `tuntap`
+//! feature.
+//!
+//! The interface is either:
+//! * a TUN interface, exchanging IP packets,
+//! * or a TAP interface, exchanging Ethernet frames, if `tap` is set.
+//!
+//! Each packet routed by the host to the interface is forwarded to the
+//! simulation, and each packet sent to the model is received by the host from
+//! the interface. Packets are exchanged without packet information header.
+//!
+//! Creating an interface requires the `CAP_NET_ADMIN` capability; the
+//! interface is removed when the model is dropped.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::tuntap::TunTapConfig;
+//!
+//! let config = ConfigLoader::<TunTapConfig>::new()
+//!     .code(
+//!         r#"
+//! name = "simtun0"
+//! address = "10.0.0.1"
+//! netmask = "255.255.255.0"
+//! period = 10
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert!(!config.tap);
+//! ```
+
+use std::fmt;
+use std::io::{Error, ErrorKind, Read, Result as IoResult, Write};
+use std::net::Ipv4Addr;
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use schematic::Config;
+
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus};
+
+/// TUN/TAP port model instance configuration.
+#[derive(Config, Debug)]
+pub struct TunTapConfig {
+    /// Interface name.
+    ///
+    /// If no value is provided, the name is chosen by the kernel.
+    pub name: Option<String>,
+
+    /// Create a TAP interface exchanging Ethernet frames rather than a TUN
+    /// interface exchanging IP packets.
+    #[setting(default = false)]
+    pub tap: bool,
+
+    /// IPv4 address of the interface.
+    ///
+    /// If no value is provided, the interface is created without address.
+    pub address: Option<String>,
+
+    /// IPv4 netmask of the interface.
+    pub netmask: Option<String>,
+
+    /// MTU of the interface.
+    ///
+    /// If no value is provided, the default MTU of the kernel is used.
+    pub mtu: Option<u16>,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which received packets are forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+
+    /// Maximum time spent writing the pending packets when the model is
+    /// dropped.
+    ///
+    /// If no value is provided, the packets not yet written are discarded.
+    pub shutdown_timeout: Option<ConfigDuration>,
+}
+
+/// Receive buffer size, large enough for any packet.
+const BUFFER_SIZE: usize = 65536;
+
+/// Interface token.
+const DEVICE: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// TUN/TAP port.
+struct TunTapInner {
+    /// Virtual interface.
+    device: tun::platform::Device,
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+}
+
+impl TunTapInner {
+    /// Creates the virtual interface.
+    fn new(config: &TunTapConfig) -> IoResult<Self> {
+        let mut configuration = tun::Configuration::default();
+        configuration
+            .layer(if config.tap {
+                tun::Layer::L2
+            } else {
+                tun::Layer::L3
+            })
+            .platform(|platform| {
+                platform.packet_information(false);
+            })
+            .up();
+        if let Some(name) = &config.name {
+            configuration.name(name);
+        }
+        if let Some(address) = &config.address {
+            configuration.address(parse_address(address)?);
+        }
+        if let Some(netmask) = &config.netmask {
+            configuration.netmask(parse_address(netmask)?);
+        }
+        if let Some(mtu) = config.mtu {
+            configuration.mtu(i32::from(mtu));
+        }
+        let device = tun::create(&configuration).map_err(Error::other)?;
+        device.set_nonblock()?;
+
+        Ok(Self {
+            device,
+            buffer: vec![0; BUFFER_SIZE],
+        })
+    }
+}
+
+impl IoPort<SourceFd<'static>, Bytes, Bytes> for TunTapInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        registry.register(
+            &mut SourceFd(&self.device.as_raw_fd()),
+            DEVICE,
+            Interest::READABLE | Interest::WRITABLE,
+        )?;
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<Bytes> {
+        if token != DEVICE {
+            // Unknown event: should never happen.
+            return Err(Error::new(ErrorKind::InvalidInput, "Unknown event."));
+        }
+        loop {
+            match self.device.read(&mut self.buffer) {
+                Ok(len) => return Ok(Bytes::copy_from_slice(&self.buffer[..len])),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write(&mut self, packet: &Bytes) -> IoResult<()> {
+        // Packets are written whole; the kernel rejects malformed packets,
+        // which are discarded.
+        match self.device.write(packet) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::InvalidInput => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Parses an IPv4 address of the configuration.
+fn parse_address(address: &str) -> IoResult<Ipv4Addr> {
+    address.parse().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid IPv4 address {address}."),
+        )
+    })
+}
+
+/// TUN/TAP port model.
+///
+/// This model:
+/// * forwards the packets routed by the host to its interface to the model
+///   output,
+/// * injects the packets from the model input into the network stack of the
+///   host,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct TunTapPort {
+    /// Packets from the host -- output port.
+    pub packet_out: Output<Bytes>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: TunTapConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<Bytes, Bytes>,
+}
+
+impl TunTapPort {
+    /// Sends a packet to the host -- input port.
+    pub async fn packet_in(&mut self, packet: Bytes) {
+        let _ = self.io_thread.send(packet);
+    }
+
+    /// Forwards the packets from the host and the I/O thread status -- input
+    /// port.
+    pub async fn process(&mut self) {
+        for packet in self.io_thread.try_recv_all() {
+            self.packet_out.send(packet).await;
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for TunTapPort {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for TunTapPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TunTapPort")
+            .field("name", &self.config.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// TUN/TAP port model prototype.
+pub struct ProtoTunTapPort {
+    /// Packets from the host -- output port.
+    pub packet_out: Output<Bytes>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// TUN/TAP port model instance configuration.
+    config: TunTapConfig,
+}
+
+impl ProtoTunTapPort {
+    /// Creates a new TUN/TAP port model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the interface cannot be created, e.g. for
+    /// lack of privileges, or if the I/O thread cannot be created.
+    pub fn new(config: TunTapConfig) -> Self {
+        Self {
+            packet_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoTunTapPort {
+    type Model = TunTapPort;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let name = self.config.name.as_deref().unwrap_or("(unnamed)");
+        let port = TunTapInner::new(&self.config)
+            .unwrap_or_else(|e| panic!("Failed to create the {name} interface: {e}."));
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            shutdown_timeout: self.config.shutdown_timeout.map(Duration::from),
+        };
+        let io_thread = IoThread::try_with_options(port, options).unwrap_or_else(|e| {
+            panic!("Failed to start the I/O thread of the {name} interface: {e}.")
+        });
+
+        TunTapPort {
+            packet_out: self.packet_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoTunTapPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoTunTapPort")
+            .field("name", &self.config.name)
+            .finish_non_exhaustive()
+    }
+}
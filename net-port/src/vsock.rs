@@ -0,0 +1,831 @@
+//! vsock port model.
+//!
+//! This module contains the [`VsockPort`] model, which exchanges bytes with
+//! software running in a virtual machine over virtio-vsock sockets, without
+//! any network configuration of the host or of the guest. It is available on
+//! Linux with the `vsock` feature.
+//!
+//! The model acts either:
+//! * as a client connecting to `address`, given as `CID:PORT`, which is
+//!   reconnected after a disconnection if `reconnect_delay` is set, as for the
+//!   [`TcpClient`](crate::tcp::TcpClient) model,
+//! * or as a server listening on `listen_port` for connections from any
+//!   virtual machine, several guests or connections being served at once.
+//!
+//! By default, `AF_VSOCK` sockets are used, as for QEMU guests with a
+//! `vhost-vsock` device. If `uds_path` is set, the connections go instead
+//! through the Unix socket of a Firecracker hybrid vsock device: as a client,
+//! the model connects to the Unix socket and requests a connection to the
+//! guest port (the CID of `address` is then ignored), and as a server, it
+//! listens on the `<uds_path>_<listen_port>` Unix socket to which Firecracker
+//! forwards the connections of the guest.
+//!
+//! In server mode, bytes from all connections are forwarded and bytes are sent
+//! to all connections.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::vsock::VsockConfig;
+//!
+//! let config = ConfigLoader::<VsockConfig>::new()
+//!     .code(
+//!         r#"
+//! address = "3:5000"
+//! reconnectDelay = 500
+//! period = 10
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.address.as_deref(), Some("3:5000"));
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io::{Error, ErrorKind, Read, Result as IoResult, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::FileTypeExt;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use schematic::Config;
+
+use mio::net::{UnixListener, UnixStream};
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
+
+use vsock::{VMADDR_CID_ANY, VsockAddr, VsockListener, VsockStream};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus, WriteBuffer};
+
+/// vsock port model instance configuration.
+///
+/// Either `address` or `listen_port` must be set.
+#[derive(Config, Debug)]
+pub struct VsockConfig {
+    /// Address of the guest, as `CID:PORT`, to connect to as a client.
+    pub address: Option<String>,
+
+    /// Port to listen on as a server.
+    ///
+    /// This setting is ignored if `address` is set.
+    pub listen_port: Option<u32>,
+
+    /// Path of the Unix socket of a Firecracker hybrid vsock device.
+    ///
+    /// If no value is provided, `AF_VSOCK` sockets are used.
+    pub uds_path: Option<String>,
+
+    /// Size of the buffer used to read the connections.
+    #[setting(default = 4096)]
+    pub buffer_size: usize,
+
+    /// Initial delay before reconnecting to the guest after a disconnection
+    /// or a failed connection attempt.
+    ///
+    /// The delay doubles after each failed attempt, up to
+    /// `reconnect_max_delay`. If no value is provided, the connection is only
+    /// attempted once. This setting is ignored in server mode.
+    pub reconnect_delay: Option<ConfigDuration>,
+
+    /// Maximum delay between connection attempts.
+    #[setting(default = ConfigDuration::from_millis(10000))]
+    pub reconnect_max_delay: ConfigDuration,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which received data is forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// vsock connection status.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VsockStatus {
+    /// A connection was established, with the address of the peer.
+    Connected(String),
+
+    /// A connection was closed or could not be established, with the address
+    /// of the peer and the error description.
+    Disconnected(String, String),
+}
+
+/// Event read from the connections.
+enum VsockEvent {
+    /// Received data.
+    Data(Bytes),
+
+    /// Connection status change.
+    Status(VsockStatus),
+}
+
+/// Connected socket.
+enum Stream {
+    /// `AF_VSOCK` socket.
+    Vsock(VsockStream),
+
+    /// Unix socket of a hybrid vsock device.
+    Unix(UnixStream),
+}
+
+impl Stream {
+    /// Registers the socket.
+    fn register(&mut self, registry: &Registry, token: Token) -> IoResult<()> {
+        let interest = Interest::READABLE | Interest::WRITABLE;
+        match self {
+            Self::Vsock(stream) => {
+                registry.register(&mut SourceFd(&stream.as_raw_fd()), token, interest)
+            }
+            Self::Unix(stream) => registry.register(stream, token, interest),
+        }
+    }
+
+    /// Deregisters the socket.
+    fn deregister(&mut self, registry: &Registry) -> IoResult<()> {
+        match self {
+            Self::Vsock(stream) => registry.deregister(&mut SourceFd(&stream.as_raw_fd())),
+            Self::Unix(stream) => registry.deregister(stream),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        match self {
+            Self::Vsock(stream) => stream.read(buf),
+            Self::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        match self {
+            Self::Vsock(stream) => stream.write(buf),
+            Self::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        match self {
+            Self::Vsock(stream) => stream.flush(),
+            Self::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Listening socket.
+enum Listener {
+    /// `AF_VSOCK` socket.
+    Vsock(VsockListener),
+
+    /// Unix socket of a hybrid vsock device.
+    Unix(UnixListener),
+}
+
+impl Listener {
+    /// Accepts a connection, returning the socket and the peer address.
+    fn accept(&self) -> IoResult<(Stream, String)> {
+        match self {
+            Self::Vsock(listener) => {
+                let (stream, address) = listener.accept()?;
+                stream.set_nonblocking(true)?;
+
+                Ok((
+                    Stream::Vsock(stream),
+                    format!("{}:{}", address.cid(), address.port()),
+                ))
+            }
+            Self::Unix(listener) => {
+                let (stream, _) = listener.accept()?;
+
+                Ok((Stream::Unix(stream), "guest".to_owned()))
+            }
+        }
+    }
+}
+
+/// Connection state.
+enum PeerState {
+    /// Hybrid vsock connection request sent, with the partial reply.
+    Requested(Vec<u8>),
+
+    /// Open connection.
+    Open,
+}
+
+/// Connection to a guest.
+struct Peer {
+    /// Address of the guest.
+    name: String,
+
+    /// Socket.
+    stream: Stream,
+
+    /// Connection state.
+    state: PeerState,
+
+    /// Data not yet written.
+    tx: WriteBuffer,
+}
+
+/// Listener token.
+const LISTENER: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// Token of the first connection.
+const FIRST_PEER: usize = 2;
+
+/// Role of the port.
+enum Role {
+    /// Client of the guest at the CID and port.
+    Client(u32, u32),
+
+    /// Server listening on the port.
+    Server(u32),
+}
+
+/// vsock port.
+struct VsockInner {
+    /// Role of the port.
+    role: Role,
+
+    /// Path of the Unix socket of a hybrid vsock device, if any.
+    uds_path: Option<String>,
+
+    /// Listener, in server mode.
+    listener: Option<Listener>,
+
+    /// Connections, indexed by token.
+    peers: Vec<Option<Peer>>,
+
+    /// Receive buffer.
+    buffer: Vec<u8>,
+
+    /// Events not yet read.
+    events: VecDeque<VsockEvent>,
+
+    /// Initial reconnection delay, if enabled.
+    reconnect_delay: Option<Duration>,
+
+    /// Maximum reconnection delay.
+    reconnect_max_delay: Duration,
+
+    /// Delay before the next attempt after a failure.
+    delay: Duration,
+
+    /// Time of the next connection attempt, if any.
+    retry_at: Option<Instant>,
+
+    /// The connection to the guest was last reported as established.
+    ///
+    /// This is `None` until the first status is reported.
+    is_up: Option<bool>,
+
+    /// MIO registry, available once the port is registered.
+    registry: Option<Registry>,
+}
+
+impl VsockInner {
+    /// Creates a vsock port, connecting or listening once registered.
+    fn new(role: Role, config: &VsockConfig) -> Self {
+        let reconnect_delay = config.reconnect_delay.map(Duration::from);
+        let reconnect_max_delay = Duration::from(config.reconnect_max_delay);
+
+        Self {
+            role,
+            uds_path: config.uds_path.clone(),
+            listener: None,
+            peers: Vec::new(),
+            buffer: vec![0; config.buffer_size.max(1)],
+            events: VecDeque::new(),
+            reconnect_delay,
+            reconnect_max_delay: reconnect_max_delay.max(reconnect_delay.unwrap_or_default()),
+            delay: reconnect_delay.unwrap_or_default(),
+            retry_at: None,
+            is_up: None,
+            registry: None,
+        }
+    }
+
+    /// Starts a connection attempt to the guest.
+    fn connect(&mut self, cid: u32, port: u32) {
+        let name = format!("{cid}:{port}");
+        if let Err(e) = self.try_connect(cid, port, &name) {
+            self.closed(name, false, e.to_string());
+        }
+    }
+
+    /// Connects to the guest.
+    ///
+    /// `AF_VSOCK` connections are established synchronously, the hypervisor
+    /// answering immediately, while hybrid vsock connections are open once
+    /// the device acknowledges the connection request.
+    fn try_connect(&mut self, cid: u32, port: u32, name: &str) -> IoResult<()> {
+        let mut tx = WriteBuffer::new();
+        let (stream, state) = match &self.uds_path {
+            Some(path) => {
+                let stream = UnixStream::connect(path)?;
+                tx.push(format!("CONNECT {port}\n").as_bytes());
+
+                (Stream::Unix(stream), PeerState::Requested(Vec::new()))
+            }
+            None => {
+                let stream = VsockStream::connect(&VsockAddr::new(cid, port))?;
+                stream.set_nonblocking(true)?;
+
+                (Stream::Vsock(stream), PeerState::Open)
+            }
+        };
+        let is_open = matches!(state, PeerState::Open);
+        let index = self.add_peer(name.to_owned(), stream, state, tx)?;
+        if is_open {
+            self.opened(index);
+        }
+
+        Ok(())
+    }
+
+    /// Accepts the pending connections.
+    fn accept(&mut self) -> IoResult<()> {
+        loop {
+            let Some(listener) = &self.listener else {
+                return Ok(());
+            };
+            let (stream, name) = match listener.accept() {
+                Ok(connection) => connection,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::Interrupted | ErrorKind::ConnectionAborted
+                    ) =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let Ok(index) = self.add_peer(name, stream, PeerState::Open, WriteBuffer::new()) else {
+                continue;
+            };
+            self.opened(index);
+            // Data may have been received with the connection.
+            self.receive(index);
+        }
+    }
+
+    /// Registers a connection in a free slot, returning its index.
+    fn add_peer(
+        &mut self,
+        name: String,
+        mut stream: Stream,
+        state: PeerState,
+        tx: WriteBuffer,
+    ) -> IoResult<usize> {
+        let index = match self.peers.iter().position(Option::is_none) {
+            Some(index) => index,
+            None => {
+                self.peers.push(None);
+                self.peers.len() - 1
+            }
+        };
+        if let Some(registry) = &self.registry {
+            stream.register(registry, Token(FIRST_PEER + index))?;
+        }
+        self.peers[index] = Some(Peer {
+            name,
+            stream,
+            state,
+            tx,
+        });
+
+        Ok(index)
+    }
+
+    /// Reports an open connection.
+    fn opened(&mut self, index: usize) {
+        let Some(peer) = &self.peers[index] else {
+            return;
+        };
+        if matches!(self.role, Role::Client(..)) {
+            self.is_up = Some(true);
+            self.delay = self.reconnect_delay.unwrap_or_default();
+        }
+        self.events
+            .push_back(VsockEvent::Status(VsockStatus::Connected(
+                peer.name.clone(),
+            )));
+    }
+
+    /// Reads the data of a connection.
+    ///
+    /// The reply to a hybrid vsock connection request is consumed before the
+    /// data is forwarded.
+    fn receive(&mut self, index: usize) {
+        loop {
+            let Some(peer) = self.peers.get_mut(index).and_then(Option::as_mut) else {
+                return;
+            };
+            let len = match peer.stream.read(&mut self.buffer) {
+                Ok(0) => {
+                    self.close(index, "connection closed by peer".to_owned());
+                    return;
+                }
+                Ok(len) => len,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.close(index, e.to_string());
+                    return;
+                }
+            };
+            let mut start = 0;
+            if let PeerState::Requested(reply) = &mut peer.state {
+                let data = &self.buffer[..len];
+                let Some(end) = data.iter().position(|&byte| byte == b'\n') else {
+                    reply.extend_from_slice(data);
+                    continue;
+                };
+                reply.extend_from_slice(&data[..end]);
+                if !reply.starts_with(b"OK ") {
+                    let reply = String::from_utf8_lossy(reply).into_owned();
+                    self.close(index, format!("connection refused ({reply})"));
+                    return;
+                }
+                peer.state = PeerState::Open;
+                start = end + 1;
+                self.opened(index);
+            }
+            if start < len {
+                self.events
+                    .push_back(VsockEvent::Data(Bytes::copy_from_slice(
+                        &self.buffer[start..len],
+                    )));
+            }
+        }
+    }
+
+    /// Writes the pending data of a connection.
+    fn resume_write(&mut self, index: usize) {
+        let Some(peer) = self.peers.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        if let Err(e) = peer.tx.flush(&mut peer.stream) {
+            self.close(index, e.to_string());
+        }
+    }
+
+    /// Closes a connection.
+    fn close(&mut self, index: usize, error: String) {
+        let Some(mut peer) = self.peers[index].take() else {
+            return;
+        };
+        if let Some(registry) = &self.registry {
+            let _ = peer.stream.deregister(registry);
+        }
+        let is_open = matches!(peer.state, PeerState::Open);
+        self.closed(peer.name, is_open, error);
+    }
+
+    /// Reports a closed connection and schedules the reconnection to the
+    /// guest, if enabled.
+    ///
+    /// Disconnections from the guest are only reported once, until the
+    /// connection is established again.
+    fn closed(&mut self, name: String, is_open: bool, error: String) {
+        let is_reported = match self.role {
+            Role::Client(..) => {
+                if self.reconnect_delay.is_some() {
+                    self.retry_at = Some(Instant::now() + self.delay);
+                    self.delay = (self.delay * 2).min(self.reconnect_max_delay);
+                }
+                self.is_up.replace(false) != Some(false)
+            }
+            Role::Server(_) => is_open,
+        };
+        if is_reported {
+            self.events
+                .push_back(VsockEvent::Status(VsockStatus::Disconnected(name, error)));
+        }
+    }
+}
+
+impl IoPort<SourceFd<'static>, VsockEvent, Bytes> for VsockInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        self.registry = Some(registry.try_clone()?);
+        match self.role {
+            Role::Client(cid, port) => self.connect(cid, port),
+            Role::Server(port) => {
+                let listener = match &self.uds_path {
+                    Some(path) => {
+                        let path = format!("{path}_{port}");
+                        // A socket left over by a previous run would prevent
+                        // binding.
+                        if fs::symlink_metadata(&path)
+                            .is_ok_and(|metadata| metadata.file_type().is_socket())
+                        {
+                            fs::remove_file(&path)?;
+                        }
+                        let mut listener = UnixListener::bind(&path)?;
+                        registry.register(&mut listener, LISTENER, Interest::READABLE)?;
+
+                        Listener::Unix(listener)
+                    }
+                    None => {
+                        let listener = VsockListener::bind(&VsockAddr::new(VMADDR_CID_ANY, port))?;
+                        listener.set_nonblocking(true)?;
+                        registry.register(
+                            &mut SourceFd(&listener.as_raw_fd()),
+                            LISTENER,
+                            Interest::READABLE,
+                        )?;
+
+                        Listener::Vsock(listener)
+                    }
+                };
+                self.listener = Some(listener);
+            }
+        }
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<VsockEvent> {
+        if self.events.is_empty() {
+            if token == LISTENER {
+                self.accept()?;
+            } else if let Some(index) = token.0.checked_sub(FIRST_PEER) {
+                self.receive(index);
+            }
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        if let Some(index) = token.0.checked_sub(FIRST_PEER) {
+            self.resume_write(index);
+        }
+
+        Ok(())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        // Events queued outside of a read, e.g. a failed connection attempt,
+        // are forwarded immediately.
+        if !self.events.is_empty() {
+            return Some(Instant::now());
+        }
+        self.retry_at
+    }
+
+    fn timeout(&mut self) -> IoResult<VsockEvent> {
+        if self
+            .retry_at
+            .is_some_and(|retry_at| retry_at <= Instant::now())
+        {
+            self.retry_at = None;
+            if let Role::Client(cid, port) = self.role {
+                self.connect(cid, port);
+            }
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn write(&mut self, data: &Bytes) -> IoResult<()> {
+        // Data is dropped while no connection is open; the part which cannot
+        // be written immediately is written once the connection is writable.
+        for index in 0..self.peers.len() {
+            let Some(peer) = &mut self.peers[index] else {
+                continue;
+            };
+            if !matches!(peer.state, PeerState::Open) {
+                continue;
+            }
+            if let Err(e) = peer.tx.write(&mut peer.stream, data) {
+                self.close(index, e.to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        self.peers
+            .iter()
+            .flatten()
+            .any(|peer| matches!(peer.state, PeerState::Open) && !peer.tx.is_empty())
+    }
+}
+
+/// Parses a `CID:PORT` address.
+fn parse_address(address: &str) -> IoResult<(u32, u32)> {
+    address
+        .split_once(':')
+        .and_then(|(cid, port)| Some((cid.trim().parse().ok()?, port.trim().parse().ok()?)))
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid vsock address {address}."),
+            )
+        })
+}
+
+/// vsock port model.
+///
+/// This model:
+/// * connects to the configured guest, or accepts the connections of guests,
+///   and forwards the received data to the model output,
+/// * forwards data from the model input to the guest or to all connections,
+/// * reports the connections and disconnections,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct VsockPort {
+    /// Received data -- output port.
+    pub bytes_out: Output<Bytes>,
+
+    /// Connection status -- output port.
+    pub status_out: Output<VsockStatus>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: VsockConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<VsockEvent, Bytes>,
+}
+
+impl VsockPort {
+    /// Sends data to the guest or to all connections -- input port.
+    pub async fn bytes_in(&mut self, data: Bytes) {
+        let _ = self.io_thread.send(data);
+    }
+
+    /// Forwards the received data, the connection status changes and the I/O
+    /// thread status -- input port.
+    pub async fn process(&mut self) {
+        for event in self.io_thread.try_recv_all() {
+            match event {
+                VsockEvent::Data(data) => self.bytes_out.send(data).await,
+                VsockEvent::Status(status) => self.status_out.send(status).await,
+            }
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for VsockPort {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for VsockPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VsockPort")
+            .field("address", &self.config.address)
+            .field("listen_port", &self.config.listen_port)
+            .finish_non_exhaustive()
+    }
+}
+
+/// vsock port model prototype.
+pub struct ProtoVsockPort {
+    /// Received data -- output port.
+    pub bytes_out: Output<Bytes>,
+
+    /// Connection status -- output port.
+    pub status_out: Output<VsockStatus>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// vsock port model instance configuration.
+    config: VsockConfig,
+}
+
+impl ProtoVsockPort {
+    /// Creates a new vsock port model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if neither `address` nor `listen_port` is
+    /// set in the configuration, if the address is invalid, or if the I/O
+    /// thread cannot be created, e.g. because the port cannot be bound.
+    pub fn new(config: VsockConfig) -> Self {
+        Self {
+            bytes_out: Output::new(),
+            status_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoVsockPort {
+    type Model = VsockPort;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let role = match (&self.config.address, self.config.listen_port) {
+            (Some(address), _) => {
+                let (cid, port) = parse_address(address).unwrap_or_else(|e| panic!("{e}"));
+                Role::Client(cid, port)
+            }
+            (None, Some(port)) => Role::Server(port),
+            (None, None) => {
+                panic!("Either the address or the listen port of the vsock port must be set.")
+            }
+        };
+        let target = match role {
+            Role::Client(cid, port) => format!("client to {cid}:{port}"),
+            Role::Server(port) => format!("server on port {port}"),
+        };
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(VsockInner::new(role, &self.config), options)
+            .unwrap_or_else(|e| {
+                panic!("Failed to start the I/O thread of the vsock {target}: {e}.")
+            });
+
+        VsockPort {
+            bytes_out: self.bytes_out,
+            status_out: self.status_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoVsockPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoVsockPort")
+            .field("address", &self.config.address)
+            .field("listen_port", &self.config.listen_port)
+            .finish_non_exhaustive()
+    }
+}
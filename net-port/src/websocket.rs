@@ -0,0 +1,767 @@
+//! WebSocket port model.
+//!
+//! This module contains the [`WebSocketPort`] model, which exchanges WebSocket
+//! messages with browser-based dashboards, test orchestrators or any other
+//! WebSocket endpoint. It is available with the `websocket` feature.
+//!
+//! The model acts either:
+//! * as a client connecting to the server at `url`, which is reconnected
+//!   after a disconnection if `reconnect_delay` is set, as for the
+//!   [`TcpClient`](crate::tcp::TcpClient) model,
+//! * or as a server listening on `listen_address`, to which several clients
+//!   can connect.
+//!
+//! The payloads of the received text and binary messages are forwarded as
+//! bytes, while the bytes sent to the model are sent as binary messages, or as
+//! text messages if `text` is set in the configuration. In server mode,
+//! messages from all clients are forwarded and messages are sent to all
+//! clients. Only unencrypted `ws://` connections are supported.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::websocket::WebSocketConfig;
+//!
+//! let config = ConfigLoader::<WebSocketConfig>::new()
+//!     .code(
+//!         r#"
+//! listenAddress = "0.0.0.0:8080"
+//! text = true
+//! period = 10
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.listen_address.as_deref(), Some("0.0.0.0:8080"));
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result as IoResult};
+use std::net::ToSocketAddrs;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use schematic::Config;
+
+use mio::net::{TcpListener, TcpStream};
+use mio::{Interest, Registry, Token};
+
+use tungstenite::client::IntoClientRequest;
+use tungstenite::handshake::HandshakeError;
+use tungstenite::handshake::MidHandshake;
+use tungstenite::handshake::client::{ClientHandshake, Request};
+use tungstenite::handshake::server::{NoCallback, ServerHandshake};
+use tungstenite::protocol::frame::Utf8Bytes;
+use tungstenite::{Message, WebSocket};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus};
+
+/// WebSocket port model instance configuration.
+///
+/// Either `url` or `listen_address` must be set.
+#[derive(Config, Debug)]
+pub struct WebSocketConfig {
+    /// Server URL, as `ws://HOST:PORT/PATH`, to connect to as a client.
+    pub url: Option<String>,
+
+    /// Local address, as `HOST:PORT`, to listen on as a server.
+    ///
+    /// This setting is ignored if `url` is set.
+    pub listen_address: Option<String>,
+
+    /// Send text messages rather than binary messages.
+    ///
+    /// Bytes which are not valid UTF-8 are then replaced by the replacement
+    /// character.
+    #[setting(default = false)]
+    pub text: bool,
+
+    /// Initial delay before reconnecting to the server after a disconnection
+    /// or a failed connection attempt.
+    ///
+    /// The delay doubles after each failed attempt, up to
+    /// `reconnect_max_delay`. If no value is provided, the connection is only
+    /// attempted once. This setting is ignored in server mode.
+    pub reconnect_delay: Option<ConfigDuration>,
+
+    /// Maximum delay between connection attempts.
+    #[setting(default = ConfigDuration::from_millis(10000))]
+    pub reconnect_max_delay: ConfigDuration,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which received messages are forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// WebSocket connection status.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WebSocketStatus {
+    /// A connection was established, with the server URL or the client
+    /// address.
+    Connected(String),
+
+    /// A connection was closed or could not be established, with the server
+    /// URL or the client address and the error description.
+    Disconnected(String, String),
+}
+
+/// Event read from the connections.
+enum WebSocketEvent {
+    /// Received message payload.
+    Data(Bytes),
+
+    /// Connection status change.
+    Status(WebSocketStatus),
+}
+
+/// State of a connection.
+enum PeerState {
+    /// TCP connection to the server being established.
+    Connecting(TcpStream),
+
+    /// Client handshake in progress.
+    Client(MidHandshake<ClientHandshake<TcpStream>>),
+
+    /// Server handshake in progress.
+    Server(MidHandshake<ServerHandshake<TcpStream, NoCallback>>),
+
+    /// Open connection.
+    Open(WebSocket<TcpStream>),
+}
+
+/// Connection to a server or a client.
+struct Peer {
+    /// Server URL or client address.
+    name: String,
+
+    /// Connection state.
+    state: PeerState,
+
+    /// The connection has been reported as open.
+    is_open: bool,
+}
+
+/// Listener token.
+const LISTENER: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// Token of the first connection.
+const FIRST_PEER: usize = 2;
+
+/// Role of the port.
+enum Role {
+    /// Client of the server at the URL.
+    Client(String),
+
+    /// Server listening on the address.
+    Server(String),
+}
+
+/// WebSocket port.
+struct WebSocketInner {
+    /// Role of the port.
+    role: Role,
+
+    /// Listener, in server mode.
+    listener: Option<TcpListener>,
+
+    /// Connections, indexed by token.
+    peers: Vec<Option<Peer>>,
+
+    /// Send text messages.
+    text: bool,
+
+    /// Decoded events not yet read.
+    events: VecDeque<WebSocketEvent>,
+
+    /// Initial reconnection delay, if enabled.
+    reconnect_delay: Option<Duration>,
+
+    /// Maximum reconnection delay.
+    reconnect_max_delay: Duration,
+
+    /// Delay before the next attempt after a failure.
+    delay: Duration,
+
+    /// Time of the next connection attempt, if any.
+    retry_at: Option<Instant>,
+
+    /// The connection to the server was last reported as established.
+    ///
+    /// This is `None` until the first status is reported.
+    is_up: Option<bool>,
+
+    /// MIO registry, available once the port is registered.
+    registry: Option<Registry>,
+}
+
+impl WebSocketInner {
+    /// Creates a WebSocket port, connecting or listening once registered.
+    fn new(role: Role, config: &WebSocketConfig) -> Self {
+        let reconnect_delay = config.reconnect_delay.map(Duration::from);
+        let reconnect_max_delay = Duration::from(config.reconnect_max_delay);
+
+        Self {
+            role,
+            listener: None,
+            peers: Vec::new(),
+            text: config.text,
+            events: VecDeque::new(),
+            reconnect_delay,
+            reconnect_max_delay: reconnect_max_delay.max(reconnect_delay.unwrap_or_default()),
+            delay: reconnect_delay.unwrap_or_default(),
+            retry_at: None,
+            is_up: None,
+            registry: None,
+        }
+    }
+
+    /// Starts a connection attempt to the server.
+    fn connect(&mut self, url: &str) {
+        if let Err(e) = self.try_connect(url) {
+            self.closed(url.to_owned(), false, e.to_string());
+        }
+    }
+
+    /// Starts a non-blocking connection to the server.
+    fn try_connect(&mut self, url: &str) -> IoResult<()> {
+        let request = request(url)?;
+        let host = request.uri().host().unwrap_or_default();
+        let port = request.uri().port_u16().unwrap_or(80);
+        let server = (host, port).to_socket_addrs()?.next().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Cannot resolve address {host}:{port}."),
+            )
+        })?;
+        let mut stream = TcpStream::connect(server)?;
+        let index = self.free_slot();
+        self.register_peer(index, &mut stream)?;
+        self.peers[index] = Some(Peer {
+            name: url.to_owned(),
+            state: PeerState::Connecting(stream),
+            is_open: false,
+        });
+
+        Ok(())
+    }
+
+    /// Accepts the pending client connections.
+    fn accept(&mut self) -> IoResult<()> {
+        loop {
+            let Some(listener) = &self.listener else {
+                return Ok(());
+            };
+            let (mut stream, address) = match listener.accept() {
+                Ok(connection) => connection,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::Interrupted | ErrorKind::ConnectionAborted
+                    ) =>
+                {
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let index = self.free_slot();
+            if self.register_peer(index, &mut stream).is_err() {
+                continue;
+            }
+            let state = match tungstenite::accept(stream) {
+                Ok(websocket) => PeerState::Open(websocket),
+                Err(HandshakeError::Interrupted(handshake)) => PeerState::Server(handshake),
+                Err(HandshakeError::Failure(_)) => continue,
+            };
+            self.peers[index] = Some(Peer {
+                name: address.to_string(),
+                state,
+                is_open: false,
+            });
+            // Messages may have been received with the handshake.
+            self.service(index);
+        }
+    }
+
+    /// Returns the index of a free connection slot, allocating it if
+    /// necessary.
+    fn free_slot(&mut self) -> usize {
+        match self.peers.iter().position(Option::is_none) {
+            Some(index) => index,
+            None => {
+                self.peers.push(None);
+                self.peers.len() - 1
+            }
+        }
+    }
+
+    /// Registers the stream of the connection at the provided index.
+    fn register_peer(&self, index: usize, stream: &mut TcpStream) -> IoResult<()> {
+        match &self.registry {
+            Some(registry) => registry.register(
+                stream,
+                Token(FIRST_PEER + index),
+                Interest::READABLE | Interest::WRITABLE,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Advances the handshake of the connection at the provided index and
+    /// reads its messages.
+    fn service(&mut self, index: usize) {
+        let Some(mut peer) = self.peers.get_mut(index).and_then(Option::take) else {
+            return;
+        };
+        peer.state = match advance(&peer.name, peer.state) {
+            Ok(state) => state,
+            Err(e) => {
+                self.closed(peer.name, peer.is_open, e);
+                return;
+            }
+        };
+        let PeerState::Open(websocket) = &mut peer.state else {
+            self.peers[index] = Some(peer);
+            return;
+        };
+        if !peer.is_open {
+            peer.is_open = true;
+            if matches!(self.role, Role::Client(_)) {
+                self.is_up = Some(true);
+                self.delay = self.reconnect_delay.unwrap_or_default();
+            }
+            self.events
+                .push_back(WebSocketEvent::Status(WebSocketStatus::Connected(
+                    peer.name.clone(),
+                )));
+        }
+        loop {
+            match websocket.read() {
+                Ok(Message::Binary(data)) => self.events.push_back(WebSocketEvent::Data(data)),
+                Ok(Message::Text(text)) => {
+                    let data: &Bytes = text.as_ref();
+                    self.events.push_back(WebSocketEvent::Data(data.clone()));
+                }
+                // Control messages are handled by the WebSocket.
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    self.closed(peer.name, true, e.to_string());
+                    return;
+                }
+            }
+        }
+        // The replies to control messages are sent with the pending data.
+        if let Err(e) = flush(websocket) {
+            self.closed(peer.name, true, e);
+            return;
+        }
+        self.peers[index] = Some(peer);
+    }
+
+    /// Writes the pending data of the connection at the provided index.
+    fn resume_write(&mut self, index: usize) {
+        let Some(peer) = self.peers.get_mut(index) else {
+            return;
+        };
+        if let Some(Peer {
+            state: PeerState::Open(websocket),
+            ..
+        }) = peer
+        {
+            if let Err(e) = flush(websocket) {
+                let peer = peer.take().unwrap();
+                self.closed(peer.name, true, e);
+            }
+        }
+    }
+
+    /// Reports a closed connection and schedules the reconnection to the
+    /// server, if enabled.
+    ///
+    /// Disconnections from the server are only reported once, until the
+    /// connection is established again, and the failed handshakes of clients
+    /// are not reported.
+    fn closed(&mut self, name: String, is_open: bool, error: String) {
+        let is_reported = match self.role {
+            Role::Client(_) => {
+                if self.reconnect_delay.is_some() {
+                    self.retry_at = Some(Instant::now() + self.delay);
+                    self.delay = (self.delay * 2).min(self.reconnect_max_delay);
+                }
+                self.is_up.replace(false) != Some(false)
+            }
+            Role::Server(_) => is_open,
+        };
+        if is_reported {
+            self.events
+                .push_back(WebSocketEvent::Status(WebSocketStatus::Disconnected(
+                    name, error,
+                )));
+        }
+    }
+}
+
+impl IoPort<TcpStream, WebSocketEvent, Bytes> for WebSocketInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        self.registry = Some(registry.try_clone()?);
+        match &self.role {
+            Role::Client(url) => {
+                let url = url.clone();
+                self.connect(&url);
+            }
+            Role::Server(address) => {
+                let address = address.to_socket_addrs()?.next().ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Cannot resolve address {address}."),
+                    )
+                })?;
+                let mut listener = TcpListener::bind(address)?;
+                registry.register(&mut listener, LISTENER, Interest::READABLE)?;
+                self.listener = Some(listener);
+            }
+        }
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<WebSocketEvent> {
+        if self.events.is_empty() {
+            if token == LISTENER {
+                self.accept()?;
+            } else if let Some(index) = token.0.checked_sub(FIRST_PEER) {
+                self.service(index);
+            }
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        if let Some(index) = token.0.checked_sub(FIRST_PEER) {
+            self.resume_write(index);
+        }
+
+        Ok(())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        // Events queued outside of a read, e.g. a failed connection attempt,
+        // are forwarded immediately.
+        if !self.events.is_empty() {
+            return Some(Instant::now());
+        }
+        self.retry_at
+    }
+
+    fn timeout(&mut self) -> IoResult<WebSocketEvent> {
+        if self
+            .retry_at
+            .is_some_and(|retry_at| retry_at <= Instant::now())
+        {
+            self.retry_at = None;
+            if let Role::Client(url) = &self.role {
+                let url = url.clone();
+                self.connect(&url);
+            }
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn write(&mut self, data: &Bytes) -> IoResult<()> {
+        let message = if self.text {
+            match Utf8Bytes::try_from(data.clone()) {
+                Ok(text) => Message::Text(text),
+                Err(_) => Message::text(String::from_utf8_lossy(data).into_owned()),
+            }
+        } else {
+            Message::Binary(data.clone())
+        };
+        // Data is dropped while no connection is open; the part of the message
+        // which cannot be written immediately is written once the connection
+        // is writable.
+        for index in 0..self.peers.len() {
+            let Some(Peer {
+                state: PeerState::Open(websocket),
+                ..
+            }) = &mut self.peers[index]
+            else {
+                continue;
+            };
+            match websocket.send(message.clone()) {
+                Ok(()) => {}
+                Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => {
+                    let peer = self.peers[index].take().unwrap();
+                    self.closed(peer.name, true, e.to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the handshake request for the provided URL.
+fn request(url: &str) -> IoResult<Request> {
+    let request = url
+        .into_client_request()
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    if request.uri().scheme_str() != Some("ws") {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unsupported WebSocket URL {url}, only ws:// URLs are supported."),
+        ));
+    }
+
+    Ok(request)
+}
+
+/// Advances the handshake of a connection, returning its new state or the
+/// description of the error which closed it.
+///
+/// The name of a connection to the server is its URL.
+fn advance(name: &str, state: PeerState) -> Result<PeerState, String> {
+    let result = match state {
+        PeerState::Connecting(stream) => {
+            if let Some(e) = stream.take_error().unwrap_or_else(Some) {
+                return Err(e.to_string());
+            }
+            match stream.peer_addr() {
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::NotConnected => {
+                    return Ok(PeerState::Connecting(stream));
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+            let request = request(name).map_err(|e| e.to_string())?;
+
+            tungstenite::client(request, stream).map(|(websocket, _)| websocket)
+        }
+        PeerState::Client(handshake) => handshake.handshake().map(|(websocket, _)| websocket),
+        PeerState::Server(handshake) => {
+            return match handshake.handshake() {
+                Ok(websocket) => Ok(PeerState::Open(websocket)),
+                Err(HandshakeError::Interrupted(handshake)) => Ok(PeerState::Server(handshake)),
+                Err(HandshakeError::Failure(e)) => Err(e.to_string()),
+            };
+        }
+        PeerState::Open(websocket) => return Ok(PeerState::Open(websocket)),
+    };
+
+    match result {
+        Ok(websocket) => Ok(PeerState::Open(websocket)),
+        Err(HandshakeError::Interrupted(handshake)) => Ok(PeerState::Client(handshake)),
+        Err(HandshakeError::Failure(e)) => Err(e.to_string()),
+    }
+}
+
+/// Writes the pending data of a WebSocket, returning the description of the
+/// error which closed it, if any.
+fn flush(websocket: &mut WebSocket<TcpStream>) -> Result<(), String> {
+    match websocket.flush() {
+        Ok(()) => Ok(()),
+        Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// WebSocket port model.
+///
+/// This model:
+/// * connects to the configured server, or accepts the connections of clients,
+///   and forwards the payloads of the received messages to the model output,
+/// * forwards data from the model input to the server or to all clients,
+/// * reports the connections and disconnections,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct WebSocketPort {
+    /// Payloads of the received messages -- output port.
+    pub bytes_out: Output<Bytes>,
+
+    /// Connection status -- output port.
+    pub status_out: Output<WebSocketStatus>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: WebSocketConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<WebSocketEvent, Bytes>,
+}
+
+impl WebSocketPort {
+    /// Sends data to the server or to all clients -- input port.
+    pub async fn bytes_in(&mut self, data: Bytes) {
+        let _ = self.io_thread.send(data);
+    }
+
+    /// Forwards the received messages, the connection status changes and the
+    /// I/O thread status -- input port.
+    pub async fn process(&mut self) {
+        for event in self.io_thread.try_recv_all() {
+            match event {
+                WebSocketEvent::Data(data) => self.bytes_out.send(data).await,
+                WebSocketEvent::Status(status) => self.status_out.send(status).await,
+            }
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for WebSocketPort {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for WebSocketPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WebSocketPort")
+            .field("url", &self.config.url)
+            .field("listen_address", &self.config.listen_address)
+            .finish_non_exhaustive()
+    }
+}
+
+/// WebSocket port model prototype.
+pub struct ProtoWebSocketPort {
+    /// Payloads of the received messages -- output port.
+    pub bytes_out: Output<Bytes>,
+
+    /// Connection status -- output port.
+    pub status_out: Output<WebSocketStatus>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// WebSocket port model instance configuration.
+    config: WebSocketConfig,
+}
+
+impl ProtoWebSocketPort {
+    /// Creates a new WebSocket port model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if neither `url` nor `listen_address` is set
+    /// in the configuration, or if the I/O thread cannot be created, e.g.
+    /// because the listen address cannot be bound.
+    pub fn new(config: WebSocketConfig) -> Self {
+        Self {
+            bytes_out: Output::new(),
+            status_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoWebSocketPort {
+    type Model = WebSocketPort;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let role = match (&self.config.url, &self.config.listen_address) {
+            (Some(url), _) => Role::Client(url.clone()),
+            (None, Some(address)) => Role::Server(address.clone()),
+            (None, None) => {
+                panic!("Either the URL or the listen address of the WebSocket port must be set.")
+            }
+        };
+        let target = match &role {
+            Role::Client(url) => format!("client to {url}"),
+            Role::Server(address) => format!("server on {address}"),
+        };
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread =
+            IoThread::try_with_options(WebSocketInner::new(role, &self.config), options)
+                .unwrap_or_else(|e| {
+                    panic!("Failed to start the I/O thread of the WebSocket {target}: {e}.")
+                });
+
+        WebSocketPort {
+            bytes_out: self.bytes_out,
+            status_out: self.status_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoWebSocketPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoWebSocketPort")
+            .field("url", &self.config.url)
+            .field("listen_address", &self.config.listen_address)
+            .finish_non_exhaustive()
+    }
+}
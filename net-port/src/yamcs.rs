@@ -0,0 +1,702 @@
+//! YAMCS bridge model.
+//!
+//! This module contains the [`YamcsBridge`] model, which connects the
+//! simulation to the data links of a YAMCS instance, so that the mission
+//! control system can be exercised against a simulated spacecraft:
+//! * the telemetry packets from the simulation are injected into a YAMCS TM
+//!   data link,
+//! * the telecommand packets released by a YAMCS TC data link are injected
+//!   into the simulation.
+//!
+//! With the TCP transport, the model listens for the connections of a
+//! `TcpTmDataLink` and of a `TcpTcDataLink`, which YAMCS opens as a client.
+//! A new TM connection replaces the previous one, and telemetry packets sent
+//! while no TM link is connected are discarded. Telecommands are delimited
+//! with the length field of their CCSDS primary header.
+//!
+//! With the UDP transport, each telemetry packet is sent as a datagram to a
+//! `UdpTmDataLink`, and each datagram received from a `UdpTcDataLink` is a
+//! telecommand packet.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::yamcs::{YamcsBridgeConfig, YamcsTransport};
+//!
+//! // YAMCS configuration:
+//! //
+//! // dataLinks:
+//! //   - name: tm-sim
+//! //     class: org.yamcs.tctm.TcpTmDataLink
+//! //     host: localhost
+//! //     port: 10015
+//! //   - name: tc-sim
+//! //     class: org.yamcs.tctm.TcpTcDataLink
+//! //     host: localhost
+//! //     port: 10025
+//! let config = ConfigLoader::<YamcsBridgeConfig>::new()
+//!     .code(
+//!         r#"
+//! tmAddress = "0.0.0.0:10015"
+//! tcAddress = "0.0.0.0:10025"
+//! period = 10
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.transport, YamcsTransport::Tcp);
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Error, ErrorKind, Read, Result as IoResult};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use schematic::{Config, ConfigEnum};
+use serde::{Deserialize, Serialize};
+
+use mio::net::{TcpListener, TcpStream, UdpSocket};
+use mio::{Interest, Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus, WriteBuffer};
+
+/// Size of the CCSDS space packet primary header.
+const PRIMARY_HEADER_LEN: usize = 6;
+
+/// TM listener or socket token.
+const TM: Token = Token(0);
+
+/// TC listener or socket token.
+const TC: Token = Token(1);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(2);
+
+/// TM link connection token.
+const TM_LINK: Token = Token(3);
+
+/// Token of the first TC link connection.
+const FIRST_TC_LINK: usize = 4;
+
+/// YAMCS bridge model instance configuration.
+#[derive(Config, Debug)]
+pub struct YamcsBridgeConfig {
+    /// Transport of the data links.
+    pub transport: YamcsTransport,
+
+    /// TM data link address, as `HOST:PORT`.
+    ///
+    /// This is the local address to listen on with the TCP transport, and the
+    /// address of the YAMCS data link with the UDP transport.
+    #[setting(default = "0.0.0.0:10015")]
+    pub tm_address: String,
+
+    /// TC data link address, as `HOST:PORT`.
+    ///
+    /// This is the local address to listen on with the TCP transport, and the
+    /// local address the YAMCS data link sends to with the UDP transport.
+    #[setting(default = "0.0.0.0:10025")]
+    pub tc_address: String,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which telecommands are forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+}
+
+/// Transport of the YAMCS data links.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum YamcsTransport {
+    /// TCP data links, connecting to the model.
+    #[default]
+    Tcp,
+
+    /// UDP data links.
+    Udp,
+}
+
+/// YAMCS data link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum YamcsLink {
+    /// TM data link.
+    Tm,
+
+    /// TC data link.
+    Tc,
+}
+
+/// Connection status of a TCP data link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct YamcsLinkStatus {
+    /// Data link.
+    pub link: YamcsLink,
+
+    /// The data link connected, or disconnected otherwise.
+    pub connected: bool,
+}
+
+/// Event read from the data links.
+enum YamcsEvent {
+    /// Received telecommand.
+    Tc(Bytes),
+
+    /// Data link connection or disconnection.
+    Link(YamcsLinkStatus),
+}
+
+/// TC data link connection.
+struct TcLink {
+    /// Connection.
+    stream: TcpStream,
+
+    /// Received bytes not yet decoded.
+    buffer: Vec<u8>,
+}
+
+/// YAMCS bridge port.
+struct YamcsBridgeInner {
+    /// Transport.
+    transport: YamcsTransport,
+
+    /// TM data link address.
+    tm_address: String,
+
+    /// TC data link address.
+    tc_address: String,
+
+    /// TM listener, with the TCP transport.
+    tm_listener: Option<TcpListener>,
+
+    /// TC listener, with the TCP transport.
+    tc_listener: Option<TcpListener>,
+
+    /// TM data link connection and the data not yet written to it.
+    tm_link: Option<(TcpStream, WriteBuffer)>,
+
+    /// TC data link connections, indexed by token.
+    tc_links: Vec<Option<TcLink>>,
+
+    /// TM socket and data link address, with the UDP transport.
+    tm_socket: Option<(UdpSocket, SocketAddr)>,
+
+    /// TC socket, with the UDP transport.
+    tc_socket: Option<UdpSocket>,
+
+    /// Decoded events not yet read.
+    events: VecDeque<YamcsEvent>,
+
+    /// Read buffer.
+    buffer: Vec<u8>,
+
+    /// MIO registry, available once the port is registered.
+    registry: Option<Registry>,
+}
+
+impl YamcsBridgeInner {
+    /// Creates a YAMCS bridge port, listening or binding once registered.
+    fn new(config: &YamcsBridgeConfig) -> Self {
+        // Until read_buf (RFC 2930) is stabilized we need an initialized
+        // buffer.
+        Self {
+            transport: config.transport,
+            tm_address: config.tm_address.clone(),
+            tc_address: config.tc_address.clone(),
+            tm_listener: None,
+            tc_listener: None,
+            tm_link: None,
+            tc_links: Vec::new(),
+            tm_socket: None,
+            tc_socket: None,
+            events: VecDeque::new(),
+            buffer: vec![0; 65536],
+            registry: None,
+        }
+    }
+
+    /// Accepts the pending TM data link connections, keeping the last one.
+    fn accept_tm(&mut self) -> IoResult<()> {
+        while let Some(mut stream) = accept(self.tm_listener.as_ref())? {
+            if let Some(registry) = &self.registry {
+                if registry
+                    .register(
+                        &mut stream,
+                        TM_LINK,
+                        Interest::READABLE | Interest::WRITABLE,
+                    )
+                    .is_err()
+                {
+                    continue;
+                }
+            }
+            if let (Some((mut previous, _)), Some(registry)) = (self.tm_link.take(), &self.registry)
+            {
+                let _ = registry.deregister(&mut previous);
+            }
+            self.tm_link = Some((stream, WriteBuffer::new()));
+            self.events.push_back(YamcsEvent::Link(YamcsLinkStatus {
+                link: YamcsLink::Tm,
+                connected: true,
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Accepts the pending TC data link connections.
+    fn accept_tc(&mut self) -> IoResult<()> {
+        while let Some(mut stream) = accept(self.tc_listener.as_ref())? {
+            let index = match self.tc_links.iter().position(Option::is_none) {
+                Some(index) => index,
+                None => {
+                    self.tc_links.push(None);
+                    self.tc_links.len() - 1
+                }
+            };
+            if let Some(registry) = &self.registry {
+                if registry
+                    .register(
+                        &mut stream,
+                        Token(FIRST_TC_LINK + index),
+                        Interest::READABLE,
+                    )
+                    .is_err()
+                {
+                    continue;
+                }
+            }
+            self.tc_links[index] = Some(TcLink {
+                stream,
+                buffer: Vec::new(),
+            });
+            self.events.push_back(YamcsEvent::Link(YamcsLinkStatus {
+                link: YamcsLink::Tc,
+                connected: true,
+            }));
+            // Telecommands may have been received with the connection.
+            self.receive_tc(index);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the telecommands of a TC data link connection.
+    fn receive_tc(&mut self, index: usize) {
+        let Some(link) = self.tc_links.get_mut(index).and_then(Option::as_mut) else {
+            return;
+        };
+        let is_closed = loop {
+            match link.stream.read(&mut self.buffer) {
+                Ok(0) => break true,
+                Ok(len) => link.buffer.extend_from_slice(&self.buffer[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break false,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => break true,
+            }
+        };
+        while let Some(len) = packet_len(&link.buffer) {
+            let packet: Vec<u8> = link.buffer.drain(..len).collect();
+            self.events.push_back(YamcsEvent::Tc(packet.into()));
+        }
+        if is_closed {
+            let mut link = self.tc_links[index].take().unwrap();
+            if let Some(registry) = &self.registry {
+                let _ = registry.deregister(&mut link.stream);
+            }
+            self.events.push_back(YamcsEvent::Link(YamcsLinkStatus {
+                link: YamcsLink::Tc,
+                connected: false,
+            }));
+        }
+    }
+
+    /// Reads the telecommand datagrams.
+    fn receive_tc_datagrams(&mut self) -> IoResult<()> {
+        let Some(socket) = &self.tc_socket else {
+            return Ok(());
+        };
+        loop {
+            match socket.recv_from(&mut self.buffer) {
+                Ok((len, _)) => {
+                    self.events
+                        .push_back(YamcsEvent::Tc(Bytes::copy_from_slice(&self.buffer[..len])));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::Interrupted | ErrorKind::ConnectionRefused
+                    ) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Discards the data received from the TM data link, and closes the
+    /// connection if closed by YAMCS.
+    fn drain_tm(&mut self) {
+        let Some((stream, _)) = &mut self.tm_link else {
+            return;
+        };
+        loop {
+            match stream.read(&mut self.buffer) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+        self.close_tm();
+    }
+
+    /// Writes the pending telemetry to the TM data link.
+    fn resume_tm(&mut self) {
+        let Some((stream, tx_buf)) = &mut self.tm_link else {
+            return;
+        };
+        if tx_buf.flush(stream).is_err() {
+            self.close_tm();
+        }
+    }
+
+    /// Closes the TM data link connection.
+    fn close_tm(&mut self) {
+        if let Some((mut stream, _)) = self.tm_link.take() {
+            if let Some(registry) = &self.registry {
+                let _ = registry.deregister(&mut stream);
+            }
+            self.events.push_back(YamcsEvent::Link(YamcsLinkStatus {
+                link: YamcsLink::Tm,
+                connected: false,
+            }));
+        }
+    }
+}
+
+impl IoPort<TcpListener, YamcsEvent, Bytes> for YamcsBridgeInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        let tm_address = resolve(&self.tm_address)?;
+        let tc_address = resolve(&self.tc_address)?;
+        match self.transport {
+            YamcsTransport::Tcp => {
+                let mut tm_listener = TcpListener::bind(tm_address)?;
+                registry.register(&mut tm_listener, TM, Interest::READABLE)?;
+                self.tm_listener = Some(tm_listener);
+                let mut tc_listener = TcpListener::bind(tc_address)?;
+                registry.register(&mut tc_listener, TC, Interest::READABLE)?;
+                self.tc_listener = Some(tc_listener);
+            }
+            YamcsTransport::Udp => {
+                let local: SocketAddr = if tm_address.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    ([0u16; 8], 0).into()
+                };
+                self.tm_socket = Some((UdpSocket::bind(local)?, tm_address));
+                let mut tc_socket = UdpSocket::bind(tc_address)?;
+                registry.register(&mut tc_socket, TC, Interest::READABLE)?;
+                self.tc_socket = Some(tc_socket);
+            }
+        }
+        self.registry = Some(registry.try_clone()?);
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<YamcsEvent> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            match (token, self.transport) {
+                (TM, _) => self.accept_tm()?,
+                (TC, YamcsTransport::Tcp) => self.accept_tc()?,
+                (TC, YamcsTransport::Udp) => self.receive_tc_datagrams()?,
+                (TM_LINK, _) => self.drain_tm(),
+                (token, _) => {
+                    if let Some(index) = token.0.checked_sub(FIRST_TC_LINK) {
+                        self.receive_tc(index);
+                    }
+                }
+            }
+            if self.events.is_empty() {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+        }
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        if token == TM_LINK {
+            self.resume_tm();
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, packet: &Bytes) -> IoResult<()> {
+        if let Some((socket, address)) = &self.tm_socket {
+            return match socket.send_to(packet, *address) {
+                Ok(_) => Ok(()),
+                // Packets to an unreachable or busy data link are lost.
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::ConnectionRefused | ErrorKind::WouldBlock
+                    ) =>
+                {
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            };
+        }
+        // Packets are discarded while the TM data link is not connected.
+        let Some((stream, tx_buf)) = &mut self.tm_link else {
+            return Ok(());
+        };
+        if tx_buf.write(stream, packet).is_err() {
+            self.close_tm();
+        }
+
+        Ok(())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        // Events queued outside of a read, e.g. a TM data link closed on
+        // write, are forwarded immediately.
+        (!self.events.is_empty()).then(Instant::now)
+    }
+
+    fn timeout(&mut self) -> IoResult<YamcsEvent> {
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        self.tm_link
+            .as_ref()
+            .is_some_and(|(_, tx_buf)| !tx_buf.is_empty())
+    }
+}
+
+/// Resolves an address.
+fn resolve(address: &str) -> IoResult<SocketAddr> {
+    address.to_socket_addrs()?.next().ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Cannot resolve address {address}."),
+        )
+    })
+}
+
+/// Accepts a pending connection, if any.
+fn accept(listener: Option<&TcpListener>) -> IoResult<Option<TcpStream>> {
+    let Some(listener) = listener else {
+        return Ok(None);
+    };
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => return Ok(Some(stream)),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(None),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::Interrupted | ErrorKind::ConnectionAborted
+                ) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Returns the length of the first space packet of a buffer, if complete.
+fn packet_len(buffer: &[u8]) -> Option<usize> {
+    let header = buffer.get(..PRIMARY_HEADER_LEN)?;
+    let len = PRIMARY_HEADER_LEN + u16::from_be_bytes([header[4], header[5]]) as usize + 1;
+
+    (buffer.len() >= len).then_some(len)
+}
+
+/// YAMCS bridge model.
+///
+/// This model:
+/// * sends the telemetry packets to the YAMCS TM data link,
+/// * forwards the telecommand packets of the YAMCS TC data link,
+/// * reports the connections and disconnections of the TCP data links,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct YamcsBridge {
+    /// Telecommand packet -- output port.
+    pub tc_out: Output<Bytes>,
+
+    /// Connection status of the TCP data links -- output port.
+    pub link_out: Output<YamcsLinkStatus>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: YamcsBridgeConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<YamcsEvent, Bytes>,
+}
+
+impl YamcsBridge {
+    /// Telemetry packet -- input port.
+    pub async fn tm_in(&mut self, packet: Bytes) {
+        let _ = self.io_thread.send(packet);
+    }
+
+    /// Forwards the telecommands, the data link status and the I/O thread
+    /// status -- input port.
+    pub async fn process(&mut self) {
+        for event in self.io_thread.try_recv_all() {
+            match event {
+                YamcsEvent::Tc(packet) => self.tc_out.send(packet).await,
+                YamcsEvent::Link(status) => self.link_out.send(status).await,
+            }
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for YamcsBridge {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for YamcsBridge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("YamcsBridge")
+            .field("transport", &self.config.transport)
+            .field("tm_address", &self.config.tm_address)
+            .field("tc_address", &self.config.tc_address)
+            .finish_non_exhaustive()
+    }
+}
+
+/// YAMCS bridge model prototype.
+pub struct ProtoYamcsBridge {
+    /// Telecommand packet -- output port.
+    pub tc_out: Output<Bytes>,
+
+    /// Connection status of the TCP data links -- output port.
+    pub link_out: Output<YamcsLinkStatus>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// YAMCS bridge model instance configuration.
+    config: YamcsBridgeConfig,
+}
+
+impl ProtoYamcsBridge {
+    /// Creates a new YAMCS bridge model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the data link addresses cannot be bound
+    /// or if the I/O thread cannot be created.
+    pub fn new(config: YamcsBridgeConfig) -> Self {
+        Self {
+            tc_out: Output::new(),
+            link_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoYamcsBridge {
+    type Model = YamcsBridge;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            ..Default::default()
+        };
+        let io_thread = IoThread::try_with_options(YamcsBridgeInner::new(&self.config), options)
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Failed to start the I/O thread of the YAMCS bridge on {} and {}: {e}.",
+                    self.config.tm_address, self.config.tc_address
+                )
+            });
+
+        YamcsBridge {
+            tc_out: self.tc_out,
+            link_out: self.link_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoYamcsBridge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoYamcsBridge")
+            .field("transport", &self.config.transport)
+            .field("tm_address", &self.config.tm_address)
+            .field("tc_address", &self.config.tc_address)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,496 @@
+//! ZeroMQ port model.
+//!
+//! This module contains the [`ZeroMqPort`] model, which exchanges
+//! [ZeroMQ](https://zeromq.org) messages with lab tools and test scripts. It
+//! is available on Unix with the `zeromq` feature.
+//!
+//! The model uses a single ZeroMQ socket, which either binds to or connects to
+//! its `endpoint`, of one of the following types:
+//! * `pub`: the messages sent to the model are published, and no message is
+//!   received,
+//! * `sub`: the messages published with a topic matching one of the
+//!   `subscriptions` prefixes are received, and the messages sent to the model
+//!   are discarded,
+//! * `req`: the messages sent to the model are sent as requests, one at a
+//!   time, and the replies are received; a request which is not answered
+//!   within `reply_timeout` is abandoned, and its reply is discarded if it is
+//!   received later,
+//! * `rep`: the requests are received and the messages sent to the model are
+//!   sent as replies; a reply sent while no request is pending is discarded.
+//!
+//! Messages are multipart messages, represented by the list of their frames.
+//!
+//! #### Examples
+//!
+//! ```
+//! use schematic::{ConfigLoader, Format};
+//!
+//! use nexosim_net_port::zeromq::{ZeroMqConfig, ZeroMqSocketType};
+//!
+//! let config = ConfigLoader::<ZeroMqConfig>::new()
+//!     .code(
+//!         r#"
+//! socketType = "sub"
+//! endpoint = "tcp://127.0.0.1:5556"
+//! subscriptions = ["telemetry"]
+//! period = 10
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.socket_type, ZeroMqSocketType::Sub);
+//! assert!(!config.bind);
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{ErrorKind, Result as IoResult};
+use std::os::fd::AsRawFd;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use schematic::{Config, ConfigEnum};
+
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, IoThreadStatus};
+
+/// ZeroMQ port model instance configuration.
+#[derive(Config, Debug)]
+pub struct ZeroMqConfig {
+    /// Socket type.
+    pub socket_type: ZeroMqSocketType,
+
+    /// ZeroMQ endpoint, e.g. `tcp://127.0.0.1:5555` or `ipc:///tmp/bench`.
+    pub endpoint: String,
+
+    /// Bind to the endpoint rather than connect to it.
+    #[setting(default = false)]
+    pub bind: bool,
+
+    /// Topic prefixes subscribed to by a `sub` socket.
+    ///
+    /// If no prefix is provided, all messages are received. This setting is
+    /// ignored for other socket types.
+    pub subscriptions: Vec<String>,
+
+    /// Time after which a request of a `req` socket is abandoned if it has
+    /// not been answered.
+    ///
+    /// If no value is provided, the next request is only sent once the reply
+    /// is received. This setting is ignored for other socket types.
+    pub reply_timeout: Option<ConfigDuration>,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which received messages are forwarded into the simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+
+    /// Maximum time spent sending the pending messages when the model is
+    /// dropped.
+    ///
+    /// If no value is provided, the messages not yet sent are discarded.
+    pub shutdown_timeout: Option<ConfigDuration>,
+}
+
+/// ZeroMQ socket type.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ZeroMqSocketType {
+    /// Publisher.
+    Pub,
+
+    /// Subscriber.
+    #[default]
+    Sub,
+
+    /// Requester.
+    Req,
+
+    /// Replier.
+    Rep,
+}
+
+impl ZeroMqSocketType {
+    /// Returns the ZeroMQ socket type.
+    fn kind(self) -> zmq::SocketType {
+        match self {
+            Self::Pub => zmq::PUB,
+            Self::Sub => zmq::SUB,
+            Self::Req => zmq::REQ,
+            Self::Rep => zmq::REP,
+        }
+    }
+}
+
+/// Socket token.
+const SOCKET: Token = Token(0);
+
+/// I/O thread waker token.
+const WAKE: Token = Token(1);
+
+/// ZeroMQ port.
+///
+/// ZeroMQ only signals its file descriptor on edges which may have been
+/// consumed by a previous socket operation, so the socket events are checked
+/// again before each poll, see [`IoPort::deadline`].
+struct ZeroMqInner {
+    /// Socket.
+    socket: zmq::Socket,
+
+    /// Socket type.
+    socket_type: ZeroMqSocketType,
+
+    /// Messages not yet sent.
+    outbox: VecDeque<Vec<Bytes>>,
+
+    /// Reply timeout, for a `req` socket.
+    reply_timeout: Option<Duration>,
+
+    /// A request has been sent and its reply is awaited.
+    is_awaiting_reply: bool,
+
+    /// Time at which the awaited reply is abandoned, if any.
+    reply_deadline: Option<Instant>,
+}
+
+impl ZeroMqInner {
+    /// Creates a ZeroMQ port, bound or connected to its endpoint.
+    fn new(config: &ZeroMqConfig) -> IoResult<Self> {
+        let context = zmq::Context::new();
+        let socket = context.socket(config.socket_type.kind())?;
+        // The context is terminated when the socket is closed, which would
+        // block until the messages queued by ZeroMQ are sent.
+        socket.set_linger(config.shutdown_timeout.map_or(0, |timeout| {
+            timeout.as_millis().try_into().unwrap_or(i32::MAX)
+        }))?;
+        match config.socket_type {
+            ZeroMqSocketType::Sub if config.subscriptions.is_empty() => {
+                socket.set_subscribe(b"")?;
+            }
+            ZeroMqSocketType::Sub => {
+                for topic in &config.subscriptions {
+                    socket.set_subscribe(topic.as_bytes())?;
+                }
+            }
+            // Allow abandoning requests, and discard their late replies.
+            ZeroMqSocketType::Req => {
+                socket.set_req_relaxed(true)?;
+                socket.set_req_correlate(true)?;
+            }
+            _ => {}
+        }
+        if config.bind {
+            socket.bind(&config.endpoint)?;
+        } else {
+            socket.connect(&config.endpoint)?;
+        }
+
+        Ok(Self {
+            socket,
+            socket_type: config.socket_type,
+            outbox: VecDeque::new(),
+            reply_timeout: config
+                .reply_timeout
+                .filter(|_| config.socket_type == ZeroMqSocketType::Req)
+                .map(Duration::from),
+            is_awaiting_reply: false,
+            reply_deadline: None,
+        })
+    }
+
+    /// Returns whether messages can be received.
+    fn can_receive(&self) -> bool {
+        self.socket_type != ZeroMqSocketType::Pub
+    }
+
+    /// Returns whether the next message can be sent.
+    fn can_send(&self) -> bool {
+        !self.is_awaiting_reply
+    }
+
+    /// Sends the pending messages until the socket would block.
+    fn flush(&mut self) -> IoResult<()> {
+        while self.can_send() {
+            let Some(frames) = self.outbox.front() else {
+                break;
+            };
+            match self
+                .socket
+                .send_multipart(frames.iter().map(|frame| &frame[..]), zmq::DONTWAIT)
+            {
+                Ok(()) => {}
+                Err(zmq::Error::EAGAIN) => break,
+                // A reply sent while no request is pending.
+                Err(zmq::Error::EFSM) if self.socket_type == ZeroMqSocketType::Rep => {}
+                Err(e) => return Err(e.into()),
+            }
+            self.outbox.pop_front();
+            if self.socket_type == ZeroMqSocketType::Req {
+                self.is_awaiting_reply = true;
+                self.reply_deadline = self.reply_timeout.map(|timeout| Instant::now() + timeout);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Receives the next message.
+    fn receive(&mut self) -> IoResult<Vec<Bytes>> {
+        if !self.can_receive() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        match self.socket.recv_multipart(zmq::DONTWAIT) {
+            Ok(frames) => {
+                if self.socket_type == ZeroMqSocketType::Req {
+                    self.is_awaiting_reply = false;
+                    self.reply_deadline = None;
+                    self.flush()?;
+                }
+
+                Ok(frames.into_iter().map(Bytes::from).collect())
+            }
+            // A `rep` socket cannot receive the next request until the reply
+            // is sent.
+            Err(zmq::Error::EAGAIN | zmq::Error::EFSM) => Err(ErrorKind::WouldBlock.into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl IoPort<SourceFd<'static>, Vec<Bytes>, Vec<Bytes>> for ZeroMqInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        // The descriptor is always writable, so that writable events are
+        // reported together with the readable ones.
+        registry.register(
+            &mut SourceFd(&self.socket.as_raw_fd()),
+            SOCKET,
+            Interest::READABLE | Interest::WRITABLE,
+        )?;
+
+        Ok(WAKE)
+    }
+
+    fn read(&mut self, _: Token) -> IoResult<Vec<Bytes>> {
+        self.receive()
+    }
+
+    fn writable(&mut self, _: Token) -> IoResult<()> {
+        self.flush()
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        // Socket errors are reported by the next timeout.
+        let Ok(events) = self.socket.get_events() else {
+            return Some(Instant::now());
+        };
+        let is_ready = (self.can_receive() && events.contains(zmq::POLLIN))
+            || (self.can_send() && !self.outbox.is_empty() && events.contains(zmq::POLLOUT));
+        if is_ready {
+            return Some(Instant::now());
+        }
+
+        self.reply_deadline
+    }
+
+    fn timeout(&mut self) -> IoResult<Vec<Bytes>> {
+        self.socket.get_events()?;
+        if self
+            .reply_deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            self.is_awaiting_reply = false;
+            self.reply_deadline = None;
+        }
+        self.flush()?;
+
+        self.receive()
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        !self.outbox.is_empty()
+    }
+
+    fn write(&mut self, frames: &Vec<Bytes>) -> IoResult<()> {
+        // Messages sent to a `sub` socket are discarded.
+        if self.socket_type != ZeroMqSocketType::Sub {
+            self.outbox.push_back(frames.clone());
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// ZeroMQ port model.
+///
+/// This model:
+/// * forwards the messages received by its socket to the model output,
+/// * sends the messages from the model input with its socket,
+/// * reports the stalls, the errors and the exit of its I/O thread.
+pub struct ZeroMqPort {
+    /// Received messages -- output port.
+    pub frames_out: Output<Vec<Bytes>>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Model instance configuration.
+    config: ZeroMqConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<Vec<Bytes>, Vec<Bytes>>,
+}
+
+impl ZeroMqPort {
+    /// Sends a message -- input port.
+    pub async fn frames_in(&mut self, frames: Vec<Bytes>) {
+        let _ = self.io_thread.send(frames);
+    }
+
+    /// Forwards the received messages and the I/O thread status -- input
+    /// port.
+    pub async fn process(&mut self) {
+        for frames in self.io_thread.try_recv_all() {
+            self.frames_out.send(frames).await;
+        }
+        self.io_thread
+            .forward_status(
+                self.config.watchdog_timeout.map(Duration::from),
+                &mut self.io_status_out,
+                &mut self.stalled_out,
+            )
+            .await;
+    }
+}
+
+impl Model for ZeroMqPort {
+    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        if let Some(period) = self.config.period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for ZeroMqPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ZeroMqPort")
+            .field("socket_type", &self.config.socket_type)
+            .field("endpoint", &self.config.endpoint)
+            .finish_non_exhaustive()
+    }
+}
+
+/// ZeroMQ port model prototype.
+pub struct ProtoZeroMqPort {
+    /// Received messages -- output port.
+    pub frames_out: Output<Vec<Bytes>>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// ZeroMQ port model instance configuration.
+    config: ZeroMqConfig,
+}
+
+impl ProtoZeroMqPort {
+    /// Creates a new ZeroMQ port model prototype.
+    ///
+    /// # Panics
+    ///
+    /// Building the model panics if the socket cannot be bound or connected
+    /// to its endpoint, or if the I/O thread cannot be created.
+    pub fn new(config: ZeroMqConfig) -> Self {
+        Self {
+            frames_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoZeroMqPort {
+    type Model = ZeroMqPort;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let port = ZeroMqInner::new(&self.config).unwrap_or_else(|e| {
+            panic!(
+                "Failed to open the ZeroMQ socket on {}: {e}.",
+                self.config.endpoint
+            )
+        });
+        let options = IoThreadOptions {
+            heartbeat_period: self.config.watchdog_timeout.map(|timeout| *timeout / 2),
+            shutdown_timeout: self.config.shutdown_timeout.map(Duration::from),
+        };
+        let io_thread = IoThread::try_with_options(port, options).unwrap_or_else(|e| {
+            panic!(
+                "Failed to start the I/O thread of the ZeroMQ socket on {}: {e}.",
+                self.config.endpoint
+            )
+        });
+
+        ZeroMqPort {
+            frames_out: self.frames_out,
+            stalled_out: self.stalled_out,
+            io_status_out: self.io_status_out,
+            config: self.config,
+            io_thread,
+        }
+    }
+}
+
+impl fmt::Debug for ProtoZeroMqPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoZeroMqPort")
+            .field("socket_type", &self.config.socket_type)
+            .field("endpoint", &self.config.endpoint)
+            .finish_non_exhaustive()
+    }
+}
@@ -1,10 +1,12 @@
 //! Example: a simulation that receives data from a serial port.
 //!
-//! Before running an example, execute `serial-setup.sh` in another shell.
+//! The serial link is emulated with a pseudoterminal pair, so this example
+//! does not require any serial port setup, but only runs on Unix platforms.
 //!
 //! This example demonstrates in particular:
 //!
 //! * serial port model,
+//! * pseudoterminal pair,
 //! * infinite simulation,
 //! * blocking event queue,
 //! * simulation halting,
@@ -21,12 +23,11 @@
 //! └╌╌╌╌╌╌╌╌╌╌╌╌┘ [serial port] ┃   └──────────┘      ┃
 //!                              ┗━━━━━━━━━━━━━━━━━━━━━┛
 //! ```
+#![cfg_attr(not(unix), allow(dead_code, unused_imports))]
 
 use std::thread::{self, sleep};
 use std::time::Duration;
 
-use schematic::{ConfigLoader, Format};
-
 use nexosim::model::{Context, Model};
 use nexosim::ports::{EventQueue, Output};
 use nexosim::simulation::{ExecutionError, Mailbox, SimInit, SimulationError};
@@ -35,15 +36,10 @@ use nexosim_util::joiners::{SimulationJoiner, ThreadJoiner};
 use nexosim_util::observables::ObservableValue;
 
 use nexosim_byte_utils::decode::{ByteDelimitedDecoder, ByteStreamDecoder};
+#[cfg(unix)]
+use nexosim_serial_port::PtyPair;
 use nexosim_serial_port::{ProtoSerialPort, SerialPort, SerialPortConfig};
 
-/// For serial ports setup see `serial-setup.sh`.
-///
-/// Simulation serial port.
-const INTERNAL_PORT_PATH: &str = "/tmp/ttyS20";
-/// Serial port used to send data.
-const EXTERNAL_PORT_PATH: &str = "/tmp/ttyS21";
-
 /// Activation period, in milliseconds, for cyclic activities inside the simulation.
 const PERIOD: u64 = 10;
 /// Time shift, in milliseconds, for scheduling events at the present moment.
@@ -126,6 +122,12 @@ impl Counter {
 
 impl Model for Counter {}
 
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("This example requires pseudoterminals, which are only available on Unix platforms.");
+}
+
+#[cfg(unix)]
 fn main() -> Result<(), SimulationError> {
     // ---------------
     // Bench assembly.
@@ -133,8 +135,10 @@ fn main() -> Result<(), SimulationError> {
 
     // Models.
 
-    // The serial port model.
-    let mut serial = ProtoSerialPort::new(get_serial_port_cfg(INTERNAL_PORT_PATH));
+    // The serial port model, using the bench side of a pseudoterminal pair.
+    let pty = PtyPair::open().unwrap();
+    let external_port_path = pty.peer_path().to_owned();
+    let mut serial = ProtoSerialPort::new(get_serial_port_cfg()).with_pty(pty);
 
     // The decoder model.
     //
@@ -211,7 +215,7 @@ fn main() -> Result<(), SimulationError> {
         }
     }
 
-    let mut receiver_port = serialport::new(EXTERNAL_PORT_PATH, 0).open().unwrap();
+    let mut receiver_port = serialport::new(external_port_path, 0).open().unwrap();
     let mut sender_port = receiver_port.try_clone().unwrap();
 
     // Thread receiving data from the serial port.
@@ -278,16 +282,10 @@ fn main() -> Result<(), SimulationError> {
 }
 
 /// Gets serial port configuration.
-fn get_serial_port_cfg(path: &str) -> SerialPortConfig {
-    let mut loader = ConfigLoader::<SerialPortConfig>::new();
-    loader
-        .code(format!("portPath = \"{}\"", path), Format::Toml)
-        .unwrap();
-    loader
-        .code(format!("delta = {}", DELTA), Format::Toml)
-        .unwrap();
-    loader
-        .code(format!("period = {}", PERIOD), Format::Toml)
-        .unwrap();
-    loader.load().unwrap().config
+fn get_serial_port_cfg() -> SerialPortConfig {
+    SerialPortConfig::builder()
+        .delta_ms(DELTA)
+        .period_ms(PERIOD)
+        .build()
+        .unwrap()
 }
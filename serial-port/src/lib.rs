@@ -5,116 +5,1840 @@
 //!   simulation,
 //! * outputs data from the simulation to the specified serial port.
 //!
+//! Received data can also be forwarded with its wall-clock receive timestamp,
+//! captured by the I/O thread when the data is read, so that latency analysis
+//! does not depend on the polling period of the model. For hardware-in-the-loop
+//! setups, the received data can also be injected at the simulation time
+//! matching its arrival by enabling `time_alignment` in the configuration, see
+//! the [`alignment`](nexosim_io_utils::alignment) module.
+//!
+//! For protocols delimiting their frames with idle gaps, such as Modbus RTU,
+//! received data can be coalesced into frames by setting `frame_gap` in the
+//! configuration.
+//!
+//! For line-oriented devices, the complete received lines can also be
+//! forwarded on a dedicated output if `line_terminator` is set in the
+//! configuration.
+//!
+//! Break conditions can be sent on the serial port and, on Unix platforms,
+//! received break conditions can be reported if `detect_breaks` is set in the
+//! configuration.
+//!
+//! Disconnections of the serial port, e.g. an unplugged USB adapter, are
+//! reported on a status output; the serial port can then be reopened
+//! periodically if `reconnect_delay` is set in the configuration.
+//!
+//! Several serial ports can be handled by a single model and I/O thread with
+//! the [`MultiSerialPort`](multi::MultiSerialPort) model of the [`multi`]
+//! module.
+//!
+//! RS-485 transceivers can be driven through the RTS line to share a
+//! half-duplex multi-drop bus, see [`Rs485Config`].
+//!
+//! Serial ports of remote terminal servers can be accessed over the network
+//! with the RFC 2217 protocol, see the [`rfc2217`] module.
+//!
+//! Half-duplex links, such as single-wire UARTs or shared RS-485 segments, can
+//! be emulated by reporting the collisions between transmissions and
+//! receptions, see [`HalfDuplexConfig`].
+//!
+//! USB serial adapters can be selected by vendor ID, product ID and serial
+//! number rather than by device path, see the [`usb`] module.
+//!
+//! On Unix platforms, a serial link can be emulated without external tools
+//! with a [`PtyPair`], whose peer side can be opened by an external process or
+//! a test. Outgoing data can then be paced at a realistic bitrate by setting
+//! `line_rate` in the configuration.
+//!
+//! A serial port can be shared by several simulation processes by opening it
+//! in a [`SharedPortBroker`] created with [`shared_port_broker`] and by setting
+//! the `broker_path` configuration of each model to the broker socket path.
+//!
+//! The model runs on Unix platforms and on Windows, where the serial ports are
+//! named e.g. `COM3`. Break detection, reconnection, pseudoterminal pairs and
+//! shared port brokers are only available on Unix platforms.
+//!
+//! With the `tracing` feature, the events of each model instance are recorded
+//! within a `serial_port` span carrying the model name, with fields such as
+//! `direction`, `length` and `data`, so that traces can be filtered and correlated per
+//! link. Payloads are recorded as compact hexadecimal dumps, see the
+//! [`hexdump`](nexosim_io_utils::hexdump) module.
+//!
 //! [NX]: https://github.com/asynchronics/nexosim
 #![warn(missing_docs, missing_debug_implementations, unreachable_pub)]
 #![forbid(unsafe_code)]
 
-use std::fmt;
-use std::io::{ErrorKind, Read, Result as IoResult, Write};
-use std::time::Duration;
+pub mod multi;
+pub mod rfc2217;
+pub mod usb;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{ErrorKind, Read, Result as IoResult, Write};
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+#[cfg(unix)]
+use std::path::Path;
+#[cfg(unix)]
+use std::sync::Arc;
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use bytes::{Bytes, BytesMut};
+
+use schematic::{Config, ConfigError, ValidateError, ValidateResult};
+
+#[cfg(unix)]
+use mio::net::UnixStream;
+#[cfg(unix)]
+use mio::unix::pipe;
+use mio::{Interest, Registry, Token};
+use mio_serial::{SerialPort as _, SerialPortBuilderExt, SerialStream};
+
+#[cfg(unix)]
+use nix::sys::termios::{self, InputFlags, SetArg};
+
+#[cfg(feature = "tracing")]
+use tracing::{Span, info, info_span, warn};
+
+use nexosim::model::{Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::alignment::{PartialTimeAlignmentConfig, TimeAlignment, TimeAlignmentConfig};
+#[cfg(unix)]
+use nexosim_io_utils::broker::{BrokerClient, BrokerCodec, SharedPortBroker};
+use nexosim_io_utils::config;
+use nexosim_io_utils::duration::ConfigDuration;
+#[cfg(feature = "tracing")]
+use nexosim_io_utils::hexdump::HexDump;
+use nexosim_io_utils::link::{LinkMonitor, LinkStatus};
+use nexosim_io_utils::metrics::PortMetrics;
+use nexosim_io_utils::port::{
+    IoPort, IoThread, IoThreadOptions, IoThreadStatus, StallChange, WriteBuffer,
+};
+
+use rfc2217::{PartialRfc2217Config, Rfc2217Config, Rfc2217Port};
+use usb::{PartialUsbPortConfig, UsbPortConfig};
+
+/// Highest baud rate or line rate accepted in the configuration, in bits per
+/// second.
+pub const MAX_BAUD_RATE: u32 = 20_000_000;
+
+/// Serial port model instance configuration.
+///
+/// The configuration is validated when loaded: the baud rate and the line
+/// rate should not exceed [`MAX_BAUD_RATE`], the buffer size, the line rate,
+/// periods and timeouts should not be zero, and `delta` should not exceed
+/// `period`.
+#[derive(Config, Debug)]
+pub struct SerialPortConfig {
+    /// Baud rate.
+    ///
+    /// Zero value shall be used for software TTY interfaces.
+    #[setting(default = 0, validate = validate_baud_rate)]
+    pub baud_rate: u32,
+
+    /// Serial port path, e.g. `/dev/ttyUSB0` on Unix platforms or `COM3` on
+    /// Windows.
+    pub port_path: String,
+
+    /// USB serial port selection.
+    ///
+    /// If any field is set, the USB serial port matching the selection is
+    /// opened and `port_path` is ignored, see the [`usb`] module.
+    #[setting(nested)]
+    pub usb: UsbPortConfig,
+
+    /// RFC 2217 serial server.
+    ///
+    /// If an address is provided, the serial port of the server is used and
+    /// `port_path` is ignored, see the [`rfc2217`] module.
+    #[setting(nested)]
+    pub rfc2217: Rfc2217Config,
+
+    /// Open the serial port with exclusive access.
+    ///
+    /// An exclusive lock is taken on the device, so that opening a serial port
+    /// already opened by another process fails immediately, and other
+    /// processes cannot open it while the model runs. Otherwise, the device
+    /// can be opened by other processes not requesting exclusive access.
+    ///
+    /// Serial ports are always opened with exclusive access on Windows.
+    #[setting(default = true)]
+    pub exclusive: bool,
+
+    /// Emulated line rate, in bits per second.
+    ///
+    /// If a value is provided, outgoing data is paced in real time as if it
+    /// was transmitted at this line rate with 10 bits per character, which is
+    /// mostly useful for pseudoterminals and virtual ports on which the baud
+    /// rate has no effect. Data is written by blocks of about 1 ms of
+    /// transmission, each block being written once its transmission would be
+    /// complete on the emulated line.
+    #[setting(validate = validate_line_rate)]
+    pub line_rate: Option<u32>,
+
+    /// Internal buffer size.
+    ///
+    /// Input is read and forwarded to the simulation by blocks up to buffer
+    /// size, unless `frame_gap` is set.
+    #[setting(default = 256, validate = validate_non_zero)]
+    pub buffer_size: usize,
+
+    /// Minimum idle time delimiting the received frames, in microseconds.
+    ///
+    /// If a value is provided, received data is coalesced and forwarded as a
+    /// single chunk once no data has been received for this time, which is how
+    /// e.g. Modbus RTU delimits its frames with a gap of 3.5 character times,
+    /// about 4010 µs at 9600 baud. The end of each frame is detected with a
+    /// timer of the I/O thread, so data can be sent to the serial port during
+    /// the reception of a frame.
+    pub frame_gap: Option<u64>,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    #[setting(validate = validate_delta)]
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which data from the serial port is forwarded into the
+    /// simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    #[setting(validate = validate_non_zero)]
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    #[setting(validate = validate_non_zero)]
+    pub watchdog_timeout: Option<ConfigDuration>,
+
+    /// Maximum time spent writing the pending data to the serial port when the
+    /// model is dropped.
+    ///
+    /// If no value is provided, the data not yet written is discarded.
+    pub shutdown_timeout: Option<ConfigDuration>,
+
+    /// Metrics report period.
+    ///
+    /// If a value is provided, the traffic metrics of the port are reported
+    /// on the metrics output with this period, starting after `delta`. If no
+    /// value is provided, metrics are only reported on request.
+    #[setting(validate = validate_non_zero)]
+    pub metrics_period: Option<ConfigDuration>,
+
+    /// Wall-clock time alignment of the received data.
+    ///
+    /// If enabled, the received data is injected at the simulation time
+    /// matching its wall-clock receive timestamp, offset by the alignment
+    /// delay, rather than when it is forwarded. Break conditions and status
+    /// changes are not delayed.
+    #[setting(nested)]
+    pub time_alignment: TimeAlignmentConfig,
+
+    /// Report received break conditions.
+    ///
+    /// Break conditions are only detected on Unix platforms, where the
+    /// terminal is then configured to mark them in the received data. Break
+    /// conditions are not forwarded through a shared port broker.
+    #[setting(default = false)]
+    pub detect_breaks: bool,
+
+    /// Initial delay before reopening a disconnected serial port.
+    ///
+    /// The delay doubles after each failed attempt, up to
+    /// `reconnect_max_delay`. Serial ports are only reopened on Unix
+    /// platforms. If no value is provided, a disconnected serial port is not
+    /// reopened.
+    pub reconnect_delay: Option<ConfigDuration>,
+
+    /// Maximum delay between the attempts to reopen a disconnected serial
+    /// port.
+    #[setting(default = ConfigDuration::from_millis(10000))]
+    pub reconnect_max_delay: ConfigDuration,
+
+    /// RS-485 direction control.
+    #[setting(nested)]
+    pub rs485: Rs485Config,
+
+    /// Half-duplex collision emulation.
+    #[setting(nested)]
+    pub half_duplex: HalfDuplexConfig,
+
+    /// Line terminator byte.
+    ///
+    /// If a value is provided, the complete lines received on the serial port
+    /// are forwarded to the line output without their terminator, in addition
+    /// to the raw bytes.
+    pub line_terminator: Option<u8>,
+
+    /// Strip the carriage return preceding the line terminator, if any.
+    #[setting(default = false)]
+    pub strip_cr: bool,
+
+    /// Maximum line length, in bytes.
+    ///
+    /// Longer lines are forwarded in parts of this length.
+    #[setting(default = 4096)]
+    pub max_line_length: usize,
+
+    /// Wait after each write until the data has been transmitted.
+    ///
+    /// The transmission of each write is then reported on the drain output.
+    /// The I/O thread is blocked during the transmission. Transmissions are not
+    /// reported through a shared port broker.
+    #[setting(default = false)]
+    pub drain_writes: bool,
+
+    /// Socket path of a shared port broker.
+    ///
+    /// If a value is provided, the serial port is accessed through the broker
+    /// listening on this socket and `port_path` is ignored.
+    pub broker_path: Option<String>,
+}
+
+impl SerialPortConfig {
+    /// Returns a builder of a serial port model instance configuration.
+    pub fn builder() -> SerialPortConfigBuilder {
+        SerialPortConfigBuilder::default()
+    }
+}
+
+/// Builder of a serial port model instance configuration.
+///
+/// Settings which are not set take their default values.
+///
+/// #### Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use nexosim_serial_port::SerialPortConfig;
+///
+/// let config = SerialPortConfig::builder()
+///     .port_path("/dev/ttyUSB0")
+///     .baud_rate(115_200)
+///     .period(Duration::from_micros(500))
+///     .line_terminator(b'\n')
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(config.buffer_size, 256);
+///
+/// // The configuration is validated.
+/// assert!(SerialPortConfig::builder().buffer_size(0).build().is_err());
+/// ```
+#[derive(Debug, Default)]
+pub struct SerialPortConfigBuilder {
+    /// Configuration built.
+    partial: PartialSerialPortConfig,
+}
+
+impl SerialPortConfigBuilder {
+    /// Sets the baud rate.
+    pub fn baud_rate(mut self, baud_rate: u32) -> Self {
+        self.partial.baud_rate = Some(baud_rate);
+        self
+    }
+
+    /// Sets the serial port path.
+    pub fn port_path(mut self, path: impl Into<String>) -> Self {
+        self.partial.port_path = Some(path.into());
+        self
+    }
+
+    /// Sets the USB serial port selection.
+    pub fn usb(mut self, usb: UsbPortConfig) -> Self {
+        self.partial.usb = Some(usb.into());
+        self
+    }
+
+    /// Sets the RFC 2217 serial server.
+    pub fn rfc2217(mut self, rfc2217: Rfc2217Config) -> Self {
+        self.partial.rfc2217 = Some(rfc2217.into());
+        self
+    }
+
+    /// Sets whether the serial port is opened with exclusive access.
+    pub fn exclusive(mut self, enabled: bool) -> Self {
+        self.partial.exclusive = Some(enabled);
+        self
+    }
+
+    /// Sets the emulated line rate, in bits per second.
+    pub fn line_rate(mut self, line_rate: u32) -> Self {
+        self.partial.line_rate = Some(line_rate);
+        self
+    }
+
+    /// Sets the internal buffer size.
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.partial.buffer_size = Some(size);
+        self
+    }
+
+    /// Sets the minimum idle time delimiting the received frames, in
+    /// microseconds.
+    pub fn frame_gap_us(mut self, gap: u64) -> Self {
+        self.partial.frame_gap = Some(gap);
+        self
+    }
+
+    /// Sets the delay for the first scheduled data forwarding.
+    pub fn delta(mut self, delta: Duration) -> Self {
+        self.partial.delta = Some(delta.into());
+        self
+    }
+
+    /// Sets the delay for the first scheduled data forwarding, in
+    /// milliseconds.
+    pub fn delta_ms(self, delta: u64) -> Self {
+        self.delta(Duration::from_millis(delta))
+    }
+
+    /// Sets the period at which data from the serial port is forwarded into
+    /// the simulation.
+    pub fn period(mut self, period: Duration) -> Self {
+        self.partial.period = Some(period.into());
+        self
+    }
+
+    /// Sets the period at which data from the serial port is forwarded into
+    /// the simulation, in milliseconds.
+    pub fn period_ms(self, period: u64) -> Self {
+        self.period(Duration::from_millis(period))
+    }
+
+    /// Sets the I/O thread watchdog timeout.
+    pub fn watchdog_timeout(mut self, timeout: Duration) -> Self {
+        self.partial.watchdog_timeout = Some(timeout.into());
+        self
+    }
+
+    /// Sets the I/O thread watchdog timeout, in milliseconds.
+    pub fn watchdog_timeout_ms(self, timeout: u64) -> Self {
+        self.watchdog_timeout(Duration::from_millis(timeout))
+    }
+
+    /// Sets the maximum time spent writing the pending data when the model is
+    /// dropped.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.partial.shutdown_timeout = Some(timeout.into());
+        self
+    }
+
+    /// Sets the maximum time spent writing the pending data when the model is
+    /// dropped, in milliseconds.
+    pub fn shutdown_timeout_ms(self, timeout: u64) -> Self {
+        self.shutdown_timeout(Duration::from_millis(timeout))
+    }
+
+    /// Sets the metrics report period.
+    pub fn metrics_period(mut self, period: Duration) -> Self {
+        self.partial.metrics_period = Some(period.into());
+        self
+    }
+
+    /// Sets the wall-clock time alignment of the received data.
+    pub fn time_alignment(mut self, time_alignment: TimeAlignmentConfig) -> Self {
+        self.partial.time_alignment = Some(time_alignment.into());
+        self
+    }
+
+    /// Sets whether received break conditions are reported.
+    pub fn detect_breaks(mut self, enabled: bool) -> Self {
+        self.partial.detect_breaks = Some(enabled);
+        self
+    }
+
+    /// Sets the initial delay before reopening a disconnected serial port.
+    pub fn reconnect_delay(mut self, delay: Duration) -> Self {
+        self.partial.reconnect_delay = Some(delay.into());
+        self
+    }
+
+    /// Sets the initial delay before reopening a disconnected serial port, in
+    /// milliseconds.
+    pub fn reconnect_delay_ms(self, delay: u64) -> Self {
+        self.reconnect_delay(Duration::from_millis(delay))
+    }
+
+    /// Sets the maximum delay between the attempts to reopen a disconnected
+    /// serial port.
+    pub fn reconnect_max_delay(mut self, delay: Duration) -> Self {
+        self.partial.reconnect_max_delay = Some(delay.into());
+        self
+    }
+
+    /// Sets the maximum delay between the attempts to reopen a disconnected
+    /// serial port, in milliseconds.
+    pub fn reconnect_max_delay_ms(self, delay: u64) -> Self {
+        self.reconnect_max_delay(Duration::from_millis(delay))
+    }
+
+    /// Sets the RS-485 direction control.
+    pub fn rs485(mut self, rs485: Rs485Config) -> Self {
+        self.partial.rs485 = Some(rs485.into());
+        self
+    }
+
+    /// Sets the half-duplex collision emulation.
+    pub fn half_duplex(mut self, half_duplex: HalfDuplexConfig) -> Self {
+        self.partial.half_duplex = Some(half_duplex.into());
+        self
+    }
+
+    /// Sets the line terminator byte.
+    pub fn line_terminator(mut self, terminator: u8) -> Self {
+        self.partial.line_terminator = Some(terminator);
+        self
+    }
+
+    /// Sets whether the carriage return preceding the line terminator is
+    /// stripped.
+    pub fn strip_cr(mut self, enabled: bool) -> Self {
+        self.partial.strip_cr = Some(enabled);
+        self
+    }
+
+    /// Sets the maximum line length, in bytes.
+    pub fn max_line_length(mut self, length: usize) -> Self {
+        self.partial.max_line_length = Some(length);
+        self
+    }
+
+    /// Sets whether each write waits until the data has been transmitted.
+    pub fn drain_writes(mut self, enabled: bool) -> Self {
+        self.partial.drain_writes = Some(enabled);
+        self
+    }
+
+    /// Sets the socket path of a shared port broker.
+    pub fn broker_path(mut self, path: impl Into<String>) -> Self {
+        self.partial.broker_path = Some(path.into());
+        self
+    }
+
+    /// Builds the configuration.
+    ///
+    /// An error is returned if the configuration is invalid.
+    pub fn build(self) -> Result<SerialPortConfig, ConfigError> {
+        config::build(self.partial)
+    }
+}
+
+/// Checks that the baud rate does not exceed the highest baud rate.
+fn validate_baud_rate<D, C>(baud_rate: &u32, _: &D, _: &C, _: bool) -> ValidateResult {
+    if *baud_rate > MAX_BAUD_RATE {
+        return Err(ValidateError::new(format!(
+            "baud rate {baud_rate} exceeds the highest baud rate {MAX_BAUD_RATE}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Checks that the line rate is not zero and does not exceed the highest
+/// baud rate.
+fn validate_line_rate<D, C>(line_rate: &u32, _: &D, _: &C, _: bool) -> ValidateResult {
+    if *line_rate == 0 || *line_rate > MAX_BAUD_RATE {
+        return Err(ValidateError::new(format!(
+            "line rate {line_rate} should be within 1 and {MAX_BAUD_RATE}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Checks that `delta` does not exceed `period`.
+fn validate_delta<C>(
+    delta: &ConfigDuration,
+    config: &PartialSerialPortConfig,
+    _: &C,
+    _: bool,
+) -> ValidateResult {
+    match config.period {
+        Some(period) if *delta > period => Err(ValidateError::new(format!(
+            "delta ({delta}) should not exceed period ({period})"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a setting is not zero.
+fn validate_non_zero<T, D, C>(value: &T, _: &D, _: &C, _: bool) -> ValidateResult
+where
+    T: Default + PartialEq,
+{
+    if *value == T::default() {
+        return Err(ValidateError::new("this setting should not be zero"));
+    }
+
+    Ok(())
+}
+
+/// RS-485 direction control configuration.
+///
+/// When enabled, the driver of the RS-485 transceiver is enabled with the RTS
+/// line during each transmission, so that the serial port can share a
+/// half-duplex multi-drop bus.
+#[derive(Config, Debug)]
+pub struct Rs485Config {
+    /// Enable RS-485 direction control.
+    #[setting(default = false)]
+    pub enabled: bool,
+
+    /// RTS line state while sending: asserted if `true`, deasserted
+    /// otherwise.
+    #[setting(default = true)]
+    pub rts_on_send: bool,
+
+    /// Delay between the driver enabling and the transmission, in
+    /// microseconds.
+    #[setting(default = 0)]
+    pub delay_before_send: u64,
+
+    /// Delay between the end of the transmission and the driver disabling, in
+    /// microseconds.
+    #[setting(default = 0)]
+    pub delay_after_send: u64,
+}
+
+impl From<Rs485Config> for PartialRs485Config {
+    fn from(config: Rs485Config) -> Self {
+        Self {
+            enabled: Some(config.enabled),
+            rts_on_send: Some(config.rts_on_send),
+            delay_before_send: Some(config.delay_before_send),
+            delay_after_send: Some(config.delay_after_send),
+        }
+    }
+}
+
+impl Rs485Config {
+    /// Returns the direction control settings, if enabled.
+    fn direction_control(&self) -> Option<Rs485Settings> {
+        self.enabled.then(|| Rs485Settings {
+            rts_on_send: self.rts_on_send,
+            delay_before_send: Duration::from_micros(self.delay_before_send),
+            delay_after_send: Duration::from_micros(self.delay_after_send),
+        })
+    }
+}
+
+/// Half-duplex collision emulation configuration.
+///
+/// When enabled, the serial port is considered as a half-duplex link, such as
+/// a single-wire UART or a shared RS-485 segment, on which data received while
+/// transmitting or transmitted while receiving is reported as a collision.
+/// Transmission and reception times are estimated from `line_rate` if set, or
+/// from `baud_rate` otherwise, with 10 bits per character, so that collisions
+/// are not detected if both are zero.
+#[derive(Config, Debug)]
+pub struct HalfDuplexConfig {
+    /// Enable collision detection.
+    #[setting(default = false)]
+    pub enabled: bool,
+
+    /// Corrupt the data received during a collision.
+    ///
+    /// The received data is combined with the transmitted data as on a
+    /// wired-AND line.
+    #[setting(default = false)]
+    pub corrupt: bool,
+}
+
+impl From<HalfDuplexConfig> for PartialHalfDuplexConfig {
+    fn from(config: HalfDuplexConfig) -> Self {
+        Self {
+            enabled: Some(config.enabled),
+            corrupt: Some(config.corrupt),
+        }
+    }
+}
+
+/// Serial port connection status.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SerialPortStatus {
+    /// The serial port was reopened after a disconnection.
+    Connected,
+
+    /// The serial port was disconnected, with the error description.
+    Disconnected(String),
+}
+
+/// Event read from the serial port.
+enum SerialEvent {
+    /// Received data, with its receive timestamp.
+    Data(Bytes, SystemTime),
+
+    /// Received break condition.
+    Break,
+
+    /// Connection status change.
+    Status(SerialPortStatus),
+}
+
+/// Transmission report of the serial port.
+enum SerialReport {
+    /// Data written to the serial port has been transmitted.
+    Drained,
+
+    /// Collision between a transmission and a reception.
+    Collision,
+}
+
+/// Command sent to the serial port.
+enum SerialCommand {
+    /// Data to be written.
+    Write(Bytes),
+
+    /// Break condition to be sent, with its duration.
+    Break(Duration),
+
+    /// Wait until the data previously written has been transmitted.
+    Drain,
+}
+
+/// Decoding state of the break marks in the received data.
+///
+/// When break detection is enabled, the terminal marks a break condition with
+/// the `FF 00 00` sequence and escapes data bytes `FF` as `FF FF`.
+#[derive(Clone, Copy, PartialEq)]
+enum MarkState {
+    /// No pending mark.
+    Idle,
+
+    /// `FF` received.
+    Escape,
+
+    /// `FF 00` received.
+    Mark,
+}
+
+/// RS-485 direction control settings.
+#[derive(Clone, Copy, Debug)]
+struct Rs485Settings {
+    /// RTS line state while sending.
+    rts_on_send: bool,
+
+    /// Delay between the driver enabling and the transmission.
+    delay_before_send: Duration,
+
+    /// Delay between the end of the transmission and the driver disabling.
+    delay_after_send: Duration,
+}
+
+/// Settings applied to a serial port when opened.
+#[derive(Clone, Debug)]
+struct PortSettings {
+    /// Serial port path.
+    port_path: String,
+
+    /// USB serial port selection, if enabled.
+    usb: Option<UsbPortConfig>,
+
+    /// Baud rate.
+    baud_rate: u32,
+
+    /// Exclusive access.
+    exclusive: bool,
+
+    /// Break conditions are marked in the received data.
+    detect_breaks: bool,
+
+    /// Emulated line rate, if enabled.
+    line_rate: Option<u32>,
+
+    /// Idle time delimiting the received frames, if enabled.
+    frame_gap: Option<Duration>,
+
+    /// RS-485 direction control, if enabled.
+    rs485: Option<Rs485Settings>,
+}
+
+impl PortSettings {
+    /// Opens the serial port.
+    fn open(&self) -> IoResult<SerialStream> {
+        let port_path = match &self.usb {
+            Some(usb) => usb.find()?,
+            None => self.port_path.clone(),
+        };
+        let builder = mio_serial::new(&port_path, self.baud_rate);
+        #[cfg(unix)]
+        let builder = builder.exclusive(self.exclusive);
+        let mut port = builder
+            .open_native_async()
+            .map_err(|e| match e.kind() {
+                // On Windows, this error kind is also used for missing devices.
+                mio_serial::ErrorKind::NoDevice if self.exclusive && cfg!(unix) => std::io::Error::new(
+                    ErrorKind::ResourceBusy,
+                    format!(
+                        "Serial port {} could not be opened with exclusive access, it may be used by another process: {}.",
+                        port_path, e
+                    ),
+                ),
+                _ => e.into(),
+            })?;
+        #[cfg(unix)]
+        if self.detect_breaks {
+            mark_breaks(&port)?;
+        }
+        // The driver is disabled until data is sent.
+        if let Some(rs485) = &self.rs485 {
+            port.write_request_to_send(!rs485.rts_on_send)?;
+        }
+
+        Ok(port)
+    }
+}
+
+/// Reconnection settings of a serial port.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(not(unix), allow(dead_code))]
+struct ReconnectSettings {
+    /// Delay before the first attempt to reopen the serial port.
+    delay: Duration,
+
+    /// Maximum delay between attempts.
+    max_delay: Duration,
+}
+
+impl ReconnectSettings {
+    /// Returns the reconnection settings of the configuration, if enabled.
+    fn new(delay: Option<ConfigDuration>, max_delay: ConfigDuration) -> Option<Self> {
+        delay.map(|delay| Self {
+            delay: *delay,
+            max_delay: *max_delay.max(delay),
+        })
+    }
+}
+
+/// Pending notification of a disconnection and, if enabled, reconnection of a
+/// serial port.
+///
+/// A byte is written to the notification pipe to make the I/O thread read the
+/// disconnection status and the reopened serial port.
+#[cfg(unix)]
+struct Reconnection {
+    /// Notification pipe.
+    notifier: pipe::Receiver,
+
+    /// Reopened serial port.
+    receiver: Receiver<SerialStream>,
+
+    /// Reconnection thread cancellation flag.
+    is_cancelled: Arc<AtomicBool>,
+}
+
+#[cfg(unix)]
+impl Reconnection {
+    /// Registers the notification pipe and spawns the reconnection thread, if
+    /// enabled.
+    fn spawn(
+        registry: &Registry,
+        token: Token,
+        port_settings: &PortSettings,
+        settings: Option<ReconnectSettings>,
+    ) -> IoResult<Self> {
+        let (mut sender, mut notifier) = pipe::new()?;
+        registry.register(&mut notifier, token, Interest::READABLE)?;
+        // Notifies the disconnection.
+        sender.write_all(&[0])?;
+
+        let (tx, receiver) = channel();
+        let is_cancelled = Arc::new(AtomicBool::new(false));
+        if let Some(settings) = settings {
+            let port_settings = port_settings.clone();
+            let thread_is_cancelled = is_cancelled.clone();
+            thread::spawn(move || {
+                let mut delay = settings.delay;
+                loop {
+                    thread::sleep(delay);
+                    if thread_is_cancelled.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    match port_settings.open() {
+                        Ok(port) => {
+                            if tx.send(port).is_ok() {
+                                let _ = sender.write_all(&[0]);
+                            }
+                            return;
+                        }
+                        Err(_e) => {
+                            delay = (delay * 2).min(settings.max_delay);
+                            #[cfg(feature = "tracing")]
+                            warn!(
+                                port = %port_settings.port_path,
+                                error = %_e,
+                                retry_delay = ?delay,
+                                "Failed to reopen the serial port."
+                            );
+                        }
+                    }
+                }
+            });
+        }
+
+        Ok(Self {
+            notifier,
+            receiver,
+            is_cancelled,
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Reconnection {
+    fn drop(&mut self) {
+        self.is_cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Waits until the data written to the serial port has been transmitted.
+fn drain(port: &SerialStream) -> IoResult<()> {
+    #[cfg(unix)]
+    termios::tcdrain(port.as_raw_fd())?;
+    #[cfg(not(unix))]
+    while port.bytes_to_write()? > 0 {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    Ok(())
+}
+
+struct SerialPortInner {
+    /// Serial port, unless disconnected.
+    port: Option<SerialStream>,
+    buffer: Vec<u8>,
+
+    /// Serial port settings.
+    settings: PortSettings,
+
+    /// Decoding state of the break marks.
+    mark_state: MarkState,
+
+    /// Data not yet written.
+    tx_buf: WriteBuffer,
+
+    /// The transmission is reported once the data not yet written has been
+    /// transmitted.
+    drain_pending: bool,
+
+    /// Transmit pacing, if enabled.
+    pacer: Option<TxPacer>,
+
+    /// Wait after each write until the data has been transmitted.
+    drain_writes: bool,
+
+    /// Transmission reports sender, if enabled.
+    reports: Option<Sender<SerialReport>>,
+
+    /// Half-duplex collision detection, if enabled.
+    half_duplex: Option<HalfDuplex>,
+
+    /// Decoded events not yet read.
+    events: VecDeque<SerialEvent>,
+
+    /// Frame being received, if frames are delimited by idle gaps.
+    frame: Option<Frame>,
+
+    /// Reconnection settings, if enabled.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    reconnect: Option<ReconnectSettings>,
+
+    /// MIO registry, available once the serial port is registered.
+    registry: Option<Registry>,
+
+    /// Serial port token.
+    token: Token,
+
+    /// Reconnection notification token.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    reconnect_token: Token,
+
+    /// Pending reconnection.
+    #[cfg(unix)]
+    reconnection: Option<Reconnection>,
+
+    /// Peer side of a pseudoterminal pair, kept open so that the serial port
+    /// is not hung up while no external process has opened it.
+    #[cfg(unix)]
+    _peer: Option<SerialStream>,
+}
+
+impl SerialPortInner {
+    fn new(
+        mut settings: PortSettings,
+        buffer_size: usize,
+        reconnect: Option<ReconnectSettings>,
+    ) -> Self {
+        settings.detect_breaks &= cfg!(unix);
+
+        // Until read_buf (RFC 2930) is stabilized we need an initialized
+        // buffer.
+        Self {
+            port: Some(settings.open().unwrap()),
+            buffer: vec![0; buffer_size],
+            pacer: settings.line_rate.map(TxPacer::new),
+            tx_buf: WriteBuffer::new(),
+            drain_pending: false,
+            drain_writes: false,
+            reports: None,
+            half_duplex: None,
+            settings,
+            mark_state: MarkState::Idle,
+            events: VecDeque::new(),
+            frame: None,
+            reconnect,
+            registry: None,
+            token: Token(0),
+            reconnect_token: Token(1),
+            #[cfg(unix)]
+            reconnection: None,
+            #[cfg(unix)]
+            _peer: None,
+        }
+    }
+
+    /// Uses the bench side of a pseudoterminal pair as the serial port.
+    #[cfg(unix)]
+    fn from_pty(pty: PtyPair, config: &SerialPortConfig) -> Self {
+        Self {
+            port: Some(pty.port),
+            buffer: vec![0; config.buffer_size],
+            settings: PortSettings {
+                port_path: pty.peer_path,
+                usb: None,
+                baud_rate: 0,
+                exclusive: false,
+                detect_breaks: false,
+                line_rate: config.line_rate,
+                frame_gap: config.frame_gap.map(Duration::from_micros),
+                rs485: None,
+            },
+            pacer: config.line_rate.map(TxPacer::new),
+            tx_buf: WriteBuffer::new(),
+            drain_pending: false,
+            drain_writes: false,
+            reports: None,
+            half_duplex: None,
+            mark_state: MarkState::Idle,
+            events: VecDeque::new(),
+            frame: None,
+            reconnect: None,
+            registry: None,
+            token: Token(0),
+            reconnect_token: Token(1),
+            reconnection: None,
+            _peer: Some(pty.peer),
+        }
+    }
+
+    /// Enables the transmission reports and the configured collision
+    /// detection, returning the reports receiver.
+    fn reports(&mut self, config: &SerialPortConfig) -> Receiver<SerialReport> {
+        let (sender, receiver) = channel();
+        self.reports = Some(sender);
+        self.drain_writes = config.drain_writes;
+        self.half_duplex = HalfDuplex::new(config);
+
+        receiver
+    }
+
+    /// Sends a transmission report, if enabled.
+    fn report(&self, report: SerialReport) {
+        if let Some(sender) = &self.reports {
+            let _ = sender.send(report);
+        }
+    }
+
+    /// Registers the serial port in MIO with its token and the token of the
+    /// reconnection notifications.
+    fn register_port(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        reconnect_token: Token,
+    ) -> IoResult<()> {
+        if let Some(port) = &mut self.port {
+            registry.register(port, token, Interest::READABLE)?;
+        }
+        self.token = token;
+        self.reconnect_token = reconnect_token;
+        self.registry = registry.try_clone().ok();
+
+        Ok(())
+    }
+
+    /// Closes the serial port after an error and starts its reconnection, if
+    /// enabled.
+    fn disconnect(&mut self, error: std::io::Error) {
+        #[cfg(feature = "tracing")]
+        warn!(
+            port = %self.settings.port_path,
+            error = %error,
+            "Serial port disconnected."
+        );
+        if let (Some(mut port), Some(registry)) = (self.port.take(), &self.registry) {
+            let _ = registry.deregister(&mut port);
+        }
+        self.mark_state = MarkState::Idle;
+        self.flush_frame();
+        self.tx_buf.clear();
+        if std::mem::take(&mut self.drain_pending) {
+            self.report(SerialReport::Drained);
+        }
+        self.events
+            .push_back(SerialEvent::Status(SerialPortStatus::Disconnected(
+                error.to_string(),
+            )));
+        #[cfg(unix)]
+        if let Some(registry) = &self.registry {
+            self.reconnection = Reconnection::spawn(
+                registry,
+                self.reconnect_token,
+                &self.settings,
+                self.reconnect,
+            )
+            .inspect_err(|_e| {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    port = %self.settings.port_path,
+                    error = %_e,
+                    "Failed to start the reconnection of the serial port."
+                );
+            })
+            .ok();
+        }
+    }
+
+    /// Takes the serial port reopened by the reconnection thread, if any.
+    #[cfg(unix)]
+    fn reconnect(&mut self) -> Option<SerialEvent> {
+        let reconnection = self.reconnection.as_mut()?;
+        let mut buf = [0; 16];
+        while matches!(reconnection.notifier.read(&mut buf), Ok(len) if len > 0) {}
+        let mut port = reconnection.receiver.try_recv().ok()?;
+        let mut reconnection = self.reconnection.take()?;
+        let registry = self.registry.as_ref()?;
+        let _ = registry.deregister(&mut reconnection.notifier);
+        if let Err(e) = registry.register(&mut port, self.token, Interest::READABLE) {
+            self.disconnect(e);
+            return self.events.pop_front();
+        }
+        #[cfg(feature = "tracing")]
+        info!(port = %self.settings.port_path, "Serial port reconnected.");
+        self.port = Some(port);
+
+        Some(SerialEvent::Status(SerialPortStatus::Connected))
+    }
+
+    /// Reads the next event from the serial port.
+    fn read_event(&mut self) -> IoResult<SerialEvent> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            let Some(port) = &mut self.port else {
+                #[cfg(unix)]
+                if let Some(event) = self.reconnect() {
+                    return Ok(event);
+                }
+                return Err(ErrorKind::WouldBlock.into());
+            };
+            let len = match port.read(&mut self.buffer) {
+                Ok(0) => {
+                    self.disconnect(ErrorKind::UnexpectedEof.into());
+                    continue;
+                }
+                Ok(len) => len,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    // The current frame is otherwise flushed at its deadline.
+                    if !self.frame_ended() {
+                        return Err(e);
+                    }
+                    self.flush_frame();
+                    continue;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => return Err(e),
+                Err(e) => {
+                    self.disconnect(e);
+                    continue;
+                }
+            };
+            let timestamp = SystemTime::now();
+            if let Some(half_duplex) = &mut self.half_duplex {
+                if half_duplex.receive(&mut self.buffer[..len]) {
+                    self.report(SerialReport::Collision);
+                }
+            }
+            if !self.settings.detect_breaks {
+                self.push_data(BytesMut::from(&self.buffer[..len]), timestamp);
+                continue;
+            }
+            let buffer = std::mem::take(&mut self.buffer);
+            self.decode(&buffer[..len], timestamp);
+            self.buffer = buffer;
+        }
+    }
+
+    /// Decodes received data, extracting the marked break conditions.
+    fn decode(&mut self, input: &[u8], timestamp: SystemTime) {
+        let mut data = BytesMut::with_capacity(input.len());
+        for &byte in input {
+            match (self.mark_state, byte) {
+                (MarkState::Idle, 0xFF) => self.mark_state = MarkState::Escape,
+                (MarkState::Idle, _) => data.extend_from_slice(&[byte]),
+                (MarkState::Escape, 0x00) => self.mark_state = MarkState::Mark,
+                (MarkState::Escape, 0xFF) => {
+                    self.mark_state = MarkState::Idle;
+                    data.extend_from_slice(&[0xFF]);
+                }
+                (MarkState::Escape, _) => {
+                    self.mark_state = MarkState::Idle;
+                    data.extend_from_slice(&[0xFF, byte]);
+                }
+                (MarkState::Mark, 0x00) => {
+                    self.mark_state = MarkState::Idle;
+                    if !data.is_empty() {
+                        self.push_data(data.split(), timestamp);
+                    }
+                    self.flush_frame();
+                    self.events.push_back(SerialEvent::Break);
+                }
+                // Byte received with a framing or parity error.
+                (MarkState::Mark, _) => {
+                    self.mark_state = MarkState::Idle;
+                    data.extend_from_slice(&[byte]);
+                }
+            }
+        }
+        if !data.is_empty() {
+            self.push_data(data, timestamp);
+        }
+    }
+
+    /// Queues received data, or appends it to the current frame if frames are
+    /// delimited by idle gaps.
+    fn push_data(&mut self, data: BytesMut, timestamp: SystemTime) {
+        if self.settings.frame_gap.is_none() {
+            self.events
+                .push_back(SerialEvent::Data(data.freeze(), timestamp));
+            return;
+        }
+        let frame = self.frame.get_or_insert_with(|| Frame {
+            data: BytesMut::new(),
+            timestamp,
+            last_rx: Instant::now(),
+        });
+        frame.data.extend_from_slice(&data);
+        frame.last_rx = Instant::now();
+    }
+
+    /// Returns the time at which the current frame ends if no more data is
+    /// received, if any.
+    fn frame_deadline(&self) -> Option<Instant> {
+        Some(self.frame.as_ref()?.last_rx + self.settings.frame_gap?)
+    }
+
+    /// Checks whether the current frame has ended.
+    fn frame_ended(&self) -> bool {
+        self.frame_deadline()
+            .is_some_and(|deadline| deadline <= Instant::now())
+    }
+
+    /// Queues the current frame, if any.
+    fn flush_frame(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            self.events
+                .push_back(SerialEvent::Data(frame.data.freeze(), frame.timestamp));
+        }
+    }
+
+    /// Adds or removes the writable interest of the serial port.
+    fn set_writable_interest(&mut self, writable: bool) -> IoResult<()> {
+        if let (Some(port), Some(registry)) = (&mut self.port, &self.registry) {
+            let interest = if writable {
+                Interest::READABLE | Interest::WRITABLE
+            } else {
+                Interest::READABLE
+            };
+            registry.reregister(port, self.token, interest)?;
+        }
+
+        Ok(())
+    }
+
+    /// Resumes the write of the data not yet written.
+    fn resume_write(&mut self) -> IoResult<()> {
+        if self.tx_buf.is_empty() {
+            return Ok(());
+        }
+        let Some(port) = &mut self.port else {
+            return Ok(());
+        };
+        match self.tx_buf.flush(port) {
+            Ok(true) => {
+                self.set_writable_interest(false)?;
+                if std::mem::take(&mut self.drain_pending) {
+                    self.drain_output()?;
+                }
+            }
+            Ok(false) => {}
+            Err(e) => self.disconnect(e),
+        }
+
+        Ok(())
+    }
+
+    /// Waits until the data written to the serial port has been transmitted
+    /// and reports the transmission.
+    ///
+    /// Data is dropped while the serial port is disconnected, so the
+    /// transmission is then reported immediately.
+    fn drain_output(&mut self) -> IoResult<()> {
+        if let Some(port) = &self.port {
+            drain(port)?;
+        }
+        self.report(SerialReport::Drained);
+
+        Ok(())
+    }
+
+    /// Sends a break condition once the pending data has been transmitted.
+    ///
+    /// Break conditions are ignored while the serial port is disconnected.
+    fn send_break(&mut self, duration: Duration) -> IoResult<()> {
+        let Some(port) = &mut self.port else {
+            return Ok(());
+        };
+        drain(port)?;
+        port.set_break()?;
+        thread::sleep(duration);
+        port.clear_break()?;
+
+        Ok(())
+    }
+}
+
+/// Frame being received.
+struct Frame {
+    /// Data received so far.
+    data: BytesMut,
 
-use bytes::{Bytes, BytesMut};
+    /// Receive timestamp of the first data of the frame.
+    timestamp: SystemTime,
 
-use schematic::Config;
+    /// Time at which data was last received.
+    last_rx: Instant,
+}
 
-use mio::{Interest, Registry, Token};
-use mio_serial::{SerialPortBuilderExt, SerialStream};
+/// Transmit pacing at an emulated line rate.
+struct TxPacer {
+    /// Transmission time of a character.
+    char_time: Duration,
 
-#[cfg(feature = "tracing")]
-use tracing::info;
+    /// Maximum number of characters written at once.
+    block_len: usize,
 
-use nexosim::model::{Context, InitializedModel, Model, ProtoModel};
-use nexosim::ports::Output;
+    /// End of the emulated transmission of the data already written.
+    busy_until: Instant,
+}
 
-use nexosim_io_utils::port::{IoPort, IoThread};
+impl TxPacer {
+    /// Creates a transmit pacer for the provided line rate, in bits per
+    /// second.
+    fn new(line_rate: u32) -> Self {
+        Self {
+            char_time: char_time(line_rate),
+            block_len: (u64::from(line_rate) / BITS_PER_CHAR / 1000).max(1) as usize,
+            busy_until: Instant::now(),
+        }
+    }
 
-/// Serial port model instance configuration.
-#[derive(Config, Debug)]
-pub struct SerialPortConfig {
-    /// Baud rate.
-    ///
-    /// Zero value shall be used for software TTY interfaces.
-    #[setting(default = 0)]
-    pub baud_rate: u32,
+    /// Waits until the emulated transmission of a block of the provided length
+    /// is complete.
+    fn wait(&mut self, len: usize) {
+        let now = Instant::now();
+        self.busy_until = self.busy_until.max(now) + self.char_time * len as u32;
+        thread::sleep(self.busy_until - now);
+    }
+}
 
-    /// Serial port path.
-    pub port_path: String,
+/// Half-duplex collision detection.
+struct HalfDuplex {
+    /// Transmission time of a character.
+    char_time: Duration,
 
-    /// Internal buffer size.
-    ///
-    /// Input is read and forwarded to the simulation by blocks up to buffer
-    /// size.
-    #[setting(default = 256)]
-    pub buffer_size: usize,
+    /// Corrupt the data received during a collision.
+    corrupt: bool,
 
-    /// Delay for the first scheduled data forwarding, in milliseconds.
-    ///
-    /// If no value is provided, `period` is used.
-    pub delta: Option<u64>,
+    /// End of the estimated transmission of the data written.
+    busy_until: Instant,
 
-    /// Period at which data from the serial port is forwarded into the
-    /// simulation, in milliseconds.
-    ///
-    /// If no value is provided, periodic activities are not scheduled
-    /// automatically.
-    pub period: Option<u64>,
+    /// Data written last.
+    tx_data: Bytes,
+
+    /// Time at which data was last received.
+    last_rx: Option<Instant>,
 }
 
-struct SerialPortInner {
-    port: SerialStream,
-    buffer: Vec<u8>,
+impl HalfDuplex {
+    /// Returns the collision detection of the configuration, if enabled and if
+    /// the character time can be estimated.
+    fn new(config: &SerialPortConfig) -> Option<Self> {
+        if !config.half_duplex.enabled {
+            return None;
+        }
+        let rate = config.line_rate.unwrap_or(config.baud_rate);
+        (rate != 0).then(|| Self {
+            char_time: char_time(rate),
+            corrupt: config.half_duplex.corrupt,
+            busy_until: Instant::now(),
+            tx_data: Bytes::new(),
+            last_rx: None,
+        })
+    }
+
+    /// Registers a transmission, returning whether it collides with a
+    /// reception.
+    fn transmit(&mut self, data: &Bytes, is_receiving: bool) -> bool {
+        let now = Instant::now();
+        let is_collision = is_receiving
+            || self
+                .last_rx
+                .is_some_and(|last_rx| now < last_rx + self.char_time);
+        self.busy_until = self.busy_until.max(now) + self.char_time * data.len() as u32;
+        self.tx_data = data.clone();
+
+        is_collision
+    }
+
+    /// Registers received data, returning whether it collides with a
+    /// transmission and corrupting it if enabled.
+    fn receive(&mut self, data: &mut [u8]) -> bool {
+        let now = Instant::now();
+        self.last_rx = Some(now);
+        // The reception of the data started one character time per byte
+        // before it was read.
+        let rx_start = now.checked_sub(self.char_time * data.len() as u32);
+        if rx_start.is_some_and(|rx_start| rx_start >= self.busy_until) {
+            return false;
+        }
+        if self.corrupt && !self.tx_data.is_empty() {
+            for (byte, tx_byte) in data.iter_mut().zip(self.tx_data.iter().cycle()) {
+                *byte &= tx_byte;
+            }
+        }
+
+        true
+    }
 }
 
-impl SerialPortInner {
-    fn new(port_path: &str, baud_rate: u32, buffer_size: usize) -> Self {
-        // Until read_buf (RFC 2930) is stabilized we need an initialized
-        // buffer.
-        Self {
-            port: mio_serial::new(port_path, baud_rate)
-                .open_native_async()
-                .unwrap(),
-            buffer: vec![0; buffer_size],
+/// Number of bits per character: start bit, 8 data bits and stop bit.
+const BITS_PER_CHAR: u64 = 10;
+
+/// Returns the transmission time of a character at the provided line rate,
+/// in bits per second.
+fn char_time(line_rate: u32) -> Duration {
+    Duration::from_nanos(BITS_PER_CHAR * 1_000_000_000 / u64::from(line_rate.max(1)))
+}
+
+/// Writes data, paced at the emulated line rate if enabled.
+fn write_paced(
+    port: &mut SerialStream,
+    data: &[u8],
+    pacer: Option<&mut TxPacer>,
+) -> IoResult<usize> {
+    let Some(pacer) = pacer else {
+        return port.write(data);
+    };
+    let mut written = 0;
+    for block in data.chunks(pacer.block_len) {
+        pacer.wait(block.len());
+        let len = port.write(block)?;
+        written += len;
+        if len != block.len() {
+            break;
         }
     }
+
+    Ok(written)
+}
+
+/// Writes data with RS-485 direction control, enabling the driver until the
+/// data has been transmitted.
+fn write_rs485(
+    port: &mut SerialStream,
+    data: &[u8],
+    rs485: Rs485Settings,
+    pacer: Option<&mut TxPacer>,
+) -> IoResult<usize> {
+    port.write_request_to_send(rs485.rts_on_send)?;
+    thread::sleep(rs485.delay_before_send);
+    let result = write_paced(port, data, pacer).and_then(|len| drain(port).map(|_| len));
+    thread::sleep(rs485.delay_after_send);
+    port.write_request_to_send(!rs485.rts_on_send)?;
+
+    result
+}
+
+/// Configures the terminal to mark the received break conditions.
+#[cfg(unix)]
+fn mark_breaks(port: &SerialStream) -> IoResult<()> {
+    let fd = port.as_raw_fd();
+    let mut settings = termios::tcgetattr(fd)?;
+    settings
+        .input_flags
+        .remove(InputFlags::IGNBRK | InputFlags::BRKINT | InputFlags::IGNPAR | InputFlags::ISTRIP);
+    settings.input_flags.insert(InputFlags::PARMRK);
+    termios::tcsetattr(fd, SetArg::TCSANOW, &settings)?;
+
+    Ok(())
 }
 
-impl IoPort<SerialStream, Bytes, Bytes> for SerialPortInner {
-    fn register(&mut self, registry: &Registry) -> Token {
-        registry
-            .register(&mut self.port, Token(0), Interest::READABLE)
-            .unwrap();
-        Token(1)
+impl IoPort<SerialStream, SerialEvent, SerialCommand> for SerialPortInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        self.register_port(registry, Token(0), Token(1))?;
+
+        Ok(Token(2))
     }
 
-    fn read(&mut self, token: Token) -> IoResult<Bytes> {
-        if token == Token(0) {
-            self.port
-                .read(&mut self.buffer)
-                .map(|len| BytesMut::from(&self.buffer[..len]).into())
-        } else {
+    fn read(&mut self, token: Token) -> IoResult<SerialEvent> {
+        if token != Token(0) && token != Token(1) {
             // Unknown event: should never happen.
-            Err(std::io::Error::new(
+            return Err(std::io::Error::new(
                 ErrorKind::InvalidInput,
                 "Unknown event.",
-            ))
+            ));
+        }
+        self.read_event()
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        if token == Token(0) {
+            self.resume_write()?;
+        }
+
+        Ok(())
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        self.frame_deadline()
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        !self.tx_buf.is_empty()
+    }
+
+    fn timeout(&mut self) -> IoResult<SerialEvent> {
+        if self.frame_ended() {
+            self.flush_frame();
+        }
+        self.events
+            .pop_front()
+            .ok_or_else(|| ErrorKind::WouldBlock.into())
+    }
+
+    fn write(&mut self, command: &SerialCommand) -> IoResult<()> {
+        // Commands are delayed until the pending data has been written.
+        if !self.tx_buf.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        let data = match command {
+            SerialCommand::Write(data) => data,
+            SerialCommand::Break(duration) => return self.send_break(*duration),
+            SerialCommand::Drain => return self.drain_output(),
+        };
+        // Data is dropped while the serial port is disconnected.
+        if let (Some(half_duplex), Some(port)) = (&mut self.half_duplex, &self.port) {
+            let is_receiving = port.bytes_to_read().is_ok_and(|len| len > 0);
+            if half_duplex.transmit(data, is_receiving) {
+                self.report(SerialReport::Collision);
+            }
+        }
+        let Some(port) = &mut self.port else {
+            return Ok(());
+        };
+        let result = match (self.settings.rs485, &mut self.pacer) {
+            (Some(rs485), pacer) => write_rs485(port, data, rs485, pacer.as_mut()),
+            (None, Some(pacer)) => write_paced(port, data, Some(pacer)),
+            // The part of the data which cannot be written immediately is
+            // written once the serial port is writable.
+            (None, None) => match self.tx_buf.write(port, data) {
+                Ok(true) => Ok(data.len()),
+                Ok(false) => {
+                    self.drain_pending = self.drain_writes;
+                    return self.set_writable_interest(true);
+                }
+                Err(e) => Err(e),
+            },
+        };
+        let len = match result {
+            Err(e) if !matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted) => {
+                self.disconnect(e);
+                return Ok(());
+            }
+            result => result?,
+        };
+        if len != data.len() {
+            return Err(std::io::Error::new(
+                ErrorKind::Other,
+                format!(
+                    "Not all bytes written: had to write {}, but wrote {}.",
+                    data.len(),
+                    len
+                ),
+            ));
+        }
+        if self.drain_writes {
+            self.drain_output()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Pseudoterminal pair emulating a serial link.
+///
+/// The bench side of the pair is used by a serial port model created with
+/// [`ProtoSerialPort::with_pty`], while the peer side is a pseudoterminal
+/// device which can be opened at [`PtyPair::peer_path`] by an external process
+/// or a test, as a real serial port.
+///
+/// #### Examples
+///
+/// ```
+/// use nexosim_serial_port::PtyPair;
+///
+/// let pty = PtyPair::open().unwrap();
+/// let mut peer = serialport::new(pty.peer_path(), 0).open().unwrap();
+/// # let _ = &mut peer;
+/// ```
+#[cfg(unix)]
+pub struct PtyPair {
+    /// Bench side of the pair.
+    port: SerialStream,
+
+    /// Peer side of the pair.
+    peer: SerialStream,
+
+    /// Path of the peer side device.
+    peer_path: String,
+}
+
+#[cfg(unix)]
+impl PtyPair {
+    /// Creates a new pseudoterminal pair.
+    pub fn open() -> IoResult<Self> {
+        let (port, peer) = SerialStream::pair()?;
+        let peer_path = peer
+            .name()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::NotFound, "Unnamed pseudoterminal."))?;
+
+        Ok(Self {
+            port,
+            peer,
+            peer_path,
+        })
+    }
+
+    /// Returns the path of the peer side device.
+    pub fn peer_path(&self) -> &str {
+        &self.peer_path
+    }
+}
+
+#[cfg(unix)]
+impl fmt::Debug for PtyPair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PtyPair")
+            .field("peer_path", &self.peer_path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Serial port owned by a shared port broker.
+///
+/// Break conditions are not forwarded.
+#[cfg(unix)]
+struct SerialBrokerPort(SerialPortInner);
+
+#[cfg(unix)]
+impl IoPort<SerialStream, Bytes, Bytes> for SerialBrokerPort {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        self.0.register(registry)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<Bytes> {
+        loop {
+            if let SerialEvent::Data(data, _) = self.0.read(token)? {
+                return Ok(data);
+            }
+        }
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        self.0.writable(token)
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        self.0.deadline()
+    }
+
+    fn timeout(&mut self) -> IoResult<Bytes> {
+        loop {
+            if let SerialEvent::Data(data, _) = self.0.timeout()? {
+                return Ok(data);
+            }
         }
     }
 
     fn write(&mut self, data: &Bytes) -> IoResult<()> {
-        self.port.write(data).map(|len| {
-            if len != data.len() {
-                Err(std::io::Error::new(
-                    ErrorKind::Other,
-                    format!(
-                        "Not all bytes written: had to write {}, but wrote {}.",
-                        data.len(),
-                        len
-                    ),
-                ))
+        // The broker does not retry blocked writes, so the data is appended to
+        // the data not yet written.
+        if !self.0.tx_buf.is_empty() {
+            self.0.tx_buf.push(data);
+            return Ok(());
+        }
+        self.0.write(&SerialCommand::Write(data.clone()))
+    }
+}
+
+/// Shared port broker client of the serial port.
+///
+/// Break conditions cannot be sent through the broker, and receive timestamps
+/// are captured when the data is read from the broker.
+#[cfg(unix)]
+struct SerialBrokerClient(BrokerClient<Bytes, SerialBrokerCodec>);
+
+#[cfg(unix)]
+impl IoPort<UnixStream, SerialEvent, SerialCommand> for SerialBrokerClient {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        self.0.register(registry)
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<SerialEvent> {
+        self.0
+            .read(token)
+            .map(|data| SerialEvent::Data(data, SystemTime::now()))
+    }
+
+    fn write(&mut self, command: &SerialCommand) -> IoResult<()> {
+        match command {
+            SerialCommand::Write(data) => self.0.write(data),
+            SerialCommand::Break(_) | SerialCommand::Drain => Ok(()),
+        }
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        self.0.writable(token)
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        self.0.is_write_pending()
+    }
+}
+
+/// Broker codec for raw serial data.
+#[cfg(unix)]
+struct SerialBrokerCodec;
+
+#[cfg(unix)]
+impl BrokerCodec<Bytes> for SerialBrokerCodec {
+    fn encode(&self, item: &Bytes, buf: &mut Vec<u8>) -> IoResult<()> {
+        buf.extend_from_slice(item);
+        Ok(())
+    }
+
+    fn decode(&self, buf: &[u8]) -> IoResult<Bytes> {
+        Ok(Bytes::copy_from_slice(buf))
+    }
+}
+
+/// Creates a shared port broker owning the configured serial port and
+/// listening on the provided socket path.
+///
+/// The `broker_path` configuration is ignored.
+///
+/// An error is returned if the broker cannot be started.
+#[cfg(unix)]
+pub fn shared_port_broker(
+    config: &SerialPortConfig,
+    socket_path: impl AsRef<Path>,
+) -> IoResult<SharedPortBroker> {
+    let settings = PortSettings {
+        port_path: config.port_path.clone(),
+        usb: config.usb.is_enabled().then(|| config.usb.clone()),
+        baud_rate: config.baud_rate,
+        exclusive: config.exclusive,
+        detect_breaks: false,
+        line_rate: config.line_rate,
+        frame_gap: config.frame_gap.map(Duration::from_micros),
+        rs485: config.rs485.direction_control(),
+    };
+    let port = SerialPortInner::new(
+        settings,
+        config.buffer_size,
+        ReconnectSettings::new(config.reconnect_delay, config.reconnect_max_delay),
+    );
+
+    SharedPortBroker::new(socket_path, SerialBrokerPort(port), SerialBrokerCodec)
+}
+
+/// Splitter of the received data into lines.
+struct LineSplitter {
+    /// Line terminator.
+    terminator: u8,
+
+    /// Carriage returns preceding the terminator are stripped.
+    strip_cr: bool,
+
+    /// Maximum line length.
+    max_length: usize,
+
+    /// Incomplete line.
+    line: BytesMut,
+}
+
+impl LineSplitter {
+    /// Creates a line splitter if lines are configured.
+    fn new(config: &SerialPortConfig) -> Option<Self> {
+        config.line_terminator.map(|terminator| Self {
+            terminator,
+            strip_cr: config.strip_cr,
+            max_length: config.max_line_length.max(1),
+            line: BytesMut::new(),
+        })
+    }
+
+    /// Appends received data and returns the completed lines.
+    fn push(&mut self, data: &[u8]) -> Vec<Bytes> {
+        let mut lines = Vec::new();
+        for &byte in data {
+            if byte == self.terminator {
+                let mut line = self.line.split();
+                if self.strip_cr && line.last() == Some(&b'\r') {
+                    line.truncate(line.len() - 1);
+                }
+                lines.push(line.freeze());
             } else {
-                Ok(())
+                self.line.extend_from_slice(&[byte]);
+                if self.line.len() >= self.max_length {
+                    lines.push(self.line.split().freeze());
+                }
             }
-        })?
+        }
+
+        lines
     }
 }
 
@@ -122,30 +1846,126 @@ impl IoPort<SerialStream, Bytes, Bytes> for SerialPortInner {
 ///
 /// This model:
 /// * listens to the configured serial port and forwards its data to the model
-///   output,
-/// * forwards data from the model input to the serial port.
+///   outputs, with and without receive timestamp, and, if configured, its
+///   complete lines to the line output, either when it is processed or at the
+///   simulation time matching its arrival,
+/// * forwards data from the model input to the serial port,
+/// * sends break conditions and reports the received ones,
+/// * reports the transmission of the sent data, on request or after each
+///   write, and the collisions in half-duplex mode,
+/// * reports the disconnections and reconnections of the serial port,
+/// * reports the errors and the exit of its I/O thread,
+/// * reports the changes of its link status,
+/// * reports its traffic metrics, periodically or on request.
 pub struct SerialPort {
     /// Data from serial port -- output port.
     pub bytes_out: Output<Bytes>,
 
+    /// Data from serial port, with its wall-clock receive timestamp -- output
+    /// port.
+    pub timestamped_bytes_out: Output<(SystemTime, Bytes)>,
+
+    /// Complete lines from serial port, without terminator -- output port.
+    pub lines_out: Output<Bytes>,
+
+    /// Break condition received on the serial port -- output port.
+    pub break_out: Output<()>,
+
+    /// Serial port connection status -- output port.
+    pub status_out: Output<SerialPortStatus>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Link status change -- output port.
+    pub link_status_out: Output<LinkStatus>,
+
+    /// Traffic metrics -- output port.
+    pub metrics_out: Output<PortMetrics>,
+
+    /// Data previously sent to the serial port has been transmitted -- output
+    /// port.
+    pub drained_out: Output<()>,
+
+    /// Collision between a transmission and a reception in half-duplex mode
+    /// -- output port.
+    pub collision_out: Output<()>,
+
     /// Model instance configuration.
     config: SerialPortConfig,
 
     /// I/O thread.
-    io_thread: IoThread<Bytes, Bytes>,
+    io_thread: IoThread<SerialEvent, SerialCommand>,
+
+    /// Link status.
+    link: LinkMonitor,
+
+    /// Traffic metrics.
+    metrics: PortMetrics,
+
+    /// Line splitter, if lines are configured.
+    lines: Option<LineSplitter>,
+
+    /// Time alignment of the received data, if enabled.
+    alignment: Option<TimeAlignment>,
+
+    /// Tracing span of the model instance.
+    #[cfg(feature = "tracing")]
+    span: Span,
+
+    /// Transmission reports receiver, unless the serial port is accessed
+    /// through a shared port broker.
+    reports: Option<Receiver<SerialReport>>,
 }
 
 impl SerialPort {
     /// Creates a new serial port model.
     fn new(
-        bytes_out: Output<Bytes>,
-        config: SerialPortConfig,
-        io_thread: IoThread<Bytes, Bytes>,
+        proto: ProtoSerialPort,
+        io_thread: IoThread<SerialEvent, SerialCommand>,
+        reports: Option<Receiver<SerialReport>>,
     ) -> Self {
+        let ProtoSerialPort {
+            bytes_out,
+            timestamped_bytes_out,
+            lines_out,
+            break_out,
+            status_out,
+            stalled_out,
+            io_status_out,
+            link_status_out,
+            metrics_out,
+            drained_out,
+            collision_out,
+            config,
+            ..
+        } = proto;
+
         Self {
             bytes_out,
+            timestamped_bytes_out,
+            lines_out,
+            break_out,
+            status_out,
+            stalled_out,
+            io_status_out,
+            link_status_out,
+            metrics_out,
+            drained_out,
+            collision_out,
+            lines: LineSplitter::new(&config),
             config,
             io_thread,
+            link: LinkMonitor::new(),
+            metrics: PortMetrics::default(),
+            alignment: None,
+            #[cfg(feature = "tracing")]
+            span: Span::none(),
+            reports,
         }
     }
 
@@ -153,27 +1973,238 @@ impl SerialPort {
     pub async fn bytes_in(&mut self, data: Bytes) {
         #[cfg(feature = "tracing")]
         info!(
-            "Will send data to the serial port {}: {:X}.",
-            self.config.port_path, data
+            parent: &self.span,
+            direction = "tx",
+            length = data.len(),
+            data = %HexDump::new(&data),
+            "Will send data."
+        );
+        let len = data.len();
+        if self.send_command(SerialCommand::Write(data)) {
+            self.metrics.record_out(len);
+        }
+    }
+
+    /// Sends a break condition with the provided duration to the serial port
+    /// -- input port.
+    ///
+    /// The break condition is sent once the data previously sent to the serial
+    /// port has been transmitted. Break conditions are ignored when the serial
+    /// port is accessed through a shared port broker.
+    pub async fn send_break(&mut self, duration: Duration) {
+        #[cfg(feature = "tracing")]
+        info!(
+            parent: &self.span,
+            direction = "tx",
+            duration = ?duration,
+            "Will send a break condition."
         );
-        self.io_thread.send(data).unwrap();
+        self.send_command(SerialCommand::Break(duration));
+    }
+
+    /// Waits until the data previously sent to the serial port has been
+    /// transmitted and reports it on the drain output -- input port.
+    ///
+    /// The transmission is not reported when the serial port is accessed
+    /// through a shared port broker.
+    pub async fn drain(&mut self) {
+        #[cfg(feature = "tracing")]
+        info!(parent: &self.span, "Will drain the output.");
+        self.send_command(SerialCommand::Drain);
+    }
+
+    /// Sends a command to the I/O thread, returning `false` if it was dropped.
+    ///
+    /// Commands sent after the I/O thread has exited are dropped and counted
+    /// as errors in the metrics.
+    fn send_command(&mut self, command: SerialCommand) -> bool {
+        match self.io_thread.send(command) {
+            Ok(()) => true,
+            Err(_e) => {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    parent: &self.span,
+                    error = %_e,
+                    "Dropping a command after the exit of the I/O thread."
+                );
+                self.metrics.record_error();
+                false
+            }
+        }
     }
 
-    /// Forwards the raw bytes received on the serial port.
-    pub async fn process(&mut self) {
-        while let Ok(data) = self.io_thread.try_recv() {
+    /// Forwards the raw bytes, the break conditions, the connection status
+    /// changes and the transmission reports of the serial port.
+    ///
+    /// If time alignment is enabled, the raw bytes are scheduled for the
+    /// simulation time matching their arrival, unless this time has passed.
+    pub async fn process(&mut self, _: (), cx: &mut Context<Self>) {
+        for event in self.io_thread.try_recv_all() {
+            match event {
+                SerialEvent::Data(data, timestamp) => {
+                    #[cfg(feature = "tracing")]
+                    info!(
+                        parent: &self.span,
+                        direction = "rx",
+                        length = data.len(),
+                        data = %HexDump::new(&data),
+                        "Received data."
+                    );
+                    self.metrics.record_in(data.len());
+                    let deadline = self.alignment.as_ref().and_then(|alignment| {
+                        alignment.deadline_from_system_time(timestamp, cx.time())
+                    });
+                    match deadline {
+                        Some(deadline) => cx
+                            .schedule_event(deadline, Self::forward_data, (timestamp, data))
+                            .unwrap(),
+                        None => self.forward_data((timestamp, data)).await,
+                    }
+                }
+                SerialEvent::Break => {
+                    #[cfg(feature = "tracing")]
+                    info!(
+                        parent: &self.span,
+                        direction = "rx",
+                        "Received a break condition."
+                    );
+                    self.break_out.send(()).await;
+                }
+                SerialEvent::Status(status) => {
+                    #[cfg(feature = "tracing")]
+                    match &status {
+                        SerialPortStatus::Connected => {
+                            info!(parent: &self.span, "Serial port reconnected.")
+                        }
+                        SerialPortStatus::Disconnected(error) => warn!(
+                            parent: &self.span,
+                            error = %error,
+                            "Serial port disconnected."
+                        ),
+                    }
+                    // Incomplete lines are discarded on disconnection.
+                    if let (Some(lines), SerialPortStatus::Disconnected(_)) =
+                        (&mut self.lines, &status)
+                    {
+                        lines.line.clear();
+                    }
+                    if let SerialPortStatus::Disconnected(_) = status {
+                        self.metrics.record_error();
+                    }
+                    let link = match &status {
+                        SerialPortStatus::Connected => LinkStatus::Connected,
+                        SerialPortStatus::Disconnected(_)
+                            if cfg!(unix) && self.config.reconnect_delay.is_some() =>
+                        {
+                            LinkStatus::Reconnecting
+                        }
+                        SerialPortStatus::Disconnected(e) => LinkStatus::disconnected(e.as_str()),
+                    };
+                    self.status_out.send(status).await;
+                    let change = self.link.set_link(link);
+                    self.report_link(change).await;
+                }
+            }
+        }
+        while let Some(Ok(report)) = self.reports.as_ref().map(Receiver::try_recv) {
+            match report {
+                SerialReport::Drained => {
+                    #[cfg(feature = "tracing")]
+                    info!(parent: &self.span, "Output drained.");
+                    self.drained_out.send(()).await;
+                }
+                SerialReport::Collision => {
+                    #[cfg(feature = "tracing")]
+                    warn!(parent: &self.span, "Collision.");
+                    self.metrics.record_error();
+                    self.collision_out.send(()).await;
+                }
+            }
+        }
+        while let Ok(status) = self.io_thread.try_recv_status() {
             #[cfg(feature = "tracing")]
-            info!(
-                "Received data on the serial port {}: {:X}.",
-                self.config.port_path, data
+            warn!(
+                parent: &self.span,
+                status = %status,
+                "I/O thread error or exit."
             );
-            self.bytes_out.send(data).await;
+            if matches!(
+                status,
+                IoThreadStatus::ReadError(..) | IoThreadStatus::WriteError(..)
+            ) {
+                self.metrics.record_error();
+            }
+            let change = self.link.set_io_status(&status);
+            self.io_status_out.send(status).await;
+            self.report_link(change).await;
+        }
+        self.check_watchdog().await;
+    }
+
+    /// Reports the traffic metrics of the port -- input port.
+    pub async fn report_metrics(&mut self) {
+        self.metrics.set_queue_depths(&self.io_thread.stats());
+        self.metrics_out.send(self.metrics).await;
+    }
+
+    /// Forwards received data and its complete lines.
+    async fn forward_data(&mut self, (timestamp, data): (SystemTime, Bytes)) {
+        let lines = match &mut self.lines {
+            Some(lines) => lines.push(&data),
+            None => Vec::new(),
+        };
+        self.bytes_out.send(data.clone()).await;
+        self.timestamped_bytes_out.send((timestamp, data)).await;
+        for line in lines {
+            self.lines_out.send(line).await;
+        }
+    }
+
+    /// Reports a stalled I/O thread once, until it recovers.
+    async fn check_watchdog(&mut self) {
+        let Some(timeout) = self.config.watchdog_timeout else {
+            return;
+        };
+        match self.io_thread.check_stall(*timeout) {
+            Some(StallChange::Stalled(age)) => {
+                #[cfg(feature = "tracing")]
+                warn!(parent: &self.span, age = ?age, "I/O thread stalled.");
+                self.stalled_out.send(age).await;
+                let change = self.link.set_stalled(true);
+                self.report_link(change).await;
+            }
+            Some(StallChange::Recovered) => {
+                let change = self.link.set_stalled(false);
+                self.report_link(change).await;
+            }
+            None => {}
+        }
+    }
+
+    /// Reports a link status change, if any.
+    async fn report_link(&mut self, change: Option<LinkStatus>) {
+        if let Some(status) = change {
+            #[cfg(feature = "tracing")]
+            info!(parent: &self.span, status = %status, "Link status changed.");
+            self.link_status_out.send(status).await;
         }
     }
 }
 
 impl Model for SerialPort {
-    async fn init(self, context: &mut Context<Self>) -> InitializedModel<Self> {
+    async fn init(mut self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        #[cfg(feature = "tracing")]
+        {
+            self.span = info_span!(
+                "serial_port",
+                model = context.name(),
+                port = %self.config.port_path,
+            );
+        }
+        self.alignment = self
+            .config
+            .time_alignment
+            .alignment(context.time(), self.config.period);
         if let Some(period) = self.config.period {
             let delta = match self.config.delta {
                 Some(delta) => delta,
@@ -181,13 +2212,27 @@ impl Model for SerialPort {
             };
             context
                 .schedule_periodic_event(
-                    Duration::from_millis(delta),
-                    Duration::from_millis(period),
+                    Duration::from(delta),
+                    Duration::from(period),
                     Self::process,
                     (),
                 )
                 .unwrap();
         }
+        if let Some(period) = self.config.metrics_period {
+            let delta = match self.config.delta {
+                Some(delta) => delta,
+                None => period,
+            };
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::report_metrics,
+                    (),
+                )
+                .unwrap();
+        }
 
         self.into()
     }
@@ -204,8 +2249,46 @@ pub struct ProtoSerialPort {
     /// Data from serial port -- output port.
     pub bytes_out: Output<Bytes>,
 
+    /// Data from serial port, with its wall-clock receive timestamp -- output
+    /// port.
+    pub timestamped_bytes_out: Output<(SystemTime, Bytes)>,
+
+    /// Complete lines from serial port, without terminator -- output port.
+    pub lines_out: Output<Bytes>,
+
+    /// Break condition received on the serial port -- output port.
+    pub break_out: Output<()>,
+
+    /// Serial port connection status -- output port.
+    pub status_out: Output<SerialPortStatus>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// I/O thread error, end of file or exit -- output port.
+    pub io_status_out: Output<IoThreadStatus>,
+
+    /// Link status change -- output port.
+    pub link_status_out: Output<LinkStatus>,
+
+    /// Traffic metrics -- output port.
+    pub metrics_out: Output<PortMetrics>,
+
+    /// Data previously sent to the serial port has been transmitted -- output
+    /// port.
+    pub drained_out: Output<()>,
+
+    /// Collision between a transmission and a reception in half-duplex mode
+    /// -- output port.
+    pub collision_out: Output<()>,
+
     /// Serial port model instance config.
     config: SerialPortConfig,
+
+    /// Pseudoterminal pair used instead of the configured serial port.
+    #[cfg(unix)]
+    pty: Option<PtyPair>,
 }
 
 impl ProtoSerialPort {
@@ -214,24 +2297,113 @@ impl ProtoSerialPort {
         Self {
             config,
             bytes_out: Output::new(),
+            timestamped_bytes_out: Output::new(),
+            lines_out: Output::new(),
+            break_out: Output::new(),
+            status_out: Output::new(),
+            stalled_out: Output::new(),
+            io_status_out: Output::new(),
+            link_status_out: Output::new(),
+            metrics_out: Output::new(),
+            drained_out: Output::new(),
+            collision_out: Output::new(),
+            #[cfg(unix)]
+            pty: None,
         }
     }
+
+    /// Uses the bench side of a pseudoterminal pair instead of the configured
+    /// serial port.
+    ///
+    /// The `port_path`, `baud_rate`, `detect_breaks`, reconnection and
+    /// `broker_path` configurations are then ignored, while `line_rate` can be
+    /// used to emulate the transmission time of a real link.
+    #[cfg(unix)]
+    pub fn with_pty(mut self, pty: PtyPair) -> Self {
+        self.pty = Some(pty);
+        self
+    }
 }
 
 impl ProtoModel for ProtoSerialPort {
     type Model = SerialPort;
 
-    fn build(self, _: &mut nexosim::model::BuildContext<Self>) -> Self::Model {
-        let port = SerialPortInner::new(
-            &self.config.port_path,
-            self.config.baud_rate,
-            self.config.buffer_size,
-        );
+    fn build(mut self, _: &mut nexosim::model::BuildContext<Self>) -> Self::Model {
+        let options = IoThreadOptions {
+            heartbeat_period: heartbeat_period(self.config.watchdog_timeout),
+            shutdown_timeout: self.config.shutdown_timeout.map(Duration::from),
+        };
+
+        #[cfg(unix)]
+        let pty = self.pty.take();
+        #[cfg(not(unix))]
+        let pty: Option<()> = None;
+
+        let (io_thread, reports) =
+            match (&self.config.broker_path, &self.config.rfc2217.address, pty) {
+                #[cfg(unix)]
+                (_, _, Some(pty)) => {
+                    let mut port = SerialPortInner::from_pty(pty, &self.config);
+                    let reports = port.reports(&self.config);
+                    (IoThread::try_with_options(port, options), Some(reports))
+                }
+                #[cfg(unix)]
+                (Some(broker_path), _, _) => {
+                    let client =
+                        BrokerClient::connect(broker_path, SerialBrokerCodec, &[]).unwrap();
+                    (
+                        IoThread::try_with_options(SerialBrokerClient(client), options),
+                        None,
+                    )
+                }
+                (_, Some(address), _) => {
+                    let port = Rfc2217Port::connect(address, &self.config).unwrap();
+                    (IoThread::try_with_options(port, options), None)
+                }
+                _ => {
+                    let settings = PortSettings {
+                        port_path: self.config.port_path.clone(),
+                        usb: self
+                            .config
+                            .usb
+                            .is_enabled()
+                            .then(|| self.config.usb.clone()),
+                        baud_rate: self.config.baud_rate,
+                        exclusive: self.config.exclusive,
+                        detect_breaks: self.config.detect_breaks,
+                        line_rate: self.config.line_rate,
+                        frame_gap: self.config.frame_gap.map(Duration::from_micros),
+                        rs485: self.config.rs485.direction_control(),
+                    };
+                    let mut port = SerialPortInner::new(
+                        settings,
+                        self.config.buffer_size,
+                        ReconnectSettings::new(
+                            self.config.reconnect_delay,
+                            self.config.reconnect_max_delay,
+                        ),
+                    );
+                    let reports = port.reports(&self.config);
+                    (IoThread::try_with_options(port, options), Some(reports))
+                }
+            };
+
+        let io_thread = io_thread.unwrap_or_else(|e| {
+            panic!(
+                "Failed to start the I/O thread of the serial port {}: {e}.",
+                self.config.port_path
+            )
+        });
 
-        Self::Model::new(self.bytes_out, self.config, IoThread::new(port))
+        Self::Model::new(self, io_thread, reports)
     }
 }
 
+/// Returns the I/O thread heartbeat period suitable for the watchdog timeout.
+fn heartbeat_period(watchdog_timeout: Option<ConfigDuration>) -> Option<Duration> {
+    watchdog_timeout.map(|timeout| *timeout / 2)
+}
+
 impl fmt::Debug for ProtoSerialPort {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("ProtoSerialPort").finish_non_exhaustive()
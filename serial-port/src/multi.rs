@@ -0,0 +1,586 @@
+//! Multi-port serial model.
+//!
+//! This module contains a model handling several serial ports with a single
+//! I/O thread. The serial ports are opened from one configuration and are
+//! identified by their index in the configured port list; data received and
+//! sent by the model is tagged with this index.
+//!
+//! Serial ports handled by this model cannot be accessed through a shared port
+//! broker.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_serial_port::multi::MultiSerialPortConfig;
+//! use schematic::{ConfigLoader, Format};
+//!
+//! let config = ConfigLoader::<MultiSerialPortConfig>::new()
+//!     .code(
+//!         r#"
+//! period = 10
+//!
+//! [[ports]]
+//! portPath = "/dev/ttyUSB0"
+//! baudRate = 115200
+//!
+//! [[ports]]
+//! portPath = "/dev/ttyUSB1"
+//! baudRate = 9600
+//! detectBreaks = true
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.ports.len(), 2);
+//! ```
+use std::fmt;
+use std::io::{ErrorKind, Result as IoResult};
+use std::time::{Duration, Instant, SystemTime};
+
+use bytes::Bytes;
+
+use schematic::Config;
+
+use mio::{Registry, Token};
+use mio_serial::SerialStream;
+
+#[cfg(feature = "tracing")]
+use tracing::{Span, info, info_span, warn};
+
+use nexosim::model::{BuildContext, Context, InitializedModel, Model, ProtoModel};
+use nexosim::ports::Output;
+
+use nexosim_io_utils::duration::ConfigDuration;
+#[cfg(feature = "tracing")]
+use nexosim_io_utils::hexdump::HexDump;
+use nexosim_io_utils::port::{IoPort, IoThread, IoThreadOptions, StallChange};
+
+use crate::usb::{PartialUsbPortConfig, UsbPortConfig};
+use crate::{
+    PartialRs485Config, PortSettings, ReconnectSettings, Rs485Config, SerialCommand, SerialEvent,
+    SerialPortInner, SerialPortStatus, heartbeat_period,
+};
+
+/// Multi-port serial model instance configuration.
+#[derive(Config, Debug)]
+pub struct MultiSerialPortConfig {
+    /// Serial ports, identified by their index.
+    #[setting(nested)]
+    pub ports: Vec<SerialPortSettings>,
+
+    /// Internal buffer size of each serial port.
+    ///
+    /// Input is read and forwarded to the simulation by blocks up to buffer
+    /// size.
+    #[setting(default = 256)]
+    pub buffer_size: usize,
+
+    /// Delay for the first scheduled data forwarding.
+    ///
+    /// If no value is provided, `period` is used.
+    pub delta: Option<ConfigDuration>,
+
+    /// Period at which data from the serial ports is forwarded into the
+    /// simulation.
+    ///
+    /// If no value is provided, periodic activities are not scheduled
+    /// automatically.
+    pub period: Option<ConfigDuration>,
+
+    /// Time without I/O thread heartbeat after which the I/O thread is
+    /// reported as stalled.
+    ///
+    /// The watchdog is checked each time data is forwarded into the
+    /// simulation. If no value is provided, the watchdog is disabled.
+    pub watchdog_timeout: Option<ConfigDuration>,
+
+    /// Maximum time spent writing the pending data to the serial ports when
+    /// the model is dropped.
+    ///
+    /// If no value is provided, the data not yet written is discarded.
+    pub shutdown_timeout: Option<ConfigDuration>,
+
+    /// Initial delay before reopening a disconnected serial port.
+    ///
+    /// The delay doubles after each failed attempt, up to
+    /// `reconnect_max_delay`. Serial ports are only reopened on Unix
+    /// platforms. If no value is provided, a disconnected serial port is not
+    /// reopened.
+    pub reconnect_delay: Option<ConfigDuration>,
+
+    /// Maximum delay between the attempts to reopen a disconnected serial
+    /// port.
+    #[setting(default = ConfigDuration::from_millis(10000))]
+    pub reconnect_max_delay: ConfigDuration,
+}
+
+/// Settings of a serial port.
+#[derive(Config, Debug)]
+pub struct SerialPortSettings {
+    /// Serial port path, e.g. `/dev/ttyUSB0` on Unix platforms or `COM3` on
+    /// Windows.
+    pub port_path: String,
+
+    /// USB serial port selection.
+    ///
+    /// If any field is set, the USB serial port matching the selection is
+    /// opened and `port_path` is ignored.
+    #[setting(nested)]
+    pub usb: UsbPortConfig,
+
+    /// Baud rate.
+    ///
+    /// Zero value shall be used for software TTY interfaces.
+    #[setting(default = 0)]
+    pub baud_rate: u32,
+
+    /// Open the serial port with exclusive access.
+    ///
+    /// An exclusive lock is taken on the device, so that opening a serial port
+    /// already opened by another process fails immediately. Serial ports are
+    /// always opened with exclusive access on Windows.
+    #[setting(default = true)]
+    pub exclusive: bool,
+
+    /// Emulated line rate, in bits per second.
+    ///
+    /// If a value is provided, outgoing data is paced in real time as if it
+    /// was transmitted at this line rate with 10 bits per character.
+    pub line_rate: Option<u32>,
+
+    /// Minimum idle time delimiting the received frames, in microseconds.
+    ///
+    /// If a value is provided, received data is coalesced and forwarded as a
+    /// single chunk once no data has been received for this time. The I/O
+    /// thread waits for the end of each frame, so data sent to any serial port
+    /// during the reception of a frame is delayed.
+    pub frame_gap: Option<u64>,
+
+    /// Report received break conditions.
+    ///
+    /// Break conditions are only detected on Unix platforms.
+    #[setting(default = false)]
+    pub detect_breaks: bool,
+
+    /// RS-485 direction control.
+    #[setting(nested)]
+    pub rs485: Rs485Config,
+}
+
+/// Data tagged with the index of its serial port.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SerialData {
+    /// Index of the serial port in the configured port list.
+    pub port: usize,
+
+    /// Raw bytes.
+    pub bytes: Bytes,
+}
+
+impl SerialData {
+    /// Creates new tagged data.
+    pub fn new(port: usize, bytes: Bytes) -> Self {
+        Self { port, bytes }
+    }
+}
+
+/// Serial ports handled by the I/O thread.
+struct MultiSerialPortInner {
+    ports: Vec<SerialPortInner>,
+}
+
+impl MultiSerialPortInner {
+    fn new(config: &MultiSerialPortConfig) -> Self {
+        let reconnect = ReconnectSettings::new(config.reconnect_delay, config.reconnect_max_delay);
+
+        Self {
+            ports: config
+                .ports
+                .iter()
+                .map(|port| {
+                    let settings = PortSettings {
+                        port_path: port.port_path.clone(),
+                        usb: port.usb.is_enabled().then(|| port.usb.clone()),
+                        baud_rate: port.baud_rate,
+                        exclusive: port.exclusive,
+                        detect_breaks: port.detect_breaks,
+                        line_rate: port.line_rate,
+                        frame_gap: port.frame_gap.map(Duration::from_micros),
+                        rs485: port.rs485.direction_control(),
+                    };
+                    SerialPortInner::new(settings, config.buffer_size, reconnect)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl IoPort<SerialStream, (usize, SerialEvent), (usize, SerialCommand)> for MultiSerialPortInner {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        // Serial ports are followed by their reconnection notification
+        // tokens.
+        let count = self.ports.len();
+        for (index, port) in self.ports.iter_mut().enumerate() {
+            port.register_port(registry, Token(index), Token(count + index))?;
+        }
+
+        Ok(Token(2 * count))
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<(usize, SerialEvent)> {
+        let count = self.ports.len();
+        if token.0 >= 2 * count {
+            // Unknown event: should never happen.
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "Unknown event.",
+            ));
+        }
+        let index = token.0 % count;
+
+        self.ports[index].read_event().map(|event| (index, event))
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        match self.ports.get_mut(token.0) {
+            Some(port) => port.resume_write(),
+            None => Ok(()),
+        }
+    }
+
+    fn deadline(&mut self) -> Option<Instant> {
+        self.ports
+            .iter_mut()
+            .filter_map(|port| port.deadline())
+            .min()
+    }
+
+    fn timeout(&mut self) -> IoResult<(usize, SerialEvent)> {
+        for (index, port) in self.ports.iter_mut().enumerate() {
+            match port.timeout() {
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
+                result => return result.map(|event| (index, event)),
+            }
+        }
+
+        Err(ErrorKind::WouldBlock.into())
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        self.ports.iter_mut().any(|port| port.is_write_pending())
+    }
+
+    fn write(&mut self, (index, command): &(usize, SerialCommand)) -> IoResult<()> {
+        match self.ports.get_mut(*index) {
+            Some(port) => port.write(command),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Multi-port serial model.
+///
+/// This model:
+/// * listens to the configured serial ports and forwards their data to the
+///   model outputs, tagged with the serial port index and optionally with the
+///   receive timestamp,
+/// * forwards tagged data from the model input to the matching serial port,
+/// * sends break conditions and reports the received ones,
+/// * reports the disconnections and reconnections of the serial ports.
+pub struct MultiSerialPort {
+    /// Data from the serial ports -- output port.
+    pub data_out: Output<SerialData>,
+
+    /// Data from the serial ports, with its wall-clock receive timestamp --
+    /// output port.
+    pub timestamped_data_out: Output<(SystemTime, SerialData)>,
+
+    /// Index of the serial port on which a break condition was received --
+    /// output port.
+    pub break_out: Output<usize>,
+
+    /// Connection status of a serial port, with its index -- output port.
+    pub status_out: Output<(usize, SerialPortStatus)>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// Model instance configuration.
+    config: MultiSerialPortConfig,
+
+    /// I/O thread.
+    io_thread: IoThread<(usize, SerialEvent), (usize, SerialCommand)>,
+
+    /// Tracing span of the model instance.
+    #[cfg(feature = "tracing")]
+    span: Span,
+}
+
+impl MultiSerialPort {
+    /// Creates a new multi-port serial model.
+    fn new(
+        data_out: Output<SerialData>,
+        timestamped_data_out: Output<(SystemTime, SerialData)>,
+        break_out: Output<usize>,
+        status_out: Output<(usize, SerialPortStatus)>,
+        stalled_out: Output<Duration>,
+        config: MultiSerialPortConfig,
+        io_thread: IoThread<(usize, SerialEvent), (usize, SerialCommand)>,
+    ) -> Self {
+        Self {
+            data_out,
+            timestamped_data_out,
+            break_out,
+            status_out,
+            stalled_out,
+            config,
+            io_thread,
+            #[cfg(feature = "tracing")]
+            span: Span::none(),
+        }
+    }
+
+    /// Sends raw bytes to the tagged serial port -- input port.
+    ///
+    /// Data tagged with an unknown serial port index is dropped.
+    pub async fn data_in(&mut self, data: SerialData) {
+        let Some(_port) = self.config.ports.get(data.port) else {
+            #[cfg(feature = "tracing")]
+            warn!(
+                parent: &self.span,
+                port = data.port,
+                direction = "tx",
+                "Dropping data to an unknown serial port."
+            );
+            return;
+        };
+        #[cfg(feature = "tracing")]
+        info!(
+            parent: &self.span,
+            port = %_port.port_path,
+            direction = "tx",
+            length = data.bytes.len(),
+            data = %HexDump::new(&data.bytes),
+            "Will send data."
+        );
+        self.send_command(data.port, SerialCommand::Write(data.bytes));
+    }
+
+    /// Sends a break condition with the provided duration to the serial port
+    /// with the provided index -- input port.
+    ///
+    /// The break condition is sent once the data previously sent to the serial
+    /// port has been transmitted.
+    pub async fn send_break(&mut self, (port, duration): (usize, Duration)) {
+        let Some(_settings) = self.config.ports.get(port) else {
+            #[cfg(feature = "tracing")]
+            warn!(
+                parent: &self.span,
+                port,
+                direction = "tx",
+                "Dropping a break condition to an unknown serial port."
+            );
+            return;
+        };
+        #[cfg(feature = "tracing")]
+        info!(
+            parent: &self.span,
+            port = %_settings.port_path,
+            direction = "tx",
+            duration = ?duration,
+            "Will send a break condition."
+        );
+        self.send_command(port, SerialCommand::Break(duration));
+    }
+
+    /// Sends a command for the serial port with the provided index to the I/O
+    /// thread.
+    ///
+    /// Commands sent after the I/O thread has exited are dropped.
+    fn send_command(&mut self, port: usize, command: SerialCommand) {
+        if let Err(_e) = self.io_thread.send((port, command)) {
+            #[cfg(feature = "tracing")]
+            warn!(
+                parent: &self.span,
+                port = %self.config.ports[port].port_path,
+                error = %_e,
+                "Dropping a command after the exit of the I/O thread."
+            );
+        }
+    }
+
+    /// Forwards the raw bytes, the break conditions and the connection status
+    /// changes of the serial ports.
+    pub async fn process(&mut self) {
+        for (port, event) in self.io_thread.try_recv_all() {
+            match event {
+                SerialEvent::Data(bytes, timestamp) => {
+                    #[cfg(feature = "tracing")]
+                    info!(
+                        parent: &self.span,
+                        port = %self.config.ports[port].port_path,
+                        direction = "rx",
+                        length = bytes.len(),
+                        data = %HexDump::new(&bytes),
+                        "Received data."
+                    );
+                    let data = SerialData::new(port, bytes);
+                    self.data_out.send(data.clone()).await;
+                    self.timestamped_data_out.send((timestamp, data)).await;
+                }
+                SerialEvent::Break => {
+                    #[cfg(feature = "tracing")]
+                    info!(
+                        parent: &self.span,
+                        port = %self.config.ports[port].port_path,
+                        direction = "rx",
+                        "Received a break condition."
+                    );
+                    self.break_out.send(port).await;
+                }
+                SerialEvent::Status(status) => {
+                    #[cfg(feature = "tracing")]
+                    match &status {
+                        SerialPortStatus::Connected => info!(
+                            parent: &self.span,
+                            port = %self.config.ports[port].port_path,
+                            "Serial port reconnected."
+                        ),
+                        SerialPortStatus::Disconnected(error) => warn!(
+                            parent: &self.span,
+                            port = %self.config.ports[port].port_path,
+                            error = %error,
+                            "Serial port disconnected."
+                        ),
+                    }
+                    self.status_out.send((port, status)).await;
+                }
+            }
+        }
+        self.check_watchdog().await;
+    }
+
+    /// Reports a stalled I/O thread once, until it recovers.
+    async fn check_watchdog(&mut self) {
+        let Some(timeout) = self.config.watchdog_timeout else {
+            return;
+        };
+        if let Some(StallChange::Stalled(age)) = self.io_thread.check_stall(*timeout) {
+            #[cfg(feature = "tracing")]
+            warn!(parent: &self.span, age = ?age, "I/O thread stalled.");
+            self.stalled_out.send(age).await;
+        }
+    }
+}
+
+impl Model for MultiSerialPort {
+    #[cfg_attr(not(feature = "tracing"), allow(unused_mut))]
+    async fn init(mut self, context: &mut Context<Self>) -> InitializedModel<Self> {
+        #[cfg(feature = "tracing")]
+        {
+            self.span = info_span!(
+                "multi_serial_port",
+                model = context.name(),
+                ports = self.config.ports.len(),
+            );
+        }
+        if let Some(period) = self.config.period {
+            let delta = self.config.delta.unwrap_or(period);
+            context
+                .schedule_periodic_event(
+                    Duration::from(delta),
+                    Duration::from(period),
+                    Self::process,
+                    (),
+                )
+                .unwrap();
+        }
+
+        self.into()
+    }
+}
+
+impl fmt::Debug for MultiSerialPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MultiSerialPort")
+            .field("ports", &self.config.ports.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Multi-port serial model prototype.
+pub struct ProtoMultiSerialPort {
+    /// Data from the serial ports -- output port.
+    pub data_out: Output<SerialData>,
+
+    /// Data from the serial ports, with its wall-clock receive timestamp --
+    /// output port.
+    pub timestamped_data_out: Output<(SystemTime, SerialData)>,
+
+    /// Index of the serial port on which a break condition was received --
+    /// output port.
+    pub break_out: Output<usize>,
+
+    /// Connection status of a serial port, with its index -- output port.
+    pub status_out: Output<(usize, SerialPortStatus)>,
+
+    /// I/O thread stall, with the time elapsed since its last heartbeat --
+    /// output port.
+    pub stalled_out: Output<Duration>,
+
+    /// Model instance configuration.
+    config: MultiSerialPortConfig,
+}
+
+impl ProtoMultiSerialPort {
+    /// Creates a new multi-port serial model prototype.
+    pub fn new(config: MultiSerialPortConfig) -> Self {
+        Self {
+            data_out: Output::new(),
+            timestamped_data_out: Output::new(),
+            break_out: Output::new(),
+            status_out: Output::new(),
+            stalled_out: Output::new(),
+            config,
+        }
+    }
+}
+
+impl ProtoModel for ProtoMultiSerialPort {
+    type Model = MultiSerialPort;
+
+    fn build(self, _: &mut BuildContext<Self>) -> Self::Model {
+        let options = IoThreadOptions {
+            heartbeat_period: heartbeat_period(self.config.watchdog_timeout),
+            shutdown_timeout: self.config.shutdown_timeout.map(Duration::from),
+        };
+        let io_thread =
+            IoThread::try_with_options(MultiSerialPortInner::new(&self.config), options)
+                .unwrap_or_else(|e| {
+                    panic!("Failed to start the I/O thread of the serial ports: {e}.")
+                });
+
+        Self::Model::new(
+            self.data_out,
+            self.timestamped_data_out,
+            self.break_out,
+            self.status_out,
+            self.stalled_out,
+            self.config,
+            io_thread,
+        )
+    }
+}
+
+impl fmt::Debug for ProtoMultiSerialPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProtoMultiSerialPort")
+            .field("ports", &self.config.ports.len())
+            .finish_non_exhaustive()
+    }
+}
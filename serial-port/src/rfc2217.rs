@@ -0,0 +1,479 @@
+//! RFC 2217 serial server backend.
+//!
+//! This module contains the configuration of the network backend of the serial
+//! port model, which accesses the serial port of a remote
+//! [RFC 2217](https://www.rfc-editor.org/rfc/rfc2217) server, such as a
+//! terminal server or `ser2net`, over a Telnet connection.
+//!
+//! The backend is selected by setting the `rfc2217.address` configuration of
+//! the serial port model to the `HOST:PORT` address of the server, in which
+//! case `port_path` is ignored. The baud rate, data bits, parity and stop bits
+//! of the remote serial port are then set by the model when it connects, while
+//! a zero `baud_rate` keeps the baud rate of the server. Break conditions can
+//! be sent and, if `detect_breaks` is set, received break conditions are
+//! reported through the line state notifications of the server.
+//!
+//! Other options of the serial port model, i.e. exclusive access, RS-485
+//! direction control, transmit pacing, frame delimitation, drain reports and
+//! half-duplex collision emulation, are not supported by this backend and are
+//! ignored. If the server closes the
+//! connection, the disconnection is reported and the serial port stays silent.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_serial_port::SerialPortConfig;
+//! use schematic::{ConfigLoader, Format};
+//!
+//! let config = ConfigLoader::<SerialPortConfig>::new()
+//!     .code(
+//!         r#"
+//! baudRate = 19200
+//! rfc2217.address = "10.0.0.2:7001"
+//! rfc2217.parity = "even"
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert_eq!(config.rfc2217.address.as_deref(), Some("10.0.0.2:7001"));
+//! ```
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Read, Result as IoResult, Write};
+use std::net::{TcpStream as StdTcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use bytes::BytesMut;
+use serde::{Deserialize, Serialize};
+
+use schematic::{Config, ConfigEnum};
+
+use mio::net::TcpStream;
+use mio::{Interest, Registry, Token};
+
+use nexosim_io_utils::port::IoPort;
+
+use crate::{SerialCommand, SerialEvent, SerialPortConfig, SerialPortStatus};
+
+/// Timeout of the server connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Telnet "interpret as command" escape.
+const IAC: u8 = 255;
+/// Telnet option negotiation: refuse an option of the sender.
+const DONT: u8 = 254;
+/// Telnet option negotiation: request an option of the sender.
+const DO: u8 = 253;
+/// Telnet option negotiation: refuse an option of the receiver.
+const WONT: u8 = 252;
+/// Telnet option negotiation: offer an option of the receiver.
+const WILL: u8 = 251;
+/// Telnet subnegotiation start.
+const SB: u8 = 250;
+/// Telnet subnegotiation end.
+const SE: u8 = 240;
+
+/// Telnet binary transmission option.
+const OPT_BINARY: u8 = 0;
+/// Telnet suppress go ahead option.
+const OPT_SGA: u8 = 3;
+/// RFC 2217 COM port control option.
+const OPT_COM_PORT: u8 = 44;
+
+/// COM port control command: baud rate.
+const SET_BAUDRATE: u8 = 1;
+/// COM port control command: data bits.
+const SET_DATASIZE: u8 = 2;
+/// COM port control command: parity.
+const SET_PARITY: u8 = 3;
+/// COM port control command: stop bits.
+const SET_STOPSIZE: u8 = 4;
+/// COM port control command: flow control, break and modem lines.
+const SET_CONTROL: u8 = 5;
+/// COM port control notification from the server: line state.
+const NOTIFY_LINESTATE: u8 = 106;
+/// COM port control command: line state notification mask.
+const SET_LINESTATE_MASK: u8 = 10;
+/// COM port control command: modem state notification mask.
+const SET_MODEMSTATE_MASK: u8 = 11;
+
+/// `SET_CONTROL` value: no flow control.
+const CONTROL_NO_FLOW_CONTROL: u8 = 1;
+/// `SET_CONTROL` value: break condition on.
+const CONTROL_BREAK_ON: u8 = 5;
+/// `SET_CONTROL` value: break condition off.
+const CONTROL_BREAK_OFF: u8 = 6;
+
+/// Line state bit of a received break condition.
+const LINESTATE_BREAK: u8 = 0x10;
+
+/// RFC 2217 serial server configuration.
+#[derive(Config, Debug)]
+pub struct Rfc2217Config {
+    /// Address of the RFC 2217 serial server, as `HOST:PORT`.
+    ///
+    /// If a value is provided, the serial port of the server is used instead
+    /// of `port_path`.
+    pub address: Option<String>,
+
+    /// Number of data bits, from 5 to 8.
+    #[setting(default = 8)]
+    pub data_bits: u8,
+
+    /// Parity.
+    pub parity: Parity,
+
+    /// Number of stop bits, 1 or 2.
+    #[setting(default = 1)]
+    pub stop_bits: u8,
+}
+
+impl From<Rfc2217Config> for PartialRfc2217Config {
+    fn from(config: Rfc2217Config) -> Self {
+        Self {
+            address: config.address,
+            data_bits: Some(config.data_bits),
+            parity: Some(config.parity),
+            stop_bits: Some(config.stop_bits),
+        }
+    }
+}
+
+/// Parity of a remote serial port.
+#[derive(ConfigEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Parity {
+    /// No parity bit.
+    #[default]
+    None,
+
+    /// Odd parity.
+    Odd,
+
+    /// Even parity.
+    Even,
+
+    /// Parity bit always set.
+    Mark,
+
+    /// Parity bit always cleared.
+    Space,
+}
+
+impl Parity {
+    /// Returns the RFC 2217 `SET-PARITY` value.
+    fn value(self) -> u8 {
+        match self {
+            Self::None => 1,
+            Self::Odd => 2,
+            Self::Even => 3,
+            Self::Mark => 4,
+            Self::Space => 5,
+        }
+    }
+}
+
+/// Decoding state of the Telnet stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TelnetState {
+    /// Serial data.
+    Data,
+
+    /// Command escape received.
+    Iac,
+
+    /// Option negotiation command received, waiting for the option.
+    Negotiation(u8),
+
+    /// Subnegotiation data.
+    Subnegotiation,
+
+    /// Command escape received in subnegotiation data.
+    SubnegotiationIac,
+}
+
+/// Serial port of an RFC 2217 server.
+pub(crate) struct Rfc2217Port {
+    /// Server connection, unless closed.
+    stream: Option<TcpStream>,
+
+    /// Read buffer.
+    buffer: Vec<u8>,
+
+    /// Decoding state of the Telnet stream.
+    state: TelnetState,
+
+    /// Subnegotiation data being received.
+    subnegotiation: Vec<u8>,
+
+    /// Decoded events not yet read.
+    events: VecDeque<SerialEvent>,
+
+    /// Data not yet written to the server.
+    tx_buf: Vec<u8>,
+
+    /// MIO registry, available once the connection is registered.
+    registry: Option<Registry>,
+}
+
+impl Rfc2217Port {
+    /// Connects to the RFC 2217 server and configures its serial port.
+    pub(crate) fn connect(address: &str, config: &SerialPortConfig) -> IoResult<Self> {
+        let server = address.to_socket_addrs()?.next().ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Cannot resolve address {address}."),
+            )
+        })?;
+        let stream = StdTcpStream::connect_timeout(&server, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+
+        let mut port = Self {
+            stream: Some(TcpStream::from_std(stream)),
+            buffer: vec![0; config.buffer_size],
+            state: TelnetState::Data,
+            subnegotiation: Vec::new(),
+            events: VecDeque::new(),
+            tx_buf: Vec::new(),
+            registry: None,
+        };
+
+        // The requested options are not waited for: the server acknowledges
+        // them and the settings asynchronously.
+        for (command, option) in [
+            (WILL, OPT_BINARY),
+            (DO, OPT_BINARY),
+            (WILL, OPT_SGA),
+            (DO, OPT_SGA),
+            (WILL, OPT_COM_PORT),
+        ] {
+            port.tx_buf.extend_from_slice(&[IAC, command, option]);
+        }
+        if config.baud_rate != 0 {
+            port.push_command(SET_BAUDRATE, &config.baud_rate.to_be_bytes());
+        }
+        port.push_command(SET_DATASIZE, &[config.rfc2217.data_bits]);
+        port.push_command(SET_PARITY, &[config.rfc2217.parity.value()]);
+        port.push_command(SET_STOPSIZE, &[config.rfc2217.stop_bits]);
+        port.push_command(SET_CONTROL, &[CONTROL_NO_FLOW_CONTROL]);
+        let linestate_mask = if config.detect_breaks {
+            LINESTATE_BREAK
+        } else {
+            0
+        };
+        port.push_command(SET_LINESTATE_MASK, &[linestate_mask]);
+        port.push_command(SET_MODEMSTATE_MASK, &[0]);
+
+        Ok(port)
+    }
+
+    /// Queues a COM port control command.
+    fn push_command(&mut self, command: u8, value: &[u8]) {
+        self.tx_buf
+            .extend_from_slice(&[IAC, SB, OPT_COM_PORT, command]);
+        push_escaped(&mut self.tx_buf, value);
+        self.tx_buf.extend_from_slice(&[IAC, SE]);
+    }
+
+    /// Writes as much pending data as possible to the server.
+    fn flush(&mut self) -> IoResult<()> {
+        let Some(stream) = &mut self.stream else {
+            self.tx_buf.clear();
+            return Ok(());
+        };
+        while !self.tx_buf.is_empty() {
+            match stream.write(&self.tx_buf) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(len) => {
+                    self.tx_buf.drain(..len);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the pending data, closing the connection on error.
+    fn try_flush(&mut self) {
+        if let Err(e) = self.flush() {
+            self.disconnect(e);
+        }
+    }
+
+    /// Closes the connection after an error.
+    fn disconnect(&mut self, error: Error) {
+        if let (Some(mut stream), Some(registry)) = (self.stream.take(), &self.registry) {
+            let _ = registry.deregister(&mut stream);
+        }
+        self.tx_buf.clear();
+        self.events
+            .push_back(SerialEvent::Status(SerialPortStatus::Disconnected(
+                error.to_string(),
+            )));
+    }
+
+    /// Decodes the Telnet stream received from the server.
+    fn decode(&mut self, len: usize, timestamp: SystemTime) {
+        let mut data = BytesMut::with_capacity(len);
+        for i in 0..len {
+            let byte = self.buffer[i];
+            self.state = match (self.state, byte) {
+                (TelnetState::Data, IAC) => TelnetState::Iac,
+                (TelnetState::Data, _) => {
+                    data.extend_from_slice(&[byte]);
+                    TelnetState::Data
+                }
+                (TelnetState::Iac, IAC) => {
+                    data.extend_from_slice(&[IAC]);
+                    TelnetState::Data
+                }
+                (TelnetState::Iac, WILL | WONT | DO | DONT) => TelnetState::Negotiation(byte),
+                (TelnetState::Iac, SB) => {
+                    self.subnegotiation.clear();
+                    TelnetState::Subnegotiation
+                }
+                // Other commands are ignored.
+                (TelnetState::Iac, _) => TelnetState::Data,
+                (TelnetState::Negotiation(command), option) => {
+                    self.negotiate(command, option);
+                    TelnetState::Data
+                }
+                (TelnetState::Subnegotiation, IAC) => TelnetState::SubnegotiationIac,
+                (TelnetState::Subnegotiation, _) => {
+                    self.subnegotiation.push(byte);
+                    TelnetState::Subnegotiation
+                }
+                (TelnetState::SubnegotiationIac, SE) => {
+                    if self.is_break_notification() {
+                        if !data.is_empty() {
+                            self.events
+                                .push_back(SerialEvent::Data(data.split().freeze(), timestamp));
+                        }
+                        self.events.push_back(SerialEvent::Break);
+                    }
+                    TelnetState::Data
+                }
+                (TelnetState::SubnegotiationIac, _) => {
+                    self.subnegotiation.push(byte);
+                    TelnetState::Subnegotiation
+                }
+            };
+        }
+        if !data.is_empty() {
+            self.events
+                .push_back(SerialEvent::Data(data.freeze(), timestamp));
+        }
+    }
+
+    /// Answers an option negotiation of the server.
+    ///
+    /// The options requested by the client are accepted without answer, as
+    /// their negotiation is initiated by the client, and other options are
+    /// refused.
+    fn negotiate(&mut self, command: u8, option: u8) {
+        let answer = match (command, option) {
+            (DO, OPT_BINARY | OPT_SGA | OPT_COM_PORT) | (WILL, OPT_BINARY | OPT_SGA) => return,
+            (DO, _) => WONT,
+            (WILL, _) => DONT,
+            _ => return,
+        };
+        self.tx_buf.extend_from_slice(&[IAC, answer, option]);
+    }
+
+    /// Checks whether the last subnegotiation notifies a break condition.
+    fn is_break_notification(&self) -> bool {
+        matches!(
+            self.subnegotiation[..],
+            [OPT_COM_PORT, NOTIFY_LINESTATE, state, ..] if state & LINESTATE_BREAK != 0
+        )
+    }
+
+    /// Sends a break condition with the provided duration.
+    fn send_break(&mut self, duration: Duration) {
+        self.push_command(SET_CONTROL, &[CONTROL_BREAK_ON]);
+        self.try_flush();
+        thread::sleep(duration);
+        self.push_command(SET_CONTROL, &[CONTROL_BREAK_OFF]);
+        self.try_flush();
+    }
+}
+
+impl IoPort<TcpStream, SerialEvent, SerialCommand> for Rfc2217Port {
+    fn register(&mut self, registry: &Registry) -> IoResult<Token> {
+        if let Some(stream) = &mut self.stream {
+            registry.register(stream, Token(0), Interest::READABLE | Interest::WRITABLE)?;
+        }
+        self.registry = registry.try_clone().ok();
+
+        Ok(Token(1))
+    }
+
+    fn read(&mut self, token: Token) -> IoResult<SerialEvent> {
+        if token != Token(0) {
+            // Unknown event: should never happen.
+            return Err(Error::new(ErrorKind::InvalidInput, "Unknown event."));
+        }
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            let Some(stream) = &mut self.stream else {
+                return Err(ErrorKind::WouldBlock.into());
+            };
+            match stream.read(&mut self.buffer) {
+                Ok(0) => self.disconnect(ErrorKind::UnexpectedEof.into()),
+                Ok(len) => {
+                    self.decode(len, SystemTime::now());
+                    // Negotiation answers, if any.
+                    self.try_flush();
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted) => {
+                    return Err(e);
+                }
+                Err(e) => self.disconnect(e),
+            }
+        }
+    }
+
+    fn writable(&mut self, token: Token) -> IoResult<()> {
+        if token == Token(0) {
+            self.try_flush();
+        }
+
+        Ok(())
+    }
+
+    fn is_write_pending(&mut self) -> bool {
+        !self.tx_buf.is_empty()
+    }
+
+    fn write(&mut self, command: &SerialCommand) -> IoResult<()> {
+        match command {
+            SerialCommand::Write(data) => {
+                push_escaped(&mut self.tx_buf, data);
+                self.try_flush();
+            }
+            SerialCommand::Break(duration) => self.send_break(*duration),
+            SerialCommand::Drain => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Appends data to a buffer, escaping the Telnet command bytes.
+fn push_escaped(buf: &mut Vec<u8>, data: &[u8]) {
+    for &byte in data {
+        if byte == IAC {
+            buf.push(IAC);
+        }
+        buf.push(byte);
+    }
+}
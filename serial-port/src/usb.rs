@@ -0,0 +1,155 @@
+//! USB serial port discovery.
+//!
+//! The device path of a USB serial adapter, e.g. `/dev/ttyUSB0`, may change
+//! between boots or when adapters are plugged in a different order. This
+//! module lists the USB serial ports of the system and selects a port by its
+//! USB vendor ID, product ID and serial number.
+//!
+//! Serial port models select a USB serial port instead of the configured path
+//! if any field of their `usb` configuration is set. The port is then looked
+//! up each time it is opened, including on reconnection.
+//!
+//! #### Examples
+//!
+//! ```
+//! use nexosim_serial_port::SerialPortConfig;
+//! use schematic::{ConfigLoader, Format};
+//!
+//! let config = ConfigLoader::<SerialPortConfig>::new()
+//!     .code(
+//!         r#"
+//! baudRate = 115200
+//! usb.vid = 0x0403
+//! usb.pid = 0x6001
+//! usb.serialNumber = "A10K3XYZ"
+//! "#,
+//!         Format::Toml,
+//!     )
+//!     .unwrap()
+//!     .load()
+//!     .unwrap()
+//!     .config;
+//!
+//! assert!(config.usb.is_enabled());
+//! ```
+use std::io::{Error, ErrorKind, Result as IoResult};
+
+use schematic::Config;
+
+use mio_serial::SerialPortType;
+
+/// USB serial port selection configuration.
+///
+/// A port is selected if it matches all provided fields.
+#[derive(Clone, Config, Debug)]
+pub struct UsbPortConfig {
+    /// USB vendor ID.
+    pub vid: Option<u16>,
+
+    /// USB product ID.
+    pub pid: Option<u16>,
+
+    /// USB serial number.
+    pub serial_number: Option<String>,
+}
+
+impl UsbPortConfig {
+    /// Checks whether USB selection is enabled, i.e. whether any field is
+    /// set.
+    pub fn is_enabled(&self) -> bool {
+        self.vid.is_some() || self.pid.is_some() || self.serial_number.is_some()
+    }
+
+    /// Checks whether a USB serial port matches the selection.
+    pub fn matches(&self, port: &UsbSerialPort) -> bool {
+        self.vid.is_none_or(|vid| vid == port.vid)
+            && self.pid.is_none_or(|pid| pid == port.pid)
+            && self
+                .serial_number
+                .as_ref()
+                .is_none_or(|serial_number| port.serial_number.as_ref() == Some(serial_number))
+    }
+
+    /// Returns the device path of the single USB serial port matching the
+    /// selection.
+    ///
+    /// An error is returned if no port or several ports match.
+    pub fn find(&self) -> IoResult<String> {
+        let mut ports = usb_ports()?.into_iter().filter(|port| self.matches(port));
+        let port = ports.next().ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("No USB serial port matches {}.", self.describe()),
+            )
+        })?;
+        if ports.next().is_some() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Several USB serial ports match {}.", self.describe()),
+            ));
+        }
+
+        Ok(port.port_path)
+    }
+
+    /// Describes the selection.
+    fn describe(&self) -> String {
+        let vid = self.vid.map_or("*".to_owned(), |vid| format!("{vid:04x}"));
+        let pid = self.pid.map_or("*".to_owned(), |pid| format!("{pid:04x}"));
+        let serial_number = self.serial_number.as_deref().unwrap_or("*");
+
+        format!("{vid}:{pid} with serial number {serial_number}")
+    }
+}
+
+impl From<UsbPortConfig> for PartialUsbPortConfig {
+    fn from(config: UsbPortConfig) -> Self {
+        Self {
+            vid: config.vid,
+            pid: config.pid,
+            serial_number: config.serial_number,
+        }
+    }
+}
+
+/// USB serial port of the system.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UsbSerialPort {
+    /// Device path.
+    pub port_path: String,
+
+    /// USB vendor ID.
+    pub vid: u16,
+
+    /// USB product ID.
+    pub pid: u16,
+
+    /// USB serial number.
+    pub serial_number: Option<String>,
+
+    /// Manufacturer name.
+    pub manufacturer: Option<String>,
+
+    /// Product name.
+    pub product: Option<String>,
+}
+
+/// Lists the USB serial ports of the system.
+pub fn usb_ports() -> IoResult<Vec<UsbSerialPort>> {
+    let ports = mio_serial::available_ports()?;
+
+    Ok(ports
+        .into_iter()
+        .filter_map(|port| match port.port_type {
+            SerialPortType::UsbPort(info) => Some(UsbSerialPort {
+                port_path: port.port_name,
+                vid: info.vid,
+                pid: info.pid,
+                serial_number: info.serial_number,
+                manufacturer: info.manufacturer,
+                product: info.product,
+            }),
+            _ => None,
+        })
+        .collect())
+}